@@ -1,8 +1,9 @@
 use std::io::prelude::*;
 use std::time::Instant;
-use yuki::math::Matrix4x4;
+use yuki::math::{Bounds3, Matrix4x4, Point3, Ray, Vec3};
 
 const ITERATIONS: usize = 5000000;
+const SLAB_RAY_RES: usize = 256;
 
 fn bench_full(m: &Matrix4x4<f32>) {
     let mut m = m.clone();
@@ -40,6 +41,128 @@ fn bench_mul(m: &Matrix4x4<f32>) {
     );
 }
 
+// Bounds roughly matching the Cornell box's BVH nodes in meters, with the walls, blocks and
+// their halves
+fn cornell_bounds() -> Vec<Bounds3<f32>> {
+    let mut bounds = vec![
+        // Whole scene
+        Bounds3::new(Point3::new(0.0, 0.0, -0.56), Point3::new(0.555, 0.564, 0.0)),
+        // Floor, ceiling and back wall
+        Bounds3::new(Point3::new(0.0, 0.0, -0.56), Point3::new(0.555, 0.0, 0.0)),
+        Bounds3::new(Point3::new(0.0, 0.55, -0.56), Point3::new(0.555, 0.55, 0.0)),
+        Bounds3::new(
+            Point3::new(0.0, 0.0, -0.56),
+            Point3::new(0.555, 0.55, -0.56),
+        ),
+        // Side walls
+        Bounds3::new(Point3::new(0.0, 0.0, -0.56), Point3::new(0.0, 0.55, 0.0)),
+        Bounds3::new(
+            Point3::new(0.555, 0.0, -0.56),
+            Point3::new(0.555, 0.55, 0.0),
+        ),
+        // Light
+        Bounds3::new(
+            Point3::new(0.2275, 0.564, -0.33),
+            Point3::new(0.3275, 0.564, -0.23),
+        ),
+        // Short and tall blocks
+        Bounds3::new(
+            Point3::new(0.08, 0.0, -0.23),
+            Point3::new(0.24, 0.165, -0.06),
+        ),
+        Bounds3::new(
+            Point3::new(0.26, 0.0, -0.47),
+            Point3::new(0.43, 0.33, -0.29),
+        ),
+    ];
+    // Split each block in halves along every axis to get some smaller nodes
+    for block in bounds[7..].to_vec() {
+        let center = (block.p_min + block.p_max) / 2.0;
+        for axis in 0..3 {
+            let mut p_max = block.p_max;
+            p_max[axis] = center[axis];
+            let mut p_min = block.p_min;
+            p_min[axis] = center[axis];
+            bounds.push(Bounds3::new(block.p_min, p_max));
+            bounds.push(Bounds3::new(p_min, block.p_max));
+        }
+    }
+    bounds
+}
+
+// Rays from the Cornell box's camera through a grid covering the opening
+fn cornell_rays() -> Vec<Ray<f32>> {
+    let o = Point3::new(0.278, 0.273, 0.8);
+    let mut rays = Vec::with_capacity(SLAB_RAY_RES * SLAB_RAY_RES);
+    for j in 0..SLAB_RAY_RES {
+        for i in 0..SLAB_RAY_RES {
+            let target = Point3::new(
+                0.555 * (i as f32 + 0.5) / (SLAB_RAY_RES as f32),
+                0.55 * (j as f32 + 0.5) / (SLAB_RAY_RES as f32),
+                0.0,
+            );
+            rays.push(Ray::new(o, (target - o).normalized(), f32::INFINITY));
+        }
+    }
+    rays
+}
+
+fn bench_slab_tests() {
+    let bounds = cornell_bounds();
+    let rays = cornell_rays();
+    let precomputed: Vec<(Vec3<f32>, [bool; 3])> = rays
+        .iter()
+        .map(|r| {
+            let inv_dir = Vec3::new(1.0 / r.d.x, 1.0 / r.d.y, 1.0 / r.d.z);
+            (inv_dir, [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0])
+        })
+        .collect();
+    let test_count = (rays.len() * bounds.len() * 20) as f64;
+
+    let start = Instant::now();
+    let mut hits_before = 0;
+    for _ in 0..20 {
+        for (r, (inv_dir, _)) in rays.iter().zip(precomputed.iter()) {
+            for b in &bounds {
+                if b.intersect(*r, *inv_dir) {
+                    hits_before += 1;
+                }
+            }
+        }
+    }
+    let elapsed_ns = start.elapsed().as_nanos();
+    println!(
+        "Sorted   took {:4.1} ms total, {:0.4} ns per test",
+        (elapsed_ns as f64) * 1e-6,
+        (elapsed_ns as f64) / test_count
+    );
+
+    let start = Instant::now();
+    let mut hits_after = 0;
+    for _ in 0..20 {
+        for (r, (inv_dir, dir_is_neg)) in rays.iter().zip(precomputed.iter()) {
+            for b in &bounds {
+                if b.intersect_p(*r, *inv_dir, *dir_is_neg) {
+                    hits_after += 1;
+                }
+            }
+        }
+    }
+    let elapsed_ns = start.elapsed().as_nanos();
+    println!(
+        "Indexed  took {:4.1} ms total, {:0.4} ns per test",
+        (elapsed_ns as f64) * 1e-6,
+        (elapsed_ns as f64) / test_count
+    );
+
+    if hits_before != hits_after {
+        panic!(
+            "Slab test results differ: {} vs {} hits",
+            hits_before, hits_after
+        )
+    }
+}
+
 fn main() {
     let s = Matrix4x4::new([
         [2.0, 0.0, 0.0, 0.0],
@@ -87,6 +210,9 @@ fn main() {
     println!("Mul");
     bench_mul(&srtp);
 
+    println!("Cornell box slab tests");
+    bench_slab_tests();
+
     println!("Press enter to quit...");
     // Read a single byte and discard
     let _ = std::io::stdin().read(&mut [0u8]).unwrap();
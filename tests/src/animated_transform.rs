@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use yuki::math::{
+        transforms::{rotation, rotation_y, scale, translation},
+        AnimatedTransform, Bounds3, Point3, Transform, Vec3,
+    };
+
+    #[test]
+    fn static_transform() {
+        let t = &translation(Vec3::new(1.0, 2.0, 3.0)) * &rotation_y(0.5);
+        let at = AnimatedTransform::new(&t, 0.0, &t, 1.0);
+        assert!(!at.is_animated());
+        assert_eq!(at.interpolate(0.5), t);
+
+        let b = Bounds3::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(at.motion_bounds(b), &t * b);
+    }
+
+    #[test]
+    fn interpolate() {
+        let start =
+            &translation(Vec3::new(0.0, 0.0, 0.0)) * &(&rotation_y(0.0f32) * &scale(1.0, 1.0, 1.0));
+        let end = &translation(Vec3::new(2.0, -4.0, 6.0))
+            * &(&rotation(1.0, Vec3::new(1.0, 1.0, 0.0).normalized()) * &scale(3.0, 2.0, 1.0));
+        let at = AnimatedTransform::new(&start, 1.0, &end, 3.0);
+        assert!(at.is_animated());
+
+        // Ends and times outside the range
+        assert_eq!(at.interpolate(1.0), start);
+        assert_eq!(at.interpolate(0.0), start);
+        assert_eq!(at.interpolate(3.0), end);
+        assert_eq!(at.interpolate(4.0), end);
+
+        let mid = &translation(Vec3::new(1.0, -2.0, 3.0))
+            * &(&rotation(0.5, Vec3::new(1.0, 1.0, 0.0).normalized()) * &scale(2.0, 1.5, 1.0));
+        let t = at.interpolate(2.0);
+        assert_abs_diff_eq!(t.m(), mid.m(), epsilon = 1e-5);
+        assert_abs_diff_eq!(t.m_inv(), mid.m_inv(), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn motion_bounds() {
+        let start = Transform::default();
+        let end = translation(Vec3::new(4.0, 0.0, 0.0));
+        let at = AnimatedTransform::new(&start, 0.0, &end, 1.0);
+
+        let b = Bounds3::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let mb = at.motion_bounds(b);
+        assert_abs_diff_eq!(mb.p_min, Point3::new(-1.0, -1.0, -1.0), epsilon = 1e-5);
+        assert_abs_diff_eq!(mb.p_max, Point3::new(5.0, 1.0, 1.0), epsilon = 1e-5);
+
+        // A quarter turn sweeps the corners outside both end bounds
+        let end = rotation_y(std::f32::consts::FRAC_PI_2);
+        let at = AnimatedTransform::new(&start, 0.0, &end, 1.0);
+        let mb = at.motion_bounds(b);
+        let r = 2.0f32.sqrt();
+        assert!(mb.p_max.x > r - 1e-3 && mb.p_max.z > r - 1e-3);
+        assert!(mb.p_min.x < -r + 1e-3 && mb.p_min.z < -r + 1e-3);
+        assert!((mb.p_max.y - 1.0).abs() < 1e-5);
+    }
+}
@@ -2,7 +2,7 @@
 mod tests {
     use num::Bounded;
 
-    use yuki::math::{Bounds2, Bounds3, Point2, Point3, Vec2, Vec3};
+    use yuki::math::{Bounds2, Bounds3, Point2, Point3, Ray, Vec2, Vec3};
 
     #[test]
     fn new() {
@@ -396,6 +396,43 @@ mod tests {
         assert!(bb.bounding_sphere().is_none());
     }
 
+    #[test]
+    fn intersect_p() {
+        let bb = Bounds3::new(Point3::new(-1.0, -0.5, 0.0), Point3::new(1.0, 0.5, 2.0));
+        let dirs = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 1.0, 1.0).normalized(),
+            Vec3::new(-1.0, 0.5, -0.25).normalized(),
+            Vec3::new(0.3, -0.7, 0.2).normalized(),
+        ];
+        for k in -3..=3 {
+            for j in -3..=3 {
+                for i in -3..=3 {
+                    let o = Point3::new(i as f32, j as f32 * 0.5, k as f32 * 0.75);
+                    for &d in &dirs {
+                        for &t_max in &[0.5, 2.0, f32::INFINITY] {
+                            let ray = Ray::new(o, d, t_max);
+                            let inv_dir = Vec3::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+                            let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+                            let hit = bb.intersect_p(ray, inv_dir, dir_is_neg);
+                            assert_eq!(hit, bb.intersect(ray, inv_dir), "{:?}", ray);
+                            assert_eq!(hit, bb.intersections(ray).is_some(), "{:?}", ray);
+                        }
+                    }
+                }
+            }
+        }
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.5);
+        let inv_dir = Vec3::new(f32::INFINITY, f32::INFINITY, 1.0);
+        assert!(!bb.intersect_p(ray, inv_dir, [false; 3]));
+        let ray = Ray::new(ray.o, ray.d, 1.5);
+        assert!(bb.intersect_p(ray, inv_dir, [false; 3]));
+    }
+
     #[test]
     fn iter() {
         let bb = Bounds2::new(Point2::new(-1, -2), Point2::new(3, 4));
@@ -1,7 +1,11 @@
+mod animated_transform;
 mod bounds;
 mod matrix;
 mod normal;
 mod point;
+mod quaternion;
 mod ray;
+mod spectrum;
 mod transform;
+mod transform_hierarchy;
 mod vector;
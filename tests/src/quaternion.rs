@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use approx::{assert_abs_diff_eq, assert_relative_eq};
+
+    use yuki::math::{
+        transforms::{rotation, rotation_euler, rotation_x, rotation_y, rotation_z, translation},
+        Matrix4x4, Point3, Quaternion, Transform, Vec3,
+    };
+
+    fn rotations() -> Vec<Matrix4x4<f32>> {
+        vec![
+            Matrix4x4::identity(),
+            *rotation_x(0.3).m(),
+            *rotation_y(-1.2).m(),
+            *rotation_z(2.5).m(),
+            // Half turns take the non-trace path with each axis as the largest component
+            *rotation_x(std::f32::consts::PI).m(),
+            *rotation_y(std::f32::consts::PI).m(),
+            *rotation_z(std::f32::consts::PI).m(),
+            *rotation(2.0, Vec3::new(1.0, -2.0, 0.5).normalized()).m(),
+            *rotation_euler(Vec3::new(0.4, -2.2, 1.9)).m(),
+        ]
+    }
+
+    #[test]
+    fn new() {
+        let q = Quaternion::new(Vec3::new(1.0, 2.0, 3.0), 4.0);
+        assert_eq!(q.v, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(q.w, 4.0);
+        let q = Quaternion::<f32>::identity();
+        assert_eq!(q.v, Vec3::zeros());
+        assert_eq!(q.w, 1.0);
+    }
+
+    #[test]
+    fn dot() {
+        let q0 = Quaternion::new(Vec3::new(1.0, 2.0, 3.0), 4.0);
+        let q1 = Quaternion::new(Vec3::new(5.0, 6.0, 7.0), 8.0);
+        assert_eq!(q0.dot(q1), 70.0);
+    }
+
+    #[test]
+    fn normalized() {
+        let q = Quaternion::new(Vec3::new(1.0f32, 2.0, 3.0), 4.0).normalized();
+        assert_relative_eq!(q.dot(q), 1.0);
+        assert_relative_eq!(
+            q,
+            Quaternion::new(Vec3::new(1.0, 2.0, 3.0), 4.0) * (1.0 / 30.0f32.sqrt())
+        );
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        for m in rotations() {
+            let q = Quaternion::from_matrix(&m);
+            assert_relative_eq!(q.dot(q), 1.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(q.to_matrix(), m, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn slerp() {
+        let q0 = Quaternion::from_matrix(rotation_y(0.0f32).m());
+        let q1 = Quaternion::from_matrix(rotation_y(1.0f32).m());
+
+        assert_relative_eq!(q0.slerp(q1, 0.0), q0, epsilon = 1e-6);
+        assert_relative_eq!(q0.slerp(q1, 1.0), q1, epsilon = 1e-6);
+        for &t in &[0.25f32, 0.5, 0.75] {
+            let q = q0.slerp(q1, t);
+            assert_abs_diff_eq!(q.to_matrix(), *rotation_y(t).m(), epsilon = 1e-6);
+        }
+
+        // -q1 is the same rotation so the result should match
+        let q = q0.slerp(-q1, 0.5);
+        assert_abs_diff_eq!(q.to_matrix(), *rotation_y(0.5).m(), epsilon = 1e-6);
+
+        // Nearly parallel rotations use the linear path
+        let q1 = Quaternion::from_matrix(rotation_y(0.01f32).m());
+        let q = q0.slerp(q1, 0.5);
+        assert_abs_diff_eq!(q.to_matrix(), *rotation_y(0.005).m(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn rigid_transform() {
+        let r = rotation(0.7, Vec3::new(0.3, 1.0, -0.2).normalized());
+        let t = &translation(Vec3::new(1.0, -2.0, 3.0)) * &r;
+
+        let (delta, q) = t.decompose_rigid();
+        assert_relative_eq!(delta, Vec3::new(1.0, -2.0, 3.0));
+        assert_abs_diff_eq!(q.to_matrix(), *r.m(), epsilon = 1e-6);
+
+        let t2 = Transform::new_rigid(delta, q);
+        assert_abs_diff_eq!(t2.m(), t.m(), epsilon = 1e-6);
+        assert_abs_diff_eq!(t2.m_inv(), t.m_inv(), epsilon = 1e-5);
+
+        let t0 = &translation(Vec3::new(0.0, 0.0, 0.0)) * &rotation_z(0.0f32);
+        let t1 = &translation(Vec3::new(2.0, 4.0, -2.0)) * &rotation_z(1.0f32);
+        let ti = t0.interpolate_rigid(&t1, 0.5);
+        let expected = &translation(Vec3::new(1.0, 2.0, -1.0)) * &rotation_z(0.5);
+        assert_abs_diff_eq!(ti.m(), expected.m(), epsilon = 1e-6);
+        assert_abs_diff_eq!(
+            &ti * Point3::new(1.0, 0.0, 0.0),
+            &expected * Point3::new(1.0, 0.0, 0.0),
+            epsilon = 1e-6
+        );
+    }
+}
@@ -3,7 +3,7 @@ mod tests {
     use approx::{assert_abs_diff_eq, assert_abs_diff_ne, assert_relative_eq, assert_relative_ne};
     use std::panic;
 
-    use yuki::math::{Point3, Ray, Vec3};
+    use yuki::math::{Point3, Ray, RayDifferential, Vec3};
 
     #[test]
     fn new() {
@@ -14,6 +14,8 @@ mod tests {
         assert_eq!(r.o, o);
         assert_eq!(r.d, d);
         assert_eq!(r.t_max, t_max);
+        assert_eq!(r.time, 0.0);
+        assert_eq!(r.differential, None);
 
         // We won't be able to construct a vec or point with NaNs so let's just check
         // a NaN t_max panics
@@ -27,6 +29,8 @@ mod tests {
         assert_eq!(r.o, Point3::zeros());
         assert_eq!(r.d, Vec3::new(0.0, 1.0, 0.0));
         assert_eq!(r.t_max, f32::INFINITY);
+        assert_eq!(r.time, 0.0);
+        assert_eq!(r.differential, None);
     }
 
     #[test]
@@ -43,6 +47,10 @@ mod tests {
         assert!(r.has_nans());
         r.t_max = f32::INFINITY;
         assert!(!r.has_nans());
+        r.time = f32::NAN;
+        assert!(r.has_nans());
+        r.time = 0.0;
+        assert!(!r.has_nans());
     }
 
     #[test]
@@ -54,6 +62,38 @@ mod tests {
         assert_eq!(r.point(2.0), o + d * 2.0);
     }
 
+    #[test]
+    fn scale_differentials() {
+        let o = Point3::new(1.0, 2.0, 3.0);
+        let d = Vec3::new(0.0, 0.0, 1.0);
+        let mut r = Ray {
+            differential: Some(RayDifferential {
+                rx_o: Point3::new(2.0, 2.0, 3.0),
+                rx_d: Vec3::new(1.0, 0.0, 1.0),
+                ry_o: Point3::new(1.0, 4.0, 3.0),
+                ry_d: Vec3::new(0.0, 2.0, 1.0),
+            }),
+            ..Ray::new(o, d, 1.0)
+        };
+        r.scale_differentials(0.5);
+        assert_eq!(
+            r.differential,
+            Some(RayDifferential {
+                rx_o: Point3::new(1.5, 2.0, 3.0),
+                rx_d: Vec3::new(0.5, 0.0, 1.0),
+                ry_o: Point3::new(1.0, 3.0, 3.0),
+                ry_d: Vec3::new(0.0, 1.0, 1.0),
+            })
+        );
+        assert_eq!(r.o, o);
+        assert_eq!(r.d, d);
+
+        // Rays without differentials are left as is
+        let mut r = Ray::new(o, d, 1.0);
+        r.scale_differentials(0.5);
+        assert_eq!(r.differential, None);
+    }
+
     #[test]
     fn abs_diff_eq() {
         let o = Point3::new(1.0, 2.0, 3.0);
@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use yuki::math::Spectrum;
+
+    // Test the Spectrum specific methods, shared ops are covered by the vector tests
+
+    #[test]
+    fn luminance() {
+        // Rec. 709 weights
+        assert_abs_diff_eq!(Spectrum::new(1.0, 0.0, 0.0).luminance(), 0.2126);
+        assert_abs_diff_eq!(Spectrum::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+        assert_abs_diff_eq!(Spectrum::new(0.0, 0.0, 1.0).luminance(), 0.0722);
+        assert_abs_diff_eq!(Spectrum::<f32>::ones().luminance(), 1.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(
+            Spectrum::new(2.0, 4.0, 8.0).luminance(),
+            3.8988,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn component_mul() {
+        let a = Spectrum::new(1.0, 2.0, 3.0);
+        let b = Spectrum::new(4.0, 5.0, 6.0);
+        assert_eq!(a.component_mul(b), Spectrum::new(4.0, 10.0, 18.0));
+        assert_eq!(a.component_mul(b), a * b);
+        assert_eq!(a.component_mul(Spectrum::ones()), a);
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Spectrum::new(1.0, 2.0, 4.0);
+        let b = Spectrum::new(4.0, 8.0, 16.0);
+        assert_abs_diff_eq!(a.lerp(b, 0.0), a);
+        assert_abs_diff_eq!(a.lerp(b, 0.5), Spectrum::new(2.5, 5.0, 10.0));
+        assert_abs_diff_eq!(a.lerp(b, 1.0), b);
+    }
+
+    #[test]
+    fn clamp() {
+        assert_eq!(
+            Spectrum::new(-1.0, 0.5, 2.0).clamp(0.0, 1.0),
+            Spectrum::new(0.0, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn exp() {
+        assert_abs_diff_eq!(
+            Spectrum::new(0.0, 1.0, 2.0).exp(),
+            Spectrum::new(1.0, 1.0f32.exp(), 2.0f32.exp())
+        );
+    }
+
+    #[test]
+    fn sqrt() {
+        assert_eq!(
+            Spectrum::new(1.0, 4.0, 9.0).sqrt(),
+            Spectrum::new(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn is_black() {
+        assert!(Spectrum::<f32>::zeros().is_black());
+        assert!(!Spectrum::new(0.0, 0.0, 1e-6).is_black());
+    }
+
+    #[test]
+    fn max_comp() {
+        assert_eq!(Spectrum::new(1.0, 3.0, 2.0).max_comp(), 3.0);
+    }
+}
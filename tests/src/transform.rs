@@ -132,6 +132,8 @@ mod tests {
                 23.0
             )
         );
+        let timed_r = Ray { time: 0.5, ..r };
+        assert_eq!((&t * timed_r).time, 0.5);
 
         let bb0 = Bounds3::new(Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 5.0, 6.0));
         let corners = [
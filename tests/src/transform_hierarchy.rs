@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use yuki::math::{
+        transforms::{rotation_y, scale, translation},
+        Point3, Transform, TransformNode, Vec3,
+    };
+
+    #[test]
+    fn flatten() {
+        let points = vec![
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(-4.0, 5.0, 0.5),
+            Point3::new(0.0, -1.0, 2.0),
+        ];
+
+        let root_transform = scale(0.5, 2.0, -1.0);
+        let group_transform = translation(Vec3::new(3.0, -2.0, 1.0));
+        let leaf_transform = rotation_y(0.7);
+
+        let mut leaf = TransformNode::new(leaf_transform.clone());
+        leaf.add_item(points.clone());
+        let mut group = TransformNode::new(group_transform.clone());
+        group.add_item(points.clone());
+        group.add_child(leaf);
+        let mut root = TransformNode::new(root_transform.clone());
+        root.add_child(group);
+
+        let flattened = root.flatten();
+        assert_eq!(flattened.len(), 2);
+
+        let group_to_world = &root_transform * &group_transform;
+        let leaf_to_world = &group_to_world * &leaf_transform;
+        for ((object_to_world, item), baked) in
+            flattened.iter().zip([group_to_world, leaf_to_world].iter())
+        {
+            assert_eq!(item, &points);
+            for &p in item {
+                assert_abs_diff_eq!(object_to_world * p, baked * p, epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn moving_group_moves_children() {
+        let p = Point3::new(1.0, 1.0, 1.0);
+        let offset = Vec3::new(0.0, 2.0, 0.0);
+
+        let mut group = TransformNode::new(translation(offset));
+        for _ in 0..3 {
+            let mut child = TransformNode::new(Transform::default());
+            child.add_item(p);
+            group.add_child(child);
+        }
+
+        for (object_to_world, item) in group.flatten() {
+            assert_abs_diff_eq!(&object_to_world * item, p + offset);
+        }
+    }
+}
@@ -3,7 +3,7 @@ mod tests {
     use approx::{assert_abs_diff_eq, assert_abs_diff_ne, assert_relative_eq, assert_relative_ne};
     use std::panic;
 
-    use yuki::math::{Normal, Point3, Vec2, Vec3, Vec4};
+    use yuki::math::{coordinate_system, Normal, Point3, Vec2, Vec3, Vec4};
 
     // Test both Vec* structs and the generation macros here.
     // Aim is to check everything we expect is implemented and works as expected.
@@ -15,6 +15,12 @@ mod tests {
     // It makes no sense to test impl evaluation here with dummy types since we
     // depend on the real types and they need to be compiled before.
 
+    /// Returns a pseudo-random value in [0,1) from a fixed LCG to keep the tests deterministic
+    fn next_random(state: &mut u32) -> f32 {
+        *state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (*state >> 8) as f32 / (1u32 << 24) as f32
+    }
+
     #[test]
     fn new() {
         let v = Vec2::new(0.0, 1.0);
@@ -101,6 +107,29 @@ mod tests {
             Vec3::new(2.0, 3.0, 4.0).cross(Vec3::new(5.0, 6.0, -7.0)),
             Vec3::new(-45.0, 34.0, -3.0)
         );
+
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        assert_eq!(x.cross(y), z);
+        assert_eq!(y.cross(z), x);
+        assert_eq!(z.cross(x), y);
+
+        let mut state = 0x1234_5678_u32;
+        let mut random_vec = || {
+            Vec3::new(
+                next_random(&mut state) * 2.0 - 1.0,
+                next_random(&mut state) * 2.0 - 1.0,
+                next_random(&mut state) * 2.0 - 1.0,
+            )
+        };
+        for _ in 0..100 {
+            let a = random_vec();
+            let b = random_vec();
+            assert_eq!(a.cross(b), -b.cross(a));
+            assert_abs_diff_eq!(a.cross(b).dot(a), 0.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(a.cross(b).dot(b), 0.0, epsilon = 1e-6);
+        }
     }
 
     #[test]
@@ -440,4 +469,47 @@ mod tests {
             max_relative = 0.5
         );
     }
+
+    #[test]
+    fn coordinate_system_is_orthonormal() {
+        for v in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 2.0, 3.0).normalized(),
+            Vec3::new(-0.2, 0.9, -0.4).normalized(),
+        ] {
+            let (v1, v2) = coordinate_system(v);
+            assert_abs_diff_eq!(v1.len(), 1.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(v2.len(), 1.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(v.dot(v1), 0.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(v.dot(v2), 0.0, epsilon = 1e-6);
+            assert_abs_diff_eq!(v1.dot(v2), 0.0, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn coordinate_system_is_orthonormal_over_sphere() {
+        // Pseudo-random unit vectors, covering both branches
+        let mut state = 0x2545_f491_u32;
+        let mut next = || next_random(&mut state);
+        let mut y_major_count = 0;
+        for _ in 0..1000 {
+            let z = 1.0 - 2.0 * next();
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * std::f32::consts::PI * next();
+            let v = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            if v.y.abs() >= v.x.abs() {
+                y_major_count += 1;
+            }
+
+            let (v1, v2) = coordinate_system(v);
+            assert_abs_diff_eq!(v1.len(), 1.0, epsilon = 1e-5);
+            assert_abs_diff_eq!(v2.len(), 1.0, epsilon = 1e-5);
+            assert_abs_diff_eq!(v.dot(v1), 0.0, epsilon = 1e-5);
+            assert_abs_diff_eq!(v.dot(v2), 0.0, epsilon = 1e-5);
+            assert_abs_diff_eq!(v1.dot(v2), 0.0, epsilon = 1e-5);
+        }
+        assert!(y_major_count > 0);
+    }
 }
@@ -1,41 +1,236 @@
 use super::{
     renderpasses::{find_min_max, HeatmapParams, ToneMapFilm, ToneMapType},
-    util::{try_load_scene, write_exr},
+    util::{try_load_scene, write_exr, write_hdr, write_png, write_single_channel_exr},
     InitialSettings,
 };
 use crate::{
     expect,
     film::{Film, FilmSettings},
+    integrators::IntegratorType,
     math::Spectrum,
-    renderer::{RenderStatus, Renderer},
-    yuki_info,
+    renderer::{RenderSettings, RenderStatus, Renderer},
+    sampling::SamplerType,
+    scene::Scene,
+    yuki_error, yuki_info,
 };
 use glium::{
     backend::glutin::headless::Headless,
     glutin::{dpi::PhysicalSize, event_loop::EventLoop, ContextBuilder},
 };
+use serde::Serialize;
 use std::{
     io::Write,
-    path::Path,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 
-pub fn render(exr_path: &Path, settings: InitialSettings) {
-    let load_settings = settings.load_settings.unwrap_or_default();
+#[derive(Serialize)]
+struct RenderStats {
+    scene: String,
+    integrator: String,
+    sampler: String,
+    resolution: [u16; 2],
+    samples_per_pixel: u32,
+    triangle_count: usize,
+    bvh_node_count: usize,
+    bvh_depth: usize,
+    peak_active_threads: usize,
+    /// `false` if the render was interrupted or hit its time limit
+    completed: bool,
+    render_time_s: f32,
+    /// Only known for completed renders
+    ray_count: Option<usize>,
+    rays_per_s: Option<f32>,
+}
+
+/// Holds the headless GL state for tone mapping.
+///
+/// The event loop can only be created once per process so this is created up front and reused
+/// for all writes.
+struct ToneMapContext {
+    _event_loop: EventLoop<()>,
+    backend: Headless,
+    tone_map_film: ToneMapFilm,
+}
+
+impl ToneMapContext {
+    fn new(film_settings: FilmSettings) -> Self {
+        let event_loop = EventLoop::new();
+        let context = expect!(
+            ContextBuilder::new().build_headless(
+                &event_loop,
+                PhysicalSize::new(film_settings.res.x as u32, film_settings.res.y as u32)
+            ),
+            "Failed to create headless context"
+        );
+        let backend = expect!(Headless::new(context), "Failed to create headless backend");
+
+        let tone_map_film = expect!(
+            ToneMapFilm::new(&backend),
+            "Failed to create tone map render pass"
+        );
+
+        Self {
+            _event_loop: event_loop,
+            backend,
+            tone_map_film,
+        }
+    }
+}
+
+/// Image format of the film output, picked from the output file extension
+#[derive(Copy, Clone)]
+enum OutputFormat {
+    Exr,
+    Png,
+    Hdr,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("exr") => Ok(Self::Exr),
+            Some("png") => Ok(Self::Png),
+            Some("hdr") => Ok(Self::Hdr),
+            Some(ext) => Err(format!("Unknown output extension '{}'", ext)),
+            None => Err("Expected an output file with an extension".into()),
+        }
+    }
+}
 
-    let (scene, camera_params, scene_film_settings, _) =
-        expect!(try_load_scene(&load_settings), "Scene loading failed");
+/// What the renderer is launched with, resolved from the persisted settings
+#[derive(Copy, Clone)]
+struct LaunchSettings {
+    film_settings: FilmSettings,
+    render_settings: RenderSettings,
+    sampler: SamplerType,
+    scene_integrator: IntegratorType,
+}
+
+impl LaunchSettings {
+    /// Picks the film, render, sampler and integrator settings from `settings` so that headless
+    /// renders match the window session they were configured in.
+    ///
+    /// The film falls back to `scene_film_settings` and the rest to their defaults.
+    fn new(
+        settings: &InitialSettings,
+        scene_film_settings: FilmSettings,
+        sample_budget: Option<u32>,
+        time_limit: Option<Duration>,
+        write_aovs: bool,
+    ) -> Self {
+        let mut film_settings = settings.film_settings.unwrap_or(scene_film_settings);
+        film_settings.accumulate |= sample_budget.is_some() || time_limit.is_some();
+        film_settings.aovs = write_aovs;
+
+        let sampler = settings.sampler.unwrap_or_default();
+        let sampler = if let Some(spp) = sample_budget {
+            sampler.with_samples_per_pixel(spp)
+        } else {
+            sampler
+        };
+
+        Self {
+            film_settings,
+            render_settings: settings.render_settings.unwrap_or_default(),
+            sampler,
+            scene_integrator: settings.scene_integrator.unwrap_or_default(),
+        }
+    }
+}
 
-    let film_settings = settings.film_settings.unwrap_or(scene_film_settings);
-    let render_settings = settings.render_settings.unwrap_or_default();
-    let sampler = settings.sampler.unwrap_or_default();
-    let scene_integrator = settings.scene_integrator.unwrap_or_default();
+/// How the per-pixel noise estimate should be written out
+#[derive(Copy, Clone)]
+pub enum NoiseOutput {
+    Raw,
+    Normalized,
+}
+
+/// Renders the scene in `settings` into `out_path`.
+///
+/// Returns an error if the output format isn't supported or the scene fails to load.
+///
+/// Setting `sample_budget` or `time_limit` switches to accumulating single sample passes so
+/// that the render can be stopped at the time limit with a properly averaged result.
+///
+/// Progress is logged at `progress_interval` if set, otherwise it is kept on a single
+/// terminal line that is rewritten as the render progresses.
+///
+/// The render is stopped and its current progress written out when `interrupted` is set. Stats
+/// are also written for stopped renders but without the ray count that only a finished render
+/// reports.
+pub fn render(
+    out_path: &Path,
+    settings: InitialSettings,
+    interrupted: &AtomicBool,
+    write_stats: bool,
+    autosave_interval: Option<Duration>,
+    progress_interval: Option<Duration>,
+    noise_output: Option<NoiseOutput>,
+    sample_budget: Option<u32>,
+    time_limit: Option<Duration>,
+    write_aovs: bool,
+) -> Result<(), String> {
+    let format = OutputFormat::from_path(out_path)?;
+
+    let load_settings = settings.load_settings.clone().unwrap_or_default();
+
+    let (scene, mut camera_params, scene_film_settings, _) =
+        try_load_scene(&load_settings, &AtomicBool::new(false))?;
+
+    let LaunchSettings {
+        film_settings,
+        render_settings,
+        sampler,
+        scene_integrator,
+    } = LaunchSettings::new(
+        &settings,
+        scene_film_settings,
+        sample_budget,
+        time_limit,
+        write_aovs,
+    );
+    if load_settings.auto_frame {
+        camera_params.frame_bounds(scene.world_bound(), &film_settings);
+    }
+    if let Some(params) = settings.camera_params_for(&scene.load_settings.path) {
+        camera_params = params;
+    }
     let tone_map = settings.tone_map.unwrap_or_default();
+    let exposure_ev = settings.exposure_ev.unwrap_or(0.0);
 
     let film = Arc::new(Mutex::new(Film::new(film_settings.res)));
     let mut renderer = Renderer::new();
 
+    let mut tone_map_context = if let ToneMapType::Raw = tone_map {
+        None
+    } else {
+        Some(ToneMapContext::new(film_settings))
+    };
+
+    let samples_per_pixel = sampler.instantiate(false).samples_per_pixel();
+
+    // Collect the static parts of the stats before the scene is moved to the renderer
+    let mut stats = write_stats.then(|| RenderStats {
+        scene: scene.name.clone(),
+        integrator: scene_integrator.to_string(),
+        sampler: sampler.to_string(),
+        resolution: [film_settings.res.x, film_settings.res.y],
+        samples_per_pixel,
+        triangle_count: triangle_count(&scene),
+        bvh_node_count: scene.bvh.node_count(),
+        bvh_depth: scene.bvh.depth(),
+        peak_active_threads: 0,
+        completed: false,
+        render_time_s: 0.0,
+        ray_count: None,
+        rays_per_s: None,
+    });
+
     let render_start = Instant::now();
     renderer.launch(
         scene,
@@ -49,48 +244,117 @@ pub fn render(exr_path: &Path, settings: InitialSettings) {
     );
 
     let mut max_line_length = 0;
+    let mut last_autosave = Instant::now();
+    let mut last_progress_log: Option<Instant> = None;
+    let end_progress_line = || {
+        if progress_interval.is_none() {
+            println!();
+        }
+    };
     loop {
+        if interrupted.load(Ordering::SeqCst) {
+            end_progress_line();
+            yuki_info!("Interrupted, writing out current progress");
+            renderer.kill();
+            write_partial_stats(stats.take(), render_start.elapsed().as_secs_f32(), out_path);
+            write_outputs(
+                format,
+                tone_map,
+                exposure_ev,
+                &film,
+                &mut tone_map_context,
+                out_path,
+                noise_output,
+                samples_per_pixel,
+                write_aovs,
+            );
+            break;
+        }
+
+        if time_limit.map_or(false, |limit| render_start.elapsed() >= limit) {
+            end_progress_line();
+            yuki_info!("Time limit reached, writing out current progress");
+            renderer.kill();
+            write_partial_stats(stats.take(), render_start.elapsed().as_secs_f32(), out_path);
+            write_outputs(
+                format,
+                tone_map,
+                exposure_ev,
+                &film,
+                &mut tone_map_context,
+                out_path,
+                noise_output,
+                samples_per_pixel,
+                write_aovs,
+            );
+            break;
+        }
+
+        if let Some(interval) = autosave_interval {
+            if last_autosave.elapsed() >= interval {
+                end_progress_line();
+                yuki_info!("Autosaving current progress");
+                write_film(
+                    format,
+                    tone_map,
+                    exposure_ev,
+                    &film,
+                    &mut tone_map_context,
+                    out_path,
+                );
+                last_autosave = Instant::now();
+            }
+        }
+
         if let Some(status) = renderer.check_status() {
             let elapsed_s = render_start.elapsed().as_secs_f32();
 
             match status {
-                RenderStatus::Finished { .. } => {
+                RenderStatus::Finished { ray_count } => {
                     // Progress rewrites its line, but let's have a new line for end logs
-                    println!();
-                    yuki_info!("Render finished in {:.2}s", elapsed_s);
-
-                    #[allow(clippy::match_wild_err_arm)]
-                    // "Wild" ignore needed as err is Arc itself
-                    match Arc::try_unwrap(film) {
-                        Ok(film) => {
-                            let (w, h, pixels) = if let ToneMapType::Raw = tone_map {
-                                let film = expect!(
-                                    film.into_inner(),
-                                    "Failed to pull Film out of its Mutex"
-                                );
-                                (
-                                    film.res().x as usize,
-                                    film.res().y as usize,
-                                    film.pixels().clone(),
-                                )
-                            } else {
-                                apply_tone_map(tone_map, &film, film_settings)
-                            };
-                            expect!(write_exr(w, h, &pixels, exr_path,), "");
-                            break;
-                        }
-                        Err(_) => {
-                            panic!("Failed to pull Film out of its Arc");
+                    end_progress_line();
+                    yuki_info!(
+                        "Render finished in {:.2}s, {} rays at {:>4.2} Mrays/s",
+                        elapsed_s,
+                        ray_count,
+                        (ray_count as f64 / elapsed_s as f64) * 1e-6
+                    );
+
+                    if let Some(mut stats) = stats.take() {
+                        stats.completed = true;
+                        stats.render_time_s = elapsed_s;
+                        stats.ray_count = Some(ray_count);
+                        stats.rays_per_s = Some((ray_count as f64 / elapsed_s as f64) as f32);
+                        if let Err(why) = write_stats_json(&stats, out_path) {
+                            yuki_error!("{}", why);
                         }
                     }
+
+                    write_outputs(
+                        format,
+                        tone_map,
+                        exposure_ev,
+                        &film,
+                        &mut tone_map_context,
+                        out_path,
+                        noise_output,
+                        samples_per_pixel,
+                        write_aovs,
+                    );
+                    break;
                 }
                 RenderStatus::Progress {
+                    active_threads,
                     tiles_done,
                     tiles_total,
                     approx_remaining_s,
                     current_rays_per_s,
                     ..
                 } => {
+                    if let Some(stats) = &mut stats {
+                        stats.peak_active_threads = stats.peak_active_threads.max(active_threads);
+                    }
+
                     let line = format!(
                         "Tile {}/{} | {:.1}s elapsed, ~{:.0}s remaining | {:>4.2} Mrays/s",
                         tiles_done,
@@ -99,37 +363,208 @@ pub fn render(exr_path: &Path, settings: InitialSettings) {
                         approx_remaining_s,
                         current_rays_per_s * 1e-6
                     );
-                    max_line_length = max_line_length.max(line.len());
+                    if let Some(interval) = progress_interval {
+                        if last_progress_log.map_or(true, |t| t.elapsed() >= interval) {
+                            yuki_info!("{}", line);
+                            last_progress_log = Some(Instant::now());
+                        }
+                    } else {
+                        max_line_length = max_line_length.max(line.len());
 
-                    print!("\r{:<width$}", line, width = max_line_length);
-                    std::io::stdout().flush().unwrap();
+                        print!("\r{:<width$}", line, width = max_line_length);
+                        std::io::stdout().flush().unwrap();
+                    }
                 }
             }
         }
         std::thread::sleep(Duration::from_millis(100));
     }
+
+    Ok(())
 }
 
-fn apply_tone_map(
-    mut tone_map: ToneMapType,
+fn triangle_count(scene: &Scene) -> usize {
+    scene.meshes.iter().map(|m| m.indices.len() / 3).sum()
+}
+
+/// Writes the `stats` of a stopped render next to `out_path` if they were requested
+fn write_partial_stats(stats: Option<RenderStats>, elapsed_s: f32, out_path: &Path) {
+    if let Some(mut stats) = stats {
+        stats.render_time_s = elapsed_s;
+        if let Err(why) = write_stats_json(&stats, out_path) {
+            yuki_error!("{}", why);
+        }
+    }
+}
+
+/// Writes `stats` next to `out_path` as `<name>.stats.json`
+fn write_stats_json(stats: &RenderStats, out_path: &Path) -> Result<(), String> {
+    let path = out_path.with_extension("stats.json");
+    let file = match std::fs::File::create(&path) {
+        Ok(file) => file,
+        Err(why) => {
+            return Err(format!(
+                "Error creating stats file '{}': {}",
+                path.to_string_lossy(),
+                why
+            ))
+        }
+    };
+    match serde_json::to_writer_pretty(file, stats) {
+        Ok(_) => {
+            yuki_info!("Stats written to '{}'", path.to_string_lossy());
+            Ok(())
+        }
+        Err(why) => Err(format!(
+            "Error writing stats to '{}': {}",
+            path.to_string_lossy(),
+            why
+        )),
+    }
+}
+
+/// Writes the current contents of `film` into `out_path` with the noise estimate and AOV sidecars
+/// if they were requested.
+fn write_outputs(
+    format: OutputFormat,
+    tone_map: ToneMapType,
+    exposure_ev: f32,
     film: &Mutex<Film>,
-    film_settings: FilmSettings,
-) -> (usize, usize, Vec<Spectrum<f32>>) {
-    let event_loop = EventLoop::new();
-    let context = expect!(
-        ContextBuilder::new().build_headless(
-            &event_loop,
-            PhysicalSize::new(film_settings.res.x as u32, film_settings.res.y as u32)
+    tone_map_context: &mut Option<ToneMapContext>,
+    out_path: &Path,
+    noise_output: Option<NoiseOutput>,
+    samples_per_pixel: u32,
+    write_aovs: bool,
+) {
+    write_film(
+        format,
+        tone_map,
+        exposure_ev,
+        film,
+        tone_map_context,
+        out_path,
+    );
+    if let Some(noise_output) = noise_output {
+        write_noise(noise_output, film, samples_per_pixel, out_path);
+    }
+    if write_aovs {
+        write_aov_sidecars(film, out_path);
+    }
+}
+
+/// Writes the current contents of `film` into `out_path` as `format`, tone mapped with exposure
+/// compensation if `tone_map` isn't raw. `out_path` is replaced only once the write has succeeded.
+fn write_film(
+    format: OutputFormat,
+    tone_map: ToneMapType,
+    exposure_ev: f32,
+    film: &Mutex<Film>,
+    tone_map_context: &mut Option<ToneMapContext>,
+    out_path: &Path,
+) {
+    let (w, h, pixels) = if let Some(context) = tone_map_context {
+        apply_tone_map(tone_map, exposure_ev, film, context)
+    } else {
+        let film = expect!(film.lock(), "Failed to lock film");
+        (
+            film.res().x as usize,
+            film.res().y as usize,
+            film.mean_pixels(),
+        )
+    };
+    // Write next to the output and move over it so that a write cut short by a crash or a kill
+    // doesn't leave a truncated image in place of the previous autosave
+    let tmp_path = tmp_sibling(out_path);
+    let result = match format {
+        OutputFormat::Exr => write_exr(w, h, &pixels, &tmp_path),
+        OutputFormat::Png => write_png(w, h, &pixels, &tmp_path),
+        OutputFormat::Hdr => write_hdr(w, h, &pixels, &tmp_path),
+    };
+    expect!(result, "");
+    expect!(
+        std::fs::rename(&tmp_path, out_path),
+        format!("Failed to move output to '{}'", out_path.to_string_lossy())
+    );
+}
+
+/// Returns `<name>.tmp` next to `path`
+fn tmp_sibling(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Writes the relative variance estimate of `film` next to `out_path` as `<name>.noise.exr`
+fn write_noise(
+    noise_output: NoiseOutput,
+    film: &Mutex<Film>,
+    samples_per_pixel: u32,
+    out_path: &Path,
+) {
+    let (res, mut variances) = {
+        let film = expect!(film.lock(), "Failed to lock film");
+        (film.res(), film.relative_variances(samples_per_pixel))
+    };
+
+    if let NoiseOutput::Normalized = noise_output {
+        let max = variances.iter().copied().fold(0.0, f32::max);
+        if max > 0.0 {
+            for v in &mut variances {
+                *v /= max;
+            }
+        }
+    }
+
+    expect!(
+        write_single_channel_exr(
+            res.x as usize,
+            res.y as usize,
+            &variances,
+            "Y",
+            &out_path.with_extension("noise.exr"),
         ),
-        "Failed to create headless context"
+        ""
     );
-    let backend = expect!(Headless::new(context), "Failed to create headless backend");
+}
 
-    let mut tone_map_film = expect!(
-        ToneMapFilm::new(&backend),
-        "Failed to create tone map render pass"
+/// Writes the first hit AOVs of `film` next to `out_path` as `<name>.albedo.exr` and
+/// `<name>.normal.exr`
+fn write_aov_sidecars(film: &Mutex<Film>, out_path: &Path) {
+    let (res, aovs) = {
+        let film = expect!(film.lock(), "Failed to lock film");
+        (film.res(), film.mean_aovs())
+    };
+    let aovs = match aovs {
+        Some(aovs) => aovs,
+        None => {
+            yuki_error!("Film has no AOVs to write");
+            return;
+        }
+    };
+    let (w, h) = (res.x as usize, res.y as usize);
+
+    expect!(
+        write_exr(w, h, &aovs.albedo, &out_path.with_extension("albedo.exr")),
+        ""
+    );
+
+    let normals: Vec<Spectrum<f32>> = aovs
+        .normal
+        .iter()
+        .map(|n| Spectrum::new(n.x, n.y, n.z))
+        .collect();
+    expect!(
+        write_exr(w, h, &normals, &out_path.with_extension("normal.exr")),
+        ""
     );
+}
 
+fn apply_tone_map(
+    mut tone_map: ToneMapType,
+    exposure_ev: f32,
+    film: &Mutex<Film>,
+    context: &mut ToneMapContext,
+) -> (usize, usize, Vec<Spectrum<f32>>) {
     if let ToneMapType::Heatmap(HeatmapParams {
         ref mut bounds,
         channel,
@@ -137,14 +572,16 @@ fn apply_tone_map(
     {
         if bounds.is_none() {
             *bounds = Some(expect!(
-                find_min_max(film, channel),
+                find_min_max(film, channel, exposure_ev),
                 "Failed to find film min, max"
             ));
         }
     }
 
     let tone_mapped_film = expect!(
-        tone_map_film.draw(&backend, film, &tone_map),
+        context
+            .tone_map_film
+            .draw(&context.backend, film, &tone_map, exposure_ev),
         "Failed to tone map film"
     );
     // TODO: This will explode if mapped texture format is not f32f32f32
@@ -156,3 +593,193 @@ fn apply_tone_map(
         pixels,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicBool, Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{render, LaunchSettings, ToneMapType};
+    use crate::{
+        app::InitialSettings,
+        film::{Film, FilmSettings},
+        integrators::IntegratorType,
+        math::{Spectrum, Vec2},
+        renderer::{RenderStatus, Renderer},
+        scene::{Scene, SceneLoadSettings},
+    };
+
+    fn render(launch: LaunchSettings) -> Vec<Spectrum<f32>> {
+        let (scene, camera_params, _, _) = Scene::cornell();
+        let film = Arc::new(Mutex::new(Film::new(launch.film_settings.res)));
+        let mut renderer = Renderer::new();
+        renderer.launch(
+            scene,
+            camera_params,
+            Arc::clone(&film),
+            launch.sampler,
+            launch.scene_integrator,
+            launch.film_settings,
+            launch.render_settings,
+            false,
+        );
+        while !matches!(renderer.check_status(), Some(RenderStatus::Finished { .. })) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let pixels = film.lock().unwrap().pixels().clone();
+        pixels
+    }
+
+    #[test]
+    fn persisted_integrator_is_used() {
+        let settings: InitialSettings = serde_yaml::from_str(
+            r#"
+render_settings:
+  mark_tiles: false
+  use_single_render_thread: false
+  priority: Normal
+  seed: 1234
+scene_integrator: GeometryNormals
+"#,
+        )
+        .unwrap();
+        let scene_film_settings = FilmSettings {
+            res: Vec2::new(32, 24),
+            tile_dim: 8,
+            ..FilmSettings::default()
+        };
+
+        let launch = LaunchSettings::new(&settings, scene_film_settings, Some(1), None, false);
+        assert!(matches!(
+            launch.scene_integrator,
+            IntegratorType::GeometryNormals
+        ));
+        assert_eq!(launch.render_settings.seed, Some(1234));
+        assert_eq!(launch.film_settings.res, scene_film_settings.res);
+
+        let normals = LaunchSettings {
+            scene_integrator: IntegratorType::GeometryNormals,
+            ..launch
+        };
+        let whitted = LaunchSettings {
+            scene_integrator: IntegratorType::default(),
+            ..launch
+        };
+        assert!(matches!(
+            whitted.scene_integrator,
+            IntegratorType::Whitted(_)
+        ));
+
+        let rendered = render(launch);
+        assert!(rendered.iter().any(|p| !p.is_black()));
+        assert!(rendered == render(normals));
+        assert!(rendered != render(whitted));
+    }
+
+    #[test]
+    fn renders_scene_file() {
+        let dir = std::env::temp_dir().join(format!("yuki_headless_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let scene_path = dir.join("triangle.obj");
+        std::fs::write(&scene_path, "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let out_path = dir.join("out.exr");
+
+        let settings = |path| InitialSettings {
+            film_settings: Some(FilmSettings {
+                res: Vec2::new(16, 16),
+                tile_dim: 8,
+                ..FilmSettings::default()
+            }),
+            tone_map: Some(ToneMapType::Raw),
+            load_settings: Some(SceneLoadSettings {
+                path,
+                ..SceneLoadSettings::default()
+            }),
+            ..InitialSettings::default()
+        };
+        let rendered = render(
+            &out_path,
+            settings(scene_path.clone()),
+            &AtomicBool::new(false),
+            false,
+            None,
+            None,
+            None,
+            Some(1),
+            None,
+            false,
+        );
+        let written = out_path.exists();
+        let missing = render(
+            &out_path,
+            settings(dir.join("missing.obj")),
+            &AtomicBool::new(false),
+            false,
+            None,
+            None,
+            None,
+            Some(1),
+            None,
+            false,
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(rendered.is_ok(), "{:?}", rendered);
+        assert!(written);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn interrupted_render_writes_progress() {
+        let dir =
+            std::env::temp_dir().join(format!("yuki_headless_interrupt_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let scene_path = dir.join("triangle.obj");
+        std::fs::write(&scene_path, "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let out_path = dir.join("out.exr");
+
+        let settings = || InitialSettings {
+            film_settings: Some(FilmSettings {
+                res: Vec2::new(16, 16),
+                tile_dim: 8,
+                ..FilmSettings::default()
+            }),
+            tone_map: Some(ToneMapType::Raw),
+            load_settings: Some(SceneLoadSettings {
+                path: scene_path.clone(),
+                ..SceneLoadSettings::default()
+            }),
+            ..InitialSettings::default()
+        };
+        // Calling render again shouldn't try to install another SIGINT handler
+        for _ in 0..2 {
+            let rendered = render(
+                &out_path,
+                settings(),
+                &AtomicBool::new(true),
+                true,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            );
+            assert!(rendered.is_ok(), "{:?}", rendered);
+        }
+        let written = out_path.exists();
+        let tmp_left = super::tmp_sibling(&out_path).exists();
+        let stats: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(out_path.with_extension("stats.json")).unwrap(),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(written);
+        assert!(!tmp_left);
+        assert_eq!(stats["completed"], false);
+        assert!(stats["ray_count"].is_null());
+    }
+}
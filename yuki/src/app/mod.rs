@@ -8,11 +8,12 @@ pub use renderpasses::{FilmicParams, HeatmapParams, ToneMapType};
 pub use window::Window;
 
 use crate::{
-    film::FilmSettings, integrators::IntegratorType, renderer::RenderSettings,
-    sampling::SamplerType, scene::SceneLoadSettings,
+    camera::CameraParameters, film::FilmSettings, integrators::IntegratorType,
+    renderer::RenderSettings, sampling::SamplerType, scene::SceneLoadSettings,
 };
 
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 #[derive(Default, Deserialize, Serialize)]
 pub struct InitialSettings {
@@ -21,5 +22,39 @@ pub struct InitialSettings {
     pub sampler: Option<SamplerType>,
     pub scene_integrator: Option<IntegratorType>,
     pub tone_map: Option<ToneMapType>,
+    /// Exposure compensation in stops, applied before the tone map operator
+    pub exposure_ev: Option<f32>,
     pub load_settings: Option<SceneLoadSettings>,
+    pub navigation: Option<NavigationSettings>,
+    pub camera_params: Option<CameraParameters>,
+    /// Scene that `camera_params` was saved in
+    pub camera_scene_path: Option<PathBuf>,
+}
+
+impl InitialSettings {
+    /// Returns the persisted camera if it was saved in the scene at `scene_path`.
+    pub fn camera_params_for(&self, scene_path: &Path) -> Option<CameraParameters> {
+        match &self.camera_scene_path {
+            Some(path) if path == scene_path => self.camera_params,
+            _ => None,
+        }
+    }
+}
+
+/// Sensitivities of the keyboard and mouse camera controls
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct NavigationSettings {
+    /// In scene units per second
+    pub move_speed: f32,
+    /// In radians per pixel
+    pub look_sensitivity: f32,
+}
+
+impl Default for NavigationSettings {
+    fn default() -> Self {
+        Self {
+            move_speed: 1.0,
+            look_sensitivity: 1.0 / 400.0,
+        }
+    }
 }
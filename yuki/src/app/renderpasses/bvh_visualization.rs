@@ -1,7 +1,7 @@
 use glium::Surface;
 
 use crate::{
-    camera::{CameraParameters, FoV},
+    camera::{CameraParameters, CameraType, FoV},
     film::FilmSettings,
     math::{transforms::look_at, Bounds3, Matrix4x4, Point3, Transform},
     yuki_trace,
@@ -124,32 +124,53 @@ impl BvhVisualization {
                     .fold(0.0, |acc, &p| (p - camera_params.position).len().max(acc));
                 let zn = zf * 1e-5;
 
-                let fov = match camera_params.fov {
-                    FoV::X(angle) | FoV::Y(angle) => angle,
-                };
-                let tan_half_fov = (fov * 0.5).to_radians().tan();
-                let (xf, yf) = match camera_params.fov {
-                    FoV::X(_) => {
+                match camera_params.camera_type.projected() {
+                    CameraType::Perspective { fov } => {
+                        let angle = match fov {
+                            FoV::X(angle) | FoV::Y(angle) => angle,
+                        };
+                        let tan_half_fov = (angle * 0.5).to_radians().tan();
+                        let (xf, yf) = match fov {
+                            FoV::X(_) => {
+                                let ar =
+                                    (film_settings.res.y as f32) / (film_settings.res.x as f32);
+                                (1.0 / tan_half_fov, 1.0 / (tan_half_fov * ar))
+                            }
+                            FoV::Y(_) => {
+                                let ar =
+                                    (film_settings.res.x as f32) / (film_settings.res.y as f32);
+                                (1.0 / (tan_half_fov * ar), 1.0 / tan_half_fov)
+                            }
+                        };
+
+                        Transform::new_m(Matrix4x4::new([
+                            [xf, 0.0, 0.0, 0.0],
+                            [0.0, yf, 0.0, 0.0],
+                            [
+                                0.0,
+                                0.0,
+                                (zf + zn) / (zf - zn),
+                                -(2.0 * zf * zn) / (zf - zn),
+                            ],
+                            [0.0, 0.0, 1.0, 0.0],
+                        ]))
+                    }
+                    CameraType::Orthographic { screen_width } => {
                         let ar = (film_settings.res.y as f32) / (film_settings.res.x as f32);
-                        (1.0 / tan_half_fov, 1.0 / (tan_half_fov * ar))
+                        let xf = 2.0 / screen_width;
+                        let yf = xf / ar;
+
+                        Transform::new_m(Matrix4x4::new([
+                            [xf, 0.0, 0.0, 0.0],
+                            [0.0, yf, 0.0, 0.0],
+                            [0.0, 0.0, 2.0 / zf, -1.0],
+                            [0.0, 0.0, 0.0, 1.0],
+                        ]))
                     }
-                    FoV::Y(_) => {
-                        let ar = (film_settings.res.x as f32) / (film_settings.res.y as f32);
-                        (1.0 / (tan_half_fov * ar), 1.0 / tan_half_fov)
+                    CameraType::Environment => {
+                        unreachable!("Projected camera can't be environment")
                     }
-                };
-
-                Transform::new_m(Matrix4x4::new([
-                    [xf, 0.0, 0.0, 0.0],
-                    [0.0, yf, 0.0, 0.0],
-                    [
-                        0.0,
-                        0.0,
-                        (zf + zn) / (zf - zn),
-                        -(2.0 * zf * zn) / (zf - zn),
-                    ],
-                    [0.0, 0.0, 1.0, 0.0],
-                ]))
+                }
             };
 
             // The film is "upside down" at this point
@@ -4,6 +4,9 @@ mod scale_output;
 mod tonemap;
 
 pub use bvh_visualization::BvhVisualization;
-pub use ray_visualization::RayVisualization;
-pub use scale_output::ScaleOutput;
-pub use tonemap::{find_min_max, FilmicParams, HeatmapParams, ToneMapFilm, ToneMapType};
+pub use ray_visualization::{RayVisualization, RayVisualizationSettings};
+pub use scale_output::{film_rect, ScaleOutput};
+pub use tonemap::{
+    auto_exposure_ev, find_auto_exposure, find_min_max, ClippingParams, FilmicParams,
+    HeatmapChannel, HeatmapParams, ReinhardParams, ToneMapFilm, ToneMapType,
+};
@@ -1,15 +1,54 @@
 use glium::Surface;
 
 use crate::{
-    camera::{CameraParameters, FoV},
+    camera::{CameraParameters, CameraType, FoV},
     film::FilmSettings,
     integrators::{IntegratorRay, RayType},
     math::{transforms::look_at, Bounds3, Matrix4x4, Point3, Transform},
     yuki_trace,
 };
 
+/// Controls which debug rays are drawn
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Copy, Clone)]
+pub struct RayVisualizationSettings {
+    /// Number of paths traced from the picked pixel
+    pub path_count: u32,
+    pub show_direct: bool,
+    pub show_reflection: bool,
+    pub show_refraction: bool,
+    pub show_normals: bool,
+    pub show_shadow: bool,
+}
+
+impl Default for RayVisualizationSettings {
+    fn default() -> Self {
+        Self {
+            path_count: 1,
+            show_direct: true,
+            show_reflection: true,
+            show_refraction: true,
+            show_normals: true,
+            show_shadow: true,
+        }
+    }
+}
+
+impl RayVisualizationSettings {
+    fn shows(&self, ray_type: RayType) -> bool {
+        match ray_type {
+            RayType::Direct => self.show_direct,
+            RayType::Reflection => self.show_reflection,
+            RayType::Refraction => self.show_refraction,
+            RayType::Normal => self.show_normals,
+            RayType::Shadow => self.show_shadow,
+        }
+    }
+}
+
 pub struct RayVisualization {
-    buffers: Option<(glium::VertexBuffer<Vertex>, glium::IndexBuffer<u16>)>,
+    rays: Vec<IntegratorRay>,
+    buffers: Option<(glium::VertexBuffer<Vertex>, glium::IndexBuffer<u32>)>,
     program: glium::Program,
 }
 
@@ -20,19 +59,42 @@ impl RayVisualization {
         let program = glium::Program::from_source(backend, VS_CODE, FS_CODE, None)?;
 
         Ok(Self {
+            rays: Vec::new(),
             buffers: None,
             program,
         })
     }
 
+    /// Sets the drawn rays, filtered by `settings`.
     pub fn set_rays<T: glium::backend::Facade>(
         &mut self,
         backend: &T,
-        rays: &[IntegratorRay],
+        rays: Vec<IntegratorRay>,
+        settings: RayVisualizationSettings,
     ) -> Result<(), SetRaysError> {
+        self.rays = rays;
+        self.update_buffers(backend, settings)
+    }
+
+    /// Re-filters the current rays with `settings`.
+    pub fn update_buffers<T: glium::backend::Facade>(
+        &mut self,
+        backend: &T,
+        settings: RayVisualizationSettings,
+    ) -> Result<(), SetRaysError> {
+        if self.rays.is_empty() {
+            self.buffers = None;
+            return Ok(());
+        }
+
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
-        for (i, IntegratorRay { ray, ray_type }) in rays.iter().enumerate() {
+        for (i, IntegratorRay { ray, ray_type }) in self
+            .rays
+            .iter()
+            .filter(|r| settings.shows(r.ray_type))
+            .enumerate()
+        {
             let color = match ray_type {
                 RayType::Direct => [1.0, 1.0, 1.0],
                 RayType::Reflection => [1.0, 0.0, 0.0],
@@ -50,8 +112,8 @@ impl RayVisualization {
                 position: [p1.x, p1.y, p1.z],
                 color,
             });
-            indices.push((i * 2) as u16);
-            indices.push((i * 2 + 1) as u16);
+            indices.push((i * 2) as u32);
+            indices.push((i * 2 + 1) as u32);
         }
 
         self.buffers = Some((
@@ -64,6 +126,7 @@ impl RayVisualization {
     }
 
     pub fn clear_rays(&mut self) {
+        self.rays.clear();
         self.buffers = None;
     }
 
@@ -103,32 +166,53 @@ impl RayVisualization {
                     .fold(0.0, |acc, &p| (p - camera_params.position).len().max(acc));
                 let zn = zf * 1e-5;
 
-                let fov = match camera_params.fov {
-                    FoV::X(angle) | FoV::Y(angle) => angle,
-                };
-                let tan_half_fov = (fov * 0.5).to_radians().tan();
-                let (xf, yf) = match camera_params.fov {
-                    FoV::X(_) => {
+                match camera_params.camera_type.projected() {
+                    CameraType::Perspective { fov } => {
+                        let angle = match fov {
+                            FoV::X(angle) | FoV::Y(angle) => angle,
+                        };
+                        let tan_half_fov = (angle * 0.5).to_radians().tan();
+                        let (xf, yf) = match fov {
+                            FoV::X(_) => {
+                                let ar =
+                                    (film_settings.res.y as f32) / (film_settings.res.x as f32);
+                                (1.0 / tan_half_fov, 1.0 / (tan_half_fov * ar))
+                            }
+                            FoV::Y(_) => {
+                                let ar =
+                                    (film_settings.res.x as f32) / (film_settings.res.y as f32);
+                                (1.0 / (tan_half_fov * ar), 1.0 / tan_half_fov)
+                            }
+                        };
+
+                        Transform::new_m(Matrix4x4::new([
+                            [xf, 0.0, 0.0, 0.0],
+                            [0.0, yf, 0.0, 0.0],
+                            [
+                                0.0,
+                                0.0,
+                                (zf + zn) / (zf - zn),
+                                -(2.0 * zf * zn) / (zf - zn),
+                            ],
+                            [0.0, 0.0, 1.0, 0.0],
+                        ]))
+                    }
+                    CameraType::Orthographic { screen_width } => {
                         let ar = (film_settings.res.y as f32) / (film_settings.res.x as f32);
-                        (1.0 / tan_half_fov, 1.0 / (tan_half_fov * ar))
+                        let xf = 2.0 / screen_width;
+                        let yf = xf / ar;
+
+                        Transform::new_m(Matrix4x4::new([
+                            [xf, 0.0, 0.0, 0.0],
+                            [0.0, yf, 0.0, 0.0],
+                            [0.0, 0.0, 2.0 / zf, -1.0],
+                            [0.0, 0.0, 0.0, 1.0],
+                        ]))
                     }
-                    FoV::Y(_) => {
-                        let ar = (film_settings.res.x as f32) / (film_settings.res.y as f32);
-                        (1.0 / (tan_half_fov * ar), 1.0 / tan_half_fov)
+                    CameraType::Environment => {
+                        unreachable!("Projected camera can't be environment")
                     }
-                };
-
-                Transform::new_m(Matrix4x4::new([
-                    [xf, 0.0, 0.0, 0.0],
-                    [0.0, yf, 0.0, 0.0],
-                    [
-                        0.0,
-                        0.0,
-                        (zf + zn) / (zf - zn),
-                        -(2.0 * zf * zn) / (zf - zn),
-                    ],
-                    [0.0, 0.0, 1.0, 0.0],
-                ]))
+                }
             };
 
             // The film is "upside down" at this point
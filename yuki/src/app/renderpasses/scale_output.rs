@@ -1,5 +1,7 @@
 use glium::Surface;
 
+use crate::math::Vec2;
+
 pub struct ScaleOutput {
     vertex_buffer: glium::VertexBuffer<Vertex>,
     index_buffer: glium::IndexBuffer<u16>,
@@ -61,26 +63,17 @@ impl ScaleOutput {
             .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear);
 
-        // Retain film aspect ratio, flip y since we have 0,0 at top left and gl at bottom left
+        // Flip y since we have 0,0 at top left and gl at bottom left
         let (width, height) = frame.get_dimensions();
-        let frame_aspect = (width as f32) / (height as f32);
-        let texture_aspect = (texture.width() as f32) / (texture.height() as f32);
-        let target_rect = if frame_aspect < texture_aspect {
-            let scaled_height = (width * texture.height()) / texture.width();
-            glium::BlitTarget {
-                left: 0,
-                bottom: (height.saturating_sub(scaled_height) / 2) + scaled_height,
-                width: i32::try_from(width).unwrap(),
-                height: -i32::try_from(scaled_height).unwrap(),
-            }
-        } else {
-            let scaled_width = (height * texture.width()) / texture.height();
-            glium::BlitTarget {
-                left: width.saturating_sub(scaled_width) / 2,
-                bottom: height,
-                width: i32::try_from(scaled_width).unwrap(),
-                height: -i32::try_from(height).unwrap(),
-            }
+        let (offset, size) = film_rect(
+            Vec2::new(width, height),
+            Vec2::new(texture.width(), texture.height()),
+        );
+        let target_rect = glium::BlitTarget {
+            left: offset.x,
+            bottom: height - offset.y,
+            width: i32::try_from(size.x).unwrap(),
+            height: -i32::try_from(size.y).unwrap(),
         };
 
         let left_ndc = (target_rect.left as f32) / (width as f32) * 2.0 - 1.0;
@@ -108,6 +101,28 @@ impl ScaleOutput {
     }
 }
 
+/// Returns the top left corner and size of the film in a window of `window_res` pixels.
+///
+/// The film retains its aspect ratio and is centered along the axis it doesn't fill.
+pub fn film_rect(window_res: Vec2<u32>, film_res: Vec2<u32>) -> (Vec2<u32>, Vec2<u32>) {
+    let window_aspect = (window_res.x as f32) / (window_res.y as f32);
+    let film_aspect = (film_res.x as f32) / (film_res.y as f32);
+    if window_aspect < film_aspect {
+        let scaled_height = (window_res.x * film_res.y) / film_res.x;
+        let bottom_margin = window_res.y.saturating_sub(scaled_height) / 2;
+        (
+            Vec2::new(0, window_res.y - bottom_margin - scaled_height),
+            Vec2::new(window_res.x, scaled_height),
+        )
+    } else {
+        let scaled_width = (window_res.y * film_res.x) / film_res.y;
+        (
+            Vec2::new(window_res.x.saturating_sub(scaled_width) / 2, 0),
+            Vec2::new(scaled_width, window_res.y),
+        )
+    }
+}
+
 #[derive(Copy, Clone)]
 struct Vertex {
     position: [f32; 2],
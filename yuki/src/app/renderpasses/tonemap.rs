@@ -20,6 +20,40 @@ impl Default for FilmicParams {
     }
 }
 
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct ReinhardParams {
+    /// Scale applied to the film before the curve, like [`FilmicParams::exposure`]
+    pub key: f32,
+    /// Smallest scaled luminance that maps to white
+    pub white_point: f32,
+}
+
+impl Default for ReinhardParams {
+    fn default() -> Self {
+        Self {
+            key: 1.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct ClippingParams {
+    /// Values above this after exposure compensation are marked as clipped
+    pub threshold: f32,
+    /// Luminance range of the film for display, `None` forces re-evaluation
+    pub range: Option<(f32, f32)>,
+}
+
+impl Default for ClippingParams {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            range: None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Deserialize, Serialize)]
 pub struct HeatmapParams {
     // No bounds forces re-evaluation of tight bounds
@@ -40,7 +74,10 @@ impl Default for HeatmapParams {
 pub enum ToneMapType {
     Raw,
     Filmic(FilmicParams),
+    Reinhard(ReinhardParams),
+    ACESFilmic(FilmicParams),
     Heatmap(HeatmapParams),
+    Clipping(ClippingParams),
 }
 
 #[allow(clippy::derivable_impls)] // Can't derive Default for non unit variants, which Filmic is
@@ -67,7 +104,7 @@ impl Default for HeatmapChannel {
 pub struct ToneMapFilm {
     vertex_buffer: glium::VertexBuffer<Vertex>,
     index_buffer: glium::IndexBuffer<u16>,
-    filmic_program: glium::Program,
+    tonemap_program: glium::Program,
     heatmap_program: glium::Program,
     input: glium::Texture2d,
     input_sample_counts: glium::texture::buffer_texture::BufferTexture<f32>,
@@ -103,7 +140,7 @@ impl ToneMapFilm {
         )
         .map_err(NewError::IndexBuffer)?;
 
-        let filmic_program = glium::Program::from_source(backend, VS_CODE, FILMIC_FS_CODE, None)
+        let tonemap_program = glium::Program::from_source(backend, VS_CODE, TONEMAP_FS_CODE, None)
             .map_err(NewError::Program)?;
 
         let heatmap_program = glium::Program::from_source(backend, VS_CODE, HEATMAP_FS_CODE, None)
@@ -133,7 +170,7 @@ impl ToneMapFilm {
         Ok(Self {
             vertex_buffer,
             index_buffer,
-            filmic_program,
+            tonemap_program,
             heatmap_program,
             input,
             input_sample_counts,
@@ -147,6 +184,7 @@ impl ToneMapFilm {
         backend: &T,
         film: &'b Mutex<Film>,
         params: &ToneMapType,
+        exposure_ev: f32,
     ) -> Result<&'a glium::Texture2d, DrawError<'b>> {
         yuki_trace!("draw: Checking for texture update");
         self.update_resources(backend, film)
@@ -159,27 +197,28 @@ impl ToneMapFilm {
             .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
 
+        // Raw output is left as is
+        let scale = exposure_scale(exposure_ev);
         let output = match params {
             ToneMapType::Raw => &self.input,
             ToneMapType::Filmic(FilmicParams { exposure }) => {
-                let uniforms = glium::uniform! {
-                    input_texture: input_sampler,
-                    input_sample_counts: &self.input_sample_counts,
-                    exposure: *exposure,
-                    tile_dim: self.tile_dim,
-                };
-
-                self.output
-                    .as_surface()
-                    .draw(
-                        &self.vertex_buffer,
-                        &self.index_buffer,
-                        &self.filmic_program,
-                        &uniforms,
-                        &glium::DrawParameters::default(),
-                    )
+                self.draw_curve(input_sampler, OPERATOR_FILMIC, scale * *exposure, 1.0)
+                    .map_err(DrawError::Draw)?;
+                &self.output
+            }
+            ToneMapType::Reinhard(ReinhardParams { key, white_point }) => {
+                self.draw_curve(input_sampler, OPERATOR_REINHARD, scale * *key, *white_point)
+                    .map_err(DrawError::Draw)?;
+                &self.output
+            }
+            ToneMapType::ACESFilmic(FilmicParams { exposure }) => {
+                self.draw_curve(input_sampler, OPERATOR_ACES_FILMIC, scale * *exposure, 1.0)
+                    .map_err(DrawError::Draw)?;
+                &self.output
+            }
+            ToneMapType::Clipping(ClippingParams { threshold, .. }) => {
+                self.draw_curve(input_sampler, OPERATOR_CLIPPING, scale, *threshold)
                     .map_err(DrawError::Draw)?;
-
                 &self.output
             }
             ToneMapType::Heatmap(HeatmapParams { bounds, channel }) => {
@@ -187,6 +226,7 @@ impl ToneMapFilm {
 
                 let uniforms = glium::uniform! {
                     input_texture: input_sampler,
+                    exposure_scale: scale,
                     min_val: min,
                     max_val: max,
                     channel: *channel as u32,
@@ -210,6 +250,33 @@ impl ToneMapFilm {
         Ok(output)
     }
 
+    /// Draws the tone curve selected by `operator` into the output texture.
+    /// `white_point` is the threshold for clipping.
+    fn draw_curve(
+        &self,
+        input_sampler: glium::uniforms::Sampler<glium::Texture2d>,
+        operator: u32,
+        exposure: f32,
+        white_point: f32,
+    ) -> Result<(), glium::DrawError> {
+        let uniforms = glium::uniform! {
+            input_texture: input_sampler,
+            input_sample_counts: &self.input_sample_counts,
+            operator: operator,
+            exposure: exposure,
+            white_point: white_point,
+            tile_dim: self.tile_dim,
+        };
+
+        self.output.as_surface().draw(
+            &self.vertex_buffer,
+            &self.index_buffer,
+            &self.tonemap_program,
+            &uniforms,
+            &glium::DrawParameters::default(),
+        )
+    }
+
     fn update_resources<'a, T: glium::backend::Facade>(
         &mut self,
         backend: &T,
@@ -301,6 +368,12 @@ impl<'a> glium::texture::Texture2dDataSource<'a> for &'a Film {
     }
 }
 
+// Match the branches in TONEMAP_FS_CODE
+const OPERATOR_FILMIC: u32 = 0;
+const OPERATOR_REINHARD: u32 = 1;
+const OPERATOR_ACES_FILMIC: u32 = 2;
+const OPERATOR_CLIPPING: u32 = 3;
+
 const VS_CODE: &str = r#"
 #version 410 core
 
@@ -315,12 +388,14 @@ void main() {
 }
 "#;
 
-const FILMIC_FS_CODE: &str = r#"
+const TONEMAP_FS_CODE: &str = r#"
 #version 410 core
 
 uniform sampler2D input_texture;
 uniform samplerBuffer input_sample_counts;
+uniform uint operator;
 uniform float exposure;
+uniform float white_point;
 uniform uint tile_dim;
 
 in vec2 frag_uv;
@@ -366,6 +441,37 @@ vec3 ACESFitted(vec3 color)
     return color;
 }
 
+// Krzysztof Narkowicz's fit of the ACES curve
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+vec3 ACESFilmic(vec3 x)
+{
+    return saturate((x * (2.51f * x + 0.03f)) / (x * (2.43f * x + 0.59f) + 0.14f));
+}
+
+// Extended Reinhard on luminance to keep hues
+// https://www-old.cs.utah.edu/docs/techreports/2002/pdf/UUCS-02-001.pdf
+vec3 Reinhard(vec3 color)
+{
+    float l = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    if (l <= 0)
+        return vec3(0);
+    float l_d = l * (1 + l / (white_point * white_point)) / (1 + l);
+    return saturate(color * (l_d / l));
+}
+
+const vec3 BELOW_COLOR = vec3(0, 0, 1);
+const vec3 ABOVE_COLOR = vec3(1, 0, 0);
+
+// Marks values outside [0, white_point], shows the rest as luminance
+vec3 Clipping(vec3 color)
+{
+    if (any(lessThan(color, vec3(0))))
+        return BELOW_COLOR;
+    if (any(greaterThan(color, vec3(white_point))))
+        return ABOVE_COLOR;
+    return vec3(dot(color, vec3(0.2126, 0.7152, 0.0722)) / white_point);
+}
+
 void main() {
     vec3 color = texture(input_texture, frag_uv).rgb;
 
@@ -379,7 +485,14 @@ void main() {
     if (sample_count > 0)
         color /= sample_count;
     color *= exposure;
-    color = ACESFitted(color);
+    if (operator == 1)
+        color = Reinhard(color);
+    else if (operator == 2)
+        color = ACESFilmic(color);
+    else if (operator == 3)
+        color = Clipping(color);
+    else
+        color = ACESFitted(color);
     output_color = vec4(color, 1.0f);
 }
 "#;
@@ -389,6 +502,7 @@ const HEATMAP_FS_CODE: &str = r#"
 
 uniform sampler2D input_texture;
 
+uniform float exposure_scale;
 uniform float min_val;
 uniform float max_val;
 uniform uint channel;
@@ -411,6 +525,7 @@ void main() {
         // Luminance
         value = dot(texture(input_texture, frag_uv).rgb, vec3(0.2126, 0.7152, 0.0722));
     }
+    value *= exposure_scale;
     float scaled_value = (value - min_val) / (max_val - min_val);
 
     // Linear gradient B->G->R
@@ -444,29 +559,257 @@ pub enum UpdateResourcesError<'a> {
     BufferTextureCreation(glium::texture::buffer_texture::CreationError),
 }
 
-pub fn find_min_max(film: &Mutex<Film>, channel: HeatmapChannel) -> Result<(f32, f32), DrawError> {
+/// Returns the linear scale for exposure compensation of `exposure_ev` stops.
+pub fn exposure_scale(exposure_ev: f32) -> f32 {
+    exposure_ev.exp2()
+}
+
+/// Finds the min and max of `channel` in `film` after the exposure compensation of
+/// `exposure_ev` stops.
+pub fn find_min_max(
+    film: &Mutex<Film>,
+    channel: HeatmapChannel,
+    exposure_ev: f32,
+) -> Result<(f32, f32), DrawError> {
     yuki_trace!("find_min_max: Waiting for lock on film");
     let film = film.lock().map_err(DrawError::FilmPoison)?;
     yuki_trace!("find_min_max: Acquired film");
 
+    let scale = exposure_scale(exposure_ev);
     let px_accessor: Box<dyn Fn(Spectrum<f32>) -> f32> = match &channel {
         HeatmapChannel::Red | HeatmapChannel::Green | HeatmapChannel::Blue => {
             Box::new(|px: Spectrum<f32>| px[channel as usize])
         }
-        HeatmapChannel::Luminance => {
-            Box::new(|px: Spectrum<f32>| 0.2126 * px.r + 0.7152 * px.g + 0.0722 * px.b)
-        }
+        HeatmapChannel::Luminance => Box::new(|px: Spectrum<f32>| px.luminance()),
     };
 
     // TODO: This is slow for large films. Do we care?
-    let ret = film
-        .pixels()
-        .iter()
-        .fold((f32::MAX, f32::MIN), |(min, max), &px| {
-            let v = px_accessor(px);
-            (min.min(v), max.max(v))
-        });
+    let ret = min_max(film.pixels().iter().map(|&px| px_accessor(px * scale)));
 
     yuki_trace!("find_min_max: Releasing film");
     Ok(ret)
 }
+
+fn min_max(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    values.fold((f32::MAX, f32::MIN), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+/// Luminance percentile that auto exposure maps to [`AUTO_EXPOSURE_TARGET`].
+const AUTO_EXPOSURE_PERCENTILE: f32 = 0.9;
+/// Target for the percentile luminance before the filmic curve, maps to ~0.8 in the output.
+const AUTO_EXPOSURE_TARGET: f32 = 1.0;
+const AUTO_EXPOSURE_BIN_COUNT: usize = 1024;
+
+/// Finds the [`FilmicParams`] exposure that balances the current contents of `film`.
+pub fn find_auto_exposure(film: &Mutex<Film>) -> Result<f32, DrawError> {
+    yuki_trace!("find_auto_exposure: Waiting for lock on film");
+    let film = film.lock().map_err(DrawError::FilmPoison)?;
+    yuki_trace!("find_auto_exposure: Acquired film");
+
+    let luminances = film.mean_luminances();
+
+    yuki_trace!("find_auto_exposure: Releasing film");
+    Ok(auto_exposure(&luminances))
+}
+
+/// Returns the exposure compensation in stops that scales the film by `auto_exposure` in total
+/// with the scale `tone_map` applies on its own.
+pub fn auto_exposure_ev(auto_exposure: f32, tone_map: &ToneMapType) -> f32 {
+    let tone_map_scale = match tone_map {
+        ToneMapType::Filmic(FilmicParams { exposure })
+        | ToneMapType::ACESFilmic(FilmicParams { exposure }) => *exposure,
+        ToneMapType::Reinhard(ReinhardParams { key, .. }) => *key,
+        ToneMapType::Raw | ToneMapType::Heatmap(_) | ToneMapType::Clipping(_) => 1.0,
+    };
+    (auto_exposure / tone_map_scale).log2()
+}
+
+/// Returns the exposure that maps the [`AUTO_EXPOSURE_PERCENTILE`] of `luminances` to
+/// [`AUTO_EXPOSURE_TARGET`].
+fn auto_exposure(luminances: &[f32]) -> f32 {
+    let (min, max) = min_max(luminances.iter().copied());
+    if luminances.is_empty() || max <= 0.0 {
+        return FilmicParams::default().exposure;
+    }
+
+    let bin_width = (max - min) / (AUTO_EXPOSURE_BIN_COUNT as f32);
+    let mut histogram = vec![0usize; AUTO_EXPOSURE_BIN_COUNT];
+    for &l in luminances {
+        let bin = if bin_width > 0.0 {
+            (((l - min) / bin_width) as usize).min(AUTO_EXPOSURE_BIN_COUNT - 1)
+        } else {
+            0
+        };
+        histogram[bin] += 1;
+    }
+
+    let target_count = AUTO_EXPOSURE_PERCENTILE * (luminances.len() as f32);
+    let mut cumulative_count = 0;
+    let mut percentile_luminance = max;
+    for (i, &count) in histogram.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let next_count = cumulative_count + count;
+        if (next_count as f32) >= target_count {
+            // Interpolate linearly within the bin
+            let t = (target_count - (cumulative_count as f32)) / (count as f32);
+            percentile_luminance = min + ((i as f32) + t) * bin_width;
+            break;
+        }
+        cumulative_count = next_count;
+    }
+
+    if percentile_luminance > 0.0 {
+        AUTO_EXPOSURE_TARGET / percentile_luminance
+    } else {
+        AUTO_EXPOSURE_TARGET / max
+    }
+}
+
+/// CPU version of `ACESFilmic` in [`TONEMAP_FS_CODE`] for a single channel.
+#[cfg(test)]
+fn aces_filmic(x: f32) -> f32 {
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+/// Classification of a film value against the clipping range
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+enum Clip {
+    Below,
+    Within,
+    Above,
+}
+
+/// CPU version of the classification in `Clipping` in [`TONEMAP_FS_CODE`].
+#[cfg(test)]
+fn classify_clipping(color: Spectrum<f32>, threshold: f32) -> Clip {
+    if color.r < 0.0 || color.g < 0.0 || color.b < 0.0 {
+        Clip::Below
+    } else if color.r > threshold || color.g > threshold || color.b > threshold {
+        Clip::Above
+    } else {
+        Clip::Within
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        aces_filmic, auto_exposure, auto_exposure_ev, classify_clipping, exposure_scale, min_max,
+        Clip, FilmicParams, ReinhardParams, ToneMapType, AUTO_EXPOSURE_PERCENTILE,
+        AUTO_EXPOSURE_TARGET,
+    };
+    use crate::math::Spectrum;
+
+    #[test]
+    fn clipping_boundaries() {
+        let gray = |v| Spectrum::new(v, v, v);
+        assert_eq!(classify_clipping(gray(-1e-3), 1.0), Clip::Below);
+        assert_eq!(classify_clipping(gray(0.0), 1.0), Clip::Within);
+        assert_eq!(classify_clipping(gray(1e-3), 1.0), Clip::Within);
+        assert_eq!(classify_clipping(gray(0.999), 1.0), Clip::Within);
+        assert_eq!(classify_clipping(gray(1.0), 1.0), Clip::Within);
+        assert_eq!(classify_clipping(gray(1.001), 1.0), Clip::Above);
+        // A single channel is enough
+        assert_eq!(
+            classify_clipping(Spectrum::new(0.5, 1.5, 0.5), 1.0),
+            Clip::Above
+        );
+        assert_eq!(
+            classify_clipping(Spectrum::new(0.5, -0.5, 0.5), 1.0),
+            Clip::Below
+        );
+        // Threshold is adjustable
+        assert_eq!(classify_clipping(gray(1.5), 2.0), Clip::Within);
+        assert_eq!(classify_clipping(gray(0.9), 0.8), Clip::Above);
+    }
+
+    #[test]
+    fn exposure_ev_scales_linearly() {
+        assert!((exposure_scale(0.0) - 1.0).abs() < f32::EPSILON);
+        for v in [0.0, 0.18, 1.0, 7.5] {
+            assert!((v * exposure_scale(1.0) - 2.0 * v).abs() < 1e-6);
+            assert!((v * exposure_scale(-1.0) - 0.5 * v).abs() < 1e-6);
+        }
+
+        // Bounds follow the compensated values
+        let values = [0.25, 1.0, 3.0];
+        let scale = exposure_scale(1.0);
+        let (min, max) = min_max(values.iter().map(|v| v * scale));
+        assert!((min - 0.5).abs() < 1e-6);
+        assert!((max - 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn aces_filmic_curve() {
+        assert!(aces_filmic(0.0).abs() < f32::EPSILON);
+        // Mid gray lands a bit lower, like in the reference curve
+        assert!((aces_filmic(0.18) - 0.2669).abs() < 1e-3);
+        assert!((aces_filmic(1.0) - 0.8038).abs() < 1e-3);
+        // Highlights roll off toward white
+        assert!((aces_filmic(10.0) - 1.0).abs() < 1e-2);
+        assert!((aces_filmic(100.0) - 1.0).abs() < f32::EPSILON);
+
+        let mut prev = 0.0;
+        for i in 1..=100 {
+            let v = aces_filmic((i as f32) * 0.1);
+            assert!(v >= prev);
+            prev = v;
+        }
+    }
+
+    #[test]
+    fn auto_exposure_hits_percentile() {
+        // Uniformly distributed luminances in [0, 2)
+        let count = 10000;
+        let luminances: Vec<f32> = (0..count)
+            .map(|i| 2.0 * (i as f32) / (count as f32))
+            .collect();
+
+        let exposure = auto_exposure(&luminances);
+
+        let percentile_luminance = 2.0 * AUTO_EXPOSURE_PERCENTILE;
+        assert!((percentile_luminance * exposure - AUTO_EXPOSURE_TARGET).abs() < 1e-2);
+
+        let mapped_below = luminances
+            .iter()
+            .filter(|&&l| l * exposure <= AUTO_EXPOSURE_TARGET)
+            .count();
+        let mapped_fraction = (mapped_below as f32) / (count as f32);
+        assert!((mapped_fraction - AUTO_EXPOSURE_PERCENTILE).abs() < 1e-2);
+    }
+
+    #[test]
+    fn auto_exposure_black_film() {
+        assert!((auto_exposure(&[0.0; 16]) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn auto_exposure_ev_is_tone_map_independent() {
+        let auto_exposure = 6.0;
+        for (tone_map, tone_map_scale) in [
+            (ToneMapType::Raw, 1.0),
+            (ToneMapType::Filmic(FilmicParams { exposure: 2.0 }), 2.0),
+            (ToneMapType::ACESFilmic(FilmicParams { exposure: 0.5 }), 0.5),
+            (
+                ToneMapType::Reinhard(ReinhardParams {
+                    key: 3.0,
+                    white_point: 4.0,
+                }),
+                3.0,
+            ),
+        ] {
+            let total_scale =
+                exposure_scale(auto_exposure_ev(auto_exposure, &tone_map)) * tone_map_scale;
+            assert!(
+                (total_scale - auto_exposure).abs() < 1e-5,
+                "{}",
+                total_scale
+            );
+        }
+    }
+}
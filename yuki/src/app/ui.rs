@@ -9,16 +9,25 @@ use std::{path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 use strum::VariantNames;
 use tinyfiledialogs::open_file_dialog;
 
-use super::renderpasses::{FilmicParams, HeatmapParams, ToneMapType};
+use super::{
+    renderpasses::{
+        ClippingParams, FilmicParams, HeatmapParams, RayVisualizationSettings, ReinhardParams,
+        ToneMapType,
+    },
+    NavigationSettings,
+};
 
 use crate::{
-    camera::{CameraParameters, FoV},
+    camera::{CameraParameters, CameraType, FoV},
     expect,
     film::FilmSettings,
-    integrators::{IntegratorType, PathParams, WhittedParams},
-    math::{Vec2, Vec3},
+    integrators::{
+        BDPTParams, DebugRecord, DirectLightingParams, IntegratorType, PathParams, SPPMParams,
+        WhittedParams,
+    },
+    math::{Bounds2, Point2, Vec2, Vec3},
     renderer::RenderSettings,
-    sampling::{SamplerType, StratifiedParams, UniformParams},
+    sampling::{BlueNoiseParams, HaltonParams, SamplerType, StratifiedParams, UniformParams},
     scene::{Scene, SceneLoadSettings},
 };
 
@@ -114,6 +123,9 @@ pub struct UIState {
     pub save_settings: bool,
     pub recompute_bvh_vis: bool,
     pub clear_bvh_vis: bool,
+    pub update_ray_vis: bool,
+    pub clear_ray_vis: bool,
+    pub auto_exposure: bool,
 }
 
 pub fn generate_ui(
@@ -122,11 +134,15 @@ pub fn generate_ui(
     film_settings: &mut FilmSettings,
     sampler: &mut SamplerType,
     camera_params: &mut CameraParameters,
+    navigation: &mut NavigationSettings,
     scene_integrator: &mut IntegratorType,
     tone_map_type: &mut ToneMapType,
+    exposure_ev: &mut f32,
     load_settings: &mut SceneLoadSettings,
     render_settings: &mut RenderSettings,
     bvh_visualization_level: Option<&mut i32>,
+    ray_visualization_settings: &mut RayVisualizationSettings,
+    debug_records: &[DebugRecord],
     scene: &Arc<Scene>,
     render_in_progress: bool,
     status_messages: &Option<Vec<String>>,
@@ -142,6 +158,9 @@ pub fn generate_ui(
     let mut save_settings = false;
     let mut recompute_bvh_vis = false;
     let mut clear_bvh_vis = false;
+    let mut update_ray_vis = false;
+    let mut clear_ray_vis = false;
+    let mut auto_exposure = false;
     // This should be collected for all windows
     let mut ui_hovered = false;
 
@@ -159,13 +178,14 @@ pub fn generate_ui(
             render_triggered |= generate_sampler_settings(ui, sampler);
             ui.spacing();
 
-            render_triggered |= generate_scene_settings(ui, scene, camera_params, load_settings);
+            render_triggered |=
+                generate_scene_settings(ui, scene, camera_params, navigation, load_settings);
             ui.spacing();
 
             render_triggered |= generate_integrator_settings(ui, scene_integrator);
             ui.spacing();
 
-            generate_tone_map_settings(ui, tone_map_type);
+            auto_exposure |= generate_tone_map_settings(ui, tone_map_type, exposure_ev);
             ui.spacing();
 
             generate_render_settings(ui, render_settings);
@@ -197,6 +217,12 @@ pub fn generate_ui(
             }
             ui.spacing();
 
+            let (update, clear) =
+                generate_ray_visualization_settings(ui, ray_visualization_settings, debug_records);
+            update_ray_vis |= update;
+            clear_ray_vis |= clear;
+            ui.spacing();
+
             if !render_in_progress {
                 if ui.button("Write raw EXR") {
                     write_exr = Some(WriteEXR::Raw);
@@ -216,6 +242,21 @@ pub fn generate_ui(
                 "Shapes in BVH node: {}",
                 (scene.load_settings.max_shapes_in_node as usize).min(scene.shapes.len())
             ));
+            // Only traverse the tree when the stats are visible
+            ui.tree_node_config("BVH statistics").build(|| {
+                let stats = scene.bvh.statistics();
+                ui.text(format!("Nodes: {}", stats.node_count));
+                ui.text(format!("Leaves: {}", stats.leaf_count));
+                ui.text(format!(
+                    "Leaf depth: {:.1} avg, {} max",
+                    stats.average_leaf_depth, stats.max_leaf_depth
+                ));
+                ui.text(format!(
+                    "Shapes per leaf: {:.2} avg",
+                    stats.average_shapes_per_leaf
+                ));
+                ui.text(format!("SAH cost: {:.2}", stats.sah_cost));
+            });
             ui.spacing();
 
             ui.separator();
@@ -238,6 +279,9 @@ pub fn generate_ui(
         save_settings,
         recompute_bvh_vis,
         clear_bvh_vis,
+        update_ray_vis,
+        clear_ray_vis,
+        auto_exposure,
     }
 }
 
@@ -320,7 +364,43 @@ fn generate_film_settings(ui: &imgui::Ui, film_settings: &mut FilmSettings) -> b
         }
 
         changed |= ui.checkbox("Accumulate", &mut film_settings.accumulate);
+        if film_settings.accumulate {
+            changed |= ui.checkbox("Adaptive sampling", &mut film_settings.adaptive);
+            if film_settings.adaptive {
+                let _width = ui.push_item_width(118.0);
+                changed |= imgui::Drag::new("Threshold##Adaptive")
+                    .range(0.001, 1.0)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .speed(0.001)
+                    .display_format("%.3f")
+                    .build(ui, &mut film_settings.adaptive_threshold);
+            }
+        }
         changed |= ui.checkbox("Low res", &mut film_settings.sixteenth_res);
+
+        let mut crop_enabled = film_settings.crop.is_some();
+        if ui.checkbox("Crop##Film", &mut crop_enabled) {
+            film_settings.crop = crop_enabled.then(|| {
+                Bounds2::new(
+                    Point2::zeros(),
+                    Point2::new(film_settings.res.x, film_settings.res.y),
+                )
+            });
+            changed = true;
+        }
+        if let Some(crop) = &mut film_settings.crop {
+            let mut min = Vec2::new(crop.p_min.x, crop.p_min.y);
+            let mut max = Vec2::new(crop.p_max.x, crop.p_max.y);
+            let min_changed = vec2_u16_picker(ui, "Crop min", &mut min, 0, MAX_RES, 1.0);
+            let max_changed = vec2_u16_picker(ui, "Crop max", &mut max, 0, MAX_RES, 1.0);
+            if min_changed || max_changed {
+                *crop = Bounds2::new(Point2::new(min.x, min.y), Point2::new(max.x, max.y));
+                changed = true;
+            }
+        }
+
+        let _width = ui.push_item_width(92.0);
+        changed |= enum_combo_box(ui, "Filter##Film", &mut film_settings.filter);
     });
 
     changed
@@ -335,7 +415,46 @@ fn generate_render_settings(ui: &imgui::Ui, render_settings: &mut RenderSettings
                 "Use single render thread",
                 &mut render_settings.use_single_render_thread,
             );
+            let _width = ui.push_item_width(92.0);
+            enum_combo_box(ui, "Priority##Renderer", &mut render_settings.priority);
+        });
+}
+
+/// Returns `(update, clear)` for the current debug rays.
+fn generate_ray_visualization_settings(
+    ui: &imgui::Ui,
+    settings: &mut RayVisualizationSettings,
+    records: &[DebugRecord],
+) -> (bool, bool) {
+    let mut update = false;
+    let mut clear = false;
+    ui.tree_node_config("Debug rays")
+        .default_open(false)
+        .build(|| {
+            let _width = ui.push_item_width(92.0);
+            u32_picker(
+                ui,
+                "Paths##DebugRays",
+                &mut settings.path_count,
+                1,
+                1024,
+                1.0,
+            );
+            update |= ui.checkbox("Direct##DebugRays", &mut settings.show_direct);
+            update |= ui.checkbox("Reflection##DebugRays", &mut settings.show_reflection);
+            update |= ui.checkbox("Refraction##DebugRays", &mut settings.show_refraction);
+            update |= ui.checkbox("Normals##DebugRays", &mut settings.show_normals);
+            update |= ui.checkbox("Shadow##DebugRays", &mut settings.show_shadow);
+            clear |= ui.button("Clear rays");
+            if !records.is_empty() {
+                ui.tree_node_config("Path events##DebugRays").build(|| {
+                    for record in records {
+                        ui.text(record.to_string());
+                    }
+                });
+            }
         });
+    (update, clear)
 }
 
 /// Returns `true` if `sampler` was changed.
@@ -346,7 +465,9 @@ fn generate_sampler_settings(ui: &imgui::Ui, sampler: &mut SamplerType) -> bool
 
         ui.indent();
         match sampler {
-            SamplerType::Uniform(UniformParams { pixel_samples }) => {
+            SamplerType::Uniform(UniformParams { pixel_samples })
+            | SamplerType::Halton(HaltonParams { pixel_samples })
+            | SamplerType::BlueNoise(BlueNoiseParams { pixel_samples }) => {
                 let _width = ui.push_item_width(118.0);
                 changed |= u32_picker(
                     ui,
@@ -361,6 +482,7 @@ fn generate_sampler_settings(ui: &imgui::Ui, sampler: &mut SamplerType) -> bool
                 pixel_samples,
                 symmetric_dimensions,
                 jitter_samples,
+                pixel_warp,
             }) => {
                 #[allow(clippy::cast_sign_loss)] // MAX_SAMPLES is u16
                 let max_dim = f64::from(MAX_SAMPLES).sqrt() as u16;
@@ -380,6 +502,8 @@ fn generate_sampler_settings(ui: &imgui::Ui, sampler: &mut SamplerType) -> bool
                 }
                 changed |= ui.checkbox("Symmetric dimensions", symmetric_dimensions);
                 changed |= ui.checkbox("Jitter samples", jitter_samples);
+                let _width = ui.push_item_width(118.0);
+                changed |= enum_combo_box(ui, "Pixel warp##Sampler", pixel_warp);
                 ui.text(format!(
                     "Samples per pixel: {}",
                     pixel_samples.x * pixel_samples.y
@@ -397,6 +521,7 @@ fn generate_scene_settings(
     ui: &imgui::Ui,
     scene: &Scene,
     camera_params: &mut CameraParameters,
+    navigation: &mut NavigationSettings,
     load_settings: &mut SceneLoadSettings,
 ) -> bool {
     let mut changed = false;
@@ -414,21 +539,63 @@ fn generate_scene_settings(
 
             {
                 let _width = ui.push_item_width(77.0);
-                let fov = match &mut camera_params.fov {
-                    FoV::X(ref mut v) | FoV::Y(ref mut v) => v,
-                };
-                changed |= imgui::Drag::new("Field of View")
-                    .range(0.1, 359.9)
-                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
-                    .speed(0.5)
-                    .display_format("%.1f")
-                    .build(ui, fov);
+                match &mut camera_params.camera_type {
+                    CameraType::Perspective { fov } => {
+                        let fov = match fov {
+                            FoV::X(ref mut v) | FoV::Y(ref mut v) => v,
+                        };
+                        changed |= imgui::Drag::new("Field of View")
+                            .range(0.1, 359.9)
+                            .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                            .speed(0.5)
+                            .display_format("%.1f")
+                            .build(ui, fov);
+                        changed |= imgui::Drag::new("Lens radius")
+                            .range(0.0, f32::MAX)
+                            .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                            .speed(0.001)
+                            .display_format("%.3f")
+                            .build(ui, &mut camera_params.lens_radius);
+                        changed |= imgui::Drag::new("Focal distance")
+                            .range(1e-3, f32::MAX)
+                            .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                            .speed(0.01)
+                            .display_format("%.2f")
+                            .build(ui, &mut camera_params.focal_distance);
+                    }
+                    CameraType::Orthographic { screen_width } => {
+                        changed |= imgui::Drag::new("Screen width")
+                            .range(1e-3, f32::MAX)
+                            .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                            .speed(0.01)
+                            .display_format("%.2f")
+                            .build(ui, screen_width);
+                    }
+                    CameraType::Environment => (),
+                }
             }
 
             if ui.button("Set +Y up") {
                 camera_params.up = Vec3::new(0.0, 1.0, 0.0);
                 changed = true;
             }
+
+            {
+                let _width = ui.push_item_width(77.0);
+                imgui::Drag::new("Move speed")
+                    .range(1e-3, f32::MAX)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .speed(0.01)
+                    .display_format("%.2f")
+                    .build(ui, &mut navigation.move_speed);
+                ui.same_line();
+                imgui::Drag::new("Look sensitivity")
+                    .range(1e-4, 0.1)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .speed(0.0001)
+                    .display_format("%.4f")
+                    .build(ui, &mut navigation.look_sensitivity);
+            }
         });
 
         ui.spacing();
@@ -453,7 +620,10 @@ fn generate_scene_settings(
             let path = open_file_dialog(
                 "Open scene",
                 open_path,
-                Some((&["*.ply", "*.xml", "*.pbrt"], "Supported scene formats")),
+                Some((
+                    &["*.ply", "*.obj", "*.gltf", "*.glb", "*.xml", "*.pbrt"],
+                    "Supported scene formats",
+                )),
             )
             .map_or_else(PathBuf::new, PathBuf::from);
             (*load_settings).path = path;
@@ -462,6 +632,22 @@ fn generate_scene_settings(
         if ui.button("Reload scene") {
             (*load_settings).path = scene.load_settings.path.clone();
         }
+        ui.same_line();
+        ui.checkbox("Auto-frame", &mut load_settings.auto_frame);
+
+        let mut smooth_ply = load_settings.ply_crease_angle.is_some();
+        if ui.checkbox("Smooth PLY normals", &mut smooth_ply) {
+            load_settings.ply_crease_angle = smooth_ply.then_some(60.0);
+        }
+        if let Some(crease_angle) = &mut load_settings.ply_crease_angle {
+            ui.same_line();
+            let _width = ui.push_item_width(77.0);
+            imgui::Drag::new("Crease angle")
+                .range(0.0, 180.0)
+                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                .display_format("%.0f")
+                .build(ui, crease_angle);
+        }
     });
 
     changed
@@ -477,17 +663,35 @@ fn generate_integrator_settings(ui: &imgui::Ui, integrator: &mut IntegratorType)
 
             ui.indent();
             match integrator {
-                IntegratorType::Whitted(WhittedParams { max_depth }) => {
+                IntegratorType::Whitted(WhittedParams {
+                    max_depth,
+                    background_intensity,
+                }) => {
                     let _width = ui.push_item_width(118.0);
 
                     changed |= imgui::Drag::new("Max depth##Integrator")
                         .range(1, u32::MAX)
                         .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                         .build(ui, max_depth);
+
+                    changed |= background_intensity_slider(ui, background_intensity);
+                }
+                IntegratorType::DirectLighting(DirectLightingParams {
+                    strategy,
+                    background_intensity,
+                }) => {
+                    let _width = ui.push_item_width(118.0);
+
+                    changed |= enum_combo_box(ui, "Strategy##Integrator", strategy);
+
+                    changed |= background_intensity_slider(ui, background_intensity);
                 }
                 IntegratorType::Path(PathParams {
                     max_depth,
+                    rr_start_depth,
                     indirect_clamp,
+                    background_intensity,
+                    transmissive_shadows,
                 }) => {
                     let _width = ui.push_item_width(118.0);
 
@@ -496,6 +700,13 @@ fn generate_integrator_settings(ui: &imgui::Ui, integrator: &mut IntegratorType)
                         .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                         .build(ui, max_depth);
 
+                    changed |= imgui::Drag::new("RR start depth##Integrator")
+                        .range(0, u32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .build(ui, rr_start_depth);
+
+                    changed |= background_intensity_slider(ui, background_intensity);
+
                     let mut clamp_active = indirect_clamp.is_some();
                     let clamp_changed =
                         ui.checkbox("Indirect clamp##Integrator", &mut clamp_active);
@@ -514,6 +725,50 @@ fn generate_integrator_settings(ui: &imgui::Ui, integrator: &mut IntegratorType)
                             .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                             .build(ui, c);
                     }
+
+                    changed |=
+                        ui.checkbox("Transmissive shadows##Integrator", transmissive_shadows);
+                }
+                IntegratorType::BDPT(BDPTParams {
+                    max_depth,
+                    background_intensity,
+                }) => {
+                    let _width = ui.push_item_width(118.0);
+
+                    changed |= imgui::Drag::new("Max depth##Integrator")
+                        .range(1, u32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .build(ui, max_depth);
+
+                    changed |= background_intensity_slider(ui, background_intensity);
+                }
+                IntegratorType::SPPM(SPPMParams {
+                    max_depth,
+                    photons_per_iteration,
+                    initial_radius,
+                    background_intensity,
+                }) => {
+                    let _width = ui.push_item_width(118.0);
+
+                    changed |= imgui::Drag::new("Max depth##Integrator")
+                        .range(1, u32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .build(ui, max_depth);
+
+                    changed |= imgui::Drag::new("Photons##Integrator")
+                        .range(1, u32::MAX)
+                        .speed(100.0)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .build(ui, photons_per_iteration);
+
+                    changed |= imgui::Drag::new("Initial radius##Integrator")
+                        .range(0.0001, 1.0)
+                        .speed(0.0001)
+                        .display_format("%.4f")
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .build(ui, initial_radius);
+
+                    changed |= background_intensity_slider(ui, background_intensity);
                 }
                 IntegratorType::BVHIntersections
                 | IntegratorType::GeometryNormals
@@ -526,15 +781,43 @@ fn generate_integrator_settings(ui: &imgui::Ui, integrator: &mut IntegratorType)
     changed
 }
 
-fn generate_tone_map_settings(ui: &imgui::Ui, params: &mut ToneMapType) {
+/// Returns `true` if auto exposure was requested.
+fn generate_tone_map_settings(
+    ui: &imgui::Ui,
+    params: &mut ToneMapType,
+    exposure_ev: &mut f32,
+) -> bool {
+    let mut auto_exposure = false;
     ui.tree_node_config("Tone map")
         .default_open(true)
         .build(|| {
             enum_combo_box(ui, "##ToneMapEnum", params);
             ui.indent();
+            if !matches!(params, ToneMapType::Raw) {
+                let _width = ui.push_item_width(118.0);
+                let ev_changed = imgui::Drag::new("EV compensation##ToneMap")
+                    .range(-10.0, 10.0)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .speed(0.05)
+                    .display_format("%.2f")
+                    .build(ui, exposure_ev);
+                ui.same_line();
+                auto_exposure |= ui.button("Auto##ToneMap");
+                // Heatmap and clipping ranges are found for the compensated film
+                match params {
+                    ToneMapType::Heatmap(HeatmapParams { bounds, .. })
+                    | ToneMapType::Clipping(ClippingParams { range: bounds, .. })
+                        if ev_changed =>
+                    {
+                        *bounds = None;
+                    }
+                    _ => (),
+                }
+            }
             match params {
                 ToneMapType::Raw => (),
-                ToneMapType::Filmic(FilmicParams { exposure }) => {
+                ToneMapType::Filmic(FilmicParams { exposure })
+                | ToneMapType::ACESFilmic(FilmicParams { exposure }) => {
                     let _width = ui.push_item_width(118.0);
                     imgui::Drag::new("Exposure##ToneMap")
                         .range(0.0, f32::MAX)
@@ -543,6 +826,33 @@ fn generate_tone_map_settings(ui: &imgui::Ui, params: &mut ToneMapType) {
                         .display_format("%.3f")
                         .build(ui, exposure);
                 }
+                ToneMapType::Reinhard(ReinhardParams { key, white_point }) => {
+                    let _width = ui.push_item_width(118.0);
+                    imgui::Drag::new("Key##ToneMap")
+                        .range(0.0, f32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .speed(0.001)
+                        .display_format("%.3f")
+                        .build(ui, key);
+                    imgui::Drag::new("White point##ToneMap")
+                        .range(1e-3, f32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .speed(0.01)
+                        .display_format("%.2f")
+                        .build(ui, white_point);
+                }
+                ToneMapType::Clipping(ClippingParams { threshold, range }) => {
+                    let _width = ui.push_item_width(118.0);
+                    imgui::Drag::new("Threshold##Clipping")
+                        .range(1e-3, f32::MAX)
+                        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                        .speed(0.01)
+                        .display_format("%.3f")
+                        .build(ui, threshold);
+                    if let Some((min, max)) = range {
+                        ui.text(format!("Luminance range: [{:.3}, {:.3}]", min, max));
+                    }
+                }
                 ToneMapType::Heatmap(HeatmapParams { bounds, channel }) => {
                     let changed = enum_combo_box(ui, "Channel##Heatmap", channel);
                     if changed {
@@ -572,6 +882,17 @@ fn generate_tone_map_settings(ui: &imgui::Ui, params: &mut ToneMapType) {
             }
             ui.unindent();
         });
+
+    auto_exposure
+}
+
+/// Returns `true` if `intensity` was changed.
+fn background_intensity_slider(ui: &imgui::Ui, intensity: &mut f32) -> bool {
+    imgui::Drag::new("Background intensity##Integrator")
+        .range(0.0, 100.0)
+        .speed(0.01)
+        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+        .build(ui, intensity)
 }
 
 // Generates a combo box for `value` and returns true if it changed.
@@ -1,7 +1,9 @@
 use chrono::{Datelike, Timelike};
 use std::{
+    fs::File,
+    io::BufWriter,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{atomic::AtomicBool, Arc},
 };
 
 use crate::{
@@ -12,13 +14,17 @@ use crate::{
     yuki_info,
 };
 
+/// Loads the scene in `settings`, the Cornell box if the path is empty.
+///
+/// Loading can be aborted by setting `cancel`.
 pub fn try_load_scene(
     settings: &SceneLoadSettings,
+    cancel: &AtomicBool,
 ) -> Result<(Arc<Scene>, CameraParameters, FilmSettings, f32), String> {
     if settings.path.exists() {
         match settings.path.extension() {
             Some(ext) => match ext.to_str().unwrap() {
-                "ply" => match Scene::ply(settings) {
+                "ply" => match Scene::ply(settings, cancel) {
                     Ok((scene, camera_params, film_settings, total_secs)) => {
                         yuki_info!(
                             "PLY loaded from {}",
@@ -28,7 +34,27 @@ pub fn try_load_scene(
                     }
                     Err(why) => Err(format!("Loading PLY failed: {}", why)),
                 },
-                "xml" => match Scene::mitsuba(settings) {
+                "obj" => match Scene::obj(settings, cancel) {
+                    Ok((scene, camera_params, film_settings, total_secs)) => {
+                        yuki_info!(
+                            "OBJ loaded from {}",
+                            settings.path.file_name().unwrap().to_str().unwrap()
+                        );
+                        Ok((Arc::new(scene), camera_params, film_settings, total_secs))
+                    }
+                    Err(why) => Err(format!("Loading OBJ failed: {}", why)),
+                },
+                "gltf" | "glb" => match Scene::gltf(settings, cancel) {
+                    Ok((scene, camera_params, film_settings, total_secs)) => {
+                        yuki_info!(
+                            "glTF loaded from {}",
+                            settings.path.file_name().unwrap().to_str().unwrap()
+                        );
+                        Ok((Arc::new(scene), camera_params, film_settings, total_secs))
+                    }
+                    Err(why) => Err(format!("Loading glTF failed: {}", why)),
+                },
+                "xml" => match Scene::mitsuba(settings, cancel) {
                     Ok((scene, camera_params, film_settings, total_secs)) => {
                         yuki_info!(
                             "Mitsuba 2.0 scene loaded from {}",
@@ -38,7 +64,7 @@ pub fn try_load_scene(
                     }
                     Err(why) => Err(format!("Loading Mitsuba 2.0 scene failed: {}", why)),
                 },
-                "pbrt" => match Scene::pbrt_v3(settings) {
+                "pbrt" => match Scene::pbrt_v3(settings, cancel) {
                     Ok((scene, camera_params, film_settings, total_secs)) => {
                         yuki_info!(
                             "PBRT v3 scene loaded from {}",
@@ -109,3 +135,116 @@ pub fn write_exr(
         )),
     }
 }
+
+pub fn write_single_channel_exr(
+    width: usize,
+    height: usize,
+    values: &[f32],
+    channel_name: &str,
+    path: &Path,
+) -> Result<(), String> {
+    yuki_info!("Writing out single channel EXR");
+    let channels = exr::prelude::SpecificChannels::build()
+        .with_channel(channel_name)
+        .with_pixel_fn(|pos: exr::prelude::Vec2<usize>| (values[pos.y() * width + pos.x()],));
+    match exr::prelude::Image::from_channels((width, height), channels)
+        .write()
+        .to_file(path)
+    {
+        Ok(_) => {
+            yuki_info!("EXR written to '{}'", path.to_string_lossy());
+            Ok(())
+        }
+        Err(why) => Err(format!(
+            "Error writing EXR to '{}': {:?}",
+            path.to_string_lossy(),
+            why
+        )),
+    }
+}
+
+/// Writes `pixels` as an 8-bit sRGB PNG, clamping values outside [0, 1].
+pub fn write_png(
+    width: usize,
+    height: usize,
+    pixels: &[Spectrum<f32>],
+    path: &Path,
+) -> Result<(), String> {
+    yuki_info!("Writing out PNG");
+    let img = image::RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let px = pixels[(y as usize) * width + (x as usize)];
+        image::Rgb([srgb_u8(px.r), srgb_u8(px.g), srgb_u8(px.b)])
+    });
+    match img.save_with_format(path, image::ImageFormat::Png) {
+        Ok(_) => {
+            yuki_info!("PNG written to '{}'", path.to_string_lossy());
+            Ok(())
+        }
+        Err(why) => Err(format!(
+            "Error writing PNG to '{}': {:?}",
+            path.to_string_lossy(),
+            why
+        )),
+    }
+}
+
+/// Writes `pixels` as a Radiance RGBE image.
+pub fn write_hdr(
+    width: usize,
+    height: usize,
+    pixels: &[Spectrum<f32>],
+    path: &Path,
+) -> Result<(), String> {
+    yuki_info!("Writing out HDR");
+    let file = match File::create(path) {
+        Ok(file) => file,
+        Err(why) => {
+            return Err(format!(
+                "Error creating HDR file '{}': {}",
+                path.to_string_lossy(),
+                why
+            ))
+        }
+    };
+    let rgb: Vec<image::Rgb<f32>> = pixels
+        .iter()
+        .map(|px| image::Rgb([px.r, px.g, px.b]))
+        .collect();
+    match image::codecs::hdr::HdrEncoder::new(BufWriter::new(file)).encode(&rgb, width, height) {
+        Ok(_) => {
+            yuki_info!("HDR written to '{}'", path.to_string_lossy());
+            Ok(())
+        }
+        Err(why) => Err(format!(
+            "Error writing HDR to '{}': {:?}",
+            path.to_string_lossy(),
+            why
+        )),
+    }
+}
+
+/// Encodes linear `v` into an 8-bit sRGB value.
+#[allow(clippy::cast_sign_loss)] // Clamped to positive
+fn srgb_u8(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let encoded = if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::srgb_u8;
+
+    #[test]
+    fn srgb_encode() {
+        assert_eq!(srgb_u8(-1.0), 0);
+        assert_eq!(srgb_u8(0.0), 0);
+        assert_eq!(srgb_u8(0.5), 188);
+        assert_eq!(srgb_u8(1.0), 255);
+        assert_eq!(srgb_u8(10.0), 255);
+    }
+}
@@ -18,24 +18,30 @@ use std::{
     borrow::Cow,
     fs::File,
     io::BufWriter,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
     time::Instant,
 };
 
 use super::{
     renderpasses::{
-        find_min_max, BvhVisualization, HeatmapParams, RayVisualization, ScaleOutput, ToneMapFilm,
+        auto_exposure_ev, find_auto_exposure, find_min_max, BvhVisualization, ClippingParams,
+        HeatmapChannel, HeatmapParams, RayVisualization, RayVisualizationSettings, ReinhardParams,
+        ScaleOutput, ToneMapFilm,
     },
     ui::{generate_ui, UIState, WriteEXR, UI},
     util::{exr_path, try_load_scene, write_exr},
-    InitialSettings, ToneMapType,
+    InitialSettings, NavigationSettings, ToneMapType,
 };
 use crate::{
-    camera::{Camera, CameraParameters, CameraSample, FoV},
+    camera::{Camera, CameraParameters, CameraSample, CameraType, FoV},
     expect,
     film::{film_or_new, Film, FilmSettings},
-    integrators::{IntegratorRay, IntegratorType},
-    math::{transforms::rotation, Point2, Spectrum, Vec2, Vec3},
+    integrators::{DebugRecord, IntegratorRay, IntegratorType},
+    math::{transforms::rotation, Bounds2, Point2, Spectrum, Vec2, Vec3},
     renderer::{RenderSettings, RenderStatus, Renderer},
     sampling::Sampler,
     sampling::SamplerType,
@@ -43,6 +49,14 @@ use crate::{
     yuki_debug, yuki_error, yuki_info, yuki_trace,
 };
 
+type SceneLoadResult = Result<(Arc<Scene>, CameraParameters, FilmSettings, f32), String>;
+
+/// Scene load running in a background thread
+struct SceneLoad {
+    cancel: Arc<AtomicBool>,
+    result: Receiver<SceneLoadResult>,
+}
+
 pub struct Window {
     // Window and GL context
     event_loop: EventLoop<()>,
@@ -60,15 +74,21 @@ pub struct Window {
 
     // Output
     tone_map_type: ToneMapType,
+    exposure_ev: f32,
     tone_map_film: ToneMapFilm,
     output_scaler: ScaleOutput,
     ray_visualization: RayVisualization,
+    ray_visualization_settings: RayVisualizationSettings,
+    /// Path events of the last debug ray
+    debug_records: Vec<DebugRecord>,
     bvh_visualization: BvhVisualization,
 
     // Scene
     load_settings: SceneLoadSettings,
+    scene_load: Option<SceneLoad>,
     scene: Arc<Scene>,
     camera_params: CameraParameters,
+    navigation: NavigationSettings,
 
     // Random main loop state
     quit: bool,
@@ -80,6 +100,8 @@ pub struct Window {
     status_messages: Option<Vec<String>>,
     cursor_state: CursorState,
     mouse_gesture: Option<MouseGesture>,
+    fly_keys: FlyKeys,
+    last_fly_update: Instant,
     camera_offset: Option<CameraOffset>,
     last_render_start: Instant,
     bvh_visualization_level: i32,
@@ -151,17 +173,26 @@ impl Window {
             "Failed to create BVH visualization render pass"
         );
 
-        let mut load_settings = settings.load_settings.unwrap_or_default();
+        let mut load_settings = settings.load_settings.clone().unwrap_or_default();
 
         // Init with cornell here so scene is loaded on first frame and ui gets load time through the normal logic
-        let (scene, camera_params, scene_film_settings, _) = match try_load_scene(&load_settings) {
-            Ok(result) => result,
-            Err(why) => {
-                yuki_error!("Scene loading failed: {}", why);
-                Scene::cornell()
-            }
-        };
+        let (scene, mut camera_params, scene_film_settings, _) =
+            match try_load_scene(&load_settings, &AtomicBool::new(false)) {
+                Ok(result) => result,
+                Err(why) => {
+                    yuki_error!("Scene loading failed: {}", why);
+                    Scene::cornell()
+                }
+            };
         load_settings.path.clear();
+        let film_settings = settings.film_settings.unwrap_or(scene_film_settings);
+        if load_settings.auto_frame {
+            camera_params.frame_bounds(scene.world_bound(), &film_settings);
+        }
+        // The saved camera is only valid in the scene it was saved with
+        if let Some(params) = settings.camera_params_for(&scene.load_settings.path) {
+            camera_params = params;
+        }
 
         Window {
             event_loop,
@@ -175,8 +206,10 @@ impl Window {
             tone_map_film,
             output_scaler,
             ray_visualization,
+            ray_visualization_settings: RayVisualizationSettings::default(),
+            debug_records: Vec::new(),
             bvh_visualization,
-            film_settings: settings.film_settings.unwrap_or(scene_film_settings),
+            film_settings,
             render_settings: settings.render_settings.unwrap_or_default(),
             scene_integrator: settings.scene_integrator.unwrap_or_default(),
             sampler: settings.sampler.unwrap_or_default(),
@@ -184,8 +217,11 @@ impl Window {
             film,
             scene,
             tone_map_type: settings.tone_map.unwrap_or_default(),
+            exposure_ev: settings.exposure_ev.unwrap_or(0.0),
             load_settings,
+            scene_load: None,
             camera_params,
+            navigation: settings.navigation.unwrap_or_default(),
             quit: false,
             last_frame: Instant::now(),
             render_triggered: false,
@@ -195,6 +231,8 @@ impl Window {
             status_messages: None,
             cursor_state: CursorState::default(),
             mouse_gesture: None,
+            fly_keys: FlyKeys::default(),
+            last_fly_update: Instant::now(),
             camera_offset: None,
             last_render_start: Instant::now(),
             bvh_visualization_level: -1i32,
@@ -214,8 +252,9 @@ impl Window {
             self.handle_events(&mut ui);
 
             if self.load_settings.path.exists() {
-                self.load_scene();
+                self.start_scene_load();
             }
+            self.poll_scene_load();
 
             superluminal_perf::begin_event("UI");
 
@@ -236,6 +275,7 @@ impl Window {
 
             self.handle_debug_ray();
 
+            self.handle_fly_movement();
             let active_camera_params = self.handle_camera_movement();
 
             self.handle_render(active_camera_params);
@@ -250,6 +290,7 @@ impl Window {
                 &self.display,
                 &self.film,
                 &mut self.tone_map_type,
+                self.exposure_ev,
                 &mut self.tone_map_film,
             );
 
@@ -325,6 +366,7 @@ impl Window {
         let render_triggered = &mut self.render_triggered;
         let cursor_state = &mut self.cursor_state;
         let mouse_gesture = &mut self.mouse_gesture;
+        let fly_keys = &mut self.fly_keys;
         let camera_offset = &mut self.camera_offset;
         let launch_debug_ray = &mut self.launch_debug_ray;
 
@@ -358,20 +400,23 @@ impl Window {
                         input:
                             KeyboardInput {
                                 virtual_keycode: Some(key),
-                                state: ElementState::Pressed,
+                                state,
                                 ..
                             },
                         ..
                     } => {
                         yuki_trace!("main_loop: KeyboardInput");
-                        if !self.any_item_active {
+                        if state == ElementState::Released {
+                            // Releases go through so keys don't get stuck when imgui grabs input
+                            fly_keys.set(key, false);
+                        } else if !self.any_item_active {
                             // We only want to handle keypresses if we're not interacting with imgui
                             match key {
                                 VirtualKeyCode::Escape => {
                                     *quit = true;
                                 }
                                 VirtualKeyCode::Return => *render_triggered = true,
-                                _ => {}
+                                _ => fly_keys.set(key, true),
                             }
                         }
                     }
@@ -419,8 +464,10 @@ impl Window {
             &mut self.film_settings,
             &mut self.sampler,
             &mut self.camera_params,
+            &mut self.navigation,
             &mut self.scene_integrator,
             &mut self.tone_map_type,
+            &mut self.exposure_ev,
             &mut self.load_settings,
             &mut self.render_settings,
             if self.bvh_visualization.bounds_set() {
@@ -428,6 +475,8 @@ impl Window {
             } else {
                 None
             },
+            &mut self.ray_visualization_settings,
+            &self.debug_records,
             &self.scene,
             self.renderer.is_active(),
             &self.status_messages,
@@ -453,8 +502,48 @@ impl Window {
             self.bvh_visualization.clear_bounds();
         }
 
+        if ui_state.update_ray_vis {
+            if let Err(why) = self
+                .ray_visualization
+                .update_buffers(&self.display, self.ray_visualization_settings)
+            {
+                yuki_error!("Updating ray visualization failed: {:?}", why);
+            };
+        }
+
+        if ui_state.clear_ray_vis {
+            self.ray_visualization.clear_rays();
+            self.debug_records.clear();
+        }
+
+        if ui_state.auto_exposure {
+            // Auto exposure sets the exposure compensation that is applied before any tone map
+            match find_auto_exposure(&self.film) {
+                Ok(auto_exposure) => {
+                    self.exposure_ev =
+                        auto_exposure_ev(auto_exposure, &self.tone_map_type).clamp(-10.0, 10.0);
+                }
+                Err(why) => yuki_error!("Finding auto exposure failed: {:?}", why),
+            }
+
+            match &mut self.tone_map_type {
+                ToneMapType::Reinhard(ReinhardParams { key, white_point }) => {
+                    // Brightest pixel maps to white
+                    match find_min_max(&self.film, HeatmapChannel::Luminance, self.exposure_ev) {
+                        Ok((_, max)) => *white_point = (max * *key).max(1e-3),
+                        Err(why) => yuki_error!("Finding film max failed: {:?}", why),
+                    }
+                }
+                // Ranges are found for the compensated film
+                ToneMapType::Heatmap(HeatmapParams { bounds, .. })
+                | ToneMapType::Clipping(ClippingParams { range: bounds, .. }) => *bounds = None,
+                ToneMapType::Raw | ToneMapType::Filmic(_) | ToneMapType::ACESFilmic(_) => (),
+            }
+        }
+
         self.render_triggered |= handle_mouse_gestures(
             self.display.gl_window().window().inner_size(),
+            self.navigation.look_sensitivity,
             &mut self.camera_params,
             &mut self.mouse_gesture,
             &mut self.camera_offset,
@@ -463,14 +552,60 @@ impl Window {
         ui_state
     }
 
-    fn load_scene(&mut self) {
+    /// Starts loading the scene in `load_settings` in the background, cancelling the previous
+    /// load if it is still running.
+    fn start_scene_load(&mut self) {
+        if let Some(SceneLoad { cancel, .. }) = self.scene_load.take() {
+            yuki_info!("Cancelling the previous scene load");
+            cancel.store(true, Ordering::Relaxed);
+        }
         self.renderer.kill();
-        match try_load_scene(&self.load_settings) {
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, result) = channel();
+        let load_settings = self.load_settings.clone();
+        {
+            let cancel = Arc::clone(&cancel);
+            expect!(
+                std::thread::Builder::new()
+                    .name("SceneLoader".into())
+                    .spawn(move || {
+                        // The window might have moved on to another load already
+                        drop(tx.send(try_load_scene(&load_settings, &cancel)));
+                    }),
+                "Failed to spawn SceneLoader"
+            );
+        }
+
+        self.scene_load = Some(SceneLoad { cancel, result });
+        self.status_messages = Some(vec!["Loading scene".into()]);
+        self.load_settings.path.clear();
+    }
+
+    /// Switches to the loaded scene if the background load has finished.
+    fn poll_scene_load(&mut self) {
+        let result = match &self.scene_load {
+            Some(SceneLoad { result, .. }) => match result.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("Scene loader exited early".into()),
+            },
+            None => return,
+        };
+        self.scene_load = None;
+
+        self.renderer.kill();
+        match result {
             Ok((new_scene, new_camera_params, new_film_settings, total_secs)) => {
                 self.scene = new_scene;
                 self.camera_params = new_camera_params;
                 self.film_settings = new_film_settings;
+                if self.load_settings.auto_frame {
+                    self.camera_params
+                        .frame_bounds(self.scene.world_bound(), &self.film_settings);
+                }
                 self.ray_visualization.clear_rays();
+                self.debug_records.clear();
                 self.bvh_visualization.clear_bounds();
                 self.status_messages = Some(vec![format!("Scene loaded in {:.2}s", total_secs)]);
             }
@@ -479,7 +614,6 @@ impl Window {
                 self.status_messages = Some(vec!["Scene loading failed".into()]);
             }
         }
-        self.load_settings.path.clear();
     }
 
     fn save_settings(&self) {
@@ -488,12 +622,18 @@ impl Window {
             sampler: Some(self.sampler),
             scene_integrator: Some(self.scene_integrator),
             tone_map: Some(self.tone_map_type),
+            exposure_ev: Some(self.exposure_ev),
             load_settings: Some(SceneLoadSettings {
                 path: self.scene.load_settings.path.clone(),
                 max_shapes_in_node: self.load_settings.max_shapes_in_node,
                 split_method: self.load_settings.split_method,
+                auto_frame: self.load_settings.auto_frame,
+                ply_crease_angle: self.load_settings.ply_crease_angle,
             }),
             render_settings: Some(self.render_settings),
+            navigation: Some(self.navigation),
+            camera_params: Some(self.camera_params),
+            camera_scene_path: Some(self.scene.load_settings.path.clone()),
         };
 
         match File::create("settings.yaml") {
@@ -509,6 +649,24 @@ impl Window {
         }
     }
 
+    fn handle_fly_movement(&mut self) {
+        let now = Instant::now();
+        let dt = (now - self.last_fly_update).as_secs_f32();
+        self.last_fly_update = now;
+
+        if let Some(dir) = self.fly_keys.direction(&self.camera_params) {
+            let offset = dir * (self.navigation.move_speed * dt);
+            self.camera_params.position += offset;
+            self.camera_params.target += offset;
+            self.render_triggered = true;
+        }
+    }
+
+    /// Returns `true` if the camera is being moved interactively.
+    fn camera_moving(&self) -> bool {
+        self.mouse_gesture.is_some() || self.fly_keys.any()
+    }
+
     fn handle_camera_movement(&mut self) -> CameraParameters {
         self.camera_offset
             .as_ref()
@@ -532,7 +690,7 @@ impl Window {
         if self.render_triggered {
             // Make sure we relaunch the render at full res after a mouse gesture ends
             let res_changed = self.film.lock().unwrap().res() != self.film_settings.res;
-            if (res_changed && self.mouse_gesture.is_none())
+            if (res_changed && !self.camera_moving())
                 || self.render_launch_timer.elapsed().as_millis() > 32
             {
                 self.trigger_render(active_camera_params);
@@ -553,7 +711,7 @@ impl Window {
         superluminal_perf::begin_event("Render triggered");
 
         yuki_debug!("main_loop: Render triggered");
-        let force_single_sample = self.mouse_gesture.is_some();
+        let force_single_sample = self.camera_moving();
 
         // Modify the settings before film_or_new to get proper film
         let mut film_settings = self.film_settings;
@@ -568,6 +726,13 @@ impl Window {
         }
         if film_settings.sixteenth_res {
             film_settings.res /= 4;
+            // Round outward to keep the full crop covered
+            film_settings.crop = film_settings.crop.map(|crop| {
+                Bounds2::new(
+                    Point2::new(crop.p_min.x / 4, crop.p_min.y / 4),
+                    Point2::new((crop.p_max.x + 3) / 4, (crop.p_max.y + 3) / 4),
+                )
+            });
         }
 
         // Make sure film matches settings
@@ -594,7 +759,7 @@ impl Window {
 
     fn handle_debug_ray(&mut self) {
         if self.launch_debug_ray {
-            if let Some(rays) = launch_debug_ray(
+            if let Some((rays, records)) = launch_debug_ray(
                 &self.cursor_state,
                 &self.display,
                 &self.film,
@@ -603,10 +768,16 @@ impl Window {
                 self.camera_params,
                 self.scene_integrator,
                 self.sampler,
+                self.ray_visualization_settings.path_count,
             ) {
-                if let Err(why) = self.ray_visualization.set_rays(&self.display, &rays) {
+                if let Err(why) = self.ray_visualization.set_rays(
+                    &self.display,
+                    rays,
+                    self.ray_visualization_settings,
+                ) {
                     yuki_error!("Setting rays to ray visualization failed: {:?}", why);
                 };
+                self.debug_records = records;
             }
 
             self.launch_debug_ray = false;
@@ -640,6 +811,56 @@ struct MouseGesture {
 enum MouseGestureType {
     TrackBall,
     TrackPlane,
+    Look,
+}
+
+/// Held keys for WASD movement, Q and E move down and up
+#[derive(Default)]
+struct FlyKeys {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+}
+
+impl FlyKeys {
+    /// Updates the state of `key` if it's a movement key.
+    fn set(&mut self, key: VirtualKeyCode, pressed: bool) {
+        match key {
+            VirtualKeyCode::W => self.forward = pressed,
+            VirtualKeyCode::S => self.back = pressed,
+            VirtualKeyCode::A => self.left = pressed,
+            VirtualKeyCode::D => self.right = pressed,
+            VirtualKeyCode::E => self.up = pressed,
+            VirtualKeyCode::Q => self.down = pressed,
+            _ => (),
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.forward || self.back || self.left || self.right || self.up || self.down
+    }
+
+    /// Returns the normalized movement direction in the look-at frame of `camera_params`,
+    /// `None` if the held keys cancel out.
+    fn direction(&self, camera_params: &CameraParameters) -> Option<Vec3<f32>> {
+        let axis = |positive: bool, negative: bool| match (positive, negative) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+
+        let fwd = (camera_params.target - camera_params.position).normalized();
+        let right = camera_params.up.cross(fwd).normalized();
+        let up = fwd.cross(right);
+
+        let dir = fwd * axis(self.forward, self.back)
+            + right * axis(self.right, self.left)
+            + up * axis(self.up, self.down);
+        (dir.len_sqr() > 0.0).then(|| dir.normalized())
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -655,7 +876,7 @@ impl CameraOffset {
             position: params.position + self.position,
             target: params.target + self.target,
             up: if self.flip_up { -params.up } else { params.up },
-            fov: params.fov,
+            ..params
         }
     }
 
@@ -678,6 +899,7 @@ impl Default for CameraOffset {
 
 fn handle_mouse_gestures(
     window_size: glutin::dpi::PhysicalSize<u32>,
+    look_sensitivity: f32,
     camera_params: &mut CameraParameters,
     mouse_gesture: &mut Option<MouseGesture>,
     camera_offset: &mut Option<CameraOffset>,
@@ -725,15 +947,22 @@ fn handle_mouse_gestures(
 
                     // TODO: Adjust for aspect ratio difference between film and window
                     let drag_scale = {
-                        match camera_params.fov {
-                            FoV::X(angle) => {
+                        match camera_params.camera_type {
+                            CameraType::Perspective { fov: FoV::X(angle) } => {
                                 let tan_half_fov = (angle.to_radians() * 0.5).tan();
                                 dist_target * tan_half_fov / ((window_size.width as f32) * 0.5)
                             }
-                            FoV::Y(angle) => {
+                            CameraType::Perspective { fov: FoV::Y(angle) } => {
                                 let tan_half_fov = (angle.to_radians() * 0.5).tan();
                                 dist_target * tan_half_fov / ((window_size.height as f32) * 0.5)
                             }
+                            CameraType::Orthographic { screen_width } => {
+                                screen_width / (window_size.width as f32)
+                            }
+                            CameraType::Environment => {
+                                // Full turn across the width
+                                dist_target * std::f32::consts::TAU / (window_size.width as f32)
+                            }
                         }
                     };
                     let drag = (*current_position - *start_position) * (drag_scale as f64);
@@ -749,6 +978,33 @@ fn handle_mouse_gestures(
                         ..CameraOffset::default()
                     });
 
+                    // We detect later if this is hovering in the same place as last time
+                    false
+                }
+                MouseGestureType::Look => {
+                    let drag = (*current_position - *start_position) * (look_sensitivity as f64);
+
+                    let to_target = camera_params.target - camera_params.position;
+                    let yawed_to_target = &rotation(drag.x as f32, camera_params.up) * to_target;
+
+                    let right = camera_params.up.cross(yawed_to_target).normalized();
+                    let pitched_to_target = &rotation(drag.y as f32, right) * yawed_to_target;
+
+                    // Stop short of the up axis to keep the look-at frame valid
+                    let cos_up = pitched_to_target
+                        .normalized()
+                        .dot(camera_params.up.normalized());
+                    let new_to_target = if cos_up.abs() < 0.99 {
+                        pitched_to_target
+                    } else {
+                        yawed_to_target
+                    };
+
+                    *camera_offset = Some(CameraOffset {
+                        target: new_to_target - to_target,
+                        ..CameraOffset::default()
+                    });
+
                     // We detect later if this is hovering in the same place as last time
                     false
                 }
@@ -818,7 +1074,8 @@ fn launch_debug_ray(
     camera_params: CameraParameters,
     scene_integrator: IntegratorType,
     sampler: SamplerType,
-) -> Option<Vec<IntegratorRay>> {
+    path_count: u32,
+) -> Option<(Vec<IntegratorRay>, Vec<DebugRecord>)> {
     let window_px = cursor_state.position;
     yuki_info!(
         "main_loop: Debug ray initiated at window px ({},{})",
@@ -826,51 +1083,23 @@ fn launch_debug_ray(
         window_px.y
     );
 
-    let (film_w, film_h) = {
+    let film_res = {
         yuki_trace!("get_film_res: Waiting for lock on film");
         let film = film.lock().unwrap();
         yuki_trace!("get_film_res: Acquired film");
 
-        let Vec2 { x, y } = film.res();
+        let res = film.res();
 
         yuki_trace!("get_film_res: Releasing film");
-        (x as f64, y as f64)
+        res
     };
-    let film_aspect = film_w / film_h;
 
-    let (window_w, window_h) = {
+    let window_res = {
         let glutin::dpi::PhysicalSize { width, height } = display.gl_window().window().inner_size();
-        (width as f64, height as f64)
-    };
-    let window_aspect = window_w / window_h;
-
-    let film_px = if window_aspect < film_aspect {
-        let x = film_w * (window_px.x / window_w);
-
-        let film_scale = window_w / film_w;
-        let bottom_margin = (window_h - film_h * film_scale) / 2.0;
-
-        let y = (window_px.y - bottom_margin) / film_scale;
-
-        Vec2::new(x, y)
-    } else {
-        let y = film_h * (window_px.y / window_h);
-
-        let film_scale = window_h / film_h;
-        let left_margin = (window_w - film_w * film_scale) / 2.0;
-
-        let x = (window_px.x - left_margin) / film_scale;
-
-        Vec2::new(x, y)
+        Vec2::new(width, height)
     };
 
-    let collected_rays = if film_px.min_comp() >= 0.0
-        && film_px.x < (film_w as f64)
-        && film_px.y < (film_h as f64)
-    {
-        #[allow(clippy::cast_sign_loss)] // We check above
-        let film_px = Vec2::new(film_px.x as u16, film_px.y as u16);
-
+    let collected_rays = if let Some(film_px) = window_to_film_px(window_px, window_res, film_res) {
         yuki_info!(
             "main_loop: Launching debug ray at film px ({},{})",
             film_px.x,
@@ -880,21 +1109,39 @@ fn launch_debug_ray(
         let camera = Camera::new(camera_params, film_settings);
 
         {
+            let pixel = Point2::new(film_px.x, film_px.y);
             let p_film = Point2::new(film_px.x as f32, film_px.y as f32);
 
             let integrator = scene_integrator.instantiate();
             let mut sampler: Box<dyn Sampler> = sampler.instantiate(false).as_ref().clone(); // The interface is a bit clunky outside the renderer
 
-            let ray = camera.ray(&CameraSample {
-                p_film: p_film + sampler.get_2d(),
-            });
-
             let mut alloc = LinearAllocator::new(1024 * 256);
             let scratch = ScopedScratch::new(&mut alloc);
 
             let mut rays = Vec::new();
-            integrator.li_debug(&scratch, ray, scene, 0, &mut sampler, &mut rays);
-            Some(rays)
+            let mut records = Vec::new();
+            for path_index in 0..path_count {
+                sampler.start_pixel_sample(pixel, path_index, 0);
+
+                let path_scratch = ScopedScratch::new_scope(&scratch);
+
+                let ray = camera.ray(&CameraSample {
+                    p_film: p_film + sampler.get_pixel_2d(),
+                    p_lens: sampler.get_2d(),
+                    time: sampler.get_1d(),
+                });
+
+                integrator.li_debug(
+                    &path_scratch,
+                    ray,
+                    scene,
+                    0,
+                    &mut sampler,
+                    &mut rays,
+                    &mut records,
+                );
+            }
+            Some((rays, records))
         }
     } else {
         yuki_info!("main_loop: Window px is outside the film");
@@ -904,6 +1151,25 @@ fn launch_debug_ray(
     collected_rays
 }
 
+/// Returns the film pixel under `window_px`, `None` if it's outside the film.
+///
+/// Inverts the fit of the film into the window done when drawing it.
+fn window_to_film_px(
+    window_px: Vec2<f64>,
+    window_res: Vec2<u32>,
+    film_res: Vec2<u16>,
+) -> Option<Vec2<u16>> {
+    let (offset, size) = film_rect(window_res, Vec2::new(film_res.x as u32, film_res.y as u32));
+    let x = (window_px.x - offset.x as f64) * (film_res.x as f64) / (size.x as f64);
+    let y = (window_px.y - offset.y as f64) * (film_res.y as f64) / (size.y as f64);
+    if x >= 0.0 && y >= 0.0 && x < film_res.x as f64 && y < film_res.y as f64 {
+        #[allow(clippy::cast_sign_loss)] // We check above
+        Some(Vec2::new(x as u16, y as u16))
+    } else {
+        None
+    }
+}
+
 fn render_status_messages(status: &RenderStatus, render_start: Instant) -> Vec<String> {
     let elapsed_s = render_start.elapsed().as_secs_f32();
 
@@ -995,15 +1261,19 @@ fn draw_tone_mapped<'a>(
     display: &glium::Display,
     film: &Arc<Mutex<Film>>,
     tone_map_type: &mut ToneMapType,
+    exposure_ev: f32,
     tone_map_film: &'a mut ToneMapFilm,
 ) -> &'a glium::Texture2d {
     superluminal_perf::begin_event("Draw::Tone map");
 
-    if let ToneMapType::Heatmap(HeatmapParams {
-        ref mut bounds,
-        channel,
-    }) = tone_map_type
-    {
+    let bounds_channel = match tone_map_type {
+        ToneMapType::Heatmap(HeatmapParams { bounds, channel }) => Some((bounds, *channel)),
+        ToneMapType::Clipping(ClippingParams { range, .. }) => {
+            Some((range, HeatmapChannel::Luminance))
+        }
+        _ => None,
+    };
+    if let Some((bounds, channel)) = bounds_channel {
         let film_dirty = {
             yuki_trace!("main_loop: Waiting for lock on film");
             let film = film.lock().unwrap();
@@ -1014,14 +1284,14 @@ fn draw_tone_mapped<'a>(
         };
         if bounds.is_none() || film_dirty {
             *bounds = Some(expect!(
-                find_min_max(film, *channel),
+                find_min_max(film, channel, exposure_ev),
                 "Failed to find film min, max"
             ));
         }
     }
 
     let tone_mapped_film = expect!(
-        tone_map_film.draw(display, film, tone_map_type),
+        tone_map_film.draw(display, film, tone_map_type, exposure_ev),
         "Film tone map pass failed"
     );
 
@@ -1128,7 +1398,13 @@ fn handle_mouse_input(
                 || (button == MouseButton::Left && cursor_state.state.alt()))
             && state == ElementState::Pressed
         {
-            if cursor_state.state.shift() {
+            if button == MouseButton::Right {
+                *mouse_gesture = Some(MouseGesture {
+                    start_position: cursor_state.position,
+                    current_position: cursor_state.position,
+                    gesture: MouseGestureType::Look,
+                });
+            } else if cursor_state.state.shift() {
                 *mouse_gesture = Some(MouseGesture {
                     start_position: cursor_state.position,
                     current_position: cursor_state.position,
@@ -1148,3 +1424,137 @@ fn handle_mouse_input(
         *mouse_gesture = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{window_to_film_px, FlyKeys};
+    use crate::{
+        camera::CameraParameters,
+        math::{Point3, Vec2, Vec3},
+    };
+
+    #[test]
+    fn window_px_maps_to_film_px() {
+        let film_res = Vec2::new(200, 100);
+        // (window res, window px, expected film px)
+        let cases = [
+            // Same aspect, scaled up
+            (
+                Vec2::new(400, 200),
+                Vec2::new(0.0, 0.0),
+                Some(Vec2::new(0, 0)),
+            ),
+            (
+                Vec2::new(400, 200),
+                Vec2::new(399.5, 199.5),
+                Some(Vec2::new(199, 99)),
+            ),
+            (
+                Vec2::new(400, 200),
+                Vec2::new(201.0, 51.0),
+                Some(Vec2::new(100, 25)),
+            ),
+            // Taller window letterboxes the film with 50px above and below
+            (
+                Vec2::new(200, 200),
+                Vec2::new(10.5, 50.5),
+                Some(Vec2::new(10, 0)),
+            ),
+            (
+                Vec2::new(200, 200),
+                Vec2::new(10.5, 149.5),
+                Some(Vec2::new(10, 99)),
+            ),
+            (Vec2::new(200, 200), Vec2::new(10.5, 49.5), None),
+            (Vec2::new(200, 200), Vec2::new(10.5, 150.5), None),
+            // Wider window pillarboxes the film with 100px on the sides
+            (
+                Vec2::new(600, 200),
+                Vec2::new(100.5, 0.5),
+                Some(Vec2::new(0, 0)),
+            ),
+            (
+                Vec2::new(600, 200),
+                Vec2::new(499.5, 199.5),
+                Some(Vec2::new(199, 99)),
+            ),
+            (
+                Vec2::new(600, 200),
+                Vec2::new(300.0, 100.0),
+                Some(Vec2::new(100, 50)),
+            ),
+            (Vec2::new(600, 200), Vec2::new(99.5, 100.0), None),
+            (Vec2::new(600, 200), Vec2::new(500.5, 100.0), None),
+            // Scaled down
+            (
+                Vec2::new(100, 100),
+                Vec2::new(50.0, 37.75),
+                Some(Vec2::new(100, 25)),
+            ),
+        ];
+        for (window_res, window_px, expected) in cases {
+            assert_eq!(
+                window_to_film_px(window_px, window_res, film_res),
+                expected,
+                "window {:?} px {:?}",
+                window_res,
+                window_px
+            );
+        }
+    }
+
+    #[test]
+    fn fly_keys_move_in_camera_frame() {
+        // Looking down world -x
+        let params = CameraParameters {
+            position: Point3::new(1.0, 0.0, 0.0),
+            target: Point3::new(0.0, 0.0, 0.0),
+            ..CameraParameters::default()
+        };
+        let direction = |keys: FlyKeys| keys.direction(&params);
+
+        assert!(direction(FlyKeys::default()).is_none());
+        let expected = [
+            (
+                FlyKeys {
+                    forward: true,
+                    ..FlyKeys::default()
+                },
+                Vec3::new(-1.0, 0.0, 0.0),
+            ),
+            (
+                FlyKeys {
+                    right: true,
+                    ..FlyKeys::default()
+                },
+                Vec3::new(0.0, 0.0, 1.0),
+            ),
+            (
+                FlyKeys {
+                    up: true,
+                    ..FlyKeys::default()
+                },
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+        ];
+        for (keys, d) in expected {
+            let dir = direction(keys).unwrap();
+            assert!((dir - d).len() < 1e-6, "{:?}", dir);
+        }
+
+        // Opposite keys cancel out
+        assert!(direction(FlyKeys {
+            forward: true,
+            back: true,
+            ..FlyKeys::default()
+        })
+        .is_none());
+        let diagonal = direction(FlyKeys {
+            forward: true,
+            left: true,
+            ..FlyKeys::default()
+        })
+        .unwrap();
+        assert!((diagonal.len() - 1.0).abs() < 1e-6);
+    }
+}
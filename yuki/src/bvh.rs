@@ -1,4 +1,5 @@
 use allocators::{LinearAllocator, ScopedScratch};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{collections::VecDeque, sync::Arc, time::Instant};
 use strum::{Display, EnumString, EnumVariantNames};
@@ -7,17 +8,30 @@ use crate::{
     lights::AreaLight,
     math::{Bounds3, Point3, Ray, Vec3},
     shapes::{Hit, Shape},
-    yuki_info,
+    yuki_info, yuki_warn,
 };
 
 // Based on Physically Based Rendering 3rd ed.
 // http://www.pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Bounding_Volume_Hierarchies.html
 
+/// Size of the on-stack traversal stack, deeper trees fall back to heap allocated stacks.
+const TRAVERSAL_STACK_SIZE: usize = 64;
+
+/// Morton code bits per axis in [`SplitMethod::HLBVH`]
+const MORTON_BITS: i32 = 10;
+/// Top Morton code bits that are shared by the shapes in a [`SplitMethod::HLBVH`] treelet
+const TREELET_MASK: u32 = 0b0011_1111_1111_1100_0000_0000_0000_0000;
+/// First Morton code bit that is split on inside a treelet
+const TREELET_FIRST_BIT: i32 = 3 * MORTON_BITS - 12 - 1;
+
 #[derive(Copy, Clone, Deserialize, Serialize, Display, EnumVariantNames, EnumString)]
 pub enum SplitMethod {
     SurfaceAreaHeuristic,
     Middle,
     EqualCounts,
+    /// Linear BVH treelets built in parallel from Morton codes, linked together with SAH
+    #[allow(clippy::upper_case_acronyms)]
+    HLBVH,
 }
 
 pub struct IntersectionResult<'a> {
@@ -26,11 +40,32 @@ pub struct IntersectionResult<'a> {
     pub intersection_count: usize,
 }
 
+/// Quality metrics of a built [`BoundingVolumeHierarchy`]
+#[derive(Clone, Copy, Debug)]
+pub struct BVHStatistics {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    /// Leaf depths count the nodes on the path from the root, including the leaf
+    pub max_leaf_depth: usize,
+    pub average_leaf_depth: f32,
+    pub average_shapes_per_leaf: f32,
+    /// Expected cost of a ray through the root using the costs in the SAH split
+    pub sah_cost: f32,
+}
+
 /// A standard BVH.
+///
+/// Serialization only stores the tree and the shape ordering, the [Shape]s are attached to a
+/// deserialized `BoundingVolumeHierarchy` with [`BoundingVolumeHierarchy::with_shapes`].
+#[derive(Deserialize, Serialize)]
 pub struct BoundingVolumeHierarchy {
     split_method: SplitMethod,
     max_shapes_in_node: usize,
     nodes: Vec<BVHNode>,
+    depth: usize,
+    /// Indices of the ordered shapes in the input shapes
+    shape_order: Vec<u32>,
+    #[serde(skip)]
     shapes: Arc<Vec<Arc<dyn Shape>>>,
 }
 
@@ -63,6 +98,8 @@ impl BoundingVolumeHierarchy {
             split_method,
             max_shapes_in_node,
             nodes: Vec::new(),
+            depth: 0,
+            shape_order: Vec::new(),
             shapes: Arc::new(shapes),
         };
 
@@ -78,13 +115,18 @@ impl BoundingVolumeHierarchy {
         let RecursiveBuildResult {
             root,
             nodes_in_tree,
-        } = ret.recursive_build(
-            &scratch,
-            &mut shape_info,
-            0,
-            ret.shapes.len(),
-            &mut ordered_shapes,
-        );
+            depth,
+        } = if let SplitMethod::HLBVH = split_method {
+            ret.hlbvh_build(&scratch, &shape_info, &mut ordered_shapes)
+        } else {
+            ret.recursive_build(
+                &scratch,
+                &mut shape_info,
+                0,
+                ret.shapes.len(),
+                &mut ordered_shapes,
+            )
+        };
 
         superluminal_perf::end_event(); // recursive build
 
@@ -93,6 +135,15 @@ impl BoundingVolumeHierarchy {
             build_start.elapsed().as_secs_f32()
         );
 
+        ret.depth = depth;
+        if depth > TRAVERSAL_STACK_SIZE {
+            yuki_warn!(
+                "BVH: Tree depth {} exceeds the default traversal stack size {}, traversal will use heap allocated stacks",
+                depth,
+                TRAVERSAL_STACK_SIZE
+            );
+        }
+
         std::mem::swap(Arc::get_mut(&mut ret.shapes).unwrap(), &mut ordered_shapes);
 
         let flatten_start = Instant::now();
@@ -114,6 +165,150 @@ impl BoundingVolumeHierarchy {
         (ret, shapes_arc)
     }
 
+    /// Attaches `shapes` to a deserialized `BoundingVolumeHierarchy`, reordering them like the
+    /// original build did. Also returns back the ordered `shapes` as an Arc.
+    ///
+    /// `shapes` should be in the order they were given to [`BoundingVolumeHierarchy::new`].
+    /// Returns [None] if they don't match the ones the tree was built for, which is checked
+    /// against the bounds of each leaf.
+    pub fn with_shapes(
+        mut self,
+        shapes: &[Arc<dyn Shape>],
+    ) -> Option<(Self, Arc<Vec<Arc<dyn Shape>>>)> {
+        if shapes.len() != self.shape_order.len() || self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut ordered_shapes = Vec::with_capacity(shapes.len());
+        let mut used = vec![false; shapes.len()];
+        for &i in &self.shape_order {
+            let i = i as usize;
+            if i >= shapes.len() || used[i] {
+                return None;
+            }
+            used[i] = true;
+            ordered_shapes.push(Arc::clone(&shapes[i]));
+        }
+
+        // Leaf bounds are the exact union of their shapes so any geometry that the tree no longer
+        // holds changes at least one of them. Interior bounds are unions of the leaves.
+        for node in &self.nodes {
+            if let NodeContent::Leaf {
+                first_shape_index,
+                shape_count,
+            } = node.content
+            {
+                let first = first_shape_index as usize;
+                let bounds = ordered_shapes
+                    .get(first..first + (shape_count as usize))?
+                    .iter()
+                    .fold(Bounds3::default(), |b, s| b.union_b(s.world_bound()));
+                if bounds != node.bounds {
+                    return None;
+                }
+            }
+        }
+
+        self.shapes = Arc::new(ordered_shapes);
+        let shapes_arc = Arc::clone(&self.shapes);
+        Some((self, shapes_arc))
+    }
+
+    /// Recomputes the node bounds for moved `shapes` without changing the tree topology. Also
+    /// returns back the ordered `shapes` as an Arc.
+    ///
+    /// `shapes` should be in the order they were given to [`BoundingVolumeHierarchy::new`]. The
+    /// quality of the tree degrades as the shapes move further from where it was built.
+    pub fn refit(&mut self, shapes: &[Arc<dyn Shape>]) -> Arc<Vec<Arc<dyn Shape>>> {
+        assert_eq!(
+            shapes.len(),
+            self.shape_order.len(),
+            "BVH: Refit shape count doesn't match the tree"
+        );
+
+        self.shapes = Arc::new(
+            self.shape_order
+                .iter()
+                .map(|&i| Arc::clone(&shapes[i as usize]))
+                .collect(),
+        );
+
+        // Children are always stored after their parent
+        for i in (0..self.nodes.len()).rev() {
+            self.nodes[i].bounds = match self.nodes[i].content {
+                NodeContent::Interior {
+                    second_child_index, ..
+                } => self.nodes[i + 1]
+                    .bounds
+                    .union_b(self.nodes[second_child_index as usize].bounds),
+                NodeContent::Leaf {
+                    first_shape_index,
+                    shape_count,
+                } => {
+                    let first = first_shape_index as usize;
+                    self.shapes[first..first + (shape_count as usize)]
+                        .iter()
+                        .fold(Bounds3::default(), |b, s| b.union_b(s.world_bound()))
+                }
+                NodeContent::Uninitialized => unreachable!(),
+            };
+        }
+
+        Arc::clone(&self.shapes)
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a leaf.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of nodes in the flattened tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Collects quality metrics of the tree.
+    pub fn statistics(&self) -> BVHStatistics {
+        let root_area = self.nodes[0].bounds.surface_area().max(1e-10);
+
+        let mut leaf_count = 0;
+        let mut max_leaf_depth = 0;
+        let mut leaf_depth_sum = 0;
+        let mut leaf_shape_sum = 0;
+        let mut sah_cost = 0.0;
+        let mut stack = vec![(0, 1)];
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.nodes[index];
+            let area_ratio = node.bounds.surface_area() / root_area;
+            match node.content {
+                NodeContent::Interior {
+                    second_child_index, ..
+                } => {
+                    sah_cost += area_ratio;
+                    stack.push((index + 1, depth + 1));
+                    stack.push((second_child_index as usize, depth + 1));
+                }
+                NodeContent::Leaf { shape_count, .. } => {
+                    sah_cost += area_ratio * (shape_count as f32);
+                    leaf_count += 1;
+                    max_leaf_depth = max_leaf_depth.max(depth);
+                    leaf_depth_sum += depth;
+                    leaf_shape_sum += shape_count as usize;
+                }
+                NodeContent::Uninitialized => unreachable!(),
+            }
+        }
+
+        BVHStatistics {
+            node_count: self.nodes.len(),
+            leaf_count,
+            max_leaf_depth,
+            average_leaf_depth: (leaf_depth_sum as f32) / (leaf_count as f32),
+            average_shapes_per_leaf: (leaf_shape_sum as f32) / (leaf_count as f32),
+            sah_cost,
+        }
+    }
+
     pub fn bounds(&self) -> Bounds3<f32> {
         self.nodes[0].bounds
     }
@@ -169,13 +364,15 @@ impl BoundingVolumeHierarchy {
         let mut current_node_index = 0;
         // to_visit_index points to the next index to access in to_visit_stack
         let mut to_visit_index = 0;
-        let mut to_visit_stack = [0; 64];
+        let mut inline_stack = [0; TRAVERSAL_STACK_SIZE];
+        let mut heap_stack = Vec::new();
+        let to_visit_stack = self.traversal_stack(&mut inline_stack, &mut heap_stack);
         loop {
             assert!(to_visit_index < to_visit_stack.len());
 
             let node = &self.nodes[current_node_index];
             intersection_test_count += 1;
-            if node.bounds.intersect(ray, inv_dir) {
+            if node.bounds.intersect_p(ray, inv_dir, dir_is_neg) {
                 intersection_count += 1;
                 match node.content {
                     NodeContent::Interior {
@@ -224,6 +421,12 @@ impl BoundingVolumeHierarchy {
                 current_node_index = to_visit_stack[to_visit_index];
             }
         }
+        if let Some(Hit { si, .. }) = &mut hit {
+            si.time = ray.time;
+            if let Some(rd) = &ray.differential {
+                si.compute_differentials(rd);
+            }
+        }
         IntersectionResult {
             hit,
             intersection_test_count,
@@ -235,21 +438,26 @@ impl BoundingVolumeHierarchy {
     pub fn any_intersect(&self, ray: Ray<f32>, area_light: Option<&dyn AreaLight>) -> bool {
         // Pre-calculated to speed up Bounds3 intersection tests
         let inv_dir = Vec3::new(1.0 / ray.d.x, 1.0 / ray.d.y, 1.0 / ray.d.z);
+        let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
 
         let mut current_node_index = 0;
         // to_visit_index points to the next index to access in to_visit_stack
         let mut to_visit_index = 0;
-        let mut to_visit_stack = [0; 64];
+        let mut inline_stack = [0; TRAVERSAL_STACK_SIZE];
+        let mut heap_stack = Vec::new();
+        let to_visit_stack = self.traversal_stack(&mut inline_stack, &mut heap_stack);
         loop {
+            assert!(to_visit_index < to_visit_stack.len());
+
             let node = &self.nodes[current_node_index];
-            if node.bounds.intersect(ray, inv_dir) {
+            if node.bounds.intersect_p(ray, inv_dir, dir_is_neg) {
                 match node.content {
                     NodeContent::Interior {
                         second_child_index,
                         split_axis,
                     } => {
                         // Traverse children front to back
-                        if inv_dir[split_axis as usize] < 0.0 {
+                        if dir_is_neg[split_axis as usize] {
                             to_visit_stack[to_visit_index] = current_node_index + 1;
                             to_visit_index += 1;
                             current_node_index = second_child_index as usize;
@@ -301,6 +509,20 @@ impl BoundingVolumeHierarchy {
         false
     }
 
+    /// Returns a traversal stack that fits this tree, only allocating if the tree is deep.
+    fn traversal_stack<'a>(
+        &self,
+        inline_stack: &'a mut [usize; TRAVERSAL_STACK_SIZE],
+        heap_stack: &'a mut Vec<usize>,
+    ) -> &'a mut [usize] {
+        if self.depth <= TRAVERSAL_STACK_SIZE {
+            inline_stack
+        } else {
+            heap_stack.resize(self.depth, 0);
+            heap_stack
+        }
+    }
+
     /// Builds the node structure as a [BVHBuildNode]-tree.
     fn recursive_build<'a>(
         &mut self,
@@ -319,6 +541,8 @@ impl BoundingVolumeHierarchy {
 
         macro_rules! init_leaf {
             () => {{
+                self.shape_order
+                    .extend(shape_info[start..end].iter().map(|s| s.shape_index as u32));
                 ordered_shapes.extend(
                     shape_info[start..end]
                         .iter()
@@ -327,6 +551,7 @@ impl BoundingVolumeHierarchy {
                 RecursiveBuildResult {
                     root: scratch.alloc(BVHBuildNode::leaf(first_shape_index, shape_count, bounds)),
                     nodes_in_tree: 1,
+                    depth: 1,
                 }
             }};
         }
@@ -340,11 +565,16 @@ impl BoundingVolumeHierarchy {
             let axis = centroid_bounds.maximum_extent();
 
             #[allow(clippy::float_cmp)] // We really do want the exact case
-            if centroid_bounds.p_max[axis] == centroid_bounds.p_min[axis] {
-                // No splitting method can help when bb is "zero"
-                init_leaf!()
+            let mid = if centroid_bounds.p_max[axis] == centroid_bounds.p_min[axis] {
+                // No splitting method can help when bb is "zero", but leaves can only
+                // hold so many shapes
+                if shape_count <= u16::MAX as usize {
+                    usize::MAX
+                } else {
+                    split_equal_counts(shape_info, start, end, axis)
+                }
             } else {
-                let mid = match self.split_method {
+                match self.split_method {
                     SplitMethod::SurfaceAreaHeuristic => {
                         let mid =
                             split_sah(shape_info, &bounds, &centroid_bounds, start, end, axis);
@@ -363,30 +593,122 @@ impl BoundingVolumeHierarchy {
                         }
                     }
                     SplitMethod::EqualCounts => split_equal_counts(shape_info, start, end, axis),
-                };
+                }
+            };
 
-                assert_ne!(mid, start, "BVH: Split failed");
+            assert_ne!(mid, start, "BVH: Split failed");
 
-                // TODO: Just use enum Split(mid)/Leaf/Failed here?
-                if mid == usize::MAX {
-                    init_leaf!()
-                } else {
-                    let RecursiveBuildResult {
-                        root: child0,
-                        nodes_in_tree: child0_node_count,
-                    } = self.recursive_build(scratch, shape_info, start, mid, ordered_shapes);
-                    let RecursiveBuildResult {
-                        root: child1,
-                        nodes_in_tree: child1_node_count,
-                    } = self.recursive_build(scratch, shape_info, mid, end, ordered_shapes);
-
-                    RecursiveBuildResult {
-                        root: scratch.alloc(BVHBuildNode::interior(axis, child0, child1)),
-                        nodes_in_tree: 1 + child0_node_count + child1_node_count,
-                    }
+            // TODO: Just use enum Split(mid)/Leaf/Failed here?
+            if mid == usize::MAX {
+                init_leaf!()
+            } else {
+                let RecursiveBuildResult {
+                    root: child0,
+                    nodes_in_tree: child0_node_count,
+                    depth: child0_depth,
+                } = self.recursive_build(scratch, shape_info, start, mid, ordered_shapes);
+                let RecursiveBuildResult {
+                    root: child1,
+                    nodes_in_tree: child1_node_count,
+                    depth: child1_depth,
+                } = self.recursive_build(scratch, shape_info, mid, end, ordered_shapes);
+
+                RecursiveBuildResult {
+                    root: scratch.alloc(BVHBuildNode::interior(axis, child0, child1)),
+                    nodes_in_tree: 1 + child0_node_count + child1_node_count,
+                    depth: 1 + child0_depth.max(child1_depth),
+                }
+            }
+        }
+    }
+
+    /// Builds the node structure as a [BVHBuildNode]-tree from treelets of shapes that are close
+    /// on a Z-order curve, linked together using SAH.
+    ///
+    /// Expects `shape_info` to be in the order of the input shapes.
+    fn hlbvh_build<'a>(
+        &mut self,
+        scratch: &'a ScopedScratch,
+        shape_info: &[BVHPrimitiveInfo],
+        ordered_shapes: &mut Vec<Arc<dyn Shape>>,
+    ) -> RecursiveBuildResult<'a> {
+        let centroid_bounds = shape_info
+            .iter()
+            .fold(Bounds3::default(), |b, s| b.union_p(s.centroid));
+
+        // Sort shapes along the Z-order curve
+        let mut morton_shapes: Vec<MortonShape> = shape_info
+            .par_iter()
+            .map(|s| {
+                let offset = centroid_bounds.offset(s.centroid);
+                let quantize = |v: f32| {
+                    let max = (1u32 << MORTON_BITS) - 1;
+                    #[allow(clippy::cast_sign_loss)] // Explicit max is used
+                    ((v * (max + 1) as f32).max(0.0) as u32).min(max)
+                };
+                MortonShape {
+                    shape_index: s.shape_index,
+                    code: encode_morton3(
+                        quantize(offset.x),
+                        quantize(offset.y),
+                        quantize(offset.z),
+                    ),
                 }
+            })
+            .collect();
+        morton_shapes.par_sort_unstable_by_key(|m| m.code);
+
+        // Leaves index directly into the sorted shapes
+        self.shape_order
+            .extend(morton_shapes.iter().map(|m| m.shape_index as u32));
+        ordered_shapes.extend(
+            morton_shapes
+                .iter()
+                .map(|m| self.shapes[m.shape_index].clone()),
+        );
+
+        // Build the treelets in parallel
+        let mut treelet_ranges = Vec::new();
+        let mut treelet_start = 0;
+        for end in 1..=morton_shapes.len() {
+            if end == morton_shapes.len()
+                || (morton_shapes[treelet_start].code & TREELET_MASK)
+                    != (morton_shapes[end].code & TREELET_MASK)
+            {
+                treelet_ranges.push((treelet_start, end));
+                treelet_start = end;
             }
         }
+        let max_shapes_in_node = self.max_shapes_in_node;
+        let treelets: Vec<TreeletNode> = treelet_ranges
+            .par_iter()
+            .map(|&(start, end)| {
+                emit_lbvh(
+                    &morton_shapes,
+                    shape_info,
+                    start,
+                    end,
+                    TREELET_FIRST_BIT,
+                    max_shapes_in_node,
+                )
+            })
+            .collect();
+
+        // Link the treelets together
+        let treelet_roots: Vec<RecursiveBuildResult> =
+            treelets.iter().map(|t| alloc_treelet(scratch, t)).collect();
+        let mut treelet_info: Vec<BVHPrimitiveInfo> = treelet_roots
+            .iter()
+            .enumerate()
+            .map(|(i, t)| BVHPrimitiveInfo {
+                shape_index: i,
+                bounds: t.root.bounds,
+                centroid: t.root.bounds.p_min + t.root.bounds.diagonal() * 0.5,
+            })
+            .collect();
+        let treelet_count = treelet_info.len();
+
+        build_upper_sah(scratch, &mut treelet_info, 0, treelet_count, &treelet_roots)
     }
 
     /// Converts the [BVHBuildNode]-tree into a linear array of [BVHNode]s.
@@ -522,9 +844,171 @@ fn split_sah(
     }
 }
 
+/// Builds a tree over treelets `treelet_info[start..end]` using SAH.
+fn build_upper_sah<'a>(
+    scratch: &'a ScopedScratch,
+    treelet_info: &mut [BVHPrimitiveInfo],
+    start: usize,
+    end: usize,
+    treelets: &[RecursiveBuildResult<'a>],
+) -> RecursiveBuildResult<'a> {
+    if end - start == 1 {
+        return treelets[treelet_info[start].shape_index];
+    }
+
+    let bounds = treelet_info[start..end]
+        .iter()
+        .fold(Bounds3::default(), |b, s| b.union_b(s.bounds));
+    let centroid_bounds = treelet_info[start..end]
+        .iter()
+        .fold(Bounds3::default(), |b, s| b.union_p(s.centroid));
+    let axis = centroid_bounds.maximum_extent();
+
+    // Treelets can't be leaves so fall back to equal counts whenever SAH doesn't split
+    #[allow(clippy::float_cmp)] // We really do want the exact case
+    let mid = if centroid_bounds.p_max[axis] == centroid_bounds.p_min[axis] {
+        split_equal_counts(treelet_info, start, end, axis)
+    } else {
+        let mid = split_sah(treelet_info, &bounds, &centroid_bounds, start, end, axis);
+        if mid != start && mid < end {
+            mid
+        } else {
+            split_equal_counts(treelet_info, start, end, axis)
+        }
+    };
+
+    let child0 = build_upper_sah(scratch, treelet_info, start, mid, treelets);
+    let child1 = build_upper_sah(scratch, treelet_info, mid, end, treelets);
+    RecursiveBuildResult {
+        root: scratch.alloc(BVHBuildNode::interior(axis, child0.root, child1.root)),
+        nodes_in_tree: 1 + child0.nodes_in_tree + child1.nodes_in_tree,
+        depth: 1 + child0.depth.max(child1.depth),
+    }
+}
+
+/// Builds a treelet over `morton_shapes[start..end]`, splitting on the Morton code bits from
+/// `bit` down. The codes in the range are expected to match above `bit`.
+fn emit_lbvh(
+    morton_shapes: &[MortonShape],
+    shape_info: &[BVHPrimitiveInfo],
+    start: usize,
+    end: usize,
+    bit: i32,
+    max_shapes_in_node: usize,
+) -> TreeletNode {
+    let shape_count = end - start;
+    let interior = |mid: usize, split_axis: usize, child_bit: i32| TreeletNode::Interior {
+        split_axis,
+        children: Box::new([
+            emit_lbvh(
+                morton_shapes,
+                shape_info,
+                start,
+                mid,
+                child_bit,
+                max_shapes_in_node,
+            ),
+            emit_lbvh(
+                morton_shapes,
+                shape_info,
+                mid,
+                end,
+                child_bit,
+                max_shapes_in_node,
+            ),
+        ]),
+    };
+
+    if shape_count > max_shapes_in_node {
+        if bit >= 0 {
+            // Codes are sorted and match above bit so the ones with it set are at the end
+            let mask = 1 << bit;
+            let mid = start + morton_shapes[start..end].partition_point(|m| m.code & mask == 0);
+            if mid == start || mid == end {
+                return emit_lbvh(
+                    morton_shapes,
+                    shape_info,
+                    start,
+                    end,
+                    bit - 1,
+                    max_shapes_in_node,
+                );
+            }
+            #[allow(clippy::cast_sign_loss)] // bit is positive
+            return interior(mid, (bit % 3) as usize, bit - 1);
+        } else if shape_count > u16::MAX as usize {
+            // Codes are identical but leaves can only hold so many shapes
+            return interior((start + end) / 2, 0, bit);
+        }
+    }
+
+    TreeletNode::Leaf {
+        first_shape_index: start,
+        shape_count,
+        bounds: morton_shapes[start..end]
+            .iter()
+            .fold(Bounds3::default(), |b, m| {
+                b.union_b(shape_info[m.shape_index].bounds)
+            }),
+    }
+}
+
+/// Moves a treelet into the scratch allocated [BVHBuildNode]-tree.
+fn alloc_treelet<'a>(scratch: &'a ScopedScratch, node: &TreeletNode) -> RecursiveBuildResult<'a> {
+    match node {
+        TreeletNode::Interior {
+            split_axis,
+            children,
+        } => {
+            let child0 = alloc_treelet(scratch, &children[0]);
+            let child1 = alloc_treelet(scratch, &children[1]);
+            RecursiveBuildResult {
+                root: scratch.alloc(BVHBuildNode::interior(
+                    *split_axis,
+                    child0.root,
+                    child1.root,
+                )),
+                nodes_in_tree: 1 + child0.nodes_in_tree + child1.nodes_in_tree,
+                depth: 1 + child0.depth.max(child1.depth),
+            }
+        }
+        TreeletNode::Leaf {
+            first_shape_index,
+            shape_count,
+            bounds,
+        } => RecursiveBuildResult {
+            root: scratch.alloc(BVHBuildNode::leaf(
+                *first_shape_index,
+                *shape_count,
+                *bounds,
+            )),
+            nodes_in_tree: 1,
+            depth: 1,
+        },
+    }
+}
+
+/// Interleaves the lowest [`MORTON_BITS`] bits of the coordinates into a Morton code with x in
+/// the lowest bit.
+fn encode_morton3(x: u32, y: u32, z: u32) -> u32 {
+    (left_shift3(z) << 2) | (left_shift3(y) << 1) | left_shift3(x)
+}
+
+/// Spreads the lowest [`MORTON_BITS`] bits of `v` so that there are two zeros between each.
+fn left_shift3(mut v: u32) -> u32 {
+    v = (v | (v << 16)) & 0b0000_0011_0000_0000_0000_0000_1111_1111;
+    v = (v | (v << 8)) & 0b0000_0011_0000_0000_1111_0000_0000_1111;
+    v = (v | (v << 4)) & 0b0000_0011_0000_1100_0011_0000_1100_0011;
+    v = (v | (v << 2)) & 0b0000_1001_0010_0100_1001_0010_0100_1001;
+    v
+}
+
+#[derive(Clone, Copy)]
 struct RecursiveBuildResult<'a> {
     root: &'a BVHBuildNode<'a>,
     nodes_in_tree: usize,
+    /// Number of nodes on the longest path from root to a leaf
+    depth: usize,
 }
 
 struct BVHPrimitiveInfo {
@@ -533,7 +1017,27 @@ struct BVHPrimitiveInfo {
     centroid: Point3<f32>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+struct MortonShape {
+    shape_index: usize,
+    code: u32,
+}
+
+/// Node of a [`SplitMethod::HLBVH`] treelet, these are built in parallel before moving them into
+/// the [BVHBuildNode]-tree
+enum TreeletNode {
+    Interior {
+        split_axis: usize,
+        children: Box<[TreeletNode; 2]>,
+    },
+    /// Indexes into the ordered shape array.
+    Leaf {
+        first_shape_index: usize,
+        shape_count: usize,
+        bounds: Bounds3<f32>,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 enum NodeContent {
     /// Indexes into the node array.
     Interior {
@@ -548,7 +1052,7 @@ enum NodeContent {
     Uninitialized,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Deserialize, Serialize)]
 struct BVHNode {
     bounds: Bounds3<f32>,
     content: NodeContent,
@@ -629,3 +1133,347 @@ impl<'a> BVHBuildNode<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{BoundingVolumeHierarchy, SplitMethod, TRAVERSAL_STACK_SIZE};
+    use crate::{
+        materials::{Material, Matte},
+        math::{
+            transforms::{scale, translation},
+            Bounds3, Point3, Ray, Spectrum, Transform, Vec3,
+        },
+        shapes::{Mesh, Shape, Sphere, Triangle},
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn deep_tree_hits() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+
+        // Exponentially spaced spheres make middle splits peel off one sphere per level
+        let sphere_count = 100;
+        let placement = |i: i32| (2.0f32.powi(i), 2.0f32.powi(i - 2));
+        let shapes: Vec<Arc<dyn Shape>> = (0..sphere_count)
+            .map(|i| {
+                let (center, radius) = placement(i);
+                let object_to_world =
+                    &translation(Vec3::new(center, 0.0, 0.0)) * &scale(radius, radius, radius);
+                Arc::new(Sphere::new(&object_to_world, 1.0, Arc::clone(&material)))
+                    as Arc<dyn Shape>
+            })
+            .collect();
+
+        let (bvh, _) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+        assert!(bvh.depth() > TRAVERSAL_STACK_SIZE);
+
+        for i in 0..sphere_count {
+            let (center, radius) = placement(i);
+            // Scale the direction with the sphere to keep the math in range
+            let ray = Ray::new(
+                Point3::new(center, 2.0 * radius, 0.0),
+                Vec3::new(0.0, -radius, 0.0),
+                f32::INFINITY,
+            );
+            let hit = bvh.intersect(ray).hit;
+            assert!(hit.is_some(), "Missed sphere {}", i);
+            assert!((hit.unwrap().t - 1.0).abs() < 1e-3);
+            assert!(bvh.any_intersect(ray, None));
+        }
+    }
+
+    #[test]
+    fn cached_matches_built() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+
+        // Scattered spheres so that the build actually reorders them
+        let shapes: Vec<Arc<dyn Shape>> = (0..64)
+            .map(|i| {
+                let p = Vec3::new(
+                    ((i * 7) % 11) as f32,
+                    ((i * 5) % 13) as f32,
+                    ((i * 3) % 17) as f32,
+                );
+                let object_to_world = &translation(p) * &scale(0.4, 0.4, 0.4);
+                Arc::new(Sphere::new(&object_to_world, 1.0, Arc::clone(&material)))
+                    as Arc<dyn Shape>
+            })
+            .collect();
+
+        let (built, built_shapes) =
+            BoundingVolumeHierarchy::new(shapes.clone(), 2, SplitMethod::SurfaceAreaHeuristic);
+        let bytes = bincode::serialize(&built).unwrap();
+        let (cached, cached_shapes) = bincode::deserialize::<BoundingVolumeHierarchy>(&bytes)
+            .unwrap()
+            .with_shapes(&shapes)
+            .unwrap();
+
+        assert_eq!(built.node_count(), cached.node_count());
+        assert_eq!(built.depth(), cached.depth());
+        for (b, c) in built_shapes.iter().zip(cached_shapes.iter()) {
+            assert!(Arc::ptr_eq(b, c));
+        }
+
+        for i in 0..256 {
+            let o = Point3::new(-5.0, (i % 16) as f32 - 1.5, (i / 16) as f32 - 1.5);
+            let ray = Ray::new(o, Vec3::new(1.0, 0.1, 0.05).normalized(), f32::INFINITY);
+            let b = built.intersect(ray);
+            let c = cached.intersect(ray);
+            assert_eq!(b.intersection_test_count, c.intersection_test_count);
+            assert_eq!(b.intersection_count, c.intersection_count);
+            match (b.hit, c.hit) {
+                (Some(b), Some(c)) => {
+                    assert_eq!(b.t, c.t);
+                    assert_eq!(b.si.p, c.si.p);
+                }
+                (None, None) => (),
+                _ => panic!("Hits differ for ray {}", i),
+            }
+            assert_eq!(
+                built.any_intersect(ray, None),
+                cached.any_intersect(ray, None)
+            );
+        }
+
+        // A different set of shapes shouldn't get the cached tree
+        let cached_again = bincode::deserialize::<BoundingVolumeHierarchy>(&bytes).unwrap();
+        assert!(cached_again.with_shapes(&shapes[1..]).is_none());
+
+        // Neither should a shape that moved inside the scene bounds
+        let mut moved_shapes = shapes.clone();
+        moved_shapes[0] = Arc::new(Sphere::new(
+            &(&translation(Vec3::new(5.0, 6.0, 8.0)) * &scale(0.4, 0.4, 0.4)),
+            1.0,
+            Arc::clone(&material),
+        ));
+        let moved_bounds = moved_shapes
+            .iter()
+            .fold(Bounds3::default(), |b, s| b.union_b(s.world_bound()));
+        assert_eq!(moved_bounds, built.bounds());
+        let cached_again = bincode::deserialize::<BoundingVolumeHierarchy>(&bytes).unwrap();
+        assert!(cached_again.with_shapes(&moved_shapes).is_none());
+    }
+
+    #[test]
+    fn statistics_of_two_triangles() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 3, 4, 5],
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(2.0, 0.0, 0.0),
+                Point3::new(3.0, 0.0, 0.0),
+                Point3::new(2.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let shapes: Vec<Arc<dyn Shape>> = [0, 3]
+            .iter()
+            .map(|&v0| {
+                Arc::new(Triangle::new(
+                    Arc::clone(&mesh),
+                    v0,
+                    Arc::clone(&material),
+                    None,
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+
+        // Both triangles fit in the root
+        let (bvh, _) =
+            BoundingVolumeHierarchy::new(shapes.clone(), 2, SplitMethod::SurfaceAreaHeuristic);
+        let stats = bvh.statistics();
+        assert_eq!(stats.node_count, 1);
+        assert_eq!(stats.leaf_count, 1);
+        assert_eq!(stats.max_leaf_depth, 1);
+        assert_eq!(stats.average_leaf_depth, 1.0);
+        assert_eq!(stats.average_shapes_per_leaf, 2.0);
+        assert_eq!(stats.sah_cost, 2.0);
+
+        // One triangle per leaf under the root, both covering a third of the root's area
+        let (bvh, _) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::SurfaceAreaHeuristic);
+        let stats = bvh.statistics();
+        assert_eq!(stats.node_count, 3);
+        assert_eq!(stats.leaf_count, 2);
+        assert_eq!(stats.max_leaf_depth, 2);
+        assert_eq!(stats.average_leaf_depth, 2.0);
+        assert_eq!(stats.average_shapes_per_leaf, 1.0);
+        assert!((stats.sah_cost - 5.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn refit_moved_triangle() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        // Row of separate triangles along X
+        let points = |offset: Vec3<f32>| {
+            (0..8)
+                .flat_map(|i| {
+                    let x = 2.0 * (i as f32);
+                    let o = if i == 3 { offset } else { Vec3::zeros() };
+                    [
+                        Point3::new(x, 0.0, 0.0) + o,
+                        Point3::new(x + 1.0, 0.0, 0.0) + o,
+                        Point3::new(x, 1.0, 0.0) + o,
+                    ]
+                })
+                .collect()
+        };
+        let triangles = |points: Vec<Point3<f32>>| {
+            let mesh = Arc::new(Mesh::new(
+                &Transform::default(),
+                (0..points.len()).collect(),
+                points,
+                Vec::new(),
+                Vec::new(),
+            ));
+            (0..mesh.indices.len())
+                .step_by(3)
+                .map(|v0| {
+                    Arc::new(Triangle::new(
+                        Arc::clone(&mesh),
+                        v0,
+                        Arc::clone(&material),
+                        None,
+                    )) as Arc<dyn Shape>
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let shapes = triangles(points(Vec3::zeros()));
+        let (mut bvh, _) =
+            BoundingVolumeHierarchy::new(shapes.clone(), 1, SplitMethod::SurfaceAreaHeuristic);
+        let node_count = bvh.node_count();
+        let node_bounds = bvh.node_bounds(-1);
+
+        // Unmoved geometry shouldn't change anything
+        bvh.refit(&shapes);
+        assert_eq!(bvh.node_bounds(-1), node_bounds);
+
+        let offset = Vec3::new(0.0, 5.0, -3.0);
+        let moved_shapes = triangles(points(offset));
+        let ordered_shapes = bvh.refit(&moved_shapes);
+        assert_eq!(bvh.node_count(), node_count);
+        assert_eq!(ordered_shapes.len(), moved_shapes.len());
+
+        let moved_bounds = moved_shapes[3].world_bound();
+        let root_bounds = bvh.bounds();
+        assert_eq!(root_bounds.union_b(moved_bounds), root_bounds);
+
+        // The moved triangle should be found at its new position and not at the old one
+        let ray = |o: Point3<f32>| Ray::new(o, Vec3::new(0.0, 0.0, -1.0), f32::INFINITY);
+        let hit = bvh.intersect(ray(Point3::new(6.25, 5.25, 1.0))).hit;
+        assert!((hit.unwrap().t - 4.0).abs() < 1e-5);
+        assert!(bvh
+            .intersect(ray(Point3::new(6.25, 0.25, 1.0)))
+            .hit
+            .is_none());
+    }
+
+    #[test]
+    fn hlbvh_matches_sah() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+
+        // Triangulated height field with a few overlapping, scattered spheres on top
+        let n = 32;
+        let points = (0..=n)
+            .flat_map(|z| {
+                (0..=n).map(move |x| {
+                    let (x, z) = (x as f32, z as f32);
+                    Point3::new(x, (x * 0.7).sin() + (z * 0.3).cos(), z)
+                })
+            })
+            .collect();
+        let indices = (0..n)
+            .flat_map(|z| {
+                (0..n).flat_map(move |x| {
+                    let v = z * (n + 1) + x;
+                    [v, v + 1, v + n + 2, v, v + n + 2, v + n + 1]
+                })
+            })
+            .collect();
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            indices,
+            points,
+            Vec::new(),
+            Vec::new(),
+        ));
+        let mut shapes: Vec<Arc<dyn Shape>> = (0..mesh.indices.len())
+            .step_by(3)
+            .map(|v0| {
+                Arc::new(Triangle::new(
+                    Arc::clone(&mesh),
+                    v0,
+                    Arc::clone(&material),
+                    None,
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+        shapes.extend((0..64).map(|i| {
+            let p = Vec3::new(
+                ((i * 7) % 31) as f32,
+                2.0 + ((i * 5) % 3) as f32,
+                ((i * 3) % 29) as f32,
+            );
+            let object_to_world = &translation(p) * &scale(0.8, 0.8, 0.8);
+            Arc::new(Sphere::new(&object_to_world, 1.0, Arc::clone(&material))) as Arc<dyn Shape>
+        }));
+
+        let (sah, _) =
+            BoundingVolumeHierarchy::new(shapes.clone(), 4, SplitMethod::SurfaceAreaHeuristic);
+        let (hlbvh, _) = BoundingVolumeHierarchy::new(shapes, 4, SplitMethod::HLBVH);
+        assert_eq!(sah.bounds(), hlbvh.bounds());
+
+        let mut hit_count = 0;
+        for i in 0..1024 {
+            let o = Point3::new((i % 32) as f32 + 0.25, 10.0, (i / 32) as f32 + 0.5);
+            let d = Vec3::new(
+                0.3 * ((i % 7) as f32 - 3.0),
+                -4.0,
+                0.2 * ((i % 5) as f32 - 2.0),
+            );
+            let ray = Ray::new(o, d.normalized(), f32::INFINITY);
+            match (sah.intersect(ray).hit, hlbvh.intersect(ray).hit) {
+                (Some(s), Some(h)) => {
+                    assert!((s.t - h.t).abs() < 1e-4, "Distances differ for ray {}", i);
+                    hit_count += 1;
+                }
+                (None, None) => (),
+                _ => panic!("Hits differ for ray {}", i),
+            }
+            assert_eq!(sah.any_intersect(ray, None), hlbvh.any_intersect(ray, None));
+        }
+        assert!(hit_count > 512);
+    }
+}
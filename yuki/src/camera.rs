@@ -2,31 +2,49 @@ use crate::{
     film::FilmSettings,
     math::{
         transforms::{look_at, scale, translation},
-        Point2, Point3, Ray, Transform, Vec2, Vec3,
+        Bounds3, Point2, Point3, Ray, RayDifferential, Transform, Vec2, Vec3,
     },
+    sampling::concentric_sample_disk,
 };
 
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI};
+
 // Based on Physically Based Rendering 3rd ed.
 // http://www.pbr-book.org/3ed-2018/Camera_Models.html
 
 /// Values needed to specify a camera ray
 pub struct CameraSample {
     pub p_film: Point2<f32>,
+    pub p_lens: Point2<f32>,
+    /// In `[0,1)`, mapped to the time between shutter open and close
+    pub time: f32,
 }
 
-/// A simple pinhole camera
+/// A simple pinhole, thin lens, orthographic or environment camera
 #[derive(Clone)]
 pub struct Camera {
     camera_to_world: Transform<f32>,
     raster_to_camera: Transform<f32>,
+    camera_type: CameraType,
+    lens_radius: f32,
+    focal_distance: f32,
+    shutter_open: f32,
+    shutter_close: f32,
+    film_res: Vec2<u16>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct CameraParameters {
     pub position: Point3<f32>,
     pub target: Point3<f32>,
     pub up: Vec3<f32>,
-    pub fov: FoV,
+    pub camera_type: CameraType,
+    /// Pinhole if `0.0`
+    pub lens_radius: f32,
+    pub focal_distance: f32,
+    pub shutter_open: f32,
+    pub shutter_close: f32,
 }
 
 impl Default for CameraParameters {
@@ -35,56 +53,158 @@ impl Default for CameraParameters {
             position: Point3::new(0.0, 0.0, 0.0),
             target: Point3::new(0.0, 0.0, 0.0),
             up: Vec3::new(0.0, 1.0, 0.0),
-            fov: FoV::X(0.0),
+            camera_type: CameraType::Perspective { fov: FoV::X(0.0) },
+            lens_radius: 0.0,
+            focal_distance: 1e6,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+        }
+    }
+}
+
+impl CameraParameters {
+    /// Moves the camera along its view direction so that the bounding sphere of `bounds` fits
+    /// in view on a film with `film_settings`.
+    ///
+    /// Orthographic cameras also have their screen width fit to the sphere.
+    pub fn frame_bounds(&mut self, bounds: Bounds3<f32>, film_settings: &FilmSettings) {
+        let (center, radius) = match bounds.bounding_sphere() {
+            Some(sphere) => sphere,
+            None => return,
+        };
+
+        let dir = {
+            let d = self.target - self.position;
+            if d.len_sqr() > 0.0 {
+                d.normalized()
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            }
+        };
+
+        let film_x = film_settings.res.x as f32;
+        let film_y = film_settings.res.y as f32;
+        let distance = match &mut self.camera_type {
+            CameraType::Perspective { fov } => {
+                // The sphere has to fit in the narrower of the two view angles
+                let tan_half_fov = match *fov {
+                    FoV::X(angle) => (angle / 2.0).to_radians().tan() * (film_y / film_x).min(1.0),
+                    FoV::Y(angle) => (angle / 2.0).to_radians().tan() * (film_x / film_y).min(1.0),
+                };
+                radius / tan_half_fov.atan().sin()
+            }
+            CameraType::Orthographic { screen_width } => {
+                *screen_width = 2.0 * radius * (film_x / film_y).max(1.0);
+                // Rays start from the image plane so it has to be outside the sphere
+                2.0 * radius
+            }
+            // The whole scene is in view from any position
+            CameraType::Environment => return,
+        };
+
+        self.target = center;
+        self.position = center - dir * distance;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum CameraType {
+    Perspective {
+        fov: FoV,
+    },
+    /// `screen_width` is the horizontal extent of the view in camera space units
+    Orthographic {
+        screen_width: f32,
+    },
+    /// Equirectangular panorama of all directions around the camera
+    ///
+    /// Longitude spans the film width with the center looking down the view direction, latitude
+    /// spans the height with the poles on the top and bottom rows.
+    Environment,
+}
+
+impl CameraType {
+    /// Returns the closest `CameraType` that can be expressed as a projection matrix.
+    /// Environment cameras are approximated with a 90 degree horizontal field of view.
+    pub fn projected(self) -> Self {
+        match self {
+            CameraType::Environment => CameraType::Perspective { fov: FoV::X(90.0) },
+            camera_type => camera_type,
         }
     }
 }
 
 // Angle in degrees
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum FoV {
     X(f32),
     Y(f32),
 }
 
 impl Camera {
-    /// Creates a new `Camera`. `fov` is horizontal and in degrees.
+    /// Creates a new `Camera`.
     pub fn new(params: CameraParameters, film_settings: FilmSettings) -> Self {
         let camera_to_world = look_at(params.position, params.target, params.up).inverted();
-        // Standard perspective projection with aspect ratio
-        // Screen is
-        // NOTE: pbrt uses a 1:1 image plane with a cutout region
-        //       that could be nice for debugging purposes, though ui requires some thought
-        // We don't really care about near, far since we only use this to project rays
-        let near = 1e-2;
-        let far = 1000.0;
-        let fov_angle = match params.fov {
-            FoV::X(v) | FoV::Y(v) => v,
-        };
-        let inv_tan = 1.0 / ((fov_angle.to_radians() / 2.0).tan());
-        let camera_to_screen = &scale(inv_tan, inv_tan, 1.0)
-            * &Transform::new([
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0, 0.0],
-                [0.0, 0.0, far / (far - near), -(far * near) / (far - near)],
-                [0.0, 0.0, 1.0, 0.0],
-            ]);
-
-        // Screen window
-        // pbrt default is [-1,1] along the shorter axis and proportionally scaled on the other
-        // We adapt the mitsuba convention that has a directional fov by scaling that to 1
+
         let film_x = film_settings.res.x as f32;
         let film_y = film_settings.res.y as f32;
-        let (screen_min, screen_max) = match params.fov {
-            FoV::X(_) => {
-                let ar = film_x / film_y;
-                (Vec2::new(-1.0, -1.0 / ar), Vec2::new(1.0, 1.0 / ar))
+        let (camera_to_screen, screen_min, screen_max) = match params.camera_type {
+            CameraType::Perspective { fov } => {
+                // Standard perspective projection with aspect ratio
+                // Screen is
+                // NOTE: pbrt uses a 1:1 image plane with a cutout region
+                //       that could be nice for debugging purposes, though ui requires some thought
+                // We don't really care about near, far since we only use this to project rays
+                let near = 1e-2;
+                let far = 1000.0;
+                let fov_angle = match fov {
+                    FoV::X(v) | FoV::Y(v) => v,
+                };
+                let inv_tan = 1.0 / ((fov_angle.to_radians() / 2.0).tan());
+                let camera_to_screen = &scale(inv_tan, inv_tan, 1.0)
+                    * &Transform::new([
+                        [1.0, 0.0, 0.0, 0.0],
+                        [0.0, 1.0, 0.0, 0.0],
+                        [0.0, 0.0, far / (far - near), -(far * near) / (far - near)],
+                        [0.0, 0.0, 1.0, 0.0],
+                    ]);
+
+                // Screen window
+                // pbrt default is [-1,1] along the shorter axis and proportionally scaled on the other
+                // We adapt the mitsuba convention that has a directional fov by scaling that to 1
+                let (screen_min, screen_max) = match fov {
+                    FoV::X(_) => {
+                        let ar = film_x / film_y;
+                        (Vec2::new(-1.0, -1.0 / ar), Vec2::new(1.0, 1.0 / ar))
+                    }
+                    FoV::Y(_) => {
+                        let ar = film_y / film_x;
+                        (Vec2::new(-1.0 / ar, -1.0), Vec2::new(1.0 / ar, 1.0))
+                    }
+                };
+
+                (camera_to_screen, screen_min, screen_max)
+            }
+            CameraType::Orthographic { screen_width } => {
+                // Depth doesn't matter as rays start on the image plane
+                let half_width = screen_width / 2.0;
+                let half_height = half_width * film_y / film_x;
+                (
+                    Transform::default(),
+                    Vec2::new(-half_width, -half_height),
+                    Vec2::new(half_width, half_height),
+                )
             }
-            FoV::Y(_) => {
-                let ar = film_y / film_x;
-                (Vec2::new(-1.0 / ar, -1.0), Vec2::new(1.0 / ar, 1.0))
+            CameraType::Environment => {
+                // Screen holds the longitude and latitude in radians
+                (
+                    Transform::default(),
+                    Vec2::new(-PI, -FRAC_PI_2),
+                    Vec2::new(PI, FRAC_PI_2),
+                )
             }
         };
+
         let screen_to_raster = &scale(film_x, film_y, 1.0)
             * &(&scale(
                 1.0 / (screen_max.x - screen_min.x),
@@ -98,18 +218,309 @@ impl Camera {
         Self {
             camera_to_world,
             raster_to_camera,
+            camera_type: params.camera_type,
+            lens_radius: params.lens_radius,
+            focal_distance: params.focal_distance,
+            shutter_open: params.shutter_open,
+            shutter_close: params.shutter_close,
+            film_res: film_settings.res,
         }
     }
 
+    /// Returns the resolution of the film this `Camera` was created for.
+    pub fn film_res(&self) -> Vec2<u16> {
+        self.film_res
+    }
+
     /// Creates a new [Ray] at the camera sample with this `Camera`.
     pub fn ray(&self, sample: &CameraSample) -> Ray<f32> {
         let p_film = Point3::new(sample.p_film.x, sample.p_film.y, 0.0);
         let p_camera = &self.raster_to_camera * p_film;
-        let r = Ray::new(
-            Point3::zeros(),
-            Vec3::from(p_camera).normalized(),
-            f32::INFINITY,
-        );
+        let mut r = match self.camera_type {
+            CameraType::Perspective { .. } => Ray::new(
+                Point3::zeros(),
+                Vec3::from(p_camera).normalized(),
+                f32::INFINITY,
+            ),
+            CameraType::Orthographic { .. } => Ray::new(
+                Point3::new(p_camera.x, p_camera.y, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                f32::INFINITY,
+            ),
+            CameraType::Environment => {
+                let (sin_phi, cos_phi) = p_camera.x.sin_cos();
+                let (sin_lat, cos_lat) = p_camera.y.sin_cos();
+                Ray::new(
+                    Point3::zeros(),
+                    Vec3::new(cos_lat * sin_phi, sin_lat, cos_lat * cos_phi),
+                    f32::INFINITY,
+                )
+            }
+        };
+
+        // Panoramas have no lens to focus through
+        if self.lens_radius > 0.0 && !matches!(self.camera_type, CameraType::Environment) {
+            let p_lens = concentric_sample_disk(sample.p_lens) * self.lens_radius;
+
+            // All rays through the same film point meet on the plane of focus
+            let ft = self.focal_distance / r.d.z;
+            let p_focus = r.point(ft);
+
+            r.o = Point3::new(p_lens.x, p_lens.y, 0.0);
+            r.d = (p_focus - r.o).normalized();
+        }
+
+        r.time = self.shutter_open + (self.shutter_close - self.shutter_open) * sample.time;
+
         &self.camera_to_world * r
     }
+
+    /// Creates a new [Ray] at the camera sample with this `Camera`, including differentials for
+    /// the neighboring pixels.
+    pub fn ray_differential(&self, sample: &CameraSample) -> Ray<f32> {
+        // Offset rays share the lens sample so they focus like the main ray
+        let offset_ray = |offset: Vec2<f32>| {
+            self.ray(&CameraSample {
+                p_film: sample.p_film + offset,
+                p_lens: sample.p_lens,
+                time: sample.time,
+            })
+        };
+        let rx = offset_ray(Vec2::new(1.0, 0.0));
+        let ry = offset_ray(Vec2::new(0.0, 1.0));
+
+        Ray {
+            differential: Some(RayDifferential {
+                rx_o: rx.o,
+                rx_d: rx.d,
+                ry_o: ry.o,
+                ry_d: ry.d,
+            }),
+            ..self.ray(sample)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Camera, CameraParameters, CameraSample, CameraType, FoV};
+    use crate::{
+        film::FilmSettings,
+        math::{Point2, Point3, Vec3},
+        scene::Scene,
+    };
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = Camera::new(
+            CameraParameters {
+                position: Point3::new(0.0, 0.0, -5.0),
+                target: Point3::new(0.0, 0.0, 0.0),
+                camera_type: CameraType::Orthographic { screen_width: 4.0 },
+                ..CameraParameters::default()
+            },
+            FilmSettings::default(),
+        );
+
+        let r0 = camera.ray(&CameraSample {
+            p_film: Point2::new(0.0, 0.0),
+            p_lens: Point2::new(0.5, 0.5),
+            time: 0.0,
+        });
+        let r1 = camera.ray(&CameraSample {
+            p_film: Point2::new(640.0, 480.0),
+            p_lens: Point2::new(0.5, 0.5),
+            time: 0.0,
+        });
+
+        assert!((r0.d - r1.d).len() < 1e-6);
+        // Film diagonal is 4 by 3 units
+        assert!(((r1.o - r0.o).len() - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn thin_lens_rays_meet_on_focal_plane() {
+        let focal_distance = 3.0;
+        let camera = Camera::new(
+            CameraParameters {
+                position: Point3::new(0.0, 0.0, 0.0),
+                target: Point3::new(0.0, 0.0, 1.0),
+                camera_type: CameraType::Perspective { fov: FoV::X(60.0) },
+                lens_radius: 0.2,
+                focal_distance,
+                ..CameraParameters::default()
+            },
+            FilmSettings::default(),
+        );
+
+        let p_film = Point2::new(123.4, 321.0);
+        let lens_samples = [
+            Point2::new(0.5, 0.5),
+            Point2::new(0.1, 0.9),
+            Point2::new(0.8, 0.3),
+            Point2::new(0.95, 0.05),
+        ];
+        let hits: Vec<Point3<f32>> = lens_samples
+            .iter()
+            .map(|&p_lens| {
+                let r = camera.ray(&CameraSample {
+                    p_film,
+                    p_lens,
+                    time: 0.0,
+                });
+                // Camera looks down world +Z from the origin
+                r.point((focal_distance - r.o.z) / r.d.z)
+            })
+            .collect();
+
+        for p in &hits[1..] {
+            assert!((*p - hits[0]).len() < 1e-4);
+        }
+        // Distinct lens samples should start from distinct origins
+        let r0 = camera.ray(&CameraSample {
+            p_film,
+            p_lens: lens_samples[1],
+            time: 0.0,
+        });
+        let r1 = camera.ray(&CameraSample {
+            p_film,
+            p_lens: lens_samples[2],
+            time: 0.0,
+        });
+        assert!((r0.o - r1.o).len() > 1e-2);
+        assert!(r0.d.dot(Vec3::new(0.0, 0.0, 1.0)) > 0.0);
+    }
+
+    #[test]
+    fn environment_rays_cover_sphere() {
+        let camera = Camera::new(
+            CameraParameters {
+                target: Point3::new(0.0, 0.0, 1.0),
+                camera_type: CameraType::Environment,
+                ..CameraParameters::default()
+            },
+            FilmSettings::default(),
+        );
+
+        let film_x = f32::from(FilmSettings::default().res.x);
+        let film_y = f32::from(FilmSettings::default().res.y);
+        let dir = |x: f32, y: f32| {
+            camera
+                .ray(&CameraSample {
+                    p_film: Point2::new(x, y),
+                    p_lens: Point2::new(0.5, 0.5),
+                    time: 0.0,
+                })
+                .d
+        };
+        let expected = [
+            // Center looks down the view direction
+            (0.5, 0.5, Vec3::new(0.0, 0.0, 1.0)),
+            (0.25, 0.5, Vec3::new(-1.0, 0.0, 0.0)),
+            (0.75, 0.5, Vec3::new(1.0, 0.0, 0.0)),
+            // Horizontal edges meet behind the camera
+            (0.0, 0.5, Vec3::new(0.0, 0.0, -1.0)),
+            (1.0, 0.5, Vec3::new(0.0, 0.0, -1.0)),
+            // Poles cover the top and bottom rows
+            (0.0, 0.0, Vec3::new(0.0, 1.0, 0.0)),
+            (1.0, 0.0, Vec3::new(0.0, 1.0, 0.0)),
+            (0.0, 1.0, Vec3::new(0.0, -1.0, 0.0)),
+            (1.0, 1.0, Vec3::new(0.0, -1.0, 0.0)),
+        ];
+        for (u, v, d) in expected {
+            let r = dir(u * film_x, v * film_y);
+            assert!((r - d).len() < 1e-5, "({}, {}): {:?}", u, v, r);
+        }
+    }
+
+    #[test]
+    fn parameters_serde_round_trip() {
+        for camera_type in [
+            CameraType::Perspective { fov: FoV::Y(35.0) },
+            CameraType::Orthographic { screen_width: 2.5 },
+            CameraType::Environment,
+        ] {
+            let params = CameraParameters {
+                position: Point3::new(1.0, -2.0, 3.5),
+                target: Point3::new(0.25, 0.0, -1.0),
+                up: Vec3::new(0.0, 0.0, 1.0),
+                camera_type,
+                lens_radius: 0.05,
+                focal_distance: 4.0,
+                shutter_open: 0.1,
+                shutter_close: 0.9,
+            };
+            let yaml = serde_yaml::to_string(&params).unwrap();
+            let deserialized: CameraParameters = serde_yaml::from_str(&yaml).unwrap();
+            assert_eq!(deserialized, params);
+        }
+    }
+
+    #[test]
+    fn ray_time_spans_shutter() {
+        let camera = Camera::new(
+            CameraParameters {
+                target: Point3::new(0.0, 0.0, 1.0),
+                camera_type: CameraType::Perspective { fov: FoV::X(60.0) },
+                shutter_open: 0.5,
+                shutter_close: 1.5,
+                ..CameraParameters::default()
+            },
+            FilmSettings::default(),
+        );
+
+        let ray = |time| {
+            camera.ray(&CameraSample {
+                p_film: Point2::new(320.0, 240.0),
+                p_lens: Point2::new(0.5, 0.5),
+                time,
+            })
+        };
+        assert!((ray(0.0).time - 0.5).abs() < 1e-6);
+        assert!((ray(0.5).time - 1.0).abs() < 1e-6);
+        assert!((ray(0.99).time - 1.49).abs() < 1e-6);
+    }
+
+    #[test]
+    fn frame_bounds_fits_cornell_box() {
+        let (scene, mut params, film_settings, _) = Scene::cornell();
+        let bounds = scene.world_bound();
+        let extremes = [bounds.p_min, bounds.p_max];
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| {
+                Point3::new(
+                    extremes[i & 1].x,
+                    extremes[(i >> 1) & 1].y,
+                    extremes[i >> 2].z,
+                )
+            })
+            .collect();
+
+        // Start from a camera that looks past the box at an angle
+        params.position = Point3::new(-3.0, 2.0, 1.0);
+        params.target = Point3::new(-4.0, 2.5, 3.0);
+        for camera_type in [
+            CameraType::Perspective { fov: FoV::X(40.0) },
+            CameraType::Perspective { fov: FoV::Y(40.0) },
+            CameraType::Orthographic { screen_width: 0.1 },
+        ] {
+            params.camera_type = camera_type;
+            params.frame_bounds(bounds, &film_settings);
+
+            let camera = Camera::new(params, film_settings);
+            let world_to_raster =
+                &camera.raster_to_camera.inverted() * &camera.camera_to_world.inverted();
+            let world_to_camera = camera.camera_to_world.inverted();
+            for &p in &corners {
+                assert!((&world_to_camera * p).z > 0.0);
+                let p_raster = &world_to_raster * p;
+                assert!(
+                    (0.0..=f32::from(film_settings.res.x)).contains(&p_raster.x)
+                        && (0.0..=f32::from(film_settings.res.y)).contains(&p_raster.y),
+                    "{:?}",
+                    p_raster
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,303 @@
+use crate::{app::headless::NoiseOutput, integrators::IntegratorType, sampling::SamplerType};
+
+use std::{path::PathBuf, str::FromStr, time::Duration};
+use strum::VariantNames;
+
+pub const HELP: &str = "\
+Yuki
+USAGE:
+  yuki [OPTIONS]
+FLAGS:
+  -h, --help          Prints this help information
+  --stats-json        Writes render statistics next to the output as FILE.stats.json
+  --noise-aov         Writes per-pixel relative variance next to the output as FILE.noise.exr
+  --normalize-noise   Normalizes the noise AOV to [0, 1]
+  --aov               Writes first hit albedo and normals next to the output as FILE.albedo.exr
+                      and FILE.normal.exr
+OPTIONS:
+  Values can be given as --option=VALUE or --option VALUE
+  --out=FILE                    Path for output, format is picked by extension (exr, png, hdr)
+  --scene=FILE                  Scene to render instead of the one in settings, format is picked
+                                by extension (pbrt, xml, ply, obj, gltf, glb)
+  --width=PIXELS                Overrides the film width
+  --height=PIXELS               Overrides the film height
+  --integrator=NAME             Overrides the integrator, uses its default parameters
+                                (Whitted, DirectLighting, Path, BDPT, SPPM, BVHIntersections,
+                                 GeometryNormals, ShadingNormals, ShadingUVs)
+  --sampler=NAME                Overrides the sampler, uses its default parameters
+                                (Uniform, Stratified, Halton, BlueNoise)
+  --autosave-interval=SECONDS   Overwrites the output with the current progress at this interval
+  --progress-interval=SECONDS   Logs render progress at this interval instead of a single line
+  --spp=N                       Accumulates N samples per pixel, overriding the sampler settings
+  --time=SECONDS                Stops accumulating samples and writes the output after this time";
+
+/// Options parsed from the command line
+#[derive(Default)]
+pub struct Args {
+    pub help: bool,
+    pub out_path: Option<PathBuf>,
+    pub scene_path: Option<PathBuf>,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    pub integrator: Option<IntegratorType>,
+    pub sampler: Option<SamplerType>,
+    pub write_stats: bool,
+    pub autosave_interval: Option<Duration>,
+    pub progress_interval: Option<Duration>,
+    pub noise_output: Option<NoiseOutput>,
+    pub write_aovs: bool,
+    pub sample_budget: Option<u32>,
+    pub time_limit: Option<Duration>,
+}
+
+/// Options that take a value
+const VALUE_OPTIONS: &[&str] = &[
+    "--out",
+    "--scene",
+    "--width",
+    "--height",
+    "--integrator",
+    "--sampler",
+    "--autosave-interval",
+    "--progress-interval",
+    "--spp",
+    "--time",
+];
+
+/// Parses `args`, not including the executable name.
+///
+/// Returns all of the problems found if the arguments are invalid.
+pub fn parse<I>(args: I) -> Result<Args, Vec<String>>
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    let mut ret = Args::default();
+    let mut errors = Vec::new();
+    let mut noise_aov = false;
+    let mut normalize_noise = false;
+
+    let mut args = args.into_iter().map(Into::into);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => ret.help = true,
+            "--stats-json" => ret.write_stats = true,
+            "--noise-aov" => noise_aov = true,
+            "--normalize-noise" => normalize_noise = true,
+            "--aov" => ret.write_aovs = true,
+            _ => {
+                let (name, value) = match arg.split_once('=') {
+                    Some((name, value)) => (name.to_string(), value.to_string()),
+                    None if VALUE_OPTIONS.contains(&arg.as_str()) => match args.next() {
+                        Some(value) => (arg, value),
+                        None => {
+                            errors.push(format!("Expected a value for '{}'", arg));
+                            continue;
+                        }
+                    },
+                    None => {
+                        errors.push(format!("Unexpected option '{}'", arg));
+                        continue;
+                    }
+                };
+                if let Err(why) = parse_value(&mut ret, &name, &value) {
+                    errors.push(why);
+                }
+            }
+        }
+    }
+
+    ret.noise_output = match (noise_aov, normalize_noise) {
+        (true, true) => Some(NoiseOutput::Normalized),
+        (true, false) => Some(NoiseOutput::Raw),
+        (false, _) => None,
+    };
+
+    if ret.out_path.is_none() {
+        let headless_only = [
+            (ret.scene_path.is_some(), "--scene"),
+            (ret.write_stats, "--stats-json"),
+            (ret.autosave_interval.is_some(), "--autosave-interval"),
+            (ret.progress_interval.is_some(), "--progress-interval"),
+            (noise_aov, "--noise-aov"),
+            (ret.write_aovs, "--aov"),
+            (ret.sample_budget.is_some(), "--spp"),
+            (ret.time_limit.is_some(), "--time"),
+        ];
+        for (_, name) in headless_only.iter().filter(|(set, _)| *set) {
+            errors.push(format!("{} requires --out", name));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(ret)
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_value(args: &mut Args, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "--out" => args.out_path = Some(PathBuf::from(value)),
+        "--scene" => args.scene_path = Some(PathBuf::from(value)),
+        "--width" => args.width = Some(parse_resolution(value, "width")?),
+        "--height" => args.height = Some(parse_resolution(value, "height")?),
+        "--integrator" => args.integrator = Some(parse_variant(value, "integrator")?),
+        "--sampler" => args.sampler = Some(parse_variant(value, "sampler")?),
+        "--autosave-interval" => {
+            args.autosave_interval = Some(parse_seconds(value, "autosave interval")?);
+        }
+        "--progress-interval" => {
+            args.progress_interval = Some(parse_seconds(value, "progress interval")?);
+        }
+        "--spp" => {
+            // Accumulated sample indices are stored as u16
+            match value.parse::<u32>() {
+                Ok(n) if (1..=(u16::MAX as u32) + 1).contains(&n) => args.sample_budget = Some(n),
+                _ => return Err(format!("Invalid sample count '{}'", value)),
+            }
+        }
+        "--time" => args.time_limit = Some(parse_seconds(value, "time limit")?),
+        _ => return Err(format!("Unexpected option '{}'", name)),
+    }
+    Ok(())
+}
+
+fn parse_resolution(value: &str, what: &str) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(v) if v > 0 => Ok(v),
+        _ => Err(format!("Invalid {} '{}'", what, value)),
+    }
+}
+
+fn parse_seconds(value: &str, what: &str) -> Result<Duration, String> {
+    // Infinite and too large values don't fit a Duration
+    match value.parse::<f32>().map(Duration::try_from_secs_f32) {
+        Ok(Ok(d)) if !d.is_zero() => Ok(d),
+        _ => Err(format!("Invalid {} '{}'", what, value)),
+    }
+}
+
+fn parse_variant<T: FromStr + VariantNames>(value: &str, what: &str) -> Result<T, String> {
+    T::from_str(value).map_err(|_| {
+        format!(
+            "Unknown {} '{}', expected one of {}",
+            what,
+            value,
+            T::VARIANTS.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::parse;
+    use crate::{app::headless::NoiseOutput, integrators::IntegratorType, sampling::SamplerType};
+
+    #[test]
+    fn no_args_opens_window() {
+        let args = parse(Vec::<String>::new()).unwrap();
+        assert!(!args.help);
+        assert!(args.out_path.is_none());
+        assert!(args.integrator.is_none());
+    }
+
+    #[test]
+    fn single_out() {
+        let args = parse(["--out=render.exr"]).unwrap();
+        assert_eq!(args.out_path, Some(PathBuf::from("render.exr")));
+        assert!(args.scene_path.is_none());
+        assert!(args.sample_budget.is_none());
+    }
+
+    #[test]
+    fn headless_options() {
+        let args = parse([
+            "--out",
+            "render.png",
+            "--scene=scenes/box.pbrt",
+            "--spp=16",
+            "--width",
+            "320",
+            "--height=240",
+            "--integrator=GeometryNormals",
+            "--sampler",
+            "Halton",
+            "--noise-aov",
+            "--normalize-noise",
+            "--time=1.5",
+        ])
+        .unwrap();
+        assert_eq!(args.out_path, Some(PathBuf::from("render.png")));
+        assert_eq!(args.scene_path, Some(PathBuf::from("scenes/box.pbrt")));
+        assert_eq!(args.sample_budget, Some(16));
+        assert_eq!(args.width, Some(320));
+        assert_eq!(args.height, Some(240));
+        assert!(matches!(
+            args.integrator,
+            Some(IntegratorType::GeometryNormals)
+        ));
+        assert!(matches!(args.sampler, Some(SamplerType::Halton(_))));
+        assert!(matches!(args.noise_output, Some(NoiseOutput::Normalized)));
+        assert_eq!(args.time_limit, Some(Duration::from_secs_f32(1.5)));
+    }
+
+    #[test]
+    fn window_overrides() {
+        let args = parse(["--integrator=Path", "--width=64"]).unwrap();
+        assert!(matches!(args.integrator, Some(IntegratorType::Path(_))));
+        assert_eq!(args.width, Some(64));
+        assert!(args.out_path.is_none());
+    }
+
+    #[test]
+    fn help() {
+        assert!(parse(["-h"]).unwrap().help);
+        assert!(parse(["--help"]).unwrap().help);
+    }
+
+    #[test]
+    fn invalid_args() {
+        let errors = parse([
+            "--integrator=Magic",
+            "--width=0",
+            "--spp=many",
+            "--bogus",
+            "--scene=box.pbrt",
+            "--time",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].contains("Whitted, DirectLighting, Path"));
+        assert_eq!(errors[1], "Invalid width '0'");
+        assert_eq!(errors[2], "Invalid sample count 'many'");
+        assert_eq!(errors[3], "Unexpected option '--bogus'");
+        assert_eq!(errors[4], "Expected a value for '--time'");
+        assert_eq!(errors[5], "--scene requires --out");
+    }
+
+    #[test]
+    fn invalid_durations() {
+        let errors = parse([
+            "--out=a.exr",
+            "--time=inf",
+            "--autosave-interval=1e30",
+            "--progress-interval=-1",
+            "--time=0",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid time limit 'inf'",
+                "Invalid autosave interval '1e30'",
+                "Invalid progress interval '-1'",
+                "Invalid time limit '0'",
+            ]
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, VecDeque},
+    ops::MulAssign,
     sync::{Arc, Mutex},
 };
 
 use crate::{
-    math::{Bounds2, Point2, Spectrum, Vec2},
+    filters::FilterType,
+    math::{Bounds2, Point2, Spectrum, Vec2, Vec3},
     yuki_debug, yuki_error, yuki_trace, yuki_warn,
 };
 
@@ -23,6 +25,22 @@ pub struct FilmSettings {
     pub accumulate: bool,
     /// `true` if render should run in 1/16th res (both dimensions divided by 4)
     pub sixteenth_res: bool,
+    /// The pixel reconstruction filter.
+    #[serde(default)]
+    pub filter: FilterType,
+    /// `true` if first hit albedo and shading normal AOVs should be rendered.
+    #[serde(default)]
+    pub aovs: bool,
+    /// `true` if accumulated tiles should stop refining once they have converged.
+    #[serde(default)]
+    pub adaptive: bool,
+    /// The relative standard error under which every pixel of a tile has to be for the tile to be
+    /// considered converged.
+    #[serde(default = "default_adaptive_threshold")]
+    pub adaptive_threshold: f32,
+    /// Pixel bounds to limit rendering to, the full film if `None`.
+    #[serde(default)]
+    pub crop: Option<Bounds2<u16>>,
 }
 
 impl Default for FilmSettings {
@@ -34,10 +52,22 @@ impl Default for FilmSettings {
             clear: true,
             accumulate: false,
             sixteenth_res: false,
+            filter: FilterType::default(),
+            aovs: false,
+            adaptive: false,
+            adaptive_threshold: default_adaptive_threshold(),
+            crop: None,
         }
     }
 }
 
+fn default_adaptive_threshold() -> f32 {
+    0.02
+}
+
+/// Accumulated tiles are never considered converged before this many samples.
+const ADAPTIVE_MIN_SAMPLES: u32 = 8;
+
 /// A film tile used for rendering.
 #[derive(Debug, Clone)]
 pub struct FilmTile {
@@ -64,12 +94,45 @@ impl FilmTile {
     }
 }
 
+/// First hit albedo and shading normal buffers for external denoisers.
+#[derive(Clone)]
+pub struct AovBuffers {
+    pub albedo: Vec<Spectrum<f32>>,
+    pub normal: Vec<Vec3<f32>>,
+}
+
+impl AovBuffers {
+    /// Creates new zeroed `AovBuffers` for `pixel_count` pixels.
+    pub fn new(pixel_count: usize) -> Self {
+        Self {
+            albedo: vec![Spectrum::zeros(); pixel_count],
+            normal: vec![Vec3::zeros(); pixel_count],
+        }
+    }
+
+    /// Zeroes the first `pixel_count` pixels.
+    pub fn clear(&mut self, pixel_count: usize) {
+        self.albedo[..pixel_count].fill(Spectrum::zeros());
+        self.normal[..pixel_count].fill(Vec3::zeros());
+    }
+
+    /// Scales the first `pixel_count` pixels by `s`.
+    pub fn scale(&mut self, pixel_count: usize, s: f32) {
+        self.albedo[..pixel_count].iter_mut().for_each(|a| *a *= s);
+        self.normal[..pixel_count].iter_mut().for_each(|n| *n *= s);
+    }
+}
+
 /// Pixel wrapper for rendering through [FilmTile]s.
 pub struct Film {
     // Resolution of the stored pixel buffer.
     res: Vec2<u16>,
     // Pixel values.
     pixels: Vec<Spectrum<f32>>,
+    // Mean of squared sample luminances for each pixel. Summed like pixels when accumulating.
+    luminance_sqr: Vec<f32>,
+    // First hit AOVs for each pixel. Summed like pixels when accumulating.
+    aovs: Option<AovBuffers>,
     // Sample count for each tile.
     samples: Option<Vec<u32>>,
     // Indicator for changed pixel values.
@@ -84,6 +147,7 @@ pub struct Film {
 
 struct TileCache {
     dim: u16,
+    crop: Option<Bounds2<u16>>,
     tiles: VecDeque<FilmTile>,
 }
 
@@ -93,6 +157,8 @@ impl Film {
         Self {
             res,
             pixels: vec![Spectrum::zeros(); (res.x as usize) * (res.y as usize)],
+            luminance_sqr: vec![0.0; (res.x as usize) * (res.y as usize)],
+            aovs: None,
             samples: None,
             dirty: true,
             generation: 0,
@@ -116,6 +182,108 @@ impl Film {
         &self.pixels
     }
 
+    /// Returns the relative variance estimate for the mean luminance of each pixel in this `Film`.
+    ///
+    /// `samples_per_pixel` is the sample count of each pixel when the film isn't accumulating.
+    pub fn relative_variances(&self, samples_per_pixel: u32) -> Vec<f32> {
+        let mut ret = vec![0.0; self.pixels.len()];
+
+        let mut write_variances = |bb: Bounds2<u16>, sample_count: u32, accumulated: bool| {
+            for p in bb {
+                let i = (p.y as usize) * (self.res.x as usize) + (p.x as usize);
+                ret[i] = self.relative_variance(i, sample_count, accumulated);
+            }
+        };
+
+        match (&self.samples, &self.tile_cache) {
+            (Some(samples), Some(TileCache { tiles, .. })) => {
+                for tile in tiles {
+                    write_variances(tile.bb, samples[tile.index], true);
+                }
+            }
+            _ => write_variances(
+                Bounds2::new(Point2::zeros(), Point2::new(self.res.x, self.res.y)),
+                samples_per_pixel,
+                false,
+            ),
+        }
+
+        ret
+    }
+
+    /// Returns the relative variance estimate for the mean luminance of pixel `i` with
+    /// `sample_count` samples. Zero if there are less than two samples.
+    fn relative_variance(&self, i: usize, sample_count: u32, accumulated: bool) -> f32 {
+        if sample_count < 2 {
+            return 0.0;
+        }
+        let n = sample_count as f32;
+        // Accumulated pixels hold sums of single sample passes
+        let scale = if accumulated { 1.0 / n } else { 1.0 };
+        let mean = self.pixels[i].luminance() * scale;
+        let mean_sqr = self.luminance_sqr[i] * scale;
+        // Unbiased sample variance, divided by sample count for the variance of the mean
+        let variance = (mean_sqr - mean * mean).max(0.0) / (n - 1.0);
+        variance / (mean * mean).max(1e-4)
+    }
+
+    /// Returns `true` if all accumulated pixels in `tile` have a relative standard error below
+    /// `threshold`. Always `false` if this `Film` isn't accumulating.
+    pub fn tile_converged(&self, tile: &FilmTile, threshold: f32) -> bool {
+        let sample_count = match &self.samples {
+            Some(samples) => samples[tile.index],
+            None => return false,
+        };
+        if sample_count < ADAPTIVE_MIN_SAMPLES {
+            return false;
+        }
+
+        let threshold_sqr = threshold * threshold;
+        tile.bb.into_iter().all(|p| {
+            let i = (p.y as usize) * (self.res.x as usize) + (p.x as usize);
+            self.relative_variance(i, sample_count, true) < threshold_sqr
+        })
+    }
+
+    /// Returns the mean value of each pixel in this `Film`.
+    pub fn mean_pixels(&self) -> Vec<Spectrum<f32>> {
+        let mut ret = self.pixels.clone();
+        self.scale_accumulated(&mut ret);
+        ret
+    }
+
+    /// Returns the mean first hit AOVs of each pixel in this `Film`, if they were rendered.
+    pub fn mean_aovs(&self) -> Option<AovBuffers> {
+        self.aovs.as_ref().map(|aovs| {
+            let mut ret = aovs.clone();
+            self.scale_accumulated(&mut ret.albedo);
+            self.scale_accumulated(&mut ret.normal);
+            ret
+        })
+    }
+
+    /// Divides accumulated per-pixel `values` by the sample counts of their tiles.
+    fn scale_accumulated<T: MulAssign<f32>>(&self, values: &mut [T]) {
+        // Accumulated pixels hold sums of single sample passes
+        if let (Some(samples), Some(TileCache { tiles, .. })) = (&self.samples, &self.tile_cache) {
+            for tile in tiles {
+                let sample_count = samples[tile.index];
+                if sample_count < 2 {
+                    continue;
+                }
+                let scale = 1.0 / (sample_count as f32);
+                for p in tile.bb {
+                    values[(p.y as usize) * (self.res.x as usize) + (p.x as usize)] *= scale;
+                }
+            }
+        }
+    }
+
+    /// Returns the mean luminance of each pixel in this `Film`.
+    pub fn mean_luminances(&self) -> Vec<f32> {
+        self.mean_pixels().iter().map(Spectrum::luminance).collect()
+    }
+
     /// Returns a reference to the the samples for each tile in this `Film`.
     pub fn samples(&self) -> Option<&Vec<u32>> {
         self.samples.as_ref()
@@ -148,15 +316,16 @@ impl Film {
         }
     }
 
-    /// Returns blank `FilmTile`s for the buffer pixel with if they have been cached in the correct dimension.
-    /// The returned tiles will be in the current generation.
-    fn cached_tiles(&self, dim: u16) -> Option<VecDeque<FilmTile>> {
+    /// Returns blank `FilmTile`s for the buffer pixel with if they have been cached in the correct dimension
+    /// and crop. The returned tiles will be in the current generation.
+    fn cached_tiles(&self, dim: u16, crop: Option<Bounds2<u16>>) -> Option<VecDeque<FilmTile>> {
         if let Some(TileCache {
             dim: cached_dim,
+            crop: cached_crop,
             tiles,
         }) = &self.tile_cache
         {
-            if *cached_dim == dim {
+            if *cached_dim == dim && *cached_crop == crop {
                 let mut tiles = tiles.clone();
                 for tile in &mut tiles {
                     tile.generation = self.generation;
@@ -170,12 +339,11 @@ impl Film {
         }
     }
 
-    fn cache_tiles(&mut self, tiles: &VecDeque<FilmTile>) {
+    fn cache_tiles(&mut self, dim: u16, crop: Option<Bounds2<u16>>, tiles: &VecDeque<FilmTile>) {
         assert!(!tiles.is_empty());
-        // Tile size is always at most the full resolution
-        let dim = tiles[0].bb.diagonal().x;
         self.tile_cache = Some(TileCache {
             dim,
+            crop,
             tiles: tiles.clone(),
         });
     }
@@ -207,8 +375,15 @@ impl Film {
     }
 
     /// Updates this `Film` with the pixel values in a [`FilmTile`].
-    pub fn update_tile(&mut self, tile: &FilmTile, tile_pixels: &[Spectrum<f32>]) {
+    pub fn update_tile(
+        &mut self,
+        tile: &FilmTile,
+        tile_pixels: &[Spectrum<f32>],
+        tile_luminance_sqr: &[f32],
+        tile_aovs: Option<&AovBuffers>,
+    ) {
         assert!(tile_pixels.len() >= tile.bb.area() as usize);
+        assert!(tile_luminance_sqr.len() >= tile.bb.area() as usize);
 
         if !self.matches(tile) {
             yuki_warn!(
@@ -236,7 +411,7 @@ impl Film {
         let tile_width = tile_max.x - tile_min.x;
 
         macro_rules! update_slices {
-            ($write_expr:expr) => {
+            ($film_values:expr, $tile_values:expr, $write_expr:expr) => {
                 // Copy pixels over to the film
                 for (tile_row, film_row) in
                     ((tile_min.y as usize)..(tile_max.y as usize)).enumerate()
@@ -249,8 +424,8 @@ impl Film {
                     let tile_slice_start = tile_row * (tile_width as usize);
                     let tile_slice_end = (tile_row + 1) * (tile_width as usize);
 
-                    let film_slice = &mut self.pixels[film_slice_start..film_slice_end];
-                    let tile_slice = &tile_pixels[tile_slice_start..tile_slice_end];
+                    let film_slice = &mut $film_values[film_slice_start..film_slice_end];
+                    let tile_slice = &$tile_values[tile_slice_start..tile_slice_end];
 
                     $write_expr(film_slice, tile_slice);
                 }
@@ -258,24 +433,54 @@ impl Film {
         }
 
         if let Some(samples) = &mut self.samples {
-            update_slices!(
-                |film_slice: &mut [Spectrum<f32>], tile_slice: &[Spectrum<f32>]| {
-                    film_slice
-                        .iter_mut()
-                        .zip(tile_slice.iter())
-                        .for_each(|(fc, &c)| {
-                            *fc += c;
-                        });
-                }
-            );
+            let add_pixels = |film_slice: &mut [Spectrum<f32>], tile_slice: &[Spectrum<f32>]| {
+                film_slice
+                    .iter_mut()
+                    .zip(tile_slice.iter())
+                    .for_each(|(fc, &c)| {
+                        *fc += c;
+                    });
+            };
+            let add_luminances = |film_slice: &mut [f32], tile_slice: &[f32]| {
+                film_slice
+                    .iter_mut()
+                    .zip(tile_slice.iter())
+                    .for_each(|(fl, &l)| {
+                        *fl += l;
+                    });
+            };
+            let add_normals = |film_slice: &mut [Vec3<f32>], tile_slice: &[Vec3<f32>]| {
+                film_slice
+                    .iter_mut()
+                    .zip(tile_slice.iter())
+                    .for_each(|(fv, &v)| {
+                        *fv += v;
+                    });
+            };
+            update_slices!(self.pixels, tile_pixels, add_pixels);
+            update_slices!(self.luminance_sqr, tile_luminance_sqr, add_luminances);
+            if let (Some(aovs), Some(tile_aovs)) = (&mut self.aovs, tile_aovs) {
+                update_slices!(aovs.albedo, tile_aovs.albedo, add_pixels);
+                update_slices!(aovs.normal, tile_aovs.normal, add_normals);
+            }
 
             samples[tile.index] += 1;
         } else {
-            update_slices!(
-                |film_slice: &mut [Spectrum<f32>], tile_slice: &[Spectrum<f32>]| {
-                    film_slice.copy_from_slice(tile_slice);
-                }
-            );
+            let copy_pixels = |film_slice: &mut [Spectrum<f32>], tile_slice: &[Spectrum<f32>]| {
+                film_slice.copy_from_slice(tile_slice);
+            };
+            let copy_luminances = |film_slice: &mut [f32], tile_slice: &[f32]| {
+                film_slice.copy_from_slice(tile_slice);
+            };
+            let copy_normals = |film_slice: &mut [Vec3<f32>], tile_slice: &[Vec3<f32>]| {
+                film_slice.copy_from_slice(tile_slice);
+            };
+            update_slices!(self.pixels, tile_pixels, copy_pixels);
+            update_slices!(self.luminance_sqr, tile_luminance_sqr, copy_luminances);
+            if let (Some(aovs), Some(tile_aovs)) = (&mut self.aovs, tile_aovs) {
+                update_slices!(aovs.albedo, tile_aovs.albedo, copy_pixels);
+                update_slices!(aovs.normal, tile_aovs.normal, copy_normals);
+            }
         }
 
         self.dirty = true;
@@ -287,6 +492,8 @@ impl Default for Film {
         Self {
             res: Vec2::new(4, 4),
             pixels: vec![Spectrum::zeros(); 4 * 4],
+            luminance_sqr: vec![0.0; 4 * 4],
+            aovs: None,
             samples: None,
             dirty: true,
             generation: 0,
@@ -375,6 +582,35 @@ fn outward_spiral(
     tile_queue
 }
 
+/// Returns `crop` clamped to the film resolution `res`, `None` if it is empty.
+fn clamped_crop(crop: Option<Bounds2<u16>>, res: Vec2<u16>) -> Option<Bounds2<u16>> {
+    let crop = crop?;
+    let full = Bounds2::new(Point2::zeros(), Point2::new(res.x, res.y));
+    match crop.intersection(full) {
+        Some(bb) if bb.area() > 0 => Some(bb),
+        _ => {
+            yuki_warn!("Crop window {:?} is outside film {:?}, ignoring", crop, res);
+            None
+        }
+    }
+}
+
+/// Drops tiles outside `crop` and clips the rest to it. Tiles keep their flat index so that the
+/// sample counts stay indexed by the full tile grid.
+fn crop_tiles(tiles: VecDeque<FilmTile>, crop: Bounds2<u16>) -> VecDeque<FilmTile> {
+    tiles
+        .into_iter()
+        .filter_map(|mut tile| {
+            let bb = tile.bb.intersection(crop)?;
+            if bb.area() == 0 {
+                return None;
+            }
+            tile.bb = bb;
+            Some(tile)
+        })
+        .collect()
+}
+
 pub fn film_or_new(film: &Arc<Mutex<Film>>, settings: FilmSettings) -> Arc<Mutex<Film>> {
     yuki_trace!("film_or_new: Waiting for lock on film (res)");
     let film_res = film.lock().unwrap().res();
@@ -409,6 +645,7 @@ pub fn film_or_new(film: &Arc<Mutex<Film>>, settings: FilmSettings) -> Arc<Mutex
 pub fn film_tiles(film: &mut Arc<Mutex<Film>>, settings: FilmSettings) -> VecDeque<FilmTile> {
     yuki_debug!("film_tiles: Begin");
 
+    let crop = clamped_crop(settings.crop, settings.res);
     let tiles = {
         yuki_trace!("film_tiles: Waiting for lock on film");
         let film = film.lock().unwrap();
@@ -417,7 +654,7 @@ pub fn film_tiles(film: &mut Arc<Mutex<Film>>, settings: FilmSettings) -> VecDeq
         assert!(film.res() == settings.res, "Film does not match settings");
 
         yuki_trace!("film_tiles: Checking for cached tiles");
-        let tiles = film.cached_tiles(settings.tile_dim);
+        let tiles = film.cached_tiles(settings.tile_dim, crop);
 
         yuki_trace!("film_tiles: Releasing film");
         tiles
@@ -440,7 +677,10 @@ pub fn film_tiles(film: &mut Arc<Mutex<Film>>, settings: FilmSettings) -> VecDeq
         yuki_trace!("film_tiles: Ordering tiles");
         // Order tiles in a spiral from middle since that makes the visualisation more snappy:
         // Most things of interest are likely towards the center of the frame
-        let tile_queue = outward_spiral(tiles, settings.res, settings.tile_dim);
+        let mut tile_queue = outward_spiral(tiles, settings.res, settings.tile_dim);
+        if let Some(crop) = crop {
+            tile_queue = crop_tiles(tile_queue, crop);
+        }
 
         {
             yuki_trace!("film_tiles: Waiting for lock on film");
@@ -448,7 +688,7 @@ pub fn film_tiles(film: &mut Arc<Mutex<Film>>, settings: FilmSettings) -> VecDeq
             yuki_trace!("film_tiles: Acquired film");
 
             yuki_trace!("film_tiles: Caching tiles");
-            film.cache_tiles(&tile_queue);
+            film.cache_tiles(settings.tile_dim, crop, &tile_queue);
 
             yuki_trace!("film_tiles: Releasing film");
         }
@@ -462,14 +702,125 @@ pub fn film_tiles(film: &mut Arc<Mutex<Film>>, settings: FilmSettings) -> VecDeq
         yuki_trace!("film_tiles: Acquired film");
 
         if settings.accumulate {
-            film.samples = Some(vec![0; ret.len()]);
+            // Sample counts cover the full tile grid even when cropped
+            let dim = settings.tile_dim as usize;
+            let x_tiles = ((settings.res.x as usize) + dim - 1) / dim;
+            let y_tiles = ((settings.res.y as usize) + dim - 1) / dim;
+            film.samples = Some(vec![0; x_tiles * y_tiles]);
         } else {
             film.samples = None;
         }
 
+        if settings.aovs {
+            let pixel_count = film.pixels.len();
+            film.aovs = Some(AovBuffers::new(pixel_count));
+        } else {
+            film.aovs = None;
+        }
+
         yuki_trace!("film_tiles: Releasing film");
     }
 
     yuki_debug!("film_tiles: End");
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{film_tiles, AovBuffers, Film, FilmSettings};
+    use crate::math::{Bounds2, Point2, Spectrum, Vec2, Vec3};
+
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn accumulated_aovs_are_averaged() {
+        let settings = FilmSettings {
+            res: Vec2::new(16, 16),
+            accumulate: true,
+            aovs: true,
+            ..FilmSettings::default()
+        };
+        let mut film = Arc::new(Mutex::new(Film::new(settings.res)));
+        let tiles = film_tiles(&mut film, settings);
+        assert_eq!(tiles.len(), 1);
+        let tile = &tiles[0];
+
+        let pixels = vec![Spectrum::zeros(); 16 * 16];
+        let luminance_sqr = vec![0.0; 16 * 16];
+        let mut tile_aovs = AovBuffers::new(16 * 16);
+        let mut film = film.lock().unwrap();
+        for pass in 1..=2 {
+            let v = (2 * pass - 1) as f32;
+            tile_aovs.albedo.fill(Spectrum::ones() * v);
+            tile_aovs.normal.fill(Vec3::new(0.0, 0.0, v));
+            film.update_tile(tile, &pixels, &luminance_sqr, Some(&tile_aovs));
+        }
+
+        let aovs = film.mean_aovs().unwrap();
+        assert!(aovs.albedo.iter().all(|a| (a.g - 2.0).abs() < 1e-6));
+        assert!(aovs.normal.iter().all(|n| (n.z - 2.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn cropped_tiles_stay_within_crop() {
+        let crop = Bounds2::new(Point2::new(10, 20), Point2::new(50, 37));
+        let settings = FilmSettings {
+            res: Vec2::new(64, 64),
+            accumulate: true,
+            crop: Some(crop),
+            ..FilmSettings::default()
+        };
+        let mut film = Arc::new(Mutex::new(Film::new(settings.res)));
+        let tiles = film_tiles(&mut film, settings);
+
+        assert!(!tiles.is_empty());
+        let mut covered = 0;
+        for tile in &tiles {
+            assert!(tile.bb.area() > 0);
+            assert!(crop.inside(tile.bb.p_min) && crop.inside(tile.bb.p_max));
+            covered += tile.bb.area() as usize;
+        }
+        assert_eq!(covered, crop.area() as usize);
+
+        // Sample counts are still indexed by the full tile grid
+        assert_eq!(film.lock().unwrap().samples().unwrap().len(), 4 * 4);
+    }
+
+    #[test]
+    fn accumulated_variance_matches_sample_stream() {
+        let settings = FilmSettings {
+            res: Vec2::new(16, 16),
+            accumulate: true,
+            ..FilmSettings::default()
+        };
+        let mut film = Arc::new(Mutex::new(Film::new(settings.res)));
+        let tiles = film_tiles(&mut film, settings);
+        let tile = &tiles[0];
+
+        let mut film = film.lock().unwrap();
+        let stream = [1.0, 2.0, 3.0, 4.0, 1.0, 2.0, 3.0, 4.0];
+        for (pass, &v) in stream.iter().enumerate() {
+            let pixels = vec![Spectrum::ones() * v; 16 * 16];
+            let luminance_sqr = vec![v * v; 16 * 16];
+            film.update_tile(tile, &pixels, &luminance_sqr, None);
+
+            // Unbiased variance of the stream so far, divided by the count for that of the mean
+            let n = (pass + 1) as f32;
+            let samples = &stream[..=pass];
+            let mean = samples.iter().sum::<f32>() / n;
+            let expected = if pass == 0 {
+                0.0
+            } else {
+                let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / (n - 1.0);
+                variance / n / (mean * mean)
+            };
+            for &rv in &film.relative_variances(1) {
+                assert!((rv - expected).abs() < 1e-5, "{} {}", rv, expected);
+            }
+        }
+
+        // Relative standard error is ~0.17 after the stream
+        assert!(film.tile_converged(tile, 0.2));
+        assert!(!film.tile_converged(tile, 0.15));
+    }
+}
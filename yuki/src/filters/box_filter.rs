@@ -0,0 +1,26 @@
+use super::Filter;
+use crate::math::Point2;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Sampling_and_Reconstruction/Image_Reconstruction#BoxFilter
+
+/// Weighs all samples within its radius equally
+pub struct BoxFilter {
+    radius: f32,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn evaluate(&self, _p: Point2<f32>) -> f32 {
+        1.0
+    }
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+}
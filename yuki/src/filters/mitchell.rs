@@ -0,0 +1,83 @@
+use super::Filter;
+use crate::math::Point2;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Sampling_and_Reconstruction/Image_Reconstruction#MitchellFilter
+
+/// Separable Mitchell-Netravali filter
+pub struct MitchellFilter {
+    radius: f32,
+    inv_radius: f32,
+    b: f32,
+    c: f32,
+}
+
+impl MitchellFilter {
+    /// Creates a new `MitchellFilter`, `b` and `c` of 1/3 are the values recommended by the paper
+    pub fn new(radius: f32, b: f32, c: f32) -> Self {
+        assert!(radius > 0.0);
+        Self {
+            radius,
+            inv_radius: 1.0 / radius,
+            b,
+            c,
+        }
+    }
+
+    /// Evaluates the 1D filter for `x` in [-1, 1]
+    fn mitchell_1d(&self, x: f32) -> f32 {
+        let Self { b, c, .. } = *self;
+        let x = (2.0 * x).abs();
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn evaluate(&self, p: Point2<f32>) -> f32 {
+        self.mitchell_1d(p.x * self.inv_radius) * self.mitchell_1d(p.y * self.inv_radius)
+    }
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MitchellFilter;
+    use crate::{filters::Filter, math::Point2};
+
+    #[test]
+    fn weights_sum_to_one_over_unit_grid() {
+        let filter = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+
+        for offset in [
+            Point2::new(0.0, 0.0),
+            Point2::new(0.5, 0.5),
+            Point2::new(0.25, 0.75),
+            Point2::new(0.9, 0.1),
+        ] {
+            let mut sum = 0.0;
+            for j in -3..=3 {
+                for i in -3..=3 {
+                    sum +=
+                        filter.evaluate(Point2::new((i as f32) + offset.x, (j as f32) + offset.y));
+                }
+            }
+            assert!((sum - 1.0).abs() < 1e-4, "{:?} sums to {}", offset, sum);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+mod box_filter;
+mod mitchell;
+
+pub use box_filter::BoxFilter;
+pub use mitchell::MitchellFilter;
+
+use crate::math::Point2;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString, EnumVariantNames};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Sampling_and_Reconstruction/Image_Reconstruction
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, Display, EnumVariantNames, EnumString)]
+pub enum FilterType {
+    Box,
+    Mitchell,
+}
+
+impl FilterType {
+    pub fn instantiate(self) -> Box<dyn Filter> {
+        match self {
+            FilterType::Box => Box::new(BoxFilter::new(0.5)),
+            FilterType::Mitchell => Box::new(MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)),
+        }
+    }
+}
+
+impl Default for FilterType {
+    fn default() -> Self {
+        FilterType::Box
+    }
+}
+
+/// Pixel reconstruction filter
+pub trait Filter: Send + Sync {
+    /// Evaluates the filter at `p` relative to the filter center
+    fn evaluate(&self, p: Point2<f32>) -> f32;
+    /// Returns the extent of the filter support from its center in both axes
+    fn radius(&self) -> f32;
+}
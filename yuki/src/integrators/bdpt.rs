@@ -0,0 +1,1176 @@
+use super::{
+    escaped_le, light_intensity, DebugRecord, Integrator, IntegratorRay, RadianceResult, RayType,
+};
+use crate::{
+    bvh::IntersectionResult,
+    interaction::{Interaction, SurfaceInteraction},
+    lights::{EmissionSample, Light, LightSample, LightType},
+    materials::{Bsdf, BxdfSample, BxdfType},
+    math::{Normal, Point3, Ray, Spectrum, Vec3},
+    sampling::Sampler,
+    scene::Scene,
+    shapes::Hit,
+    visibility::VisibilityTester,
+};
+
+use allocators::ScopedScratch;
+use serde::{Deserialize, Serialize};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Light_Transport_III_Bidirectional_Methods/Bidirectional_Path_Tracing
+//
+// Strategies with a single camera vertex would need splatting onto arbitrary film pixels so they
+// are left out. The MIS weights only consider the used strategies so the estimate stays unbiased.
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Params {
+    pub max_depth: u32,
+    pub background_intensity: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            background_intensity: 1.0,
+        }
+    }
+}
+
+pub struct BDPT {
+    max_depth: u32,
+    background_intensity: f32,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum TransportMode {
+    Radiance,
+    Importance,
+}
+
+enum VertexContent<'a> {
+    Camera,
+    /// Light subpath origin or a light sampled for a connection
+    Light(&'a dyn Light),
+    /// Camera ray that escaped the scene
+    Background,
+    Surface {
+        si: SurfaceInteraction,
+        bsdf: Bsdf<'a>,
+    },
+}
+
+struct Vertex<'a> {
+    content: VertexContent<'a>,
+    p: Point3<f32>,
+    /// Geometric normal, `None` if the vertex isn't on a surface
+    n: Option<Normal<f32>>,
+    beta: Spectrum<f32>,
+    delta: bool,
+    /// Area density of sampling this vertex from the previous one
+    pdf_fwd: f32,
+    /// Area density of sampling this vertex from the next one in a reversed path
+    pdf_rev: f32,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(p: Point3<f32>) -> Self {
+        Self {
+            content: VertexContent::Camera,
+            p,
+            n: None,
+            beta: Spectrum::ones(),
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(
+        light: &'a dyn Light,
+        p: Point3<f32>,
+        n: Option<Normal<f32>>,
+        beta: Spectrum<f32>,
+        pdf_fwd: f32,
+    ) -> Self {
+        Self {
+            content: VertexContent::Light(light),
+            p,
+            n,
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn background(ray: Ray<f32>, beta: Spectrum<f32>, pdf_fwd: f32) -> Self {
+        Self {
+            content: VertexContent::Background,
+            p: ray.o + ray.d,
+            n: None,
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(
+        si: SurfaceInteraction,
+        bsdf: Bsdf<'a>,
+        beta: Spectrum<f32>,
+        pdf: f32,
+        prev: &Vertex,
+    ) -> Self {
+        let mut ret = Self {
+            p: si.p,
+            n: Some(si.n),
+            content: VertexContent::Surface { si, bsdf },
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        ret.pdf_fwd = prev.convert_density(pdf, &ret);
+        ret
+    }
+
+    /// Returns the shading normal for surfaces, the geometric normal otherwise.
+    fn ns(&self) -> Option<Normal<f32>> {
+        match &self.content {
+            VertexContent::Surface { si, .. } => Some(si.shading.n),
+            _ => self.n,
+        }
+    }
+
+    fn is_connectible(&self) -> bool {
+        match &self.content {
+            VertexContent::Camera => true,
+            VertexContent::Light(light) => !light.flags().contains(LightType::DELTA_DIRECTION),
+            VertexContent::Background => false,
+            VertexContent::Surface { bsdf, .. } => {
+                bsdf.num_components(BxdfType::all().difference(BxdfType::SPECULAR)) > 0
+            }
+        }
+    }
+
+    fn is_light(&self) -> bool {
+        match &self.content {
+            VertexContent::Light(_) | VertexContent::Background => true,
+            VertexContent::Surface { si, .. } => si.area_light.is_some(),
+            _ => false,
+        }
+    }
+
+    fn is_delta_light(&self) -> bool {
+        match &self.content {
+            VertexContent::Light(light) => light.is_delta(),
+            _ => false,
+        }
+    }
+
+    fn is_infinite_light(&self) -> bool {
+        match &self.content {
+            VertexContent::Light(light) => light.flags().contains(LightType::INFINITE),
+            VertexContent::Background => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the emitted radiance toward `v`.
+    ///
+    /// Lights at infinity are scaled by `background_intensity`.
+    fn le(&self, scene: &Scene, v: &Vertex, background_intensity: f32) -> Spectrum<f32> {
+        let w = v.p - self.p;
+        if w.len_sqr() == 0.0 {
+            return Spectrum::zeros();
+        }
+        let w = w.normalized();
+        match &self.content {
+            VertexContent::Surface { si, .. } => si.emitted_radiance(w),
+            VertexContent::Background => escaped_le(
+                scene,
+                Ray::new(v.p, -w, f32::INFINITY),
+                background_intensity,
+            ),
+            _ => Spectrum::zeros(),
+        }
+    }
+
+    /// Evaluates the scattering function toward `next`.
+    fn f(&self, next: &Vertex, mode: TransportMode) -> Spectrum<f32> {
+        let wi = next.p - self.p;
+        if wi.len_sqr() == 0.0 {
+            return Spectrum::zeros();
+        }
+        let wi = wi.normalized();
+        match &self.content {
+            VertexContent::Surface { si, bsdf } => {
+                bsdf.f(si.wo, wi, BxdfType::all()) * correct_shading_normal(si, si.wo, wi, mode)
+            }
+            _ => Spectrum::zeros(),
+        }
+    }
+
+    /// Converts the solid angle density `pdf` at this vertex into area density at `next`.
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        if next.is_infinite_light() {
+            return pdf;
+        }
+        let w = next.p - self.p;
+        let dist_sqr = w.len_sqr();
+        if dist_sqr == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / dist_sqr;
+        if let Some(n) = next.n {
+            pdf *= n.dot_v(w / dist_sqr.sqrt()).abs();
+        }
+        pdf
+    }
+
+    /// Returns the area density of sampling `next` from this vertex, when it was reached from `prev`.
+    fn pdf(&self, scene: &Scene, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        if let VertexContent::Light(_) = self.content {
+            return self.pdf_light(scene, next);
+        }
+
+        let wn = next.p - self.p;
+        if wn.len_sqr() == 0.0 {
+            return 0.0;
+        }
+        let wn = wn.normalized();
+
+        let pdf = match (&self.content, prev) {
+            (VertexContent::Surface { si, bsdf }, Some(prev)) => {
+                let wp = prev.p - self.p;
+                if wp.len_sqr() == 0.0 {
+                    return 0.0;
+                }
+                bsdf.pdf(wp.normalized(), wn, BxdfType::all())
+            }
+            // Camera densities are only needed for the strategies that aren't used
+            _ => return 0.0,
+        };
+
+        self.convert_density(pdf, next)
+    }
+
+    /// Returns the directional densities of the light on this vertex toward `w`.
+    fn pdf_le(&self, w: Vec3<f32>) -> (f32, f32) {
+        let ray = Ray::new(self.p, w, f32::INFINITY);
+        let n = self.n.unwrap_or_else(|| Normal::from(w));
+        match &self.content {
+            VertexContent::Light(light) => light.pdf_le(ray, n),
+            VertexContent::Surface { si, .. } => si
+                .area_light
+                .as_ref()
+                .map_or((0.0, 0.0), |light| light.pdf_le(ray, n)),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    /// Returns the area density of the light on this vertex emitting toward `next`.
+    fn pdf_light(&self, scene: &Scene, next: &Vertex) -> f32 {
+        let w = next.p - self.p;
+        let dist_sqr = w.len_sqr();
+        if dist_sqr == 0.0 {
+            return 0.0;
+        }
+        let w = w / dist_sqr.sqrt();
+
+        let mut pdf = if self.is_infinite_light() {
+            // Rays from infinite lights are distributed on a disk covering the scene
+            let radius = scene
+                .bvh
+                .bounds()
+                .bounding_sphere()
+                .map_or(1.0, |(_, radius)| radius);
+            1.0 / (std::f32::consts::PI * radius * radius)
+        } else {
+            let (_, pdf_dir) = self.pdf_le(w);
+            pdf_dir / dist_sqr
+        };
+        if let Some(n) = next.n {
+            pdf *= n.dot_v(w).abs();
+        }
+        pdf
+    }
+
+    /// Returns the area density of sampling this vertex as the origin of a light subpath.
+    ///
+    /// Lights at infinity get the solid angle density of sampling the direction from `next`
+    /// toward them instead.
+    fn pdf_light_origin(&self, scene: &Scene, next: &Vertex) -> f32 {
+        let w = next.p - self.p;
+        if w.len_sqr() == 0.0 {
+            return 0.0;
+        }
+        let w = w.normalized();
+        if self.is_infinite_light() {
+            return match &next.content {
+                VertexContent::Surface { si, .. } => {
+                    infinite_light_density(scene, &Interaction::from(si), -w)
+                }
+                // Only needed toward the camera by the strategies with a single camera vertex
+                _ => 0.0,
+            };
+        }
+        let light_pdf = match &self.content {
+            VertexContent::Light(light) => scene.light_pdf(*light),
+            VertexContent::Surface { si, .. } => si
+                .area_light
+                .as_ref()
+                .map_or(0.0, |light| scene.light_pdf(light.as_ref())),
+            _ => 0.0,
+        };
+        let (pdf_pos, _) = self.pdf_le(w);
+        pdf_pos * light_pdf
+    }
+}
+
+/// Returns the solid angle density of picking any of the lights at infinity in `scene` and
+/// sampling `w` toward it at `it`.
+fn infinite_light_density(scene: &Scene, it: &Interaction, w: Vec3<f32>) -> f32 {
+    scene
+        .lights
+        .iter()
+        .filter(|l| l.flags().contains(LightType::INFINITE))
+        .map(|l| l.pdf_li(it, w) * scene.light_pdf(l.as_ref()))
+        .sum()
+}
+
+/// Accounts for the asymmetry of shading normals with importance transport.
+fn correct_shading_normal(
+    si: &SurfaceInteraction,
+    wo: Vec3<f32>,
+    wi: Vec3<f32>,
+    mode: TransportMode,
+) -> f32 {
+    if mode == TransportMode::Importance {
+        let num = wo.dot_n(si.shading.n).abs() * wi.dot_n(si.n).abs();
+        let denom = wo.dot_n(si.n).abs() * wi.dot_n(si.shading.n).abs();
+        if denom == 0.0 {
+            0.0
+        } else {
+            num / denom
+        }
+    } else {
+        1.0
+    }
+}
+
+/// Per vertex densities used to evaluate the MIS weight of a strategy
+#[derive(Copy, Clone)]
+struct VertexDensities {
+    pdf_fwd: f32,
+    pdf_rev: f32,
+    delta: bool,
+}
+
+impl From<&Vertex<'_>> for VertexDensities {
+    fn from(v: &Vertex) -> Self {
+        Self {
+            pdf_fwd: v.pdf_fwd,
+            pdf_rev: v.pdf_rev,
+            delta: v.delta,
+        }
+    }
+}
+
+impl BDPT {
+    pub fn new(params: Params) -> Self {
+        Self {
+            max_depth: params.max_depth,
+            background_intensity: params.background_intensity,
+        }
+    }
+
+    // Always inline to have the compiler strip out ray collection in li()-calls
+    #[inline(always)]
+    fn li_internal(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        sampler: &mut Box<dyn Sampler>,
+        mut rays: Option<&mut Vec<IntegratorRay>>,
+    ) -> RadianceResult {
+        let max_depth = self.max_depth as usize;
+        let mut ray_count = 0;
+
+        let mut camera_path = Vec::with_capacity(max_depth + 2);
+        camera_path.push(Vertex::camera(ray.o));
+        // The camera densities are not used so the initial pdf doesn't matter
+        ray_count += random_walk(
+            scratch,
+            scene,
+            ray,
+            sampler,
+            Spectrum::ones(),
+            1.0,
+            max_depth + 1,
+            TransportMode::Radiance,
+            &mut camera_path,
+        );
+
+        if let Some(collected_rays) = &mut rays {
+            collect_camera_path_rays(scene, &camera_path, collected_rays);
+        }
+
+        let mut light_path = Vec::with_capacity(max_depth + 1);
+        if let Some((light, light_pdf)) = scene.sample_light(sampler.get_1d()) {
+            let EmissionSample {
+                ray,
+                n,
+                le,
+                pdf_pos,
+                pdf_dir,
+            } = light.sample_le(sampler.get_2d(), sampler.get_2d(), scene.bvh.bounds());
+            if light_pdf > 0.0 && pdf_pos > 0.0 && pdf_dir > 0.0 && !le.is_black() {
+                let le = le * light_intensity(light, self.background_intensity);
+                let is_area = light.flags().contains(LightType::AREA);
+                light_path.push(Vertex::light(
+                    light,
+                    ray.o,
+                    if is_area { Some(n) } else { None },
+                    le,
+                    pdf_pos * light_pdf,
+                ));
+                let beta = le * n.dot_v(ray.d).abs() / (light_pdf * pdf_pos * pdf_dir);
+                ray_count += random_walk(
+                    scratch,
+                    scene,
+                    ray,
+                    sampler,
+                    beta,
+                    pdf_dir,
+                    max_depth,
+                    TransportMode::Importance,
+                    &mut light_path,
+                );
+
+                if light_path[0].is_infinite_light() {
+                    // The first vertex was sampled on a disk perpendicular to the light direction
+                    if let Some(v) = light_path.get_mut(1) {
+                        v.pdf_fwd = pdf_pos;
+                        if let Some(n) = v.n {
+                            v.pdf_fwd *= n.dot_v(ray.d).abs();
+                        }
+                    }
+                    // Escaping camera paths hit the light by sampling the direction toward it
+                    light_path[0].pdf_fwd = match light_path.get(1).map(|v| &v.content) {
+                        Some(VertexContent::Surface { si, .. }) => {
+                            infinite_light_density(scene, &Interaction::from(si), -ray.d)
+                        }
+                        _ => 0.0,
+                    };
+                }
+            }
+        }
+
+        let mut incoming_radiance = Spectrum::zeros();
+        // Strategies with a single camera vertex are not supported
+        for t in 2..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = s + t - 2;
+                if depth > max_depth {
+                    continue;
+                }
+
+                incoming_radiance += self.connect(
+                    scene,
+                    &light_path,
+                    &camera_path,
+                    s,
+                    t,
+                    sampler,
+                    &mut ray_count,
+                    &mut rays,
+                );
+            }
+        }
+
+        RadianceResult {
+            li: incoming_radiance,
+            ray_scene_intersections: ray_count,
+        }
+    }
+
+    /// Returns the MIS weighted contribution of the strategy with `s` light and `t` camera vertices.
+    fn connect(
+        &self,
+        scene: &Scene,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        sampler: &mut Box<dyn Sampler>,
+        ray_count: &mut usize,
+        rays: &mut Option<&mut Vec<IntegratorRay>>,
+    ) -> Spectrum<f32> {
+        let pt = &camera_path[t - 1];
+
+        let mut sampled = None;
+        let l = if s == 0 {
+            if pt.is_light() {
+                pt.le(scene, &camera_path[t - 2], self.background_intensity) * pt.beta
+            } else {
+                Spectrum::zeros()
+            }
+        } else if s == 1 {
+            if let Some((l, v)) = connect_sampled_light(
+                scene,
+                pt,
+                sampler,
+                self.background_intensity,
+                ray_count,
+                rays,
+            ) {
+                sampled = Some(v);
+                l
+            } else {
+                Spectrum::zeros()
+            }
+        } else {
+            let qs = &light_path[s - 1];
+            if qs.is_connectible() && pt.is_connectible() {
+                let l = qs.beta
+                    * qs.f(pt, TransportMode::Importance)
+                    * pt.f(qs, TransportMode::Radiance)
+                    * pt.beta;
+                if l.is_black() {
+                    l
+                } else {
+                    l * g(scene, qs, pt, ray_count, rays)
+                }
+            } else {
+                Spectrum::zeros()
+            }
+        };
+
+        let mut l = if l.is_black() {
+            l
+        } else {
+            l * mis_weight(scene, light_path, camera_path, sampled.as_ref(), s, t)
+        };
+
+        // The constant background isn't a light so only escaped camera paths reach it
+        if s == 0 {
+            if let VertexContent::Background = pt.content {
+                l += pt.beta * scene.background * self.background_intensity;
+            }
+        }
+
+        l
+    }
+}
+
+/// Connects `pt` to a point sampled on a light.
+///
+/// Returns the unweighted contribution and the sampled light vertex.
+fn connect_sampled_light<'a>(
+    scene: &'a Scene,
+    pt: &Vertex,
+    sampler: &mut Box<dyn Sampler>,
+    background_intensity: f32,
+    ray_count: &mut usize,
+    rays: &mut Option<&mut Vec<IntegratorRay>>,
+) -> Option<(Spectrum<f32>, Vertex<'a>)> {
+    if !pt.is_connectible() {
+        return None;
+    }
+    let si = match &pt.content {
+        VertexContent::Surface { si, .. } => si,
+        _ => return None,
+    };
+    let (light, light_pdf) = scene.sample_light(sampler.get_1d())?;
+
+    let LightSample {
+        l: wi,
+        li,
+        vis,
+        pdf,
+    } = light.sample_li(&Interaction::from(si), sampler.get_2d());
+    let vis = vis?;
+    if pdf == 0.0 || light_pdf == 0.0 || li.is_black() {
+        return None;
+    }
+
+    let n = if light.flags().contains(LightType::AREA) {
+        Some(vis.p1().n)
+    } else {
+        None
+    };
+    let li = li * light_intensity(light, background_intensity);
+    let mut v = Vertex::light(light, vis.p1().p, n, li / (pdf * light_pdf), 0.0);
+    v.pdf_fwd = v.pdf_light_origin(scene, pt);
+
+    let mut l = pt.beta * pt.f(&v, TransportMode::Radiance) * v.beta * wi.dot_n(si.shading.n).abs();
+    if !l.is_black() {
+        if let Some(collected_rays) = rays {
+            collected_rays.push(IntegratorRay {
+                ray: vis.ray(),
+                ray_type: RayType::Shadow,
+            });
+        }
+        *ray_count += 1;
+        if !vis.unoccluded(scene) {
+            l = Spectrum::zeros();
+        }
+    }
+
+    Some((l, v))
+}
+
+/// Extends `path` with at most `max_vertices` vertices by tracing `ray` through the scene.
+///
+/// Returns the number of rays intersected with `scene`.
+fn random_walk<'a>(
+    scratch: &'a ScopedScratch,
+    scene: &'a Scene,
+    mut ray: Ray<f32>,
+    sampler: &mut Box<dyn Sampler>,
+    mut beta: Spectrum<f32>,
+    pdf: f32,
+    max_vertices: usize,
+    mode: TransportMode,
+    path: &mut Vec<Vertex<'a>>,
+) -> usize {
+    if max_vertices == 0 {
+        return 0;
+    }
+
+    let mut ray_count = 0;
+    let mut pdf_fwd = pdf;
+    let mut vertex_count = 0;
+    loop {
+        ray_count += 1;
+        let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
+
+        let (si, shape) = if let Some(Hit { si, shape, .. }) = hit {
+            (si, shape)
+        } else {
+            // Only camera rays get radiance from the background, the density stays in solid
+            // angle like for lights at infinity
+            if mode == TransportMode::Radiance {
+                path.push(Vertex::background(ray, beta, pdf_fwd));
+            }
+            break;
+        };
+
+        let bsdf = shape.compute_scattering_functions(scratch, &si);
+        let vertex = Vertex::surface(si, bsdf, beta, pdf_fwd, path.last().unwrap());
+        path.push(vertex);
+        vertex_count += 1;
+        if vertex_count >= max_vertices {
+            break;
+        }
+
+        let (wi, pdf_rev, specular, spawned_ray) = {
+            let vertex = path.last().unwrap();
+            let (si, bsdf) = match &vertex.content {
+                VertexContent::Surface { si, bsdf } => (si, bsdf),
+                _ => unreachable!(),
+            };
+
+            let BxdfSample {
+                wi,
+                f,
+                pdf,
+                sample_type,
+            } = bsdf.sample_f(si.wo, sampler.get_2d(), BxdfType::all());
+            if f.is_black() || pdf == 0.0 {
+                break;
+            }
+
+            beta *= f * wi.dot_n(si.shading.n).abs() / pdf;
+            beta *= correct_shading_normal(si, si.wo, wi, mode);
+            pdf_fwd = pdf;
+
+            (
+                wi,
+                bsdf.pdf(wi, si.wo, BxdfType::all()),
+                sample_type.contains(BxdfType::SPECULAR),
+                si.spawn_ray(wi),
+            )
+        };
+        debug_assert!(!wi.has_nans());
+
+        let pdf_rev = if specular {
+            pdf_fwd = 0.0;
+            0.0
+        } else {
+            pdf_rev
+        };
+
+        let vertex_index = path.len() - 1;
+        path[vertex_index].delta = specular;
+        path[vertex_index - 1].pdf_rev =
+            path[vertex_index].convert_density(pdf_rev, &path[vertex_index - 1]);
+
+        ray = spawned_ray;
+    }
+
+    ray_count
+}
+
+/// Returns the geometry term between `v0` and `v1`, including visibility.
+fn g(
+    scene: &Scene,
+    v0: &Vertex,
+    v1: &Vertex,
+    ray_count: &mut usize,
+    rays: &mut Option<&mut Vec<IntegratorRay>>,
+) -> f32 {
+    let d = v0.p - v1.p;
+    let dist_sqr = d.len_sqr();
+    if dist_sqr == 0.0 {
+        return 0.0;
+    }
+    let d = d / dist_sqr.sqrt();
+
+    let mut g = 1.0 / dist_sqr;
+    if let Some(ns) = v0.ns() {
+        g *= ns.dot_v(d).abs();
+    }
+    if let Some(ns) = v1.ns() {
+        g *= ns.dot_v(d).abs();
+    }
+    if g == 0.0 {
+        return 0.0;
+    }
+
+    // TODO: Vertices should carry the path's time for motion blur
+    let interaction = |v: &Vertex| Interaction {
+        p: v.p,
+        n: v.n.unwrap_or_else(|| Interaction::default().n),
+        ..Interaction::default()
+    };
+    let vis = VisibilityTester::new(interaction(v1), interaction(v0), None);
+    if let Some(collected_rays) = rays {
+        collected_rays.push(IntegratorRay {
+            ray: vis.ray(),
+            ray_type: RayType::Shadow,
+        });
+    }
+    *ray_count += 1;
+    if vis.unoccluded(scene) {
+        g
+    } else {
+        0.0
+    }
+}
+
+/// Returns the balance heuristic weight of the strategy with `s` light and `t` camera vertices.
+///
+/// `sampled` is the light vertex that replaces the first light subpath vertex when `s == 1`.
+fn mis_weight(
+    scene: &Scene,
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    sampled: Option<&Vertex>,
+    s: usize,
+    t: usize,
+) -> f32 {
+    if s + t == 2 {
+        return 1.0;
+    }
+
+    let qs = if s == 1 {
+        sampled
+    } else if s > 1 {
+        Some(&light_path[s - 1])
+    } else {
+        None
+    };
+    let qs_minus = if s > 1 {
+        Some(&light_path[s - 2])
+    } else {
+        None
+    };
+    let pt = &camera_path[t - 1];
+    let pt_minus = &camera_path[t - 2];
+
+    // Densities of the vertices are updated for this connection without touching the subpaths
+    let mut light_densities: Vec<VertexDensities> =
+        light_path[..s].iter().map(VertexDensities::from).collect();
+    let mut camera_densities: Vec<VertexDensities> =
+        camera_path[..t].iter().map(VertexDensities::from).collect();
+    if let Some(sampled) = sampled {
+        light_densities[0] = VertexDensities::from(sampled);
+    }
+
+    // Connection vertices can't be deltas
+    camera_densities[t - 1].delta = false;
+    if s > 0 {
+        light_densities[s - 1].delta = false;
+    }
+
+    camera_densities[t - 1].pdf_rev = if let Some(qs) = qs {
+        qs.pdf(scene, qs_minus, pt)
+    } else {
+        pt.pdf_light_origin(scene, pt_minus)
+    };
+    camera_densities[t - 2].pdf_rev = if let Some(qs) = qs {
+        pt.pdf(scene, Some(qs), pt_minus)
+    } else {
+        pt.pdf_light(scene, pt_minus)
+    };
+    if let Some(qs) = qs {
+        light_densities[s - 1].pdf_rev = pt.pdf(scene, Some(pt_minus), qs);
+        if let Some(qs_minus) = qs_minus {
+            light_densities[s - 2].pdf_rev = qs.pdf(scene, Some(pt), qs_minus);
+        }
+    }
+
+    let remap0 = |f: f32| if f == 0.0 { 1.0 } else { f };
+
+    let mut sum_ri = 0.0;
+    // Strategies with less than two camera vertices are not used
+    let mut ri = 1.0;
+    for i in (2..t).rev() {
+        ri *= remap0(camera_densities[i].pdf_rev) / remap0(camera_densities[i].pdf_fwd);
+        if !camera_densities[i].delta && !camera_densities[i - 1].delta {
+            sum_ri += ri;
+        }
+    }
+
+    let mut ri = 1.0;
+    for i in (0..s).rev() {
+        ri *= remap0(light_densities[i].pdf_rev) / remap0(light_densities[i].pdf_fwd);
+        let delta_light_vertex = if i > 0 {
+            light_densities[i - 1].delta
+        } else {
+            sampled.unwrap_or(&light_path[0]).is_delta_light()
+        };
+        if !light_densities[i].delta && !delta_light_vertex {
+            sum_ri += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum_ri)
+}
+
+/// Collects the segments of `camera_path` for debug visualization.
+fn collect_camera_path_rays(
+    scene: &Scene,
+    camera_path: &[Vertex],
+    collected_rays: &mut Vec<IntegratorRay>,
+) {
+    let min_debug_ray_length = {
+        let bounds = scene.bvh.bounds();
+        let i = bounds.maximum_extent();
+        (bounds.p_max[i] - bounds.p_min[i]) / 10.0
+    };
+
+    for (i, (v0, v1)) in camera_path
+        .iter()
+        .zip(camera_path.iter().skip(1))
+        .enumerate()
+    {
+        let d = v1.p - v0.p;
+        let ray_type = match &v0.content {
+            VertexContent::Surface { si, .. } => {
+                if d.dot_n(si.n) * si.wo.dot_n(si.n) > 0.0 {
+                    RayType::Reflection
+                } else {
+                    RayType::Refraction
+                }
+            }
+            _ => RayType::Direct,
+        };
+        debug_assert!(i > 0 || ray_type == RayType::Direct);
+
+        let ray = if let VertexContent::Background = v1.content {
+            let ray = Ray::new(v0.p, d.normalized(), f32::INFINITY);
+            let t_max = scene
+                .bvh
+                .bounds()
+                .intersections(ray)
+                .map_or(min_debug_ray_length, |(_, t_max)| t_max);
+            Ray::new(ray.o, ray.d, t_max)
+        } else {
+            let dist = d.len();
+            Ray::new(v0.p, d / dist, dist)
+        };
+        collected_rays.push(IntegratorRay { ray, ray_type });
+    }
+}
+
+impl Integrator for BDPT {
+    fn li(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        _depth: u32,
+        sampler: &mut Box<dyn Sampler>,
+    ) -> RadianceResult {
+        self.li_internal(scratch, ray, scene, sampler, None)
+    }
+
+    fn li_debug(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        _depth: u32,
+        sampler: &mut Box<dyn Sampler>,
+        rays: &mut Vec<IntegratorRay>,
+        _records: &mut Vec<DebugRecord>,
+    ) -> RadianceResult {
+        self.li_internal(scratch, ray, scene, sampler, Some(rays))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::Params;
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        integrators::{Integrator, IntegratorType, PathParams},
+        lights::{power_distribution, AreaLight, DiffuseAreaLight, Light, RectangularLight},
+        materials::{Material, Matte},
+        math::{transforms::translation, Point2, Point3, Ray, Spectrum, Transform, Vec2, Vec3},
+        sampling::{Sampler, UniformParams, UniformSampler},
+        scene::{Scene, SceneLoadSettings},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    /// Creates a diffuse floor lit by a small area light.
+    fn floor_scene() -> Scene {
+        let matte = |reflectance: f32| -> Arc<dyn Material> {
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones() * reflectance)),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            ))
+        };
+        let quad = |points: Vec<Point3<f32>>| {
+            Arc::new(Mesh::new(
+                &Transform::default(),
+                vec![0, 1, 2, 0, 2, 3],
+                points,
+                Vec::new(),
+                Vec::new(),
+            ))
+        };
+
+        let light = Arc::new(RectangularLight::new(
+            &translation(Vec3::new(0.0, 1.0, 0.0)),
+            Spectrum::ones() * 10.0,
+            Vec2::new(0.5, 0.5),
+        ));
+
+        // Facing -y like the light
+        let light_mesh = quad(vec![
+            Point3::new(-0.25, 1.0, -0.25),
+            Point3::new(0.25, 1.0, -0.25),
+            Point3::new(0.25, 1.0, 0.25),
+            Point3::new(-0.25, 1.0, 0.25),
+        ]);
+        // Facing +y
+        let floor_mesh = quad(vec![
+            Point3::new(-2.0, 0.0, -2.0),
+            Point3::new(-2.0, 0.0, 2.0),
+            Point3::new(2.0, 0.0, 2.0),
+            Point3::new(2.0, 0.0, -2.0),
+        ]);
+
+        let black = matte(0.0);
+        let gray = matte(0.5);
+        let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
+        for v0 in [0, 3] {
+            shapes.push(Arc::new(Triangle::new(
+                Arc::clone(&light_mesh),
+                v0,
+                Arc::clone(&black),
+                Some(Arc::clone(&light) as Arc<dyn AreaLight>),
+            )));
+            shapes.push(Arc::new(Triangle::new(
+                Arc::clone(&floor_mesh),
+                v0,
+                Arc::clone(&gray),
+                None,
+            )));
+        }
+
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
+        Scene {
+            name: "BDPT test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![light_mesh, floor_mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    /// Creates a closed box where every wall emits a radiance of one and reflects `albedo`.
+    ///
+    /// The walls have different areas so the lights aren't picked uniformly.
+    fn furnace_scene(albedo: f32) -> Scene {
+        let corner = |i: usize| {
+            let side = |bit: usize, extent: f32| if i & bit == 0 { -extent } else { extent };
+            Point3::new(side(4, 0.5), side(2, 1.0), side(1, 2.0))
+        };
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![
+                0, 1, 3, 0, 3, 2, // -x
+                4, 5, 7, 4, 7, 6, // +x
+                0, 1, 5, 0, 5, 4, // -y
+                2, 3, 7, 2, 7, 6, // +y
+                0, 2, 6, 0, 6, 4, // -z
+                1, 3, 7, 1, 7, 5, // +z
+            ],
+            (0..8).map(corner).collect(),
+            Vec::new(),
+            Vec::new(),
+        ));
+
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones() * albedo)),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let mut lights: Vec<Arc<dyn Light>> = Vec::new();
+        let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
+        for v0 in (0..mesh.indices.len()).step_by(3) {
+            let light = Arc::new(DiffuseAreaLight::new(&mesh, v0, Spectrum::ones(), true));
+            lights.push(Arc::clone(&light) as Arc<dyn Light>);
+            shapes.push(Arc::new(Triangle::new(
+                Arc::clone(&mesh),
+                v0,
+                Arc::clone(&material),
+                Some(light as Arc<dyn AreaLight>),
+            )));
+        }
+
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
+        Scene {
+            name: "BDPT furnace".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    fn mean_radiance(
+        integrator: &dyn Integrator,
+        scene: &Scene,
+        ray: Ray<f32>,
+        sample_count: u32,
+    ) -> Spectrum<f32> {
+        let mut allocator = LinearAllocator::new(1024 * 1024);
+        let scratch = ScopedScratch::new(&mut allocator);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams {
+                pixel_samples: sample_count,
+            },
+            false,
+        ));
+
+        let mut sum = Spectrum::zeros();
+        for i in 0..sample_count {
+            sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+            let sample_scratch = ScopedScratch::new_scope(&scratch);
+            sum += integrator
+                .li(&sample_scratch, ray, scene, 0, &mut sampler)
+                .li;
+        }
+        sum / (sample_count as f32)
+    }
+
+    #[test]
+    fn matches_path() {
+        let scene = floor_scene();
+        let o = Point3::new(0.0, 0.5, -1.0);
+        let ray = Ray::new(
+            o,
+            (Point3::new(0.3, 0.0, 0.2) - o).normalized(),
+            f32::INFINITY,
+        );
+
+        let sample_count = 1 << 14;
+        let bdpt = IntegratorType::BDPT(Params {
+            max_depth: 3,
+            background_intensity: 1.0,
+        })
+        .instantiate();
+        let path = IntegratorType::Path(PathParams {
+            max_depth: 3,
+            rr_start_depth: 3,
+            indirect_clamp: None,
+            background_intensity: 1.0,
+            transmissive_shadows: false,
+        })
+        .instantiate();
+
+        let bdpt_l = mean_radiance(bdpt.as_ref(), &scene, ray, sample_count).luminance();
+        let path_l = mean_radiance(path.as_ref(), &scene, ray, sample_count).luminance();
+
+        assert!(path_l > 0.0);
+        assert!(
+            ((bdpt_l - path_l) / path_l).abs() < 0.05,
+            "BDPT {} Path {}",
+            bdpt_l,
+            path_l
+        );
+    }
+
+    #[test]
+    fn white_furnace() {
+        let albedo = 0.5;
+        let max_depth = 3;
+        let scene = furnace_scene(albedo);
+        let ray = Ray::new(
+            Point3::new(0.1, 0.2, -0.3),
+            Vec3::new(0.3, -0.4, 0.8).normalized(),
+            f32::INFINITY,
+        );
+
+        let bdpt = IntegratorType::BDPT(Params {
+            max_depth,
+            background_intensity: 1.0,
+        })
+        .instantiate();
+        let l = mean_radiance(bdpt.as_ref(), &scene, ray, 1 << 14).luminance();
+
+        // Every bounce adds the emission of the walls scaled by the albedo once more
+        let mut expected = 0.0;
+        let mut scale = 1.0;
+        for _ in 0..=max_depth {
+            expected += scale;
+            scale *= albedo;
+        }
+        assert!(
+            ((l - expected) / expected).abs() < 0.03,
+            "BDPT {} expected {}",
+            l,
+            expected
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use super::{
+    escaped_le, light_intensity, DebugRecord, Integrator, IntegratorRay, RadianceResult, RayType,
+};
+use crate::{
+    bvh::IntersectionResult,
+    interaction::{Interaction, SurfaceInteraction},
+    lights::{Light, LightSample},
+    materials::{Bsdf, BxdfType},
+    math::{Ray, Spectrum},
+    sampling::Sampler,
+    scene::Scene,
+    shapes::Hit,
+};
+
+use allocators::ScopedScratch;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString, EnumVariantNames};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Direct_Lighting
+
+#[derive(Copy, Clone, Deserialize, Serialize, Display, EnumVariantNames, EnumString, PartialEq)]
+pub enum LightStrategy {
+    /// Takes a sample from each light
+    UniformAll,
+    /// Takes a sample from a single uniformly chosen light
+    UniformOne,
+    /// Takes a sample from a single light chosen proportional to its power
+    Power,
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Params {
+    pub strategy: LightStrategy,
+    pub background_intensity: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            strategy: LightStrategy::UniformAll,
+            background_intensity: 1.0,
+        }
+    }
+}
+
+/// Evaluates emission and direct lighting at the first hit without following any bounces
+pub struct DirectLighting {
+    strategy: LightStrategy,
+    background_intensity: f32,
+}
+
+impl DirectLighting {
+    pub fn new(params: Params) -> Self {
+        Self {
+            strategy: params.strategy,
+            background_intensity: params.background_intensity,
+        }
+    }
+
+    /// Returns the light reflected toward `si.wo` from a sample on `light`.
+    fn sample_light(
+        &self,
+        light: &dyn Light,
+        si: &SurfaceInteraction,
+        bsdf: &Bsdf,
+        scene: &Scene,
+        sampler: &mut Box<dyn Sampler>,
+        rays: &mut Option<&mut Vec<IntegratorRay>>,
+    ) -> Spectrum<f32> {
+        let LightSample { l, li, vis, pdf } =
+            light.sample_li(&Interaction::from(si), sampler.get_2d());
+        if li.is_black() || pdf == 0.0 {
+            return Spectrum::zeros();
+        }
+
+        let f = bsdf.f(si.wo, l, BxdfType::all());
+        if let Some(test) = vis {
+            if let Some(collected_rays) = rays {
+                collected_rays.push(IntegratorRay {
+                    ray: test.ray(),
+                    ray_type: RayType::Shadow,
+                });
+            }
+            if !f.is_black() && test.unoccluded(scene) {
+                let scale = light_intensity(light, self.background_intensity);
+                return f * li * scale * si.shading.n.dot_v(l).abs() / pdf;
+            }
+        }
+        Spectrum::zeros()
+    }
+
+    // Always inline to have the compiler strip out ray collection in li()-calls
+    #[inline(always)]
+    fn li_internal(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        sampler: &mut Box<dyn Sampler>,
+        mut rays: Option<&mut Vec<IntegratorRay>>,
+    ) -> RadianceResult {
+        let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
+
+        if let Some(collected_rays) = &mut rays {
+            collected_rays.push(IntegratorRay {
+                ray: Ray::new(ray.o, ray.d, ray.t_max),
+                ray_type: RayType::Direct,
+            });
+        }
+
+        let li = if let Some(Hit { si, t, shape }) = hit {
+            if let Some(collected_rays) = &mut rays {
+                let min_debug_ray_length = {
+                    let bounds = scene.bvh.bounds();
+                    let i = bounds.maximum_extent();
+                    (bounds.p_max[i] - bounds.p_min[i]) / 10.0
+                };
+                collected_rays.last_mut().unwrap().ray.t_max = t;
+                collected_rays.push(IntegratorRay {
+                    ray: Ray::new(si.p, si.n.into(), min_debug_ray_length),
+                    ray_type: RayType::Normal,
+                });
+            }
+
+            let bsdf = shape.compute_scattering_functions(scratch, &si);
+
+            let direct = match self.strategy {
+                LightStrategy::UniformAll => scene.lights.iter().fold(Spectrum::zeros(), |c, l| {
+                    c + self.sample_light(l.as_ref(), &si, &bsdf, scene, sampler, &mut rays)
+                }),
+                LightStrategy::UniformOne => {
+                    let light_count = scene.lights.len();
+                    if light_count == 0 {
+                        Spectrum::zeros()
+                    } else {
+                        #[allow(clippy::cast_sign_loss)] // Always expect u in [0, 1)
+                        let light_index = ((sampler.get_1d() * (light_count as f32)) as usize)
+                            .min(light_count - 1);
+                        let light = scene.lights[light_index].as_ref();
+                        self.sample_light(light, &si, &bsdf, scene, sampler, &mut rays)
+                            * (light_count as f32)
+                    }
+                }
+                LightStrategy::Power => match scene.sample_light(sampler.get_1d()) {
+                    Some((light, light_pdf)) if light_pdf > 0.0 => {
+                        self.sample_light(light, &si, &bsdf, scene, sampler, &mut rays) / light_pdf
+                    }
+                    _ => Spectrum::zeros(),
+                },
+            };
+
+            direct + si.emitted_radiance(-ray.d)
+        } else {
+            let le = escaped_le(scene, ray, self.background_intensity);
+            scene.background * self.background_intensity + le
+        };
+
+        RadianceResult {
+            li,
+            ray_scene_intersections: 1,
+        }
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn li(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        _depth: u32,
+        sampler: &mut Box<dyn Sampler>,
+    ) -> RadianceResult {
+        self.li_internal(scratch, ray, scene, sampler, None)
+    }
+
+    fn li_debug(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        _depth: u32,
+        sampler: &mut Box<dyn Sampler>,
+        rays: &mut Vec<IntegratorRay>,
+        _records: &mut Vec<DebugRecord>,
+    ) -> RadianceResult {
+        self.li_internal(scratch, ray, scene, sampler, Some(rays))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::{LightStrategy, Params};
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        integrators::{Integrator, IntegratorType},
+        lights::{power_distribution, Light, RectangularLight},
+        materials::{Material, Matte},
+        math::{transforms::translation, Point2, Point3, Ray, Spectrum, Transform, Vec2, Vec3},
+        sampling::{Sampler, UniformParams, UniformSampler},
+        scene::{Scene, SceneLoadSettings},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    /// Creates a diffuse floor lit by a small area light without geometry.
+    fn floor_scene() -> Scene {
+        let floor_mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 0, 2, 3],
+            vec![
+                Point3::new(-2.0, 0.0, -2.0),
+                Point3::new(-2.0, 0.0, 2.0),
+                Point3::new(2.0, 0.0, 2.0),
+                Point3::new(2.0, 0.0, -2.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let gray: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones() * 0.5)),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let shapes: Vec<Arc<dyn Shape>> = [0, 3]
+            .into_iter()
+            .map(|v0| {
+                Arc::new(Triangle::new(
+                    Arc::clone(&floor_mesh),
+                    v0,
+                    Arc::clone(&gray),
+                    None,
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+
+        let light = Arc::new(RectangularLight::new(
+            &translation(Vec3::new(0.0, 1.0, 0.0)),
+            Spectrum::ones() * 10.0,
+            Vec2::new(0.5, 0.5),
+        ));
+
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
+        Scene {
+            name: "Direct lighting test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![floor_mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    fn mean_radiance(
+        integrator: &dyn Integrator,
+        scene: &Scene,
+        ray: Ray<f32>,
+        sample_count: u32,
+    ) -> Spectrum<f32> {
+        let mut allocator = LinearAllocator::new(1024 * 1024);
+        let scratch = ScopedScratch::new(&mut allocator);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams {
+                pixel_samples: sample_count,
+            },
+            false,
+        ));
+
+        let mut sum = Spectrum::zeros();
+        for i in 0..sample_count {
+            sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+            let sample_scratch = ScopedScratch::new_scope(&scratch);
+            sum += integrator
+                .li(&sample_scratch, ray, scene, 0, &mut sampler)
+                .li;
+        }
+        sum / (sample_count as f32)
+    }
+
+    #[test]
+    fn strategies_agree_with_one_light() {
+        let scene = floor_scene();
+        let o = Point3::new(0.0, 0.5, -1.0);
+        let ray = Ray::new(
+            o,
+            (Point3::new(0.3, 0.0, 0.2) - o).normalized(),
+            f32::INFINITY,
+        );
+
+        let sample_count = 1 << 12;
+        let integrator = |strategy| {
+            IntegratorType::DirectLighting(Params {
+                strategy,
+                background_intensity: 1.0,
+            })
+            .instantiate()
+        };
+        let all = mean_radiance(
+            integrator(LightStrategy::UniformAll).as_ref(),
+            &scene,
+            ray,
+            sample_count,
+        )
+        .luminance();
+        assert!(all > 0.0);
+
+        for strategy in [LightStrategy::UniformOne, LightStrategy::Power] {
+            let one =
+                mean_radiance(integrator(strategy).as_ref(), &scene, ray, sample_count).luminance();
+            assert!(
+                ((one - all) / all).abs() < 0.02,
+                "{} {} UniformAll {}",
+                strategy,
+                one,
+                all
+            );
+        }
+    }
+}
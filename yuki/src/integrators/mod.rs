@@ -1,15 +1,21 @@
+mod bdpt;
 mod bvh_heatmap;
+mod direct_lighting;
 mod geometry_normals;
 mod path;
 mod shading_normals;
 mod shading_uvs;
+mod sppm;
 mod whitted;
 
+use bdpt::BDPT;
 use bvh_heatmap::BVHIntersections;
+use direct_lighting::DirectLighting;
 use geometry_normals::GeometryNormals;
 use path::Path;
 use shading_normals::ShadingNormals;
 use shading_uvs::ShadingUVs;
+use sppm::SPPM;
 use whitted::Whitted;
 
 use allocators::ScopedScratch;
@@ -17,22 +23,33 @@ use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString, EnumVariantNames};
 
 use crate::{
+    bvh::IntersectionResult,
     camera::{Camera, CameraSample},
-    film::FilmTile,
-    math::{Point2, Ray, Spectrum, Vec2},
+    film::{AovBuffers, FilmTile},
+    filters::Filter,
+    lights::{Light, LightType},
+    materials::{BxdfSample, BxdfType},
+    math::{Bounds2, Point2, Ray, Spectrum, Vec2, Vec3},
     sampling::Sampler,
     scene::Scene,
+    shapes::Hit,
 };
 
 use std::sync::Arc;
 
 pub type WhittedParams = whitted::Params;
+pub type DirectLightingParams = direct_lighting::Params;
 pub type PathParams = path::Params;
+pub type BDPTParams = bdpt::Params;
+pub type SPPMParams = sppm::Params;
 
 #[derive(Copy, Clone, Deserialize, Serialize, Display, EnumVariantNames, EnumString)]
 pub enum IntegratorType {
     Whitted(whitted::Params),
+    DirectLighting(direct_lighting::Params),
     Path(path::Params),
+    BDPT(bdpt::Params),
+    SPPM(sppm::Params),
     BVHIntersections,
     GeometryNormals,
     ShadingNormals,
@@ -43,7 +60,10 @@ impl IntegratorType {
     pub fn instantiate(self) -> Box<dyn Integrator> {
         match self {
             IntegratorType::Whitted(params) => Box::new(Whitted::new(params)),
+            IntegratorType::DirectLighting(params) => Box::new(DirectLighting::new(params)),
             IntegratorType::Path(params) => Box::new(Path::new(params)),
+            IntegratorType::BDPT(params) => Box::new(BDPT::new(params)),
+            IntegratorType::SPPM(params) => Box::new(SPPM::new(params)),
             IntegratorType::BVHIntersections => Box::new(BVHIntersections {}),
             IntegratorType::GeometryNormals => Box::new(GeometryNormals {}),
             IntegratorType::ShadingNormals => Box::new(ShadingNormals {}),
@@ -59,6 +79,29 @@ impl Default for IntegratorType {
     }
 }
 
+fn default_background_intensity() -> f32 {
+    1.0
+}
+
+/// Returns the scale `background_intensity` applies to the radiance of `light`.
+///
+/// Lights at infinity are scaled like the background as they light the scene from the same
+/// environment.
+fn light_intensity(light: &dyn Light, background_intensity: f32) -> f32 {
+    if light.flags().contains(LightType::INFINITE) {
+        background_intensity
+    } else {
+        1.0
+    }
+}
+
+/// Returns the radiance the lights of `scene` contribute to `ray` escaping it.
+fn escaped_le(scene: &Scene, ray: Ray<f32>, background_intensity: f32) -> Spectrum<f32> {
+    scene.lights.iter().fold(Spectrum::zeros(), |c, l| {
+        c + l.le(ray) * light_intensity(l.as_ref(), background_intensity)
+    })
+}
+
 pub struct RadianceResult {
     pub li: Spectrum<f32>,
     pub ray_scene_intersections: usize,
@@ -88,6 +131,58 @@ pub enum RayType {
     Shadow,
 }
 
+/// Event on a path traced by [`Integrator::li_debug`], used to diagnose e.g. dark pixels
+#[derive(Debug)]
+pub enum DebugRecord {
+    /// Light sampled from a hit and what it added to the path
+    LightSample {
+        bounce: u32,
+        /// Index into [`Scene::lights`]
+        light: usize,
+        contribution: Spectrum<f32>,
+        /// `true` if the shadow ray was blocked
+        occluded: bool,
+    },
+    /// Lobe sampled from the bsdf of a hit to continue the path, [`BxdfType::NONE`] if no lobe
+    /// matched
+    Scattering {
+        bounce: u32,
+        lobe: BxdfType,
+        pdf: f32,
+    },
+    /// Total radiance of a path
+    PathRadiance(Spectrum<f32>),
+}
+
+impl std::fmt::Display for DebugRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebugRecord::LightSample {
+                bounce,
+                light,
+                contribution,
+                occluded,
+            } => {
+                if *occluded {
+                    write!(f, "  {}: light {} occluded", bounce, light)
+                } else {
+                    write!(
+                        f,
+                        "  {}: light {} added ({:.3}, {:.3}, {:.3})",
+                        bounce, light, contribution.r, contribution.g, contribution.b
+                    )
+                }
+            }
+            DebugRecord::Scattering { bounce, lobe, pdf } => {
+                write!(f, "  {}: sampled {:?} with pdf {:.3}", bounce, lobe, pdf)
+            }
+            DebugRecord::PathRadiance(li) => {
+                write!(f, "Path radiance ({:.3}, {:.3}, {:.3})", li.r, li.g, li.b)
+            }
+        }
+    }
+}
+
 // Public interface for scene integrators.
 pub trait Integrator {
     /// Evaluates the incoming radiance along `ray`. Also returns the number of rays intersected with `scene`.
@@ -100,7 +195,7 @@ pub trait Integrator {
         sampler: &mut Box<dyn Sampler>,
     ) -> RadianceResult;
 
-    /// Should be identical with li() but also fill out debug data like rays.
+    /// Should be identical with li() but also fill out debug data like rays and path events.
     fn li_debug(
         &self,
         _scratch: &ScopedScratch,
@@ -109,6 +204,7 @@ pub trait Integrator {
         _depth: u32,
         _sampler: &mut Box<dyn Sampler>,
         _rays: &mut Vec<IntegratorRay>,
+        _records: &mut Vec<DebugRecord>,
     ) -> RadianceResult {
         RadianceResult {
             li: Spectrum::zeros(),
@@ -117,23 +213,37 @@ pub trait Integrator {
     }
 
     /// Renders the given `Tile`. Returns the number of rays intersected with `scene`.
+    ///
+    /// Samples are splatted to the pixels within `filter`'s radius that are inside the tile.
+    /// Pixels around the tile are also sampled so that the filter isn't cut at the tile edges.
+    /// Also writes the filtered mean of the squared sample luminances of each pixel into
+    /// `tile_luminance_sqr` and the mean first hit AOVs into `tile_aovs` if given.
     fn render(
         &self,
         scratch: &ScopedScratch,
         scene: &Scene,
         camera: &Camera,
         sampler: &Arc<dyn Sampler>,
+        filter: &dyn Filter,
         accumulating: bool,
         tile: &mut FilmTile,
         tile_pixels: &mut [Spectrum<f32>],
+        tile_luminance_sqr: &mut [f32],
+        mut tile_aovs: Option<&mut AovBuffers>,
         early_termination_predicate: &mut dyn FnMut() -> bool,
     ) -> usize {
-        assert!(tile_pixels.len() >= tile.bb.area() as usize);
+        let pixel_count = tile.bb.area() as usize;
+        assert!(tile_pixels.len() >= pixel_count);
+        assert!(tile_luminance_sqr.len() >= pixel_count);
+        if let Some(aovs) = &mut tile_aovs {
+            aovs.clear(pixel_count);
+        }
 
         let tile_width = tile.bb.width();
 
-        // Init per tile to try and get as deterministic results as possible between runs
-        // This makes the rng the same per tile regardless of which threads take which tiles
+        // Init per tile to get deterministic results between runs
+        // Samples only depend on the pixel and the sample index so the result doesn't depend on
+        // which threads take which tiles or in which order
         // Of course, this is useful only for debug but the init hit is miniscule in comparison to render time
         let corner = tile.bb.p_min;
         #[allow(unused_comparisons)] // Just in case these are changed from u16 for some reason
@@ -141,14 +251,39 @@ pub trait Integrator {
         assert!(types_fit);
         let mut sampler = sampler.as_ref().clone();
 
+        tile_pixels[..pixel_count].fill(Spectrum::zeros());
+        tile_luminance_sqr[..pixel_count].fill(0.0);
+        let mut filter_weights = vec![0.0; pixel_count];
+
+        let filter_radius = filter.radius();
+        let (tile_min, tile_max) = (tile.bb.p_min, tile.bb.p_max);
+
+        // Samples of the pixels around the tile also land within the filter radius of the pixels
+        // on its border. They are traced here too so that the border pixels get the full filter
+        // instead of leaving seams on the tile grid. Samples only depend on the pixel and the
+        // sample index so neighboring tiles trace matching samples.
+        #[allow(clippy::cast_sign_loss)] // Clamped to zero
+        let guard_band = (filter_radius - 0.5).max(0.0).ceil() as u16;
+        let film_res = camera.film_res();
+        let sample_bb = Bounds2::new(
+            Point2::new(
+                tile_min.x.saturating_sub(guard_band),
+                tile_min.y.saturating_sub(guard_band),
+            ),
+            Point2::new(
+                tile_max.x.saturating_add(guard_band).min(film_res.x),
+                tile_max.y.saturating_add(guard_band).min(film_res.y),
+            ),
+        );
+
         let mut ray_count = 0;
-        for p in tile.bb {
-            let mut color = Spectrum::zeros();
-            let sample_count = if accumulating {
-                1
-            } else {
-                sampler.samples_per_pixel()
-            };
+        let sample_count = if accumulating {
+            1
+        } else {
+            sampler.samples_per_pixel()
+        };
+        for p in sample_bb {
+            let in_tile = tile.bb.inside_exclusive(p);
             for sample_index in 0..sample_count {
                 if early_termination_predicate() {
                     return ray_count;
@@ -164,23 +299,331 @@ pub trait Integrator {
 
                 let sample_scratch = ScopedScratch::new_scope(scratch);
 
-                let p_film = Point2::new(p.x as f32, p.y as f32) + sampler.get_2d();
+                let p_film = Point2::new(p.x as f32, p.y as f32) + sampler.get_pixel_2d();
+                let p_lens = sampler.get_2d();
+                let time = sampler.get_1d();
 
-                let ray = camera.ray(&CameraSample { p_film });
+                let mut ray = camera.ray_differential(&CameraSample {
+                    p_film,
+                    p_lens,
+                    time,
+                });
+                // Texture filtering should match the spacing of the samples in the pixel
+                ray.scale_differentials(1.0 / (sampler.samples_per_pixel() as f32).sqrt());
 
                 let result = self.li(&sample_scratch, ray, scene, 0, &mut sampler);
-                color += result.li;
+                let luminance_sqr = result.li.luminance().powi(2);
                 ray_count += result.ray_scene_intersections;
+
+                if let (true, Some(aovs)) = (in_tile, &mut tile_aovs) {
+                    let Vec2 { x, y } = p - tile_min;
+                    let pixel_offset = (y * tile_width + x) as usize;
+                    ray_count += add_first_hit_aovs(
+                        &sample_scratch,
+                        ray,
+                        scene,
+                        &mut sampler,
+                        aovs,
+                        pixel_offset,
+                    );
+                }
+
+                // Splat to the pixels whose centers are within the filter radius
+                // Pixels outside the tile get this sample when their own tile is rendered
+                let x_range = ((p_film.x - 0.5 - filter_radius).ceil() as i32)
+                    .max(tile_min.x as i32)
+                    ..=((p_film.x - 0.5 + filter_radius).floor() as i32).min(tile_max.x as i32 - 1);
+                let y_range = ((p_film.y - 0.5 - filter_radius).ceil() as i32)
+                    .max(tile_min.y as i32)
+                    ..=((p_film.y - 0.5 + filter_radius).floor() as i32).min(tile_max.y as i32 - 1);
+                for y in y_range {
+                    for x in x_range.clone() {
+                        let weight = filter.evaluate(Point2::new(
+                            p_film.x - (x as f32 + 0.5),
+                            p_film.y - (y as f32 + 0.5),
+                        ));
+                        #[allow(clippy::cast_sign_loss)] // Ranges are clamped to the tile
+                        let pixel_offset = ((y - tile_min.y as i32) * (tile_width as i32)
+                            + (x - tile_min.x as i32))
+                            as usize;
+                        tile_pixels[pixel_offset] += result.li * weight;
+                        tile_luminance_sqr[pixel_offset] += luminance_sqr * weight;
+                        filter_weights[pixel_offset] += weight;
+                    }
+                }
             }
-            color /= sample_count as f32;
-
-            let Vec2 {
-                x: tile_x,
-                y: tile_y,
-            } = p - tile.bb.p_min;
-            let pixel_offset = (tile_y * tile_width + tile_x) as usize;
-            tile_pixels[pixel_offset] = color;
         }
+
+        for ((color, luminance_sqr), &weight) in tile_pixels
+            .iter_mut()
+            .zip(tile_luminance_sqr.iter_mut())
+            .zip(filter_weights.iter())
+        {
+            if weight != 0.0 {
+                *color /= weight;
+                *luminance_sqr /= weight;
+            }
+        }
+        if let Some(aovs) = tile_aovs {
+            aovs.scale(pixel_count, 1.0 / (sample_count as f32));
+        }
+
         ray_count
     }
 }
+
+/// Adds the albedo and shading normal at the first hit of `ray` to `pixel_offset` in `aovs`.
+/// Returns the number of rays intersected with `scene`.
+///
+/// The albedo is a single sample estimate of the reflectance of the first hit [`Bsdf`], so
+/// specular surfaces get their tint instead of black.
+///
+/// [`Bsdf`]: crate::materials::Bsdf
+fn add_first_hit_aovs(
+    scratch: &ScopedScratch,
+    ray: Ray<f32>,
+    scene: &Scene,
+    sampler: &mut Box<dyn Sampler>,
+    aovs: &mut AovBuffers,
+    pixel_offset: usize,
+) -> usize {
+    let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
+    if let Some(Hit { si, shape, .. }) = hit {
+        let bsdf = shape.compute_scattering_functions(scratch, &si);
+        let n = bsdf.shading_normal();
+
+        let BxdfSample { wi, f, pdf, .. } = bsdf.sample_f(si.wo, sampler.get_2d(), BxdfType::all());
+        if pdf > 0.0 {
+            aovs.albedo[pixel_offset] += f * n.dot_v(wi).abs() / pdf;
+        }
+        aovs.normal[pixel_offset] += Vec3::from(n);
+    }
+
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use approx::abs_diff_eq;
+    use std::sync::Arc;
+
+    use super::{Integrator, IntegratorType, PathParams, RadianceResult, WhittedParams};
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        camera::Camera,
+        film::{FilmSettings, FilmTile},
+        filters::BoxFilter,
+        lights::{power_distribution, DistantLight, Light},
+        materials::{Material, Matte},
+        math::{Bounds2, Point2, Point3, Ray, Spectrum, Transform, Vec2, Vec3},
+        sampling::{Sampler, UniformParams, UniformSampler},
+        scene::{Scene, SceneLoadSettings},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    /// Returns radiance that varies linearly with the ray direction, ignoring the scene.
+    struct DirectionGradient {
+        gradient: f32,
+    }
+
+    impl Integrator for DirectionGradient {
+        fn li(
+            &self,
+            _scratch: &ScopedScratch,
+            ray: Ray<f32>,
+            _scene: &Scene,
+            _depth: u32,
+            _sampler: &mut Box<dyn Sampler>,
+        ) -> RadianceResult {
+            RadianceResult {
+                li: Spectrum::ones() * (1.0 + self.gradient * ray.d.x),
+                ray_scene_intersections: 0,
+            }
+        }
+    }
+
+    /// Renders the film one tile at a time with a box filter of radius 2.
+    fn render_tiles(
+        integrator: &DirectionGradient,
+        res: Vec2<u16>,
+        tiles: &[Bounds2<u16>],
+    ) -> Vec<Spectrum<f32>> {
+        let (scene, camera_params, _, _) = Scene::cornell();
+        let camera = Camera::new(
+            camera_params,
+            FilmSettings {
+                res,
+                ..FilmSettings::default()
+            },
+        );
+        let sampler: Arc<dyn Sampler> = Arc::new(UniformSampler::with_seed(
+            UniformParams { pixel_samples: 4 },
+            false,
+            0x73B9_642E_74AC_471C,
+        ));
+        let filter = BoxFilter::new(2.0);
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let mut pixels = vec![Spectrum::zeros(); (res.x as usize) * (res.y as usize)];
+        for (i, &bb) in tiles.iter().enumerate() {
+            let mut tile = FilmTile::new(bb, i, 0, 0, 0);
+            let pixel_count = bb.area() as usize;
+            let mut tile_pixels = vec![Spectrum::zeros(); pixel_count];
+            let mut tile_luminance_sqr = vec![0.0; pixel_count];
+            integrator.render(
+                &scratch,
+                &scene,
+                &camera,
+                &sampler,
+                &filter,
+                false,
+                &mut tile,
+                &mut tile_pixels,
+                &mut tile_luminance_sqr,
+                None,
+                &mut || false,
+            );
+
+            for (p, &color) in bb.into_iter().zip(tile_pixels.iter()) {
+                pixels[(p.y as usize) * (res.x as usize) + (p.x as usize)] = color;
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn wide_filter_has_no_tile_seams() {
+        let res = Vec2::new(16, 8);
+        let full = [Bounds2::new(Point2::new(0, 0), Point2::new(16, 8))];
+        let split = [
+            Bounds2::new(Point2::new(0, 0), Point2::new(8, 8)),
+            Bounds2::new(Point2::new(8, 0), Point2::new(16, 8)),
+        ];
+        let border_pixels = |pixels: &[Spectrum<f32>]| {
+            (0..8)
+                .flat_map(|y| [7, 8].map(move |x| pixels[y * 16 + x]))
+                .collect::<Vec<_>>()
+        };
+
+        // Constant radiance has to stay constant on both sides of the tile border
+        let constant = DirectionGradient { gradient: 0.0 };
+        for color in border_pixels(&render_tiles(&constant, res, &split)) {
+            assert!((color.g - 1.0).abs() < 1e-5, "{:?}", color);
+        }
+
+        // Border pixels have to get the same filtered result as when the film is a single tile
+        let gradient = DirectionGradient { gradient: 4.0 };
+        let full_pixels = border_pixels(&render_tiles(&gradient, res, &full));
+        let split_pixels = border_pixels(&render_tiles(&gradient, res, &split));
+        for (full_color, split_color) in full_pixels.iter().zip(split_pixels.iter()) {
+            assert!(
+                (full_color.g - split_color.g).abs() < 1e-4,
+                "{:?} {:?}",
+                full_color,
+                split_color
+            );
+        }
+    }
+
+    /// Returns a scene with a single white triangle at depth `z`, facing the z axis.
+    fn triangle_scene(z: f32, lights: Vec<Arc<dyn Light>>, background: Spectrum<f32>) -> Scene {
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2],
+            vec![
+                Point3::new(-1.0, -1.0, z),
+                Point3::new(1.0, -1.0, z),
+                Point3::new(0.0, 1.0, z),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let shapes: Vec<Arc<dyn Shape>> = vec![Arc::new(Triangle::new(
+            Arc::clone(&mesh),
+            0,
+            material,
+            None,
+        ))];
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+        Scene {
+            name: "Background test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background,
+            medium: None,
+        }
+    }
+
+    /// Returns the radiance `integrator` finds along the z axis from the origin.
+    fn li_along_z(integrator: IntegratorType, scene: &Scene) -> Spectrum<f32> {
+        let ray = Ray::new(Point3::zeros(), Vec3::new(0.0, 0.0, 1.0), f32::INFINITY);
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::with_seed(
+            UniformParams { pixel_samples: 1 },
+            false,
+            0,
+        ));
+        integrator
+            .instantiate()
+            .li(&scratch, ray, scene, 0, &mut sampler)
+            .li
+    }
+
+    fn whitted(background_intensity: f32) -> IntegratorType {
+        IntegratorType::Whitted(WhittedParams {
+            background_intensity,
+            ..WhittedParams::default()
+        })
+    }
+
+    fn path(background_intensity: f32) -> IntegratorType {
+        IntegratorType::Path(PathParams {
+            background_intensity,
+            ..PathParams::default()
+        })
+    }
+
+    #[test]
+    fn background_intensity_scales_escaped_rays() {
+        // The triangle is behind the ray so that it escapes the scene
+        let scene = triangle_scene(-2.0, Vec::new(), Spectrum::new(0.25, 0.5, 0.75));
+        let li = |integrator| li_along_z(integrator, &scene);
+
+        assert_eq!(li(whitted(1.0)), scene.background);
+        assert_eq!(li(whitted(2.0)), scene.background * 2.0);
+        assert_eq!(li(path(1.0)), scene.background);
+        assert_eq!(li(path(2.0)), scene.background * 2.0);
+    }
+
+    #[test]
+    fn background_intensity_scales_lights_at_infinity() {
+        // The triangle is lit head on by a distant light behind the ray origin
+        let lights: Vec<Arc<dyn Light>> = vec![Arc::new(DistantLight::new(
+            Spectrum::ones(),
+            Vec3::new(0.0, 0.0, -1.0),
+        ))];
+        let scene = triangle_scene(2.0, lights, Spectrum::zeros());
+        let li = |integrator| li_along_z(integrator, &scene);
+
+        for integrator in [whitted, path] {
+            let lit = li(integrator(1.0));
+            assert!(!lit.is_black());
+            assert!(abs_diff_eq!(li(integrator(2.0)), lit * 2.0, epsilon = 1e-6));
+        }
+    }
+}
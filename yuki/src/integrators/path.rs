@@ -1,11 +1,14 @@
-use super::{Integrator, IntegratorRay, RadianceResult, RayType};
+use super::{
+    escaped_le, light_intensity, DebugRecord, Integrator, IntegratorRay, RadianceResult, RayType,
+};
 use crate::{
     bvh::IntersectionResult,
     interaction::Interaction,
-    lights::LightSample,
+    lights::{Light, LightSample},
     materials::{BxdfSample, BxdfType},
-    math::{Ray, Spectrum},
-    sampling::Sampler,
+    math::{Ray, Spectrum, Vec3},
+    media::MediumSample,
+    sampling::{power_heuristic, Sampler},
     scene::Scene,
     shapes::Hit,
 };
@@ -19,28 +22,69 @@ use serde::{Deserialize, Serialize};
 #[derive(Copy, Clone, Deserialize, Serialize)]
 pub struct Params {
     pub max_depth: u32,
+    /// Bounces after which paths are terminated with Russian roulette
+    #[serde(default = "default_rr_start_depth")]
+    pub rr_start_depth: u32,
+    /// Maximum component of a single indirect contribution, reduces fireflies at the cost of bias
     pub indirect_clamp: Option<f32>,
+    #[serde(default = "super::default_background_intensity")]
+    pub background_intensity: f32,
+    /// Lets light samples pass through specular transmitters like glass without refracting.
+    /// Paths that reach lights through them from a diffuse hit are skipped to avoid counting the
+    /// light twice, so caustics are approximated by colored shadows.
+    #[serde(default)]
+    pub transmissive_shadows: bool,
 }
 
 impl Default for Params {
     fn default() -> Self {
         Self {
             max_depth: 3,
+            rr_start_depth: default_rr_start_depth(),
             indirect_clamp: None,
+            background_intensity: super::default_background_intensity(),
+            transmissive_shadows: false,
         }
     }
 }
 
+fn default_rr_start_depth() -> u32 {
+    3
+}
+
 pub struct Path {
     max_depth: u32,
+    rr_start_depth: u32,
     indirect_clamp: Option<f32>,
+    background_intensity: f32,
+    transmissive_shadows: bool,
 }
 
 impl Path {
     pub fn new(params: Params) -> Self {
         Self {
             max_depth: params.max_depth,
+            rr_start_depth: params.rr_start_depth,
             indirect_clamp: params.indirect_clamp,
+            background_intensity: params.background_intensity,
+            transmissive_shadows: params.transmissive_shadows,
+        }
+    }
+
+    /// Scales `contribution` down so that its largest component is at most the indirect clamp.
+    ///
+    /// Contributions on the first hit are direct lighting and are never clamped.
+    fn clamp_contribution(&self, contribution: Spectrum<f32>, bounces: u32) -> Spectrum<f32> {
+        match self.indirect_clamp {
+            Some(max) if bounces > 0 => {
+                let max_comp = contribution.max_comp();
+                if max_comp > max {
+                    contribution * (max / max_comp)
+                } else {
+                    contribution
+                }
+            }
+            _ => contribution,
         }
     }
 
@@ -54,6 +98,7 @@ impl Path {
         _depth: u32,
         sampler: &mut Box<dyn Sampler>,
         mut rays: Option<&mut Vec<IntegratorRay>>,
+        mut records: Option<&mut Vec<DebugRecord>>,
     ) -> RadianceResult {
         let min_debug_ray_length = {
             let bounds = scene.bvh.bounds();
@@ -65,6 +110,11 @@ impl Path {
         let mut beta = Spectrum::ones();
         let mut bounces = 0;
         let mut specular_bounce = false;
+        // Set when the light sample at the last non-specular hit already covered the lights
+        // reached through the following specular transmissions
+        let mut light_sampled_through = false;
+        // Set to the last vertex and its phase pdf when the path scattered in the medium
+        let mut medium_vertex: Option<(Interaction, f32)> = None;
         let mut ray_count = 0;
         // Ray type is only updated and used if we're collecting into 'rays'
         let mut ray_type = RayType::Direct;
@@ -88,7 +138,92 @@ impl Path {
 
             let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
 
-            if let Some(Hit { si, t, shape }) = hit {
+            // Sample a scattering point in the medium before the surface
+            let mut scattering = None;
+            if let Some(medium) = &scene.medium {
+                let t_max = hit.as_ref().map_or(ray.t_max, |h| h.t);
+                let MediumSample {
+                    t,
+                    beta: medium_beta,
+                    phase,
+                } = medium.sample(Ray { t_max, ..ray }, sampler);
+                beta *= medium_beta;
+                if beta.is_black() {
+                    break;
+                }
+                scattering = t.map(|t| (t, phase));
+            }
+
+            if let Some((t, phase)) = scattering {
+                if let Some(collected_rays) = &mut rays {
+                    collected_rays.last_mut().unwrap().ray.t_max = t;
+                }
+
+                let it = Interaction {
+                    p: ray.point(t),
+                    time: ray.time,
+                    ..Interaction::default()
+                };
+                let wo = -ray.d.normalized();
+
+                // Sample one light proportional to power, weighted against the phase sample
+                let mut radiance = Spectrum::zeros();
+                if let Some((light, light_pdf)) = scene.sample_light(sampler.get_1d()) {
+                    let LightSample { l, li, vis, pdf } = light.sample_li(&it, sampler.get_2d());
+                    let p = phase.p(wo, l);
+                    if !li.is_black() && pdf > 0.0 && light_pdf > 0.0 && p > 0.0 {
+                        if let Some(test) = vis {
+                            if let Some(collected_rays) = &mut rays {
+                                collected_rays.push(IntegratorRay {
+                                    ray: test.ray(),
+                                    ray_type: RayType::Shadow,
+                                });
+                            }
+                            // The phase sample can't reach lights through specular transmitters
+                            // so those are left to the light sample alone
+                            let (mut transmittance, weight) = if test.unoccluded(scene) {
+                                let weight = if light.is_delta() {
+                                    1.0
+                                } else {
+                                    power_heuristic(pdf, p)
+                                };
+                                (Spectrum::ones(), weight)
+                            } else if self.transmissive_shadows {
+                                (test.unoccluded_transmittance(scratch, scene), 1.0)
+                            } else {
+                                (Spectrum::zeros(), 0.0)
+                            };
+                            if let Some(medium) = &scene.medium {
+                                transmittance *= medium.tr(test.ray());
+                            }
+                            radiance = li
+                                * light_intensity(light, self.background_intensity)
+                                * transmittance
+                                * (p * weight / (pdf * light_pdf));
+                            if let Some(records) = &mut records {
+                                records.push(DebugRecord::LightSample {
+                                    bounce: bounces,
+                                    light: light_index(scene, light),
+                                    contribution: beta * radiance,
+                                    occluded: transmittance.is_black(),
+                                });
+                            }
+                        }
+                    }
+                }
+                incoming_radiance += self.clamp_contribution(beta * radiance, bounces);
+
+                // The phase function is sampled exactly so beta stays as is
+                let (wi, phase_pdf) = phase.sample_p(wo, sampler.get_2d());
+                ray = Ray {
+                    time: ray.time,
+                    ..Ray::new(it.p, wi, f32::INFINITY)
+                };
+                // Emission the scattered path hits is weighted against the light sample
+                medium_vertex = Some((it, phase_pdf));
+                specular_bounce = false;
+                light_sampled_through = self.transmissive_shadows;
+            } else if let Some(Hit { si, t, shape }) = hit {
                 if let Some(collected_rays) = &mut rays {
                     collected_rays.last_mut().unwrap().ray.t_max = t;
                     collected_rays.push(IntegratorRay {
@@ -99,9 +234,12 @@ impl Path {
 
                 let bsdf = shape.compute_scattering_functions(scratch, &si);
 
-                let mut radiance = scene.lights.iter().fold(Spectrum::zeros(), |c, l| {
-                    let LightSample { l, li, vis, pdf } = l.sample_li(&si, sampler.get_2d());
-                    if !li.is_black() {
+                // Sample one light proportional to power
+                let mut radiance = Spectrum::zeros();
+                if let Some((light, light_pdf)) = scene.sample_light(sampler.get_1d()) {
+                    let LightSample { l, li, vis, pdf } =
+                        light.sample_li(&Interaction::from(&si), sampler.get_2d());
+                    if !li.is_black() && light_pdf > 0.0 {
                         let f = bsdf.f(si.wo, l, BxdfType::all());
                         if let Some(test) = vis {
                             if let Some(collected_rays) = &mut rays {
@@ -110,23 +248,46 @@ impl Path {
                                     ray_type: RayType::Shadow,
                                 });
                             }
-                            if !f.is_black() && test.unoccluded(scene) {
-                                return c + f * li * si.shading.n.dot_v(l).clamp(0.0, 1.0) / pdf;
+                            if !f.is_black() {
+                                let mut transmittance = if self.transmissive_shadows {
+                                    test.unoccluded_transmittance(scratch, scene)
+                                } else if test.unoccluded(scene) {
+                                    Spectrum::ones()
+                                } else {
+                                    Spectrum::zeros()
+                                };
+                                if let Some(medium) = &scene.medium {
+                                    transmittance *= medium.tr(test.ray());
+                                }
+                                radiance = f
+                                    * li
+                                    * light_intensity(light, self.background_intensity)
+                                    * transmittance
+                                    * si.shading.n.dot_v(l).abs()
+                                    / (pdf * light_pdf);
+                                if let Some(records) = &mut records {
+                                    records.push(DebugRecord::LightSample {
+                                        bounce: bounces,
+                                        light: light_index(scene, light),
+                                        contribution: beta * radiance,
+                                        occluded: transmittance.is_black(),
+                                    });
+                                }
                             }
                         }
                     }
-                    c
-                });
-
-                if bounces == 0 || specular_bounce {
-                    radiance += beta * si.emitted_radiance(-ray.d);
                 }
 
-                if bounces > 0 && self.indirect_clamp.is_some() {
-                    radiance = radiance.min(Spectrum::ones() * self.indirect_clamp.unwrap());
+                if bounces == 0 || (specular_bounce && !light_sampled_through) {
+                    radiance += si.emitted_radiance(-ray.d);
+                } else if let (Some((it, phase_pdf)), Some(light)) =
+                    (&medium_vertex, &si.area_light)
+                {
+                    let weight = phase_sample_weight(scene, light.as_ref(), it, ray.d, *phase_pdf);
+                    radiance += si.emitted_radiance(-ray.d) * weight;
                 }
 
-                incoming_radiance += beta * radiance;
+                incoming_radiance += self.clamp_contribution(beta * radiance, bounces);
 
                 let wo = -ray.d;
                 let BxdfSample {
@@ -135,14 +296,27 @@ impl Path {
                     pdf,
                     sample_type,
                 } = bsdf.sample_f(wo, sampler.get_2d(), BxdfType::all());
+                if let Some(records) = &mut records {
+                    records.push(DebugRecord::Scattering {
+                        bounce: bounces,
+                        lobe: sample_type,
+                        pdf,
+                    });
+                }
 
                 if f.is_black() || pdf == 0.0 {
                     break;
                 }
+                medium_vertex = None;
                 specular_bounce = sample_type.contains(BxdfType::SPECULAR);
+                light_sampled_through = if specular_bounce {
+                    light_sampled_through && sample_type.contains(BxdfType::TRANSMISSION)
+                } else {
+                    self.transmissive_shadows
+                };
 
                 beta *= f * wi.dot_n(si.shading.n).abs() / pdf;
-                ray = Interaction::from(&si).spawn_ray(wi);
+                ray = si.spawn_ray(wi);
                 if rays.is_some() {
                     ray_type = if sample_type.contains(BxdfType::REFLECTION) {
                         RayType::Reflection
@@ -155,22 +329,39 @@ impl Path {
             } else {
                 // TODO: pbrt doesn't do this on miss after first ray in path,
                 //       but on direct illumination estimate for previous hit
-                incoming_radiance += beta * scene.background;
+                let mut radiance = scene.background * self.background_intensity;
+                // Infinite lights are sampled on hits like area lights
+                if bounces == 0 || (specular_bounce && !light_sampled_through) {
+                    radiance += escaped_le(scene, ray, self.background_intensity);
+                } else if let Some((it, phase_pdf)) = &medium_vertex {
+                    radiance += scene.lights.iter().fold(Spectrum::zeros(), |sum, light| {
+                        let weight =
+                            phase_sample_weight(scene, light.as_ref(), it, ray.d, *phase_pdf);
+                        sum + light.le(ray)
+                            * light_intensity(light.as_ref(), self.background_intensity)
+                            * weight
+                    });
+                }
+                incoming_radiance += self.clamp_contribution(beta * radiance, bounces);
                 break;
             };
 
             // Russian roulette
-            if bounces > 3 {
-                let q = (1.0 - beta.g).max(0.05);
+            if bounces > self.rr_start_depth {
+                let q = (1.0 - beta.max_comp()).max(0.05);
                 if sampler.get_1d() < q {
                     break;
                 }
-                beta *= Spectrum::ones() / (1.0 - q);
+                beta /= 1.0 - q;
             }
 
             bounces += 1;
         }
 
+        if let Some(records) = records {
+            records.push(DebugRecord::PathRadiance(incoming_radiance));
+        }
+
         RadianceResult {
             li: incoming_radiance,
             ray_scene_intersections: ray_count,
@@ -178,6 +369,33 @@ impl Path {
     }
 }
 
+/// Returns the MIS weight of the phase function sample with `phase_pdf` that reached `light` from
+/// `it` in direction `w`, against sampling the light directly.
+fn phase_sample_weight<L: Light + ?Sized>(
+    scene: &Scene,
+    light: &L,
+    it: &Interaction,
+    w: Vec3<f32>,
+    phase_pdf: f32,
+) -> f32 {
+    let light_pdf = scene.light_pdf(light) * light.pdf_li(it, w);
+    power_heuristic(phase_pdf, light_pdf)
+}
+
+/// Finds the index of `light` in the lights of `scene`.
+fn light_index(scene: &Scene, light: &dyn Light) -> usize {
+    scene
+        .lights
+        .iter()
+        .position(|l| {
+            std::ptr::eq(
+                (l.as_ref() as *const dyn Light).cast::<()>(),
+                (light as *const dyn Light).cast::<()>(),
+            )
+        })
+        .unwrap()
+}
+
 impl Integrator for Path {
     fn li(
         &self,
@@ -187,7 +405,7 @@ impl Integrator for Path {
         depth: u32,
         sampler: &mut Box<dyn Sampler>,
     ) -> RadianceResult {
-        self.li_internal(scratch, ray, scene, depth, sampler, None)
+        self.li_internal(scratch, ray, scene, depth, sampler, None, None)
     }
 
     fn li_debug(
@@ -198,7 +416,407 @@ impl Integrator for Path {
         depth: u32,
         sampler: &mut Box<dyn Sampler>,
         rays: &mut Vec<IntegratorRay>,
+        records: &mut Vec<DebugRecord>,
     ) -> RadianceResult {
-        self.li_internal(scratch, ray, scene, depth, sampler, Some(rays))
+        self.li_internal(
+            scratch,
+            ray,
+            scene,
+            depth,
+            sampler,
+            Some(rays),
+            Some(records),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::{Params, Path};
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        integrators::{DebugRecord, Integrator},
+        lights::{power_distribution, AreaLight, Light, PointLight, RectangularLight},
+        materials::{BxdfType, Material, Matte, Translucent},
+        math::{transforms::translation, Point2, Point3, Ray, Spectrum, Transform, Vec2, Vec3},
+        media::HomogeneousMedium,
+        sampling::{Sampler, UniformParams, UniformSampler},
+        scene::{Scene, SceneLoadSettings},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    /// Creates two facing planes of constant albedo with a small area light on the ceiling.
+    fn parallel_planes_scene() -> Scene {
+        let matte = |reflectance: f32| -> Arc<dyn Material> {
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones() * reflectance)),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            ))
+        };
+        let quad = |points: Vec<Point3<f32>>| {
+            Arc::new(Mesh::new(
+                &Transform::default(),
+                vec![0, 1, 2, 0, 2, 3],
+                points,
+                Vec::new(),
+                Vec::new(),
+            ))
+        };
+
+        let light = Arc::new(RectangularLight::new(
+            &translation(Vec3::new(0.0, 0.99, 0.0)),
+            Spectrum::ones() * 10.0,
+            Vec2::new(0.5, 0.5),
+        ));
+
+        // Facing -y like the light
+        let light_mesh = quad(vec![
+            Point3::new(-0.25, 0.99, -0.25),
+            Point3::new(0.25, 0.99, -0.25),
+            Point3::new(0.25, 0.99, 0.25),
+            Point3::new(-0.25, 0.99, 0.25),
+        ]);
+        let ceiling_mesh = quad(vec![
+            Point3::new(-4.0, 1.0, -4.0),
+            Point3::new(4.0, 1.0, -4.0),
+            Point3::new(4.0, 1.0, 4.0),
+            Point3::new(-4.0, 1.0, 4.0),
+        ]);
+        // Facing +y
+        let floor_mesh = quad(vec![
+            Point3::new(-4.0, 0.0, -4.0),
+            Point3::new(-4.0, 0.0, 4.0),
+            Point3::new(4.0, 0.0, 4.0),
+            Point3::new(4.0, 0.0, -4.0),
+        ]);
+
+        let black = matte(0.0);
+        let albedo = matte(0.7);
+        let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
+        for v0 in [0, 3] {
+            shapes.push(Arc::new(Triangle::new(
+                Arc::clone(&light_mesh),
+                v0,
+                Arc::clone(&black),
+                Some(Arc::clone(&light) as Arc<dyn AreaLight>),
+            )));
+            for mesh in [&ceiling_mesh, &floor_mesh] {
+                shapes.push(Arc::new(Triangle::new(
+                    Arc::clone(mesh),
+                    v0,
+                    Arc::clone(&albedo),
+                    None,
+                )));
+            }
+        }
+
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
+        Scene {
+            name: "Path test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![light_mesh, ceiling_mesh, floor_mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    /// Creates a quad at y = 0 lit only from above by a small rectangular light.
+    fn backlit_quad_scene(material: Arc<dyn Material>) -> Scene {
+        let light = Arc::new(RectangularLight::new(
+            &translation(Vec3::new(0.0, 1.0, 0.0)),
+            Spectrum::ones() * 10.0,
+            Vec2::new(0.5, 0.5),
+        ));
+
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 0, 2, 3],
+            vec![
+                Point3::new(-1.0, 0.0, -1.0),
+                Point3::new(-1.0, 0.0, 1.0),
+                Point3::new(1.0, 0.0, 1.0),
+                Point3::new(1.0, 0.0, -1.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let shapes: Vec<Arc<dyn Shape>> = [0, 3]
+            .into_iter()
+            .map(|v0| {
+                Arc::new(Triangle::new(
+                    Arc::clone(&mesh),
+                    v0,
+                    Arc::clone(&material),
+                    None,
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
+        Scene {
+            name: "Backlit test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![mesh],
+            shapes,
+            bvh,
+            lights,
+            light_distribution,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    fn mean_luminance(integrator: &Path, scene: &Scene, ray: Ray<f32>, sample_count: u32) -> f32 {
+        let mut allocator = LinearAllocator::new(1024 * 1024);
+        let scratch = ScopedScratch::new(&mut allocator);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams {
+                pixel_samples: sample_count,
+            },
+            false,
+        ));
+
+        let mut sum = 0.0;
+        for i in 0..sample_count {
+            sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+            let sample_scratch = ScopedScratch::new_scope(&scratch);
+            sum += integrator
+                .li(&sample_scratch, ray, scene, 0, &mut sampler)
+                .li
+                .luminance();
+        }
+        sum / (sample_count as f32)
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        let scene = parallel_planes_scene();
+        let o = Point3::new(0.0, 0.5, -1.0);
+        let ray = Ray::new(
+            o,
+            (Point3::new(0.5, 0.0, 0.5) - o).normalized(),
+            f32::INFINITY,
+        );
+
+        let max_depth = 12;
+        let sample_count = 1 << 14;
+        let without_rr = Path::new(Params {
+            max_depth,
+            rr_start_depth: max_depth,
+            ..Params::default()
+        });
+        let with_rr = Path::new(Params {
+            max_depth,
+            rr_start_depth: 0,
+            ..Params::default()
+        });
+
+        let reference = mean_luminance(&without_rr, &scene, ray, sample_count);
+        let roulette = mean_luminance(&with_rr, &scene, ray, sample_count);
+
+        assert!(reference > 0.0);
+        assert!(
+            ((roulette - reference) / reference).abs() < 0.05,
+            "RR {} Reference {}",
+            roulette,
+            reference
+        );
+    }
+
+    #[test]
+    fn debug_records_path_events() {
+        let scene = parallel_planes_scene();
+        let o = Point3::new(0.0, 0.5, -1.0);
+        let ray = Ray::new(
+            o,
+            (Point3::new(0.5, 0.0, 0.5) - o).normalized(),
+            f32::INFINITY,
+        );
+        let integrator = Path::new(Params {
+            max_depth: 1,
+            ..Params::default()
+        });
+
+        let mut allocator = LinearAllocator::new(1024 * 1024);
+        let scratch = ScopedScratch::new(&mut allocator);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams { pixel_samples: 1 },
+            false,
+        ));
+        sampler.start_pixel_sample(Point2::new(0, 0), 0, 0);
+
+        let mut rays = Vec::new();
+        let mut records = Vec::new();
+        let result = integrator.li_debug(
+            &scratch,
+            ray,
+            &scene,
+            0,
+            &mut sampler,
+            &mut rays,
+            &mut records,
+        );
+        assert!(!rays.is_empty());
+
+        match records.as_slice() {
+            [DebugRecord::LightSample {
+                bounce: 0,
+                light: 0,
+                contribution,
+                occluded: false,
+            }, DebugRecord::Scattering {
+                bounce: 0, lobe, ..
+            }, DebugRecord::PathRadiance(li)] => {
+                assert!(!contribution.is_black());
+                assert!(lobe.contains(BxdfType::REFLECTION | BxdfType::DIFFUSE));
+                assert_eq!(*li, result.li);
+            }
+            _ => panic!("Unexpected records {:?}", records),
+        }
+    }
+
+    #[test]
+    fn backlit_translucent_quad_transmits() {
+        let translucent: Arc<dyn Material> = Arc::new(Translucent::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(Spectrum::zeros())),
+            Arc::new(ConstantTexture::new(Spectrum::new(0.8, 0.4, 0.0))),
+            None,
+            None,
+        ));
+        let matte: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+
+        // Look at the unlit side of the quad
+        let ray = Ray::new(
+            Point3::new(0.3, -1.0, -0.1),
+            Vec3::new(0.0, 1.0, 0.0),
+            f32::INFINITY,
+        );
+        // Direct lighting only
+        let integrator = Path::new(Params {
+            max_depth: 1,
+            ..Params::default()
+        });
+
+        let mean_radiance = |scene: &Scene| {
+            let mut allocator = LinearAllocator::new(1024 * 1024);
+            let scratch = ScopedScratch::new(&mut allocator);
+            let sample_count = 256;
+            let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+                UniformParams {
+                    pixel_samples: sample_count,
+                },
+                false,
+            ));
+
+            let mut sum = Spectrum::zeros();
+            for i in 0..sample_count {
+                sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+                let sample_scratch = ScopedScratch::new_scope(&scratch);
+                sum += integrator
+                    .li(&sample_scratch, ray, scene, 0, &mut sampler)
+                    .li;
+            }
+            sum / (sample_count as f32)
+        };
+
+        let transmitted = mean_radiance(&backlit_quad_scene(translucent));
+        assert!(transmitted.r > 0.0);
+        assert!(
+            (transmitted.g / transmitted.r - 0.5).abs() < 1e-4,
+            "{:?}",
+            transmitted
+        );
+        assert_eq!(transmitted.b, 0.0);
+
+        let reflected = mean_radiance(&backlit_quad_scene(matte));
+        assert!(reflected.is_black(), "{:?}", reflected);
+    }
+
+    #[test]
+    fn medium_scatters_point_light() {
+        let matte = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones() * 0.5)),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let scene = backlit_quad_scene(matte);
+        let lights = vec![Arc::new(PointLight::new(
+            &translation(Vec3::new(0.0, 1.0, 0.0)),
+            Spectrum::ones() * 10.0,
+        )) as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, scene.bvh.bounds());
+        let scene = Scene {
+            lights,
+            light_distribution,
+            medium: Some(Arc::new(HomogeneousMedium::new(
+                Spectrum::zeros(),
+                Spectrum::ones() * 0.5,
+                0.0,
+            ))),
+            ..scene
+        };
+
+        // The ray passes above the quad so the point light can only be reached by sampling it
+        // at the scattering points in the medium
+        let ray = Ray::new(
+            Point3::new(-2.0, 0.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            f32::INFINITY,
+        );
+        let integrator = Path::new(Params {
+            max_depth: 1,
+            ..Params::default()
+        });
+
+        assert!(mean_luminance(&integrator, &scene, ray, 1 << 10) > 0.0);
+    }
+
+    #[test]
+    fn indirect_clamp_limits_max_component() {
+        let integrator = Path::new(Params {
+            indirect_clamp: Some(2.0),
+            ..Params::default()
+        });
+
+        // Scaled down to the clamp with the hue intact
+        let bright = Spectrum::new(8.0, 4.0, 1.0);
+        assert_eq!(
+            integrator.clamp_contribution(bright, 1),
+            Spectrum::new(2.0, 1.0, 0.25)
+        );
+
+        let dim = Spectrum::new(1.5, 0.5, 0.0);
+        assert_eq!(integrator.clamp_contribution(dim, 1), dim);
+
+        // Direct lighting is left alone
+        assert_eq!(integrator.clamp_contribution(bright, 0), bright);
+
+        let unclamped = Path::new(Params::default());
+        assert_eq!(unclamped.clamp_contribution(bright, 3), bright);
     }
 }
@@ -0,0 +1,524 @@
+use super::{add_first_hit_aovs, escaped_le, light_intensity, Integrator, RadianceResult};
+use crate::{
+    bvh::IntersectionResult,
+    camera::{Camera, CameraSample},
+    film::{AovBuffers, FilmTile},
+    filters::Filter,
+    hash_values,
+    interaction::{Interaction, SurfaceInteraction},
+    lights::{EmissionSample, LightSample},
+    materials::{BxdfSample, BxdfType},
+    math::{Point2, Point3, Ray, Spectrum, Vec2},
+    sampling::Sampler,
+    scene::Scene,
+    shapes::{Hit, Shape},
+};
+
+use allocators::ScopedScratch;
+use rand::{distributions::Standard, Rng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Light_Transport_III_Bidirectional_Methods/Stochastic_Progressive_Photon_Mapping
+// and the probabilistic formulation in
+// Progressive Photon Mapping: A Probabilistic Approach, Knaus and Zwicker 2011
+//
+// Each iteration gathers a fresh photon map with a shared radius that shrinks between
+// iterations. The estimates of the iterations can then be averaged like regular samples.
+// Photons are traced per tile to keep the render threads independent of each other.
+
+/// Controls how fast the photon radius shrinks between iterations
+const ALPHA: f32 = 2.0 / 3.0;
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Params {
+    pub max_depth: u32,
+    pub photons_per_iteration: u32,
+    /// Photon radius of the first iteration relative to the radius of the scene's bounding sphere
+    pub initial_radius: f32,
+    pub background_intensity: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            photons_per_iteration: 1 << 14,
+            initial_radius: 0.01,
+            background_intensity: 1.0,
+        }
+    }
+}
+
+pub struct SPPM {
+    max_depth: u32,
+    photons_per_iteration: u32,
+    initial_radius: f32,
+    background_intensity: f32,
+}
+
+/// First diffuse hit of a camera path, where photons are gathered
+struct VisiblePoint<'a> {
+    si: SurfaceInteraction,
+    shape: &'a dyn Shape,
+    beta: Spectrum<f32>,
+    /// Offset of the pixel in the tile
+    pixel_offset: usize,
+}
+
+/// Uniform hash grid for finding the visible points near photon hits
+struct PhotonGrid {
+    cells: HashMap<[i32; 3], Vec<usize>>,
+    radius: f32,
+}
+
+impl PhotonGrid {
+    fn new(points: impl Iterator<Item = Point3<f32>>, radius: f32) -> Self {
+        let mut ret = Self {
+            cells: HashMap::new(),
+            radius,
+        };
+        for (i, p) in points.enumerate() {
+            ret.cells.entry(ret.cell(p)).or_default().push(i);
+        }
+        ret
+    }
+
+    fn cell(&self, p: Point3<f32>) -> [i32; 3] {
+        // Cells are the size of the radius so neighbors are at most one cell away
+        [
+            (p.x / self.radius).floor() as i32,
+            (p.y / self.radius).floor() as i32,
+            (p.z / self.radius).floor() as i32,
+        ]
+    }
+
+    /// Calls `f` with the index of each point that might be within the radius of `p`.
+    fn for_each_near(&self, p: Point3<f32>, mut f: impl FnMut(usize)) {
+        let [x, y, z] = self.cell(p);
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if let Some(indices) = self.cells.get(&[x + dx, y + dy, z + dz]) {
+                        indices.iter().for_each(|&i| f(i));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the photon radius of `iteration` relative to the first one.
+fn radius_scale(iteration: u32) -> f32 {
+    // r_{i+1}^2 = r_i^2 (i + alpha) / (i + 1)
+    (1..=iteration)
+        .fold(1.0, |r_sqr, i| {
+            r_sqr * ((i as f32) + ALPHA) / ((i as f32) + 1.0)
+        })
+        .sqrt()
+}
+
+fn sample_2d(rng: &mut Pcg32) -> Point2<f32> {
+    Point2::new(rng.sample(Standard), rng.sample(Standard))
+}
+
+/// Returns the radiance estimate for the flux `phi` gathered within `radius`.
+fn density_estimate(phi: Spectrum<f32>, photon_count: u32, radius: f32) -> Spectrum<f32> {
+    phi / ((photon_count as f32) * std::f32::consts::PI * radius * radius)
+}
+
+impl SPPM {
+    pub fn new(params: Params) -> Self {
+        Self {
+            max_depth: params.max_depth,
+            photons_per_iteration: params.photons_per_iteration,
+            initial_radius: params.initial_radius,
+            background_intensity: params.background_intensity,
+        }
+    }
+
+    /// Traces `ray` until it hits a diffuse surface, evaluating emitted and direct lighting on the way.
+    ///
+    /// Also returns the visible point of the path, if any.
+    fn camera_path<'a>(
+        &self,
+        scratch: &ScopedScratch,
+        mut ray: Ray<f32>,
+        scene: &'a Scene,
+        sampler: &mut Box<dyn Sampler>,
+    ) -> (RadianceResult, Option<VisiblePoint<'a>>) {
+        let mut incoming_radiance = Spectrum::zeros();
+        let mut beta = Spectrum::ones();
+        let mut specular_bounce = false;
+        let mut ray_count = 0;
+        let mut visible_point = None;
+        for depth in 0..self.max_depth {
+            ray_count += 1;
+
+            let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
+            let (si, shape) = if let Some(Hit { si, shape, .. }) = hit {
+                (si, shape)
+            } else {
+                incoming_radiance += beta * scene.background * self.background_intensity;
+                if depth == 0 || specular_bounce {
+                    incoming_radiance += beta * escaped_le(scene, ray, self.background_intensity);
+                }
+                break;
+            };
+
+            let bsdf = shape.compute_scattering_functions(scratch, &si);
+
+            if depth == 0 || specular_bounce {
+                incoming_radiance += beta * si.emitted_radiance(-ray.d);
+            }
+
+            // Photons gathered at the visible point only account for indirect lighting
+            incoming_radiance += scene.lights.iter().fold(Spectrum::zeros(), |c, light| {
+                let LightSample { l, li, vis, pdf } =
+                    light.sample_li(&Interaction::from(&si), sampler.get_2d());
+                if !li.is_black() {
+                    let f = bsdf.f(si.wo, l, BxdfType::all());
+                    if let Some(test) = vis {
+                        if !f.is_black() && test.unoccluded(scene) {
+                            let scale = light_intensity(light.as_ref(), self.background_intensity);
+                            return c + beta * f * li * scale * si.shading.n.dot_v(l).abs() / pdf;
+                        }
+                    }
+                }
+                c
+            });
+
+            let is_diffuse = bsdf
+                .num_components(BxdfType::DIFFUSE | BxdfType::REFLECTION | BxdfType::TRANSMISSION)
+                > 0;
+            let is_glossy = bsdf
+                .num_components(BxdfType::GLOSSY | BxdfType::REFLECTION | BxdfType::TRANSMISSION)
+                > 0;
+            if is_diffuse || (is_glossy && depth == self.max_depth - 1) {
+                visible_point = Some(VisiblePoint {
+                    si,
+                    shape,
+                    beta,
+                    pixel_offset: 0,
+                });
+                break;
+            }
+
+            let BxdfSample {
+                wi,
+                f,
+                pdf,
+                sample_type,
+            } = bsdf.sample_f(si.wo, sampler.get_2d(), BxdfType::all());
+            if f.is_black() || pdf == 0.0 {
+                break;
+            }
+            specular_bounce = sample_type.contains(BxdfType::SPECULAR);
+
+            beta *= f * wi.dot_n(si.shading.n).abs() / pdf;
+            ray = si.spawn_ray(wi);
+        }
+
+        (
+            RadianceResult {
+                li: incoming_radiance,
+                ray_scene_intersections: ray_count,
+            },
+            visible_point,
+        )
+    }
+
+    /// Traces the photons of one iteration and adds their contributions to `pixels`.
+    ///
+    /// Returns the number of rays intersected with `scene`.
+    fn trace_photons(
+        &self,
+        scratch: &ScopedScratch,
+        scene: &Scene,
+        rng: &mut Pcg32,
+        radius: f32,
+        visible_points: &[VisiblePoint],
+        pixels: &mut [Spectrum<f32>],
+    ) -> usize {
+        if visible_points.is_empty() || scene.lights.is_empty() {
+            return 0;
+        }
+
+        let grid = PhotonGrid::new(visible_points.iter().map(|vp| vp.si.p), radius);
+        let radius_sqr = radius * radius;
+        let scene_bounds = scene.bvh.bounds();
+        let light_count = scene.lights.len();
+        let light_pdf = 1.0 / (light_count as f32);
+
+        let mut ray_count = 0;
+        let mut phi = vec![Spectrum::zeros(); visible_points.len()];
+        for _ in 0..self.photons_per_iteration {
+            #[allow(clippy::cast_sign_loss)] // Always expect u in [0, 1)
+            let light_index = ((rng.sample::<f32, _>(Standard) * (light_count as f32)) as usize)
+                .min(light_count - 1);
+            let light = scene.lights[light_index].as_ref();
+            let EmissionSample {
+                mut ray,
+                n,
+                le,
+                pdf_pos,
+                pdf_dir,
+            } = light.sample_le(sample_2d(rng), sample_2d(rng), scene_bounds);
+            if pdf_pos == 0.0 || pdf_dir == 0.0 || le.is_black() {
+                continue;
+            }
+
+            let mut beta =
+                le * light_intensity(light, self.background_intensity) * n.dot_v(ray.d).abs()
+                    / (light_pdf * pdf_pos * pdf_dir);
+            for depth in 0..self.max_depth {
+                ray_count += 1;
+
+                let IntersectionResult { hit, .. } = scene.bvh.intersect(ray);
+                let (si, shape) = if let Some(Hit { si, shape, .. }) = hit {
+                    (si, shape)
+                } else {
+                    break;
+                };
+
+                // Direct lighting is evaluated by the camera paths
+                if depth > 0 {
+                    grid.for_each_near(si.p, |i| {
+                        let vp = &visible_points[i];
+                        if vp.si.p.dist_sqr(si.p) <= radius_sqr {
+                            let vp_scratch = ScopedScratch::new_scope(scratch);
+                            let bsdf = vp.shape.compute_scattering_functions(&vp_scratch, &vp.si);
+                            phi[i] += beta * bsdf.f(vp.si.wo, -ray.d, BxdfType::all());
+                        }
+                    });
+                }
+
+                let photon_scratch = ScopedScratch::new_scope(scratch);
+                let bsdf = shape.compute_scattering_functions(&photon_scratch, &si);
+                let BxdfSample { wi, f, pdf, .. } =
+                    bsdf.sample_f(si.wo, sample_2d(rng), BxdfType::all());
+                if f.is_black() || pdf == 0.0 {
+                    break;
+                }
+
+                let beta_new = beta * f * wi.dot_n(si.shading.n).abs() / pdf;
+                // Russian roulette based on the throughput change keeps photon powers similar
+                let beta_luminance = beta.luminance();
+                let q = if beta_luminance > 0.0 {
+                    (1.0 - beta_new.luminance() / beta_luminance).max(0.0)
+                } else {
+                    1.0
+                };
+                if rng.sample::<f32, _>(Standard) < q {
+                    break;
+                }
+                beta = beta_new / (1.0 - q);
+                ray = si.spawn_ray(wi);
+            }
+        }
+
+        for (vp, phi) in visible_points.iter().zip(phi) {
+            pixels[vp.pixel_offset] +=
+                vp.beta * density_estimate(phi, self.photons_per_iteration, radius);
+        }
+
+        ray_count
+    }
+}
+
+impl Integrator for SPPM {
+    /// Only evaluates emitted and direct lighting as photons are gathered per tile in `render`.
+    fn li(
+        &self,
+        scratch: &ScopedScratch,
+        ray: Ray<f32>,
+        scene: &Scene,
+        _depth: u32,
+        sampler: &mut Box<dyn Sampler>,
+    ) -> RadianceResult {
+        self.camera_path(scratch, ray, scene, sampler).0
+    }
+
+    fn render(
+        &self,
+        scratch: &ScopedScratch,
+        scene: &Scene,
+        camera: &Camera,
+        sampler: &Arc<dyn Sampler>,
+        _filter: &dyn Filter,
+        accumulating: bool,
+        tile: &mut FilmTile,
+        tile_pixels: &mut [Spectrum<f32>],
+        tile_luminance_sqr: &mut [f32],
+        mut tile_aovs: Option<&mut AovBuffers>,
+        early_termination_predicate: &mut dyn FnMut() -> bool,
+    ) -> usize {
+        let pixel_count = tile.bb.area() as usize;
+        assert!(tile_pixels.len() >= pixel_count);
+        assert!(tile_luminance_sqr.len() >= pixel_count);
+        if let Some(aovs) = &mut tile_aovs {
+            aovs.clear(pixel_count);
+        }
+
+        let tile_width = tile.bb.width();
+        let mut sampler = sampler.as_ref().clone();
+
+        let (first_iteration, iteration_count) = if accumulating {
+            (tile.sample as u32, 1)
+        } else {
+            (0, sampler.samples_per_pixel())
+        };
+        let scene_radius = scene
+            .bvh
+            .bounds()
+            .bounding_sphere()
+            .map_or(1.0, |(_, radius)| radius);
+
+        tile_pixels[..pixel_count].fill(Spectrum::zeros());
+        tile_luminance_sqr[..pixel_count].fill(0.0);
+
+        let mut ray_count = 0;
+        let mut iteration_pixels = vec![Spectrum::zeros(); pixel_count];
+        for iteration in first_iteration..(first_iteration + iteration_count) {
+            iteration_pixels.fill(Spectrum::zeros());
+
+            let mut visible_points = Vec::with_capacity(pixel_count);
+            for p in tile.bb {
+                if early_termination_predicate() {
+                    return ray_count;
+                }
+
+                sampler.start_pixel_sample(p, iteration, 0);
+
+                let sample_scratch = ScopedScratch::new_scope(scratch);
+
+                let p_film = Point2::new(p.x as f32, p.y as f32) + sampler.get_2d();
+                let p_lens = sampler.get_2d();
+                let time = sampler.get_1d();
+
+                let ray = camera.ray_differential(&CameraSample {
+                    p_film,
+                    p_lens,
+                    time,
+                });
+
+                let (result, visible_point) =
+                    self.camera_path(&sample_scratch, ray, scene, &mut sampler);
+
+                let Vec2 {
+                    x: tile_x,
+                    y: tile_y,
+                } = p - tile.bb.p_min;
+                let pixel_offset = (tile_y * tile_width + tile_x) as usize;
+                iteration_pixels[pixel_offset] = result.li;
+                ray_count += result.ray_scene_intersections;
+
+                if let Some(aovs) = &mut tile_aovs {
+                    ray_count += add_first_hit_aovs(
+                        &sample_scratch,
+                        ray,
+                        scene,
+                        &mut sampler,
+                        aovs,
+                        pixel_offset,
+                    );
+                }
+
+                if let Some(vp) = visible_point {
+                    visible_points.push(VisiblePoint { pixel_offset, ..vp });
+                }
+            }
+
+            // Photons should differ between tiles and iterations
+            let mut rng = Pcg32::new(hash_values!(tile.bb.p_min, iteration), 0);
+            let radius = scene_radius * self.initial_radius * radius_scale(iteration);
+            ray_count += self.trace_photons(
+                scratch,
+                scene,
+                &mut rng,
+                radius,
+                &visible_points,
+                &mut iteration_pixels,
+            );
+
+            for ((color, luminance_sqr), li) in tile_pixels
+                .iter_mut()
+                .zip(tile_luminance_sqr.iter_mut())
+                .zip(&iteration_pixels)
+            {
+                *color += *li;
+                *luminance_sqr += li.luminance().powi(2);
+            }
+        }
+
+        for (color, luminance_sqr) in tile_pixels[..pixel_count]
+            .iter_mut()
+            .zip(tile_luminance_sqr[..pixel_count].iter_mut())
+        {
+            *color /= iteration_count as f32;
+            *luminance_sqr /= iteration_count as f32;
+        }
+        if let Some(aovs) = tile_aovs {
+            aovs.scale(pixel_count, 1.0 / (iteration_count as f32));
+        }
+
+        ray_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{density_estimate, radius_scale, PhotonGrid};
+    use crate::math::{Point3, Spectrum};
+
+    #[test]
+    fn density_conserves_energy_as_radius_shrinks() {
+        // Photons spread evenly on a 2x2 plane give a density of 1/4 per traced photon
+        let photons_per_side: u32 = 512;
+        let photon_count = photons_per_side * photons_per_side;
+        let photons: Vec<Point3<f32>> = (0..photon_count)
+            .map(|i| {
+                let x = ((i % photons_per_side) as f32 + 0.5) / (photons_per_side as f32);
+                let z = ((i / photons_per_side) as f32 + 0.5) / (photons_per_side as f32);
+                Point3::new(x * 2.0 - 1.0, 0.0, z * 2.0 - 1.0)
+            })
+            .collect();
+        let expected = 1.0 / 4.0;
+
+        let initial_radius = 0.2;
+        let mut previous_radius = f32::INFINITY;
+        for iteration in [0, 10, 100, 1000] {
+            let radius = initial_radius * radius_scale(iteration);
+            assert!(radius < previous_radius);
+            previous_radius = radius;
+
+            let visible_point = Point3::new(0.1, 0.0, -0.2);
+            let grid = PhotonGrid::new(std::iter::once(visible_point), radius);
+
+            let mut phi = Spectrum::zeros();
+            for &p in &photons {
+                grid.for_each_near(p, |i| {
+                    assert_eq!(i, 0);
+                    if p.dist_sqr(visible_point) <= radius * radius {
+                        phi += Spectrum::ones();
+                    }
+                });
+            }
+
+            let estimate = density_estimate(phi, photon_count, radius).g;
+            assert!(
+                ((estimate - expected) / expected).abs() < 0.02,
+                "Iteration {} radius {} estimate {}",
+                iteration,
+                radius,
+                estimate
+            );
+        }
+    }
+}
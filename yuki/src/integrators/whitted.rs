@@ -1,4 +1,6 @@
-use super::{Integrator, IntegratorRay, RadianceResult, RayType};
+use super::{
+    escaped_le, light_intensity, DebugRecord, Integrator, IntegratorRay, RadianceResult, RayType,
+};
 use crate::{
     bvh::IntersectionResult,
     interaction::{Interaction, SurfaceInteraction},
@@ -16,22 +18,29 @@ use serde::{Deserialize, Serialize};
 #[derive(Copy, Clone, Deserialize, Serialize)]
 pub struct Params {
     pub max_depth: u32,
+    #[serde(default = "super::default_background_intensity")]
+    pub background_intensity: f32,
 }
 
 impl Default for Params {
     fn default() -> Self {
-        Self { max_depth: 3 }
+        Self {
+            max_depth: 3,
+            background_intensity: super::default_background_intensity(),
+        }
     }
 }
 
 pub struct Whitted {
     max_depth: u32,
+    background_intensity: f32,
 }
 
 impl Whitted {
     pub fn new(params: Params) -> Self {
         Self {
             max_depth: params.max_depth,
+            background_intensity: params.background_intensity,
         }
     }
 
@@ -52,7 +61,7 @@ impl Whitted {
         if sample_type == BxdfType::NONE {
             RadianceResult::default()
         } else {
-            let refl = Interaction::from(si).spawn_ray(wi);
+            let refl = si.spawn_ray(wi);
 
             let mut ret = self.li_internal(
                 scratch,
@@ -106,8 +115,9 @@ impl Whitted {
             let bsdf = shape.compute_scattering_functions(scratch, &si);
 
             let mut ray_count = 1;
-            let mut sum_li = scene.lights.iter().fold(Spectrum::zeros(), |c, l| {
-                let LightSample { l, li, vis, pdf } = l.sample_li(&si, sampler.get_2d());
+            let mut sum_li = scene.lights.iter().fold(Spectrum::zeros(), |c, light| {
+                let LightSample { l, li, vis, pdf } =
+                    light.sample_li(&Interaction::from(&si), sampler.get_2d());
                 if !li.is_black() {
                     let f = bsdf.f(si.wo, l, BxdfType::all());
                     if let Some(test) = vis {
@@ -118,7 +128,8 @@ impl Whitted {
                             });
                         }
                         if !f.is_black() && test.unoccluded(scene) {
-                            return c + f * li * si.shading.n.dot_v(l).clamp(0.0, 1.0) / pdf;
+                            let scale = light_intensity(light.as_ref(), self.background_intensity);
+                            return c + f * li * scale * si.shading.n.dot_v(l).abs() / pdf;
                         }
                     }
                 }
@@ -171,7 +182,8 @@ impl Whitted {
 
             (sum_li, ray_count)
         } else {
-            (scene.background, 1)
+            let le = escaped_le(scene, ray, self.background_intensity);
+            (scene.background * self.background_intensity + le, 1)
         };
 
         RadianceResult {
@@ -201,6 +213,7 @@ impl Integrator for Whitted {
         depth: u32,
         sampler: &mut Box<dyn Sampler>,
         rays: &mut Vec<IntegratorRay>,
+        _records: &mut Vec<DebugRecord>,
     ) -> RadianceResult {
         self.li_internal(scratch, ray, scene, depth, sampler, Some(rays), false)
     }
@@ -1,6 +1,9 @@
 use crate::{
     lights::AreaLight,
-    math::{Normal, Point2, Point3, Ray, Spectrum, Transform, Vec3},
+    math::{
+        coordinate_system, gamma, next_float_down, next_float_up, Normal, Point2, Point3, Ray,
+        RayDifferential, Spectrum, Transform, Vec3,
+    },
     shapes::Shape,
 };
 use std::{ops::Mul, sync::Arc};
@@ -8,57 +11,79 @@ use std::{ops::Mul, sync::Arc};
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Geometry_and_Transformations/Interactions#SurfaceInteraction
 
+/// Shadow rays stop this far from their target, relative to the segment length
+const SHADOW_EPSILON: f32 = 0.0001;
+
+#[derive(Clone, Copy)]
 pub struct Interaction {
     pub p: Point3<f32>,
+    /// Conservative bound on the absolute floating point error in `p`
+    pub p_error: Vec3<f32>,
     pub n: Normal<f32>,
+    pub time: f32,
 }
 
 impl Default for Interaction {
     fn default() -> Self {
         Self {
             p: Point3::zeros(),
+            p_error: Vec3::zeros(),
             n: Normal::new(0.0, 0.0, 1.0),
+            time: 0.0,
         }
     }
 }
 
 impl Interaction {
-    /// Spawns a ray from the `SurfaceInteraction` toward `d`.
+    /// Spawns a ray from the `Interaction` toward `d`.
     pub fn spawn_ray(&self, d: Vec3<f32>) -> Ray<f32> {
-        let o = {
-            // TODO: Base offset on p error
-            let n = Vec3::from(self.n);
-            let offset = n * 0.001;
-            if d.dot(n) > 0.0 {
-                self.p + offset
-            } else {
-                self.p - offset
-            }
-            // TODO: Round away from p
-        };
-        Ray::new(o, d, f32::INFINITY)
+        let o = offset_ray_origin(self.p, self.p_error, self.n, d);
+        Ray {
+            time: self.time,
+            ..Ray::new(o, d, f32::INFINITY)
+        }
     }
 
-    /// Spawns a ray from this `SurfaceInteraction` toward another one.
+    /// Spawns a ray from this `Interaction` toward another one.
     /// Note that the ray direction is not normalized.
     pub fn spawn_ray_to(&self, other: &Interaction) -> Ray<f32> {
-        let o = {
-            // TODO: Base offset on p error
-            let n = Vec3::from(self.n);
-            let offset = n * 0.001;
-            if (other.p - self.p).dot(n) > 0.0 {
-                self.p + offset
-            } else {
-                self.p - offset
-            }
-            // TODO: Round away from p
-        };
+        let o = offset_ray_origin(self.p, self.p_error, self.n, other.p - self.p);
+        let target = offset_ray_origin(other.p, other.p_error, other.n, o - other.p);
         // NOTE: This is not normalized
-        let d = other.p - o;
-        Ray::new(o, d, 0.9999)
+        let d = target - o;
+        Ray {
+            time: self.time,
+            ..Ray::new(o, d, 1.0 - SHADOW_EPSILON)
+        }
+    }
+}
+
+// From https://www.pbr-book.org/3ed-2018/Shapes/Managing_Rounding_Error#RobustSpawnedRayOrigins
+/// Offsets `p` along `n` to the side of `w` so that the result is outside the error bounds of `p`.
+///
+/// Rays started from the returned point won't re-intersect the surface `p` was found on.
+fn offset_ray_origin(
+    p: Point3<f32>,
+    p_error: Vec3<f32>,
+    n: Normal<f32>,
+    w: Vec3<f32>,
+) -> Point3<f32> {
+    let n = Vec3::from(n);
+    let d = n.abs().dot(p_error);
+    let offset = if w.dot(n) < 0.0 { -(n * d) } else { n * d };
+    let mut po = p + offset;
+    // Round away from p to make sure the offset isn't lost
+    for i in 0..3 {
+        if offset[i] > 0.0 {
+            po[i] = next_float_up(po[i]);
+        } else if offset[i] < 0.0 {
+            po[i] = next_float_down(po[i]);
+        }
     }
+    po
 }
 
+#[derive(Clone)]
 pub struct ShadingGeometry {
     pub n: Normal<f32>,
     pub dpdu: Vec3<f32>,
@@ -78,20 +103,38 @@ impl<'a> Mul<&ShadingGeometry> for &'a Transform<f32> {
 }
 
 // Info for a point on a surface
+#[derive(Clone)]
 pub struct SurfaceInteraction {
     pub p: Point3<f32>,
+    /// Conservative bound on the absolute floating point error in `p`
+    pub p_error: Vec3<f32>,
+    /// Geometric normal
     pub n: Normal<f32>,
     pub uv: Point2<f32>,
+    /// Geometric partial derivative of `p` in u
     pub dpdu: Vec3<f32>,
+    /// Geometric partial derivative of `p` in v
     pub dpdv: Vec3<f32>,
+    /// Possibly perturbed geometry used for shading, e.g. from interpolated normals
     pub shading: ShadingGeometry,
     pub wo: Vec3<f32>,
+    pub time: f32,
+    /// Change in u per pixel in x, `0` unless computed from a [`RayDifferential`]
+    pub dudx: f32,
+    pub dvdx: f32,
+    pub dudy: f32,
+    pub dvdy: f32,
     shape_transform_swaps_handedness: bool,
     pub area_light: Option<Arc<dyn AreaLight>>,
 }
 
 impl SurfaceInteraction {
     /// Creates a new `SurfaceInteraction` with its surface geometry populated and shading geometry initialized to match the surface geometry.
+    ///
+    /// `time` is initialized to `0` and should be set to match the intersected ray.
+    /// `p_error` is initialized to a bound for a `p` computed in a few operations from values of
+    /// similar magnitude, e.g. a refined hit on an analytic surface. Shapes that compute `p`
+    /// differently should set a matching bound.
     pub fn new(
         p: Point3<f32>,
         wo: Vec3<f32>,
@@ -112,17 +155,107 @@ impl SurfaceInteraction {
         };
         Self {
             p,
+            p_error: Vec3::from(p.abs()) * gamma(7),
             n,
             uv,
             dpdu,
             dpdv,
             shading: ShadingGeometry { n, dpdu, dpdv },
             wo,
+            time: 0.0,
+            dudx: 0.0,
+            dvdx: 0.0,
+            dudy: 0.0,
+            dvdy: 0.0,
             shape_transform_swaps_handedness,
             area_light,
         }
     }
 
+    /// Estimates the uv derivatives per pixel from the camera ray differentials `rd` by
+    /// intersecting the offset rays with the tangent plane at this `SurfaceInteraction`.
+    ///
+    /// Derivatives are zeroed if the offset rays miss the plane.
+    pub fn compute_differentials(&mut self, rd: &RayDifferential<f32>) {
+        let n = Vec3::from(self.n);
+        let d = n.dot(Vec3::from(self.p));
+        let tx = (d - n.dot(Vec3::from(rd.rx_o))) / n.dot(rd.rx_d);
+        let ty = (d - n.dot(Vec3::from(rd.ry_o))) / n.dot(rd.ry_d);
+        if !tx.is_finite() || !ty.is_finite() {
+            self.dudx = 0.0;
+            self.dvdx = 0.0;
+            self.dudy = 0.0;
+            self.dvdy = 0.0;
+            return;
+        }
+        let dpdx = (rd.rx_o + rd.rx_d * tx) - self.p;
+        let dpdy = (rd.ry_o + rd.ry_d * ty) - self.p;
+
+        // Solve dp = dpdu * du + dpdv * dv in the two dimensions where the plane projects
+        // largest
+        let (a, b) = if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+            (1, 2)
+        } else if n.y.abs() > n.z.abs() {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let (dpdu, dpdv) = (self.dpdu, self.dpdv);
+        let det = dpdu[a] * dpdv[b] - dpdv[a] * dpdu[b];
+        let solve = |dp: Vec3<f32>| {
+            if det.abs() < 1e-10 {
+                return (0.0, 0.0);
+            }
+            let du = (dpdv[b] * dp[a] - dpdv[a] * dp[b]) / det;
+            let dv = (dpdu[a] * dp[b] - dpdu[b] * dp[a]) / det;
+            if du.is_finite() && dv.is_finite() {
+                (du, dv)
+            } else {
+                (0.0, 0.0)
+            }
+        };
+        (self.dudx, self.dvdx) = solve(dpdx);
+        (self.dudy, self.dvdy) = solve(dpdy);
+    }
+
+    /// Spawns a ray from the `SurfaceInteraction` toward `d`.
+    ///
+    /// The origin is offset along the geometric normal past the error bounds of `p` so that the
+    /// ray doesn't re-intersect the surface.
+    pub fn spawn_ray(&self, d: Vec3<f32>) -> Ray<f32> {
+        Interaction::from(self).spawn_ray(d)
+    }
+
+    /// Spawns a ray from the `SurfaceInteraction` toward `p`, stopping just short of it.
+    /// Note that the ray direction is not normalized.
+    pub fn spawn_ray_to(&self, p: Point3<f32>) -> Ray<f32> {
+        let o = offset_ray_origin(self.p, self.p_error, self.n, p - self.p);
+        Ray {
+            time: self.time,
+            ..Ray::new(o, p - o, 1.0 - SHADOW_EPSILON)
+        }
+    }
+
+    /// Returns the orthonormal shading basis `(s, t, n)` where `s` follows `shading.dpdu` and
+    /// `t` is `n` crossed with `s`.
+    ///
+    /// An arbitrary `s` around `shading.n` is used if `shading.dpdu` is degenerate or near-parallel
+    /// to the normal, e.g. on triangles with degenerate uvs.
+    pub fn shading_basis(&self) -> (Vec3<f32>, Vec3<f32>, Vec3<f32>) {
+        let n = Vec3::from(self.shading.n);
+        let dpdu = self.shading.dpdu;
+        // dpdu isn't necessarily perpendicular to the shading normal
+        let s = dpdu - n * n.dot(dpdu);
+        // Also false for NaNs
+        if s.len_sqr() > 1e-6 * dpdu.len_sqr() {
+            let s = s.normalized();
+            (s, n.cross(s), n)
+        } else {
+            let (s, t) = coordinate_system(n);
+            (s, t, n)
+        }
+    }
+
     pub fn set_shading_geometry(&mut self, dpdus: Vec3<f32>, dpdvs: Vec3<f32>) {
         self.shading.n = Normal::from(dpdus.cross(dpdvs)).normalized();
         self.n = self.n.faceforward_n(self.shading.n);
@@ -148,12 +281,18 @@ impl<'a> Mul<SurfaceInteraction> for &'a Transform<f32> {
 
         let mut ret = SurfaceInteraction {
             p: self * other.p,
+            p_error: self.point_error(other.p, other.p_error),
             n,
             uv: other.uv,
             dpdu: self * other.dpdu,
             dpdv: self * other.dpdv,
             wo: (self * other.wo).normalized(),
             shading,
+            time: other.time,
+            dudx: other.dudx,
+            dvdx: other.dvdx,
+            dudy: other.dudy,
+            dvdy: other.dvdy,
             area_light: other.area_light,
             shape_transform_swaps_handedness: other.shape_transform_swaps_handedness,
         };
@@ -165,6 +304,174 @@ impl<'a> Mul<SurfaceInteraction> for &'a Transform<f32> {
 
 impl From<&SurfaceInteraction> for Interaction {
     fn from(si: &SurfaceInteraction) -> Self {
-        Self { p: si.p, n: si.n }
+        Self {
+            p: si.p,
+            p_error: si.p_error,
+            n: si.n,
+            time: si.time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::SurfaceInteraction;
+    use crate::{
+        camera::{Camera, CameraParameters, CameraSample, CameraType, FoV},
+        film::FilmSettings,
+        materials::{Material, Matte},
+        math::{transforms::translation, Normal, Point2, Point3, Ray, Spectrum, Transform, Vec3},
+        shapes::{Mesh, Shape, Sphere, Triangle},
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn differentials_match_pixel_footprint() {
+        let camera = Camera::new(
+            CameraParameters {
+                target: Point3::new(0.0, 0.0, 1.0),
+                camera_type: CameraType::Perspective { fov: FoV::X(90.0) },
+                ..CameraParameters::default()
+            },
+            FilmSettings::default(),
+        );
+        let ray = camera.ray_differential(&CameraSample {
+            p_film: Point2::new(320.0, 240.0),
+            p_lens: Point2::new(0.5, 0.5),
+            time: 0.0,
+        });
+
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        // Plane at z = 2 with uv matching xy
+        let mut si = SurfaceInteraction::new(
+            ray.point(2.0 / ray.d.z),
+            -ray.d,
+            Point2::zeros(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+        si.compute_differentials(&ray.differential.unwrap());
+
+        // The 90 degree view is 4 units wide at z = 2 and y goes down on the film
+        let pixel_width = 4.0 / 640.0;
+        assert!((si.dudx - pixel_width).abs() < 1e-5, "{}", si.dudx);
+        assert!(si.dvdx.abs() < 1e-5, "{}", si.dvdx);
+        assert!(si.dudy.abs() < 1e-5, "{}", si.dudy);
+        assert!((si.dvdy + pixel_width).abs() < 1e-5, "{}", si.dvdy);
+    }
+
+    #[test]
+    fn shading_basis_is_orthonormal() {
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        let mut si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::zeros(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let n = Normal::from(Vec3::new(0.2, -0.4, 0.9).normalized());
+        for dpdu in [
+            // Regular, skewed, near-parallel, parallel and degenerate
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::from(n) + Vec3::new(1e-5, 0.0, 0.0),
+            Vec3::from(n) * -2.0,
+            Vec3::zeros(),
+            Vec3::new(1e-30, 0.0, 0.0),
+        ] {
+            si.shading.n = n;
+            si.shading.dpdu = dpdu;
+            let (s, t, basis_n) = si.shading_basis();
+
+            assert!((basis_n - Vec3::from(n)).len() < 1e-6);
+            for v in [s, t] {
+                assert!((v.len() - 1.0).abs() < 1e-5, "{:?} {:?}", dpdu, v);
+                assert!(v.dot(basis_n).abs() < 1e-5, "{:?} {:?}", dpdu, v);
+            }
+            assert!(s.dot(t).abs() < 1e-5, "{:?}", dpdu);
+            assert!((s.cross(t) - basis_n).len() < 1e-5, "{:?}", dpdu);
+        }
+    }
+
+    #[test]
+    fn spawned_rays_dont_self_intersect() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        // Far from the origin so that the rounding errors are significant
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2],
+            vec![
+                Point3::new(-100.0, 20.0, 300.0),
+                Point3::new(400.0, -30.0, 310.0),
+                Point3::new(50.0, 500.0, 250.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let shapes: [Arc<dyn Shape>; 2] = [
+            Arc::new(Triangle::new(mesh, 0, Arc::clone(&material), None)),
+            Arc::new(Sphere::new(
+                &translation(Vec3::new(150.0, 120.0, 280.0)),
+                60.0,
+                material,
+            )),
+        ];
+        let light = Point3::new(50.0, 100.0, 0.0);
+
+        for shape in &shapes {
+            let mut hits = 0;
+            for i in 0..64 {
+                for j in 0..64 {
+                    let target = Point3::new(
+                        -100.0 + 500.0 * (i as f32) / 64.0,
+                        -30.0 + 530.0 * (j as f32) / 64.0,
+                        280.0,
+                    );
+                    let si = match shape.intersect(Ray::new(light, target - light, f32::INFINITY)) {
+                        Some(hit) => hit.si,
+                        None => continue,
+                    };
+                    hits += 1;
+
+                    let to_light = si.spawn_ray_to(light);
+                    assert!(shape.intersect(to_light).is_none(), "{:?}", si.p);
+                    let toward_light = si.spawn_ray((light - si.p).normalized());
+                    assert!(shape.intersect(toward_light).is_none(), "{:?}", si.p);
+                }
+            }
+            assert!(hits > 100);
+        }
     }
 }
@@ -0,0 +1,309 @@
+use super::{AreaLight, EmissionSample, Light, LightSample, LightType};
+use crate::{
+    interaction::{Interaction, SurfaceInteraction},
+    math::{coordinate_system, gamma, Bounds3, Normal, Point2, Point3, Ray, Spectrum, Vec3},
+    sampling::{cosine_hemisphere_pdf, cosine_sample_hemisphere, uniform_sample_triangle},
+    shapes::Mesh,
+    visibility::VisibilityTester,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://pbr-book.org/3ed-2018/Light_Sources/Area_Lights
+// https://pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Light_Sources#AreaLights
+
+/// Uniform emitter covering a single triangle of a [`Mesh`]
+///
+/// Emits on the side of the triangle's geometric normal unless two-sided.
+pub struct DiffuseAreaLight {
+    p: [Point3<f32>; 3],
+    n: Normal<f32>,
+    l: Spectrum<f32>,
+    two_sided: bool,
+    area: f32,
+}
+
+impl DiffuseAreaLight {
+    /// Creates a new `DiffuseAreaLight` with the given radiance on the triangle in `mesh`.
+    /// `first_vertex` is the index of the first vertex index in `mesh`'s index list, like for
+    /// the matching [`Triangle`](crate::shapes::Triangle).
+    pub fn new(mesh: &Mesh, first_vertex: usize, l: Spectrum<f32>, two_sided: bool) -> Self {
+        let p = [
+            mesh.points[mesh.indices[first_vertex]],
+            mesh.points[mesh.indices[first_vertex + 1]],
+            mesh.points[mesh.indices[first_vertex + 2]],
+        ];
+
+        // Match the geometric normal of Triangle so that hits agree on the emitting side
+        let cross = (p[0] - p[2]).cross(p[1] - p[2]);
+        let n = Normal::from(cross.normalized());
+
+        Self {
+            p,
+            n: if mesh.transform_swaps_handedness {
+                -n
+            } else {
+                n
+            },
+            l,
+            two_sided,
+            area: 0.5 * cross.len(),
+        }
+    }
+
+    /// Returns the emitted radiance toward a direction at `cos_theta` from the light normal.
+    fn emitted(&self, cos_theta: f32) -> Spectrum<f32> {
+        if self.two_sided || cos_theta > 0.0 {
+            self.l
+        } else {
+            Spectrum::zeros()
+        }
+    }
+
+    /// Returns the point at barycentrics `b0`, `b1` on the triangle with its error bound.
+    fn point(&self, b: Point2<f32>) -> (Point3<f32>, Vec3<f32>) {
+        let b2 = 1.0 - b.x - b.y;
+        let p = self.p[0] * b.x + self.p[1] * b.y + self.p[2] * b2;
+        let p_abs_sum = Vec3::from((self.p[0] * b.x).abs())
+            + Vec3::from((self.p[1] * b.y).abs())
+            + Vec3::from((self.p[2] * b2).abs());
+        (p, p_abs_sum * gamma(6))
+    }
+
+    /// Returns the distance along `ray` to the triangle if they intersect.
+    fn intersect(&self, ray: Ray<f32>) -> Option<f32> {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+        let pvec = ray.d.cross(e2);
+        let det = e1.dot(pvec);
+        if det == 0.0 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.o - self.p[0];
+        let u = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = tvec.cross(e1);
+        let v = ray.d.dot(qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(qvec) * inv_det;
+        (t > 0.0 && t < ray.t_max).then_some(t)
+    }
+}
+
+impl Light for DiffuseAreaLight {
+    fn sample_li(&self, it: &Interaction, u: Point2<f32>) -> LightSample {
+        let (p, p_error) = self.point(uniform_sample_triangle(u));
+
+        let wi = (p - it.p).normalized();
+        let cos_theta = self.n.dot_v(-wi);
+        let li = self.emitted(cos_theta);
+
+        let vis = Some(VisibilityTester::new(
+            *it,
+            Interaction {
+                p,
+                p_error,
+                n: self.n,
+                time: it.time,
+            },
+            Some(self),
+        ));
+
+        let pdf = if cos_theta == 0.0 {
+            0.0
+        } else {
+            it.p.dist_sqr(p) / (cos_theta.abs() * self.area)
+        };
+
+        LightSample {
+            l: wi,
+            li,
+            vis,
+            pdf,
+        }
+    }
+
+    fn pdf_li(&self, it: &Interaction, w: Vec3<f32>) -> f32 {
+        let ray = Ray::new(it.p, w, f32::INFINITY);
+        let t = match self.intersect(ray) {
+            Some(t) => t,
+            None => return 0.0,
+        };
+
+        let cos_theta = self.n.dot_v(-w).abs();
+        if cos_theta == 0.0 {
+            return 0.0;
+        }
+        it.p.dist_sqr(ray.point(t)) / (cos_theta * self.area)
+    }
+
+    fn sample_le(
+        &self,
+        u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let (p, p_error) = self.point(uniform_sample_triangle(u_pos));
+
+        // Two-sided lights pick the emitting side with the first dimension
+        let (n, u_dir, side_pdf) = if !self.two_sided {
+            (self.n, u_dir, 1.0)
+        } else if u_dir.x < 0.5 {
+            (self.n, Point2::new(u_dir.x * 2.0, u_dir.y), 0.5)
+        } else {
+            (-self.n, Point2::new((u_dir.x - 0.5) * 2.0, u_dir.y), 0.5)
+        };
+
+        // Cosine distributed around the normal
+        let w_local = cosine_sample_hemisphere(u_dir);
+        let nv = Vec3::from(n);
+        let (v1, v2) = coordinate_system(nv);
+        let w = v1 * w_local.x + v2 * w_local.y + nv * w_local.z;
+
+        EmissionSample {
+            ray: Interaction {
+                p,
+                p_error,
+                n,
+                ..Interaction::default()
+            }
+            .spawn_ray(w),
+            n,
+            le: self.l,
+            pdf_pos: 1.0 / self.area,
+            pdf_dir: side_pdf * cosine_hemisphere_pdf(w_local.z),
+        }
+    }
+
+    fn pdf_le(&self, ray: Ray<f32>, n: Normal<f32>) -> (f32, f32) {
+        let cos_theta = n.dot_v(ray.d);
+        let pdf_dir = if self.two_sided {
+            0.5 * cosine_hemisphere_pdf(cos_theta.abs())
+        } else {
+            cosine_hemisphere_pdf(cos_theta.max(0.0))
+        };
+        (1.0 / self.area, pdf_dir)
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        self.l * self.area * std::f32::consts::PI * sides
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::AREA
+    }
+}
+
+impl AreaLight for DiffuseAreaLight {
+    fn radiance(&self, _si: &SurfaceInteraction, w: Vec3<f32>) -> Spectrum<f32> {
+        self.emitted(self.n.dot_v(w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::DiffuseAreaLight;
+    use crate::{
+        interaction::{Interaction, SurfaceInteraction},
+        lights::Light,
+        materials::Matte,
+        math::{Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::{Mesh, Sphere},
+        textures::ConstantTexture,
+    };
+
+    /// Returns the lights of a 0.1m quad at y = 1 that faces down.
+    fn emissive_quad(two_sided: bool) -> Vec<DiffuseAreaLight> {
+        let mesh = Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 0, 2, 3],
+            vec![
+                Point3::new(-0.05, 1.0, -0.05),
+                Point3::new(0.05, 1.0, -0.05),
+                Point3::new(0.05, 1.0, 0.05),
+                Point3::new(-0.05, 1.0, 0.05),
+            ],
+            Vec::new(),
+            Vec::new(),
+        );
+        (0..mesh.indices.len())
+            .step_by(3)
+            .map(|v0| DiffuseAreaLight::new(&mesh, v0, Spectrum::ones() * 5.0, two_sided))
+            .collect()
+    }
+
+    #[test]
+    fn quad_lights_nearby_wall() {
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        // Wall at `y` facing the light
+        let interaction = |y: f32| {
+            let (dpdu, dpdv) = if y < 1.0 {
+                (Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0))
+            } else {
+                (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0))
+            };
+            SurfaceInteraction::new(
+                Point3::new(0.0, y, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Point2::zeros(),
+                dpdu,
+                dpdv,
+                &shape,
+                None,
+            )
+        };
+        // Stratified estimate of the irradiance from all triangles of the quad
+        let irradiance = |lights: &[DiffuseAreaLight], si: &SurfaceInteraction| {
+            const N: usize = 16;
+            let mut e = 0.0;
+            for light in lights {
+                for i in 0..N * N {
+                    let u = Point2::new(
+                        ((i % N) as f32 + 0.5) / (N as f32),
+                        ((i / N) as f32 + 0.5) / (N as f32),
+                    );
+                    let sample = light.sample_li(&Interaction::from(si), u);
+                    assert!(sample.pdf > 0.0);
+                    // Sampled directions should agree with the solid angle density
+                    let pdf = light.pdf_li(&Interaction::from(si), sample.l);
+                    assert!((pdf - sample.pdf).abs() / sample.pdf < 1e-3);
+
+                    let cos_wall = si.n.dot_v(sample.l).max(0.0);
+                    e += sample.li.g * cos_wall / sample.pdf;
+                }
+            }
+            e / ((N * N) as f32)
+        };
+
+        // A small emitter is close to a point source with I = L * A
+        let lights = emissive_quad(false);
+        let e = irradiance(&lights, &interaction(0.0));
+        let expected = 5.0 * 0.01;
+        assert!((e - expected).abs() / expected < 0.01, "{}", e);
+
+        // Only the front side emits unless the light is two-sided
+        assert_eq!(irradiance(&lights, &interaction(2.0)), 0.0);
+        let lights = emissive_quad(true);
+        let e = irradiance(&lights, &interaction(2.0));
+        assert!((e - expected).abs() / expected < 0.01, "{}", e);
+    }
+}
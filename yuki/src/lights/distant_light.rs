@@ -1,7 +1,8 @@
-use super::{Light, LightSample};
+use super::{EmissionSample, Light, LightSample, LightType};
 use crate::{
-    interaction::{Interaction, SurfaceInteraction},
-    math::{Point2, Spectrum, Vec3},
+    interaction::Interaction,
+    math::{coordinate_system, Bounds3, Normal, Point2, Ray, Spectrum, Vec3},
+    sampling::concentric_sample_disk,
     visibility::VisibilityTester,
 };
 
@@ -21,14 +22,15 @@ impl DistantLight {
 }
 
 impl Light for DistantLight {
-    fn sample_li(&self, si: &SurfaceInteraction, _u: Point2<f32>) -> LightSample {
+    fn sample_li(&self, it: &Interaction, _u: Point2<f32>) -> LightSample {
         let li = self.radiance;
         let l = self.w;
 
         let vis = Some(VisibilityTester::new(
-            Interaction::from(si),
+            *it,
             Interaction {
-                p: si.p + self.w * 10000.0, // TODO: put point at distance of 2x world radius
+                p: it.p + self.w * 10000.0, // TODO: put point at distance of 2x world radius
+                time: it.time,
                 ..Interaction::default()
             },
             None,
@@ -41,4 +43,45 @@ impl Light for DistantLight {
             pdf: 1.0,
         }
     }
+
+    fn sample_le(
+        &self,
+        u_pos: Point2<f32>,
+        _u_dir: Point2<f32>,
+        scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let (center, radius) = scene_bounds
+            .bounding_sphere()
+            .unwrap_or((scene_bounds.p_min, 1.0));
+
+        // Sample a point on a disk that covers the scene from the light's direction
+        let (v1, v2) = coordinate_system(self.w);
+        let cd = concentric_sample_disk(u_pos);
+        let p_disk = center + (v1 * cd.x + v2 * cd.y) * radius;
+
+        let d = -self.w;
+        EmissionSample {
+            ray: Ray::new(p_disk + self.w * radius, d, f32::INFINITY),
+            n: Normal::from(d),
+            le: self.radiance,
+            pdf_pos: 1.0 / (std::f32::consts::PI * radius * radius),
+            pdf_dir: 1.0,
+        }
+    }
+
+    fn pdf_le(&self, _ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        // The positional density depends on the scene bounds so infinite lights are handled by
+        // the caller
+        (0.0, 0.0)
+    }
+
+    fn power(&self, scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        // Light that reaches the scene through a disk covering its bounding sphere
+        let radius = scene_bounds.bounding_sphere().map_or(1.0, |(_, r)| r);
+        self.radiance * std::f32::consts::PI * radius * radius
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::DELTA_DIRECTION | LightType::INFINITE
+    }
 }
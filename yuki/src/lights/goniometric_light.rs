@@ -0,0 +1,194 @@
+use super::{EmissionSample, Light, LightSample, LightType};
+use crate::{
+    interaction::Interaction,
+    math::{Bounds3, Normal, Point2, Point3, Ray, Spectrum, Transform, Vec3},
+    sampling::{uniform_sample_sphere, uniform_sphere_pdf},
+    textures::ImageTexture,
+    visibility::VisibilityTester,
+};
+
+use std::{
+    f32::consts::{FRAC_1_PI, PI},
+    sync::Arc,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://pbr-book.org/3ed-2018/Light_Sources/Point_Lights#GoniophotometricDiagramLights
+
+/// Point light with its intensity modulated by an equirectangular image
+pub struct GoniometricLight {
+    world_to_light: Transform<f32>,
+    p: Point3<f32>,
+    i: Spectrum<f32>,
+    map: Arc<ImageTexture<Spectrum<f32>>>,
+}
+
+impl GoniometricLight {
+    /// Creates a new `GoniometricLight` with the given transform, intensity scale and map.
+    ///
+    /// Identity transform has the poles of the map along Y and phi going from +X toward +Z like
+    /// in pbrt.
+    pub fn new(
+        light_to_world: &Transform<f32>,
+        i: Spectrum<f32>,
+        map: Arc<ImageTexture<Spectrum<f32>>>,
+    ) -> Self {
+        Self {
+            world_to_light: light_to_world.inverted(),
+            p: light_to_world * Point3::new(0.0, 0.0, 0.0),
+            i,
+            map,
+        }
+    }
+
+    /// Returns the scaled intensity toward world direction `w`.
+    fn intensity(&self, w: Vec3<f32>) -> Spectrum<f32> {
+        let wl = (&self.world_to_light * w).normalized();
+        let theta = wl.y.clamp(-1.0, 1.0).acos();
+        let phi = {
+            let phi = wl.z.atan2(wl.x);
+            if phi < 0.0 {
+                phi + 2.0 * PI
+            } else {
+                phi
+            }
+        };
+
+        // Lookups repeat so keep the pole at theta = pi from wrapping to the opposite one
+        let st = Point2::new(
+            phi * 0.5 * FRAC_1_PI,
+            (theta * FRAC_1_PI).min(1.0 - f32::EPSILON),
+        );
+        self.i * self.map.lookup(st)
+    }
+}
+
+impl Light for GoniometricLight {
+    fn sample_li(&self, it: &Interaction, _u: Point2<f32>) -> LightSample {
+        let to_light = self.p - it.p;
+        let dist_sqr = to_light.len_sqr();
+        let dist = dist_sqr.sqrt();
+        let l = to_light / dist;
+        let li = self.intensity(-l) / dist_sqr;
+
+        let vis = Some(VisibilityTester::new(
+            *it,
+            Interaction {
+                p: self.p,
+                time: it.time,
+                ..Interaction::default()
+            },
+            None,
+        ));
+
+        LightSample {
+            l,
+            li,
+            vis,
+            pdf: 1.0,
+        }
+    }
+
+    fn sample_le(
+        &self,
+        _u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let ray = Ray::new(self.p, uniform_sample_sphere(u_dir), f32::INFINITY);
+        EmissionSample {
+            ray,
+            n: Normal::from(ray.d),
+            le: self.intensity(ray.d),
+            pdf_pos: 1.0,
+            pdf_dir: uniform_sphere_pdf(),
+        }
+    }
+
+    fn pdf_le(&self, _ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        (0.0, uniform_sphere_pdf())
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        self.i * self.map.average() * 4.0 * PI
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::DELTA_POSITION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::abs_diff_eq;
+    use std::{io::Cursor, sync::Arc};
+
+    use super::GoniometricLight;
+    use crate::{
+        interaction::{Interaction, SurfaceInteraction},
+        lights::{Light, PointLight},
+        materials::Matte,
+        math::{transforms::translation, Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::Sphere,
+        textures::{ConstantTexture, ImageTexture},
+    };
+
+    #[test]
+    fn white_map_matches_point_light() {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 4, image::Rgb([255; 3])))
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+        let map = Arc::new(ImageTexture::from_image_bytes(&bytes).unwrap());
+
+        let light_to_world = translation(Vec3::new(0.5, 2.0, -1.0));
+        let i = Spectrum::new(1.0, 2.0, 3.0);
+        let goniometric = GoniometricLight::new(&light_to_world, i, map);
+        let point = PointLight::new(&light_to_world, i);
+
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        // Cover both poles as well as both sides of the phi seam
+        for p in [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.5, -3.0, -1.0),
+            Point3::new(0.5, 5.0, -1.0),
+            Point3::new(3.0, 2.0, -1.001),
+            Point3::new(3.0, 2.0, -0.999),
+            Point3::new(-2.0, 1.0, 4.0),
+        ] {
+            let si = SurfaceInteraction::new(
+                p,
+                Vec3::new(0.0, 1.0, 0.0),
+                Point2::zeros(),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                &shape,
+                None,
+            );
+            let u = Point2::new(0.5, 0.5);
+            let expected = point.sample_li(&Interaction::from(&si), u);
+            let sample = goniometric.sample_li(&Interaction::from(&si), u);
+
+            assert!(
+                abs_diff_eq!(sample.l, expected.l, epsilon = 1e-6),
+                "{:?}",
+                p
+            );
+            assert!(
+                abs_diff_eq!(sample.li, expected.li, epsilon = 1e-6),
+                "{:?}",
+                p
+            );
+        }
+    }
+}
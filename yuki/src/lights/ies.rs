@@ -0,0 +1,220 @@
+use std::path::Path;
+
+// Based on IESNA LM-63-2002
+// http://lumen.iee.put.poznan.pl/kw/iesna.txt
+
+#[derive(Debug)]
+pub enum IesLoadError {
+    IoError(std::io::Error),
+    FormatError(String),
+}
+
+/// Photometric intensity distribution of a luminaire
+///
+/// Vertical angles are measured from the nadir and horizontal angles around it, both in degrees.
+pub struct IesProfile {
+    vertical_angles: Vec<f32>,
+    horizontal_angles: Vec<f32>,
+    /// Intensities in candela, `vertical_angles.len()` values for each horizontal angle
+    candela: Vec<f32>,
+}
+
+impl IesProfile {
+    /// Loads an `IesProfile` from an LM-63 file.
+    pub fn load(path: &Path) -> Result<Self, IesLoadError> {
+        let text = std::fs::read_to_string(path).map_err(IesLoadError::IoError)?;
+        Self::parse(&text)
+    }
+
+    /// Parses an `IesProfile` from the contents of an LM-63 file.
+    pub fn parse(text: &str) -> Result<Self, IesLoadError> {
+        // Keywords and the header are before the tilt line
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) => {
+                    if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                        break tilt.trim().to_string();
+                    }
+                }
+                None => return Err(IesLoadError::FormatError("Missing 'TILT=' line".into())),
+            }
+        };
+
+        let mut values = lines
+            .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                v.parse::<f32>()
+                    .map_err(|_| IesLoadError::FormatError(format!("Invalid number '{}'", v)))
+            });
+        let mut next = || {
+            values.next().unwrap_or_else(|| {
+                Err(IesLoadError::FormatError(
+                    "Unexpected end of photometric data".into(),
+                ))
+            })
+        };
+
+        if tilt == "INCLUDE" {
+            // Tilt only matters for lamps that aren't mounted as measured
+            let _lamp_to_luminaire_geometry = next()?;
+            #[allow(clippy::cast_sign_loss)] // Counts are never negative
+            let count = next()? as usize;
+            for _ in 0..(2 * count) {
+                next()?;
+            }
+        }
+
+        let _lamp_count = next()?;
+        let _lumens_per_lamp = next()?;
+        let candela_multiplier = next()?;
+        #[allow(clippy::cast_sign_loss)] // Counts are never negative
+        let vertical_count = next()? as usize;
+        #[allow(clippy::cast_sign_loss)] // Counts are never negative
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        // Units and luminous opening dimensions
+        for _ in 0..4 {
+            next()?;
+        }
+        let ballast_factor = next()?;
+        // Future use / ballast-lamp factor and input watts
+        for _ in 0..2 {
+            next()?;
+        }
+
+        if photometric_type != 1.0 {
+            return Err(IesLoadError::FormatError(format!(
+                "Only type C photometry is supported, got type {}",
+                photometric_type
+            )));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(IesLoadError::FormatError("Empty angle table".into()));
+        }
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f32>, _>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f32>, _>>()?;
+        let scale = candela_multiplier * ballast_factor;
+        let candela = (0..(vertical_count * horizontal_count))
+            .map(|_| next().map(|v| v * scale))
+            .collect::<Result<Vec<f32>, _>>()?;
+
+        let is_increasing = |angles: &[f32]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !is_increasing(&vertical_angles) || !is_increasing(&horizontal_angles) {
+            return Err(IesLoadError::FormatError(
+                "Angles should be in increasing order".into(),
+            ));
+        }
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        })
+    }
+
+    /// Returns the largest intensity in this `IesProfile`.
+    pub fn max_intensity(&self) -> f32 {
+        self.candela.iter().fold(0.0, |acc, &v| acc.max(v))
+    }
+
+    /// Returns the bilinearly interpolated intensity in candela toward the given angles in degrees.
+    ///
+    /// Directions outside the measured range get the nearest measured value.
+    pub fn intensity(&self, vertical: f32, horizontal: f32) -> f32 {
+        let horizontal = self.fold_horizontal(horizontal);
+
+        let (v0, v1, tv) = lerp_indices(&self.vertical_angles, vertical);
+        let (h0, h1, th) = lerp_indices(&self.horizontal_angles, horizontal);
+
+        let vertical_count = self.vertical_angles.len();
+        let at = |h: usize, v: usize| self.candela[h * vertical_count + v];
+        let lerp = |a: f32, b: f32, t: f32| a * (1.0 - t) + b * t;
+
+        lerp(
+            lerp(at(h0, v0), at(h0, v1), tv),
+            lerp(at(h1, v0), at(h1, v1), tv),
+            th,
+        )
+    }
+
+    /// Maps `horizontal` into the measured range using the symmetry implied by the last angle.
+    fn fold_horizontal(&self, horizontal: f32) -> f32 {
+        let horizontal = horizontal.rem_euclid(360.0);
+        let last = *self.horizontal_angles.last().unwrap();
+        if last <= 0.0 {
+            // Rotationally symmetric
+            0.0
+        } else if (last - 90.0).abs() < 1e-3 {
+            // Symmetric in each quadrant
+            let h = horizontal % 180.0;
+            if h > 90.0 {
+                180.0 - h
+            } else {
+                h
+            }
+        } else if (last - 180.0).abs() < 1e-3 {
+            // Symmetric about the 0-180 plane
+            if horizontal > 180.0 {
+                360.0 - horizontal
+            } else {
+                horizontal
+            }
+        } else {
+            horizontal
+        }
+    }
+}
+
+/// Returns the indices around `x` in `angles` and the interpolation weight between them.
+fn lerp_indices(angles: &[f32], x: f32) -> (usize, usize, f32) {
+    let last = angles.len() - 1;
+    if x <= angles[0] {
+        return (0, 0, 0.0);
+    }
+    if x >= angles[last] {
+        return (last, last, 0.0);
+    }
+    let i1 = angles.partition_point(|&a| a <= x);
+    let i0 = i1 - 1;
+    let t = (x - angles[i0]) / (angles[i1] - angles[i0]);
+    (i0, i1, t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IesProfile;
+
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] Synthetic downlight
+[MANUFAC] yuki
+TILT=NONE
+1 1000 2.0 3 2 1 2 0.1 0.1 0.0
+1.0 1.0 10
+0 45 90
+0 90
+100 50 0
+200 100 0
+";
+
+    #[test]
+    fn interpolates_and_clamps() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+
+        assert!((profile.max_intensity() - 400.0).abs() < 1e-4);
+        assert!((profile.intensity(0.0, 0.0) - 200.0).abs() < 1e-4);
+        assert!((profile.intensity(22.5, 0.0) - 150.0).abs() < 1e-4);
+        assert!((profile.intensity(22.5, 45.0) - 225.0).abs() < 1e-4);
+        // Quadrant symmetry
+        assert!((profile.intensity(0.0, 270.0) - 400.0).abs() < 1e-4);
+        assert!((profile.intensity(0.0, 180.0) - 200.0).abs() < 1e-4);
+        // Past the last vertical angle
+        assert!(profile.intensity(135.0, 0.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,182 @@
+use super::{EmissionSample, Light, LightSample, LightType};
+use crate::{
+    interaction::Interaction,
+    math::{coordinate_system, Bounds3, Normal, Point2, Ray, Spectrum, Transform, Vec3},
+    sampling::{concentric_sample_disk, Distribution2D},
+    textures::ImageTexture,
+    visibility::VisibilityTester,
+};
+
+use std::{
+    f32::consts::{FRAC_1_PI, PI},
+    sync::Arc,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://pbr-book.org/3ed-2018/Light_Sources/Infinite_Area_Lights
+// https://pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Light_Sources#InfiniteAreaLights
+
+/// Environment light around the scene, mapped from an equirectangular image
+/// Identity transform has +z up
+pub struct InfiniteLight {
+    light_to_world: Transform<f32>,
+    world_to_light: Transform<f32>,
+    scale: Spectrum<f32>,
+    map: Arc<ImageTexture<Spectrum<f32>>>,
+    distribution: Distribution2D,
+}
+
+impl InfiniteLight {
+    /// Creates a new `InfiniteLight` with the given transform, radiance scale and radiance map.
+    pub fn new(
+        light_to_world: &Transform<f32>,
+        scale: Spectrum<f32>,
+        map: Arc<ImageTexture<Spectrum<f32>>>,
+    ) -> Self {
+        // Sample texel centers in uv so that rows of the distribution match v in lookups
+        let width = map.width();
+        let height = map.height();
+        let mut func = Vec::with_capacity(width * height);
+        for v in 0..height {
+            let vp = ((v as f32) + 0.5) / (height as f32);
+            // Compensate for the distortion of the equirectangular mapping near the poles
+            let sin_theta = (PI * vp).sin();
+            for u in 0..width {
+                let up = ((u as f32) + 0.5) / (width as f32);
+                func.push(map.lookup(Point2::new(up, vp)).luminance() * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+
+        Self {
+            light_to_world: light_to_world.clone(),
+            world_to_light: light_to_world.inverted(),
+            scale,
+            map,
+            distribution,
+        }
+    }
+
+    /// Returns the world direction and the solid angle density for the map sample `uv`
+    fn direction(&self, uv: Point2<f32>, map_pdf: f32) -> (Vec3<f32>, f32) {
+        let theta = uv.y * PI;
+        let phi = uv.x * 2.0 * PI;
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        let w =
+            &self.light_to_world * Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+        let pdf = if sin_theta == 0.0 {
+            0.0
+        } else {
+            map_pdf / (2.0 * PI * PI * sin_theta)
+        };
+
+        (w.normalized(), pdf)
+    }
+
+    /// Returns the map coordinates and `sin(theta)` for the world direction `w`.
+    fn map_coordinates(&self, w: Vec3<f32>) -> (Point2<f32>, f32) {
+        let wl = (&self.world_to_light * w).normalized();
+        let theta = wl.z.clamp(-1.0, 1.0).acos();
+        let phi = {
+            let phi = wl.y.atan2(wl.x);
+            if phi < 0.0 {
+                phi + 2.0 * PI
+            } else {
+                phi
+            }
+        };
+
+        (
+            Point2::new(phi * 0.5 * FRAC_1_PI, theta * FRAC_1_PI),
+            theta.sin(),
+        )
+    }
+}
+
+impl Light for InfiniteLight {
+    fn sample_li(&self, it: &Interaction, u: Point2<f32>) -> LightSample {
+        let (uv, map_pdf) = self.distribution.sample_continuous(u);
+        if map_pdf == 0.0 {
+            return LightSample {
+                l: Vec3::zeros(),
+                li: Spectrum::zeros(),
+                vis: None,
+                pdf: 0.0,
+            };
+        }
+
+        let (l, pdf) = self.direction(uv, map_pdf);
+        let li = self.map.lookup(uv) * self.scale;
+
+        let vis = Some(VisibilityTester::new(
+            *it,
+            Interaction {
+                p: it.p + l * 10000.0, // TODO: put point at distance of 2x world radius
+                time: it.time,
+                ..Interaction::default()
+            },
+            None,
+        ));
+
+        LightSample { l, li, vis, pdf }
+    }
+
+    fn pdf_li(&self, _it: &Interaction, w: Vec3<f32>) -> f32 {
+        let (uv, sin_theta) = self.map_coordinates(w);
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn le(&self, ray: Ray<f32>) -> Spectrum<f32> {
+        let (uv, _) = self.map_coordinates(ray.d);
+        self.map.lookup(uv) * self.scale
+    }
+
+    fn sample_le(
+        &self,
+        u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let (center, radius) = scene_bounds
+            .bounding_sphere()
+            .unwrap_or((scene_bounds.p_min, 1.0));
+
+        let (uv, map_pdf) = self.distribution.sample_continuous(u_dir);
+        let (w, pdf_dir) = self.direction(uv, map_pdf);
+
+        // Sample a point on a disk that covers the scene from the sampled direction
+        let (v1, v2) = coordinate_system(w);
+        let cd = concentric_sample_disk(u_pos);
+        let p_disk = center + (v1 * cd.x + v2 * cd.y) * radius;
+
+        let d = -w;
+        EmissionSample {
+            ray: Ray::new(p_disk + w * radius, d, f32::INFINITY),
+            n: Normal::from(d),
+            le: self.map.lookup(uv) * self.scale,
+            pdf_pos: 1.0 / (PI * radius * radius),
+            pdf_dir,
+        }
+    }
+
+    fn pdf_le(&self, _ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        // The positional density depends on the scene bounds so infinite lights are handled by
+        // the caller
+        (0.0, 0.0)
+    }
+
+    fn power(&self, scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        // Light that reaches the scene through a disk covering its bounding sphere
+        let radius = scene_bounds.bounding_sphere().map_or(1.0, |(_, r)| r);
+        self.scale * self.map.average() * PI * radius * radius
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::INFINITE
+    }
+}
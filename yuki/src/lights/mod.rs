@@ -1,19 +1,33 @@
+mod diffuse_area_light;
 mod distant_light;
+mod goniometric_light;
+mod ies;
+mod infinite_light;
+mod photometric_light;
 mod point_light;
 mod rectangular_light;
 mod spot_light;
 
+pub use diffuse_area_light::DiffuseAreaLight;
 pub use distant_light::DistantLight;
+pub use goniometric_light::GoniometricLight;
+pub use ies::{IesLoadError, IesProfile};
+pub use infinite_light::InfiniteLight;
+pub use photometric_light::PhotometricLight;
 pub use point_light::PointLight;
 pub use rectangular_light::RectangularLight;
 pub use spot_light::SpotLight;
 
 use crate::{
-    interaction::SurfaceInteraction,
-    math::{Point2, Spectrum, Vec3},
+    interaction::{Interaction, SurfaceInteraction},
+    math::{Bounds3, Normal, Point2, Ray, Spectrum, Vec3},
+    sampling::Distribution1D,
     visibility::VisibilityTester,
 };
 
+use bitflags::bitflags;
+use std::sync::Arc;
+
 // Based on Physically Based Rendering 3rd ed.
 // http://www.pbr-book.org/3ed-2018/Light_Sources/Light_Interface.html#Light
 // https://pbr-book.org/3ed-2018/Light_Sources/Area_Lights
@@ -26,12 +40,139 @@ pub struct LightSample<'a> {
     pub pdf: f32,
 }
 
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct LightType: u8 {
+        const DELTA_POSITION    = 0b0001;
+        const DELTA_DIRECTION   = 0b0010;
+        const AREA              = 0b0100;
+        const INFINITE          = 0b1000;
+    }
+}
+
+/// Sample of light leaving a light source
+pub struct EmissionSample {
+    pub ray: Ray<f32>,
+    /// Surface normal at the ray origin, the ray direction for lights that have no surface
+    pub n: Normal<f32>,
+    pub le: Spectrum<f32>,
+    pub pdf_pos: f32,
+    pub pdf_dir: f32,
+}
+
 pub trait Light: Send + Sync {
-    /// Returns a [`LightSample`] from the given [`SurfaceInteraction`] to this [`Light`].
-    fn sample_li(&self, si: &SurfaceInteraction, u: Point2<f32>) -> LightSample;
+    /// Returns a [`LightSample`] from the given [`Interaction`] to this [`Light`].
+    fn sample_li(&self, it: &Interaction, u: Point2<f32>) -> LightSample;
+
+    /// Returns the solid angle density of [`Light::sample_li`] sampling `w` from `si`.
+    ///
+    /// Delta lights can't be sampled by other strategies so they return `0.0`.
+    fn pdf_li(&self, _it: &Interaction, _w: Vec3<f32>) -> f32 {
+        0.0
+    }
+
+    /// Returns the radiance this `Light` contributes to `ray` escaping the scene.
+    fn le(&self, _ray: Ray<f32>) -> Spectrum<f32> {
+        Spectrum::zeros()
+    }
+
+    /// Returns an [`EmissionSample`] leaving this [`Light`].
+    ///
+    /// `scene_bounds` is needed to cover the scene with rays from lights at infinity.
+    fn sample_le(
+        &self,
+        u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample;
+
+    /// Returns the positional and directional densities for [`Light::sample_le`] sampling `ray`,
+    /// which leaves this [`Light`] at a point with surface normal `n`.
+    fn pdf_le(&self, ray: Ray<f32>, n: Normal<f32>) -> (f32, f32);
+
+    /// Returns the total power emitted by this `Light`.
+    ///
+    /// `scene_bounds` is needed to estimate the power reaching the scene from lights at infinity.
+    fn power(&self, scene_bounds: Bounds3<f32>) -> Spectrum<f32>;
+
+    /// Returns the type flags for this `Light`
+    fn flags(&self) -> LightType;
+
+    /// Returns `true` if this `Light` is described by a delta distribution
+    fn is_delta(&self) -> bool {
+        self.flags()
+            .intersects(LightType::DELTA_POSITION | LightType::DELTA_DIRECTION)
+    }
 }
 
-pub trait AreaLight: Send + Sync {
+pub trait AreaLight: Light {
     /// Returns the emitted radiance in the direction `w`.
     fn radiance(&self, si: &SurfaceInteraction, w: Vec3<f32>) -> Spectrum<f32>;
 }
+
+/// Returns a distribution for picking one of `lights` proportional to its power, `None` if there
+/// are no lights.
+pub fn power_distribution(
+    lights: &[Arc<dyn Light>],
+    scene_bounds: Bounds3<f32>,
+) -> Option<Distribution1D> {
+    if lights.is_empty() {
+        return None;
+    }
+    let power: Vec<f32> = lights
+        .iter()
+        .map(|l| l.power(scene_bounds).luminance())
+        .collect();
+    Some(Distribution1D::new(&power))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{power_distribution, Light, PointLight, RectangularLight};
+    use crate::math::{transforms::translation, Bounds3, Point3, Spectrum, Vec2, Vec3};
+
+    #[test]
+    fn power_distribution_matches_power_ratios() {
+        let lights: Vec<Arc<dyn Light>> = vec![
+            Arc::new(PointLight::new(
+                &translation(Vec3::new(0.0, 1.0, 0.0)),
+                Spectrum::ones(),
+            )),
+            Arc::new(PointLight::new(
+                &translation(Vec3::new(1.0, 1.0, 0.0)),
+                Spectrum::ones() * 3.0,
+            )),
+            Arc::new(RectangularLight::new(
+                &translation(Vec3::new(0.0, 2.0, 0.0)),
+                Spectrum::ones() * 8.0,
+                Vec2::new(0.5, 0.5),
+            )),
+        ];
+        let bounds = Bounds3::new(Point3::zeros(), Point3::new(1.0, 1.0, 1.0));
+
+        // 4pi * I for point lights and pi * A * L for the diffuse rectangle
+        let pi = std::f32::consts::PI;
+        let power = [4.0 * pi, 12.0 * pi, 2.0 * pi];
+        let total: f32 = power.iter().sum();
+
+        let distribution = power_distribution(&lights, bounds).unwrap();
+        let mut pdf_sum = 0.0;
+        for (i, (light, &expected)) in lights.iter().zip(power.iter()).enumerate() {
+            assert!(((light.power(bounds).luminance() - expected) / expected).abs() < 1e-5);
+
+            let pdf = distribution.discrete_pdf(i);
+            assert!((pdf - expected / total).abs() < 1e-6, "{}: {}", i, pdf);
+            pdf_sum += pdf;
+        }
+        assert!((pdf_sum - 1.0).abs() < 1e-6);
+
+        // Samples should land on the light whose cdf range contains u
+        assert_eq!(distribution.sample_discrete(0.1).0, 0);
+        assert_eq!(distribution.sample_discrete(0.5).0, 1);
+        assert_eq!(distribution.sample_discrete(0.95).0, 2);
+
+        assert!(power_distribution(&[], bounds).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use super::{EmissionSample, IesProfile, Light, LightSample, LightType};
+use crate::{
+    interaction::Interaction,
+    math::{Bounds3, Normal, Point2, Point3, Ray, Spectrum, Transform, Vec3},
+    sampling::{uniform_sample_sphere, uniform_sphere_pdf},
+    visibility::VisibilityTester,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://pbr-book.org/3ed-2018/Light_Sources/Point_Lights#GoniophotometricDiagramLights
+
+/// Point light with its intensity distribution given by an [`IesProfile`]
+pub struct PhotometricLight {
+    world_to_light: Transform<f32>,
+    p: Point3<f32>,
+    i: Spectrum<f32>,
+    profile: IesProfile,
+    inv_max_intensity: f32,
+}
+
+impl PhotometricLight {
+    /// Creates a new `PhotometricLight` with the given transform, peak intensity and profile.
+    ///
+    /// Identity transform has the profile nadir pointing down -Z and horizontal angles going from
+    /// +X toward +Y.
+    pub fn new(light_to_world: &Transform<f32>, i: Spectrum<f32>, profile: IesProfile) -> Self {
+        let max_intensity = profile.max_intensity();
+        Self {
+            world_to_light: light_to_world.inverted(),
+            p: light_to_world * Point3::new(0.0, 0.0, 0.0),
+            i,
+            profile,
+            inv_max_intensity: if max_intensity > 0.0 {
+                1.0 / max_intensity
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Returns the profile scale toward world direction `w`.
+    fn scale(&self, w: Vec3<f32>) -> f32 {
+        let wl = (&self.world_to_light * w).normalized();
+        let vertical = (-wl.z).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = wl.y.atan2(wl.x).to_degrees();
+        self.profile.intensity(vertical, horizontal) * self.inv_max_intensity
+    }
+}
+
+impl Light for PhotometricLight {
+    fn sample_li(&self, it: &Interaction, _u: Point2<f32>) -> LightSample {
+        let to_light = self.p - it.p;
+        let dist_sqr = to_light.len_sqr();
+        let dist = dist_sqr.sqrt();
+        let l = to_light / dist;
+        let li = self.i * self.scale(-l) / dist_sqr;
+
+        let vis = Some(VisibilityTester::new(
+            *it,
+            Interaction {
+                p: self.p,
+                time: it.time,
+                ..Interaction::default()
+            },
+            None,
+        ));
+
+        LightSample {
+            l,
+            li,
+            vis,
+            pdf: 1.0,
+        }
+    }
+
+    fn sample_le(
+        &self,
+        _u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let ray = Ray::new(self.p, uniform_sample_sphere(u_dir), f32::INFINITY);
+        EmissionSample {
+            ray,
+            n: Normal::from(ray.d),
+            le: self.i * self.scale(ray.d),
+            pdf_pos: 1.0,
+            pdf_dir: uniform_sphere_pdf(),
+        }
+    }
+
+    fn pdf_le(&self, _ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        (0.0, uniform_sphere_pdf())
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        // Integrate the profile over the sphere in light space
+        const VERTICAL_STEPS: usize = 64;
+        const HORIZONTAL_STEPS: usize = 128;
+        let d_vertical = std::f32::consts::PI / (VERTICAL_STEPS as f32);
+        let d_horizontal = 2.0 * std::f32::consts::PI / (HORIZONTAL_STEPS as f32);
+        let mut sum = 0.0;
+        for v in 0..VERTICAL_STEPS {
+            let vertical = ((v as f32) + 0.5) * d_vertical;
+            for h in 0..HORIZONTAL_STEPS {
+                let horizontal = ((h as f32) + 0.5) * d_horizontal;
+                sum += self
+                    .profile
+                    .intensity(vertical.to_degrees(), horizontal.to_degrees())
+                    * vertical.sin();
+            }
+        }
+        self.i * (sum * self.inv_max_intensity * d_vertical * d_horizontal)
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::DELTA_POSITION
+    }
+}
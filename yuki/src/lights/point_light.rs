@@ -1,7 +1,8 @@
-use super::{Light, LightSample};
+use super::{EmissionSample, Light, LightSample, LightType};
 use crate::{
-    interaction::{Interaction, SurfaceInteraction},
-    math::{Point2, Point3, Spectrum, Transform},
+    interaction::Interaction,
+    math::{Bounds3, Normal, Point2, Point3, Ray, Spectrum, Transform},
+    sampling::{uniform_sample_sphere, uniform_sphere_pdf},
     visibility::VisibilityTester,
 };
 
@@ -24,17 +25,18 @@ impl PointLight {
 }
 
 impl Light for PointLight {
-    fn sample_li(&self, si: &SurfaceInteraction, _u: Point2<f32>) -> LightSample {
-        let to_light = self.p - si.p;
+    fn sample_li(&self, it: &Interaction, _u: Point2<f32>) -> LightSample {
+        let to_light = self.p - it.p;
         let dist_sqr = to_light.len_sqr();
         let li = self.i / dist_sqr;
         let dist = dist_sqr.sqrt();
         let l = to_light / dist;
 
         let vis = Some(VisibilityTester::new(
-            Interaction::from(si),
+            *it,
             Interaction {
                 p: self.p,
+                time: it.time,
                 ..Interaction::default()
             },
             None,
@@ -47,4 +49,32 @@ impl Light for PointLight {
             pdf: 1.0,
         }
     }
+
+    fn sample_le(
+        &self,
+        _u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let ray = Ray::new(self.p, uniform_sample_sphere(u_dir), f32::INFINITY);
+        EmissionSample {
+            ray,
+            n: Normal::from(ray.d),
+            le: self.i,
+            pdf_pos: 1.0,
+            pdf_dir: uniform_sphere_pdf(),
+        }
+    }
+
+    fn pdf_le(&self, _ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        (0.0, uniform_sphere_pdf())
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        self.i * 4.0 * std::f32::consts::PI
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::DELTA_POSITION
+    }
 }
@@ -1,10 +1,12 @@
-use super::{AreaLight, Light, LightSample};
+use super::{AreaLight, EmissionSample, Light, LightSample, LightType};
 use crate::{
     interaction::{Interaction, SurfaceInteraction},
     math::{
+        coordinate_system,
         transforms::{scale, translation},
-        Normal, Point2, Point3, Spectrum, Transform, Vec2, Vec3,
+        Bounds3, Normal, Point2, Point3, Ray, Spectrum, Transform, Vec2, Vec3,
     },
+    sampling::{cosine_hemisphere_pdf, cosine_sample_hemisphere},
     visibility::VisibilityTester,
 };
 
@@ -18,8 +20,11 @@ use approx::relative_eq;
 /// Identity transform facing -y at origin
 pub struct RectangularLight {
     sample_to_world: Transform<f32>,
+    world_to_sample: Transform<f32>,
     l: Spectrum<f32>,
     area: f32,
+    /// Power of the clamped cosine emission profile, `0.0` for a diffuse emitter
+    falloff_exponent: f32,
 }
 
 impl RectangularLight {
@@ -35,32 +40,56 @@ impl RectangularLight {
         let sample_to_world = light_to_world * &sample_to_light;
         let area = size.x * size.y;
         Self {
+            world_to_sample: sample_to_world.inverted(),
             sample_to_world,
             l,
             area,
+            falloff_exponent: 0.0,
+        }
+    }
+
+    /// Returns this `RectangularLight` with its radiance scaled by `cos^falloff_exponent` of the
+    /// angle between the emission direction and the light normal.
+    pub fn with_falloff_exponent(mut self, falloff_exponent: f32) -> Self {
+        assert!(falloff_exponent >= 0.0);
+        self.falloff_exponent = falloff_exponent;
+        self
+    }
+
+    /// Returns the radiance scale toward a direction at `cos_theta` from the light normal.
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta <= 0.0 {
+            0.0
+        } else if self.falloff_exponent == 0.0 {
+            1.0
+        } else {
+            cos_theta.powf(self.falloff_exponent)
         }
     }
 }
 
 impl Light for RectangularLight {
-    fn sample_li(&self, si: &SurfaceInteraction, u: Point2<f32>) -> LightSample {
-        let p = &self.sample_to_world * Point3::new(u.x, 0.0, u.y);
+    fn sample_li(&self, it: &Interaction, u: Point2<f32>) -> LightSample {
+        let p_sample = Point3::new(u.x, 0.0, u.y);
+        let p = &self.sample_to_world * p_sample;
+        let p_error = self.sample_to_world.point_error(p_sample, Vec3::zeros());
         let n = &self.sample_to_world * Normal::new(0.0, -1.0, 0.0);
 
-        let wi = (p - si.p).normalized();
-        let li = if n.dot_v(-wi) > 0.0 {
-            self.l
-        } else {
-            Spectrum::zeros()
-        };
+        let wi = (p - it.p).normalized();
+        let li = self.l * self.falloff(n.dot_v(-wi));
 
         let vis = Some(VisibilityTester::new(
-            Interaction::from(si),
-            Interaction { p, n },
+            *it,
+            Interaction {
+                p,
+                p_error,
+                n,
+                time: it.time,
+            },
             Some(self),
         ));
 
-        let pdf = si.p.dist_sqr(p) / (n.dot_v(-wi).abs() * self.area);
+        let pdf = it.p.dist_sqr(p) / (n.dot_v(-wi).abs() * self.area);
 
         LightSample {
             l: wi,
@@ -69,14 +98,160 @@ impl Light for RectangularLight {
             pdf,
         }
     }
+
+    fn pdf_li(&self, it: &Interaction, w: Vec3<f32>) -> f32 {
+        // Find where w hits the light in sample space
+        let r = &self.world_to_sample * Ray::new(it.p, w, f32::INFINITY);
+        if r.d.y == 0.0 {
+            return 0.0;
+        }
+        let t = -r.o.y / r.d.y;
+        if t <= 0.0 {
+            return 0.0;
+        }
+        let p_sample = r.point(t);
+        if !(0.0..1.0).contains(&p_sample.x) || !(0.0..1.0).contains(&p_sample.z) {
+            return 0.0;
+        }
+
+        let p = &self.sample_to_world * p_sample;
+        let n = &self.sample_to_world * Normal::new(0.0, -1.0, 0.0);
+        let cos_theta = n.dot_v(-w).abs();
+        if cos_theta == 0.0 {
+            return 0.0;
+        }
+        it.p.dist_sqr(p) / (cos_theta * self.area)
+    }
+
+    fn sample_le(
+        &self,
+        u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let p_sample = Point3::new(u_pos.x, 0.0, u_pos.y);
+        let p = &self.sample_to_world * p_sample;
+        let p_error = self.sample_to_world.point_error(p_sample, Vec3::zeros());
+        let n = (&self.sample_to_world * Normal::new(0.0, -1.0, 0.0)).normalized();
+
+        // Cosine distributed around the normal
+        let w_local = cosine_sample_hemisphere(u_dir);
+        let nv = Vec3::from(n);
+        let (v1, v2) = coordinate_system(nv);
+        let w = v1 * w_local.x + v2 * w_local.y + nv * w_local.z;
+
+        EmissionSample {
+            ray: Interaction {
+                p,
+                p_error,
+                n,
+                ..Interaction::default()
+            }
+            .spawn_ray(w),
+            n,
+            le: self.l * self.falloff(w_local.z),
+            pdf_pos: 1.0 / self.area,
+            pdf_dir: cosine_hemisphere_pdf(w_local.z),
+        }
+    }
+
+    fn pdf_le(&self, ray: Ray<f32>, n: Normal<f32>) -> (f32, f32) {
+        (
+            1.0 / self.area,
+            cosine_hemisphere_pdf(n.dot_v(ray.d).max(0.0)),
+        )
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        // Integral of the cosine weighted emission profile over the hemisphere
+        self.l * self.area * 2.0 * std::f32::consts::PI / (self.falloff_exponent + 2.0)
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::AREA
+    }
 }
 
 impl AreaLight for RectangularLight {
     fn radiance(&self, si: &SurfaceInteraction, w: Vec3<f32>) -> Spectrum<f32> {
-        if si.n.dot_v(w) > 0.0 {
-            self.l
-        } else {
-            Spectrum::zeros()
+        self.l * self.falloff(si.n.dot_v(w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::RectangularLight;
+    use crate::{
+        interaction::{Interaction, SurfaceInteraction},
+        lights::{AreaLight, Light},
+        materials::Matte,
+        math::{Point2, Point3, Spectrum, Transform, Vec2, Vec3},
+        shapes::Sphere,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn radiance_falls_off_with_cosine_power() {
+        let exponent = 8.0;
+        let l = Spectrum::ones() * 3.0;
+        let light = RectangularLight::new(&Transform::default(), l, Vec2::new(0.01, 0.01))
+            .with_falloff_exponent(exponent);
+
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        let interaction = |p: Point3<f32>| {
+            SurfaceInteraction::new(
+                p,
+                Vec3::new(0.0, 1.0, 0.0),
+                Point2::zeros(),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                &shape,
+                None,
+            )
+        };
+
+        let mut previous_li = f32::INFINITY;
+        for x in [0.0, 0.5, 1.0, 2.0] {
+            let si = interaction(Point3::new(x, -1.0, 0.0));
+            let li = light
+                .sample_li(&Interaction::from(&si), Point2::new(0.5, 0.5))
+                .li
+                .g;
+
+            let cos_theta = 1.0 / (1.0 + x * x).sqrt();
+            let expected = l.g * cos_theta.powf(exponent);
+            assert!((li - expected).abs() < 1e-4 * l.g, "x {}", x);
+            assert!(li < previous_li);
+            previous_li = li;
         }
+
+        // Emitted radiance on the light surface follows the same profile
+        let light_si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, -1.0, 0.0),
+            Point2::zeros(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &shape,
+            None,
+        );
+        let w = Vec3::new(1.0, -1.0, 0.0).normalized();
+        let radiance = light.radiance(&light_si, w).g;
+        assert!((radiance - l.g * 0.5f32.sqrt().powf(exponent)).abs() < 1e-4 * l.g);
+        assert!(light
+            .radiance(&light_si, Vec3::new(0.0, 1.0, 0.0))
+            .is_black());
     }
 }
@@ -1,7 +1,8 @@
-use super::{Light, LightSample};
+use super::{EmissionSample, Light, LightSample, LightType};
 use crate::{
-    interaction::{Interaction, SurfaceInteraction},
-    math::{Point2, Point3, Spectrum, Transform, Vec3},
+    interaction::Interaction,
+    math::{Bounds3, Normal, Point2, Point3, Ray, Spectrum, Transform, Vec3},
+    sampling::{uniform_cone_pdf, uniform_sample_cone},
     visibility::VisibilityTester,
 };
 
@@ -9,6 +10,7 @@ use crate::{
 // http://www.pbr-book.org/3ed-2018/Light_Sources/Point_Lights.html
 
 pub struct SpotLight {
+    light_to_world: Transform<f32>,
     world_to_light: Transform<f32>,
     p: Point3<f32>,
     i: Spectrum<f32>,
@@ -19,7 +21,8 @@ pub struct SpotLight {
 impl SpotLight {
     /// Creates a new `SpotLight` with the given transform, intensity and cone parameters.
     ///
-    /// Identity transform has the light pointing down -Z.
+    /// Identity transform has the light pointing down +Z. The intensity falls off smoothly
+    /// between `falloff_start_degrees` and `total_width_degrees` from the axis.
     pub fn new(
         light_to_world: &Transform<f32>,
         i: Spectrum<f32>,
@@ -27,6 +30,7 @@ impl SpotLight {
         falloff_start_degrees: f32,
     ) -> Self {
         Self {
+            light_to_world: light_to_world.clone(),
             world_to_light: light_to_world.inverted(),
             p: light_to_world * Point3::new(0.0, 0.0, 0.0),
             i,
@@ -51,8 +55,8 @@ impl SpotLight {
 }
 
 impl Light for SpotLight {
-    fn sample_li(&self, si: &SurfaceInteraction, _u: Point2<f32>) -> LightSample {
-        let to_light = self.p - si.p;
+    fn sample_li(&self, it: &Interaction, _u: Point2<f32>) -> LightSample {
+        let to_light = self.p - it.p;
         let dist_sqr = to_light.len_sqr();
         let dist = dist_sqr.sqrt();
         let l = to_light / dist;
@@ -62,9 +66,10 @@ impl Light for SpotLight {
             None
         } else {
             Some(VisibilityTester::new(
-                Interaction::from(si),
+                *it,
                 Interaction {
                     p: self.p,
+                    time: it.time,
                     ..Interaction::default()
                 },
                 None,
@@ -78,4 +83,115 @@ impl Light for SpotLight {
             pdf: 1.0,
         }
     }
+
+    fn sample_le(
+        &self,
+        _u_pos: Point2<f32>,
+        u_dir: Point2<f32>,
+        _scene_bounds: Bounds3<f32>,
+    ) -> EmissionSample {
+        let w =
+            (&self.light_to_world * uniform_sample_cone(u_dir, self.cos_total_width)).normalized();
+        EmissionSample {
+            ray: Ray::new(self.p, w, f32::INFINITY),
+            n: Normal::from(w),
+            le: self.i * self.falloff(-w),
+            pdf_pos: 1.0,
+            pdf_dir: uniform_cone_pdf(self.cos_total_width),
+        }
+    }
+
+    fn pdf_le(&self, ray: Ray<f32>, _n: Normal<f32>) -> (f32, f32) {
+        let cos_theta = (&self.world_to_light * ray.d).normalized().z;
+        let pdf_dir = if cos_theta >= self.cos_total_width {
+            uniform_cone_pdf(self.cos_total_width)
+        } else {
+            0.0
+        };
+        (0.0, pdf_dir)
+    }
+
+    fn power(&self, _scene_bounds: Bounds3<f32>) -> Spectrum<f32> {
+        // Treat the falloff as reaching halfway between the inner and outer cone
+        self.i
+            * 2.0
+            * std::f32::consts::PI
+            * (1.0 - 0.5 * (self.cos_falloff_start + self.cos_total_width))
+    }
+
+    fn flags(&self) -> LightType {
+        LightType::DELTA_POSITION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpotLight;
+    use crate::{
+        interaction::{Interaction, SurfaceInteraction},
+        lights::Light,
+        materials::Matte,
+        math::{transforms::look_at, Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::Sphere,
+        textures::ConstantTexture,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn cone_has_soft_edge() {
+        // Shine down from above like the pbrt loader orients lights from 'from' to 'to'
+        let light_to_world = look_at(
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .inverted();
+        let i = Spectrum::ones() * 2.0;
+        let light = SpotLight::new(&light_to_world, i, 30.0, 25.0);
+
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        // Intensity at `degrees` off the light's axis, without the distance falloff
+        let li = |degrees: f32| {
+            let p = Point3::new(degrees.to_radians().tan(), 0.0, 0.0);
+            let si = SurfaceInteraction::new(
+                p,
+                Vec3::new(0.0, 1.0, 0.0),
+                Point2::zeros(),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                &shape,
+                None,
+            );
+            let dist_sqr = (Point3::new(0.0, 1.0, 0.0) - p).len_sqr();
+            light
+                .sample_li(&Interaction::from(&si), Point2::new(0.5, 0.5))
+                .li
+                .g
+                * dist_sqr
+        };
+
+        assert!((li(0.0) - i.g).abs() < 1e-5);
+        assert!((li(20.0) - i.g).abs() < 1e-5);
+
+        // Monotonically darker through the falloff region
+        let mut previous = i.g;
+        for degrees in [26.0, 27.0, 28.0, 29.0] {
+            let v = li(degrees);
+            assert!(v > 0.0 && v < previous, "{} degrees", degrees);
+            previous = v;
+        }
+
+        assert_eq!(li(31.0), 0.0);
+        assert_eq!(li(60.0), 0.0);
+    }
 }
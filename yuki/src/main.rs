@@ -14,13 +14,16 @@
 mod app;
 mod bvh;
 mod camera;
+mod cli;
 mod film;
+mod filters;
 mod integrators;
 mod interaction;
 mod lights;
 mod macros;
 mod materials;
 mod math;
+mod media;
 mod renderer;
 mod sampling;
 mod scene;
@@ -28,17 +31,14 @@ mod shapes;
 mod textures;
 mod visibility;
 
-use itertools::Itertools;
-use std::{fs::File, io::BufReader, path::PathBuf};
-
-const HELP: &str = "\
-Yuki
-USAGE:
-  yuki [OPTIONS]
-FLAGS:
-  -h, --help   Prints this help information
-OPTIONS:
-  --out=FILE   Path for EXR output";
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 fn setup_logger() -> Result<(), fern::InitError> {
     fern::Dispatch::new()
@@ -91,52 +91,79 @@ fn main() {
         yuki_error!("{}", msg);
     }));
 
-    let args: Vec<String> = std::env::args().collect();
-    let (print_help, out_path) = match args.len() {
-        1 => (false, None),
-        2 => {
-            let arg = &args[1];
-            if arg == "--help" || arg == "-h" {
-                (true, None)
-            } else {
-                let parts: Vec<&str> = arg.split('=').collect();
-                if parts.len() == 2 {
-                    let (arg_name, value) = parts.iter().next_tuple().unwrap();
-                    if arg_name == &"--out" {
-                        (false, Some(PathBuf::from(value)))
-                    } else {
-                        yuki_error!("Unexpected option '{}'", arg_name);
-                        (true, None)
-                    }
-                } else {
-                    yuki_error!("Unexpected option '{}'", arg);
-                    (true, None)
-                }
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(errors) => {
+            for why in errors {
+                yuki_error!("{}", why);
             }
+            println!("{}", cli::HELP);
+            return;
         }
-        _ => (true, None),
     };
-
-    if print_help {
-        println!("{}", HELP);
+    if args.help {
+        println!("{}", cli::HELP);
         return;
     }
 
-    let settings = match load_settings() {
+    let mut settings = match load_settings() {
         Ok(settings) => settings,
         Err(why) => {
             panic!("Failed to load previous settings: {}", why);
         }
     };
 
-    if let Some(path) = out_path {
-        app::headless::render(&path, settings);
+    if let Some(path) = args.scene_path {
+        settings
+            .load_settings
+            .get_or_insert_with(Default::default)
+            .path = path;
+    }
+    if args.width.is_some() || args.height.is_some() {
+        let film_settings = settings
+            .film_settings
+            .get_or_insert_with(film::FilmSettings::default);
+        film_settings.res.x = args.width.unwrap_or(film_settings.res.x);
+        film_settings.res.y = args.height.unwrap_or(film_settings.res.y);
+    }
+    if let Some(integrator) = args.integrator {
+        settings.scene_integrator = Some(integrator);
+    }
+    if let Some(sampler) = args.sampler {
+        settings.sampler = Some(sampler);
+    }
+
+    if let Some(path) = args.out_path {
+        // Interrupted headless renders still write out their current progress
+        let interrupted = Arc::new(AtomicBool::new(false));
+        {
+            let interrupted = Arc::clone(&interrupted);
+            expect!(
+                ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst)),
+                "Failed to set SIGINT handler"
+            );
+        }
+
+        if let Err(why) = app::headless::render(
+            &path,
+            settings,
+            &interrupted,
+            args.write_stats,
+            args.autosave_interval,
+            args.progress_interval,
+            args.noise_output,
+            args.sample_budget,
+            args.time_limit,
+            args.write_aovs,
+        ) {
+            yuki_error!("Headless render failed: {}", why);
+            std::process::exit(1);
+        }
     } else {
         let window = app::Window::new("yuki", (1920, 1080), settings);
         window.main_loop();
     }
 }
-
 fn load_settings() -> Result<app::InitialSettings, serde_yaml::Error> {
     match File::open("settings.yaml") {
         Ok(file) => {
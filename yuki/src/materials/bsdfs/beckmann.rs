@@ -0,0 +1,84 @@
+use super::{
+    cos_2_phi, cos_2_theta, cos_theta, microfacet::MicrofacetDistribution, same_hemisphere,
+    sin_2_phi, spherical_direction, tan_2_theta, tan_theta, TrowbridgeReitzDistribution,
+};
+use crate::math::{Point2, Vec3};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#MicrofacetDistributionFunctions
+// https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#MaskingandShadowing
+// https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Reflection_Functions#MicrofacetBxDFs
+
+pub struct BeckmannDistribution {
+    alpha: f32,
+}
+
+impl BeckmannDistribution {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.max(0.001),
+        }
+    }
+
+    pub fn roughness_to_alpha(roughness: f32) -> f32 {
+        // pbrt-v3 uses the same fit for both distributions
+        TrowbridgeReitzDistribution::roughness_to_alpha(roughness)
+    }
+}
+
+impl MicrofacetDistribution for BeckmannDistribution {
+    fn d(&self, wh: Vec3<f32>) -> f32 {
+        let tan_2_theta = tan_2_theta(wh);
+        if tan_2_theta.is_infinite() {
+            return 0.0;
+        }
+
+        let alpha_2 = self.alpha * self.alpha;
+        let cos_4_theta = cos_2_theta(wh) * cos_2_theta(wh);
+        (-tan_2_theta * (cos_2_phi(wh) / alpha_2 + sin_2_phi(wh) / alpha_2)).exp()
+            / (std::f32::consts::PI * alpha_2 * cos_4_theta)
+    }
+
+    fn lambda(&self, w: Vec3<f32>) -> f32 {
+        let abs_tan_theta = tan_theta(w).abs();
+        if abs_tan_theta.is_infinite() {
+            return 0.0;
+        }
+
+        let alpha = (cos_2_phi(w) * self.alpha * self.alpha
+            + sin_2_phi(w) * self.alpha * self.alpha)
+            .sqrt();
+
+        // Rational approximation of the erf based form
+        let a = 1.0 / (alpha * abs_tan_theta);
+        if a >= 1.6 {
+            return 0.0;
+        }
+        (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+    }
+
+    fn sample_wh(&self, wo: Vec3<f32>, u: Point2<f32>) -> Vec3<f32> {
+        // TODO: Visible area sampling. Bench the difference
+        let log_sample = (1.0 - u[0]).ln();
+        let tan_theta_2 = if log_sample.is_infinite() {
+            0.0
+        } else {
+            -self.alpha * self.alpha * log_sample
+        };
+        let cos_theta = 1.0 / (1.0 + tan_theta_2).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u[1];
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+        let wh = spherical_direction(sin_theta, cos_theta, phi);
+        if same_hemisphere(wo, wh) {
+            wh
+        } else {
+            -wh
+        }
+    }
+
+    fn pdf(&self, _wo: Vec3<f32>, wh: Vec3<f32>) -> f32 {
+        self.d(wh) * cos_theta(wh).abs()
+    }
+}
@@ -64,11 +64,6 @@ impl Conductor {
 
 impl Fresnel for Conductor {
     fn evaluate(&self, mut cos_theta_i: f32) -> Spectrum<f32> {
-        // TODO: Move to Specturm if this is needed elsewhere
-        fn sqrt(v: Spectrum<f32>) -> Spectrum<f32> {
-            Spectrum::new(v[0].sqrt(), v[1].sqrt(), v[2].sqrt())
-        }
-
         // pbrt does the abs before calling a helper that does the clamp into [-1,1]
         cos_theta_i = cos_theta_i.abs().min(1.0);
         let eta = self.eta_t / self.eta_i;
@@ -80,9 +75,9 @@ impl Fresnel for Conductor {
         let eta_k_2 = eta_k * eta_k;
 
         let t0 = eta_2 - eta_k_2 - sin_theta_i_2;
-        let a_2_plus_b_2 = sqrt(t0 * t0 + eta_2 * eta_k_2 * 4.0);
+        let a_2_plus_b_2 = (t0 * t0 + eta_2 * eta_k_2 * 4.0).sqrt();
         let t1 = a_2_plus_b_2 + cos_theta_i_2;
-        let a = sqrt((a_2_plus_b_2 + t0) * 0.5);
+        let a = ((a_2_plus_b_2 + t0) * 0.5).sqrt();
         let t2 = a * cos_theta_i * 2.0;
         let rs = (t1 - t2) / (t1 + t2);
 
@@ -0,0 +1,188 @@
+use super::{
+    cos_theta, reflect, same_hemisphere, Bxdf, BxdfSample, BxdfType, MicrofacetDistribution,
+};
+use crate::{
+    math::{Point2, Spectrum, Vec3},
+    sampling::cosine_sample_hemisphere,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Reflection_Models/Fresnel_Incidence_Effects#FresnelBlend
+// https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Reflection_Functions#FresnelBlend
+
+/// Ashikhmin-Shirley model of a glossy coating on a diffuse base
+///
+/// The diffuse base only receives light that isn't reflected by the coating so the lobes stay
+/// energy conserving for `rd + rs <= 1`.
+pub struct FresnelBlend<'a> {
+    rd: Spectrum<f32>,
+    rs: Spectrum<f32>,
+    distribution: &'a dyn MicrofacetDistribution,
+}
+
+impl<'a> FresnelBlend<'a> {
+    pub fn new(
+        rd: Spectrum<f32>,
+        rs: Spectrum<f32>,
+        distribution: &'a dyn MicrofacetDistribution,
+    ) -> Self {
+        Self {
+            rd,
+            rs,
+            distribution,
+        }
+    }
+
+    /// Evaluates Schlick's approximation of the coating's Fresnel reflectance
+    fn schlick_fresnel(&self, cos_theta: f32) -> Spectrum<f32> {
+        self.rs + (Spectrum::ones() - self.rs) * pow5(1.0 - cos_theta)
+    }
+}
+
+fn pow5(v: f32) -> f32 {
+    (v * v) * (v * v) * v
+}
+
+impl<'a> Bxdf for FresnelBlend<'a> {
+    fn f(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> Spectrum<f32> {
+        let cos_theta_o = cos_theta(wo).abs();
+        let cos_theta_i = cos_theta(wi).abs();
+
+        let diffuse = self.rd
+            * (Spectrum::ones() - self.rs)
+            * (28.0 / (23.0 * std::f32::consts::PI))
+            * (1.0 - pow5(1.0 - 0.5 * cos_theta_i))
+            * (1.0 - pow5(1.0 - 0.5 * cos_theta_o));
+
+        let wh = {
+            let wh = wi + wo;
+            if wh == Vec3::zeros() {
+                return Spectrum::zeros();
+            }
+            wh.normalized()
+        };
+        let i_dot_h = wi.dot(wh).abs();
+        let specular = self.schlick_fresnel(i_dot_h) * self.distribution.d(wh)
+            / (4.0 * i_dot_h * cos_theta_i.max(cos_theta_o));
+
+        diffuse + specular
+    }
+
+    fn sample_f(&self, wo: Vec3<f32>, u: Point2<f32>) -> BxdfSample {
+        // Pick the lobe with the first dimension and stretch it back to [0, 1)
+        let wi = if u.x < 0.5 {
+            let u = Point2::new((2.0 * u.x).min(1.0 - f32::EPSILON), u.y);
+            let mut wi = cosine_sample_hemisphere(u);
+            if wo.z < 0.0 {
+                wi.z *= -1.0;
+            }
+            wi
+        } else {
+            let u = Point2::new((2.0 * (u.x - 0.5)).min(1.0 - f32::EPSILON), u.y);
+            let wh = self.distribution.sample_wh(wo, u);
+            let wi = reflect(wo, wh);
+            if !same_hemisphere(wo, wi) {
+                return BxdfSample::default();
+            }
+            wi
+        };
+
+        BxdfSample {
+            wi,
+            f: self.f(wo, wi),
+            pdf: self.pdf(wo, wi),
+            sample_type: self.flags(),
+        }
+    }
+
+    fn pdf(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        let wh = (wo + wi).normalized();
+        let pdf_wh = self.distribution.pdf(wo, wh);
+
+        0.5 * (cos_theta(wi).abs() * std::f32::consts::FRAC_1_PI + pdf_wh / (4.0 * wo.dot(wh)))
+    }
+
+    fn flags(&self) -> BxdfType {
+        BxdfType::REFLECTION | BxdfType::GLOSSY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{spherical_direction, BeckmannDistribution, Bxdf},
+        FresnelBlend,
+    };
+    use crate::math::{Point2, Spectrum, Vec3};
+
+    #[test]
+    fn pdf_is_normalized() {
+        let distribution = BeckmannDistribution::new(0.2);
+        let blend = FresnelBlend::new(
+            Spectrum::ones() * 0.5,
+            Spectrum::ones() * 0.5,
+            &distribution,
+        );
+
+        for wo in [Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.6, 0.0, 0.8)] {
+            let theta_steps = 512;
+            let phi_steps = 512;
+            let d_theta = std::f32::consts::FRAC_PI_2 / (theta_steps as f32);
+            let d_phi = 2.0 * std::f32::consts::PI / (phi_steps as f32);
+            let mut sum = 0.0;
+            for i in 0..theta_steps {
+                let theta = ((i as f32) + 0.5) * d_theta;
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                for j in 0..phi_steps {
+                    let phi = ((j as f32) + 0.5) * d_phi;
+                    let wi = spherical_direction(sin_theta, cos_theta, phi);
+                    sum += blend.pdf(wo, wi) * sin_theta * d_theta * d_phi;
+                }
+            }
+            assert!((sum - 1.0).abs() < 2e-2, "{:?} integrates to {}", wo, sum);
+        }
+    }
+
+    #[test]
+    fn conserves_energy() {
+        let distribution = BeckmannDistribution::new(0.3);
+        for (rd, rs) in [(0.5, 0.5), (1.0, 0.0), (0.2, 0.8)] {
+            let blend =
+                FresnelBlend::new(Spectrum::ones() * rd, Spectrum::ones() * rs, &distribution);
+            for wo in [
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.6, 0.0, 0.8),
+                Vec3::new(0.0, 0.8, 0.6),
+            ] {
+                let steps = 256;
+                let mut sum = 0.0;
+                for i in 0..steps {
+                    for j in 0..steps {
+                        let u = Point2::new(
+                            ((i as f32) + 0.5) / (steps as f32),
+                            ((j as f32) + 0.5) / (steps as f32),
+                        );
+                        let sample = blend.sample_f(wo, u);
+                        if sample.pdf == 0.0 {
+                            continue;
+                        }
+                        sum += sample.f.g * sample.wi.z.abs() / sample.pdf;
+                    }
+                }
+                let albedo = sum / ((steps * steps) as f32);
+                assert!(
+                    albedo <= 1.01,
+                    "Rd {} Rs {} {:?} has albedo {}",
+                    rd,
+                    rs,
+                    wo,
+                    albedo
+                );
+                assert!(albedo > 0.0);
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
 
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Reflection_Models/Lambertian_Reflection
+// https://www.pbr-book.org/3ed-2018/Materials/Material_Interface_and_Implementations#TranslucentMaterial
 
 pub struct Lambertian {
     reflectance: Spectrum<f32>,
@@ -51,3 +52,49 @@ impl Bxdf for Lambertian {
         BxdfType::DIFFUSE | BxdfType::REFLECTION
     }
 }
+
+/// Lambertian lobe that scatters into the hemisphere opposite to `wo`
+pub struct LambertianTransmission {
+    transmittance: Spectrum<f32>,
+}
+
+impl LambertianTransmission {
+    pub fn new(transmittance: Spectrum<f32>) -> Self {
+        Self { transmittance }
+    }
+}
+
+impl Bxdf for LambertianTransmission {
+    fn f(&self, _: Vec3<f32>, _: Vec3<f32>) -> Spectrum<f32> {
+        self.transmittance * std::f32::consts::FRAC_1_PI
+    }
+
+    fn sample_f(&self, wo: Vec3<f32>, u: Point2<f32>) -> BxdfSample {
+        let mut wi = cosine_sample_hemisphere(u);
+        if wo.z > 0.0 {
+            wi.z *= -1.0;
+        };
+
+        let pdf = self.pdf(wo, wi);
+        let f = self.f(wo, wi);
+
+        BxdfSample {
+            wi,
+            f,
+            pdf,
+            sample_type: self.flags(),
+        }
+    }
+
+    fn pdf(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        if same_hemisphere(wo, wi) {
+            0.0
+        } else {
+            cos_theta(wi).abs() * std::f32::consts::FRAC_1_PI
+        }
+    }
+
+    fn flags(&self) -> BxdfType {
+        BxdfType::DIFFUSE | BxdfType::TRANSMISSION
+    }
+}
@@ -1,10 +1,18 @@
-use super::{cos_theta, fresnel::Fresnel, reflect, same_hemisphere, Bxdf, BxdfSample, BxdfType};
+use super::{
+    cos_theta,
+    fresnel::{self, Fresnel},
+    reflect, refract, same_hemisphere, BeckmannDistribution, Bxdf, BxdfSample, BxdfType,
+    TrowbridgeReitzDistribution,
+};
 use crate::math::{Normal, Point2, Spectrum, Vec3};
 
+use allocators::ScopedScratch;
+
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#MicrofacetDistributionFunctions
 // https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#MaskingandShadowing
 // https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#TheTorrancendashSparrowModel
+// https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Reflection_Functions#MicrofacetBxDFs
 
 pub trait MicrofacetDistribution {
     /// Evaluates the distribution function for the given surface normal
@@ -27,6 +35,40 @@ pub trait MicrofacetDistribution {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MicrofacetDistributionType {
+    Beckmann,
+    TrowbridgeReitz,
+}
+
+impl MicrofacetDistributionType {
+    /// Maps the perceptually linear `roughness` to alpha of this distribution type.
+    pub fn roughness_to_alpha(self, roughness: f32) -> f32 {
+        match self {
+            MicrofacetDistributionType::Beckmann => {
+                BeckmannDistribution::roughness_to_alpha(roughness)
+            }
+            MicrofacetDistributionType::TrowbridgeReitz => {
+                TrowbridgeReitzDistribution::roughness_to_alpha(roughness)
+            }
+        }
+    }
+
+    /// Allocates a distribution of this type with the given `alpha`.
+    pub fn alloc<'a>(
+        self,
+        scratch: &'a ScopedScratch,
+        alpha: f32,
+    ) -> &'a dyn MicrofacetDistribution {
+        match self {
+            MicrofacetDistributionType::Beckmann => scratch.alloc(BeckmannDistribution::new(alpha)),
+            MicrofacetDistributionType::TrowbridgeReitz => {
+                scratch.alloc(TrowbridgeReitzDistribution::new(alpha))
+            }
+        }
+    }
+}
+
 pub struct MicrofacetReflection<'a> {
     r: Spectrum<f32>,
     distribution: &'a dyn MicrofacetDistribution,
@@ -111,3 +153,225 @@ impl<'a> Bxdf for MicrofacetReflection<'a> {
         BxdfType::REFLECTION | BxdfType::GLOSSY
     }
 }
+
+pub struct MicrofacetTransmission<'a> {
+    t: Spectrum<f32>,
+    distribution: &'a dyn MicrofacetDistribution,
+    eta_i: f32,
+    eta_t: f32,
+    fresnel: fresnel::Dielectric,
+}
+
+impl<'a> MicrofacetTransmission<'a> {
+    pub fn new(
+        t: Spectrum<f32>,
+        distribution: &'a dyn MicrofacetDistribution,
+        eta_i: f32,
+        eta_t: f32,
+    ) -> Self {
+        Self {
+            t,
+            distribution,
+            eta_i,
+            eta_t,
+            fresnel: fresnel::Dielectric::new(eta_i, eta_t),
+        }
+    }
+
+    /// Returns the relative ior for transmission from `wo`'s side into the other side
+    fn eta(&self, wo: Vec3<f32>) -> f32 {
+        if cos_theta(wo) > 0.0 {
+            self.eta_t / self.eta_i
+        } else {
+            self.eta_i / self.eta_t
+        }
+    }
+
+    /// Returns the generalized half vector for the refraction pair, oriented with the surface normal
+    fn half_vector(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> Option<Vec3<f32>> {
+        let wh = wo + wi * self.eta(wo);
+        if wh == Vec3::zeros() {
+            return None;
+        }
+        let wh = wh.normalized();
+        Some(if wh.z < 0.0 { -wh } else { wh })
+    }
+}
+
+impl<'a> Bxdf for MicrofacetTransmission<'a> {
+    fn f(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> Spectrum<f32> {
+        if same_hemisphere(wo, wi) {
+            return Spectrum::zeros();
+        }
+
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        if cos_theta_i == 0.0 || cos_theta_o == 0.0 {
+            return Spectrum::zeros();
+        }
+
+        let wh = match self.half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return Spectrum::zeros(),
+        };
+
+        // Only microfacets that separate wo and wi can refract between them
+        let wo_dot_h = wo.dot(wh);
+        let wi_dot_h = wi.dot(wh);
+        if wo_dot_h * wi_dot_h > 0.0 {
+            return Spectrum::zeros();
+        }
+
+        let f = self.fresnel.evaluate(wo_dot_h);
+
+        let eta = self.eta(wo);
+        let sqrt_denom = wo_dot_h + eta * wi_dot_h;
+
+        (Spectrum::ones() - f)
+            * self.t
+            * (self.distribution.d(wh)
+                * self.distribution.g(wo, wi)
+                * eta
+                * eta
+                * wi_dot_h.abs()
+                * wo_dot_h.abs()
+                / (cos_theta_i * cos_theta_o * sqrt_denom * sqrt_denom))
+                .abs()
+    }
+
+    fn sample_f(&self, wo: Vec3<f32>, u: Point2<f32>) -> BxdfSample {
+        // Bsdf n = (0,0,1)
+        if wo.z == 0.0 {
+            return BxdfSample::default();
+        }
+
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(wh) < 0.0 {
+            return BxdfSample::default();
+        }
+
+        // Total internal reflection is left to the reflection lobe, where the dielectric fresnel
+        // term is one for these directions
+        refract(wo, Normal::from(wh), 1.0 / self.eta(wo)).map_or_else(BxdfSample::default, |wi| {
+            BxdfSample {
+                wi,
+                f: self.f(wo, wi),
+                pdf: self.pdf(wo, wi),
+                sample_type: self.flags(),
+            }
+        })
+    }
+
+    fn pdf(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        if same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        let wh = match self.half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return 0.0,
+        };
+
+        let wo_dot_h = wo.dot(wh);
+        let wi_dot_h = wi.dot(wh);
+        if wo_dot_h * wi_dot_h > 0.0 {
+            return 0.0;
+        }
+
+        let eta = self.eta(wo);
+        let sqrt_denom = wo_dot_h + eta * wi_dot_h;
+        let dwh_dwi = (eta * eta * wi_dot_h / (sqrt_denom * sqrt_denom)).abs();
+
+        self.distribution.pdf(wo, wh) * dwh_dwi
+    }
+
+    fn flags(&self) -> BxdfType {
+        BxdfType::TRANSMISSION | BxdfType::GLOSSY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{fresnel, spherical_direction, Bxdf, TrowbridgeReitzDistribution},
+        MicrofacetDistributionType, MicrofacetReflection, MicrofacetTransmission,
+    };
+    use crate::math::{Point2, Spectrum, Vec3};
+    use allocators::{LinearAllocator, ScopedScratch};
+
+    #[test]
+    fn distributions_are_normalized() {
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        for distribution_type in [
+            MicrofacetDistributionType::Beckmann,
+            MicrofacetDistributionType::TrowbridgeReitz,
+        ] {
+            let distribution = distribution_type.alloc(&scratch, 0.5);
+
+            // Projected microfacet area should integrate to one over the hemisphere
+            let theta_steps = 1024;
+            let phi_steps = 32;
+            let d_theta = std::f32::consts::FRAC_PI_2 / (theta_steps as f32);
+            let d_phi = 2.0 * std::f32::consts::PI / (phi_steps as f32);
+            let mut sum = 0.0;
+            for i in 0..theta_steps {
+                let theta = ((i as f32) + 0.5) * d_theta;
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                for j in 0..phi_steps {
+                    let phi = ((j as f32) + 0.5) * d_phi;
+                    let wh = spherical_direction(sin_theta, cos_theta, phi);
+                    sum += distribution.d(wh) * cos_theta * sin_theta * d_theta * d_phi;
+                }
+            }
+            assert!(
+                (sum - 1.0).abs() < 1e-2,
+                "{:?} integrates to {}",
+                distribution_type,
+                sum
+            );
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_conserves_energy() {
+        let distribution = TrowbridgeReitzDistribution::new(0.3);
+        let fresnel = fresnel::Dielectric::new(1.0, 1.5);
+        let reflection = MicrofacetReflection::new(Spectrum::ones(), &distribution, &fresnel);
+        let transmission = MicrofacetTransmission::new(Spectrum::ones(), &distribution, 1.0, 1.5);
+        let lobes: [&dyn Bxdf; 2] = [&reflection, &transmission];
+
+        // Inside directions include total internal reflection
+        for wo in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.6, 0.0, 0.8),
+            Vec3::new(0.0, 0.6, -0.8),
+            Vec3::new(0.8, 0.0, -0.6),
+        ] {
+            // Pick both lobes evenly like Bsdf does
+            let steps = 256;
+            let mut sum = 0.0;
+            for lobe in lobes {
+                for i in 0..steps {
+                    for j in 0..steps {
+                        let u = Point2::new(
+                            ((i as f32) + 0.5) / (steps as f32),
+                            ((j as f32) + 0.5) / (steps as f32),
+                        );
+                        let sample = lobe.sample_f(wo, u);
+                        if sample.pdf == 0.0 {
+                            continue;
+                        }
+                        let f = reflection.f(wo, sample.wi) + transmission.f(wo, sample.wi);
+                        let pdf =
+                            (reflection.pdf(wo, sample.wi) + transmission.pdf(wo, sample.wi)) / 2.0;
+                        sum += f.g * sample.wi.z.abs() / pdf;
+                    }
+                }
+            }
+            let albedo = sum / ((2 * steps * steps) as f32);
+            assert!(albedo <= 1.01, "{:?} has albedo {}", wo, albedo);
+            assert!(albedo > 0.5, "{:?} has albedo {}", wo, albedo);
+        }
+    }
+}
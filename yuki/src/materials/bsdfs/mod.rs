@@ -1,12 +1,19 @@
+mod beckmann;
 pub mod fresnel;
+mod fresnel_blend;
 mod lambertian;
 mod microfacet;
 mod oren_nayar;
 pub mod specular;
 mod trowbridge_reitz;
 
-pub use lambertian::Lambertian;
-pub use microfacet::{MicrofacetDistribution, MicrofacetReflection};
+pub use beckmann::BeckmannDistribution;
+pub use fresnel_blend::FresnelBlend;
+pub use lambertian::{Lambertian, LambertianTransmission};
+pub use microfacet::{
+    MicrofacetDistribution, MicrofacetDistributionType, MicrofacetReflection,
+    MicrofacetTransmission,
+};
 pub use oren_nayar::OrenNayar;
 pub use trowbridge_reitz::TrowbridgeReitzDistribution;
 
@@ -65,6 +72,13 @@ pub trait Bxdf {
     /// Returns the type flags for this `Bxdf`
     fn flags(&self) -> BxdfType;
 
+    /// Returns the fraction of light from `w` passing straight through the surface.
+    ///
+    /// Only specular transmission lets light through, other lobes block it.
+    fn transmittance(&self, _w: Vec3<f32>) -> Spectrum<f32> {
+        Spectrum::zeros()
+    }
+
     /// Returns `true` if the `Bxdf` matches the given type
     fn matches(&self, t: BxdfType) -> bool {
         t.contains(self.flags())
@@ -86,8 +100,7 @@ impl<'a> Bsdf<'a> {
     /// if shading geometry is different from the surface geometry
     pub fn new(si: &SurfaceInteraction) -> Self {
         let n_shading = si.shading.n;
-        let s_shading = si.shading.dpdu.normalized();
-        let t_shading = Vec3::from(n_shading).cross(s_shading);
+        let (s_shading, t_shading, _) = si.shading_basis();
 
         Self {
             bxdfs: Vec::new(),
@@ -98,6 +111,11 @@ impl<'a> Bsdf<'a> {
         }
     }
 
+    /// Returns the shading normal of this [`Bsdf`].
+    pub fn shading_normal(&self) -> Normal<f32> {
+        self.n_shading
+    }
+
     /// Adds 'bxdf' into this [`Bsdf`].
     pub fn add(&mut self, bxdf: &'a dyn Bxdf) {
         self.bxdfs.push(bxdf);
@@ -121,6 +139,27 @@ impl<'a> Bsdf<'a> {
         )
     }
 
+    /// Checks if `wo_world` and `wi_world` are on the same side of the geometric surface.
+    fn reflects(&self, wo_world: Vec3<f32>, wi_world: Vec3<f32>) -> bool {
+        wi_world.dot_n(self.n_geom) * wo_world.dot_n(self.n_geom) > 0.0
+    }
+
+    /// Transforms the pair of directions to surface local, keeping them on the sides of the
+    /// shading frame that match the geometric surface.
+    ///
+    /// Shading normals tilted far from the geometric one, e.g. by normal maps, can put the
+    /// directions on the same side of one and opposite sides of the other. `wi` is then
+    /// mirrored over the shading tangent plane as the geometric normal decides between reflection
+    /// and transmission.
+    fn consistent_local(&self, wo_world: Vec3<f32>, wi_world: Vec3<f32>) -> (Vec3<f32>, Vec3<f32>) {
+        let wo = self.world_to_local(wo_world);
+        let mut wi = self.world_to_local(wi_world);
+        if (wo.z * wi.z > 0.0) != self.reflects(wo_world, wi_world) {
+            wi.z = -wi.z;
+        }
+        (wo, wi)
+    }
+
     /// Evaluate distribution function for the pair of directions.
     pub fn f(
         &self,
@@ -128,10 +167,8 @@ impl<'a> Bsdf<'a> {
         wi_world: Vec3<f32>,
         bxdf_type: BxdfType,
     ) -> Spectrum<f32> {
-        let wo = self.world_to_local(wo_world);
-        let wi = self.world_to_local(wi_world);
-
-        let reflect = wi_world.dot_n(self.n_geom) * wo_world.dot_n(self.n_geom) > 0.0;
+        let (wo, wi) = self.consistent_local(wo_world, wi_world);
+        let reflect = self.reflects(wo_world, wi_world);
 
         let mut f = Spectrum::zeros();
         for bxdf in &self.bxdfs {
@@ -146,6 +183,55 @@ impl<'a> Bsdf<'a> {
         f
     }
 
+    /// Evaluate probability distribution function for the pair of directions.
+    ///
+    /// Directions that [`Bsdf::f`] mirrors to keep it consistent with the geometric normal are
+    /// rejected by [`Bsdf::sample_f`] so their pdf is zero.
+    pub fn pdf(&self, wo_world: Vec3<f32>, wi_world: Vec3<f32>, bxdf_type: BxdfType) -> f32 {
+        let wo = self.world_to_local(wo_world);
+        let wi = self.world_to_local(wi_world);
+        if wo.z == 0.0 || (wo.z * wi.z > 0.0) != self.reflects(wo_world, wi_world) {
+            return 0.0;
+        }
+
+        let mut pdf = 0.0;
+        let mut matching_comps = 0;
+        for bxdf in &self.bxdfs {
+            if bxdf.matches(bxdf_type) {
+                matching_comps += 1;
+                // Specular lobes can't be hit by arbitrary directions
+                if !bxdf.flags().contains(BxdfType::SPECULAR) {
+                    pdf += bxdf.pdf(wo, wi);
+                }
+            }
+        }
+
+        if matching_comps > 0 {
+            pdf / (matching_comps as f32)
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the fraction of light from `w_world` passing straight through the surface.
+    ///
+    /// Refraction is ignored, which is only exact for index-matched transmission like the opacity
+    /// of the uber material.
+    pub fn transmittance(&self, w_world: Vec3<f32>) -> Spectrum<f32> {
+        let w = self.world_to_local(w_world);
+        self.bxdfs
+            .iter()
+            .fold(Spectrum::zeros(), |tr, bxdf| tr + bxdf.transmittance(w))
+    }
+
+    /// Returns the number of `Bxdf`s matching `bxdf_type`.
+    pub fn num_components(&self, bxdf_type: BxdfType) -> usize {
+        self.bxdfs
+            .iter()
+            .filter(|bxdf| bxdf.matches(bxdf_type))
+            .count()
+    }
+
     /// Samples the first `Bxdf` matching `sample_type`.
     pub fn sample_f(
         &self,
@@ -173,7 +259,11 @@ impl<'a> Bsdf<'a> {
             .unwrap();
 
         let wo = self.world_to_local(wo_world);
-        let u_remapped = Point2::new(u[0] * (matching_comps - comp) as f32, u[1]);
+        // Stretch the part of u[0] that picked the component back to [0, 1)
+        let u_remapped = Point2::new(
+            (u[0] * (matching_comps as f32) - (comp as f32)).min(1.0 - f32::EPSILON),
+            u[1],
+        );
 
         let BxdfSample {
             wi: wi_local,
@@ -186,6 +276,13 @@ impl<'a> Bsdf<'a> {
         }
         let wi_world = self.local_to_world(wi_local);
 
+        // The shading frame can put the sampled direction on the other side of the geometric
+        // surface than the lobe expects, which would leak light through the surface
+        let reflect = self.reflects(wo_world, wi_world);
+        if reflect != sampled_type.contains(BxdfType::REFLECTION) {
+            return BxdfSample::default();
+        }
+
         // TODO: Verify this once multiple non-specular lobes are used
         if !bxdf.flags().contains(BxdfType::SPECULAR) && matching_comps > 1 {
             for b in &self.bxdfs {
@@ -200,7 +297,6 @@ impl<'a> Bsdf<'a> {
 
         // TODO: Verify this once multiple non-specular lobes are used
         if !bxdf.flags().contains(BxdfType::SPECULAR) && matching_comps > 1 {
-            let reflect = wi_world.dot_n(self.n_geom) * wo_world.dot_n(self.n_geom) > 0.0;
             f = Spectrum::zeros();
             for b in &self.bxdfs {
                 if b.matches(sample_type)
@@ -298,3 +394,112 @@ fn refract(wi: Vec3<f32>, n: Normal<f32>, eta: f32) -> Option<Vec3<f32>> {
 fn reflect(wo: Vec3<f32>, n: Vec3<f32>) -> Vec3<f32> {
     -wo + n * 2.0 * wo.dot(n)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fresnel, Bsdf, Bxdf, BxdfType, Lambertian, MicrofacetReflection,
+        TrowbridgeReitzDistribution,
+    };
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::Matte,
+        math::{Point2, Point3, Spectrum, Vec3},
+        shapes::Quad,
+        textures::ConstantTexture,
+    };
+
+    use std::sync::Arc;
+
+    #[test]
+    fn tilted_shading_normal_doesnt_leak() {
+        let quad = Quad::new(
+            Point3::zeros(),
+            [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)],
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+            None,
+        );
+        let mut si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::new(0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &quad,
+            None,
+        );
+        // Shading normal is tilted ~60 degrees towards -x from the geometric +z
+        si.set_shading_geometry(Vec3::new(1.0, 0.0, 1.7), Vec3::new(0.0, 1.0, 0.0));
+
+        let lambertian = Lambertian::new(Spectrum::ones());
+        let distribution = TrowbridgeReitzDistribution::new(0.3);
+        let fresnel = fresnel::Dielectric::new(1.0, 1.5);
+        let microfacet = MicrofacetReflection::new(Spectrum::ones(), &distribution, &fresnel);
+
+        // Above the geometric surface but below the shading hemisphere
+        let wo = Vec3::new(1.0, 0.0, 0.3).normalized();
+        assert!(wo.dot_n(si.shading.n) < 0.0);
+
+        let is_valid =
+            |f: Spectrum<f32>| [f.r, f.g, f.b].iter().all(|&c| c.is_finite() && c >= 0.0);
+
+        for (lobe, name) in [
+            (&lambertian as &dyn Bxdf, "lambertian"),
+            (&microfacet, "microfacet"),
+        ] {
+            let mut bsdf = Bsdf::new(&si);
+            bsdf.add(lobe);
+
+            for i in 0..16 {
+                for j in 0..16 {
+                    let u = Point2::new((i as f32 + 0.5) / 16.0, (j as f32 + 0.5) / 16.0);
+                    let sample = bsdf.sample_f(wo, u, BxdfType::all());
+                    assert!(is_valid(sample.f), "{} {:?}", name, sample.f);
+                    if !sample.f.is_black() && sample.pdf > 0.0 {
+                        assert!(sample.wi.z > 0.0, "{} leaked to {:?}", name, sample.wi);
+                    }
+
+                    // Evaluate over the sphere
+                    let theta = std::f32::consts::PI * u[0];
+                    let phi = 2.0 * std::f32::consts::PI * u[1];
+                    let wi = Vec3::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                    );
+                    let f = bsdf.f(wo, wi, BxdfType::all());
+                    assert!(is_valid(f), "{} {:?}", name, f);
+                    if wi.z < 0.0 {
+                        assert!(f.is_black(), "{} leaked to {:?}", name, wi);
+                    }
+                }
+            }
+        }
+
+        // Reflection is kept on the geometric side even where the shading frame disagrees but
+        // sampling never produces such directions so they are left to light sampling
+        let mut bsdf = Bsdf::new(&si);
+        bsdf.add(&lambertian);
+        let wi = Vec3::new(-0.3, 0.0, 1.0).normalized();
+        assert!(wi.dot_n(si.shading.n) > 0.0);
+        assert!(!bsdf.f(wo, wi, BxdfType::all()).is_black());
+        assert!(bsdf.pdf(wo, wi, BxdfType::all()) == 0.0);
+
+        // Sampled directions have the pdf they were sampled with
+        for i in 0..16 {
+            for j in 0..16 {
+                let u = Point2::new((i as f32 + 0.5) / 16.0, (j as f32 + 0.5) / 16.0);
+                let sample = bsdf.sample_f(wo, u, BxdfType::all());
+                if sample.pdf > 0.0 {
+                    let pdf = bsdf.pdf(wo, sample.wi, BxdfType::all());
+                    assert!((pdf - sample.pdf).abs() < 1e-4, "{} {}", pdf, sample.pdf);
+                }
+            }
+        }
+    }
+}
@@ -98,4 +98,8 @@ impl Bxdf for Transmission {
     fn flags(&self) -> BxdfType {
         BxdfType::SPECULAR | BxdfType::TRANSMISSION
     }
+
+    fn transmittance(&self, w: Vec3<f32>) -> Spectrum<f32> {
+        self.t * (Spectrum::ones() - self.fresnel.evaluate(cos_theta(w)))
+    }
 }
@@ -74,6 +74,6 @@ impl MicrofacetDistribution for TrowbridgeReitzDistribution {
     }
 
     fn pdf(&self, _wo: Vec3<f32>, wh: Vec3<f32>) -> f32 {
-        self.d(wh) * cos_theta(wh)
+        self.d(wh) * cos_theta(wh).abs()
     }
 }
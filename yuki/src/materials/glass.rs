@@ -1,5 +1,8 @@
 use super::{
-    bsdfs::{fresnel, specular, Bsdf},
+    bsdfs::{
+        fresnel, specular, Bsdf, MicrofacetDistributionType, MicrofacetReflection,
+        MicrofacetTransmission,
+    },
     Material,
 };
 use crate::{interaction::SurfaceInteraction, math::Spectrum, textures::Texture};
@@ -11,6 +14,9 @@ pub struct Glass {
     r: Arc<dyn Texture<Spectrum<f32>>>,
     t: Arc<dyn Texture<Spectrum<f32>>>,
     eta: f32,
+    roughness: Arc<dyn Texture<f32>>,
+    remap_roughness: bool,
+    distribution: MicrofacetDistributionType,
 }
 
 impl Glass {
@@ -18,8 +24,18 @@ impl Glass {
         r: Arc<dyn Texture<Spectrum<f32>>>,
         t: Arc<dyn Texture<Spectrum<f32>>>,
         eta: f32,
+        roughness: Arc<dyn Texture<f32>>,
+        remap_roughness: bool,
+        distribution: MicrofacetDistributionType,
     ) -> Self {
-        Self { r, t, eta }
+        Self {
+            r,
+            t,
+            eta,
+            roughness,
+            remap_roughness,
+            distribution,
+        }
     }
 }
 
@@ -30,16 +46,40 @@ impl Material for Glass {
         si: &SurfaceInteraction,
     ) -> Bsdf<'a> {
         let mut bsdf = Bsdf::new(si);
-        bsdf.add(scratch.alloc(specular::Reflection::new(
-            self.r.evaluate(si),
-            scratch.alloc(fresnel::Dielectric::new(1.0, self.eta)),
-        )));
-
-        bsdf.add(scratch.alloc(specular::Transmission::new(
-            self.t.evaluate(si),
-            1.0,
-            self.eta,
-        )));
+
+        let roughness = self.roughness.evaluate(si);
+        if roughness == 0.0 {
+            bsdf.add(scratch.alloc(specular::Reflection::new(
+                self.r.evaluate(si),
+                scratch.alloc(fresnel::Dielectric::new(1.0, self.eta)),
+            )));
+
+            bsdf.add(scratch.alloc(specular::Transmission::new(
+                self.t.evaluate(si),
+                1.0,
+                self.eta,
+            )));
+        } else {
+            let roughness = if self.remap_roughness {
+                self.distribution.roughness_to_alpha(roughness)
+            } else {
+                roughness
+            };
+            let distribution = self.distribution.alloc(scratch, roughness);
+
+            bsdf.add(scratch.alloc(MicrofacetReflection::new(
+                self.r.evaluate(si),
+                distribution,
+                scratch.alloc(fresnel::Dielectric::new(1.0, self.eta)),
+            )));
+
+            bsdf.add(scratch.alloc(MicrofacetTransmission::new(
+                self.t.evaluate(si),
+                distribution,
+                1.0,
+                self.eta,
+            )));
+        }
 
         bsdf
     }
@@ -1,5 +1,5 @@
 use super::{
-    bsdfs::{fresnel, Bsdf, MicrofacetReflection, TrowbridgeReitzDistribution},
+    bsdfs::{fresnel, Bsdf, MicrofacetDistributionType, MicrofacetReflection},
     Material,
 };
 use crate::{interaction::SurfaceInteraction, math::Spectrum, textures::Texture};
@@ -12,6 +12,7 @@ pub struct Glossy {
     rs: Arc<dyn Texture<Spectrum<f32>>>,
     roughness: Arc<dyn Texture<f32>>,
     remap_roughness: bool,
+    distribution: MicrofacetDistributionType,
 }
 
 impl Glossy {
@@ -19,11 +20,13 @@ impl Glossy {
         rs: Arc<dyn Texture<Spectrum<f32>>>,
         roughness: Arc<dyn Texture<f32>>,
         remap_roughness: bool,
+        distribution: MicrofacetDistributionType,
     ) -> Self {
         Self {
             rs,
             roughness,
             remap_roughness,
+            distribution,
         }
     }
 }
@@ -37,7 +40,8 @@ impl Material for Glossy {
         let mut bsdf = Bsdf::new(si);
 
         let roughness = if self.remap_roughness {
-            TrowbridgeReitzDistribution::roughness_to_alpha(self.roughness.evaluate(si))
+            self.distribution
+                .roughness_to_alpha(self.roughness.evaluate(si))
         } else {
             self.roughness.evaluate(si)
         };
@@ -46,11 +50,11 @@ impl Material for Glossy {
 
         let fresnel = fresnel::Schlick::new(rs);
         // Squared roughness seems to mirror how Blender's shader node behaves
-        let distribution = TrowbridgeReitzDistribution::new(roughness * roughness);
+        let distribution = self.distribution.alloc(scratch, roughness * roughness);
 
         bsdf.add(scratch.alloc(MicrofacetReflection::new(
             Spectrum::new(1.0, 1.0, 1.0),
-            scratch.alloc(distribution),
+            distribution,
             scratch.alloc(fresnel),
         )));
 
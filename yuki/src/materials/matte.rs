@@ -1,8 +1,12 @@
 use super::{
     bsdfs::{Bsdf, Lambertian, OrenNayar},
-    Material,
+    bump_mapped, normal_mapped, Material,
+};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+    textures::Texture,
 };
-use crate::{interaction::SurfaceInteraction, math::Spectrum, textures::Texture};
 
 use allocators::ScopedScratch;
 use std::sync::Arc;
@@ -10,11 +14,23 @@ use std::sync::Arc;
 pub struct Matte {
     kd: Arc<dyn Texture<Spectrum<f32>>>,
     sigma: Arc<dyn Texture<f32>>,
+    normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+    bump_map: Option<Arc<dyn Texture<f32>>>,
 }
 
 impl Matte {
-    pub fn new(kd: Arc<dyn Texture<Spectrum<f32>>>, sigma: Arc<dyn Texture<f32>>) -> Self {
-        Self { kd, sigma }
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum<f32>>>,
+        sigma: Arc<dyn Texture<f32>>,
+        normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+        bump_map: Option<Arc<dyn Texture<f32>>>,
+    ) -> Self {
+        Self {
+            kd,
+            sigma,
+            normal_map,
+            bump_map,
+        }
     }
 }
 
@@ -24,10 +40,12 @@ impl Material for Matte {
         scratch: &'a ScopedScratch,
         si: &SurfaceInteraction,
     ) -> Bsdf<'a> {
-        let mut bsdf = Bsdf::new(si);
+        let si = bump_mapped(self.bump_map.as_deref(), si);
+        let si = normal_mapped(self.normal_map.as_deref(), &si);
+        let mut bsdf = Bsdf::new(&si);
 
-        let reflectance = self.kd.evaluate(si);
-        let sigma = self.sigma.evaluate(si);
+        let reflectance = self.kd.evaluate(&si);
+        let sigma = self.sigma.evaluate(&si);
         if !reflectance.is_black() {
             if sigma == 0.0 {
                 bsdf.add(scratch.alloc(Lambertian::new(reflectance)));
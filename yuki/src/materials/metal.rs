@@ -1,5 +1,5 @@
 use super::{
-    bsdfs::{fresnel, Bsdf, MicrofacetReflection, TrowbridgeReitzDistribution},
+    bsdfs::{fresnel, Bsdf, MicrofacetDistributionType, MicrofacetReflection},
     Material,
 };
 use crate::{interaction::SurfaceInteraction, math::Spectrum, textures::Texture};
@@ -7,11 +7,55 @@ use crate::{interaction::SurfaceInteraction, math::Spectrum, textures::Texture};
 use allocators::ScopedScratch;
 use std::sync::Arc;
 
+// Measured spectra are from pbrt-v3 (copper) and refractiveindex.info
+// https://github.com/mmp/pbrt-v3/blob/master/src/materials/metal.cpp
+// https://refractiveindex.info/?shelf=main&book=Au&page=Johnson
+// https://refractiveindex.info/?shelf=main&book=Ag&page=Johnson
+// https://refractiveindex.info/?shelf=main&book=Al&page=Rakic
+
+/// Measured complex index of refraction of a conductor, sampled at `wavelengths` in nm
+pub struct ConductorSpectra {
+    pub wavelengths: &'static [f32],
+    pub eta: &'static [f32],
+    pub k: &'static [f32],
+}
+
+impl ConductorSpectra {
+    /// Returns the spectra of the conductor named by its chemical symbol, one of `"Au"`, `"Ag"`,
+    /// `"Al"` or `"Cu"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Au" => Some(Self {
+                wavelengths: &AU_AG_WAVELENGTHS,
+                eta: &AU_ETA,
+                k: &AU_K,
+            }),
+            "Ag" => Some(Self {
+                wavelengths: &AU_AG_WAVELENGTHS,
+                eta: &AG_ETA,
+                k: &AG_K,
+            }),
+            "Al" => Some(Self {
+                wavelengths: &AL_WAVELENGTHS,
+                eta: &AL_ETA,
+                k: &AL_K,
+            }),
+            "Cu" => Some(Self {
+                wavelengths: &CU_WAVELENGTHS,
+                eta: &CU_ETA,
+                k: &CU_K,
+            }),
+            _ => None,
+        }
+    }
+}
+
 pub struct Metal {
     eta: Arc<dyn Texture<Spectrum<f32>>>,
     k: Arc<dyn Texture<Spectrum<f32>>>,
     roughness: Arc<dyn Texture<f32>>,
     remap_roughness: bool,
+    distribution: MicrofacetDistributionType,
 }
 
 impl Metal {
@@ -20,12 +64,14 @@ impl Metal {
         k: Arc<dyn Texture<Spectrum<f32>>>,
         roughness: Arc<dyn Texture<f32>>,
         remap_roughness: bool,
+        distribution: MicrofacetDistributionType,
     ) -> Self {
         Self {
             eta,
             k,
             roughness,
             remap_roughness,
+            distribution,
         }
     }
 }
@@ -39,7 +85,8 @@ impl Material for Metal {
         let mut bsdf = Bsdf::new(si);
 
         let roughness = if self.remap_roughness {
-            TrowbridgeReitzDistribution::roughness_to_alpha(self.roughness.evaluate(si))
+            self.distribution
+                .roughness_to_alpha(self.roughness.evaluate(si))
         } else {
             self.roughness.evaluate(si)
         };
@@ -49,14 +96,123 @@ impl Material for Metal {
             self.eta.evaluate(si),
             self.k.evaluate(si),
         );
-        let distribution = TrowbridgeReitzDistribution::new(roughness);
+        let distribution = self.distribution.alloc(scratch, roughness);
 
         bsdf.add(scratch.alloc(MicrofacetReflection::new(
             Spectrum::new(1.0, 1.0, 1.0),
-            scratch.alloc(distribution),
+            distribution,
             scratch.alloc(fresnel),
         )));
 
         bsdf
     }
 }
+
+const N_AU_AG_SAMPLES: usize = 13;
+const AU_AG_WAVELENGTHS: [f32; N_AU_AG_SAMPLES] = [
+    397.4, 413.3, 430.5, 450.9, 471.4, 495.9, 520.9, 548.6, 582.1, 616.8, 659.5, 704.5, 756.0,
+];
+
+const AU_ETA: [f32; N_AU_AG_SAMPLES] = [
+    1.47, 1.46, 1.45, 1.38, 1.31, 1.04, 0.62, 0.43, 0.29, 0.21, 0.14, 0.13, 0.14,
+];
+
+const AU_K: [f32; N_AU_AG_SAMPLES] = [
+    1.952, 1.958, 1.948, 1.914, 1.849, 1.833, 2.081, 2.455, 2.863, 3.272, 3.697, 4.103, 4.542,
+];
+
+const AG_ETA: [f32; N_AU_AG_SAMPLES] = [
+    0.05, 0.05, 0.04, 0.04, 0.05, 0.05, 0.05, 0.06, 0.05, 0.06, 0.05, 0.04, 0.03,
+];
+
+const AG_K: [f32; N_AU_AG_SAMPLES] = [
+    2.070, 2.275, 2.462, 2.657, 2.869, 3.093, 3.324, 3.586, 3.858, 4.152, 4.483, 4.838, 5.242,
+];
+
+const N_AL_SAMPLES: usize = 8;
+const AL_WAVELENGTHS: [f32; N_AL_SAMPLES] =
+    [400.0, 450.0, 500.0, 550.0, 600.0, 650.0, 700.0, 750.0];
+
+const AL_ETA: [f32; N_AL_SAMPLES] = [0.49, 0.62, 0.77, 0.96, 1.20, 1.49, 1.83, 2.40];
+
+const AL_K: [f32; N_AL_SAMPLES] = [4.86, 5.47, 6.08, 6.69, 7.26, 7.82, 8.31, 8.62];
+
+const N_CU_SAMPLES: usize = 56;
+#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
+const CU_WAVELENGTHS: [f32; N_CU_SAMPLES] = [
+    298.7570554,
+    302.4004341,
+    306.1337728,
+    309.960445,
+    313.8839949,
+    317.9081487,
+    322.036826,
+    326.2741526,
+    330.6244747,
+    335.092373,
+    339.6826795,
+    344.4004944,
+    349.2512056,
+    354.2405086,
+    359.374429,
+    364.6593471,
+    370.1020239,
+    375.7096303,
+    381.4897785,
+    387.4505563,
+    393.6005651,
+    399.9489613,
+    406.5055016,
+    413.2805933,
+    420.2853492,
+    427.5316483,
+    435.0322035,
+    442.8006357,
+    450.8515564,
+    459.2006593,
+    467.8648226,
+    476.8622231,
+    486.2124627,
+    495.936712,
+    506.0578694,
+    516.6007417,
+    527.5922468,
+    539.0616435,
+    551.0407911,
+    563.5644455,
+    576.6705953,
+    590.4008476,
+    604.8008683,
+    619.92089,
+    635.8162974,
+    652.5483053,
+    670.1847459,
+    688.8009889,
+    708.4810171,
+    729.3186941,
+    751.4192606,
+    774.9011125,
+    799.8979226,
+    826.5611867,
+    855.0632966,
+    885.6012714,
+];
+
+#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
+const CU_ETA: [f32; N_CU_SAMPLES] = [
+    1.400313, 1.38, 1.358438, 1.34, 1.329063, 1.325, 1.3325, 1.34, 1.334375, 1.325, 1.317812, 1.31,
+    1.300313, 1.29, 1.281563, 1.27, 1.249062, 1.225, 1.2, 1.18, 1.174375, 1.175, 1.1775, 1.18,
+    1.178125, 1.175, 1.172812, 1.17, 1.165312, 1.16, 1.155312, 1.15, 1.142812, 1.135, 1.131562,
+    1.12, 1.092437, 1.04, 0.950375, 0.826, 0.645875, 0.468, 0.35125, 0.272, 0.230813, 0.214,
+    0.20925, 0.213, 0.21625, 0.223, 0.2365, 0.25, 0.254188, 0.26, 0.28, 0.3,
+];
+
+#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
+const CU_K: [f32; N_CU_SAMPLES] = [
+    1.662125, 1.687, 1.703313, 1.72, 1.744563, 1.77, 1.791625, 1.81, 1.822125, 1.834, 1.85175,
+    1.872, 1.89425, 1.916, 1.931688, 1.95, 1.972438, 2.015, 2.121562, 2.21, 2.177188, 2.13,
+    2.160063, 2.21, 2.249938, 2.289, 2.326, 2.362, 2.397625, 2.433, 2.469187, 2.504, 2.535875,
+    2.564, 2.589625, 2.605, 2.595562, 2.583, 2.5765, 2.599, 2.678062, 2.809, 3.01075, 3.24,
+    3.458187, 3.67, 3.863125, 4.05, 4.239563, 4.43, 4.619563, 4.817, 5.034125, 5.26, 5.485625,
+    5.717,
+];
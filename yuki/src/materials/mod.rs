@@ -3,16 +3,29 @@ mod glass;
 mod glossy;
 mod matte;
 mod metal;
+mod plastic;
+mod substrate;
+mod translucent;
+mod uber;
 
-pub use bsdfs::{Bsdf, BxdfSample, BxdfType};
+pub use bsdfs::{Bsdf, BxdfSample, BxdfType, MicrofacetDistributionType};
 pub use glass::Glass;
 pub use glossy::Glossy;
 pub use matte::Matte;
-pub use metal::Metal;
+pub use metal::{ConductorSpectra, Metal};
+pub use plastic::Plastic;
+pub use substrate::Substrate;
+pub use translucent::Translucent;
+pub use uber::Uber;
 
 use allocators::ScopedScratch;
+use std::borrow::Cow;
 
-use crate::interaction::SurfaceInteraction;
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{coordinate_system, Normal, Vec2, Vec3},
+    textures::Texture,
+};
 
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Materials/Material_Interface_and_Implementations
@@ -25,3 +38,187 @@ pub trait Material: Send + Sync {
         si: &SurfaceInteraction,
     ) -> Bsdf<'a>;
 }
+
+/// Returns `si` with its shading geometry perturbed by the tangent-space `normal_map`, if any.
+///
+/// The tangent frame is built from the shading `dpdu` and normal, falling back to an arbitrary
+/// frame around the normal if `dpdu` is degenerate.
+fn normal_mapped<'a>(
+    normal_map: Option<&dyn Texture<Normal<f32>>>,
+    si: &'a SurfaceInteraction,
+) -> Cow<'a, SurfaceInteraction> {
+    let normal_map = match normal_map {
+        Some(map) => map,
+        None => return Cow::Borrowed(si),
+    };
+
+    let n = Vec3::from(si.shading.n);
+    let (s, t) = if n.cross(si.shading.dpdu).len_sqr() > 0.0 {
+        let s = si.shading.dpdu.normalized();
+        (s, n.cross(s))
+    } else {
+        coordinate_system(n)
+    };
+
+    let nt = normal_map.evaluate(si);
+    let ns = (s * nt.x + t * nt.y + n * nt.z).normalized();
+
+    Cow::Owned(with_shading_normal(si, ns, s))
+}
+
+/// World space distance used to finite-difference bump maps.
+const BUMP_DELTA: f32 = 0.0005;
+
+/// Returns `si` with its shading geometry perturbed by the scalar displacement `bump_map`, if any.
+///
+/// The uv offsets are scaled by the lengths of the shading `dpdu` and `dpdv` so that the
+/// displacement is differentiated over the same world space distance regardless of the surface
+/// parametrization.
+fn bump_mapped<'a>(
+    bump_map: Option<&dyn Texture<f32>>,
+    si: &'a SurfaceInteraction,
+) -> Cow<'a, SurfaceInteraction> {
+    let bump_map = match bump_map {
+        Some(map) => map,
+        None => return Cow::Borrowed(si),
+    };
+
+    let uv_delta = |dpd: Vec3<f32>| {
+        let len = dpd.len();
+        if len > 0.0 {
+            BUMP_DELTA / len
+        } else {
+            BUMP_DELTA
+        }
+    };
+    let du = uv_delta(si.shading.dpdu);
+    let dv = uv_delta(si.shading.dpdv);
+
+    let displace = bump_map.evaluate(si);
+
+    let mut shifted = si.clone();
+    shifted.p = si.p + si.shading.dpdu * du;
+    shifted.uv = si.uv + Vec2::new(du, 0.0);
+    let u_displace = bump_map.evaluate(&shifted);
+
+    shifted.p = si.p + si.shading.dpdv * dv;
+    shifted.uv = si.uv + Vec2::new(0.0, dv);
+    let v_displace = bump_map.evaluate(&shifted);
+
+    // pbrt also includes the displacement times dndu, dndv but we don't track normal derivatives
+    let n = Vec3::from(si.shading.n);
+    let dpdu = si.shading.dpdu + n * ((u_displace - displace) / du);
+    let dpdv = si.shading.dpdv + n * ((v_displace - displace) / dv);
+
+    let ns = dpdu.cross(dpdv);
+    if ns.len_sqr() == 0.0 {
+        return Cow::Borrowed(si);
+    }
+    // Keep the normal on the side of the original shading normal regardless of handedness
+    let ns = Vec3::from(Normal::from(ns.normalized()).faceforward_n(si.shading.n));
+
+    Cow::Owned(with_shading_normal(si, ns, dpdu))
+}
+
+/// Returns a copy of `si` with `ns` as its shading normal.
+///
+/// The shading tangent is `s` projected onto the plane of `ns`.
+fn with_shading_normal(si: &SurfaceInteraction, ns: Vec3<f32>, s: Vec3<f32>) -> SurfaceInteraction {
+    let dpdu = (s - ns * s.dot(ns)).normalized();
+    let dpdv = ns.cross(dpdu);
+
+    let mut mapped = si.clone();
+    mapped.set_shading_geometry(dpdu, dpdv);
+    mapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bump_mapped, normal_mapped, Matte};
+    use crate::{
+        interaction::SurfaceInteraction,
+        math::{Normal, Point2, Point3, Spectrum, Vec3},
+        shapes::Quad,
+        textures::{ConstantTexture, Texture},
+    };
+
+    use approx::abs_diff_eq;
+    use std::sync::Arc;
+
+    fn quad() -> Quad {
+        Quad::new(
+            Point3::zeros(),
+            [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)],
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+            None,
+        )
+    }
+
+    fn interaction(shape: &Quad, dpdu: Vec3<f32>, dpdv: Vec3<f32>) -> SurfaceInteraction {
+        SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::new(0.5, 0.5),
+            dpdu,
+            dpdv,
+            shape,
+            None,
+        )
+    }
+
+    struct RampTexture {
+        slope: f32,
+    }
+
+    impl Texture<f32> for RampTexture {
+        fn evaluate(&self, si: &SurfaceInteraction) -> f32 {
+            si.p.x * self.slope
+        }
+    }
+
+    #[test]
+    fn normal_map_perturbs_shading_normal() {
+        // Only used for its handedness
+        let quad = quad();
+        let si = interaction(&quad, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let flat = ConstantTexture::new(Normal::new(0.0, 0.0, 1.0));
+        let mapped = normal_mapped(Some(&flat), &si);
+        assert!(abs_diff_eq!(mapped.shading.n, si.n, epsilon = 1e-6));
+
+        // Tilted towards +u
+        let tilted = Normal::new(1.0, 0.0, 1.0).normalized();
+        let mapped = normal_mapped(Some(&ConstantTexture::new(tilted)), &si);
+        assert!(abs_diff_eq!(mapped.shading.n, tilted, epsilon = 1e-6));
+        assert!(mapped.shading.n.dot_v(mapped.shading.dpdu).abs() < 1e-6);
+        assert!(mapped.shading.n.dot_v(mapped.shading.dpdv).abs() < 1e-6);
+
+        assert!(matches!(
+            normal_mapped(None, &si),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn bump_map_perturbs_shading_normal() {
+        let quad = quad();
+        // Stretched parametrization shouldn't affect the result
+        let si = interaction(&quad, Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.25, 0.0));
+
+        let flat = ConstantTexture::new(0.3);
+        let bumped = bump_mapped(Some(&flat), &si);
+        assert!(abs_diff_eq!(bumped.shading.n, si.n, epsilon = 1e-6));
+        assert!(abs_diff_eq!(bumped.n, si.n, epsilon = 1e-6));
+
+        // Height grows along +x so the normal should tilt towards -x
+        let ramp = RampTexture { slope: 1.0 };
+        let bumped = bump_mapped(Some(&ramp), &si);
+        let expected = Normal::new(-1.0, 0.0, 1.0).normalized();
+        assert!(abs_diff_eq!(bumped.shading.n, expected, epsilon = 1e-3));
+    }
+}
@@ -0,0 +1,81 @@
+use super::{
+    bsdfs::{fresnel, Bsdf, Lambertian, MicrofacetReflection, TrowbridgeReitzDistribution},
+    bump_mapped, normal_mapped, Material,
+};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+    textures::Texture,
+};
+
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Materials/Material_Interface_and_Implementations#PlasticMaterial
+
+pub struct Plastic {
+    kd: Arc<dyn Texture<Spectrum<f32>>>,
+    ks: Arc<dyn Texture<Spectrum<f32>>>,
+    roughness: Arc<dyn Texture<f32>>,
+    remap_roughness: bool,
+    normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+    bump_map: Option<Arc<dyn Texture<f32>>>,
+}
+
+impl Plastic {
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum<f32>>>,
+        ks: Arc<dyn Texture<Spectrum<f32>>>,
+        roughness: Arc<dyn Texture<f32>>,
+        remap_roughness: bool,
+        normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+        bump_map: Option<Arc<dyn Texture<f32>>>,
+    ) -> Self {
+        Self {
+            kd,
+            ks,
+            roughness,
+            remap_roughness,
+            normal_map,
+            bump_map,
+        }
+    }
+}
+
+impl Material for Plastic {
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        let si = bump_mapped(self.bump_map.as_deref(), si);
+        let si = normal_mapped(self.normal_map.as_deref(), &si);
+        let mut bsdf = Bsdf::new(&si);
+
+        let kd = self.kd.evaluate(&si);
+        if !kd.is_black() {
+            bsdf.add(scratch.alloc(Lambertian::new(kd)));
+        }
+
+        let ks = self.ks.evaluate(&si);
+        if !ks.is_black() {
+            let roughness = if self.remap_roughness {
+                TrowbridgeReitzDistribution::roughness_to_alpha(self.roughness.evaluate(&si))
+            } else {
+                self.roughness.evaluate(&si)
+            };
+
+            let fresnel = fresnel::Dielectric::new(1.5, 1.0);
+            let distribution = TrowbridgeReitzDistribution::new(roughness);
+
+            bsdf.add(scratch.alloc(MicrofacetReflection::new(
+                ks,
+                scratch.alloc(distribution),
+                scratch.alloc(fresnel),
+            )));
+        }
+
+        bsdf
+    }
+}
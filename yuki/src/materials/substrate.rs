@@ -0,0 +1,79 @@
+use super::{
+    bsdfs::{Bsdf, FresnelBlend, MicrofacetDistributionType},
+    bump_mapped, normal_mapped, Material,
+};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+    textures::Texture,
+};
+
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Materials/Material_Interface_and_Implementations#SubstrateMaterial
+
+/// Diffuse base under a glossy coating
+pub struct Substrate {
+    kd: Arc<dyn Texture<Spectrum<f32>>>,
+    ks: Arc<dyn Texture<Spectrum<f32>>>,
+    roughness: Arc<dyn Texture<f32>>,
+    remap_roughness: bool,
+    distribution: MicrofacetDistributionType,
+    normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+    bump_map: Option<Arc<dyn Texture<f32>>>,
+}
+
+impl Substrate {
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum<f32>>>,
+        ks: Arc<dyn Texture<Spectrum<f32>>>,
+        roughness: Arc<dyn Texture<f32>>,
+        remap_roughness: bool,
+        distribution: MicrofacetDistributionType,
+        normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+        bump_map: Option<Arc<dyn Texture<f32>>>,
+    ) -> Self {
+        Self {
+            kd,
+            ks,
+            roughness,
+            remap_roughness,
+            distribution,
+            normal_map,
+            bump_map,
+        }
+    }
+}
+
+impl Material for Substrate {
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        let si = bump_mapped(self.bump_map.as_deref(), si);
+        let si = normal_mapped(self.normal_map.as_deref(), &si);
+        let mut bsdf = Bsdf::new(&si);
+
+        let kd = self.kd.evaluate(&si);
+        let ks = self.ks.evaluate(&si);
+        if !kd.is_black() || !ks.is_black() {
+            let roughness = self.roughness.evaluate(&si);
+            let alpha = if self.remap_roughness {
+                self.distribution.roughness_to_alpha(roughness)
+            } else {
+                roughness
+            };
+
+            bsdf.add(scratch.alloc(FresnelBlend::new(
+                kd,
+                ks,
+                self.distribution.alloc(scratch, alpha),
+            )));
+        }
+
+        bsdf
+    }
+}
@@ -0,0 +1,136 @@
+use super::{
+    bsdfs::{Bsdf, Lambertian, LambertianTransmission},
+    bump_mapped, normal_mapped, Material,
+};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+    textures::Texture,
+};
+
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Materials/Material_Interface_and_Implementations#TranslucentMaterial
+
+/// Thin diffuse surface that both reflects and transmits light
+///
+/// The diffuse color `kd` is split into the reflected and transmitted parts by `reflect` and
+/// `transmit`.
+pub struct Translucent {
+    kd: Arc<dyn Texture<Spectrum<f32>>>,
+    reflect: Arc<dyn Texture<Spectrum<f32>>>,
+    transmit: Arc<dyn Texture<Spectrum<f32>>>,
+    normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+    bump_map: Option<Arc<dyn Texture<f32>>>,
+}
+
+impl Translucent {
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum<f32>>>,
+        reflect: Arc<dyn Texture<Spectrum<f32>>>,
+        transmit: Arc<dyn Texture<Spectrum<f32>>>,
+        normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+        bump_map: Option<Arc<dyn Texture<f32>>>,
+    ) -> Self {
+        Self {
+            kd,
+            reflect,
+            transmit,
+            normal_map,
+            bump_map,
+        }
+    }
+}
+
+impl Material for Translucent {
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        let si = bump_mapped(self.bump_map.as_deref(), si);
+        let si = normal_mapped(self.normal_map.as_deref(), &si);
+        let mut bsdf = Bsdf::new(&si);
+
+        let kd = self.kd.evaluate(&si);
+        if kd.is_black() {
+            return bsdf;
+        }
+
+        let reflectance = kd * self.reflect.evaluate(&si);
+        if !reflectance.is_black() {
+            bsdf.add(scratch.alloc(Lambertian::new(reflectance)));
+        }
+        let transmittance = kd * self.transmit.evaluate(&si);
+        if !transmittance.is_black() {
+            bsdf.add(scratch.alloc(LambertianTransmission::new(transmittance)));
+        }
+
+        bsdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::Translucent;
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::{BxdfType, Material, Matte},
+        math::{Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::Sphere,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn fully_reflective_matches_matte() {
+        let kd = Spectrum::new(0.8, 0.5, 0.2);
+        let matte: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(kd)),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let translucent = Translucent::new(
+            Arc::new(ConstantTexture::new(kd)),
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(Spectrum::zeros())),
+            None,
+            None,
+        );
+
+        // Only used for its handedness
+        let shape = Sphere::new(&Transform::default(), 1.0, Arc::clone(&matte));
+        let si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::zeros(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let matte_bsdf = matte.compute_scattering_functions(&scratch, &si);
+        let translucent_bsdf = translucent.compute_scattering_functions(&scratch, &si);
+
+        let wo = Vec3::new(0.0, 0.6, 0.8);
+        for wi in [Vec3::new(0.6, 0.0, 0.8), Vec3::new(0.0, 0.0, -1.0)] {
+            let f_matte = matte_bsdf.f(wo, wi, BxdfType::all());
+            let f_translucent = translucent_bsdf.f(wo, wi, BxdfType::all());
+            assert_eq!(f_matte.r, f_translucent.r);
+            assert_eq!(f_matte.g, f_translucent.g);
+            assert_eq!(f_matte.b, f_translucent.b);
+            assert_eq!(
+                matte_bsdf.pdf(wo, wi, BxdfType::all()),
+                translucent_bsdf.pdf(wo, wi, BxdfType::all())
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::{
+    bsdfs::{
+        fresnel, specular, Bsdf, Lambertian, MicrofacetDistributionType, MicrofacetReflection,
+    },
+    bump_mapped, normal_mapped, Material,
+};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+    textures::Texture,
+};
+
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://github.com/mmp/pbrt-v3/blob/master/src/materials/uber.cpp
+
+/// Catch-all material of pbrt scenes
+///
+/// Combines diffuse, glossy, specular reflection and specular transmission lobes. Each lobe is
+/// scaled by `opacity` and the rest passes straight through the surface.
+pub struct Uber {
+    kd: Arc<dyn Texture<Spectrum<f32>>>,
+    ks: Arc<dyn Texture<Spectrum<f32>>>,
+    kr: Arc<dyn Texture<Spectrum<f32>>>,
+    kt: Arc<dyn Texture<Spectrum<f32>>>,
+    roughness: Arc<dyn Texture<f32>>,
+    opacity: Arc<dyn Texture<Spectrum<f32>>>,
+    eta: f32,
+    remap_roughness: bool,
+    distribution: MicrofacetDistributionType,
+    normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+    bump_map: Option<Arc<dyn Texture<f32>>>,
+}
+
+impl Uber {
+    pub fn new(
+        kd: Arc<dyn Texture<Spectrum<f32>>>,
+        ks: Arc<dyn Texture<Spectrum<f32>>>,
+        kr: Arc<dyn Texture<Spectrum<f32>>>,
+        kt: Arc<dyn Texture<Spectrum<f32>>>,
+        roughness: Arc<dyn Texture<f32>>,
+        opacity: Arc<dyn Texture<Spectrum<f32>>>,
+        eta: f32,
+        remap_roughness: bool,
+        distribution: MicrofacetDistributionType,
+        normal_map: Option<Arc<dyn Texture<Normal<f32>>>>,
+        bump_map: Option<Arc<dyn Texture<f32>>>,
+    ) -> Self {
+        Self {
+            kd,
+            ks,
+            kr,
+            kt,
+            roughness,
+            opacity,
+            eta,
+            remap_roughness,
+            distribution,
+            normal_map,
+            bump_map,
+        }
+    }
+}
+
+impl Material for Uber {
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        let si = bump_mapped(self.bump_map.as_deref(), si);
+        let si = normal_mapped(self.normal_map.as_deref(), &si);
+        let mut bsdf = Bsdf::new(&si);
+
+        let opacity = self
+            .opacity
+            .evaluate(&si)
+            .max(Spectrum::zeros())
+            .min(Spectrum::ones());
+        let passthrough = Spectrum::ones() - opacity;
+        if !passthrough.is_black() {
+            bsdf.add(scratch.alloc(specular::Transmission::new(passthrough, 1.0, 1.0)));
+        }
+
+        let kd = opacity * self.kd.evaluate(&si);
+        if !kd.is_black() {
+            bsdf.add(scratch.alloc(Lambertian::new(kd)));
+        }
+
+        let ks = opacity * self.ks.evaluate(&si);
+        if !ks.is_black() {
+            let roughness = self.roughness.evaluate(&si);
+            let alpha = if self.remap_roughness {
+                self.distribution.roughness_to_alpha(roughness)
+            } else {
+                roughness
+            };
+
+            bsdf.add(scratch.alloc(MicrofacetReflection::new(
+                ks,
+                self.distribution.alloc(scratch, alpha),
+                scratch.alloc(fresnel::Dielectric::new(1.0, self.eta)),
+            )));
+        }
+
+        let kr = opacity * self.kr.evaluate(&si);
+        if !kr.is_black() {
+            bsdf.add(scratch.alloc(specular::Reflection::new(
+                kr,
+                scratch.alloc(fresnel::Dielectric::new(1.0, self.eta)),
+            )));
+        }
+
+        let kt = opacity * self.kt.evaluate(&si);
+        if !kt.is_black() {
+            bsdf.add(scratch.alloc(specular::Transmission::new(kt, 1.0, self.eta)));
+        }
+
+        bsdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::Uber;
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::{BxdfType, Material, Matte, MicrofacetDistributionType},
+        math::{Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::Sphere,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn diffuse_only_matches_matte() {
+        let kd = Spectrum::new(0.8, 0.5, 0.2);
+        let matte: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(kd)),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let uber = Uber::new(
+            Arc::new(ConstantTexture::new(kd)),
+            Arc::new(ConstantTexture::new(Spectrum::zeros())),
+            Arc::new(ConstantTexture::new(Spectrum::zeros())),
+            Arc::new(ConstantTexture::new(Spectrum::zeros())),
+            Arc::new(ConstantTexture::new(0.1)),
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            1.5,
+            true,
+            MicrofacetDistributionType::TrowbridgeReitz,
+            None,
+            None,
+        );
+
+        // Only used for its handedness
+        let shape = Sphere::new(&Transform::default(), 1.0, Arc::clone(&matte));
+        let si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::zeros(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let matte_bsdf = matte.compute_scattering_functions(&scratch, &si);
+        let uber_bsdf = uber.compute_scattering_functions(&scratch, &si);
+
+        let wo = Vec3::new(0.0, 0.6, 0.8);
+        for wi in [Vec3::new(0.6, 0.0, 0.8), Vec3::new(0.0, 0.0, -1.0)] {
+            assert_eq!(
+                matte_bsdf.f(wo, wi, BxdfType::all()),
+                uber_bsdf.f(wo, wi, BxdfType::all())
+            );
+            assert_eq!(
+                matte_bsdf.pdf(wo, wi, BxdfType::all()),
+                uber_bsdf.pdf(wo, wi, BxdfType::all())
+            );
+        }
+        assert_eq!(
+            matte_bsdf.num_components(BxdfType::all()),
+            uber_bsdf.num_components(BxdfType::all())
+        );
+    }
+}
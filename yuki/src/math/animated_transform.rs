@@ -0,0 +1,170 @@
+use super::{
+    bounds::Bounds3, common::FloatValueType, matrix::Matrix4x4, quaternion::Quaternion,
+    transform::Transform, vector::Vec3,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Geometry_and_Transformations/Animating_Transformations
+
+/// Number of interpolated transforms used to approximate the swept bounds of a motion
+const MOTION_BOUNDS_SAMPLES: usize = 64;
+
+/// A [Transform] that is interpolated between `start` and `end` over a time range
+///
+/// Translation and scale are interpolated linearly and rotation spherically.
+#[derive(Clone, Debug)]
+pub struct AnimatedTransform<T>
+where
+    T: FloatValueType,
+{
+    start: Transform<T>,
+    end: Transform<T>,
+    start_time: T,
+    end_time: T,
+    animated: bool,
+    translations: [Vec3<T>; 2],
+    rotations: [Quaternion<T>; 2],
+    scales: [Matrix4x4<T>; 2],
+    /// Transforms at evenly spaced times for bounding the motion
+    motion_samples: Vec<Transform<T>>,
+}
+
+impl<T> AnimatedTransform<T>
+where
+    T: FloatValueType,
+{
+    /// Creates a new `AnimatedTransform` that is `start` at `start_time` and `end` at `end_time`.
+    pub fn new(start: &Transform<T>, start_time: T, end: &Transform<T>, end_time: T) -> Self {
+        let animated = start != end;
+
+        let (t0, r0, s0) = decompose(start.m());
+        let (t1, r1, s1) = decompose(end.m());
+
+        let mut ret = Self {
+            start: start.clone(),
+            end: end.clone(),
+            start_time,
+            end_time,
+            animated,
+            translations: [t0, t1],
+            rotations: [r0, r1],
+            scales: [s0, s1],
+            motion_samples: Vec::new(),
+        };
+
+        if animated {
+            let motion_samples = (0..MOTION_BOUNDS_SAMPLES)
+                .map(|i| {
+                    let t = T::from_usize(i).unwrap()
+                        / T::from_usize(MOTION_BOUNDS_SAMPLES - 1).unwrap();
+                    ret.interpolate(start_time + (end_time - start_time) * t)
+                })
+                .collect();
+            ret.motion_samples = motion_samples;
+        }
+
+        ret
+    }
+
+    /// Checks if the start and end [Transform]s of this `AnimatedTransform` differ.
+    pub fn is_animated(&self) -> bool {
+        self.animated
+    }
+
+    /// Returns the [Transform] at the start of this `AnimatedTransform`.
+    pub fn start(&self) -> &Transform<T> {
+        &self.start
+    }
+
+    /// Returns the [Transform] at the end of this `AnimatedTransform`.
+    pub fn end(&self) -> &Transform<T> {
+        &self.end
+    }
+
+    /// Returns the interpolated [Transform] at `time`.
+    ///
+    /// Times outside the range of this `AnimatedTransform` are clamped to it.
+    pub fn interpolate(&self, time: T) -> Transform<T> {
+        if !self.animated || time <= self.start_time {
+            return self.start.clone();
+        }
+        if time >= self.end_time {
+            return self.end.clone();
+        }
+
+        let dt = (time - self.start_time) / (self.end_time - self.start_time);
+
+        let translation = self.translations[0] * (T::one() - dt) + self.translations[1] * dt;
+        let rotation = self.rotations[0].slerp(self.rotations[1], dt);
+        let mut scale = Matrix4x4::zeros();
+        for row in 0..4 {
+            for col in 0..4 {
+                scale.m[row][col] =
+                    self.scales[0].m[row][col] * (T::one() - dt) + self.scales[1].m[row][col] * dt;
+            }
+        }
+
+        &Transform::new_rigid(translation, rotation) * &Transform::new_m(scale)
+    }
+
+    /// Returns bounds that contain `b` transformed by this `AnimatedTransform` over its whole
+    /// time range.
+    ///
+    /// The motion is approximated by the union of the bounds at evenly spaced times, so
+    /// strong rotations can cut corners slightly between the samples.
+    pub fn motion_bounds(&self, b: Bounds3<T>) -> Bounds3<T> {
+        if !self.animated {
+            return &self.start * b;
+        }
+
+        self.motion_samples
+            .iter()
+            .fold(Bounds3::default(), |acc, t| acc.union_b(t * b))
+    }
+}
+
+/// Decomposes `m` into translation, rotation and scale so that `m = T * R * S`.
+fn decompose<T>(m: &Matrix4x4<T>) -> (Vec3<T>, Quaternion<T>, Matrix4x4<T>)
+where
+    T: FloatValueType,
+{
+    let translation = Vec3::new(m.m[0][3], m.m[1][3], m.m[2][3]);
+
+    let mut upper = *m;
+    for i in 0..3 {
+        upper.m[i][3] = T::zero();
+        upper.m[3][i] = T::zero();
+    }
+    upper.m[3][3] = T::one();
+
+    // Polar decomposition, iterate R towards the average of itself and its inverse transpose
+    let half = T::from_f32(0.5).unwrap();
+    let mut r = upper;
+    for _ in 0..100 {
+        let r_it = r.transposed().inverted();
+        let mut r_next = Matrix4x4::zeros();
+        for row in 0..4 {
+            for col in 0..4 {
+                r_next.m[row][col] = half * (r.m[row][col] + r_it.m[row][col]);
+            }
+        }
+
+        let mut norm = T::zero();
+        for row in 0..3 {
+            let n = (0..3).fold(T::zero(), |acc, col| {
+                acc + (r.m[row][col] - r_next.m[row][col]).abs()
+            });
+            norm = norm.max(n);
+        }
+        r = r_next;
+
+        if norm <= T::from_f32(1e-4).unwrap() {
+            break;
+        }
+    }
+
+    let rotation = Quaternion::from_matrix(&r).normalized();
+    let scale = &r.inverted() * &upper;
+
+    (translation, rotation, scale)
+}
@@ -1,4 +1,5 @@
 use num::Integer;
+use serde::{Deserialize, Serialize};
 use std::ops::{Index, IndexMut};
 
 use yuki_derive::{impl_bounds, Index, IndexMut};
@@ -15,7 +16,7 @@ use super::{
 
 /// Two-dimensional bounds.
 #[impl_bounds]
-#[derive(Copy, Clone, Debug, PartialEq, Index, IndexMut)]
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Index, IndexMut)]
 pub struct Bounds2<T>
 where
     T: ValueType,
@@ -28,7 +29,7 @@ where
 
 /// Three-dimensional bounds.
 #[impl_bounds]
-#[derive(Copy, Clone, Debug, PartialEq, Index, IndexMut)]
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Index, IndexMut)]
 pub struct Bounds3<T>
 where
     T: ValueType,
@@ -213,4 +214,28 @@ where
 
         tmin <= tmax
     }
+
+    /// Checks if `ray` hits this `Bounds3`.
+    ///
+    /// Precomputed `inv_dir` and `dir_is_neg` are supplied as an optimization so that the near
+    /// and far slab of each axis can be picked directly instead of sorting the distances.
+    // From https://www.pbr-book.org/3ed-2018/Shapes/Basic_Shape_Interface#Bounds3::IntersectP
+    // without the error bound on t_max to keep results identical with intersect()
+    pub fn intersect_p(&self, ray: Ray<T>, inv_dir: Vec3<T>, dir_is_neg: [bool; 3]) -> bool {
+        let bounds = [self.p_min, self.p_max];
+
+        let mut t_min = T::zero();
+        let mut t_max = ray.t_max;
+        for axis in 0..3 {
+            let near = bounds[dir_is_neg[axis] as usize][axis];
+            let far = bounds[1 - (dir_is_neg[axis] as usize)][axis];
+            t_min = t_min.max((near - ray.o[axis]) * inv_dir[axis]);
+            t_max = t_max.min((far - ray.o[axis]) * inv_dir[axis]);
+            if t_min > t_max {
+                return false;
+            }
+        }
+
+        true
+    }
 }
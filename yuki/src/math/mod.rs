@@ -1,34 +1,69 @@
+mod animated_transform;
 mod bounds;
 mod common;
 mod matrix;
 mod normal;
 mod point;
+mod quaternion;
 mod ray;
 mod spectrum;
 mod transform;
+mod transform_hierarchy;
 pub mod transforms;
 mod vector;
 
+pub use animated_transform::AnimatedTransform;
 pub use bounds::{Bounds2, Bounds3};
 pub use common::ValueType;
 pub use matrix::{DecomposedMatrix, Matrix4x4};
 pub use normal::Normal;
 pub use point::{Point2, Point3};
-pub use ray::Ray;
+pub use quaternion::Quaternion;
+pub use ray::{Ray, RayDifferential};
 pub use spectrum::Spectrum;
 pub use transform::Transform;
+pub use transform_hierarchy::TransformNode;
 pub use vector::{Vec2, Vec3, Vec4};
 
 // From https://www.pbr-book.org/3ed-2018/Geometry_and_Transformations/Vectors#CoordinateSystemfromaVector
 /// Creates perpendicular vectors for `v`.
 ///
-/// `v` is expecte to be normalized.
+/// `v` is expected to be normalized.
 pub fn coordinate_system<T: common::FloatValueType>(v: Vec3<T>) -> (Vec3<T>, Vec3<T>) {
     let v1 = if v.x.abs() > v.y.abs() {
         Vec3::new(-v.z, T::zero(), v.x) / (v.x * v.x + v.z * v.z).sqrt()
     } else {
-        Vec3::new(T::zero(), v.z, -v.y) / (v.y * v.y + v.z + v.z)
+        Vec3::new(T::zero(), v.z, -v.y) / (v.y * v.y + v.z * v.z).sqrt()
     };
     let v2 = v.cross(v1);
     (v1, v2)
 }
+
+// From https://www.pbr-book.org/3ed-2018/Shapes/Managing_Rounding_Error#ConservativeBoundingofFloating-PointError
+/// Returns the conservative relative error bound for `n` consecutive floating point operations.
+pub fn gamma(n: u8) -> f32 {
+    let e = f32::from(n) * f32::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
+/// Returns the smallest representable `f32` greater than `v`.
+pub fn next_float_up(v: f32) -> f32 {
+    if v.is_infinite() && v > 0.0 {
+        return v;
+    }
+    // Skip -0.0 so that the step is taken from +0.0
+    let v = if v == 0.0 { 0.0 } else { v };
+    let bits = v.to_bits();
+    f32::from_bits(if v >= 0.0 { bits + 1 } else { bits - 1 })
+}
+
+/// Returns the largest representable `f32` less than `v`.
+pub fn next_float_down(v: f32) -> f32 {
+    if v.is_infinite() && v < 0.0 {
+        return v;
+    }
+    // Skip +0.0 so that the step is taken from -0.0
+    let v = if v == 0.0 { -0.0 } else { v };
+    let bits = v.to_bits();
+    f32::from_bits(if v > 0.0 { bits - 1 } else { bits + 1 })
+}
@@ -1,4 +1,5 @@
 use approx::{AbsDiffEq, RelativeEq};
+use serde::{Deserialize, Serialize};
 use std::{
     hash::Hash,
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign},
@@ -24,6 +25,7 @@ use super::{
 // point *= scalar
 
 /// A two-dimensional point.
+#[allow(clippy::unsafe_derive_deserialize)] // This is a POD
 #[impl_point]
 #[vec_op(Add Vec2 Point2)]
 #[vec_op(Add Point2 Point2)]
@@ -35,6 +37,8 @@ use super::{
     Copy,
     Clone,
     Debug,
+    Deserialize,
+    Serialize,
     Hash,
     AbsDiffEq,
     RelativeEq,
@@ -58,6 +62,7 @@ where
 }
 
 /// A three-dimensional point.
+#[allow(clippy::unsafe_derive_deserialize)] // This is a POD
 #[impl_point]
 #[vec_op(Add Vec3 Point3)]
 #[vec_op(Add Point3 Point3)]
@@ -69,6 +74,8 @@ where
     Copy,
     Clone,
     Debug,
+    Deserialize,
+    Serialize,
     PartialEq,
     AbsDiffEq,
     RelativeEq,
@@ -0,0 +1,257 @@
+use approx::{AbsDiffEq, RelativeEq};
+use std::ops::{Add, Mul, Neg, Sub};
+
+use super::{common::FloatValueType, matrix::Matrix4x4, vector::Vec3};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Geometry_and_Transformations/Quaternions
+
+/// A `Quaternion` with the imaginary part in `v` and the real part in `w`
+///
+/// Unit quaternions represent rotations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quaternion<T>
+where
+    T: FloatValueType,
+{
+    pub v: Vec3<T>,
+    pub w: T,
+}
+
+impl<T> Quaternion<T>
+where
+    T: FloatValueType,
+{
+    /// Creates a new `Quaternion`.
+    pub fn new(v: Vec3<T>, w: T) -> Self {
+        let ret = Self { v, w };
+        debug_assert!(!ret.has_nans());
+        ret
+    }
+
+    /// Creates a new identity `Quaternion`.
+    pub fn identity() -> Self {
+        Self {
+            v: Vec3::zeros(),
+            w: T::one(),
+        }
+    }
+
+    /// Creates a new `Quaternion` from the rotation in the upper 3x3 of `m`.
+    ///
+    /// `m` is expected to be a pure rotation in its upper 3x3.
+    #[allow(clippy::many_single_char_names)] // Convention
+    pub fn from_matrix(m: &Matrix4x4<T>) -> Self {
+        let m = &m.m;
+        let half = T::from_f32(0.5).unwrap();
+
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > T::zero() {
+            // |w| > 1/2 so it's safe to divide by it
+            let s = (trace + T::one()).sqrt();
+            let w = s * half;
+            let s = half / s;
+            Self::new(
+                Vec3::new(
+                    (m[2][1] - m[1][2]) * s,
+                    (m[0][2] - m[2][0]) * s,
+                    (m[1][0] - m[0][1]) * s,
+                ),
+                w,
+            )
+        } else {
+            // Compute the largest of x, y, z first to keep the division stable
+            const NEXT: [usize; 3] = [1, 2, 0];
+            let mut i = 0;
+            if m[1][1] > m[0][0] {
+                i = 1;
+            }
+            if m[2][2] > m[i][i] {
+                i = 2;
+            }
+            let j = NEXT[i];
+            let k = NEXT[j];
+
+            let mut s = (m[i][i] - (m[j][j] + m[k][k]) + T::one()).sqrt();
+            let mut q = [T::zero(); 3];
+            q[i] = s * half;
+            if s != T::zero() {
+                s = half / s;
+            }
+            let w = (m[k][j] - m[j][k]) * s;
+            q[j] = (m[j][i] + m[i][j]) * s;
+            q[k] = (m[k][i] + m[i][k]) * s;
+
+            Self::new(Vec3::new(q[0], q[1], q[2]), w)
+        }
+    }
+
+    /// Returns the rotation [Matrix4x4] of this `Quaternion`.
+    ///
+    /// This `Quaternion` is expected to be normalized.
+    #[allow(clippy::many_single_char_names)] // Convention
+    pub fn to_matrix(self) -> Matrix4x4<T> {
+        let Vec3 { x, y, z } = self.v;
+        let w = self.w;
+        let one = T::one();
+        let two = T::from_f32(2.0).unwrap();
+
+        let xx = x * x;
+        let yy = y * y;
+        let zz = z * z;
+        let xy = x * y;
+        let xz = x * z;
+        let yz = y * z;
+        let wx = x * w;
+        let wy = y * w;
+        let wz = z * w;
+
+        Matrix4x4::new([
+            [
+                one - two * (yy + zz),
+                two * (xy - wz),
+                two * (xz + wy),
+                T::zero(),
+            ],
+            [
+                two * (xy + wz),
+                one - two * (xx + zz),
+                two * (yz - wx),
+                T::zero(),
+            ],
+            [
+                two * (xz - wy),
+                two * (yz + wx),
+                one - two * (xx + yy),
+                T::zero(),
+            ],
+            [T::zero(), T::zero(), T::zero(), one],
+        ])
+    }
+
+    /// Checks if this `Quaternion` contains NaNs.
+    pub fn has_nans(&self) -> bool {
+        self.v.has_nans() || self.w.is_nan()
+    }
+
+    /// Calculates the dot product of this `Quaternion` and `other`.
+    pub fn dot(&self, other: Self) -> T {
+        self.v.dot(other.v) + self.w * other.w
+    }
+
+    /// Returns a new `Quaternion` with this `Quaternion` normalized.
+    pub fn normalized(&self) -> Self {
+        *self * (T::one() / self.dot(*self).sqrt())
+    }
+
+    /// Spherically interpolates between this `Quaternion` and `other` at `t`.
+    ///
+    /// Both `Quaternion`s are expected to be normalized. The interpolation takes the shorter
+    /// path between the two rotations.
+    pub fn slerp(&self, other: Self, t: T) -> Self {
+        let mut other = other;
+        let mut cos_theta = self.dot(other);
+        // q and -q are the same rotation, pick the one that is closer
+        if cos_theta < T::zero() {
+            other = -other;
+            cos_theta = -cos_theta;
+        }
+
+        if cos_theta > T::from_f32(0.9995).unwrap() {
+            // Nearly parallel, linear interpolation is accurate and avoids dividing by ~0
+            (*self * (T::one() - t) + other * t).normalized()
+        } else {
+            let theta = cos_theta.min(T::one()).acos();
+            let theta_p = theta * t;
+            let q_perp = (other - *self * cos_theta).normalized();
+            *self * theta_p.cos() + q_perp * theta_p.sin()
+        }
+    }
+}
+
+impl<T> Default for Quaternion<T>
+where
+    T: FloatValueType,
+{
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl<T> Add for Quaternion<T>
+where
+    T: FloatValueType,
+{
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.v + other.v, self.w + other.w)
+    }
+}
+
+impl<T> Sub for Quaternion<T>
+where
+    T: FloatValueType,
+{
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.v - other.v, self.w - other.w)
+    }
+}
+
+impl<T> Mul<T> for Quaternion<T>
+where
+    T: FloatValueType,
+{
+    type Output = Self;
+
+    fn mul(self, s: T) -> Self {
+        Self::new(self.v * s, self.w * s)
+    }
+}
+
+impl<T> Neg for Quaternion<T>
+where
+    T: FloatValueType,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.v, -self.w)
+    }
+}
+
+impl<T> AbsDiffEq for Quaternion<T>
+where
+    T: FloatValueType + AbsDiffEq + approx::AbsDiffEq<Epsilon = T>,
+{
+    type Epsilon = T::Epsilon;
+
+    fn default_epsilon() -> Self::Epsilon {
+        T::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.v.abs_diff_eq(&other.v, epsilon) && self.w.abs_diff_eq(&other.w, epsilon)
+    }
+}
+
+impl<T> RelativeEq for Quaternion<T>
+where
+    T: FloatValueType + RelativeEq + approx::AbsDiffEq<Epsilon = T>,
+{
+    fn default_max_relative() -> Self::Epsilon {
+        T::default_max_relative()
+    }
+
+    fn relative_eq(
+        &self,
+        other: &Self,
+        epsilon: Self::Epsilon,
+        max_relative: Self::Epsilon,
+    ) -> bool {
+        self.v.relative_eq(&other.v, epsilon, max_relative)
+            && self.w.relative_eq(&other.w, epsilon, max_relative)
+    }
+}
@@ -13,30 +13,65 @@ where
     pub o: Point3<T>,
     pub d: Vec3<T>,
     pub t_max: T,
-    // TODO: Time
+    /// Time the `Ray` is cast at, used to evaluate animated transforms
+    pub time: T,
+    /// Offset rays for estimating the footprint of this `Ray` on surfaces, only set for camera
+    /// rays
+    pub differential: Option<RayDifferential<T>>,
     // TODO: Medium
 }
 
+/// Rays offset by one pixel on the film in x and y from a camera [Ray]
+#[derive(Copy, PartialEq, Clone, Debug)]
+pub struct RayDifferential<T>
+where
+    T: FloatValueType,
+{
+    pub rx_o: Point3<T>,
+    pub rx_d: Vec3<T>,
+    pub ry_o: Point3<T>,
+    pub ry_d: Vec3<T>,
+}
+
 impl<T> Ray<T>
 where
     T: FloatValueType,
 {
-    /// Creates a new `Ray`.
+    /// Creates a new `Ray` at time `0`.
     pub fn new(o: Point3<T>, d: Vec3<T>, t_max: T) -> Self {
-        let ret = Self { o, d, t_max };
+        let ret = Self {
+            o,
+            d,
+            t_max,
+            time: T::zero(),
+            differential: None,
+        };
         debug_assert!(!ret.has_nans());
         ret
     }
 
     /// Checks if any of the members in this `Ray` contain NaNs.
     pub fn has_nans(&self) -> bool {
-        self.o.has_nans() || self.d.has_nans() || self.t_max.is_nan()
+        self.o.has_nans() || self.d.has_nans() || self.t_max.is_nan() || self.time.is_nan()
     }
 
     /// Finds the [Point3] on this `Ray` at distance `t`.
     pub fn point(&self, t: T) -> Point3<T> {
         self.o + self.d * t
     }
+
+    /// Scales the offsets of the differential rays of this `Ray` by `s`.
+    ///
+    /// Used to match the differentials to the sample spacing when taking multiple samples per
+    /// pixel.
+    pub fn scale_differentials(&mut self, s: T) {
+        if let Some(rd) = &mut self.differential {
+            rd.rx_o = self.o + (rd.rx_o - self.o) * s;
+            rd.ry_o = self.o + (rd.ry_o - self.o) * s;
+            rd.rx_d = self.d + (rd.rx_d - self.d) * s;
+            rd.ry_d = self.d + (rd.ry_d - self.d) * s;
+        }
+    }
 }
 
 impl<T> Default for Ray<T>
@@ -48,6 +83,8 @@ where
             o: Point3::zeros(),
             d: Vec3::new(T::zero(), T::one(), T::zero()),
             t_max: T::infinity(),
+            time: T::zero(),
+            differential: None,
         }
     }
 }
@@ -66,6 +103,7 @@ where
         self.o.abs_diff_eq(&other.o, epsilon)
             && self.d.abs_diff_eq(&other.d, epsilon)
             && self.t_max.abs_diff_eq(&other.t_max, epsilon)
+            && self.time.abs_diff_eq(&other.time, epsilon)
     }
 }
 
@@ -86,5 +124,6 @@ where
         self.o.relative_eq(&other.o, epsilon, max_relative)
             && self.d.relative_eq(&other.d, epsilon, max_relative)
             && self.t_max.relative_eq(&other.t_max, epsilon, max_relative)
+            && self.time.relative_eq(&other.time, epsilon, max_relative)
     }
 }
@@ -3,7 +3,7 @@ use std::ops::{
     Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
 };
 
-use super::common::ValueType;
+use super::common::{FloatValueType, ValueType};
 use yuki_derive::{
     impl_spectrum, AbsDiffEq, Add, AddAssign, AddAssignScalar, AddScalar, Div, DivAssign,
     DivAssignScalar, DivScalar, Index, IndexMut, Mul, MulAssign, MulAssignScalar, MulScalar, Neg,
@@ -53,3 +53,60 @@ where
     /// The b component of the spd
     pub b: T,
 }
+
+impl<T> Spectrum<T>
+where
+    T: FloatValueType,
+{
+    /// Returns the luminance of this `Spectrum` using Rec. 709 weights.
+    pub fn luminance(&self) -> T {
+        T::from_f32(0.2126).unwrap() * self.r
+            + T::from_f32(0.7152).unwrap() * self.g
+            + T::from_f32(0.0722).unwrap() * self.b
+    }
+
+    /// Returns the largest component of this `Spectrum`.
+    pub fn max_comp(&self) -> T {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// Returns the component-wise product of this `Spectrum` and `other`.
+    ///
+    /// Same as `*`, spelled out for call sites where scalar multiplication would also make sense.
+    pub fn component_mul(&self, other: Self) -> Self {
+        Self::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+
+    /// Linearly interpolates from this `Spectrum` at `t = 0` to `other` at `t = 1`.
+    pub fn lerp(&self, other: Self, t: T) -> Self {
+        *self * (T::one() - t) + other * t
+    }
+
+    /// Returns this `Spectrum` with its components clamped into `[min, max]`.
+    pub fn clamp(&self, min: T, max: T) -> Self {
+        Self::new(
+            self.r.max(min).min(max),
+            self.g.max(min).min(max),
+            self.b.max(min).min(max),
+        )
+    }
+
+    /// Returns the component-wise exponential of this `Spectrum`.
+    pub fn exp(&self) -> Self {
+        Self::new(self.r.exp(), self.g.exp(), self.b.exp())
+    }
+
+    /// Returns the component-wise square root of this `Spectrum`.
+    pub fn sqrt(&self) -> Self {
+        Self::new(self.r.sqrt(), self.g.sqrt(), self.b.sqrt())
+    }
+}
+
+impl<T> Default for Spectrum<T>
+where
+    T: ValueType,
+{
+    fn default() -> Self {
+        Self::zeros()
+    }
+}
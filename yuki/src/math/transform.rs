@@ -1,8 +1,15 @@
 use std::ops::Mul;
 
 use super::{
-    bounds::Bounds3, common::FloatValueType, matrix::Matrix4x4, normal::Normal, point::Point3,
-    ray::Ray, vector::Vec3,
+    bounds::Bounds3,
+    common::FloatValueType,
+    gamma,
+    matrix::Matrix4x4,
+    normal::Normal,
+    point::Point3,
+    quaternion::Quaternion,
+    ray::{Ray, RayDifferential},
+    vector::Vec3,
 };
 
 // Based on Physically Based Rendering 3rd ed.
@@ -40,6 +47,24 @@ where
         Self { m, m_inv }
     }
 
+    /// Creates a new rigid `Transform` that rotates by `rotation` and then translates by
+    /// `translation`.
+    pub fn new_rigid(translation: Vec3<T>, rotation: Quaternion<T>) -> Self {
+        let mut m = rotation.to_matrix();
+        // The inverse of a rotation is its transpose
+        let mut m_inv = m.transposed();
+
+        m.m[0][3] = translation.x;
+        m.m[1][3] = translation.y;
+        m.m[2][3] = translation.z;
+        // Undo the translation before the rotation
+        for row in m_inv.m.iter_mut().take(3) {
+            row[3] = -(row[0] * translation.x + row[1] * translation.y + row[2] * translation.z);
+        }
+
+        Self::new_full(m, m_inv)
+    }
+
     /// Returns a reference to the [Matrix4x4] of this `Transformation`.
     pub fn m(&self) -> &Matrix4x4<T> {
         &self.m
@@ -60,6 +85,25 @@ where
         Self::new_full(self.m.transposed(), self.m_inv.transposed())
     }
 
+    /// Decomposes this `Transform` into a translation and a rotation.
+    ///
+    /// The `Transform` is expected to be rigid, i.e. not contain scaling or shearing.
+    pub fn decompose_rigid(&self) -> (Vec3<T>, Quaternion<T>) {
+        let m = &self.m.m;
+        let translation = Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let rotation = Quaternion::from_matrix(&self.m).normalized();
+        (translation, rotation)
+    }
+
+    /// Interpolates between this rigid `Transform` and `other` at `t`.
+    ///
+    /// The translations are interpolated linearly and the rotations spherically.
+    pub fn interpolate_rigid(&self, other: &Self, t: T) -> Self {
+        let (t0, r0) = self.decompose_rigid();
+        let (t1, r1) = other.decompose_rigid();
+        Self::new_rigid(t0 * (T::one() - t) + t1 * t, r0.slerp(r1, t))
+    }
+
     /// Checks if this `Transform` is the identity transform.
     pub fn is_identity(&self) -> bool {
         self.m.m[0][0] == T::one()
@@ -90,6 +134,23 @@ where
     }
 }
 
+impl Transform<f32> {
+    /// Returns the error bound of `self * p` when `p` itself has the absolute error `p_error`.
+    // From https://www.pbr-book.org/3ed-2018/Shapes/Managing_Rounding_Error#TransformingPoints
+    pub fn point_error(&self, p: Point3<f32>, p_error: Vec3<f32>) -> Vec3<f32> {
+        let g3 = gamma(3);
+        let row_error = |[m0, m1, m2, m3]: [f32; 4]| {
+            (g3 + 1.0) * (m0.abs() * p_error.x + m1.abs() * p_error.y + m2.abs() * p_error.z)
+                + g3 * ((m0 * p.x).abs() + (m1 * p.y).abs() + (m2 * p.z).abs() + m3.abs())
+        };
+        Vec3::new(
+            row_error(self.m.m[0]),
+            row_error(self.m.m[1]),
+            row_error(self.m.m[2]),
+        )
+    }
+}
+
 impl<T> Default for Transform<T>
 where
     T: FloatValueType,
@@ -169,11 +230,17 @@ where
     type Output = Ray<T>;
 
     fn mul(self, other: Ray<T>) -> Ray<T> {
-        Ray::new(
-            self * other.o, // TODO: Offset to error bound
-            self * other.d,
-            other.t_max,
-        )
+        Ray {
+            o: self * other.o, // TODO: Offset to error bound
+            d: self * other.d,
+            differential: other.differential.map(|rd| RayDifferential {
+                rx_o: self * rd.rx_o,
+                rx_d: self * rd.rx_d,
+                ry_o: self * rd.ry_o,
+                ry_d: self * rd.ry_d,
+            }),
+            ..other
+        }
     }
 }
 
@@ -0,0 +1,67 @@
+use super::{common::FloatValueType, transform::Transform};
+
+/// A node in a transform hierarchy.
+///
+/// Items and child nodes inherit the transform of the node and all of its ancestors.
+#[derive(Clone, Debug)]
+pub struct TransformNode<T, I>
+where
+    T: FloatValueType,
+{
+    /// Transform from this node's space into its parent's space
+    pub transform: Transform<T>,
+    pub items: Vec<I>,
+    pub children: Vec<TransformNode<T, I>>,
+}
+
+impl<T, I> TransformNode<T, I>
+where
+    T: FloatValueType,
+{
+    /// Creates a new empty `TransformNode` with the given transform.
+    pub fn new(transform: Transform<T>) -> Self {
+        Self {
+            transform,
+            items: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Adds an item directly under this `TransformNode`.
+    pub fn add_item(&mut self, item: I) {
+        self.items.push(item);
+    }
+
+    /// Adds a child node under this `TransformNode`.
+    pub fn add_child(&mut self, child: TransformNode<T, I>) {
+        self.children.push(child);
+    }
+
+    /// Flattens the hierarchy into items paired with their full item-to-world transforms.
+    ///
+    /// Items are returned in depth-first order with a node's own items before its children's.
+    pub fn flatten(self) -> Vec<(Transform<T>, I)> {
+        let mut ret = Vec::new();
+        self.flatten_into(&Transform::default(), &mut ret);
+        ret
+    }
+
+    fn flatten_into(self, parent_to_world: &Transform<T>, ret: &mut Vec<(Transform<T>, I)>) {
+        let node_to_world = parent_to_world * &self.transform;
+        for item in self.items {
+            ret.push((node_to_world.clone(), item));
+        }
+        for child in self.children {
+            child.flatten_into(&node_to_world, ret);
+        }
+    }
+}
+
+impl<T, I> Default for TransformNode<T, I>
+where
+    T: FloatValueType,
+{
+    fn default() -> Self {
+        Self::new(Transform::default())
+    }
+}
@@ -0,0 +1,149 @@
+use super::{HenyeyGreenstein, Medium, MediumSample};
+use crate::{
+    math::{Ray, Spectrum},
+    sampling::Sampler,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Volume_Scattering/Media#HomogeneousMedium
+// https://www.pbr-book.org/3ed-2018/Light_Transport_II_Volume_Rendering/Sampling_Volume_Scattering#HomogeneousMedium
+
+/// Medium with constant absorption and scattering coefficients
+pub struct HomogeneousMedium {
+    sigma_s: Spectrum<f32>,
+    sigma_t: Spectrum<f32>,
+    phase: HenyeyGreenstein,
+}
+
+impl HomogeneousMedium {
+    /// Creates a new `HomogeneousMedium` with the given per unit distance absorption and
+    /// scattering coefficients and Henyey-Greenstein asymmetry `g`.
+    pub fn new(sigma_a: Spectrum<f32>, sigma_s: Spectrum<f32>, g: f32) -> Self {
+        Self {
+            sigma_s,
+            sigma_t: sigma_a + sigma_s,
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    fn tr_dist(&self, dist: f32) -> Spectrum<f32> {
+        // Clamp infinite distances to avoid 0 * inf for channels that don't attenuate
+        (self.sigma_t * -dist.min(f32::MAX)).exp()
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn tr(&self, ray: Ray<f32>) -> Spectrum<f32> {
+        self.tr_dist(ray.t_max * ray.d.len())
+    }
+
+    fn sample(&self, ray: Ray<f32>, sampler: &mut Box<dyn Sampler>) -> MediumSample {
+        // Sample the distance with one channel and weight with the average pdf over all of them
+        let channel = ((sampler.get_1d() * 3.0) as usize).min(2);
+        let d_len = ray.d.len();
+        let dist = -(1.0 - sampler.get_1d()).ln() / self.sigma_t[channel];
+        let t = (dist / d_len).min(ray.t_max);
+        let sampled_medium = t < ray.t_max;
+
+        let tr = self.tr_dist(t * d_len);
+        let density = if sampled_medium {
+            self.sigma_t * tr
+        } else {
+            tr
+        };
+        let pdf = {
+            let pdf = (density.r + density.g + density.b) / 3.0;
+            if pdf == 0.0 {
+                1.0
+            } else {
+                pdf
+            }
+        };
+
+        if sampled_medium {
+            MediumSample {
+                t: Some(t),
+                beta: tr * self.sigma_s / pdf,
+                phase: self.phase,
+            }
+        } else {
+            MediumSample {
+                t: None,
+                beta: tr / pdf,
+                phase: self.phase,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HomogeneousMedium;
+    use crate::{
+        math::{Point2, Point3, Ray, Spectrum, Vec3},
+        media::Medium,
+        sampling::{Sampler, UniformParams, UniformSampler},
+    };
+
+    #[test]
+    fn absorption_follows_beers_law() {
+        let sigma_a = Spectrum::new(0.5, 1.0, 2.0);
+        let medium = HomogeneousMedium::new(sigma_a, Spectrum::zeros(), 0.0);
+
+        // Transmittance depends on the traveled distance, not the ray parameter
+        let d = Vec3::new(0.0, 3.0, 4.0);
+        for t in [0.0, 0.1, 0.4, 1.0] {
+            let tr = medium.tr(Ray::new(Point3::new(1.0, 2.0, 3.0), d, t));
+            let dist = t * d.len();
+            for (c, sigma) in [(tr.r, 0.5), (tr.g, 1.0), (tr.b, 2.0)] {
+                let expected = (-sigma * dist).exp();
+                assert!((c - expected).abs() < 1e-5, "{} {}", c, expected);
+            }
+        }
+
+        let infinite = medium.tr(Ray::new(Point3::zeros(), d, f32::INFINITY));
+        assert!(infinite.is_black());
+
+        // Pure absorbers never scatter and the weights carry the transmittance
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams { pixel_samples: 32 },
+            false,
+        ));
+        let ray = Ray::new(Point3::zeros(), d, 0.2);
+        let expected = medium.tr(ray);
+        let avg = (expected.r + expected.g + expected.b) / 3.0;
+        for i in 0..16 {
+            sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+            let sample = medium.sample(ray, &mut sampler);
+            assert!(sample.t.is_none());
+            // Weighted by the average pdf of the channels
+            let beta = sample.beta * avg;
+            for (c, e) in [
+                (beta.r, expected.r),
+                (beta.g, expected.g),
+                (beta.b, expected.b),
+            ] {
+                assert!((c - e).abs() < 1e-5, "{} {}", c, e);
+            }
+        }
+    }
+
+    #[test]
+    fn scattering_medium_samples_inside_segment() {
+        let medium = HomogeneousMedium::new(Spectrum::zeros(), Spectrum::ones() * 2.0, 0.3);
+        let mut sampler: Box<dyn Sampler> = Box::new(UniformSampler::new(
+            UniformParams { pixel_samples: 32 },
+            false,
+        ));
+        let ray = Ray::new(Point3::zeros(), Vec3::new(0.0, 0.0, 2.0), 10.0);
+        for i in 0..32 {
+            sampler.start_pixel_sample(Point2::new(0, 0), i, 0);
+            let sample = medium.sample(ray, &mut sampler);
+            // Albedo 1 with a single sampled channel keeps the weight at 1
+            assert!((sample.beta.g - 1.0).abs() < 1e-4, "{:?}", sample.beta);
+            if let Some(t) = sample.t {
+                assert!((0.0..ray.t_max).contains(&t));
+            }
+        }
+    }
+}
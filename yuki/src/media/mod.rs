@@ -0,0 +1,106 @@
+mod homogeneous;
+
+pub use homogeneous::HomogeneousMedium;
+
+use crate::{
+    math::{coordinate_system, Point2, Ray, Spectrum, Vec3},
+    sampling::Sampler,
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Volume_Scattering/Media
+
+/// Participating medium that attenuates and scatters light along rays
+pub trait Medium: Send + Sync {
+    /// Returns the transmittance from the origin of `ray` to `ray.point(ray.t_max)`.
+    fn tr(&self, ray: Ray<f32>) -> Spectrum<f32>;
+
+    /// Samples a scattering point along `ray` before `ray.t_max`.
+    fn sample(&self, ray: Ray<f32>, sampler: &mut Box<dyn Sampler>) -> MediumSample;
+}
+
+pub struct MediumSample {
+    /// Ray parameter of the sampled scattering point, `None` if the ray reached `t_max`
+    pub t: Option<f32>,
+    /// Path throughput weight of the sample
+    pub beta: Spectrum<f32>,
+    /// Phase function at the scattering point
+    pub phase: HenyeyGreenstein,
+}
+
+// https://www.pbr-book.org/3ed-2018/Volume_Scattering/Phase_Functions
+/// Henyey-Greenstein phase function
+///
+/// Both `wo` and `wi` point away from the scattering point like with BSDFs.
+#[derive(Copy, Clone, Debug)]
+pub struct HenyeyGreenstein {
+    /// Asymmetry in `[-1, 1]`, positive values scatter forward
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        Self { g }
+    }
+
+    /// Returns the value of the phase function for light scattering from `wi` to `wo`.
+    pub fn p(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        phase_hg(wo.dot(wi), self.g)
+    }
+
+    /// Samples an incident direction for `wo`, returning it with its pdf.
+    ///
+    /// The sampling is exact so the pdf matches [`HenyeyGreenstein::p`].
+    pub fn sample_p(&self, wo: Vec3<f32>, u: Point2<f32>) -> (Vec3<f32>, f32) {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u[0]
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * u[0]);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        };
+
+        // cos_theta is relative to the propagation direction -wo
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u[1];
+        let (v1, v2) = coordinate_system(wo);
+        let wi = v1 * (sin_theta * phi.cos()) + v2 * (sin_theta * phi.sin()) - wo * cos_theta;
+
+        (wi, phase_hg(-cos_theta, g))
+    }
+}
+
+fn phase_hg(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + g * g + 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * std::f32::consts::PI * denom * denom.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HenyeyGreenstein;
+    use crate::math::{Point2, Vec3};
+
+    #[test]
+    fn henyey_greenstein_sampling_matches_p() {
+        let wo = Vec3::new(0.3, -0.5, 0.8).normalized();
+        for g in [-0.7, 0.0, 0.4, 0.9] {
+            let phase = HenyeyGreenstein::new(g);
+            let mut forward = 0;
+            for (u0, u1) in [(0.1, 0.2), (0.5, 0.7), (0.9, 0.4), (0.33, 0.95)] {
+                let (wi, pdf) = phase.sample_p(wo, Point2::new(u0, u1));
+                assert!((wi.len() - 1.0).abs() < 1e-4);
+                assert!((pdf - phase.p(wo, wi)).abs() < 1e-4 * pdf.max(1.0));
+                if wi.dot(-wo) > 0.0 {
+                    forward += 1;
+                }
+            }
+            if g > 0.5 {
+                assert_eq!(forward, 4, "g {}", g);
+            }
+        }
+        // Isotropic scattering is uniform over the sphere
+        let isotropic = HenyeyGreenstein::new(0.0);
+        let expected = 1.0 / (4.0 * std::f32::consts::PI);
+        assert!((isotropic.p(wo, Vec3::new(1.0, 0.0, 0.0)) - expected).abs() < 1e-6);
+    }
+}
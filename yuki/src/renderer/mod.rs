@@ -8,6 +8,7 @@ use std::sync::{
     mpsc::{channel, SendError, TryRecvError},
     Arc, Mutex,
 };
+use strum::{Display, EnumString, EnumVariantNames};
 
 use crate::{
     camera::CameraParameters,
@@ -31,10 +32,36 @@ pub enum RenderStatus {
     },
 }
 
+/// OS scheduling priority of the render threads
+#[derive(
+    Debug, Copy, Clone, PartialEq, Deserialize, Serialize, Display, EnumVariantNames, EnumString,
+)]
+pub enum RenderPriority {
+    /// Leaves room for the UI and other applications
+    Low,
+    /// Maximizes render throughput
+    Normal,
+}
+
+impl Default for RenderPriority {
+    fn default() -> Self {
+        RenderPriority::Normal
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, Deserialize, Serialize)]
 pub struct RenderSettings {
     pub mark_tiles: bool,
     pub use_single_render_thread: bool,
+    #[serde(default)]
+    pub priority: RenderPriority,
+    /// Fixed sampler seed, random for each render if `None`
+    ///
+    /// Renders that don't accumulate are identical for the same seed regardless of the number of
+    /// render threads since samples only depend on the pixel and sample index. Accumulation sums
+    /// passes in the order the tiles finish in.
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 pub struct Renderer {
@@ -182,3 +209,63 @@ impl Drop for Renderer {
         self.kill();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{RenderSettings, RenderStatus, Renderer};
+    use crate::{
+        film::{Film, FilmSettings},
+        integrators::{IntegratorType, PathParams},
+        math::{Spectrum, Vec2},
+        sampling::SamplerType,
+        scene::Scene,
+    };
+
+    #[test]
+    fn render_is_independent_of_thread_count() {
+        let (scene, camera_params, _, _) = Scene::cornell();
+        let film_settings = FilmSettings {
+            res: Vec2::new(64, 48),
+            tile_dim: 8,
+            ..FilmSettings::default()
+        };
+
+        let render = |use_single_render_thread: bool| -> Vec<Spectrum<f32>> {
+            let film = Arc::new(Mutex::new(Film::new(film_settings.res)));
+            let mut renderer = Renderer::new();
+            renderer.launch(
+                Arc::clone(&scene),
+                camera_params,
+                Arc::clone(&film),
+                SamplerType::default().with_samples_per_pixel(4),
+                IntegratorType::Path(PathParams::default()),
+                film_settings,
+                RenderSettings {
+                    use_single_render_thread,
+                    seed: Some(0x73B9_642E_74AC_471C),
+                    ..RenderSettings::default()
+                },
+                false,
+            );
+            while !matches!(renderer.check_status(), Some(RenderStatus::Finished { .. })) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let pixels = film.lock().unwrap().pixels().clone();
+            pixels
+        };
+
+        let single = render(true);
+        let multi = render(false);
+        assert!(single.iter().any(|p| p.luminance() > 0.0));
+        let bits = |p: &Spectrum<f32>| [p.r.to_bits(), p.g.to_bits(), p.b.to_bits()];
+        assert!(single
+            .iter()
+            .zip(multi.iter())
+            .all(|(a, b)| bits(a) == bits(b)));
+    }
+}
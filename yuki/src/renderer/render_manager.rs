@@ -75,7 +75,8 @@ pub fn launch(
         .spawn(move || {
             yuki_trace!("Render manager: Launch threads");
             // TODO: Keep track of how physical vs logical behaves with optimizations
-            let thread_count = num_cpus::get() - 1;
+            // Always have at least one worker as none would never finish a render
+            let thread_count = (num_cpus::get() - 1).max(1);
             let (worker_send, from_workers) = channel();
             let workers = (0..thread_count)
                 .map(|thread| {
@@ -130,7 +131,12 @@ pub fn launch(
                     if let Some(mut payload) = payload {
                         let mut tiles = film_tiles(&mut payload.film, payload.film_settings);
 
-                        let sampler = payload.sampler.instantiate(payload.force_single_sample);
+                        let sampler = match payload.render_settings.seed {
+                            Some(seed) => payload
+                                .sampler
+                                .instantiate_with_seed(payload.force_single_sample, seed),
+                            None => payload.sampler.instantiate(payload.force_single_sample),
+                        };
 
                         let mut initial_tiles = tiles.clone();
                         if payload.film_settings.accumulate {
@@ -222,6 +228,12 @@ fn propagate_payload(
             film: Arc::clone(&payload.film),
             mark_tiles: payload.render_settings.mark_tiles,
             accumulate: payload.film_settings.accumulate,
+            adaptive_threshold: (payload.film_settings.accumulate
+                && payload.film_settings.adaptive)
+                .then_some(payload.film_settings.adaptive_threshold),
+            aovs: payload.film_settings.aovs,
+            filter: payload.film_settings.filter,
+            priority: payload.render_settings.priority,
         };
 
         if let Err(SendError { .. }) = tx.send(Some(thread_payload)) {
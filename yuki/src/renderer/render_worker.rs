@@ -9,16 +9,20 @@ use std::{
     time::Instant,
 };
 
+use super::RenderPriority;
 use crate::{
     camera::Camera,
-    film::{Film, FilmTile},
+    film::{AovBuffers, Film, FilmTile},
+    filters::FilterType,
     integrators::IntegratorType,
     math::Spectrum,
     sampling::Sampler,
     scene::Scene,
-    yuki_debug, yuki_error, yuki_trace,
+    yuki_debug, yuki_error, yuki_trace, yuki_warn,
 };
 
+use thread_priority::{set_current_thread_priority, ThreadPriority, ThreadPriorityValue};
+
 pub enum Message {
     TileDone {
         info: WorkerInfo,
@@ -44,6 +48,11 @@ pub struct Payload {
     pub film: Arc<Mutex<Film>>,
     pub mark_tiles: bool,
     pub accumulate: bool,
+    /// Converged tiles are skipped if set
+    pub adaptive_threshold: Option<f32>,
+    pub aovs: bool,
+    pub filter: FilterType,
+    pub priority: RenderPriority,
 }
 
 impl Deref for Payload {
@@ -69,6 +78,9 @@ pub fn launch(
     let mut alloc = LinearAllocator::new(1024 * 256);
     let scratch = ScopedScratch::new(&mut alloc);
     let mut tile_pixels = [Spectrum::zeros(); 64 * 64];
+    let mut tile_luminance_sqr = [0.0; 64 * 64];
+    let mut tile_aovs = AovBuffers::new(64 * 64);
+    let mut priority = RenderPriority::Normal;
 
     'thread: loop {
         let mut worker_info = WorkerInfo {
@@ -97,6 +109,13 @@ pub fn launch(
                 break 'thread;
             }
 
+            if let Some(p) = payload.as_deref() {
+                if p.priority != priority {
+                    set_priority(thread_id, p.priority);
+                    priority = p.priority;
+                }
+            }
+
             let tile = match payload.as_deref_mut() {
                 Some(p) => match pop_tile_or_signal_finish(&worker_info, p, to_parent) {
                     Some(tile) => Some(tile),
@@ -117,14 +136,21 @@ pub fn launch(
                     &scratch,
                     &mut tile,
                     &mut tile_pixels,
+                    &mut tile_luminance_sqr,
+                    payload.aovs.then_some(&mut tile_aovs),
                     payload,
                     from_parent,
                 ) {
                     RenderTileResult::Interrupted(p) => newest_msg = Some(Ok(p)),
+                    RenderTileResult::Converged => {
+                        signal_tile_done(&worker_info, 0, tile_start, to_parent);
+                    }
                     RenderTileResult::Rendered { ray_count } => update_tile(
                         &worker_info,
                         &mut tile,
                         &tile_pixels,
+                        &tile_luminance_sqr,
+                        payload.aovs.then_some(&tile_aovs),
                         payload,
                         ray_count,
                         tile_start,
@@ -136,6 +162,28 @@ pub fn launch(
     }
 }
 
+/// Sets the OS priority of the current thread, only logging a warning if that fails.
+fn set_priority(thread_id: usize, priority: RenderPriority) {
+    let thread_priority = match priority {
+        RenderPriority::Low => ThreadPriority::Min,
+        // Middle of the cross-platform range maps to the default priority
+        RenderPriority::Normal => ThreadPriority::Crossplatform(
+            ThreadPriorityValue::try_from(50u8).expect("Priority value should be in range"),
+        ),
+    };
+
+    if let Err(why) = set_current_thread_priority(thread_priority) {
+        yuki_warn!(
+            "Render thread {}: Failed to set priority to {}: {:?}",
+            thread_id,
+            priority,
+            why
+        );
+    } else {
+        yuki_debug!("Render thread {}: Priority set to {}", thread_id, priority);
+    }
+}
+
 // Returns `true` if manager signaled kill
 fn handle_manager_messages(
     thread_id: usize,
@@ -200,6 +248,7 @@ fn pop_tile_or_signal_finish(
 #[allow(clippy::large_enum_variant)] // This is not in the hot path, seems to make sense
 enum RenderTileResult {
     Interrupted(Option<Payload>),
+    Converged,
     Rendered { ray_count: usize },
 }
 
@@ -208,9 +257,24 @@ fn render_tile(
     scratch: &ScopedScratch,
     tile: &mut FilmTile,
     tile_pixels: &mut [Spectrum<f32>],
+    tile_luminance_sqr: &mut [f32],
+    tile_aovs: Option<&mut AovBuffers>,
     payload: &Payload,
     from_parent: &Receiver<Option<Payload>>,
 ) -> RenderTileResult {
+    if let Some(threshold) = payload.adaptive_threshold {
+        yuki_trace!("Render thread {}: Waiting for lock on film", thread_id);
+        let film = payload.film.lock().unwrap();
+        yuki_trace!("Render thread {}: Acquired film", thread_id);
+
+        if film.matches(tile) && film.tile_converged(tile, threshold) {
+            yuki_trace!("Render thread {}: Tile {:?} converged", thread_id, tile.bb);
+            return RenderTileResult::Converged;
+        }
+
+        yuki_trace!("Render thread {}: Releasing film", thread_id);
+    }
+
     if payload.mark_tiles {
         yuki_trace!("Render thread {}: Mark tile {:?}", thread_id, tile.bb);
         yuki_trace!("Render thread {}: Waiting for lock on film", thread_id);
@@ -228,15 +292,19 @@ fn render_tile(
     let mut received_msg = None;
     let tile_scratch = ScopedScratch::new_scope(scratch);
     let integrator = payload.integrator_type.instantiate();
+    let filter = payload.filter.instantiate();
 
     let ray_count = integrator.render(
         &tile_scratch,
         &payload.scene,
         &payload.camera,
         &payload.sampler,
+        filter.as_ref(),
         payload.accumulate,
         tile,
         tile_pixels,
+        tile_luminance_sqr,
+        tile_aovs,
         &mut || {
             // Let's have low latency kills for more interactive view
             if let Ok(msg) = from_parent.try_recv() {
@@ -259,6 +327,8 @@ fn update_tile(
     worker_info: &WorkerInfo,
     tile: &mut FilmTile,
     tile_pixels: &[Spectrum<f32>],
+    tile_luminance_sqr: &[f32],
+    tile_aovs: Option<&AovBuffers>,
     payload: &Payload,
     ray_count: usize,
     tile_start: Instant,
@@ -278,7 +348,7 @@ fn update_tile(
         yuki_trace!("Render thread {}: Acquired film", worker_info.thread_id);
 
         if film.matches(tile) {
-            film.update_tile(tile, tile_pixels);
+            film.update_tile(tile, tile_pixels, tile_luminance_sqr, tile_aovs);
         } else {
             yuki_trace!("Render thread {}: Stale tile", worker_info.thread_id);
         }
@@ -286,6 +356,15 @@ fn update_tile(
         yuki_trace!("Render thread {}: Releasing film", worker_info.thread_id);
     }
 
+    signal_tile_done(worker_info, ray_count, tile_start, to_parent);
+}
+
+fn signal_tile_done(
+    worker_info: &WorkerInfo,
+    ray_count: usize,
+    tile_start: Instant,
+    to_parent: &Sender<Message>,
+) {
     if let Err(why) = to_parent.send(Message::TileDone {
         info: *worker_info,
         ray_count,
@@ -0,0 +1,145 @@
+use super::Sampler;
+use crate::{hash_values, math::Point2};
+
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+// Tiles a precomputed blue noise mask over the image so that each sample index and dimension
+// sees the mask at a different toroidal offset. Low sample counts then have their error spread
+// as high frequency noise, which looks cleaner in the first accumulated frames.
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Params {
+    pub pixel_samples: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self { pixel_samples: 1 }
+    }
+}
+
+const MASK_DIM: u16 = 64;
+/// Two independent masks, stored as interleaved channels
+static MASK: &[u8; (MASK_DIM as usize) * (MASK_DIM as usize) * 2] =
+    std::include_bytes!("../../../res/blue_noise/blue_noise_64x64_rg.bin");
+
+pub struct BlueNoiseSampler {
+    pixel_samples: u32,
+    pixel: Point2<u16>,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(mut params: Params, force_single_sample: bool) -> Self {
+        if force_single_sample {
+            params.pixel_samples = 1;
+        }
+
+        Self {
+            pixel_samples: params.pixel_samples,
+            pixel: Point2::new(0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Returns both mask channels at the current pixel for `dimension`
+    fn sample_mask(&self, dimension: u32) -> Point2<f32> {
+        let hashed = hash_values!(self.sample_index, dimension);
+        // Split the hash for both axes
+        let offset = Point2::new(hashed as u16, (hashed >> 16) as u16);
+        let texel = mask_texel(
+            self.pixel.x.wrapping_add(offset.x),
+            self.pixel.y.wrapping_add(offset.y),
+        );
+
+        // Center values in their quantization bins to keep them in [0, 1)
+        Point2::new(
+            ((texel[0] as f32) + 0.5) / 256.0,
+            ((texel[1] as f32) + 0.5) / 256.0,
+        )
+    }
+}
+
+/// Returns the mask channels at `x`,`y`, wrapping around the mask edges.
+fn mask_texel(x: u16, y: u16) -> [u8; 2] {
+    let i = (((y % MASK_DIM) as usize) * (MASK_DIM as usize) + ((x % MASK_DIM) as usize)) * 2;
+    [MASK[i], MASK[i + 1]]
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn clone(&self) -> Box<dyn Sampler> {
+        Box::new(Self::new(
+            Params {
+                pixel_samples: self.pixel_samples,
+            },
+            false,
+        ))
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.pixel_samples
+    }
+
+    fn start_pixel_sample(&mut self, p: Point2<u16>, sample_index: u32, dimension: u32) {
+        self.pixel = p;
+        self.sample_index = sample_index;
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let ret = self.sample_mask(self.dimension).x;
+        self.dimension += 1;
+        ret
+    }
+
+    fn get_2d(&mut self) -> Point2<f32> {
+        let ret = self.sample_mask(self.dimension);
+        self.dimension += 2;
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mask_texel, BlueNoiseSampler, Params, MASK_DIM};
+    use crate::{math::Point2, sampling::Sampler};
+
+    #[test]
+    fn mask_wraps_and_stays_deterministic() {
+        for (x, y) in [(0, 0), (MASK_DIM - 1, 3), (5, MASK_DIM - 1)] {
+            assert_eq!(mask_texel(x, y), mask_texel(x + MASK_DIM, y));
+            assert_eq!(mask_texel(x, y), mask_texel(x, y + 3 * MASK_DIM));
+        }
+        // u16::MAX + 1 is a multiple of the mask size so wrapping offsets stay continuous
+        assert_eq!(mask_texel(u16::MAX, 0), mask_texel(MASK_DIM - 1, 0));
+
+        let sampler = BlueNoiseSampler::new(Params { pixel_samples: 4 }, false);
+        let sequence = |sampler: &mut dyn Sampler, p: Point2<u16>| {
+            let mut values = Vec::new();
+            for i in 0..4 {
+                sampler.start_pixel_sample(p, i, 0);
+                let u = sampler.get_2d();
+                values.extend([u.x, u.y, sampler.get_1d()]);
+            }
+            values
+        };
+
+        let p = Point2::new(MASK_DIM - 2, 7);
+        let values = sequence(sampler.clone().as_mut(), p);
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+        assert_eq!(values, sequence(sampler.clone().as_mut(), p));
+        // Pixels a mask apart see the same values
+        assert_eq!(
+            values,
+            sequence(
+                sampler.clone().as_mut(),
+                Point2::new(p.x + MASK_DIM, p.y + 2 * MASK_DIM)
+            )
+        );
+        // Sample indices see the mask at different offsets
+        assert_ne!(values[0..3], values[3..6]);
+    }
+}
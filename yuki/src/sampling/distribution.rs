@@ -0,0 +1,182 @@
+use crate::math::Point2;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/Sampling_Random_Variables#PiecewiseConstant1DFunctions
+// https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#Piecewise-Constant2DDistributions
+
+/// Piecewise-constant distribution over [0,1)
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    func_int: f32,
+}
+
+impl Distribution1D {
+    /// Creates a new `Distribution1D` over the non-negative `func` values.
+    pub fn new(func: &[f32]) -> Self {
+        assert!(!func.is_empty());
+
+        let n = func.len();
+        let func = func.to_vec();
+
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1] / (n as f32);
+        }
+
+        let func_int = cdf[n];
+        if func_int == 0.0 {
+            // Fall back to uniform
+            for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+                *c = (i as f32) / (n as f32);
+            }
+        } else {
+            for c in cdf.iter_mut().skip(1) {
+                *c /= func_int;
+            }
+        }
+
+        Self {
+            func,
+            cdf,
+            func_int,
+        }
+    }
+
+    /// Returns the number of pieces in this `Distribution1D`.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Returns the integral of the function this `Distribution1D` was created from.
+    pub fn func_int(&self) -> f32 {
+        self.func_int
+    }
+
+    /// Samples a value in [0,1) and returns it with its density and the index of its piece.
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        // Find the last cdf entry that is <= u
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let mut du = u - self.cdf[offset];
+        let cdf_step = self.cdf[offset + 1] - self.cdf[offset];
+        if cdf_step > 0.0 {
+            du /= cdf_step;
+        }
+
+        let pdf = if self.func_int > 0.0 {
+            self.func[offset] / self.func_int
+        } else {
+            0.0
+        };
+
+        (((offset as f32) + du) / (self.count() as f32), pdf, offset)
+    }
+
+    /// Samples a piece and returns its index with its probability.
+    pub fn sample_discrete(&self, u: f32) -> (usize, f32) {
+        let (_, _, offset) = self.sample_continuous(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    /// Returns the probability of [`Distribution1D::sample_discrete`] picking piece `index`.
+    pub fn discrete_pdf(&self, index: usize) -> f32 {
+        if self.func_int == 0.0 {
+            return 0.0;
+        }
+        self.func[index] / (self.func_int * (self.count() as f32))
+    }
+
+    /// Returns the density of sampling `x` in [0,1).
+    pub fn pdf(&self, x: f32) -> f32 {
+        if self.func_int == 0.0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_sign_loss)] // Clamped to positive
+        let offset = ((x * (self.count() as f32)).max(0.0) as usize).min(self.count() - 1);
+        self.func[offset] / self.func_int
+    }
+}
+
+/// Piecewise-constant distribution over [0,1)^2
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Creates a new `Distribution2D` over `func` laid out in `nv` rows of `nu` values.
+    pub fn new(func: &[f32], nu: usize, nv: usize) -> Self {
+        assert!(func.len() == nu * nv);
+
+        let conditionals: Vec<Distribution1D> =
+            func.chunks_exact(nu).map(Distribution1D::new).collect();
+        let marginal_func: Vec<f32> = conditionals.iter().map(Distribution1D::func_int).collect();
+        let marginal = Distribution1D::new(&marginal_func);
+
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    /// Samples a point in [0,1)^2 and returns it with its density.
+    pub fn sample_continuous(&self, u: Point2<f32>) -> (Point2<f32>, f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u.y);
+        let (u, pdf_u, _) = self.conditionals[row].sample_continuous(u.x);
+        (Point2::new(u, v), pdf_u * pdf_v)
+    }
+
+    /// Returns the density of sampling `p` in [0,1)^2.
+    pub fn pdf(&self, p: Point2<f32>) -> f32 {
+        let marginal_int = self.marginal.func_int();
+        if marginal_int == 0.0 {
+            return 0.0;
+        }
+
+        let nu = self.conditionals[0].count();
+        let nv = self.marginal.count();
+        #[allow(clippy::cast_sign_loss)] // Clamped to positive
+        let iu = ((p.x * (nu as f32)).max(0.0) as usize).min(nu - 1);
+        #[allow(clippy::cast_sign_loss)] // Clamped to positive
+        let iv = ((p.y * (nv as f32)).max(0.0) as usize).min(nv - 1);
+        self.conditionals[iv].func[iu] / marginal_int
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Distribution1D, Distribution2D};
+    use crate::math::Point2;
+
+    #[test]
+    fn sampled_pdf_matches_pdf() {
+        let d = Distribution1D::new(&[0.0, 1.0, 3.0, 0.0]);
+        for u in [0.01, 0.2, 0.5, 0.9, 0.999] {
+            let (x, pdf, offset) = d.sample_continuous(u);
+            assert!((0.0..1.0).contains(&x));
+            // Zero pieces should never be sampled
+            assert!(offset == 1 || offset == 2);
+            assert!((pdf - d.pdf(x)).abs() < 1e-6);
+        }
+        // Quarter of the mass is in the second piece
+        let (x, _, _) = d.sample_continuous(0.25);
+        assert!((x - 0.5).abs() < 1e-6);
+
+        let func = [1.0, 2.0, 0.0, 4.0, 0.5, 0.5];
+        let d = Distribution2D::new(&func, 3, 2);
+        for u in [
+            Point2::new(0.1, 0.1),
+            Point2::new(0.7, 0.4),
+            Point2::new(0.5, 0.9),
+        ] {
+            let (p, pdf) = d.sample_continuous(u);
+            assert!(pdf > 0.0);
+            assert!((pdf - d.pdf(p)).abs() < 1e-5);
+        }
+    }
+}
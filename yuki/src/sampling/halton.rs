@@ -0,0 +1,245 @@
+use super::Sampler;
+use crate::{hash_values, math::Point2};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Sampling_and_Reconstruction/The_Halton_Sampler
+// and pbrt-v4
+// https://github.com/mmp/pbrt-v4/blob/master/src/pbrt/util/lowdiscrepancy.h
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Params {
+    pub pixel_samples: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self { pixel_samples: 1 }
+    }
+}
+
+const PRIME_COUNT: usize = 256;
+/// Bases for the radical inverses of each dimension
+const PRIMES: [u32; PRIME_COUNT] = first_primes();
+
+const fn first_primes() -> [u32; PRIME_COUNT] {
+    let mut primes = [0; PRIME_COUNT];
+    let mut count = 0;
+    let mut candidate = 2;
+    while count < PRIME_COUNT {
+        let mut is_prime = true;
+        let mut i = 0;
+        while i < count && primes[i] * primes[i] <= candidate {
+            if candidate % primes[i] == 0 {
+                is_prime = false;
+                break;
+            }
+            i += 1;
+        }
+        if is_prime {
+            primes[count] = candidate;
+            count += 1;
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+pub struct HaltonSampler {
+    pixel_samples: u32,
+    /// Index of the current sample in the full sequence
+    sequence_index: u64,
+    dimension: u32,
+    seed: u64,
+}
+
+impl HaltonSampler {
+    pub fn new(params: Params, force_single_sample: bool) -> Self {
+        // Known seed for debugging
+        // let seed = 0x73B9642E74AC471C;
+        // Random seed for normal use
+        let seed = rand::thread_rng().gen();
+
+        Self::with_seed(params, force_single_sample, seed)
+    }
+
+    /// Creates a new `HaltonSampler` that scrambles its sequence with `seed`.
+    pub fn with_seed(mut params: Params, force_single_sample: bool, seed: u64) -> Self {
+        if force_single_sample {
+            params.pixel_samples = 1;
+        }
+
+        Self {
+            pixel_samples: params.pixel_samples,
+            sequence_index: 0,
+            dimension: 0,
+            seed,
+        }
+    }
+
+    fn sample_dimension(&self, dimension: u32) -> f32 {
+        // Wrap around like pbrt-v4, skipping the film dimensions to avoid correlating with them
+        let dimension = if (dimension as usize) < PRIME_COUNT {
+            dimension
+        } else {
+            2 + dimension % ((PRIME_COUNT as u32) - 2)
+        };
+        scrambled_radical_inverse(
+            PRIMES[dimension as usize],
+            self.sequence_index,
+            mix_bits(self.seed ^ (dimension as u64)),
+        )
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn clone(&self) -> Box<dyn Sampler> {
+        Box::new(Self {
+            pixel_samples: self.pixel_samples,
+            sequence_index: 0,
+            dimension: 0,
+            seed: self.seed,
+        })
+    }
+
+    fn samples_per_pixel(&self) -> u32 {
+        self.pixel_samples
+    }
+
+    fn start_pixel_sample(&mut self, p: Point2<u16>, sample_index: u32, dimension: u32) {
+        // Each pixel walks its own contiguous part of the sequence, which is well distributed
+        // on its own and decorrelates neighboring pixels
+        let pixel_offset = hash_values!(p, self.seed) & 0xFFFF_FFFF;
+        self.sequence_index = pixel_offset + (sample_index as u64);
+        self.dimension = dimension;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let ret = self.sample_dimension(self.dimension);
+        self.dimension += 1;
+        ret
+    }
+
+    fn get_2d(&mut self) -> Point2<f32> {
+        let ret = Point2::new(
+            self.sample_dimension(self.dimension),
+            self.sample_dimension(self.dimension + 1),
+        );
+        self.dimension += 2;
+        ret
+    }
+}
+
+/// Returns the radical inverse of `a` in `base` with each digit permuted by a random offset
+/// derived from `seed`.
+fn scrambled_radical_inverse(base: u32, mut a: u64, seed: u64) -> f32 {
+    let inv_base = 1.0 / (base as f32);
+    let base = base as u64;
+
+    let mut inv_base_m = 1.0;
+    let mut value = 0.0;
+    let mut digit_index = 0u64;
+    // Trailing zero digits also get permuted so continue until digits don't affect the value
+    while 1.0 - ((base - 1) as f32) * inv_base_m < 1.0 {
+        let digit = a % base;
+        a /= base;
+
+        let digit_offset = mix_bits(seed ^ digit_index) % base;
+        let scrambled_digit = (digit + digit_offset) % base;
+
+        inv_base_m *= inv_base;
+        value += (scrambled_digit as f32) * inv_base_m;
+        digit_index += 1;
+    }
+
+    value.min(1.0 - f32::EPSILON / 2.0)
+}
+
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HaltonSampler, Params};
+    use crate::{
+        math::Point2,
+        sampling::{Sampler, UniformParams, UniformSampler},
+    };
+
+    /// Returns the star discrepancy of `points`, evaluated at the anchored boxes spanned by them
+    fn star_discrepancy(points: &[Point2<f32>]) -> f32 {
+        let n = points.len() as f32;
+        let xs: Vec<f32> = points.iter().map(|p| p.x).chain([1.0]).collect();
+        let ys: Vec<f32> = points.iter().map(|p| p.y).chain([1.0]).collect();
+
+        let mut discrepancy = 0.0f32;
+        for &x in &xs {
+            for &y in &ys {
+                let open = points.iter().filter(|p| p.x < x && p.y < y).count() as f32;
+                let closed = points.iter().filter(|p| p.x <= x && p.y <= y).count() as f32;
+                let volume = x * y;
+                discrepancy = discrepancy
+                    .max((open / n - volume).abs())
+                    .max((closed / n - volume).abs());
+            }
+        }
+        discrepancy
+    }
+
+    fn pixel_samples(sampler: &mut dyn Sampler, count: u32) -> Vec<Point2<f32>> {
+        (0..count)
+            .map(|i| {
+                sampler.start_pixel_sample(Point2::new(3, 7), i, 0);
+                sampler.get_2d()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lower_discrepancy_than_uniform() {
+        let count = 256;
+        let mut halton = HaltonSampler::new(
+            Params {
+                pixel_samples: count,
+            },
+            false,
+        );
+        let mut uniform = UniformSampler::new(
+            UniformParams {
+                pixel_samples: count,
+            },
+            false,
+        );
+
+        let halton_points = pixel_samples(&mut halton, count);
+        let uniform_points = pixel_samples(&mut uniform, count);
+        assert!(halton_points
+            .iter()
+            .all(|p| (0.0..1.0).contains(&p.x) && (0.0..1.0).contains(&p.y)));
+
+        let halton_discrepancy = star_discrepancy(&halton_points);
+        let uniform_discrepancy = star_discrepancy(&uniform_points);
+        assert!(
+            halton_discrepancy < uniform_discrepancy,
+            "Halton {} Uniform {}",
+            halton_discrepancy,
+            uniform_discrepancy
+        );
+
+        // Later dimensions continue from the given one
+        halton.start_pixel_sample(Point2::new(3, 7), 5, 0);
+        let _ = halton.get_2d();
+        let third = halton.get_1d();
+        halton.start_pixel_sample(Point2::new(3, 7), 5, 2);
+        assert!((halton.get_1d() - third).abs() < f32::EPSILON);
+    }
+}
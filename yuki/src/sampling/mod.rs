@@ -1,9 +1,17 @@
+mod blue_noise;
+mod distribution;
+mod halton;
 mod stratified;
 mod uniform;
 
+pub use blue_noise::BlueNoiseSampler;
+pub use distribution::{Distribution1D, Distribution2D};
+pub use halton::HaltonSampler;
 pub use stratified::StratifiedSampler;
 pub use uniform::UniformSampler;
 
+pub type BlueNoiseParams = blue_noise::Params;
+pub type HaltonParams = halton::Params;
 pub type StratifiedParams = stratified::Params;
 pub type UniformParams = uniform::Params;
 
@@ -16,6 +24,8 @@ use strum::{Display, EnumString, EnumVariantNames};
 pub enum SamplerType {
     Uniform(uniform::Params),
     Stratified(stratified::Params),
+    Halton(halton::Params),
+    BlueNoise(blue_noise::Params),
 }
 
 impl SamplerType {
@@ -27,6 +37,64 @@ impl SamplerType {
             SamplerType::Uniform(params) => {
                 Arc::new(UniformSampler::new(params, force_single_sample))
             }
+            SamplerType::Halton(params) => {
+                Arc::new(HaltonSampler::new(params, force_single_sample))
+            }
+            SamplerType::BlueNoise(params) => {
+                Arc::new(BlueNoiseSampler::new(params, force_single_sample))
+            }
+        }
+    }
+
+    /// Like [`SamplerType::instantiate`] but with a fixed `seed` so that the sample values only
+    /// depend on the pixel and sample index.
+    pub fn instantiate_with_seed(self, force_single_sample: bool, seed: u64) -> Arc<dyn Sampler> {
+        match self {
+            SamplerType::Stratified(params) => Arc::new(StratifiedSampler::with_seed(
+                params,
+                force_single_sample,
+                seed,
+            )),
+            SamplerType::Uniform(params) => {
+                Arc::new(UniformSampler::with_seed(params, force_single_sample, seed))
+            }
+            SamplerType::Halton(params) => {
+                Arc::new(HaltonSampler::with_seed(params, force_single_sample, seed))
+            }
+            // Blue noise is deterministic as is
+            SamplerType::BlueNoise(params) => {
+                Arc::new(BlueNoiseSampler::new(params, force_single_sample))
+            }
+        }
+    }
+
+    /// Returns this `SamplerType` configured to take at least `samples_per_pixel` samples.
+    ///
+    /// Stratified samplers are rounded up to the smallest grid that fits the samples.
+    pub fn with_samples_per_pixel(self, samples_per_pixel: u32) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        match self {
+            SamplerType::Stratified(mut params) => {
+                let mut x = 1;
+                while x * x < samples_per_pixel {
+                    x += 1;
+                }
+                let y = (samples_per_pixel + x - 1) / x;
+                params.pixel_samples = Vec2::new(x as u16, y as u16);
+                SamplerType::Stratified(params)
+            }
+            SamplerType::Uniform(mut params) => {
+                params.pixel_samples = samples_per_pixel;
+                SamplerType::Uniform(params)
+            }
+            SamplerType::Halton(mut params) => {
+                params.pixel_samples = samples_per_pixel;
+                SamplerType::Halton(params)
+            }
+            SamplerType::BlueNoise(mut params) => {
+                params.pixel_samples = samples_per_pixel;
+                SamplerType::BlueNoise(params)
+            }
         }
     }
 }
@@ -54,6 +122,12 @@ pub trait Sampler: Send + Sync {
     fn get_1d(&mut self) -> f32;
     /// Returns the next two dimensions in the current sample vector.
     fn get_2d(&mut self) -> Point2<f32>;
+    /// Returns the next two dimensions in the current sample vector as an offset for the film
+    /// sample from the pixel's minimum corner. The offset may land outside the pixel if the
+    /// sampler warps it.
+    fn get_pixel_2d(&mut self) -> Point2<f32> {
+        self.get_2d()
+    }
 }
 
 // Based on Physically Based Rendering 3rd ed.
@@ -65,6 +139,50 @@ pub fn cosine_sample_hemisphere(u: Point2<f32>) -> Vec3<f32> {
     Vec3::new(d.x, d.y, z)
 }
 
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta * std::f32::consts::FRAC_1_PI
+}
+
+pub fn uniform_sample_sphere(u: Point2<f32>) -> Vec3<f32> {
+    let z = 1.0 - 2.0 * u[0];
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u[1];
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f32 {
+    1.0 / (4.0 * std::f32::consts::PI)
+}
+
+/// Returns the power heuristic weight of a sample from the strategy with `f_pdf` when it is
+/// combined with one sample from the strategy with `g_pdf`.
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g == 0.0 {
+        0.0
+    } else {
+        f / (f + g)
+    }
+}
+
+pub fn uniform_sample_cone(u: Point2<f32>, cos_theta_max: f32) -> Vec3<f32> {
+    let cos_theta = (1.0 - u[0]) + u[0] * cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u[1];
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f32) -> f32 {
+    1.0 / (2.0 * std::f32::consts::PI * (1.0 - cos_theta_max))
+}
+
+/// Returns the first two barycentrics of a point uniformly distributed on a triangle.
+pub fn uniform_sample_triangle(u: Point2<f32>) -> Point2<f32> {
+    let su0 = u[0].sqrt();
+    Point2::new(1.0 - su0, u[1] * su0)
+}
+
 pub fn concentric_sample_disk(u: Point2<f32>) -> Point2<f32> {
     let offset = u * 2.0 - Vec2::new(1.0, 1.0);
     if offset == Point2::zeros() {
@@ -101,3 +219,26 @@ macro_rules! hash_values {
         hasher.finish()
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SamplerType;
+
+    #[test]
+    fn samples_per_pixel_override() {
+        for sampler_type in [
+            SamplerType::Uniform(super::UniformParams::default()),
+            SamplerType::Halton(super::HaltonParams::default()),
+            SamplerType::BlueNoise(super::BlueNoiseParams::default()),
+        ] {
+            let sampler = sampler_type.with_samples_per_pixel(37).instantiate(false);
+            assert_eq!(sampler.samples_per_pixel(), 37);
+        }
+
+        let stratified = SamplerType::Stratified(super::StratifiedParams::default());
+        for (spp, expected) in [(0, 1), (16, 16), (17, 20), (37, 42)] {
+            let sampler = stratified.with_samples_per_pixel(spp).instantiate(false);
+            assert_eq!(sampler.samples_per_pixel(), expected, "spp {}", spp);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use rand::{distributions::Standard, Rng};
 use rand_pcg::Pcg32;
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
+use strum::{Display, EnumString, EnumVariantNames};
 
 // Ported with tweaks from pbrt-v4
 // https://github.com/mmp/pbrt-v4/blob/master/src/pbrt/samplers.h
@@ -16,11 +17,60 @@ use std::hash::{Hash, Hasher};
 // but this generates each sample on the fly like in
 // https://graphics.pixar.com/library/MultiJitteredSampling/paper.pdf
 
+/// Distribution of the film offsets within and around a pixel
+#[derive(
+    Copy, Clone, Debug, PartialEq, Deserialize, Serialize, Display, EnumVariantNames, EnumString,
+)]
+pub enum PixelWarp {
+    /// Offsets stay within the pixel
+    Uniform,
+    /// Offsets follow a tent of radius 1 around the pixel center
+    Tent,
+    /// Offsets follow a gaussian with standard deviation 0.5, truncated to 1.5 from the center
+    Gaussian,
+}
+
+impl PixelWarp {
+    /// Warps the uniform `u` into an offset from the pixel's minimum corner.
+    pub fn apply(self, u: Point2<f32>) -> Point2<f32> {
+        match self {
+            PixelWarp::Uniform => u,
+            PixelWarp::Tent => Point2::new(0.5 + sample_tent(u.x), 0.5 + sample_tent(u.y)),
+            PixelWarp::Gaussian => {
+                // Box-Muller, flipping u.x to keep the log finite
+                let r = 0.5 * (-2.0 * (1.0 - u.x).ln()).sqrt();
+                let (sin, cos) = (2.0 * std::f32::consts::PI * u.y).sin_cos();
+                Point2::new(
+                    0.5 + (r * cos).clamp(-1.5, 1.5),
+                    0.5 + (r * sin).clamp(-1.5, 1.5),
+                )
+            }
+        }
+    }
+}
+
+// Based on Physically Based Rendering 4th ed.
+// https://pbr-book.org/4ed/Sampling_Algorithms/Sampling_1D_Functions#SampleTent
+/// Maps the uniform `u` to [-1, 1] with density 1 - |x|.
+fn sample_tent(u: f32) -> f32 {
+    if u < 0.5 {
+        (2.0 * u).sqrt() - 1.0
+    } else {
+        1.0 - (2.0 - 2.0 * u).sqrt()
+    }
+}
+
 #[derive(Copy, Clone, Deserialize, Serialize)]
 pub struct Params {
     pub pixel_samples: Vec2<u16>,
     pub symmetric_dimensions: bool,
     pub jitter_samples: bool,
+    #[serde(default = "default_pixel_warp")]
+    pub pixel_warp: PixelWarp,
+}
+
+fn default_pixel_warp() -> PixelWarp {
+    PixelWarp::Uniform
 }
 
 impl Default for Params {
@@ -29,6 +79,7 @@ impl Default for Params {
             pixel_samples: Vec2::new(1, 1),
             symmetric_dimensions: true,
             jitter_samples: true,
+            pixel_warp: default_pixel_warp(),
         }
     }
 }
@@ -36,6 +87,7 @@ impl Default for Params {
 pub struct StratifiedSampler {
     pixel_samples: Vec2<u16>,
     jitter_samples: bool,
+    pixel_warp: PixelWarp,
     pixel: Point2<u16>,
     sample_index: u32,
     dimension: u32,
@@ -44,12 +96,17 @@ pub struct StratifiedSampler {
 }
 
 impl StratifiedSampler {
-    pub fn new(mut params: Params, force_single_sample: bool) -> Self {
+    pub fn new(params: Params, force_single_sample: bool) -> Self {
         // Known seed for debugging
         // let seed = 0x73B9642E74AC471C;
         // Random seed for normal use
         let seed = rand::thread_rng().gen();
 
+        Self::with_seed(params, force_single_sample, seed)
+    }
+
+    /// Creates a new `StratifiedSampler` that derives its random numbers from `seed`.
+    pub fn with_seed(mut params: Params, force_single_sample: bool, seed: u64) -> Self {
         if force_single_sample {
             params.pixel_samples = Vec2::new(1, 1);
         }
@@ -57,6 +114,7 @@ impl StratifiedSampler {
         Self {
             pixel_samples: params.pixel_samples,
             jitter_samples: params.jitter_samples,
+            pixel_warp: params.pixel_warp,
             pixel: Point2::new(0, 0),
             sample_index: 0,
             dimension: 0,
@@ -68,19 +126,17 @@ impl StratifiedSampler {
 
 impl Sampler for StratifiedSampler {
     fn clone(&self) -> Box<dyn Sampler> {
-        Box::new(Self {
-            // Different streams are used for pixels so literally clone the sampler
-            rng: Pcg32::new(self.rng_seed, 0),
-            rng_seed: self.rng_seed,
-            ..Self::new(
-                Params {
-                    pixel_samples: self.pixel_samples,
-                    symmetric_dimensions: false,
-                    jitter_samples: self.jitter_samples,
-                },
-                false,
-            )
-        })
+        // Different streams are used for pixels so literally clone the sampler
+        Box::new(Self::with_seed(
+            Params {
+                pixel_samples: self.pixel_samples,
+                symmetric_dimensions: false,
+                jitter_samples: self.jitter_samples,
+                pixel_warp: self.pixel_warp,
+            },
+            false,
+            self.rng_seed,
+        ))
     }
 
     fn samples_per_pixel(&self) -> u32 {
@@ -141,6 +197,11 @@ impl Sampler for StratifiedSampler {
             ((y as f32) + dy) / (self.pixel_samples.y as f32),
         )
     }
+
+    fn get_pixel_2d(&mut self) -> Point2<f32> {
+        let u = self.get_2d();
+        self.pixel_warp.apply(u)
+    }
 }
 
 // This appears to be from https://graphics.pixar.com/library/MultiJitteredSampling/paper.pdf
@@ -176,3 +237,51 @@ fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
     }
     (i.wrapping_add(p)) % l
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Params, PixelWarp, StratifiedSampler};
+    use crate::{
+        math::{Point2, Vec2},
+        sampling::Sampler,
+    };
+
+    #[test]
+    fn uniform_warp_matches_get_2d() {
+        let params = Params {
+            pixel_samples: Vec2::new(4, 4),
+            ..Params::default()
+        };
+        let mut sampler = StratifiedSampler::new(params, false);
+        let mut reference = sampler.clone();
+        for i in 0..sampler.samples_per_pixel() {
+            sampler.start_pixel_sample(Point2::new(3, 7), i, 0);
+            reference.start_pixel_sample(Point2::new(3, 7), i, 0);
+            assert_eq!(sampler.get_pixel_2d(), reference.get_2d());
+            // Following dimensions should stay in sync
+            assert_eq!(sampler.get_2d(), reference.get_2d());
+        }
+    }
+
+    #[test]
+    fn tent_warp_stays_within_radius() {
+        const N: usize = 64;
+        for i in 0..=N {
+            for j in 0..=N {
+                // Include the ends of the range
+                let u = Point2::new(
+                    (i as f32 / N as f32).min(1.0 - f32::EPSILON),
+                    (j as f32 / N as f32).min(1.0 - f32::EPSILON),
+                );
+                let p = PixelWarp::Tent.apply(u);
+                let offset = Point2::new(p.x - 0.5, p.y - 0.5);
+                assert!((-1.0..=1.0).contains(&offset.x), "{:?}", offset);
+                assert!((-1.0..=1.0).contains(&offset.y), "{:?}", offset);
+            }
+        }
+        assert_eq!(
+            PixelWarp::Tent.apply(Point2::new(0.5, 0.5)),
+            Point2::new(0.5, 0.5)
+        );
+    }
+}
@@ -30,12 +30,17 @@ pub struct UniformSampler {
 }
 
 impl UniformSampler {
-    pub fn new(mut params: Params, force_single_sample: bool) -> Self {
+    pub fn new(params: Params, force_single_sample: bool) -> Self {
         // Known seed for debugging
         // let seed = 0x73B9642E74AC471C;
         // Random seed for normal use
         let seed = rand::thread_rng().gen();
 
+        Self::with_seed(params, force_single_sample, seed)
+    }
+
+    /// Creates a new `UniformSampler` that derives its random numbers from `seed`.
+    pub fn with_seed(mut params: Params, force_single_sample: bool, seed: u64) -> Self {
         if force_single_sample {
             params.pixel_samples = 1;
         }
@@ -52,17 +57,14 @@ impl UniformSampler {
 }
 impl Sampler for UniformSampler {
     fn clone(&self) -> Box<dyn Sampler> {
-        Box::new(Self {
-            // Different streams are used for pixels so literally clone the sampler
-            rng: Pcg32::new(self.rng_seed, 0),
-            rng_seed: self.rng_seed,
-            ..Self::new(
-                Params {
-                    pixel_samples: self.pixel_samples,
-                },
-                false,
-            )
-        })
+        // Different streams are used for pixels so literally clone the sampler
+        Box::new(Self::with_seed(
+            Params {
+                pixel_samples: self.pixel_samples,
+            },
+            false,
+            self.rng_seed,
+        ))
     }
 
     fn samples_per_pixel(&self) -> u32 {
@@ -0,0 +1,109 @@
+use crate::{
+    bvh::BoundingVolumeHierarchy, scene::SceneLoadSettings, shapes::Shape, yuki_info, yuki_warn,
+};
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::OsString,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Instant, UNIX_EPOCH},
+};
+
+/// Builds a [`BoundingVolumeHierarchy`] for `shapes` or loads it from the cache next to the scene
+/// file if the cache matches the scene and `settings`. Also returns back `shapes` as an Arc.
+///
+/// `shapes` need to be given in the same order on each load for the cache to be valid. Only the
+/// main scene file is checked for modifications so meshes referenced by it are validated against
+/// the cached leaf bounds instead, and a cache that doesn't match the loaded shapes is rebuilt.
+pub fn build_bvh(
+    settings: &SceneLoadSettings,
+    shapes: Vec<Arc<dyn Shape>>,
+) -> (BoundingVolumeHierarchy, Arc<Vec<Arc<dyn Shape>>>) {
+    let key = cache_key(settings);
+    let path = cache_path(settings);
+
+    if let Some(key) = key {
+        let load_start = Instant::now();
+        if let Some(bvh) = load(&path, key) {
+            if let Some(ret) = bvh.with_shapes(&shapes) {
+                yuki_info!(
+                    "BVH: Loaded from cache in {:.2}s",
+                    load_start.elapsed().as_secs_f32()
+                );
+                return ret;
+            }
+            yuki_info!("BVH: Cache doesn't match the scene, rebuilding");
+        }
+    }
+
+    let (bvh, shapes) = BoundingVolumeHierarchy::new(
+        shapes,
+        settings.max_shapes_in_node as usize,
+        settings.split_method,
+    );
+
+    if let Some(key) = key {
+        if let Err(why) = store(&path, key, &bvh) {
+            yuki_warn!(
+                "BVH: Failed to write cache '{}': {}",
+                path.to_string_lossy(),
+                why
+            );
+        }
+    }
+
+    (bvh, shapes)
+}
+
+/// Returns the key for the cache of the scene in `settings`, `None` if the scene file has no
+/// modification time.
+fn cache_key(settings: &SceneLoadSettings) -> Option<u64> {
+    let mtime = std::fs::metadata(&settings.path)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    mtime.hash(&mut hasher);
+    settings.split_method.to_string().hash(&mut hasher);
+    settings.max_shapes_in_node.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+fn cache_path(settings: &SceneLoadSettings) -> PathBuf {
+    let mut path = OsString::from(&settings.path);
+    path.push(".bvhcache");
+    PathBuf::from(path)
+}
+
+fn load(path: &Path, key: u64) -> Option<BoundingVolumeHierarchy> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let cached_key: u64 = bincode::deserialize_from(&mut reader).ok()?;
+    if cached_key != key {
+        yuki_info!("BVH: Cache is outdated, rebuilding");
+        return None;
+    }
+
+    match bincode::deserialize_from(&mut reader) {
+        Ok(bvh) => Some(bvh),
+        Err(why) => {
+            yuki_warn!(
+                "BVH: Failed to read cache '{}': {}",
+                path.to_string_lossy(),
+                why
+            );
+            None
+        }
+    }
+}
+
+fn store(path: &Path, key: u64, bvh: &BoundingVolumeHierarchy) -> bincode::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut writer, &key)?;
+    bincode::serialize_into(&mut writer, bvh)
+}
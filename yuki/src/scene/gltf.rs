@@ -0,0 +1,238 @@
+use super::{
+    ply::{triangle_mesh, PlyResult, CREASE_ANGLE_DEGREES},
+    Result,
+};
+use crate::{
+    materials::{Material, Metal, MicrofacetDistributionType, Plastic},
+    math::{transforms::scale, Normal, Point2, Point3, Spectrum, Transform},
+    textures::ConstantTexture,
+    yuki_info, yuki_trace,
+};
+
+use std::{path::Path, sync::Arc, time::Instant};
+
+// Based on
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+
+/// Smallest microfacet alpha, perfectly smooth surfaces break the distributions
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Loads the single mesh of a glTF 2.0 `.gltf` or `.glb` with its metallic-roughness material.
+///
+/// `default_material` is used if the mesh has no material. Animations, skins, morph targets and
+/// multiple meshes or primitives are not supported.
+pub fn load(
+    path: &Path,
+    default_material: &Arc<dyn Material>,
+    transform: Option<Transform<f32>>,
+) -> Result<PlyResult> {
+    let parse_start = Instant::now();
+    let (document, buffers, _) = gltf::import(path)?;
+
+    if document.animations().next().is_some() {
+        return Err("glTF: Animations are not supported".into());
+    }
+    if document.skins().next().is_some() {
+        return Err("glTF: Skinning is not supported".into());
+    }
+
+    // glTF is right-handed, flip X to get the same image from our left-handed space
+    let mut mesh_nodes = Vec::new();
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or("glTF: No scenes in file")?;
+    for node in scene.nodes() {
+        collect_mesh_nodes(node, &scale(-1.0, 1.0, 1.0), &mut mesh_nodes);
+    }
+    let (mesh, mesh_to_world) = match mesh_nodes.as_slice() {
+        [(mesh, mesh_to_world)] => (mesh, mesh_to_world),
+        [] => return Err("glTF: No meshes in the scene".into()),
+        _ => return Err("glTF: Only scenes with a single mesh are supported".into()),
+    };
+
+    let mut primitives = mesh.primitives();
+    let primitive = match (primitives.next(), primitives.next()) {
+        (Some(primitive), None) => primitive,
+        (None, _) => return Err("glTF: Mesh has no primitives".into()),
+        _ => return Err("glTF: Only meshes with a single primitive are supported".into()),
+    };
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        return Err(format!("glTF: Unsupported primitive mode {:?}", primitive.mode()).into());
+    }
+    if primitive.morph_targets().next().is_some() {
+        return Err("glTF: Morph targets are not supported".into());
+    }
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let points: Vec<Point3<f32>> = reader
+        .read_positions()
+        .ok_or("glTF: Mesh has no positions")?
+        .map(|[x, y, z]| mesh_to_world * Point3::new(x, y, z))
+        .collect();
+    let normals: Vec<Normal<f32>> = reader.read_normals().map_or_else(Vec::new, |normals| {
+        normals
+            .map(|[x, y, z]| mesh_to_world * Normal::new(x, y, z))
+            .collect()
+    });
+    let uvs: Vec<Point2<f32>> = reader.read_tex_coords(0).map_or_else(Vec::new, |uvs| {
+        uvs.into_f32().map(|[u, v]| Point2::new(u, v)).collect()
+    });
+    let mut indices: Vec<usize> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+        None => (0..points.len()).collect(),
+    };
+    if indices.len() % 3 != 0 {
+        return Err("glTF: Index count is not a multiple of 3".into());
+    }
+    if let Some(&i) = indices.iter().find(|&&i| i >= points.len()) {
+        return Err(format!("glTF: Index {} is out of bounds", i).into());
+    }
+    // Keep the winding counter clockwise after the flip
+    if mesh_to_world.swaps_handedness() {
+        for tri in indices.chunks_exact_mut(3) {
+            tri.swap(1, 2);
+        }
+    }
+
+    yuki_trace!(
+        "glTF: Parsed {} vertices and {} triangles in {:.2}s",
+        points.len(),
+        indices.len() / 3,
+        parse_start.elapsed().as_secs_f32()
+    );
+
+    let material = match primitive.material().index() {
+        Some(_) => convert_material(&primitive.material()),
+        None => Arc::clone(default_material),
+    };
+
+    Ok(triangle_mesh(
+        "glTF",
+        indices,
+        points,
+        normals,
+        uvs,
+        &material,
+        transform,
+        Some(CREASE_ANGLE_DEGREES),
+    ))
+}
+
+/// Pushes the meshes in the hierarchy under `node` with their transforms into `mesh_nodes`.
+fn collect_mesh_nodes<'a>(
+    node: gltf::Node<'a>,
+    parent_to_world: &Transform<f32>,
+    mesh_nodes: &mut Vec<(gltf::Mesh<'a>, Transform<f32>)>,
+) {
+    // glTF matrices are column major
+    let m = node.transform().matrix();
+    let node_to_parent = Transform::new([
+        [m[0][0], m[1][0], m[2][0], m[3][0]],
+        [m[0][1], m[1][1], m[2][1], m[3][1]],
+        [m[0][2], m[1][2], m[2][2], m[3][2]],
+        [m[0][3], m[1][3], m[2][3], m[3][3]],
+    ]);
+    let node_to_world = parent_to_world * &node_to_parent;
+
+    for child in node.children() {
+        collect_mesh_nodes(child, &node_to_world, mesh_nodes);
+    }
+    if let Some(mesh) = node.mesh() {
+        mesh_nodes.push((mesh, node_to_world));
+    }
+}
+
+/// Converts the factors of a metallic-roughness material into a [`Metal`] or a [`Plastic`].
+fn convert_material(material: &gltf::Material) -> Arc<dyn Material> {
+    let pbr = material.pbr_metallic_roughness();
+    if pbr.base_color_texture().is_some() || pbr.metallic_roughness_texture().is_some() {
+        yuki_info!("glTF: Material textures are not supported, using factors");
+    }
+
+    let [r, g, b, _] = pbr.base_color_factor();
+    let base_color = Spectrum::new(r, g, b);
+    // glTF roughness is perceptual, alpha is its square
+    let alpha = (pbr.roughness_factor() * pbr.roughness_factor()).max(MIN_ALPHA);
+
+    if pbr.metallic_factor() >= 0.5 {
+        // Pick k so that the normal incidence reflectance with eta = 1 matches the base color
+        let k = |f0: f32| {
+            let f0 = f0.clamp(0.0, 0.999);
+            2.0 * (f0 / (1.0 - f0)).sqrt()
+        };
+        Arc::new(Metal::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(Spectrum::new(k(r), k(g), k(b)))),
+            Arc::new(ConstantTexture::new(alpha)),
+            false,
+            MicrofacetDistributionType::TrowbridgeReitz,
+        ))
+    } else {
+        Arc::new(Plastic::new(
+            Arc::new(ConstantTexture::new(base_color)),
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(alpha)),
+            false,
+            None,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::load;
+    use crate::{
+        materials::{Material, Matte},
+        math::{Point3, Spectrum, Transform},
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn embedded_triangle() {
+        // Positions (0, 0, 0), (1, 0, 0) and (0, 1, 0) as a data uri
+        let gltf = r#"{
+    "asset": { "version": "2.0" },
+    "scene": 0,
+    "scenes": [{ "nodes": [0] }],
+    "nodes": [{ "mesh": 0 }],
+    "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+    "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [0.8, 0.2, 0.2, 1.0] } }],
+    "accessors": [{
+        "bufferView": 0,
+        "componentType": 5126,
+        "count": 3,
+        "type": "VEC3",
+        "min": [0.0, 0.0, 0.0],
+        "max": [1.0, 1.0, 0.0]
+    }],
+    "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+    "buffers": [{
+        "byteLength": 36,
+        "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+    }]
+}"#;
+        let path = std::env::temp_dir().join(format!("yuki_gltf_{}.gltf", std::process::id()));
+        std::fs::write(&path, gltf).unwrap();
+
+        let white: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let loaded = load(&path, &white, Some(Transform::default()));
+        std::fs::remove_file(&path).unwrap();
+
+        let result = loaded.unwrap();
+        assert_eq!(result.shapes.len(), 1);
+        // X is flipped and the winding swapped to match
+        assert_eq!(result.mesh.indices, vec![0, 2, 1]);
+        assert_eq!(result.mesh.points[0], Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(result.mesh.points[1], Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(result.mesh.points[2], Point3::new(0.0, 1.0, 0.0));
+    }
+}
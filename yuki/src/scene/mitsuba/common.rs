@@ -1,4 +1,8 @@
-use crate::{find_attr, math::Spectrum, scene::Result};
+use crate::{
+    find_attr,
+    math::{Point3, Spectrum},
+    scene::Result,
+};
 use xml::attribute::OwnedAttribute;
 
 pub fn parse_rgb(attributes: &[OwnedAttribute], expected_name: &str) -> Result<Spectrum<f32>> {
@@ -16,3 +20,22 @@ pub fn parse_rgb(attributes: &[OwnedAttribute], expected_name: &str) -> Result<S
     }
     Ok(v)
 }
+
+pub fn parse_point(attributes: &[OwnedAttribute], expected_name: &str) -> Result<Point3<f32>> {
+    let mut p = Point3::zeros();
+    let name = find_attr!(attributes, "name").as_str();
+    if name != expected_name {
+        return Err(format!("Expected point to be '{}', got '{}'", expected_name, name).into());
+    }
+    for attr in attributes {
+        let axis_value = match attr.name.local_name.as_str() {
+            "x" => &mut p.x,
+            "y" => &mut p.y,
+            "z" => &mut p.z,
+            "name" => continue,
+            a => return Err(format!("Invalid point axis '{}'", a).into()),
+        };
+        *axis_value = attr.value.parse()?;
+    }
+    Ok(p)
+}
@@ -1,4 +1,7 @@
-use super::{common::parse_rgb, transform};
+use super::{
+    common::{parse_point, parse_rgb},
+    transform,
+};
 use crate::{
     find_attr,
     lights::{Light, PointLight, SpotLight},
@@ -15,8 +18,16 @@ use xml::{attribute::OwnedAttribute, name::OwnedName, reader::EventReader};
 use std::sync::Arc;
 
 pub enum Emitter {
-    Background { color: Spectrum<f32> },
-    Light { light: Arc<dyn Light> },
+    Background {
+        color: Spectrum<f32>,
+    },
+    Light {
+        light: Arc<dyn Light>,
+    },
+    /// Emission of the shape the emitter is nested in
+    Area {
+        radiance: Spectrum<f32>,
+    },
 }
 
 pub fn parse<T: std::io::Read>(
@@ -27,7 +38,10 @@ pub fn parse<T: std::io::Read>(
     let attr_type = find_attr!(attributes, "type");
     let ret = match attr_type.as_str() {
         "constant" => Some(Emitter::Background {
-            color: parse_constant_emitter(parser, indent)?,
+            color: parse_radiance(parser, indent)?,
+        }),
+        "area" => Some(Emitter::Area {
+            radiance: parse_radiance(parser, indent)?,
         }),
         "point" => Some(Emitter::Light {
             light: parse_point_light(parser, indent)?,
@@ -40,7 +54,7 @@ pub fn parse<T: std::io::Read>(
     Ok(ret)
 }
 
-fn parse_constant_emitter<T: std::io::Read>(
+fn parse_radiance<T: std::io::Read>(
     parser: &mut EventReader<T>,
     mut indent: String,
 ) -> Result<Spectrum<f32>> {
@@ -56,7 +70,7 @@ fn parse_constant_emitter<T: std::io::Read>(
             "rgb" => {
                 radiance = parse_rgb(&attributes, "radiance")?;
             }
-            _ => return Err(format!("Unknown constant emitter data type '{}'", data_type).into()),
+            _ => return Err(format!("Unknown emitter data type '{}'", data_type).into()),
         }
         Ok(())
     });
@@ -78,25 +92,7 @@ fn parse_point_light<T: std::io::Read>(
      -> Result<()> {
         let data_type = name.local_name.as_str();
         match data_type {
-            "point" => {
-                if find_attr!(&attributes, "name").as_str() != "position" {
-                    return Err(
-                        "Expected 'name': 'filename' as first mesh 'string' attribute".into(),
-                    );
-                }
-
-                for axis_value in attributes.iter().skip(1) {
-                    let pos_value = match axis_value.name.local_name.as_str() {
-                        "x" => &mut position.x,
-                        "y" => &mut position.y,
-                        "z" => &mut position.z,
-                        a => {
-                            return Err(format!("Invalid point axis '{}'", a).into());
-                        }
-                    };
-                    *pos_value = axis_value.value.parse()?;
-                }
-            }
+            "point" => position = parse_point(&attributes, "position")?,
             "rgb" => {
                 intensity = parse_rgb(&attributes, "intensity")?;
             }
@@ -1,8 +1,10 @@
 use crate::{
-    materials::{Glass, Material, Matte},
+    materials::{
+        ConductorSpectra, Glass, Material, Matte, Metal, MicrofacetDistributionType, Plastic,
+    },
     math::Spectrum,
     parse_element,
-    scene::Result,
+    scene::{pbrt::sampled_spectrum_into_rgb, Result},
     textures::ConstantTexture,
     yuki_error, yuki_info, yuki_trace,
 };
@@ -13,14 +15,32 @@ use approx::abs_diff_eq;
 use std::sync::Arc;
 use xml::{attribute::OwnedAttribute, name::OwnedName, reader::EventReader};
 
-// TODO: This does not support glass now. Make "bsdf" -parsing generic, move it out of mod.rs
-pub fn parse_twosided<T: std::io::Read>(
+/// Parses the `bsdf` element with `attributes`.
+pub fn parse<T: std::io::Read>(
+    attributes: &[OwnedAttribute],
+    parser: &mut EventReader<T>,
+    indent: String,
+) -> Result<Arc<dyn Material>> {
+    let bsdf_type = find_attr!(attributes, "type");
+    match bsdf_type.as_str() {
+        "twosided" => parse_twosided(parser, indent),
+        "diffuse" => parse_diffuse(parser, indent),
+        "dielectric" => parse_dielectric(parser, indent),
+        "roughconductor" => parse_roughconductor(parser, indent),
+        "roughplastic" => parse_roughplastic(parser, indent),
+        _ => Err(format!("Unknown bsdf type '{}'", bsdf_type).into()),
+    }
+}
+
+fn parse_twosided<T: std::io::Read>(
     parser: &mut EventReader<T>,
     mut indent: String,
 ) -> Result<Arc<dyn Material>> {
     let mut material: Arc<dyn Material> = Arc::new(Matte::new(
         Arc::new(ConstantTexture::new(Spectrum::ones())),
         Arc::new(ConstantTexture::new(0.0)),
+        None,
+        None,
     ));
 
     parse_element!(parser, indent, |name: &OwnedName,
@@ -31,7 +51,7 @@ pub fn parse_twosided<T: std::io::Read>(
         let data_type = name.local_name.as_str();
         match data_type {
             "bsdf" => {
-                material = parse_diffuse(parser, indent.clone())?;
+                material = parse(&attributes, parser, indent.clone())?;
                 *level -= 1;
                 indent.truncate(indent.len() - 2);
             }
@@ -39,6 +59,8 @@ pub fn parse_twosided<T: std::io::Read>(
                 material = Arc::new(Matte::new(
                     Arc::new(ConstantTexture::new(parse_rgb(&attributes, "reflectance")?)),
                     Arc::new(ConstantTexture::new(0.0)),
+                    None,
+                    None,
                 ));
             }
             _ => return Err(format!("Unknown material data type '{}'", data_type).into()),
@@ -48,7 +70,7 @@ pub fn parse_twosided<T: std::io::Read>(
     Ok(material)
 }
 
-pub fn parse_diffuse<T: std::io::Read>(
+fn parse_diffuse<T: std::io::Read>(
     parser: &mut EventReader<T>,
     mut indent: String,
 ) -> Result<Arc<dyn Material>> {
@@ -73,13 +95,15 @@ pub fn parse_diffuse<T: std::io::Read>(
     Ok(Arc::new(Matte::new(
         reflectance,
         Arc::new(ConstantTexture::new(0.0)),
+        None,
+        None,
     )))
 }
 
 const BK7_GLASS_IOR: f32 = 1.5046;
 const AIR_IOR: f32 = 1.000_277;
 
-pub fn parse_dielectric<T: std::io::Read>(
+fn parse_dielectric<T: std::io::Read>(
     parser: &mut EventReader<T>,
     mut indent: String,
 ) -> Result<Arc<dyn Material>> {
@@ -138,5 +162,208 @@ pub fn parse_dielectric<T: std::io::Read>(
         specular_reflectance,
         specular_transmittance,
         int_ior,
+        Arc::new(ConstantTexture::new(0.0)),
+        false,
+        MicrofacetDistributionType::TrowbridgeReitz,
     )))
 }
+
+/// Default roughness of Mitsuba's rough BSDFs
+const DEFAULT_ALPHA: f32 = 0.1;
+
+fn parse_distribution(name: &str) -> Result<MicrofacetDistributionType> {
+    match name {
+        "beckmann" => Ok(MicrofacetDistributionType::Beckmann),
+        "ggx" => Ok(MicrofacetDistributionType::TrowbridgeReitz),
+        d => Err(format!("Unknown microfacet distribution '{}'", d).into()),
+    }
+}
+
+fn parse_roughconductor<T: std::io::Read>(
+    parser: &mut EventReader<T>,
+    mut indent: String,
+) -> Result<Arc<dyn Material>> {
+    let mut alpha = DEFAULT_ALPHA;
+    let mut distribution = MicrofacetDistributionType::Beckmann;
+    // Mitsuba defaults to a perfect mirror
+    let mut eta = Spectrum::zeros();
+    let mut k = Spectrum::ones();
+    let mut explicit_eta = None;
+    let mut explicit_k = None;
+
+    parse_element!(parser, indent, |name: &OwnedName,
+                                    attributes: Vec<OwnedAttribute>,
+                                    _: &mut i32,
+                                    _: &mut Option<u32>|
+     -> Result<()> {
+        let data_type = name.local_name.as_str();
+        let (attr_name, attr_value) = (
+            find_attr!(&attributes, "name").as_str(),
+            find_attr!(&attributes, "value").as_str(),
+        );
+        match (data_type, attr_name) {
+            ("float", "alpha") => alpha = attr_value.parse()?,
+            ("string", "distribution") => distribution = parse_distribution(attr_value)?,
+            ("string", "material") => {
+                if attr_value != "none" {
+                    let spectra = ConductorSpectra::from_name(attr_value)
+                        .ok_or_else(|| format!("Unknown conductor '{}'", attr_value))?;
+                    eta = sampled_spectrum_into_rgb(spectra.wavelengths, spectra.eta);
+                    k = sampled_spectrum_into_rgb(spectra.wavelengths, spectra.k);
+                }
+            }
+            ("rgb", "eta") => explicit_eta = Some(parse_rgb(&attributes, "eta")?),
+            ("rgb", "k") => explicit_k = Some(parse_rgb(&attributes, "k")?),
+            _ => {
+                return Err(format!(
+                    "Unknown roughconductor data '{}' '{}'",
+                    data_type, attr_name
+                )
+                .into())
+            }
+        }
+        Ok(())
+    });
+
+    Ok(Arc::new(Metal::new(
+        Arc::new(ConstantTexture::new(explicit_eta.unwrap_or(eta))),
+        Arc::new(ConstantTexture::new(explicit_k.unwrap_or(k))),
+        Arc::new(ConstantTexture::new(alpha)),
+        false,
+        distribution,
+    )))
+}
+
+fn parse_roughplastic<T: std::io::Read>(
+    parser: &mut EventReader<T>,
+    mut indent: String,
+) -> Result<Arc<dyn Material>> {
+    let mut alpha = DEFAULT_ALPHA;
+    let mut diffuse_reflectance = Spectrum::new(0.5, 0.5, 0.5);
+    let mut specular_reflectance = Spectrum::ones();
+
+    parse_element!(parser, indent, |name: &OwnedName,
+                                    attributes: Vec<OwnedAttribute>,
+                                    _: &mut i32,
+                                    _: &mut Option<u32>|
+     -> Result<()> {
+        let data_type = name.local_name.as_str();
+        let (attr_name, attr_value) = (
+            find_attr!(&attributes, "name").as_str(),
+            find_attr!(&attributes, "value").as_str(),
+        );
+        match (data_type, attr_name) {
+            ("float", "alpha") => alpha = attr_value.parse()?,
+            ("string", "distribution") => {
+                if !matches!(
+                    parse_distribution(attr_value)?,
+                    MicrofacetDistributionType::TrowbridgeReitz
+                ) {
+                    yuki_info!("Plastic only supports the 'ggx' distribution, using it instead");
+                }
+            }
+            ("rgb", "diffuse_reflectance") => {
+                diffuse_reflectance = parse_rgb(&attributes, attr_name)?;
+            }
+            ("rgb", "specular_reflectance") => {
+                specular_reflectance = parse_rgb(&attributes, attr_name)?;
+            }
+            _ => {
+                return Err(
+                    format!("Unknown roughplastic data '{}' '{}'", data_type, attr_name).into(),
+                )
+            }
+        }
+        Ok(())
+    });
+
+    Ok(Arc::new(Plastic::new(
+        Arc::new(ConstantTexture::new(diffuse_reflectance)),
+        Arc::new(ConstantTexture::new(specular_reflectance)),
+        Arc::new(ConstantTexture::new(alpha)),
+        false,
+        None,
+        None,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::parse;
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::{BxdfType, ConductorSpectra, Material, Metal, MicrofacetDistributionType},
+        math::{Point2, Point3, Spectrum, Transform, Vec3},
+        scene::pbrt::sampled_spectrum_into_rgb,
+        shapes::Sphere,
+        textures::ConstantTexture,
+    };
+    use allocators::{LinearAllocator, ScopedScratch};
+    use xml::reader::{EventReader, XmlEvent};
+
+    #[test]
+    fn roughconductor_uses_named_material() {
+        let xml = r#"<bsdf type="twosided">
+    <bsdf type="roughconductor">
+        <string name="material" value="Au"/>
+        <string name="distribution" value="ggx"/>
+        <float name="alpha" value="0.2"/>
+    </bsdf>
+</bsdf>"#;
+        let mut parser = EventReader::new(xml.as_bytes());
+        let attributes = loop {
+            if let XmlEvent::StartElement { attributes, .. } = parser.next().unwrap() {
+                break attributes;
+            }
+        };
+        let parsed = parse(&attributes, &mut parser, "  ".into()).unwrap();
+
+        let metal = |conductor: &str| -> Arc<dyn Material> {
+            let spectra = ConductorSpectra::from_name(conductor).unwrap();
+            Arc::new(Metal::new(
+                Arc::new(ConstantTexture::new(sampled_spectrum_into_rgb(
+                    spectra.wavelengths,
+                    spectra.eta,
+                ))),
+                Arc::new(ConstantTexture::new(sampled_spectrum_into_rgb(
+                    spectra.wavelengths,
+                    spectra.k,
+                ))),
+                Arc::new(ConstantTexture::new(0.2)),
+                false,
+                MicrofacetDistributionType::TrowbridgeReitz,
+            ))
+        };
+        let gold = metal("Au");
+        let copper = metal("Cu");
+
+        let shape = Sphere::new(&Transform::default(), 1.0, Arc::clone(&gold));
+        let si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::new(0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let f = |material: &Arc<dyn Material>| -> Spectrum<f32> {
+            material.compute_scattering_functions(&scratch, &si).f(
+                Vec3::new(0.0, 0.6, 0.8),
+                Vec3::new(0.0, -0.6, 0.8),
+                BxdfType::all(),
+            )
+        };
+        let (f_parsed, f_gold, f_copper) = (f(&parsed), f(&gold), f(&copper));
+        assert!(f_gold.r > 0.0);
+        assert_eq!(f_parsed.r, f_gold.r);
+        assert_eq!(f_parsed.g, f_gold.g);
+        assert_eq!(f_parsed.b, f_gold.b);
+        assert_ne!(f_parsed.b, f_copper.b);
+    }
+}
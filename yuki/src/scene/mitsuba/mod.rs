@@ -7,25 +7,36 @@ mod shape;
 mod transform;
 
 use crate::{
-    bvh::BoundingVolumeHierarchy,
     film::FilmSettings,
     find_attr,
-    lights::Light,
+    lights::{power_distribution, Light},
     materials::Material,
     math::{Ray, Spectrum},
-    scene::{ply::PlyResult, CameraParameters, Result, Scene, SceneLoadSettings},
+    scene::{
+        bvh_cache::build_bvh, CameraParameters, LoadCancelled, Result, Scene, SceneLoadSettings,
+    },
     yuki_error, yuki_trace,
 };
 
-use self::emitter::Emitter;
+use self::{emitter::Emitter, shape::ShapeResult};
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use xml::{
     attribute::OwnedAttribute,
     reader::{EventReader, XmlEvent},
 };
 
-pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, FilmSettings)> {
+/// Loads the scene in `settings`, returning [`LoadCancelled`] soon after `cancel` is set.
+pub fn load(
+    settings: &SceneLoadSettings,
+    cancel: &AtomicBool,
+) -> Result<(Scene, CameraParameters, FilmSettings)> {
     let dir_path = settings.path.parent().unwrap().to_path_buf();
     let file = std::fs::File::open(settings.path.to_str().unwrap())?;
     let file_buf = std::io::BufReader::new(file);
@@ -41,6 +52,9 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
     let mut indent = String::new();
     let mut ignore_level: Option<u32> = None;
     loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(LoadCancelled.into());
+        }
         match parser.next() {
             Ok(evt) => match evt {
                 XmlEvent::StartDocument {
@@ -93,24 +107,8 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
                                 indent.truncate(indent.len() - 2);
                             }
                             "bsdf" => {
-                                let bsdf_type = find_attr!(&attributes, "type");
-
-                                let material = match bsdf_type.as_str() {
-                                    "twosided" => {
-                                        material::parse_twosided(&mut parser, indent.clone())?
-                                    }
-                                    "diffuse" => {
-                                        material::parse_diffuse(&mut parser, indent.clone())?
-                                    }
-                                    "dielectric" => {
-                                        material::parse_dielectric(&mut parser, indent.clone())?
-                                    }
-                                    _ => {
-                                        return Err(
-                                            format!("Unknown bsdf type '{}'", bsdf_type).into()
-                                        )
-                                    }
-                                };
+                                let material =
+                                    material::parse(&attributes, &mut parser, indent.clone())?;
                                 indent.truncate(indent.len() - 2);
 
                                 let id = find_attr!(&attributes, "id");
@@ -121,23 +119,29 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
                                     Some(e) => match e {
                                         Emitter::Background { color } => background = color,
                                         Emitter::Light { light } => lights.push(light),
+                                        Emitter::Area { .. } => {
+                                            return Err("Area emitter outside of a shape".into());
+                                        }
                                     },
                                     None => ignore_level = Some(0),
                                 }
                             }
                             "shape" => {
-                                let PlyResult {
+                                let ShapeResult {
                                     mesh,
-                                    shapes: ply_shapes,
+                                    shapes: shape_shapes,
+                                    lights: shape_lights,
                                 } = shape::parse(
                                     &dir_path,
                                     &materials,
                                     &attributes,
                                     &mut parser,
                                     indent.clone(),
+                                    settings.ply_crease_angle,
                                 )?;
-                                meshes.push(mesh);
-                                shapes.extend(ply_shapes);
+                                meshes.extend(mesh);
+                                shapes.extend(shape_shapes);
+                                lights.extend(shape_lights);
                                 indent.truncate(indent.len() - 2);
                             }
                             name => return Err(format!("Unknown element: '{}'", name).into()),
@@ -183,11 +187,11 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
         }
     }
 
-    let (bvh, shapes) = BoundingVolumeHierarchy::new(
-        shapes,
-        settings.max_shapes_in_node as usize,
-        settings.split_method,
-    );
+    if cancel.load(Ordering::Relaxed) {
+        return Err(LoadCancelled.into());
+    }
+
+    let (bvh, shapes) = build_bvh(settings, shapes);
 
     // Let's default target to middle way into the visible scene since we don't have look at here
     {
@@ -202,6 +206,8 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
         }
     }
 
+    let light_distribution = power_distribution(&lights, bvh.bounds());
+
     Ok((
         Scene {
             name: settings.path.file_name().unwrap().to_str().unwrap().into(),
@@ -210,9 +216,110 @@ pub fn load(settings: &SceneLoadSettings) -> Result<(Scene, CameraParameters, Fi
             shapes,
             bvh,
             lights,
+            light_distribution,
             background,
+            medium: None,
         },
         camera_params,
         film_settings,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::load;
+    use crate::{math::Point3, scene::SceneLoadSettings};
+
+    #[test]
+    fn rectangle_is_two_triangles() {
+        let dir = std::env::temp_dir().join(format!("yuki_mitsuba_rect_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.xml"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            r#"<scene version="2.1.0">
+    <bsdf type="diffuse" id="white"/>
+    <shape type="rectangle">
+        <transform name="to_world">
+            <scale value="2 3 1"/>
+            <translate value="0 0 1"/>
+        </transform>
+        <ref name="bsdf" id="white"/>
+    </shape>
+</scene>
+"#,
+        )
+        .unwrap();
+
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = loaded.unwrap().0;
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.meshes[0].indices.len(), 6);
+        assert_eq!(scene.shapes.len(), 2);
+        let bounds = scene.bvh.bounds();
+        assert!((bounds.p_min - Point3::new(-2.0, -3.0, 1.0)).len() < 1e-5);
+        assert!((bounds.p_max - Point3::new(2.0, 3.0, 1.0)).len() < 1e-5);
+    }
+
+    #[test]
+    fn emissive_sphere_creates_lights() {
+        let dir = std::env::temp_dir().join(format!("yuki_mitsuba_sphere_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.xml"),
+            ..SceneLoadSettings::default()
+        };
+        let scene = |emitter: &str| {
+            std::fs::write(
+                &settings.path,
+                format!(
+                    r#"<scene version="2.1.0">
+    <bsdf type="diffuse" id="white"/>
+    <shape type="sphere">
+        <point name="center" x="1" y="0" z="0"/>
+        <float name="radius" value="0.5"/>
+        <transform name="to_world">
+            <scale value="2"/>
+        </transform>
+        <ref name="bsdf" id="white"/>
+        {}
+    </shape>
+</scene>
+"#,
+                    emitter
+                ),
+            )
+            .unwrap();
+            load(&settings, &AtomicBool::new(false))
+        };
+
+        let plain = scene("");
+        let emissive =
+            scene(r#"<emitter type="area"><rgb name="radiance" value="1 2 3"/></emitter>"#);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The scale applies to both the center and the radius, and x is flipped
+        let plain = plain.unwrap().0;
+        assert!(plain.meshes.is_empty());
+        assert_eq!(plain.shapes.len(), 1);
+        assert!(plain.lights.is_empty());
+        let bounds = plain.bvh.bounds();
+        assert!((bounds.p_min - Point3::new(-3.0, -1.0, -1.0)).len() < 1e-4);
+        assert!((bounds.p_max - Point3::new(-1.0, 1.0, 1.0)).len() < 1e-4);
+
+        let emissive = emissive.unwrap().0;
+        assert_eq!(emissive.meshes.len(), 1);
+        assert!(!emissive.lights.is_empty());
+        assert_eq!(emissive.lights.len(), emissive.shapes.len());
+        let bounds = emissive.bvh.bounds();
+        assert!((bounds.p_min - Point3::new(-3.0, -1.0, -1.0)).len() < 1e-4);
+        assert!((bounds.p_max - Point3::new(-1.0, 1.0, 1.0)).len() < 1e-4);
+    }
+}
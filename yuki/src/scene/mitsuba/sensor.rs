@@ -1,5 +1,5 @@
 use crate::{
-    camera::FoV,
+    camera::{CameraType, FoV},
     find_attr,
     math::{
         transforms::{rotation_euler, scale, translation},
@@ -21,6 +21,8 @@ pub fn parse<T: std::io::Read>(
 ) -> Result<CameraParameters> {
     let mut fov_axis = String::new();
     let mut fov_angle = 0.0;
+    let mut lens_radius = 0.0;
+    let mut focal_distance = 1e6;
     let mut transform = Transform::default();
 
     parse_element!(parser, indent, |name: &OwnedName,
@@ -49,6 +51,8 @@ pub fn parse<T: std::io::Read>(
                 );
                 match attr_name {
                     "fov" => fov_angle = attr_value.as_str().parse()?,
+                    "aperture_radius" => lens_radius = attr_value.as_str().parse()?,
+                    "focus_distance" => focal_distance = attr_value.as_str().parse()?,
                     "near_clip" | "far_clip" | "" => (), // TODO
                     _ => {
                         return Err(format!("Unknown sensor string element '{}'", attr_name).into())
@@ -104,6 +108,9 @@ pub fn parse<T: std::io::Read>(
         position,
         target,
         up,
-        fov,
+        camera_type: CameraType::Perspective { fov },
+        lens_radius,
+        focal_distance,
+        ..CameraParameters::default()
     })
 }
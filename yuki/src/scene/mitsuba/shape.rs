@@ -1,13 +1,22 @@
 use crate::{
     find_attr,
+    lights::{AreaLight, DiffuseAreaLight, Light},
     materials::Material,
-    math::{transforms::scale, Transform},
+    math::{
+        transforms::{scale, translation},
+        Normal, Point2, Point3, Spectrum, Transform, Vec3,
+    },
     parse_element,
     scene::{ply, Result},
+    shapes::{Mesh, Shape, Sphere, Triangle},
     yuki_error, yuki_info, yuki_trace,
 };
 
-use super::transform;
+use super::{
+    common::parse_point,
+    emitter::{self, Emitter},
+    transform,
+};
 
 use std::{
     collections::HashMap,
@@ -16,28 +25,60 @@ use std::{
 };
 use xml::{attribute::OwnedAttribute, name::OwnedName, reader::EventReader};
 
+/// Rings and segments in the triangle meshes of emissive spheres
+const SPHERE_RINGS: usize = 32;
+const SPHERE_SEGMENTS: usize = 64;
+
+pub struct ShapeResult {
+    /// `None` for analytic shapes
+    pub mesh: Option<Arc<Mesh>>,
+    pub shapes: Vec<Arc<dyn Shape>>,
+    pub lights: Vec<Arc<dyn Light>>,
+}
+
 pub fn parse<T: std::io::Read>(
     dir_path: &Path,
     materials: &HashMap<String, Arc<dyn Material>>,
     attributes: &[OwnedAttribute],
     parser: &mut EventReader<T>,
     mut indent: String,
-) -> Result<ply::PlyResult> {
-    let data_type = find_attr!(attributes, "type").as_str();
-    if data_type != "ply" {
-        return Err(format!("Unexpected shape type '{}'!", data_type).into());
+    ply_crease_angle: Option<f32>,
+) -> Result<ShapeResult> {
+    let shape_type = find_attr!(attributes, "type").as_str();
+    if !matches!(shape_type, "ply" | "rectangle" | "cube" | "sphere") {
+        return Err(format!("Unexpected shape type '{}'!", shape_type).into());
     }
     let mut transform = Transform::default();
     let mut ply_abspath = None;
     let mut material_id = None;
+    let mut center = Point3::zeros();
+    let mut radius = 1.0;
+    let mut area_radiance = None;
     // TODO: Parse whole shape first, load with constructed material after
     parse_element!(parser, indent, |name: &OwnedName,
                                     attributes: Vec<OwnedAttribute>,
                                     level: &mut i32,
-                                    _: &mut Option<u32>|
+                                    ignore_level: &mut Option<u32>|
      -> Result<()> {
         let data_type = name.local_name.as_str();
         match data_type {
+            "point" => center = parse_point(&attributes, "center")?,
+            "float" => {
+                let name = find_attr!(&attributes, "name").as_str();
+                if name != "radius" {
+                    return Err(format!("Unexpected shape float '{}'", name).into());
+                }
+                radius = find_attr!(&attributes, "value").parse()?;
+            }
+            "emitter" => match emitter::parse(&attributes, parser, indent.clone())? {
+                Some(Emitter::Area { radiance }) => {
+                    area_radiance = Some(radiance);
+                    *level -= 1;
+                    indent.truncate(indent.len() - 2);
+                }
+                Some(_) => return Err("Only area emitters can be attached to shapes".into()),
+                None => *ignore_level = Some(0),
+            },
             "string" => {
                 if find_attr!(&attributes, "name").as_str() != "filename" {
                     return Err("Expected 'name': 'filename' as mesh 'string' attribute".into());
@@ -78,17 +119,180 @@ pub fn parse<T: std::io::Read>(
     // Mitsuba's +X is to the left of +Z, ours to the right of it
     transform = &scale(-1.0, 1.0, 1.0) * &transform;
 
-    if ply_abspath.is_none() {
+    if shape_type == "ply" && ply_abspath.is_none() {
         return Err("Mesh with no ply".into());
     }
 
-    if let Some(id) = material_id {
-        if let Some(material) = materials.get(&id) {
-            ply::load(&ply_abspath.unwrap(), material, Some(transform))
-        } else {
-            Err(format!("Unknown mesh material '{}'", id).into())
+    let material = match material_id {
+        Some(id) => match materials.get(&id) {
+            Some(material) => material,
+            None => return Err(format!("Unknown mesh material '{}'", id).into()),
+        },
+        None => return Err("Mesh with no material".into()),
+    };
+
+    let mesh = match shape_type {
+        "sphere" => {
+            // Mitsuba applies to_world after placing the sphere so scales also apply to the radius
+            let object_to_world = &transform * &translation(Vec3::from(center));
+            if area_radiance.is_none() {
+                return Ok(ShapeResult {
+                    mesh: None,
+                    shapes: vec![Arc::new(Sphere::new(
+                        &object_to_world,
+                        radius,
+                        Arc::clone(material),
+                    )) as Arc<dyn Shape>],
+                    lights: Vec::new(),
+                });
+            }
+            // Area lights are only implemented for triangles
+            yuki_info!("Tessellating emissive sphere");
+            Arc::new(sphere_mesh(&object_to_world, radius))
         }
-    } else {
-        Err("Mesh with no material".into())
+        "rectangle" => Arc::new(rectangle_mesh(&transform)),
+        "cube" => Arc::new(cube_mesh(&transform)),
+        _ => {
+            let ply::PlyResult { mesh, shapes } = ply::load(
+                &ply_abspath.unwrap(),
+                material,
+                Some(transform),
+                ply_crease_angle,
+            )?;
+            if area_radiance.is_none() {
+                return Ok(ShapeResult {
+                    mesh: Some(mesh),
+                    shapes,
+                    lights: Vec::new(),
+                });
+            }
+            // Emissive triangles need their lights so recreate them
+            mesh
+        }
+    };
+
+    Ok(triangles(mesh, material, area_radiance))
+}
+
+/// Returns a sphere of `radius` around the origin as a triangle mesh.
+fn sphere_mesh(object_to_world: &Transform<f32>, radius: f32) -> Mesh {
+    let mut points = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for ring in 0..=SPHERE_RINGS {
+        let v = (ring as f32) / (SPHERE_RINGS as f32);
+        let (sin_theta, cos_theta) = (v * std::f32::consts::PI).sin_cos();
+        for segment in 0..=SPHERE_SEGMENTS {
+            let u = (segment as f32) / (SPHERE_SEGMENTS as f32);
+            let (sin_phi, cos_phi) = (u * 2.0 * std::f32::consts::PI).sin_cos();
+            let n = Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+            points.push(Point3::zeros() + n * radius);
+            normals.push(Normal::from(n));
+            uvs.push(Point2::new(u, v));
+        }
+    }
+
+    // Triangles touching the poles would be degenerate on one side of their quad
+    let vertex = |ring: usize, segment: usize| ring * (SPHERE_SEGMENTS + 1) + segment;
+    let mut indices = Vec::new();
+    for ring in 0..SPHERE_RINGS {
+        for segment in 0..SPHERE_SEGMENTS {
+            let v00 = vertex(ring, segment);
+            let v10 = vertex(ring + 1, segment);
+            let v11 = vertex(ring + 1, segment + 1);
+            let v01 = vertex(ring, segment + 1);
+            if ring != SPHERE_RINGS - 1 {
+                indices.extend([v00, v10, v11]);
+            }
+            if ring != 0 {
+                indices.extend([v00, v11, v01]);
+            }
+        }
+    }
+
+    Mesh::new(object_to_world, indices, points, normals, uvs)
+}
+
+/// Returns Mitsuba's rectangle that covers `[-1, 1]` on x and y, facing +z.
+fn rectangle_mesh(object_to_world: &Transform<f32>) -> Mesh {
+    Mesh::new(
+        object_to_world,
+        vec![0, 1, 2, 2, 3, 0],
+        vec![
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(-1.0, 1.0, 0.0),
+        ],
+        vec![Normal::new(0.0, 0.0, 1.0); 4],
+        vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(1.0, 0.0),
+            Point2::new(1.0, 1.0),
+            Point2::new(0.0, 1.0),
+        ],
+    )
+}
+
+/// Returns Mitsuba's cube that covers `[-1, 1]` on all axes.
+///
+/// Faces don't share vertices to keep the edges hard.
+fn cube_mesh(object_to_world: &Transform<f32>) -> Mesh {
+    let mut indices = Vec::new();
+    let mut points = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for axis in 0..3 {
+        for sign in [-1.0f32, 1.0] {
+            // Tangents are picked so that n = u x v, which keeps the winding counter clockwise
+            // when looking at the face from the outside
+            let mut n = Vec3::zeros();
+            n[axis] = sign;
+            let mut u = Vec3::zeros();
+            u[(axis + 1) % 3] = 1.0;
+            let mut v = Vec3::zeros();
+            v[(axis + 2) % 3] = sign;
+
+            let first_vertex = points.len();
+            for (s, t) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                points.push(Point3::zeros() + n + u * (2.0 * s - 1.0) + v * (2.0 * t - 1.0));
+                normals.push(Normal::from(n));
+                uvs.push(Point2::new(s, t));
+            }
+            indices.extend([0, 1, 2, 2, 3, 0].iter().map(|i| first_vertex + i));
+        }
+    }
+
+    Mesh::new(object_to_world, indices, points, normals, uvs)
+}
+
+/// Creates the triangles of `mesh`, with lights if `area_radiance` is given.
+fn triangles(
+    mesh: Arc<Mesh>,
+    material: &Arc<dyn Material>,
+    area_radiance: Option<Spectrum<f32>>,
+) -> ShapeResult {
+    let mut lights: Vec<Arc<dyn Light>> = Vec::new();
+    let shapes = (0..mesh.indices.len())
+        .step_by(3)
+        .map(|v0| {
+            let light = area_radiance.map(|l| {
+                // Mitsuba's area emitters are one-sided
+                let light = Arc::new(DiffuseAreaLight::new(&mesh, v0, l, false));
+                lights.push(Arc::clone(&light) as Arc<dyn Light>);
+                light as Arc<dyn AreaLight>
+            });
+            Arc::new(Triangle::new(
+                Arc::clone(&mesh),
+                v0,
+                Arc::clone(material),
+                light,
+            )) as Arc<dyn Shape>
+        })
+        .collect();
+    ShapeResult {
+        mesh: Some(mesh),
+        shapes,
+        lights,
     }
 }
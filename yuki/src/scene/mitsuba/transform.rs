@@ -60,7 +60,7 @@ pub fn parse<T: std::io::Read>(
                         vec![v, v, v]
                     }
                     3 => p_strs.iter().map(|v| v.parse::<f32>().unwrap()).collect(),
-                    _ => unreachable!(),
+                    n => return Err(format!("Expected 1 or 3 scale values, got {}", n).into()),
                 };
                 transform = &scale(p[0], p[1], p[2]) * &transform;
             }
@@ -79,3 +79,41 @@ pub fn parse<T: std::io::Read>(
 
     Ok(transform)
 }
+
+#[cfg(test)]
+mod tests {
+    use xml::reader::{EventReader, XmlEvent};
+
+    use super::parse;
+    use crate::math::{Point3, Transform};
+
+    fn parse_str(xml: &str) -> Transform<f32> {
+        let mut parser = EventReader::new(xml.as_bytes());
+        while !matches!(parser.next().unwrap(), XmlEvent::StartElement { .. }) {}
+        parse(&mut parser, "  ".into()).unwrap()
+    }
+
+    #[test]
+    fn scale_scales() {
+        let transform = parse_str(
+            r#"<transform name="to_world">
+    <scale value="2 3 4"/>
+</transform>"#,
+        );
+        assert_eq!(
+            &transform * Point3::new(1.0, 1.0, 1.0),
+            Point3::new(2.0, 3.0, 4.0)
+        );
+
+        let transform = parse_str(
+            r#"<transform name="to_world">
+    <scale value="2"/>
+    <translate value="1 0 0"/>
+</transform>"#,
+        );
+        assert_eq!(
+            &transform * Point3::new(1.0, 1.0, 1.0),
+            Point3::new(3.0, 2.0, 2.0)
+        );
+    }
+}
@@ -1,31 +1,52 @@
+mod bvh_cache;
+mod gltf;
 mod mitsuba;
+mod obj;
 mod pbrt;
 mod ply;
 
+use bvh_cache::build_bvh;
+
 use crate::{
     bvh::{BoundingVolumeHierarchy, SplitMethod},
-    camera::{CameraParameters, FoV},
+    camera::{CameraParameters, CameraType, FoV},
     film::FilmSettings,
-    lights::{AreaLight, Light, PointLight, RectangularLight},
-    materials::{Glass, Material, Matte, Metal},
+    lights::{power_distribution, AreaLight, Light, PointLight, RectangularLight},
+    materials::{Glass, Material, Matte, Metal, MicrofacetDistributionType},
     math::{
         transforms::{scale, translation},
-        Point2, Point3, Spectrum, Transform, Vec2, Vec3,
+        Bounds3, Normal, Point2, Point3, Spectrum, Transform, TransformNode, Vec2, Vec3,
     },
-    shapes::{Mesh, Shape, Sphere, Triangle},
-    textures::{ConstantTexture, ImageTexture},
+    media::Medium,
+    sampling::Distribution1D,
+    shapes::{Mesh, Quad, Shape, Sphere, Triangle},
+    textures::{ConstantTexture, FilterMode, ImageTexture},
     yuki_info,
 };
 use ply::PlyResult;
 use serde::{Deserialize, Serialize};
 
-use std::{path::PathBuf, sync::Arc, time::Instant};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct SceneLoadSettings {
     pub path: PathBuf,
     pub split_method: SplitMethod,
     pub max_shapes_in_node: u16,
+    /// Moves the camera to frame the whole scene after loading
+    #[serde(default)]
+    pub auto_frame: bool,
+    /// Computes smooth normals for PLYs that don't have them, keeping edges sharper than this
+    /// angle in degrees hard. Geometric normals are used if `None`.
+    #[serde(default)]
+    pub ply_crease_angle: Option<f32>,
 }
 
 impl Default for SceneLoadSettings {
@@ -34,6 +55,8 @@ impl Default for SceneLoadSettings {
             path: PathBuf::new(),
             split_method: SplitMethod::SurfaceAreaHeuristic,
             max_shapes_in_node: 1,
+            auto_frame: false,
+            ply_crease_angle: None,
         }
     }
 }
@@ -45,26 +68,77 @@ pub struct Scene {
     pub shapes: Arc<Vec<Arc<dyn Shape>>>,
     pub bvh: BoundingVolumeHierarchy,
     pub lights: Vec<Arc<dyn Light>>,
+    /// Picks lights proportional to their power, `None` if there are no lights
+    pub light_distribution: Option<Distribution1D>,
     pub background: Spectrum<f32>,
+    /// Medium filling the space between the shapes, e.g. fog. Only the path integrator
+    /// renders it.
+    pub medium: Option<Arc<dyn Medium>>,
 }
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Returned by the loaders when their cancellation flag is set mid-load
+#[derive(Debug)]
+pub struct LoadCancelled;
+
+impl std::fmt::Display for LoadCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Loading was cancelled")
+    }
+}
+
+impl std::error::Error for LoadCancelled {}
+
 impl Scene {
+    /// Returns the bounds of all shapes in this `Scene`.
+    pub fn world_bound(&self) -> Bounds3<f32> {
+        self.bvh.bounds()
+    }
+
+    /// Picks one of the lights proportional to its power and returns it with the probability of
+    /// picking it, `None` if there are no lights.
+    pub fn sample_light(&self, u: f32) -> Option<(&dyn Light, f32)> {
+        self.light_distribution.as_ref().map(|d| {
+            let (index, pdf) = d.sample_discrete(u);
+            (self.lights[index].as_ref(), pdf)
+        })
+    }
+
+    /// Returns the probability of [`Scene::sample_light`] picking `light`, which has to be one
+    /// of the lights in this `Scene`.
+    pub fn light_pdf<L: Light + ?Sized>(&self, light: &L) -> f32 {
+        // Lights are weighted by power so this avoids searching for the index of the light
+        self.light_distribution.as_ref().map_or(0.0, |d| {
+            let power_sum = d.func_int() * (d.count() as f32);
+            if power_sum == 0.0 {
+                0.0
+            } else {
+                light.power(self.world_bound()).luminance() / power_sum
+            }
+        })
+    }
+
+    /// Returns the center and radius of a sphere that contains all shapes in this `Scene`.
+    pub fn bounding_sphere(&self) -> (Point3<f32>, f32) {
+        let bounds = self.world_bound();
+        bounds.bounding_sphere().unwrap_or((bounds.p_min, 0.0))
+    }
+
     /// Loads a pbrt-v3 scene
     ///
-    /// Also returns the time it took to load in seconds.
+    /// Also returns the time it took to load in seconds. Returns [`LoadCancelled`] if `cancel` is
+    /// set during the load.
     pub fn pbrt_v3(
         settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
     ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
         let load_start = Instant::now();
 
-        let (scene, dynamic_params, film_settings) = match pbrt::load(settings) {
+        let (scene, dynamic_params, film_settings) = match pbrt::load(settings, cancel) {
             Ok(ret) => ret,
-            Err(why) => {
-                // TODO: Proper error type?
-                return Err(format!("{:?}", why).into());
-            }
+            Err(pbrt::LoadError::Cancelled) => return Err(LoadCancelled.into()),
+            Err(why) => return Err(why.into()),
         };
 
         let total_secs = load_start.elapsed().as_secs_f32();
@@ -76,13 +150,15 @@ impl Scene {
 
     /// Loads a Mitsuba 2 scene
     ///
-    /// Also returns the time it took to load in seconds.
+    /// Also returns the time it took to load in seconds. Returns [`LoadCancelled`] if `cancel` is
+    /// set during the load.
     pub fn mitsuba(
         settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
     ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
         let load_start = Instant::now();
 
-        let (scene, dynamic_params, film_settings) = mitsuba::load(settings)?;
+        let (scene, dynamic_params, film_settings) = mitsuba::load(settings, cancel)?;
 
         let total_secs = load_start.elapsed().as_secs_f32();
 
@@ -95,38 +171,88 @@ impl Scene {
     /// Loads a PLY, scales it to fit 2 units around the origin and orients the camera
     /// on it at an angle.
     ///
-    /// Also returns the time it took to load in seconds.
+    /// Also returns the time it took to load in seconds. Returns [`LoadCancelled`] if `cancel` is
+    /// set during the load.
     pub fn ply(
         settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
+    ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
+        Self::single_mesh(settings, cancel, "PLY", &|path, material, transform| {
+            ply::load(path, material, transform, settings.ply_crease_angle)
+        })
+    }
+
+    ///
+    /// Loads a Wavefront OBJ, scales it to fit 2 units around the origin and orients the camera
+    /// on it at an angle.
+    ///
+    /// Also returns the time it took to load in seconds. Returns [`LoadCancelled`] if `cancel` is
+    /// set during the load.
+    pub fn obj(
+        settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
+    ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
+        Self::single_mesh(settings, cancel, "OBJ", &obj::load)
+    }
+
+    ///
+    /// Loads the mesh of a glTF 2.0 `.gltf` or `.glb`, scales it to fit 2 units around the origin
+    /// and orients the camera on it at an angle.
+    ///
+    /// Also returns the time it took to load in seconds. Returns [`LoadCancelled`] if `cancel` is
+    /// set during the load.
+    pub fn gltf(
+        settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
+    ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
+        Self::single_mesh(settings, cancel, "glTF", &gltf::load)
+    }
+
+    fn single_mesh(
+        settings: &SceneLoadSettings,
+        cancel: &AtomicBool,
+        format_name: &str,
+        load: &dyn Fn(
+            &std::path::Path,
+            &Arc<dyn Material>,
+            Option<Transform<f32>>,
+        ) -> Result<PlyResult>,
     ) -> Result<(Scene, CameraParameters, FilmSettings, f32)> {
         let load_start = Instant::now();
 
         let white = Arc::new(Matte::new(
             Arc::new(ConstantTexture::new(Spectrum::ones())),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         )) as Arc<dyn Material>;
-        let PlyResult { mesh, shapes } = ply::load(&settings.path, &white, None)?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err(LoadCancelled.into());
+        }
+        let PlyResult { mesh, shapes } = load(&settings.path, &white, None)?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err(LoadCancelled.into());
+        }
 
         let meshes = vec![mesh];
 
-        let (bvh, shapes) = BoundingVolumeHierarchy::new(
-            shapes,
-            settings.max_shapes_in_node as usize,
-            settings.split_method,
-        );
+        let (bvh, shapes) = build_bvh(settings, shapes);
 
         let light = Arc::new(PointLight::new(
             &translation(Vec3::new(5.0, 5.0, 0.0)),
             Spectrum::ones() * 600.0,
         ));
 
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
         let cam_pos = Point3::new(2.0, 2.0, 2.0);
         let cam_target = Point3::new(0.0, 0.0, 0.0);
         let cam_fov = FoV::X(40.0);
 
         let total_secs = load_start.elapsed().as_secs_f32();
 
-        yuki_info!("PLY: Loading took {:.2}s in total", total_secs);
+        yuki_info!("{}: Loading took {:.2}s in total", format_name, total_secs);
 
         Ok((
             Self {
@@ -135,13 +261,15 @@ impl Scene {
                 meshes,
                 shapes,
                 bvh,
-                lights: vec![light],
+                lights,
+                light_distribution,
                 background: Spectrum::zeros(),
+                medium: None,
             },
             CameraParameters {
                 position: cam_pos,
                 target: cam_target,
-                fov: cam_fov,
+                camera_type: CameraType::Perspective { fov: cam_fov },
                 ..CameraParameters::default()
             },
             FilmSettings::default(),
@@ -189,27 +317,38 @@ impl Scene {
         let white = Arc::new(Matte::new(
             Arc::new(ConstantTexture::new(Spectrum::ones() * 180.0 / 255.0)),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         ));
         let image = Arc::new(Matte::new(
             Arc::new(
                 ImageTexture::from_image_bytes(std::include_bytes!(
                     "../../../res/tiling_58-1K/tiling_58_basecolor-1K.png"
                 ))
-                .unwrap(),
+                .unwrap()
+                .with_filter_mode(FilterMode::Bilinear),
             ),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         ));
         let red = Arc::new(Matte::new(
             Arc::new(ConstantTexture::new(Spectrum::new(180.0, 0.0, 0.0) / 255.0)),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         ));
         let green = Arc::new(Matte::new(
             Arc::new(ConstantTexture::new(Spectrum::new(0.0, 180.0, 0.0) / 255.0)),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         ));
         let blackbody = Arc::new(Matte::new(
             Arc::new(ConstantTexture::new(Spectrum::zeros())),
             Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
         ));
         let copper = Arc::new(Metal::new(
             Arc::new(ConstantTexture::new(Spectrum::new(
@@ -220,11 +359,15 @@ impl Scene {
             ))),
             Arc::new(ConstantTexture::new(0.01)),
             true,
+            MicrofacetDistributionType::TrowbridgeReitz,
         ));
         let glass = Arc::new(Glass::new(
             Arc::new(ConstantTexture::new(Spectrum::ones())),
             Arc::new(ConstantTexture::new(Spectrum::ones())),
             1.5,
+            Arc::new(ConstantTexture::new(0.0)),
+            false,
+            MicrofacetDistributionType::TrowbridgeReitz,
         ));
 
         let light = {
@@ -242,30 +385,16 @@ impl Scene {
         let mut meshes: Vec<Arc<Mesh>> = Vec::new();
         let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
 
-        // Light
-        {
-            let mesh = Arc::new(Mesh::new(
-                &handedness_swap_and_into_meters,
-                vec![0, 1, 2, 0, 2, 3],
-                vec![
-                    Point3::new(LIGHT_RIGHT, HOLE_TOP, LIGHT_FRONT),
-                    Point3::new(LIGHT_LEFT, HOLE_TOP, LIGHT_FRONT),
-                    Point3::new(LIGHT_LEFT, HOLE_TOP, LIGHT_BACK),
-                    Point3::new(LIGHT_RIGHT, HOLE_TOP, LIGHT_BACK),
-                ],
-                Vec::new(),
-                Vec::new(),
-            ));
-            for v0 in (0..mesh.indices.len()).step_by(3) {
-                shapes.push(Arc::new(Triangle::new(
-                    Arc::clone(&mesh),
-                    v0,
-                    Arc::clone(&blackbody) as Arc<dyn Material>,
-                    Some(Arc::clone(&light) as Arc<dyn AreaLight>),
-                )));
-            }
-            meshes.push(mesh);
-        }
+        // Light, a single quad so that it matches the light's sampled rectangle exactly
+        shapes.push(Arc::new(Quad::new(
+            Point3::new(LIGHT_RIGHT, HOLE_TOP, -LIGHT_BACK) / 1000.0,
+            [
+                Vec3::new(LIGHT_WH, 0.0, 0.0) / 1000.0,
+                Vec3::new(0.0, 0.0, LIGHT_WH) / 1000.0,
+            ],
+            Arc::clone(&blackbody) as Arc<dyn Material>,
+            Some(Arc::clone(&light) as Arc<dyn AreaLight>),
+        )));
 
         // Walls
         {
@@ -463,13 +592,14 @@ impl Scene {
 
         // Tall box
         {
-            let mesh = Arc::new(Mesh::new(
-                &handedness_swap_and_into_meters,
-                vec![
+            // Grouped so that the whole box can be moved by changing the group transform
+            let mut tall_box = TransformNode::new(Transform::default());
+            tall_box.add_item(MeshData {
+                indices: vec![
                     0, 1, 2, 0, 2, 3, 4, 0, 3, 4, 3, 5, 5, 3, 2, 5, 2, 6, 6, 2, 1, 6, 1, 7, 7, 1,
                     0, 7, 0, 4,
                 ],
-                vec![
+                points: vec![
                     Point3::new(423.0, 330.0, 247.0),
                     Point3::new(265.0, 330.0, 296.0),
                     Point3::new(314.0, 330.0, 456.0),
@@ -479,19 +609,15 @@ impl Scene {
                     Point3::new(314.0, 0.0, 456.0),
                     Point3::new(265.0, 0.0, 296.0),
                 ],
-                Vec::new(),
-                Vec::new(),
-            ));
-
-            for v0 in (0..mesh.indices.len()).step_by(3) {
-                shapes.push(Arc::new(Triangle::new(
-                    Arc::clone(&mesh),
-                    v0,
-                    Arc::<Glass>::clone(&glass),
-                    None,
-                )));
-            }
-            meshes.push(mesh);
+                normals: Vec::new(),
+                uvs: Vec::new(),
+                material: glass,
+            });
+
+            let mut root = TransformNode::new(handedness_swap_and_into_meters);
+            root.add_child(tall_box);
+
+            flatten_meshes(root, &mut meshes, &mut shapes);
         }
 
         shapes.push(Arc::new(Sphere::new(
@@ -502,6 +628,9 @@ impl Scene {
 
         let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
 
+        let lights = vec![light as Arc<dyn Light>];
+        let light_distribution = power_distribution(&lights, bvh.bounds());
+
         let cam_pos = Point3::new(0.278, 0.273, 0.800);
         let cam_target = Point3::new(0.278, 0.273, -0.260);
         let cam_fov = FoV::X(40.0);
@@ -515,13 +644,15 @@ impl Scene {
                 meshes,
                 shapes,
                 bvh,
-                lights: vec![light],
+                lights,
+                light_distribution,
                 background: Spectrum::zeros(),
+                medium: None,
             }),
             CameraParameters {
                 position: cam_pos,
                 target: cam_target,
-                fov: cam_fov,
+                camera_type: CameraType::Perspective { fov: cam_fov },
                 ..CameraParameters::default()
             },
             FilmSettings::default(),
@@ -529,3 +660,38 @@ impl Scene {
         )
     }
 }
+
+/// Object space mesh data that gets placed into the world by a [TransformNode]
+struct MeshData {
+    indices: Vec<usize>,
+    points: Vec<Point3<f32>>,
+    normals: Vec<Normal<f32>>,
+    uvs: Vec<Point2<f32>>,
+    material: Arc<dyn Material>,
+}
+
+/// Flattens the mesh hierarchy in `root`, baking the accumulated transforms into the meshes.
+fn flatten_meshes(
+    root: TransformNode<f32, MeshData>,
+    meshes: &mut Vec<Arc<Mesh>>,
+    shapes: &mut Vec<Arc<dyn Shape>>,
+) {
+    for (object_to_world, data) in root.flatten() {
+        let mesh = Arc::new(Mesh::new(
+            &object_to_world,
+            data.indices,
+            data.points,
+            data.normals,
+            data.uvs,
+        ));
+        for v0 in (0..mesh.indices.len()).step_by(3) {
+            shapes.push(Arc::new(Triangle::new(
+                Arc::clone(&mesh),
+                v0,
+                Arc::clone(&data.material),
+                None,
+            )));
+        }
+        meshes.push(mesh);
+    }
+}
@@ -0,0 +1,291 @@
+use super::{
+    ply::{triangle_mesh, triangulate, PlyResult, CREASE_ANGLE_DEGREES},
+    Result,
+};
+use crate::{
+    materials::Material,
+    math::{Normal, Point2, Point3, Transform},
+    yuki_error, yuki_info, yuki_trace,
+};
+
+use std::{collections::HashMap, io::BufRead, path::Path, sync::Arc, time::Instant};
+
+// Based on
+// http://paulbourke.net/dataformats/obj/
+
+/// Loads the geometry of a Wavefront OBJ as a single mesh, ignoring groups and materials.
+pub fn load(
+    path: &Path,
+    material: &Arc<dyn Material>,
+    transform: Option<Transform<f32>>,
+) -> Result<PlyResult> {
+    let file = match std::fs::File::open(path.to_str().unwrap()) {
+        Ok(f) => f,
+        Err(e) => {
+            yuki_error!("Could not open '{}'", path.to_string_lossy());
+            return Err(e.into());
+        }
+    };
+
+    let parse_start = Instant::now();
+    let ObjGeometry {
+        indices,
+        points,
+        normals,
+        uvs,
+    } = parse(std::io::BufReader::new(file))?;
+    yuki_trace!(
+        "OBJ: Parsed {} vertices and {} triangles in {:.2}s",
+        points.len(),
+        indices.len() / 3,
+        parse_start.elapsed().as_secs_f32()
+    );
+
+    Ok(triangle_mesh(
+        "OBJ",
+        indices,
+        points,
+        normals,
+        uvs,
+        material,
+        transform,
+        Some(CREASE_ANGLE_DEGREES),
+    ))
+}
+
+/// Triangulated geometry with the OBJ attribute indices unified into single vertex indices
+struct ObjGeometry {
+    indices: Vec<usize>,
+    points: Vec<Point3<f32>>,
+    normals: Vec<Normal<f32>>,
+    uvs: Vec<Point2<f32>>,
+}
+
+/// Attribute indices of a face vertex, as `(point, uv, normal)`
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+fn parse<R: BufRead>(reader: R) -> Result<ObjGeometry> {
+    let mut file_points = Vec::new();
+    let mut file_normals = Vec::new();
+    let mut file_uvs = Vec::new();
+
+    let mut vertices: HashMap<VertexKey, usize> = HashMap::new();
+    let mut vertex_keys: Vec<VertexKey> = Vec::new();
+    let mut indices = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let mut tokens = line.split_whitespace();
+
+        let parse_floats = |tokens: std::str::SplitWhitespace, count: usize| -> Result<Vec<f32>> {
+            let values = tokens
+                .take(count)
+                .map(str::parse::<f32>)
+                .collect::<std::result::Result<Vec<f32>, _>>()?;
+            if values.len() < count {
+                return Err(
+                    format!("OBJ: Expected {} values on line {}", count, line_number).into(),
+                );
+            }
+            Ok(values)
+        };
+
+        match tokens.next() {
+            Some("v") => {
+                let v = parse_floats(tokens, 3)?;
+                file_points.push(Point3::new(v[0], v[1], v[2]));
+            }
+            Some("vn") => {
+                let v = parse_floats(tokens, 3)?;
+                file_normals.push(Normal::new(v[0], v[1], v[2]));
+            }
+            Some("vt") => {
+                let v = parse_floats(tokens, 2)?;
+                file_uvs.push(Point2::new(v[0], v[1]));
+            }
+            Some("f") => {
+                let mut face = Vec::new();
+                for token in tokens {
+                    let mut attributes = token.split('/');
+                    let point = attributes.next().unwrap_or("");
+                    let uv = attributes.next().unwrap_or("");
+                    let normal = attributes.next().unwrap_or("");
+
+                    let key = (
+                        resolve_index(point, file_points.len(), line_number)?.ok_or_else(|| {
+                            format!("OBJ: Missing point index on line {}", line_number)
+                        })?,
+                        resolve_index(uv, file_uvs.len(), line_number)?,
+                        resolve_index(normal, file_normals.len(), line_number)?,
+                    );
+
+                    let vertex = *vertices.entry(key).or_insert_with(|| {
+                        vertex_keys.push(key);
+                        vertex_keys.len() - 1
+                    });
+                    face.push(vertex);
+                }
+                if face.len() < 3 {
+                    return Err(format!("OBJ: Degenerate face on line {}", line_number).into());
+                }
+                triangulate(&face, &mut indices);
+            }
+            // Comments and empty lines
+            Some(t) if t.starts_with('#') => (),
+            None => (),
+            // Grouping and materials don't affect the geometry
+            Some("o" | "g" | "s" | "mtllib" | "usemtl") => (),
+            Some(t) => yuki_trace!("OBJ: Ignoring '{}' on line {}", t, line_number),
+        }
+    }
+
+    // Mesh needs either all or none of the vertices to have normals and uvs
+    let has_normals = vertex_keys.iter().all(|&(_, _, n)| n.is_some());
+    let has_uvs = vertex_keys.iter().all(|&(_, uv, _)| uv.is_some());
+    if !has_normals && vertex_keys.iter().any(|&(_, _, n)| n.is_some()) {
+        yuki_info!("OBJ: Some face vertices are missing normals, computing them instead");
+    }
+    if !has_uvs && vertex_keys.iter().any(|&(_, uv, _)| uv.is_some()) {
+        yuki_info!("OBJ: Some face vertices are missing uvs, ignoring uvs");
+    }
+
+    let points = vertex_keys
+        .iter()
+        .map(|&(p, _, _)| file_points[p])
+        .collect();
+    let normals = if has_normals {
+        vertex_keys
+            .iter()
+            .map(|&(_, _, n)| file_normals[n.unwrap()])
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let uvs = if has_uvs {
+        vertex_keys
+            .iter()
+            .map(|&(_, uv, _)| file_uvs[uv.unwrap()])
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(ObjGeometry {
+        indices,
+        points,
+        normals,
+        uvs,
+    })
+}
+
+/// Converts the one-based, possibly negative relative OBJ `index` into an index into the
+/// `count` attributes parsed so far. Returns `None` for an empty index.
+fn resolve_index(index: &str, count: usize, line_number: usize) -> Result<Option<usize>> {
+    if index.is_empty() {
+        return Ok(None);
+    }
+
+    let i = index.parse::<i64>()?;
+    let resolved = match i {
+        i if i > 0 => usize::try_from(i - 1).ok(),
+        i if i < 0 => usize::try_from(i.unsigned_abs())
+            .ok()
+            .and_then(|back| count.checked_sub(back)),
+        _ => None,
+    };
+
+    match resolved {
+        Some(i) if i < count => Ok(Some(i)),
+        _ => Err(format!("OBJ: Invalid index '{}' on line {}", index, line_number).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    const CUBE: &str = "
+# Unit cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 2 3 7 6
+f 3 4 8 7
+f 4 1 5 8
+";
+
+    #[test]
+    fn parses_cube_with_relative_indices() {
+        let cube = parse(CUBE.as_bytes()).unwrap();
+        assert_eq!(cube.points.len(), 8);
+        assert_eq!(cube.indices.len(), 6 * 2 * 3);
+        assert!(cube.normals.is_empty());
+        assert!(cube.uvs.is_empty());
+        assert_eq!(&cube.indices[0..6], &[0, 1, 2, 0, 2, 3]);
+
+        // The same cube with the faces referring to the points relative to the end
+        let relative: String = CUBE
+            .lines()
+            .map(|l| {
+                if let Some(face) = l.strip_prefix("f ") {
+                    let indices: Vec<String> = face
+                        .split_whitespace()
+                        .map(|i| (i.parse::<i32>().unwrap() - 9).to_string())
+                        .collect();
+                    format!("f {}\n", indices.join(" "))
+                } else {
+                    format!("{}\n", l)
+                }
+            })
+            .collect();
+        let relative_cube = parse(relative.as_bytes()).unwrap();
+        assert_eq!(cube.indices, relative_cube.indices);
+        assert_eq!(cube.points, relative_cube.points);
+    }
+
+    #[test]
+    fn unifies_attribute_indices() {
+        let quad = parse(
+            "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+f 1/1/1 3/3/1 -1/-1/-1
+f 1/3/1 2/2/1 3/3/1
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        // Shared corners are reused but the same point with a different uv is a new vertex
+        assert_eq!(quad.points.len(), 5);
+        assert_eq!(quad.indices, vec![0, 1, 2, 0, 2, 3, 4, 1, 2]);
+        assert_eq!(quad.normals.len(), 5);
+        assert_eq!(quad.uvs.len(), 5);
+        assert!((quad.uvs[4].x - 1.0).abs() < f32::EPSILON);
+
+        // Partial normals are dropped instead of producing mismatched attributes
+        let partial =
+            parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nvn 0 0 1\nf 1//1 2 3\n".as_bytes()).unwrap();
+        assert!(partial.normals.is_empty());
+        assert!(partial.uvs.is_empty());
+
+        assert!(parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n".as_bytes()).is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 -4\n".as_bytes()).is_err());
+    }
+}
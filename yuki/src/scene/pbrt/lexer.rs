@@ -1,3 +1,4 @@
+use std::io::BufRead;
 use strum::Display;
 
 // Based on the rules laid out in the old pbrtlex.ll of pbrt-v3
@@ -6,6 +7,8 @@ use strum::Display;
 pub struct LexerError {
     pub error_type: LexerErrorType,
     pub location: FileLocation,
+    /// Text of the line the error is on, if it is known
+    pub source_line: Option<String>,
 }
 
 impl LexerError {
@@ -13,6 +16,7 @@ impl LexerError {
         Self {
             error_type,
             location,
+            source_line: None,
         }
     }
 }
@@ -24,18 +28,40 @@ pub enum LexerErrorType {
     UnterminatedString,
     InvalidNumber,
     UnknownIdentifier(String),
+    Io(String),
 }
 
 impl std::fmt::Display for LexerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let type_str = match &self.error_type {
             LexerErrorType::UnknownIdentifier(ident) => format!("UnknownIdentifier '{}'", ident),
+            LexerErrorType::Io(why) => format!("Io '{}'", why),
             _ => self.error_type.to_string(),
         };
-        write!(f, "{}: {}", self.location, type_str)
+        write!(f, "{}: {}", self.location, type_str)?;
+        if let Some(line) = &self.source_line {
+            write_source_line(f, line, self.location.column)?;
+        }
+        Ok(())
     }
 }
 
+/// Writes `line` on its own row followed by a caret under `column`.
+pub fn write_source_line(
+    f: &mut std::fmt::Formatter<'_>,
+    line: &str,
+    column: usize,
+) -> std::fmt::Result {
+    // Match tabs so that the caret lines up regardless of tab width
+    let indent: String = line
+        .chars()
+        .take(column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    write!(f, "\n    {}\n    {}^", line, indent)
+}
+
+/// One-based line and zero-based column in a file
 #[derive(Copy, Clone, Debug)]
 pub struct FileLocation {
     pub line: usize,
@@ -43,23 +69,36 @@ pub struct FileLocation {
 }
 
 impl std::fmt::Display for FileLocation {
+    /// Formats as `line:column`, both one-based like in most editors
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "line {}, column {}", self.line, self.column)
+        write!(f, "{}:{}", self.line, self.column + 1)
     }
 }
 
-pub struct Lexer {
-    input: Vec<u8>,
-    position: usize,
+/// Tokenizes pbrt-v3 input, pulling bytes from the reader only as they are needed
+pub struct Lexer<R: BufRead> {
+    reader: R,
+    // Byte that was moved back into the input
+    pending: Option<u8>,
+    // Bytes of the token being collected
+    token: Vec<u8>,
+    io_error: Option<std::io::Error>,
+    // Bytes read so far on the current line and the whole previous line, for error messages
+    line_text: Vec<u8>,
+    previous_line_text: Vec<u8>,
     file_location: FileLocation,
     previous_token_location: FileLocation,
 }
 
-impl Lexer {
-    pub fn new(input: Vec<u8>) -> Self {
+impl<R: BufRead> Lexer<R> {
+    pub fn new(reader: R) -> Self {
         Self {
-            input,
-            position: 0,
+            reader,
+            pending: None,
+            token: Vec::new(),
+            io_error: None,
+            line_text: Vec::new(),
+            previous_line_text: Vec::new(),
             file_location: FileLocation { line: 1, column: 0 },
             previous_token_location: FileLocation { line: 1, column: 0 },
         }
@@ -70,13 +109,57 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> Result<Token, LexerError> {
+        let ret = self.lex_token();
+        // Read errors look like the end of input to the lexing itself
+        match self.io_error.take() {
+            Some(why) => Err(LexerError::new(
+                LexerErrorType::Io(why.to_string()),
+                self.file_location,
+            )),
+            None => ret.map_err(|mut why| {
+                if !matches!(why.error_type, LexerErrorType::EndOfInput) {
+                    why.source_line = self.source_line(why.location);
+                }
+                why
+            }),
+        }
+    }
+
+    /// Returns the text of the line at `location` if it is the current or the previous line.
+    ///
+    /// Reads the rest of the current line from the input so lexing shouldn't be continued after
+    /// calling this.
+    pub fn source_line(&mut self, location: FileLocation) -> Option<String> {
+        let text = if location.line + 1 == self.file_location.line {
+            &self.previous_line_text
+        } else if location.line == self.file_location.line {
+            loop {
+                match self.get_char() {
+                    Some(b'\n') => break &self.previous_line_text,
+                    Some(_) => (),
+                    None => break &self.line_text,
+                }
+            }
+        } else {
+            return None;
+        };
+        self.io_error = None;
+
+        Some(
+            String::from_utf8_lossy(text)
+                .trim_end_matches('\r')
+                .to_owned(),
+        )
+    }
+
+    fn lex_token(&mut self) -> Result<Token, LexerError> {
         // Seek past any whitespace
         loop {
             match self.get_char() {
                 Some(c) => match c {
-                    ' ' | '\t' | '\n' | '\r' => (),
+                    b' ' | b'\t' | b'\n' | b'\r' => (),
                     _ => {
-                        self.unget_char();
+                        self.unget_char(c);
                         break;
                     }
                 },
@@ -93,15 +176,16 @@ impl Lexer {
         }
 
         // Collect the token
-        let mut identifier_start_position = None;
+        let mut in_identifier = false;
+        self.token.clear();
         self.previous_token_location = self.file_location;
         loop {
             match self.get_char() {
                 Some(c) => match c {
-                    '#' => {
+                    b'#' => {
                         'comment: loop {
                             match self.get_char() {
-                                Some('\n' | '\r') => {
+                                Some(b'\n' | b'\r') => {
                                     break 'comment;
                                 }
                                 None => {
@@ -113,25 +197,20 @@ impl Lexer {
                                 _ => (),
                             }
                         }
-                        return self.next_token();
+                        return self.lex_token();
                     }
-                    '"' => {
-                        let start_position = self.position;
+                    b'"' => {
+                        self.mark_token_start();
                         loop {
                             match self.get_char() {
                                 Some(c) => match c {
-                                    '"' => {
+                                    b'"' => {
                                         return Ok(Token::String(
-                                            unsafe {
-                                                std::str::from_utf8_unchecked(
-                                                    &self.input[start_position..self.position - 1],
-                                                )
-                                            }
-                                            .into(),
+                                            String::from_utf8_lossy(&self.token).into_owned(),
                                         ));
                                     }
-                                    '\\' => match self.get_char() {
-                                        Some(_) => (),
+                                    b'\\' => match self.get_char() {
+                                        Some(escaped) => self.token.extend([c, escaped]),
                                         None => {
                                             return Err(LexerError::new(
                                                 LexerErrorType::UnexpectedEndOfInput,
@@ -142,7 +221,7 @@ impl Lexer {
                                             ));
                                         }
                                     },
-                                    '\n' => {
+                                    b'\n' => {
                                         return Err(LexerError::new(
                                             LexerErrorType::UnterminatedString,
                                             FileLocation {
@@ -151,7 +230,7 @@ impl Lexer {
                                             },
                                         ))
                                     }
-                                    _ => (),
+                                    _ => self.token.push(c),
                                 },
                                 None => {
                                     return Err(LexerError::new(
@@ -165,19 +244,18 @@ impl Lexer {
                             }
                         }
                     }
-                    '[' => {
+                    b'[' => {
+                        self.mark_token_start();
                         return Ok(Token::LeftBracket);
                     }
-                    ' ' | '\t' | '\r' | '\n' | ']' => {
-                        if let Some(position) = identifier_start_position.take() {
-                            let end_position = self.position - 1;
-
+                    b' ' | b'\t' | b'\r' | b'\n' | b']' => {
+                        if in_identifier {
                             // We need to return right bracket as a the next token if encountered
-                            if c == ']' {
-                                self.position -= 1;
+                            if c == b']' {
+                                self.unget_char(c);
                             }
 
-                            return Token::try_from(&self.input[position..end_position]).map_err(
+                            return Token::try_from(self.token.as_slice()).map_err(
                                 |err| match err {
                                     TokenError::InvalidNumber => LexerError::new(
                                         LexerErrorType::InvalidNumber,
@@ -189,18 +267,17 @@ impl Lexer {
                                     ),
                                 },
                             );
-                        } else if c == ']' {
+                        } else if c == b']' {
+                            self.mark_token_start();
                             return Ok(Token::RightBracket);
                         }
                     }
                     _ => {
-                        if identifier_start_position.is_none() {
-                            identifier_start_position = Some(self.position - 1);
-                            self.previous_token_location = FileLocation {
-                                line: self.file_location.line,
-                                column: self.file_location.column - 1,
-                            }
+                        if !in_identifier {
+                            in_identifier = true;
+                            self.mark_token_start();
                         }
+                        self.token.push(c);
                     }
                 },
                 None => {
@@ -216,36 +293,57 @@ impl Lexer {
         }
     }
 
-    fn get_char(&mut self) -> Option<char> {
-        if self.position < self.input.len() {
-            let c = self.input[self.position] as char;
-            self.position += 1;
+    /// Marks the previous character as the start of the current token.
+    fn mark_token_start(&mut self) {
+        self.previous_token_location = FileLocation {
+            line: self.file_location.line,
+            column: self.file_location.column - 1,
+        };
+    }
 
-            if c == '\n' {
-                self.file_location.line += 1;
-                self.file_location.column = 0;
-            } else {
-                self.file_location.column += 1;
+    fn get_char(&mut self) -> Option<u8> {
+        let c = match self.pending.take() {
+            Some(c) => c,
+            None => {
+                let c = match self.reader.fill_buf() {
+                    Ok(buf) => *buf.first()?,
+                    Err(why) => {
+                        self.io_error = Some(why);
+                        return None;
+                    }
+                };
+                self.reader.consume(1);
+                c
             }
+        };
 
-            Some(c)
+        if c == b'\n' {
+            self.file_location.line += 1;
+            self.file_location.column = 0;
+            std::mem::swap(&mut self.line_text, &mut self.previous_line_text);
+            self.line_text.clear();
         } else {
-            None
+            self.file_location.column += 1;
+            self.line_text.push(c);
         }
+
+        Some(c)
     }
 
-    fn unget_char(&mut self) {
+    fn unget_char(&mut self, c: u8) {
         debug_assert!(
             self.file_location.column > 0,
             "Tried to move lexer back past a newline"
         );
+        debug_assert!(self.pending.is_none(), "Tried to move lexer back twice");
 
-        self.position -= 1;
+        self.pending = Some(c);
         self.file_location.column -= 1;
+        self.line_text.pop();
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Token {
     Number(f64),
     // TODO: Check how much this being owned affects perf.
@@ -359,3 +457,63 @@ impl<'a> TryFrom<&'a [u8]> for Token {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+    };
+
+    use super::{Lexer, LexerErrorType, Token};
+
+    #[test]
+    fn file_reader_matches_in_memory_input() {
+        let input = "# Comment\nLookAt 0 1 -2.5 0 0 0 0 1 0\nShape \"trianglemesh\"\n  \
+                     \"point P\" [-1 0 0 1 0 0 0 1 0]\n  \"string name\" [\"esc\\\"aped\"]\n\
+                     WorldBegin\nWorldEnd\n";
+
+        let path = std::env::temp_dir().join(format!("yuki_lexer_{}.pbrt", std::process::id()));
+        std::fs::write(&path, input).unwrap();
+        // Tiny buffer to have tokens span multiple reads
+        let file_tokens = lex_all(BufReader::with_capacity(4, File::open(&path).unwrap()));
+        std::fs::remove_file(&path).unwrap();
+
+        let memory_tokens = lex_all(input.as_bytes());
+        assert_eq!(memory_tokens.len(), 30);
+        assert_eq!(file_tokens.len(), memory_tokens.len());
+        for ((file_token, file_location), (memory_token, memory_location)) in
+            file_tokens.iter().zip(memory_tokens.iter())
+        {
+            assert_eq!(file_token, memory_token);
+            assert_eq!(file_location, memory_location);
+        }
+        assert_eq!(memory_tokens[0].0, Token::LookAt);
+        assert_eq!(memory_tokens[0].1, (2, 0));
+        assert_eq!(memory_tokens[3].0, Token::Number(-2.5));
+        assert_eq!(memory_tokens[26].0, Token::String("esc\\\"aped".into()));
+    }
+
+    /// Returns all tokens in `reader` with their lines and columns.
+    fn lex_all(reader: impl BufRead) -> Vec<(Token, (usize, usize))> {
+        let mut lexer = Lexer::new(reader);
+        let mut tokens = Vec::new();
+        loop {
+            match lexer.next_token() {
+                Ok(token) => {
+                    let location = lexer.previous_token_location();
+                    tokens.push((token, (location.line, location.column)));
+                }
+                Err(why) => {
+                    assert!(
+                        matches!(why.error_type, LexerErrorType::EndOfInput),
+                        "{}",
+                        why
+                    );
+                    break;
+                }
+            }
+        }
+        tokens
+    }
+}
@@ -3,24 +3,38 @@ mod lexer;
 mod param_set;
 
 use cie::{x_fit_1931, y_fit_1931, z_fit_1931};
-use lexer::{FileLocation, Lexer, LexerError, LexerErrorType, Token};
+use lexer::{write_source_line, FileLocation, Lexer, LexerError, LexerErrorType, Token};
 use param_set::ParamSet;
 use rayon::prelude::*;
 
 use crate::{
     bvh::BoundingVolumeHierarchy,
-    camera::FoV,
+    camera::{CameraType, FoV},
     film::FilmSettings,
-    lights::{DistantLight, Light, PointLight},
-    materials::{Glass, Glossy, Material, Matte, Metal},
+    filters::FilterType,
+    lights::{
+        power_distribution, AreaLight, DiffuseAreaLight, DistantLight, GoniometricLight,
+        IesProfile, InfiniteLight, Light, PhotometricLight, PointLight, SpotLight,
+    },
+    materials::{
+        ConductorSpectra, Glass, Glossy, Material, Matte, Metal, MicrofacetDistributionType,
+        Plastic, Substrate, Translucent, Uber,
+    },
     math::{
-        transforms::{rotation, scale, translation},
-        Normal, Point2, Point3, Spectrum, Transform, Vec3,
+        transforms::{look_at, rotation, scale, translation},
+        AnimatedTransform, Normal, Point2, Point3, Spectrum, Transform, Vec2, Vec3,
+    },
+    media::{HomogeneousMedium, Medium},
+    scene::{bvh_cache::build_bvh, ply, CameraParameters, Scene, SceneLoadSettings},
+    shapes::{
+        heightfield_mesh, loop_subdivision_mesh, AnimatedShape, BilinearPatch, Cylinder, Disk,
+        Instance, Mesh, Shape, Sphere, Triangle,
     },
-    scene::{ply, CameraParameters, Scene, SceneLoadSettings},
-    shapes::{Mesh, Shape, Sphere, Triangle},
-    textures::{ConstantTexture, ImageTexture, Texture},
-    yuki_error, yuki_info,
+    textures::{
+        CheckerboardTexture, ConstantTexture, FilterMode, ImageTexture, MixTexture,
+        NormalMapTexture, ScaleTexture, Texture, UvTransform, WrapMode,
+    },
+    yuki_error, yuki_info, yuki_warn,
 };
 
 use bitflags::bitflags;
@@ -29,8 +43,11 @@ use std::{
     collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Instant,
 };
 
@@ -41,17 +58,41 @@ use std::{
 struct RenderOptions {
     camera_params: CameraParameters,
     film_settings: FilmSettings,
+    screen_window: Option<[f32; 4]>,
+    /// Times of the start and end transforms, `[0, 1]` if not given
+    transform_times: Option<[f32; 2]>,
+    /// Name of the medium the camera is in
+    camera_medium: Option<String>,
 }
 
 #[derive(Clone)]
 struct GraphicsState {
     material: Arc<dyn Material>,
+    /// Emission of the shapes that follow, if any
+    area_light: Option<AreaLightParams>,
+    /// Name of the medium outside the shapes that follow, if any
+    outside_medium: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct AreaLightParams {
+    l: Spectrum<f32>,
+    two_sided: bool,
 }
 
 impl Default for GraphicsState {
     fn default() -> Self {
         Self {
-            material: get_material("matte", &ParamSet::default(), &HashMap::default()).unwrap(),
+            material: get_material(
+                "matte",
+                &ParamSet::default(),
+                &HashMap::default(),
+                &HashMap::default(),
+                Path::new(""),
+            )
+            .unwrap(),
+            area_light: None,
+            outside_medium: None,
         }
     }
 }
@@ -59,21 +100,61 @@ impl Default for GraphicsState {
 #[derive(Debug)]
 pub enum LoadError {
     Io(std::io::Error),
-    Lexer(LexerError),
+    /// Error in the lexer and the file it happened in
+    Lexer(String, LexerError),
     Parser(ParserError),
     Content(String),
     Image(crate::textures::LoadError),
+    Ies(crate::lights::IesLoadError),
     Ply(String),
     Path(String),
+    Cancelled,
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(why) => write!(f, "{}", why),
+            LoadError::Lexer(file, why) => write!(f, "{}:{}", file, why),
+            LoadError::Parser(why) => write!(f, "{}", why),
+            LoadError::Image(why) => write!(f, "Image: {:?}", why),
+            LoadError::Ies(why) => write!(f, "IES: {:?}", why),
+            LoadError::Ply(why) => write!(f, "PLY: {}", why),
+            LoadError::Content(why) | LoadError::Path(why) => write!(f, "{}", why),
+            LoadError::Cancelled => write!(f, "Loading was cancelled"),
+        }
+    }
 }
 
-#[allow(dead_code)]
+impl std::error::Error for LoadError {}
+
 #[derive(Debug)]
 pub struct ParserError {
     error_type: ParserErrorType,
     token: String,
     file: String,
     location: FileLocation,
+    source_line: Option<String>,
+}
+
+impl std::fmt::Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let type_str = match self.error_type {
+            ParserErrorType::UnexpectedToken => "Unexpected token",
+            ParserErrorType::UnimplementedToken => "Unimplemented token",
+            ParserErrorType::UnknownParamType => "Unknown parameter type",
+            ParserErrorType::UnknownCoordinateSystem => "Unknown coordinate system",
+        };
+        write!(
+            f,
+            "{}:{}: {} '{}'",
+            self.file, self.location, type_str, self.token
+        )?;
+        if let Some(line) = &self.source_line {
+            write_source_line(f, line, self.location.column)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -81,6 +162,7 @@ pub enum ParserErrorType {
     UnexpectedToken,
     UnimplementedToken,
     UnknownParamType,
+    UnknownCoordinateSystem,
 }
 
 bitflags! {
@@ -91,37 +173,60 @@ bitflags! {
     }
 }
 
+enum ParseShape {
+    Shape(Arc<dyn Shape>),
+    Mesh(Arc<Mesh>, Vec<Arc<dyn Shape>>, Vec<Arc<dyn Light>>),
+    PlyMesh(
+        PathBuf,
+        Arc<dyn Material>,
+        Option<Arc<dyn Texture<f32>>>,
+        Transform<f32>,
+        Option<Arc<AnimatedTransform<f32>>>,
+        Option<AreaLightParams>,
+    ),
+}
+
+/// Loads the scene in `settings`, returning [`LoadError::Cancelled`] soon after `cancel` is set.
 pub fn load(
     settings: &SceneLoadSettings,
+    cancel: &AtomicBool,
 ) -> Result<(Scene, CameraParameters, FilmSettings), LoadError> {
-    enum ParseShape {
-        Shape(Arc<dyn Shape>),
-        Mesh(Arc<Mesh>, Vec<Arc<dyn Shape>>),
-        PlyMesh(PathBuf, Arc<dyn Material>, Transform<f32>),
-    }
-
     superluminal_perf::begin_event("pbrt load");
 
     let mut scope_stack = vec![FileScope::new(&settings.path)?];
 
-    let default_material = get_material("matte", &ParamSet::default(), &HashMap::default())?;
+    let default_material = get_material(
+        "matte",
+        &ParamSet::default(),
+        &HashMap::default(),
+        &HashMap::default(),
+        Path::new(""),
+    )?;
 
     let mut render_options = RenderOptions::default();
 
     let mut graphics_state = GraphicsState::default();
     let mut current_transform: Transform<f32> = Transform::default();
+    let mut end_transform: Transform<f32> = Transform::default();
     let mut active_transform_bits = TransformBits::all();
 
     let mut transform_stack = Vec::new();
     let mut graphics_state_stack = Vec::new();
     let mut active_transform_bits_stack = Vec::new();
+    let mut named_coordinate_systems: HashMap<String, Transform<f32>> = HashMap::new();
 
-    // TODO: Support instancing
     let mut parse_shapes = Vec::new();
+    // Shapes of named objects are collected into parse_shapes until ObjectEnd
+    let mut objects: HashMap<String, Vec<ParseShape>> = HashMap::new();
+    let mut current_object: Option<(String, usize)> = None;
+    let mut instances: Vec<(String, Transform<f32>, Option<Arc<AnimatedTransform<f32>>>)> =
+        Vec::new();
     let mut lights: Vec<Arc<dyn Light>> = Vec::new();
     let mut background = Spectrum::zeros();
     let mut named_materials = HashMap::new();
-    let mut textures = HashMap::new();
+    let mut textures: HashMap<String, Arc<dyn Texture<Spectrum<f32>>>> = HashMap::new();
+    let mut float_textures: HashMap<String, Arc<dyn Texture<f32>>> = HashMap::new();
+    let mut named_media: HashMap<String, Arc<dyn Medium>> = HashMap::new();
 
     let parse_start = Instant::now();
     superluminal_perf::begin_event("parse");
@@ -139,6 +244,9 @@ pub fn load(
             //       Better to just pass required context to util funcs and propagate errors with macro?
             macro_rules! get_next_token {
                 () => {{
+                    if cancel.load(Ordering::Relaxed) {
+                        return Err(LoadError::Cancelled);
+                    }
                     if let Some(token) = fetched_token.take() {
                         token
                     } else {
@@ -152,7 +260,10 @@ pub fn load(
                                     break 'top_parse;
                                 }
                                 err => {
-                                    return Err(LoadError::Lexer(err));
+                                    return Err(LoadError::Lexer(
+                                        String::from(path.to_string_lossy()),
+                                        err,
+                                    ));
                                 }
                             },
                         }
@@ -182,6 +293,19 @@ pub fn load(
                 };
             }
 
+            // Applies the transform to the active start and end transforms
+            macro_rules! apply_transform {
+                ($t:expr) => {{
+                    let t = $t;
+                    if active_transform_bits.contains(TransformBits::START) {
+                        current_transform = &current_transform * &t;
+                    }
+                    if active_transform_bits.contains(TransformBits::END) {
+                        end_transform = &end_transform * &t;
+                    }
+                }};
+            }
+
             macro_rules! match_bool {
                 ($s:ident) => {
                     match $s.as_str() {
@@ -201,6 +325,42 @@ pub fn load(
                 }};
             }
 
+            macro_rules! begin_attributes {
+                () => {{
+                    graphics_state_stack.push(graphics_state.clone());
+                    transform_stack.push((current_transform.clone(), end_transform.clone()));
+                    active_transform_bits_stack.push(active_transform_bits);
+                }};
+            }
+
+            macro_rules! end_attributes {
+                ($token:literal) => {{
+                    if graphics_state_stack.is_empty() {
+                        yuki_error!("Unmatched '{}' found. Ignoring", $token);
+                    } else {
+                        graphics_state = graphics_state_stack.pop().unwrap();
+                        (current_transform, end_transform) = transform_stack.pop().unwrap();
+                        active_transform_bits = active_transform_bits_stack.pop().unwrap();
+                    }
+                }};
+            }
+
+            // Returns the animation between the current transforms, None if they are equal
+            macro_rules! current_animation {
+                () => {{
+                    (current_transform != end_transform).then(|| {
+                        let [start_time, end_time] =
+                            render_options.transform_times.unwrap_or([0.0, 1.0]);
+                        Arc::new(AnimatedTransform::new(
+                            &current_transform,
+                            start_time,
+                            &end_transform,
+                            end_time,
+                        ))
+                    })
+                }};
+            }
+
             macro_rules! get_string {
                 () => {{
                     match get_next_token!() {
@@ -499,30 +659,76 @@ pub fn load(
                         t => match_unexpected_token_err!(t),
                     }
                 }
-                Token::AreaLightSource => ignore_type_definition!(Token::AreaLightSource),
-                Token::AttributeBegin => {
-                    graphics_state_stack.push(graphics_state.clone());
-                    transform_stack.push(current_transform.clone());
-                    active_transform_bits_stack.push(active_transform_bits);
-                }
-                Token::AttributeEnd => {
-                    if graphics_state_stack.is_empty() {
-                        yuki_error!("Unmatched 'AttributeEnd' found. Ignoring");
+                Token::AreaLightSource => {
+                    let light_type = get_string!();
+                    let params = get_param_set!();
+                    if light_type == "diffuse" {
+                        let l = params.find_spectrum("L", Spectrum::ones())
+                            * params.find_spectrum("scale", Spectrum::ones());
+                        graphics_state.area_light = Some(AreaLightParams {
+                            l,
+                            two_sided: params.find_bool("twosided", false),
+                        });
                     } else {
-                        graphics_state = graphics_state_stack.pop().unwrap();
-                        current_transform = transform_stack.pop().unwrap();
-                        active_transform_bits = active_transform_bits_stack.pop().unwrap();
+                        yuki_info!("'{}' area light not implemented", light_type);
                     }
                 }
+                Token::AttributeBegin => begin_attributes!(),
+                Token::AttributeEnd => end_attributes!("AttributeEnd"),
                 Token::Camera => {
                     let name = get_string!();
-                    if name != "perspective" {
-                        return Err(LoadError::Content(
-                            "Only perspective camera is supported".into(),
-                        ));
-                    }
                     let params = get_param_set!();
-                    render_options.camera_params.fov = FoV::Y(params.find_f32("fov", 45.0));
+                    render_options.camera_medium = graphics_state.outside_medium.clone();
+                    render_options.camera_params.lens_radius = params.find_f32("lensradius", 0.0);
+                    render_options.camera_params.focal_distance =
+                        params.find_f32("focaldistance", 1e6);
+                    render_options.camera_params.shutter_open = params.find_f32("shutteropen", 0.0);
+                    render_options.camera_params.shutter_close =
+                        params.find_f32("shutterclose", 1.0);
+                    render_options.camera_params.camera_type = match name.as_str() {
+                        "perspective" => CameraType::Perspective {
+                            fov: FoV::Y(params.find_f32("fov", 45.0)),
+                        },
+                        "orthographic" => {
+                            // Default width depends on the film aspect ratio so it's resolved
+                            // after parsing
+                            let window = params.find_f32s("screenwindow", &[]);
+                            if !window.is_empty() && window.len() != 4 {
+                                return Err(LoadError::Content(
+                                    "Expected 4 values in 'screenwindow'".into(),
+                                ));
+                            }
+                            render_options.screen_window = window.try_into().ok();
+                            CameraType::Orthographic { screen_width: 0.0 }
+                        }
+                        "environment" => CameraType::Environment,
+                        _ => {
+                            return Err(LoadError::Content(format!(
+                                "Unsupported camera '{}'",
+                                name
+                            )));
+                        }
+                    };
+                }
+                Token::CoordinateSystem => {
+                    named_coordinate_systems.insert(get_string!(), current_transform.clone());
+                }
+                Token::CoordSysTransform => {
+                    let name = get_string!();
+                    match named_coordinate_systems.get(&name) {
+                        Some(t) => {
+                            if active_transform_bits.contains(TransformBits::START) {
+                                current_transform = t.clone();
+                            }
+                            if active_transform_bits.contains(TransformBits::END) {
+                                end_transform = t.clone();
+                            }
+                        }
+                        None => {
+                            error_token = Some((ParserErrorType::UnknownCoordinateSystem, name));
+                            break 'top_parse;
+                        }
+                    }
                 }
                 Token::Film => {
                     // TODO: Variants
@@ -554,7 +760,20 @@ pub fn load(
                     match type_name.as_str() {
                         "infinite" => {
                             let default_l = Spectrum::ones();
-                            background = params.find_spectrum("L", default_l);
+                            let l = params.find_spectrum("L", default_l)
+                                * params.find_spectrum("scale", Spectrum::ones());
+                            let map_name = params.find_string("mapname", "");
+                            if map_name.is_empty() {
+                                background = l;
+                            } else {
+                                let path = parent_path.join(PathBuf::from(map_name));
+                                let map = ImageTexture::new(&path).map_err(LoadError::Image)?;
+                                lights.push(Arc::new(InfiniteLight::new(
+                                    &current_transform,
+                                    l,
+                                    Arc::new(map),
+                                )));
+                            }
                         }
                         "distant" => {
                             let radiance = params.find_spectrum("L", Spectrum::ones());
@@ -573,7 +792,69 @@ pub fn load(
                             if !i.is_black() {
                                 let default_pos = Point3::zeros();
                                 let pos = params.find_point("from", default_pos);
-                                lights.push(Arc::new(PointLight::new(&translation(pos.into()), i)));
+                                let ies_name = params.find_string("iesname", "");
+                                if ies_name.is_empty() {
+                                    lights.push(Arc::new(PointLight::new(
+                                        &translation(pos.into()),
+                                        i,
+                                    )));
+                                } else {
+                                    let path = parent_path.join(PathBuf::from(ies_name));
+                                    let profile =
+                                        IesProfile::load(&path).map_err(LoadError::Ies)?;
+                                    // The profile needs the orientation from the current transform
+                                    lights.push(Arc::new(PhotometricLight::new(
+                                        &(&current_transform * &translation(pos.into())),
+                                        i,
+                                        profile,
+                                    )));
+                                }
+                            }
+                        }
+                        "goniometric" => {
+                            let i = params.find_spectrum("I", Spectrum::ones())
+                                * params.find_spectrum("scale", Spectrum::ones());
+                            if !i.is_black() {
+                                let map_name = params.find_string("mapname", "");
+                                if map_name.is_empty() {
+                                    // No map means uniform emission like pbrt does
+                                    lights.push(Arc::new(PointLight::new(&current_transform, i)));
+                                } else {
+                                    let path = parent_path.join(PathBuf::from(map_name));
+                                    let map = ImageTexture::new(&path).map_err(LoadError::Image)?;
+                                    lights.push(Arc::new(GoniometricLight::new(
+                                        &current_transform,
+                                        i,
+                                        Arc::new(map),
+                                    )));
+                                }
+                            }
+                        }
+                        "spot" => {
+                            let i = params.find_spectrum("I", Spectrum::ones())
+                                * params.find_spectrum("scale", Spectrum::ones());
+                            if !i.is_black() {
+                                let cone_angle = params.find_f32("coneangle", 30.0);
+                                let cone_delta = params.find_f32("conedeltaangle", 5.0);
+                                let from = params.find_point("from", Point3::zeros());
+                                let to = params.find_point("to", Point3::new(0.0, 0.0, 1.0));
+
+                                // The light shines along its local +Z so orient that toward 'to'
+                                let dir = (to - from).normalized();
+                                let up = if dir.y.abs() < 0.999 {
+                                    Vec3::new(0.0, 1.0, 0.0)
+                                } else {
+                                    Vec3::new(1.0, 0.0, 0.0)
+                                };
+                                let light_to_world =
+                                    &current_transform * &look_at(from, to, up).inverted();
+
+                                lights.push(Arc::new(SpotLight::new(
+                                    &light_to_world,
+                                    i,
+                                    cone_angle,
+                                    cone_angle - cone_delta,
+                                )));
                             }
                         }
                         _ => {
@@ -598,8 +879,13 @@ pub fn load(
                     graphics_state.material = Arc::clone(material);
                 }
                 Token::Material => {
-                    graphics_state.material =
-                        get_material(&get_string!(), &get_param_set!(), &textures)?;
+                    graphics_state.material = get_material(
+                        &get_string!(),
+                        &get_param_set!(),
+                        &textures,
+                        &float_textures,
+                        &parent_path,
+                    )?;
                 }
                 Token::MakeNamedMaterial => {
                     let name = get_string!();
@@ -611,13 +897,73 @@ pub fn load(
                     }
                     named_materials.insert(
                         name,
-                        get_material(&get_string!(), &get_param_set!(), &textures)?,
+                        get_material(
+                            &get_string!(),
+                            &get_param_set!(),
+                            &textures,
+                            &float_textures,
+                            &parent_path,
+                        )?,
                     );
                 }
+                Token::MakeNamedMedium => {
+                    let name = get_string!();
+                    let params = get_param_set!();
+                    match params.find_string("type", "") {
+                        "homogeneous" => {
+                            if !params.find_string("preset", "").is_empty() {
+                                yuki_info!("Medium presets not implemented");
+                            }
+                            // Defaults from pbrt-v3
+                            let scale = params.find_f32("scale", 1.0);
+                            let sigma_a = params
+                                .find_spectrum("sigma_a", Spectrum::new(0.0011, 0.0024, 0.014));
+                            let sigma_s =
+                                params.find_spectrum("sigma_s", Spectrum::new(2.55, 3.21, 3.77));
+                            named_media.insert(
+                                name,
+                                Arc::new(HomogeneousMedium::new(
+                                    sigma_a * scale,
+                                    sigma_s * scale,
+                                    params.find_f32("g", 0.0),
+                                )),
+                            );
+                        }
+                        t => yuki_info!("'{}' medium not implemented", t),
+                    }
+                }
+                Token::MediumInterface => {
+                    let inside = get_string!();
+                    // The outside medium is optional and matches the inside one if omitted
+                    let outside = match try_get_string!() {
+                        Ok(s) => s,
+                        Err(t) => {
+                            fetched_token = Some(t);
+                            inside.clone()
+                        }
+                    };
+                    // Empty names mean vacuum, common for shapes bounding the camera medium
+                    if !inside.is_empty() && inside != outside {
+                        yuki_info!("Media bounded by shapes not implemented");
+                    }
+                    graphics_state.outside_medium = (!outside.is_empty()).then_some(outside);
+                }
+                Token::PixelFilter => {
+                    let name = get_string!();
+                    let _params = get_param_set!();
+                    render_options.film_settings.filter = match name.as_str() {
+                        "box" => FilterType::Box,
+                        "mitchell" => FilterType::Mitchell,
+                        _ => {
+                            yuki_info!("Unsupported pixel filter '{}'. Using box.", name);
+                            FilterType::Box
+                        }
+                    };
+                }
                 Token::Rotate => {
                     let angle = get_f32!();
                     let axis = Vec3::new(get_f32!(), get_f32!(), get_f32!());
-                    current_transform = &current_transform * &rotation(angle.to_radians(), axis);
+                    apply_transform!(rotation(angle.to_radians(), axis));
                 }
                 Token::Sampler => ignore_type_definition!(Token::Sampler),
                 Token::Shape => {
@@ -625,11 +971,97 @@ pub fn load(
                     let shape_type = get_string!();
                     let params = get_param_set!();
                     let material = Arc::clone(&graphics_state.material);
+
+                    // Animated shapes are created in object space and moved by a wrapper
+                    let animated_transform = current_animation!();
+                    let shape_transform = if animated_transform.is_some() {
+                        Transform::default()
+                    } else {
+                        current_transform.clone()
+                    };
+                    let first_new_shape = parse_shapes.len();
+
+                    let alpha = find_alpha(&params, &float_textures)?;
+                    if alpha.is_some()
+                        && !matches!(
+                            shape_type.as_str(),
+                            "trianglemesh"
+                                | "heightfield"
+                                | "loopsubdiv"
+                                | "plymesh"
+                                | "bilinearmesh"
+                        )
+                    {
+                        yuki_info!("Alpha on '{}' is not supported. Ignoring", shape_type);
+                    }
+
+                    let mut area_light = graphics_state.area_light;
+                    if area_light.is_some() {
+                        if current_object.is_some() {
+                            // Instances would need their own lights for each placement
+                            yuki_info!(
+                                "Area lights in objects are not supported. Skipping emission"
+                            );
+                            area_light = None;
+                        } else if animated_transform.is_some() {
+                            // Lights are created in world space so they can't follow the animation
+                            yuki_info!("Animated area lights are not supported. Skipping emission");
+                            area_light = None;
+                        } else if !matches!(
+                            shape_type.as_str(),
+                            "trianglemesh"
+                                | "heightfield"
+                                | "loopsubdiv"
+                                | "plymesh"
+                                | "bilinearmesh"
+                        ) {
+                            yuki_info!(
+                                "Area lights on '{}' are not supported. Skipping emission",
+                                shape_type
+                            );
+                            area_light = None;
+                        }
+                    }
+
                     match shape_type.as_str() {
+                        "cylinder" => {
+                            let radius = params.find_f32("radius", 1.0);
+                            let z_min = params.find_f32("zmin", -1.0);
+                            let z_max = params.find_f32("zmax", 1.0);
+                            let phi_max = params.find_f32("phimax", 360.0);
+                            parse_shapes.push(ParseShape::Shape(Arc::new(Cylinder::new(
+                                &shape_transform,
+                                radius,
+                                z_min,
+                                z_max,
+                                phi_max,
+                                material,
+                            ))));
+                        }
+                        "disk" => {
+                            let height = params.find_f32("height", 0.0);
+                            let radius = params.find_f32("radius", 1.0);
+                            let inner_radius = params.find_f32("innerradius", 0.0);
+                            if inner_radius < 0.0 || inner_radius >= radius {
+                                yuki_error!(
+                                    "Invalid 'disk' with radius {} and inner radius {}",
+                                    radius,
+                                    inner_radius
+                                );
+                                continue 'top_parse;
+                            }
+                            parse_shapes.push(ParseShape::Shape(Arc::new(Disk::new(
+                                &shape_transform,
+                                height,
+                                radius,
+                                inner_radius,
+                                material,
+                            ))));
+                        }
                         "sphere" => {
                             let radius = params.find_f32("radius", 1.0);
                             parse_shapes.push(ParseShape::Shape(Arc::new(Sphere::new(
-                                &current_transform,
+                                &shape_transform,
                                 radius,
                                 material,
                             ))));
@@ -660,25 +1092,157 @@ pub fn load(
                             let default_uvs = Vec::new();
                             let uvs = Vec::from(params.find_uvs("uv", &default_uvs));
 
-                            let mesh = Arc::new(Mesh::new(
-                                &current_transform,
-                                indices,
-                                points,
-                                normals,
-                                uvs,
+                            parse_shapes.push(mesh_shape(
+                                Arc::new(Mesh::new(
+                                    &shape_transform,
+                                    indices,
+                                    points,
+                                    normals,
+                                    uvs,
+                                )),
+                                &material,
+                                area_light,
+                                alpha.as_ref(),
+                            ));
+                        }
+                        "heightfield" => {
+                            #[allow(clippy::cast_sign_loss)] // Checked to be positive
+                            let (nu, nv) =
+                                match (params.find_i32("nu", -1), params.find_i32("nv", -1)) {
+                                    (nu, nv) if nu >= 2 && nv >= 2 => (nu as usize, nv as usize),
+                                    _ => {
+                                        yuki_error!(
+                                            "Invalid 'heightfield' with less than 2x2 heights"
+                                        );
+                                        continue 'top_parse;
+                                    }
+                                };
+                            let default_z = Vec::new();
+                            let z = params.find_f32s("Pz", &default_z);
+                            if z.len() != nu * nv {
+                                yuki_error!("Invalid 'heightfield' with a height count that doesn't match 'nu' * 'nv'");
+                                continue 'top_parse;
+                            }
+
+                            parse_shapes.push(mesh_shape(
+                                Arc::new(heightfield_mesh(&shape_transform, nu, nv, z)),
+                                &material,
+                                area_light,
+                                alpha.as_ref(),
+                            ));
+                        }
+                        "loopsubdiv" => {
+                            let default_indices = Vec::new();
+                            #[allow(clippy::cast_sign_loss)] // Valid indices are never negative
+                            let indices: Vec<usize> = params
+                                .find_i32s("indices", &default_indices)
+                                .iter()
+                                .map(|&i| i as usize)
+                                .collect();
+                            let default_points = Vec::new();
+                            let points = params.find_points("P", &default_points);
+
+                            if indices.is_empty() || indices.len() % 3 != 0 {
+                                yuki_error!("Invalid 'loopsubdiv' with an index count that is not a non-zero multiple of 3");
+                                continue 'top_parse;
+                            }
+                            if indices.iter().any(|&i| i >= points.len()) {
+                                yuki_error!("Invalid 'loopsubdiv' with out of bounds indices");
+                                continue 'top_parse;
+                            }
+
+                            #[allow(clippy::cast_sign_loss)] // Clamped to be positive
+                            let levels = params.find_i32("levels", 3).max(0) as u32;
+                            parse_shapes.push(mesh_shape(
+                                Arc::new(loop_subdivision_mesh(
+                                    &shape_transform,
+                                    levels,
+                                    &indices,
+                                    points,
+                                )),
+                                &material,
+                                area_light,
+                                alpha.as_ref(),
                             ));
-                            let tri_shapes = (0..num_indices)
-                                .step_by(3)
-                                .map(|v0| {
-                                    Arc::new(Triangle::new(
-                                        Arc::clone(&mesh),
-                                        v0,
-                                        Arc::clone(&material),
-                                        None,
-                                    )) as Arc<dyn Shape>
-                                })
+                        }
+                        "bilinearmesh" => {
+                            let default_points = Vec::new();
+                            let points = params.find_points("P", &default_points);
+                            // A single patch can omit indices
+                            let default_indices = if points.len() == 4 {
+                                vec![0, 1, 2, 3]
+                            } else {
+                                Vec::new()
+                            };
+                            #[allow(clippy::cast_sign_loss)] // Valid indices are never negative
+                            let indices: Vec<usize> = params
+                                .find_i32s("indices", &default_indices)
+                                .iter()
+                                .map(|&i| i as usize)
                                 .collect();
-                            parse_shapes.push(ParseShape::Mesh(mesh, tri_shapes));
+
+                            if indices.is_empty() || indices.len() % 4 != 0 {
+                                yuki_error!("Invalid 'bilinearmesh' with an index count that is not a non-zero multiple of 4");
+                                continue 'top_parse;
+                            }
+                            if indices.iter().any(|&i| i >= points.len()) {
+                                yuki_error!("Invalid 'bilinearmesh' with out of bounds indices");
+                                continue 'top_parse;
+                            }
+
+                            let default_uvs = Vec::new();
+                            let uvs = params.find_uvs("uv", &default_uvs);
+                            let has_uvs = uvs.len() == points.len();
+
+                            if area_light.is_some() || alpha.is_some() {
+                                // Lights and alpha are only supported on triangles so split
+                                // each patch in two along the p00-p11 diagonal
+                                yuki_info!("Triangulating emissive or masked 'bilinearmesh'");
+                                let tri_indices = indices
+                                    .chunks_exact(4)
+                                    .flat_map(|p| [p[0], p[1], p[3], p[0], p[3], p[2]])
+                                    .collect();
+                                let tri_uvs = if has_uvs { Vec::from(uvs) } else { Vec::new() };
+                                parse_shapes.push(mesh_shape(
+                                    Arc::new(Mesh::new(
+                                        &shape_transform,
+                                        tri_indices,
+                                        Vec::from(points),
+                                        Vec::new(),
+                                        tri_uvs,
+                                    )),
+                                    &material,
+                                    area_light,
+                                    alpha.as_ref(),
+                                ));
+                            } else {
+                                for patch in indices.chunks_exact(4) {
+                                    let patch_uvs = if has_uvs {
+                                        Some([
+                                            uvs[patch[0]],
+                                            uvs[patch[1]],
+                                            uvs[patch[2]],
+                                            uvs[patch[3]],
+                                        ])
+                                    } else {
+                                        None
+                                    };
+                                    parse_shapes.push(ParseShape::Shape(Arc::new(
+                                        BilinearPatch::new(
+                                            &shape_transform,
+                                            [
+                                                points[patch[0]],
+                                                points[patch[1]],
+                                                points[patch[2]],
+                                                points[patch[3]],
+                                            ],
+                                            patch_uvs,
+                                            Arc::clone(&material),
+                                            None,
+                                        ),
+                                    )));
+                                }
+                            }
                         }
                         "plymesh" => {
                             let filename = params.find_string("filename", "");
@@ -698,17 +1262,68 @@ pub fn load(
                             parse_shapes.push(ParseShape::PlyMesh(
                                 ply_abspath,
                                 material,
-                                current_transform.clone(),
+                                alpha,
+                                shape_transform.clone(),
+                                None,
+                                area_light,
                             ));
                         }
                         t => {
                             yuki_info!("Unsupported shape type '{}'. Skipping", t);
                         }
                     }
+
+                    if let Some(object_to_world) = animated_transform {
+                        for s in &mut parse_shapes[first_new_shape..] {
+                            match s {
+                                ParseShape::Shape(shape) => animate(shape, &object_to_world),
+                                ParseShape::Mesh(_, shapes, _) => {
+                                    for shape in shapes {
+                                        animate(shape, &object_to_world);
+                                    }
+                                }
+                                ParseShape::PlyMesh(_, _, _, _, animation, _) => {
+                                    *animation = Some(Arc::clone(&object_to_world));
+                                }
+                            }
+                        }
+                    }
+                }
+                Token::ObjectBegin => {
+                    let name = get_string!();
+                    if let Some((outer, _)) = &current_object {
+                        return Err(LoadError::Content(format!(
+                            "Object '{}' begins inside object '{}'",
+                            name, outer
+                        )));
+                    }
+                    begin_attributes!();
+                    current_object = Some((name, parse_shapes.len()));
+                }
+                Token::ObjectEnd => match current_object.take() {
+                    Some((name, first_shape)) => {
+                        objects.insert(name, parse_shapes.split_off(first_shape));
+                        end_attributes!("ObjectEnd");
+                    }
+                    None => yuki_error!("Unmatched 'ObjectEnd' found. Ignoring"),
+                },
+                Token::ObjectInstance => {
+                    let name = get_string!();
+                    if current_object.is_some() {
+                        yuki_error!("Instances inside objects are not supported. Skipping");
+                    } else {
+                        // Animated instances are placed by a wrapper like animated shapes
+                        let animation = current_animation!();
+                        let object_to_world = if animation.is_some() {
+                            Transform::default()
+                        } else {
+                            current_transform.clone()
+                        };
+                        instances.push((name, object_to_world, animation));
+                    }
                 }
                 Token::Scale => {
-                    current_transform =
-                        &current_transform * &scale(get_f32!(), get_f32!(), get_f32!());
+                    apply_transform!(scale(get_f32!(), get_f32!(), get_f32!()));
                 }
                 Token::Texture => {
                     let name = get_string!();
@@ -727,10 +1342,99 @@ pub fn load(
 
                         let path = parent_path.join(PathBuf::from(filename));
 
+                        let texture = configure_image_texture(
+                            ImageTexture::new(&path).map_err(LoadError::Image)?,
+                            &params,
+                            &name,
+                        )?;
+                        textures.insert(name, Arc::new(texture));
+                    } else if &ttype == "spectrum" && &class == "checkerboard" {
+                        let dimension = params.find_i32("dimension", 2);
+                        if dimension != 2 {
+                            return Err(LoadError::Content(format!(
+                                "{}D checkerboard texture '{}' is not supported",
+                                dimension, name
+                            )));
+                        }
+
+                        let tex1 =
+                            find_spectrum_texture(&params, "tex1", Spectrum::ones(), &textures)?;
+                        let tex2 =
+                            find_spectrum_texture(&params, "tex2", Spectrum::zeros(), &textures)?;
+                        let u_scale = params.find_f32("uscale", 1.0);
+                        let v_scale = params.find_f32("vscale", 1.0);
+
                         textures.insert(
                             name,
-                            Arc::new(ImageTexture::new(&path).map_err(LoadError::Image)?),
+                            Arc::new(CheckerboardTexture::new(tex1, tex2, u_scale, v_scale)),
                         );
+                    } else if &ttype == "float" && &class == "imagemap" {
+                        let filename = params.find_string("filename", "");
+                        if filename.is_empty() {
+                            return Err(LoadError::Content(format!(
+                                "missing file for texture '{}'",
+                                name
+                            )));
+                        }
+
+                        let path = parent_path.join(PathBuf::from(filename));
+
+                        let texture = configure_image_texture(
+                            ImageTexture::new_luminance(&path).map_err(LoadError::Image)?,
+                            &params,
+                            &name,
+                        )?;
+                        float_textures.insert(name, Arc::new(texture));
+                    } else if &ttype == "float" && &class == "checkerboard" {
+                        let dimension = params.find_i32("dimension", 2);
+                        if dimension != 2 {
+                            return Err(LoadError::Content(format!(
+                                "{}D checkerboard texture '{}' is not supported",
+                                dimension, name
+                            )));
+                        }
+
+                        let tex1 = find_float_texture(&params, "tex1", 1.0, &float_textures)?;
+                        let tex2 = find_float_texture(&params, "tex2", 0.0, &float_textures)?;
+                        let u_scale = params.find_f32("uscale", 1.0);
+                        let v_scale = params.find_f32("vscale", 1.0);
+
+                        float_textures.insert(
+                            name,
+                            Arc::new(CheckerboardTexture::new(tex1, tex2, u_scale, v_scale)),
+                        );
+                    } else if &ttype == "spectrum" && &class == "constant" {
+                        let value = params.find_spectrum("value", Spectrum::ones());
+                        textures.insert(name, Arc::new(ConstantTexture::new(value)));
+                    } else if &ttype == "float" && &class == "constant" {
+                        let value = params.find_f32("value", 1.0);
+                        float_textures.insert(name, Arc::new(ConstantTexture::new(value)));
+                    } else if &ttype == "spectrum" && &class == "scale" {
+                        let tex1 =
+                            find_spectrum_texture(&params, "tex1", Spectrum::ones(), &textures)?;
+                        let tex2 =
+                            find_spectrum_texture(&params, "tex2", Spectrum::ones(), &textures)?;
+
+                        textures.insert(name, Arc::new(ScaleTexture::new(tex1, tex2)));
+                    } else if &ttype == "float" && &class == "scale" {
+                        let tex1 = find_float_texture(&params, "tex1", 1.0, &float_textures)?;
+                        let tex2 = find_float_texture(&params, "tex2", 1.0, &float_textures)?;
+
+                        float_textures.insert(name, Arc::new(ScaleTexture::new(tex1, tex2)));
+                    } else if &ttype == "spectrum" && &class == "mix" {
+                        let tex1 =
+                            find_spectrum_texture(&params, "tex1", Spectrum::zeros(), &textures)?;
+                        let tex2 =
+                            find_spectrum_texture(&params, "tex2", Spectrum::ones(), &textures)?;
+                        let amount = find_float_texture(&params, "amount", 0.5, &float_textures)?;
+
+                        textures.insert(name, Arc::new(MixTexture::new(tex1, tex2, amount)));
+                    } else if &ttype == "float" && &class == "mix" {
+                        let tex1 = find_float_texture(&params, "tex1", 0.0, &float_textures)?;
+                        let tex2 = find_float_texture(&params, "tex2", 1.0, &float_textures)?;
+                        let amount = find_float_texture(&params, "amount", 0.5, &float_textures)?;
+
+                        float_textures.insert(name, Arc::new(MixTexture::new(tex1, tex2, amount)));
                     } else {
                         yuki_info!(
                             "Ignoring unsupported texture type '{}' class '{}'",
@@ -741,10 +1445,10 @@ pub fn load(
                 }
                 Token::Translate => {
                     let delta = Vec3::new(get_f32!(), get_f32!(), get_f32!());
-                    current_transform = &current_transform * &translation(delta);
+                    apply_transform!(translation(delta));
                 }
                 Token::TransformBegin => {
-                    transform_stack.push(current_transform.clone());
+                    transform_stack.push((current_transform.clone(), end_transform.clone()));
                 }
                 Token::TransformEnd => {
                     if graphics_state_stack.is_empty() {
@@ -753,8 +1457,12 @@ pub fn load(
                         graphics_state = graphics_state_stack.pop().unwrap();
                     }
                 }
+                Token::TransformTimes => {
+                    render_options.transform_times = Some([get_f32!(), get_f32!()]);
+                }
                 Token::WorldBegin => {
                     current_transform = Transform::default();
+                    end_transform = Transform::default();
                 }
                 Token::WorldEnd => (), // Don't enforce state rules for now
                 _ => {
@@ -772,6 +1480,7 @@ pub fn load(
                 token,
                 file: String::from(path.to_string_lossy()),
                 location,
+                source_line: lexer.source_line(location),
             }));
         }
     }
@@ -786,18 +1495,10 @@ pub fn load(
     let ply_start = Instant::now();
     superluminal_perf::begin_event("load plys");
 
-    parse_shapes.par_iter_mut().try_for_each(|s| match s {
-        ParseShape::PlyMesh(path, material, transform) => {
-            let ply::PlyResult {
-                mesh,
-                shapes: ply_shapes,
-            } = ply::load(path, material, Some(transform.clone()))
-                .map_err(|e| LoadError::Ply(e.to_string()))?;
-            *s = ParseShape::Mesh(mesh, ply_shapes);
-            Ok(())
-        }
-        _ => Ok(()),
-    })?;
+    load_plys(&mut parse_shapes, settings.ply_crease_angle, cancel)?;
+    for object_shapes in objects.values_mut() {
+        load_plys(object_shapes, settings.ply_crease_angle, cancel)?;
+    }
 
     superluminal_perf::end_event(); // load plys
     yuki_info!(
@@ -810,38 +1511,98 @@ pub fn load(
     let mut meshes: Vec<Arc<Mesh>> = Vec::new();
     let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
 
-    for s in parse_shapes {
-        match s {
-            ParseShape::Shape(shape) => shapes.push(shape),
-            ParseShape::Mesh(mesh, tri_shapes) => {
-                meshes.push(mesh);
-                shapes.extend(tri_shapes);
+    collect_shapes(parse_shapes, &mut meshes, &mut shapes, &mut lights);
+
+    superluminal_perf::end_event(); // collect meshes
+
+    superluminal_perf::begin_event("build objects");
+
+    // Each object gets its own hierarchy that is shared by all of its instances
+    let mut object_bvhs = HashMap::new();
+    for (name, object_shapes) in objects {
+        let mut object_meshes = Vec::new();
+        let mut object_bvh_shapes = Vec::new();
+        // Emission is skipped in objects so there are no lights
+        collect_shapes(
+            object_shapes,
+            &mut object_meshes,
+            &mut object_bvh_shapes,
+            &mut Vec::new(),
+        );
+        if object_bvh_shapes.is_empty() {
+            yuki_warn!("Object '{}' has no shapes", name);
+            continue;
+        }
+        meshes.extend(object_meshes);
+
+        let (bvh, _) = BoundingVolumeHierarchy::new(
+            object_bvh_shapes,
+            settings.max_shapes_in_node as usize,
+            settings.split_method,
+        );
+        object_bvhs.insert(name, Arc::new(bvh));
+    }
+
+    for (name, object_to_world, animation) in instances {
+        match object_bvhs.get(&name) {
+            Some(bvh) => {
+                let mut shape: Arc<dyn Shape> =
+                    Arc::new(Instance::new(Arc::clone(bvh), &object_to_world));
+                if let Some(object_to_world) = animation {
+                    animate(&mut shape, &object_to_world);
+                }
+                shapes.push(shape);
             }
-            ParseShape::PlyMesh(..) => unreachable!("We should have converted these to Mesh()"),
+            None => yuki_warn!("Instance of unknown object '{}'. Skipping", name),
         }
     }
 
-    superluminal_perf::end_event(); // collect meshes
+    superluminal_perf::end_event(); // build objects
 
     // TODO: This could be much cleaner
-    if render_options.film_settings.res.y < render_options.film_settings.res.x {
-        render_options.camera_params.fov = FoV::Y(match render_options.camera_params.fov {
-            FoV::X(angle) | FoV::Y(angle) => angle,
-        });
-    } else {
-        render_options.camera_params.fov = FoV::X(match render_options.camera_params.fov {
-            FoV::X(angle) | FoV::Y(angle) => angle,
-        });
+    let res_x = render_options.film_settings.res.x as f32;
+    let res_y = render_options.film_settings.res.y as f32;
+    match &mut render_options.camera_params.camera_type {
+        CameraType::Perspective { fov } => {
+            let angle = match fov {
+                FoV::X(angle) | FoV::Y(angle) => *angle,
+            };
+            *fov = if res_y < res_x {
+                FoV::Y(angle)
+            } else {
+                FoV::X(angle)
+            };
+        }
+        CameraType::Orthographic { screen_width } => {
+            // pbrt defaults to [-1,1] along the shorter axis
+            *screen_width = match render_options.screen_window {
+                Some([x_min, x_max, _, _]) => x_max - x_min,
+                None => 2.0 * (res_x / res_y).max(1.0),
+            };
+        }
+        CameraType::Environment => (),
     }
 
-    let (bvh, shapes) = BoundingVolumeHierarchy::new(
-        shapes,
-        settings.max_shapes_in_node as usize,
-        settings.split_method,
-    );
+    if cancel.load(Ordering::Relaxed) {
+        return Err(LoadError::Cancelled);
+    }
+
+    let (bvh, shapes) = build_bvh(settings, shapes);
 
     superluminal_perf::end_event(); // pbrt load
 
+    let light_distribution = power_distribution(&lights, bvh.bounds());
+
+    let medium = render_options
+        .camera_medium
+        .and_then(|name| match named_media.get(&name) {
+            Some(medium) => Some(Arc::clone(medium)),
+            None => {
+                yuki_warn!("Camera is in unknown medium '{}'. Ignoring", name);
+                None
+            }
+        });
+
     Ok((
         Scene {
             name: settings.path.file_name().unwrap().to_str().unwrap().into(),
@@ -850,79 +1611,272 @@ pub fn load(
             shapes,
             bvh,
             lights,
+            light_distribution,
             background,
+            medium,
         },
         render_options.camera_params,
         render_options.film_settings,
     ))
 }
 
+/// Loads the meshes of [`ParseShape::PlyMesh`]es in `parse_shapes`, converting them into
+/// [`ParseShape::Mesh`]es. Meshes without normals get smooth ones if `crease_angle_degrees` is
+/// given.
+fn load_plys(
+    parse_shapes: &mut [ParseShape],
+    crease_angle_degrees: Option<f32>,
+    cancel: &AtomicBool,
+) -> Result<(), LoadError> {
+    parse_shapes.par_iter_mut().try_for_each(|s| match s {
+        ParseShape::PlyMesh(..) if cancel.load(Ordering::Relaxed) => Err(LoadError::Cancelled),
+        ParseShape::PlyMesh(path, material, alpha, transform, animation, area_light) => {
+            let ply::PlyResult {
+                mesh,
+                shapes: ply_shapes,
+            } = ply::load(
+                path,
+                material,
+                Some(transform.clone()),
+                crease_angle_degrees,
+            )
+            .map_err(|e| LoadError::Ply(e.to_string()))?;
+            let mut loaded = if area_light.is_some() || alpha.is_some() {
+                // Emissive triangles need their lights and masked ones their alpha so recreate them
+                mesh_shape(mesh, material, *area_light, alpha.as_ref())
+            } else {
+                ParseShape::Mesh(mesh, ply_shapes, Vec::new())
+            };
+            if let (Some(object_to_world), ParseShape::Mesh(_, shapes, _)) =
+                (animation, &mut loaded)
+            {
+                for shape in shapes {
+                    animate(shape, object_to_world);
+                }
+            }
+            *s = loaded;
+            Ok(())
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Moves the contents of loaded `parse_shapes` into `meshes`, `shapes` and `lights`.
+fn collect_shapes(
+    parse_shapes: Vec<ParseShape>,
+    meshes: &mut Vec<Arc<Mesh>>,
+    shapes: &mut Vec<Arc<dyn Shape>>,
+    lights: &mut Vec<Arc<dyn Light>>,
+) {
+    for s in parse_shapes {
+        match s {
+            ParseShape::Shape(shape) => shapes.push(shape),
+            ParseShape::Mesh(mesh, tri_shapes, tri_lights) => {
+                meshes.push(mesh);
+                shapes.extend(tri_shapes);
+                lights.extend(tri_lights);
+            }
+            ParseShape::PlyMesh(..) => unreachable!("We should have converted these to Mesh()"),
+        }
+    }
+}
+
+/// Wraps `shape` to be moved by `object_to_world`.
+fn animate(shape: &mut Arc<dyn Shape>, object_to_world: &Arc<AnimatedTransform<f32>>) {
+    *shape = Arc::new(AnimatedShape::new(
+        Arc::clone(shape),
+        Arc::clone(object_to_world),
+    ));
+}
+
+/// Wraps the triangles of `mesh` into a `ParseShape`, with a light for each triangle if
+/// `area_light` is given and cut out by `alpha` if given.
+fn mesh_shape(
+    mesh: Arc<Mesh>,
+    material: &Arc<dyn Material>,
+    area_light: Option<AreaLightParams>,
+    alpha: Option<&Arc<dyn Texture<f32>>>,
+) -> ParseShape {
+    let mut tri_lights: Vec<Arc<dyn Light>> = Vec::new();
+    let tri_shapes = (0..mesh.indices.len())
+        .step_by(3)
+        .map(|v0| {
+            let light = area_light.map(|params| {
+                let light = Arc::new(DiffuseAreaLight::new(&mesh, v0, params.l, params.two_sided));
+                tri_lights.push(Arc::clone(&light) as Arc<dyn Light>);
+                light as Arc<dyn AreaLight>
+            });
+            Arc::new(
+                Triangle::new(Arc::clone(&mesh), v0, Arc::clone(material), light)
+                    .with_alpha(alpha.cloned()),
+            ) as Arc<dyn Shape>
+        })
+        .collect();
+    ParseShape::Mesh(mesh, tri_shapes, tri_lights)
+}
+
 fn get_material(
     material_type: &str,
     params: &ParamSet,
-    textures: &HashMap<String, Arc<ImageTexture<Spectrum<f32>>>>,
+    textures: &HashMap<String, Arc<dyn Texture<Spectrum<f32>>>>,
+    float_textures: &HashMap<String, Arc<dyn Texture<f32>>>,
+    parent_path: &Path,
 ) -> Result<Arc<dyn Material>, LoadError> {
     match material_type {
         "glass" => {
             let kr = params.find_spectrum("Kr", Spectrum::ones());
             let kt = params.find_spectrum("Kt", Spectrum::ones());
             let eta = params.find_f32("eta", 1.5);
+            let roughness = params.find_f32("roughness", 0.0);
+            let u_roughness = params.find_f32("uroughness", roughness);
+            let v_roughness = params.find_f32("vroughness", roughness);
+            if (u_roughness - v_roughness).abs() > f32::EPSILON {
+                yuki_info!("Anisotropic glass roughness is not supported. Using the average.");
+            }
+            let remap_roughness = params.find_bool("remaproughness", true);
+            let distribution = find_microfacet_distribution(params)?;
 
             Ok(Arc::new(Glass::new(
                 Arc::new(ConstantTexture::new(kr)),
                 Arc::new(ConstantTexture::new(kt)),
                 eta,
+                Arc::new(ConstantTexture::new((u_roughness + v_roughness) / 2.0)),
+                remap_roughness,
+                distribution,
             )) as Arc<dyn Material>)
         }
         "glossy" => {
             let rs = params.find_spectrum("Rs", Spectrum::new(0.5, 0.5, 0.5));
             let roughness = params.find_f32("roughness", 0.5);
+            let distribution = find_microfacet_distribution(params)?;
             Ok(Arc::new(Glossy::new(
                 Arc::new(ConstantTexture::new(rs)),
                 Arc::new(ConstantTexture::new(roughness)),
                 false,
+                distribution,
             )) as Arc<dyn Material>)
         }
         "matte" => {
-            let kd = {
-                let kd_tex = params.find_string("Kd", "");
-                if kd_tex.is_empty() {
-                    let kd = params.find_spectrum("Kd", Spectrum::new(0.5, 0.5, 0.5));
-                    Ok(Arc::new(ConstantTexture::new(kd)) as Arc<dyn Texture<Spectrum<f32>>>)
-                } else {
-                    if let Some(tex) = textures.get(kd_tex) {
-                        Ok(Arc::clone(tex) as Arc<dyn Texture<Spectrum<f32>>>)
-                    } else {
-                        Err(LoadError::Content(format!(
-                            "Texture '{}' not found",
-                            kd_tex
-                        )))
-                    }
-                }
-            }?;
+            let kd = find_spectrum_texture(params, "Kd", Spectrum::new(0.5, 0.5, 0.5), textures)?;
             // Matte expects sigma as radians instead of degrees
             let sigma = params.find_f32("sigma", 0.0).to_radians();
+            let normal_map = find_normal_map(params, parent_path)?;
+            let bump_map = find_bump_map(params, float_textures)?;
             Ok(Arc::new(Matte::new(
                 kd,
-                Arc::new(ConstantTexture::new(sigma.to_radians())),
+                Arc::new(ConstantTexture::new(sigma)),
+                normal_map,
+                bump_map,
             )) as Arc<dyn Material>)
         }
         "metal" => {
+            let conductor = params.find_string("conductor", "Cu");
+            let spectra = ConductorSpectra::from_name(conductor)
+                .ok_or_else(|| LoadError::Content(format!("Unknown conductor '{}'", conductor)))?;
             let eta = params.find_spectrum(
                 "eta",
-                sampled_spectrum_into_rgb(&COPPER_WAVELENGTHS, &COPPER_N),
+                sampled_spectrum_into_rgb(spectra.wavelengths, spectra.eta),
             );
             let k = params.find_spectrum(
                 "k",
-                sampled_spectrum_into_rgb(&COPPER_WAVELENGTHS, &COPPER_K),
+                sampled_spectrum_into_rgb(spectra.wavelengths, spectra.k),
             );
             let roughness = params.find_f32("roughness", 0.01);
             let remap_roughness = params.find_bool("remaproughness", true);
+            let distribution = find_microfacet_distribution(params)?;
             Ok(Arc::new(Metal::new(
                 Arc::new(ConstantTexture::new(eta)),
                 Arc::new(ConstantTexture::new(k)),
                 Arc::new(ConstantTexture::new(roughness)),
                 remap_roughness,
+                distribution,
+            )) as Arc<dyn Material>)
+        }
+        "plastic" => {
+            let kd =
+                find_spectrum_texture(params, "Kd", Spectrum::new(0.25, 0.25, 0.25), textures)?;
+            let ks =
+                find_spectrum_texture(params, "Ks", Spectrum::new(0.25, 0.25, 0.25), textures)?;
+            let roughness = params.find_f32("roughness", 0.1);
+            let remap_roughness = params.find_bool("remaproughness", true);
+            let normal_map = find_normal_map(params, parent_path)?;
+            let bump_map = find_bump_map(params, float_textures)?;
+            Ok(Arc::new(Plastic::new(
+                kd,
+                ks,
+                Arc::new(ConstantTexture::new(roughness)),
+                remap_roughness,
+                normal_map,
+                bump_map,
+            )) as Arc<dyn Material>)
+        }
+        "substrate" => {
+            let kd = find_spectrum_texture(params, "Kd", Spectrum::new(0.5, 0.5, 0.5), textures)?;
+            let ks = find_spectrum_texture(params, "Ks", Spectrum::new(0.5, 0.5, 0.5), textures)?;
+            let roughness = params.find_f32("roughness", 0.1);
+            let u_roughness = params.find_f32("uroughness", roughness);
+            let v_roughness = params.find_f32("vroughness", roughness);
+            if (u_roughness - v_roughness).abs() > f32::EPSILON {
+                yuki_info!("Anisotropic substrate roughness is not supported. Using the average.");
+            }
+            let remap_roughness = params.find_bool("remaproughness", true);
+            let distribution = find_microfacet_distribution(params)?;
+            let normal_map = find_normal_map(params, parent_path)?;
+            let bump_map = find_bump_map(params, float_textures)?;
+            Ok(Arc::new(Substrate::new(
+                kd,
+                ks,
+                Arc::new(ConstantTexture::new((u_roughness + v_roughness) / 2.0)),
+                remap_roughness,
+                distribution,
+                normal_map,
+                bump_map,
+            )) as Arc<dyn Material>)
+        }
+        "translucent" => {
+            let kd =
+                find_spectrum_texture(params, "Kd", Spectrum::new(0.25, 0.25, 0.25), textures)?;
+            let reflect =
+                find_spectrum_texture(params, "reflect", Spectrum::new(0.5, 0.5, 0.5), textures)?;
+            let transmit =
+                find_spectrum_texture(params, "transmit", Spectrum::new(0.5, 0.5, 0.5), textures)?;
+            let normal_map = find_normal_map(params, parent_path)?;
+            let bump_map = find_bump_map(params, float_textures)?;
+            Ok(Arc::new(Translucent::new(
+                kd, reflect, transmit, normal_map, bump_map,
+            )) as Arc<dyn Material>)
+        }
+        "uber" => {
+            let kd =
+                find_spectrum_texture(params, "Kd", Spectrum::new(0.25, 0.25, 0.25), textures)?;
+            let ks =
+                find_spectrum_texture(params, "Ks", Spectrum::new(0.25, 0.25, 0.25), textures)?;
+            let kr = find_spectrum_texture(params, "Kr", Spectrum::zeros(), textures)?;
+            let kt = find_spectrum_texture(params, "Kt", Spectrum::zeros(), textures)?;
+            let opacity = find_spectrum_texture(params, "opacity", Spectrum::ones(), textures)?;
+            let roughness = params.find_f32("roughness", 0.1);
+            let u_roughness = params.find_f32("uroughness", roughness);
+            let v_roughness = params.find_f32("vroughness", roughness);
+            if (u_roughness - v_roughness).abs() > f32::EPSILON {
+                yuki_info!("Anisotropic uber roughness is not supported. Using the average.");
+            }
+            let eta = params.find_f32("eta", params.find_f32("index", 1.5));
+            let remap_roughness = params.find_bool("remaproughness", true);
+            let distribution = find_microfacet_distribution(params)?;
+            let normal_map = find_normal_map(params, parent_path)?;
+            let bump_map = find_bump_map(params, float_textures)?;
+            Ok(Arc::new(Uber::new(
+                kd,
+                ks,
+                kr,
+                kt,
+                Arc::new(ConstantTexture::new((u_roughness + v_roughness) / 2.0)),
+                opacity,
+                eta,
+                remap_roughness,
+                distribution,
+                normal_map,
+                bump_map,
             )) as Arc<dyn Material>)
         }
         t => {
@@ -930,24 +1884,171 @@ fn get_material(
             Ok(Arc::new(Matte::new(
                 Arc::new(ConstantTexture::new(Spectrum::ones() * 0.5)),
                 Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
             )) as Arc<dyn Material>)
         }
     }
 }
 
+/// Finds the texture referenced by `name` or a constant texture of the spectrum value.
+///
+/// Unknown texture names fall back to `default` with a warning.
+fn find_spectrum_texture(
+    params: &ParamSet,
+    name: &str,
+    default: Spectrum<f32>,
+    textures: &HashMap<String, Arc<dyn Texture<Spectrum<f32>>>>,
+) -> Result<Arc<dyn Texture<Spectrum<f32>>>, LoadError> {
+    let tex_name = params.find_string(name, "");
+    if tex_name.is_empty() {
+        let value = params.find_spectrum(name, default);
+        Ok(Arc::new(ConstantTexture::new(value)))
+    } else if let Some(tex) = textures.get(tex_name) {
+        Ok(Arc::clone(tex))
+    } else {
+        yuki_warn!(
+            "Texture '{}' for '{}' not found, using a constant",
+            tex_name,
+            name
+        );
+        Ok(Arc::new(ConstantTexture::new(default)))
+    }
+}
+
+/// Applies the mapping, filtering and wrap parameters of image texture `name` to `texture`.
+fn configure_image_texture<T>(
+    texture: ImageTexture<T>,
+    params: &ParamSet,
+    name: &str,
+) -> Result<ImageTexture<T>, LoadError>
+where
+    T: Copy + Send + Sync,
+{
+    let uv_transform = UvTransform::new(
+        Vec2::new(
+            params.find_f32("uscale", 1.0),
+            params.find_f32("vscale", 1.0),
+        ),
+        Vec2::new(
+            params.find_f32("udelta", 0.0),
+            params.find_f32("vdelta", 0.0),
+        ),
+    );
+
+    // pbrt-v4 filters bilinearly by default, mip-mapped filters aren't supported
+    let filter_mode = match params.find_string("filter", "bilinear") {
+        "point" => FilterMode::Nearest,
+        _ => FilterMode::Bilinear,
+    };
+
+    let wrap_mode = match params.find_string("wrap", "repeat") {
+        "repeat" => WrapMode::Repeat,
+        "clamp" => WrapMode::Clamp,
+        "black" => WrapMode::Black,
+        wrap => {
+            return Err(LoadError::Content(format!(
+                "unknown wrap mode '{}' for texture '{}'",
+                wrap, name
+            )))
+        }
+    };
+
+    Ok(texture
+        .with_uv_transform(uv_transform)
+        .with_filter_mode(filter_mode)
+        .with_wrap_mode(wrap_mode))
+}
+
+/// Finds the float texture referenced by `name` or a constant texture of the float value.
+///
+/// Unknown texture names fall back to `default` with a warning.
+fn find_float_texture(
+    params: &ParamSet,
+    name: &str,
+    default: f32,
+    float_textures: &HashMap<String, Arc<dyn Texture<f32>>>,
+) -> Result<Arc<dyn Texture<f32>>, LoadError> {
+    let tex_name = params.find_string(name, "");
+    if tex_name.is_empty() {
+        let value = params.find_f32(name, default);
+        Ok(Arc::new(ConstantTexture::new(value)))
+    } else if let Some(tex) = float_textures.get(tex_name) {
+        Ok(Arc::clone(tex))
+    } else {
+        yuki_warn!(
+            "Texture '{}' for '{}' not found, using a constant",
+            tex_name,
+            name
+        );
+        Ok(Arc::new(ConstantTexture::new(default)))
+    }
+}
+
+/// Finds the float texture referenced by the `"bumpmap"` param, if any.
+fn find_bump_map(
+    params: &ParamSet,
+    float_textures: &HashMap<String, Arc<dyn Texture<f32>>>,
+) -> Result<Option<Arc<dyn Texture<f32>>>, LoadError> {
+    if params.find_string("bumpmap", "").is_empty() {
+        Ok(None)
+    } else {
+        find_float_texture(params, "bumpmap", 0.0, float_textures).map(Some)
+    }
+}
+
+/// Finds the float texture or constant referenced by the `"alpha"` param of a shape, if any.
+///
+/// A constant alpha of one doesn't mask anything and is ignored.
+fn find_alpha(
+    params: &ParamSet,
+    float_textures: &HashMap<String, Arc<dyn Texture<f32>>>,
+) -> Result<Option<Arc<dyn Texture<f32>>>, LoadError> {
+    if params.find_string("alpha", "").is_empty() && params.find_f32("alpha", 1.0) >= 1.0 {
+        Ok(None)
+    } else {
+        find_float_texture(params, "alpha", 1.0, float_textures).map(Some)
+    }
+}
+
+/// Parses the microfacet distribution type, defaulting to GGX.
+fn find_microfacet_distribution(
+    params: &ParamSet,
+) -> Result<MicrofacetDistributionType, LoadError> {
+    match params.find_string("distribution", "ggx") {
+        "ggx" | "trowbridge_reitz" => Ok(MicrofacetDistributionType::TrowbridgeReitz),
+        "beckmann" => Ok(MicrofacetDistributionType::Beckmann),
+        d => Err(LoadError::Content(format!(
+            "Unknown microfacet distribution '{}'",
+            d
+        ))),
+    }
+}
+
+/// Loads the tangent-space normal map referenced by the `"normalmap"` param, if any.
+fn find_normal_map(
+    params: &ParamSet,
+    parent_path: &Path,
+) -> Result<Option<Arc<dyn Texture<Normal<f32>>>>, LoadError> {
+    let filename = params.find_string("normalmap", "");
+    if filename.is_empty() {
+        return Ok(None);
+    }
+
+    let path = parent_path.join(PathBuf::from(filename));
+    let image = ImageTexture::new(&path).map_err(LoadError::Image)?;
+    Ok(Some(Arc::new(NormalMapTexture::new(image))))
+}
+
 struct FileScope {
-    lexer: Lexer,
+    lexer: Lexer<BufReader<File>>,
     path: PathBuf,
     parent_path: PathBuf,
 }
 
 impl FileScope {
     fn new(path: &PathBuf) -> Result<FileScope, LoadError> {
-        // TODO: Very large files should be read on the fly, not as a whole
-        let input: Vec<u8> = std::fs::read_to_string(path)
-            .map_err(LoadError::Io)?
-            .bytes()
-            .collect();
+        let file = File::open(path).map_err(LoadError::Io)?;
 
         let parent_path = path
             .parent()
@@ -955,7 +2056,7 @@ impl FileScope {
             .into();
 
         Ok(FileScope {
-            lexer: Lexer::new(input),
+            lexer: Lexer::new(BufReader::new(file)),
             path: path.clone(),
             parent_path,
         })
@@ -976,7 +2077,7 @@ fn try_split_param_def(def: &str) -> Option<(String, String)> {
     }
 }
 
-fn sampled_spectrum_into_rgb(lambda: &[f32], samples: &[f32]) -> Spectrum<f32> {
+pub(super) fn sampled_spectrum_into_rgb(lambda: &[f32], samples: &[f32]) -> Spectrum<f32> {
     assert!(
         lambda.len() == samples.len(),
         "Sample count doesn't match the number of wavelengths"
@@ -992,8 +2093,8 @@ fn sampled_spectrum_into_rgb(lambda: &[f32], samples: &[f32]) -> Spectrum<f32> {
             sorted_samples[i] = *p.1;
         }
 
-        sampled_spectrum_into_rgb(&sorted_lambda, &sorted_samples);
-    };
+        return sampled_spectrum_into_rgb(&sorted_lambda, &sorted_samples);
+    }
 
     // Riemann sum
     let mut xyz = (0.0, 0.0, 0.0);
@@ -1024,82 +2125,413 @@ fn is_sorted<T: PartialOrd + Copy>(values: &[T]) -> bool {
     true
 }
 
-const N_COPPER_SAMPLES: usize = 56;
-#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
-const COPPER_WAVELENGTHS: [f32; N_COPPER_SAMPLES] = [
-    298.7570554,
-    302.4004341,
-    306.1337728,
-    309.960445,
-    313.8839949,
-    317.9081487,
-    322.036826,
-    326.2741526,
-    330.6244747,
-    335.092373,
-    339.6826795,
-    344.4004944,
-    349.2512056,
-    354.2405086,
-    359.374429,
-    364.6593471,
-    370.1020239,
-    375.7096303,
-    381.4897785,
-    387.4505563,
-    393.6005651,
-    399.9489613,
-    406.5055016,
-    413.2805933,
-    420.2853492,
-    427.5316483,
-    435.0322035,
-    442.8006357,
-    450.8515564,
-    459.2006593,
-    467.8648226,
-    476.8622231,
-    486.2124627,
-    495.936712,
-    506.0578694,
-    516.6007417,
-    527.5922468,
-    539.0616435,
-    551.0407911,
-    563.5644455,
-    576.6705953,
-    590.4008476,
-    604.8008683,
-    619.92089,
-    635.8162974,
-    652.5483053,
-    670.1847459,
-    688.8009889,
-    708.4810171,
-    729.3186941,
-    751.4192606,
-    774.9011125,
-    799.8979226,
-    826.5611867,
-    855.0632966,
-    885.6012714,
-];
-
-#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
-const COPPER_N: [f32; N_COPPER_SAMPLES] = [
-    1.400313, 1.38, 1.358438, 1.34, 1.329063, 1.325, 1.3325, 1.34, 1.334375, 1.325, 1.317812, 1.31,
-    1.300313, 1.29, 1.281563, 1.27, 1.249062, 1.225, 1.2, 1.18, 1.174375, 1.175, 1.1775, 1.18,
-    1.178125, 1.175, 1.172812, 1.17, 1.165312, 1.16, 1.155312, 1.15, 1.142812, 1.135, 1.131562,
-    1.12, 1.092437, 1.04, 0.950375, 0.826, 0.645875, 0.468, 0.35125, 0.272, 0.230813, 0.214,
-    0.20925, 0.213, 0.21625, 0.223, 0.2365, 0.25, 0.254188, 0.26, 0.28, 0.3,
-];
-
-#[allow(clippy::excessive_precision, clippy::unreadable_literal)] // In case f64 is used at some point
-const COPPER_K: [f32; N_COPPER_SAMPLES] = [
-    1.662125, 1.687, 1.703313, 1.72, 1.744563, 1.77, 1.791625, 1.81, 1.822125, 1.834, 1.85175,
-    1.872, 1.89425, 1.916, 1.931688, 1.95, 1.972438, 2.015, 2.121562, 2.21, 2.177188, 2.13,
-    2.160063, 2.21, 2.249938, 2.289, 2.326, 2.362, 2.397625, 2.433, 2.469187, 2.504, 2.535875,
-    2.564, 2.589625, 2.605, 2.595562, 2.583, 2.5765, 2.599, 2.678062, 2.809, 3.01075, 3.24,
-    3.458187, 3.67, 3.863125, 4.05, 4.239563, 4.43, 4.619563, 4.817, 5.034125, 5.26, 5.485625,
-    5.717,
-];
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::Path,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    use super::{get_material, load, param_set::ParamSet, sampled_spectrum_into_rgb, LoadError};
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::{BxdfType, ConductorSpectra, Material, Matte},
+        math::{Point2, Point3, Ray, Spectrum, Transform, Vec3},
+        scene::SceneLoadSettings,
+        shapes::Sphere,
+        textures::{ConstantTexture, Texture},
+    };
+    use allocators::{LinearAllocator, ScopedScratch};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn named_texture_matches_inline_constant() {
+        let kd = Spectrum::new(0.2, 0.4, 0.6);
+
+        let mut textures: HashMap<String, Arc<dyn Texture<Spectrum<f32>>>> = HashMap::new();
+        textures.insert("albedo".into(), Arc::new(ConstantTexture::new(kd)));
+        let float_textures = HashMap::new();
+
+        let mut named_params = ParamSet::default();
+        named_params.add_string("Kd".into(), vec!["albedo".into()]);
+        let mut inline_params = ParamSet::default();
+        inline_params.add_spectrum("Kd".into(), vec![kd]);
+
+        let named = get_material(
+            "matte",
+            &named_params,
+            &textures,
+            &float_textures,
+            Path::new(""),
+        )
+        .unwrap();
+        let inline = get_material(
+            "matte",
+            &inline_params,
+            &textures,
+            &float_textures,
+            Path::new(""),
+        )
+        .unwrap();
+
+        let shape = Sphere::new(&Transform::default(), 1.0, Arc::clone(&inline));
+        let si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::new(0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let f = |material: &Arc<dyn Material>| {
+            material.compute_scattering_functions(&scratch, &si).f(
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.6, 0.8),
+                BxdfType::all(),
+            )
+        };
+        let (f_named, f_inline) = (f(&named), f(&inline));
+        assert!(f_inline.g > 0.0);
+        assert_eq!(f_named.r, f_inline.r);
+        assert_eq!(f_named.g, f_inline.g);
+        assert_eq!(f_named.b, f_inline.b);
+    }
+
+    #[test]
+    fn matte_sigma_is_converted_once() {
+        let mut params = ParamSet::default();
+        params.add_f32("sigma".into(), vec![20.0]);
+        let parsed = get_material(
+            "matte",
+            &params,
+            &HashMap::new(),
+            &HashMap::new(),
+            Path::new(""),
+        )
+        .unwrap();
+
+        let matte = |sigma: f32| -> Arc<dyn Material> {
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::new(0.5, 0.5, 0.5))),
+                Arc::new(ConstantTexture::new(sigma)),
+                None,
+                None,
+            ))
+        };
+        // ~0.349 rad
+        let expected = matte(20.0f32.to_radians());
+        let doubly_converted = matte(20.0f32.to_radians().to_radians());
+
+        let shape = Sphere::new(&Transform::default(), 1.0, Arc::clone(&parsed));
+        let si = SurfaceInteraction::new(
+            Point3::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Point2::new(0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let f = |material: &Arc<dyn Material>| {
+            material.compute_scattering_functions(&scratch, &si).f(
+                Vec3::new(0.6, 0.0, 0.8),
+                Vec3::new(0.0, 0.6, 0.8),
+                BxdfType::all(),
+            )
+        };
+        assert_eq!(f(&parsed), f(&expected));
+        assert_ne!(f(&parsed), f(&doubly_converted));
+    }
+
+    #[test]
+    fn copper_preset_matches_default_metal() {
+        let copper = ConductorSpectra::from_name("Cu").unwrap();
+        let eta = sampled_spectrum_into_rgb(copper.wavelengths, copper.eta);
+        let k = sampled_spectrum_into_rgb(copper.wavelengths, copper.k);
+
+        // Converted from the copper spectra in pbrt-v3's metal.cpp like before the presets
+        assert_relative_eq!(
+            eta,
+            Spectrum::new(7.358_718, 86.180_68, 155.271),
+            max_relative = 1e-3
+        );
+        assert_relative_eq!(
+            k,
+            Spectrum::new(265.559_35, 230.962_29, 307.721_15),
+            max_relative = 1e-3
+        );
+
+        for conductor in ["Au", "Ag", "Al"] {
+            let spectra = ConductorSpectra::from_name(conductor).unwrap();
+            assert_eq!(spectra.wavelengths.len(), spectra.eta.len());
+            assert_eq!(spectra.wavelengths.len(), spectra.k.len());
+        }
+        assert!(ConductorSpectra::from_name("Fe").is_none());
+    }
+
+    #[test]
+    fn unsorted_spectrum_matches_sorted() {
+        let copper = ConductorSpectra::from_name("Cu").unwrap();
+        let sorted = sampled_spectrum_into_rgb(copper.wavelengths, copper.eta);
+
+        // Reverse and swap pairs to get something that isn't trivially in either order
+        let mut pairs: Vec<(f32, f32)> = copper
+            .wavelengths
+            .iter()
+            .copied()
+            .zip(copper.eta.iter().copied())
+            .rev()
+            .collect();
+        for chunk in pairs.chunks_exact_mut(2) {
+            chunk.swap(0, 1);
+        }
+        let (lambda, samples): (Vec<f32>, Vec<f32>) = pairs.into_iter().unzip();
+        let shuffled = sampled_spectrum_into_rgb(&lambda, &samples);
+
+        assert_eq!(shuffled, sorted);
+    }
+
+    #[test]
+    fn cancelled_load_returns_error() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_cancel_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            "WorldBegin\nShape \"sphere\" \"float radius\" [1]\nWorldEnd\n",
+        )
+        .unwrap();
+
+        // The flag is checked before every token so a set flag stops the parse right away
+        let cancelled = load(&settings, &AtomicBool::new(true));
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(cancelled, Err(LoadError::Cancelled)));
+        assert_eq!(loaded.unwrap().0.shapes.len(), 1);
+    }
+
+    #[test]
+    fn object_instances_share_shapes() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_instance_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            "WorldBegin\n\
+             ObjectBegin \"tri\"\n\
+             Shape \"trianglemesh\" \"point P\" [-1 -1 0 1 -1 0 0 1 0]\n\
+             \"integer indices\" [0 1 2]\n\
+             ObjectEnd\n\
+             AttributeBegin\nTranslate -3 0 0\nObjectInstance \"tri\"\nAttributeEnd\n\
+             AttributeBegin\nTranslate 3 0 0\nObjectInstance \"tri\"\nAttributeEnd\n\
+             WorldEnd\n",
+        )
+        .unwrap();
+
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = loaded.unwrap().0;
+        // The object's triangle is only stored once and not placed without an instance
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.shapes.len(), 2);
+        let bounds = scene.bvh.bounds();
+        assert!((bounds.p_min.x + 4.0).abs() < 1e-5);
+        assert!((bounds.p_max.x - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn alpha_cuts_out_triangles() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_alpha_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            "WorldBegin\n\
+             Shape \"trianglemesh\" \"point P\" [-1 -1 0 1 -1 0 0 1 0]\n\
+             \"integer indices\" [0 1 2] \"float alpha\" 0\n\
+             Shape \"trianglemesh\" \"point P\" [-1 -1 -1 1 -1 -1 0 1 -1]\n\
+             \"integer indices\" [0 1 2] \"float alpha\" 1\n\
+             WorldEnd\n",
+        )
+        .unwrap();
+
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = loaded.unwrap().0;
+        let ray = Ray::new(
+            Point3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        let hit = scene.bvh.intersect(ray).hit.unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn emissive_bilinearmesh_gets_lights() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_bilinear_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            "WorldBegin\n\
+             AttributeBegin\n\
+             AreaLightSource \"diffuse\" \"rgb L\" [1 1 1]\n\
+             Shape \"bilinearmesh\" \"point P\" [-1 -1 0 1 -1 0 -1 1 0 1 1 0]\n\
+             AttributeEnd\n\
+             Shape \"bilinearmesh\" \"point P\" [-1 -1 -1 1 -1 -1 -1 1 -1 1 1 -1]\n\
+             \"float alpha\" 0\n\
+             WorldEnd\n",
+        )
+        .unwrap();
+
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = loaded.unwrap().0;
+        // Both patches are split into emissive or masked triangles
+        assert_eq!(scene.shapes.len(), 4);
+        assert_eq!(scene.lights.len(), 2);
+        let ray = Ray::new(
+            Point3::new(0.5, -0.25, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        let hit = scene.bvh.intersect(ray).hit.unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert!(hit.si.area_light.is_some());
+
+        let below = Ray::new(
+            Point3::new(0.5, -0.25, -0.5),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        assert!(scene.bvh.intersect(below).hit.is_none());
+    }
+
+    #[test]
+    fn camera_medium_fills_scene() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_medium_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        std::fs::write(
+            &settings.path,
+            "MakeNamedMedium \"fog\" \"string type\" \"homogeneous\"\n\
+             \"rgb sigma_a\" [0.5 0.5 0.5] \"rgb sigma_s\" [0 0 0] \"float scale\" 2\n\
+             MediumInterface \"\" \"fog\"\n\
+             Camera \"perspective\"\n\
+             WorldBegin\n\
+             Shape \"sphere\"\n\
+             WorldEnd\n",
+        )
+        .unwrap();
+
+        let loaded = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let medium = loaded.unwrap().0.medium.unwrap();
+        let tr = medium.tr(Ray::new(Point3::zeros(), Vec3::new(0.0, 0.0, 1.0), 1.0));
+        assert!((tr.g - (-1.0f32).exp()).abs() < 1e-5, "{:?}", tr);
+    }
+
+    #[test]
+    fn named_coordinate_system_round_trips() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_coordsys_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        let scene = |coordsys: &str| {
+            std::fs::write(
+                &settings.path,
+                format!(
+                    "WorldBegin\n\
+                     Translate 1 2 3\nCoordinateSystem \"offset\"\n\
+                     Scale 5 5 5\nCoordSysTransform \"{}\"\n\
+                     Shape \"sphere\" \"float radius\" [1]\n\
+                     WorldEnd\n",
+                    coordsys
+                ),
+            )
+            .unwrap();
+            load(&settings, &AtomicBool::new(false))
+        };
+
+        let restored = scene("offset");
+        let unknown = scene("offest");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The scale after saving the system is dropped by the restore
+        let bounds = restored.unwrap().0.bvh.bounds();
+        assert!((bounds.p_min - Point3::new(0.0, 1.0, 2.0)).len() < 1e-5);
+        assert!((bounds.p_max - Point3::new(2.0, 3.0, 4.0)).len() < 1e-5);
+
+        let why = match unknown {
+            Err(why @ LoadError::Parser(_)) => why,
+            Err(why) => panic!("Expected a parser error, got '{}'", why),
+            Ok(_) => panic!("Expected a parser error"),
+        };
+        assert!(why
+            .to_string()
+            .contains("Unknown coordinate system 'offest'"));
+    }
+
+    #[test]
+    fn parser_error_points_at_token() {
+        let dir = std::env::temp_dir().join(format!("yuki_pbrt_error_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = SceneLoadSettings {
+            path: dir.join("scene.pbrt"),
+            ..SceneLoadSettings::default()
+        };
+        // Shape expects its type as a string
+        std::fs::write(
+            &settings.path,
+            "WorldBegin\n  Shape 1 \"sphere\"\nWorldEnd\n",
+        )
+        .unwrap();
+
+        let result = load(&settings, &AtomicBool::new(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let why = match result {
+            Err(why @ LoadError::Parser(_)) => why,
+            Err(why) => panic!("Expected a parser error, got '{}'", why),
+            Ok(_) => panic!("Expected a parser error"),
+        };
+        assert_eq!(
+            why.to_string(),
+            format!(
+                "{}:2:9: Unexpected token 'Number(1.0)'\n      Shape 1 \"sphere\"\n            ^",
+                settings.path.to_string_lossy()
+            )
+        );
+    }
+}
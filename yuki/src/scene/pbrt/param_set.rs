@@ -62,6 +62,10 @@ impl ParamSet {
         find_param_value(name, &self.floats, default)
     }
 
+    pub fn find_f32s<'a>(&'a self, name: &str, default: &'a [f32]) -> &'a [f32] {
+        find_param_values(name, &self.floats, default)
+    }
+
     pub fn find_i32(&self, name: &str, default: i32) -> i32 {
         find_param_value(name, &self.integers, default)
     }
@@ -6,11 +6,31 @@ use crate::{
         Bounds3, Normal, Point2, Point3, Transform, Vec3,
     },
     shapes::{Mesh, Shape, Triangle},
-    yuki_error, yuki_info, yuki_trace,
+    yuki_error, yuki_info, yuki_trace, yuki_warn,
 };
 
 use std::{collections::HashSet, path::Path, sync::Arc, time::Instant};
 
+/// Edges sharper than this keep hard normals when normals are computed for an OBJ or a glTF mesh
+pub const CREASE_ANGLE_DEGREES: f32 = 60.0;
+
+const SUPPORTED_VERTEX_PROPERTIES: [&str; 14] = [
+    "x",
+    "y",
+    "z",
+    "nx",
+    "ny",
+    "nz",
+    "u",
+    "v",
+    "s",
+    "t",
+    "texture_u",
+    "texture_v",
+    "texture_s",
+    "texture_t",
+];
+
 pub struct PlyResult {
     pub mesh: Arc<Mesh>,
     pub shapes: Vec<Arc<dyn Shape>>,
@@ -20,6 +40,7 @@ pub fn load(
     path: &Path,
     material: &Arc<dyn Material>,
     transform: Option<Transform<f32>>,
+    crease_angle_degrees: Option<f32>,
 ) -> Result<PlyResult> {
     let file = match std::fs::File::open(path.to_str().unwrap()) {
         Ok(f) => f,
@@ -64,7 +85,16 @@ pub fn load(
     let mut points = Vec::new();
     let mut normals = Vec::new();
     let mut uvs = Vec::new();
-    for Vertex { point, normal, uv } in vertices {
+    for Vertex {
+        point,
+        normal,
+        uv,
+        error,
+    } in vertices
+    {
+        if let Some(why) = error {
+            return Err(format!("PLY: {}", why).into());
+        }
         points.push(point);
         if let Some(n) = normal {
             normals.push(n);
@@ -73,6 +103,14 @@ pub fn load(
             uvs.push(uv);
         }
     }
+    if !normals.is_empty() && normals.len() != points.len() {
+        yuki_warn!("PLY: Only some vertices have normals, ignoring them");
+        normals.clear();
+    }
+    if !uvs.is_empty() && uvs.len() != points.len() {
+        yuki_warn!("PLY: Only some vertices have uvs, ignoring them");
+        uvs.clear();
+    }
     yuki_trace!(
         "PLY: Extracted vertex attributes in {:.2}s",
         vertices_start.elapsed().as_secs_f32()
@@ -81,21 +119,59 @@ pub fn load(
     let indices_start = Instant::now();
     let mut indices = Vec::new();
     for f in faces {
-        let v0 = f.indices[0];
-        let mut is = f.indices.iter().skip(1).peekable();
-        while let Some(&v1) = is.next() {
-            if let Some(&&v2) = is.peek() {
-                indices.push(v0);
-                indices.push(v1);
-                indices.push(v2);
-            }
+        let face = f.indices.map_err(|why| format!("PLY: {}", why))?;
+        if face.len() < 3 {
+            return Err(format!("PLY: Face with {} vertices", face.len()).into());
         }
+        if let Some(&i) = face.iter().find(|&&i| i >= points.len()) {
+            return Err(format!("PLY: Vertex index {} is out of bounds", i).into());
+        }
+        triangulate(&face, &mut indices);
     }
     yuki_trace!(
         "PLY: Converted faces to an index buffer in {:.2}s",
         indices_start.elapsed().as_secs_f32()
     );
 
+    Ok(triangle_mesh(
+        "PLY",
+        indices,
+        points,
+        normals,
+        uvs,
+        material,
+        transform,
+        crease_angle_degrees,
+    ))
+}
+
+/// Appends the triangle fan of the polygon `face` into `indices`.
+pub fn triangulate(face: &[usize], indices: &mut Vec<usize>) {
+    let v0 = face[0];
+    let mut is = face.iter().skip(1).peekable();
+    while let Some(&v1) = is.next() {
+        if let Some(&&v2) = is.peek() {
+            indices.push(v0);
+            indices.push(v1);
+            indices.push(v2);
+        }
+    }
+}
+
+/// Creates the [`Mesh`] and its triangles from the vertex attributes.
+///
+/// The mesh is scaled to unit size around the origin if `transform` is `None`. Smooth normals are
+/// computed if `normals` is empty and `crease_angle_degrees` is given.
+pub fn triangle_mesh(
+    log_prefix: &str,
+    indices: Vec<usize>,
+    points: Vec<Point3<f32>>,
+    normals: Vec<Normal<f32>>,
+    uvs: Vec<Point2<f32>>,
+    material: &Arc<dyn Material>,
+    transform: Option<Transform<f32>>,
+    crease_angle_degrees: Option<f32>,
+) -> PlyResult {
     // Find bounds and transform to fit in (-1,-1,-1),(1,1,1) in world space
     let bb = points
         .iter()
@@ -106,7 +182,19 @@ pub fn load(
     let trfn = transform.unwrap_or(
         &scale(mesh_scale, mesh_scale, mesh_scale) * &translation(-Vec3::from(mesh_center)),
     );
-    let mesh = Arc::new(Mesh::new(&trfn, indices, points, normals, uvs));
+    let mesh = Mesh::new(&trfn, indices, points, normals, uvs);
+    let mesh = if let (true, Some(crease_angle)) = (mesh.normals.is_empty(), crease_angle_degrees) {
+        let normals_start = Instant::now();
+        let mesh = mesh.with_computed_smooth_normals(crease_angle.to_radians());
+        yuki_trace!(
+            "{}: Computed smooth normals in {:.2}s",
+            log_prefix,
+            normals_start.elapsed().as_secs_f32()
+        );
+        Arc::new(mesh)
+    } else {
+        Arc::new(mesh)
+    };
 
     let triangles_start = Instant::now();
     let shapes: Vec<Arc<dyn Shape>> = (0..mesh.indices.len())
@@ -121,12 +209,13 @@ pub fn load(
         })
         .collect();
     yuki_trace!(
-        "PLY: Gathered {} triangles in {:.2}s",
+        "{}: Gathered {} triangles in {:.2}s",
+        log_prefix,
         shapes.len(),
         triangles_start.elapsed().as_secs_f32()
     );
 
-    Ok(PlyResult { mesh, shapes })
+    PlyResult { mesh, shapes }
 }
 
 struct PlyContent {
@@ -169,7 +258,6 @@ fn is_valid(header: &ply_rs::ply::Header) -> bool {
 
     if let Some(props) = content.vertex {
         let expected_vert_props = vec!["x", "y", "z"];
-        let supported_vert_props = vec!["x", "y", "z", "nx", "ny", "nz", "u", "v"];
         for p in &expected_vert_props {
             if !props.contains(&(*p).to_string()) {
                 yuki_error!("PLY: Element 'vertex' missing property '{}'", p);
@@ -177,7 +265,7 @@ fn is_valid(header: &ply_rs::ply::Header) -> bool {
             }
         }
         for p in props.difference(
-            &supported_vert_props
+            &SUPPORTED_VERTEX_PROPERTIES
                 .iter()
                 .map(|p| (*p).to_string())
                 .collect(),
@@ -214,10 +302,43 @@ fn is_valid(header: &ply_rs::ply::Header) -> bool {
     valid
 }
 
+/// Returns the value of a scalar `property` as `f32` or `None` if it is a list.
+fn scalar_f32(property: &ply_rs::ply::Property) -> Option<f32> {
+    use ply_rs::ply::Property;
+
+    match *property {
+        Property::Char(v) => Some(v as f32),
+        Property::UChar(v) => Some(v as f32),
+        Property::Short(v) => Some(v as f32),
+        Property::UShort(v) => Some(v as f32),
+        Property::Int(v) => Some(v as f32),
+        Property::UInt(v) => Some(v as f32),
+        Property::Float(v) => Some(v),
+        Property::Double(v) => Some(v as f32),
+        _ => None,
+    }
+}
+
+/// Converts the face index list `values` into `usize` indices.
+fn list_indices<T>(values: &[T]) -> std::result::Result<Vec<usize>, String>
+where
+    T: Copy + TryInto<usize> + std::fmt::Display,
+{
+    values
+        .iter()
+        .map(|&i| {
+            i.try_into()
+                .map_err(|_| format!("Invalid vertex index {}", i))
+        })
+        .collect()
+}
+
 struct Vertex {
     point: Point3<f32>,
     normal: Option<Normal<f32>>,
     uv: Option<Point2<f32>>,
+    /// Set if a property couldn't be read since the parser has no way to return errors
+    error: Option<String>,
 }
 
 impl ply_rs::ply::PropertyAccess for Vertex {
@@ -226,59 +347,276 @@ impl ply_rs::ply::PropertyAccess for Vertex {
             point: Point3::zeros(),
             normal: None,
             uv: None,
+            error: None,
         }
     }
 
     fn set_property(&mut self, key: &str, property: ply_rs::ply::Property) {
-        if let ply_rs::ply::Property::Float(v) = property {
-            match key {
-                "x" => self.point.x = v,
-                "y" => self.point.y = v,
-                "z" => self.point.z = v,
-                // TODO: Do relevant plys have nx first?
-                "nx" => {
-                    self.normal = Some(Normal::new(0.0, 0.0, 0.0));
-                    self.normal.as_mut().unwrap().x = v;
+        let v = match scalar_f32(&property) {
+            Some(v) => v,
+            None => {
+                if SUPPORTED_VERTEX_PROPERTIES.contains(&key) {
+                    self.error = Some(format!("Vertex property '{}' is a list", key));
                 }
-                "ny" => self.normal.as_mut().unwrap().y = v,
-                "nz" => self.normal.as_mut().unwrap().z = v,
-                // TODO: Do relevant plys have u first?
-                "u" => self.uv = Some(Point2::new(v, 0.0)),
-                "v" => self.uv.as_mut().unwrap().y = v,
-                _ => (),
+                return;
+            }
+        };
+        // Components can come in any order so the attributes are created by whichever is first
+        match key {
+            "x" => self.point.x = v,
+            "y" => self.point.y = v,
+            "z" => self.point.z = v,
+            "nx" => self.normal.get_or_insert_with(Normal::zeros).x = v,
+            "ny" => self.normal.get_or_insert_with(Normal::zeros).y = v,
+            "nz" => self.normal.get_or_insert_with(Normal::zeros).z = v,
+            // Exporters disagree on the names of texture coordinates
+            "u" | "s" | "texture_u" | "texture_s" => {
+                self.uv.get_or_insert_with(Point2::zeros).x = v;
             }
+            "v" | "t" | "texture_v" | "texture_t" => {
+                self.uv.get_or_insert_with(Point2::zeros).y = v;
+            }
+            _ => (),
         }
     }
 }
 
 struct Face {
-    indices: Vec<usize>,
+    /// An error if the index list is missing or couldn't be read since the parser has no way to
+    /// return errors
+    indices: std::result::Result<Vec<usize>, String>,
 }
 
 impl ply_rs::ply::PropertyAccess for Face {
     fn new() -> Self {
         Self {
-            indices: Vec::new(),
+            indices: Err("Face is missing its vertex index list".into()),
         }
     }
 
     fn set_property(&mut self, key: &str, property: ply_rs::ply::Property) {
-        match key {
-            // For some reason (Paul Bourke's example?), PLYs come with one of two different
-            // names for face indices
-            "vertex_index" | "vertex_indices" => match property {
-                ply_rs::ply::Property::ListInt(v) => {
-                    self.indices = v
-                        .iter()
-                        .map(|&i| usize::try_from(i).expect("Negative PLY index"))
-                        .collect();
+        use ply_rs::ply::Property;
+
+        // For some reason (Paul Bourke's example?), PLYs come with one of two different
+        // names for face indices
+        if key == "vertex_index" || key == "vertex_indices" {
+            // Exporters use any integer type for the indices, e.g. uchar count and int indices
+            self.indices = match property {
+                Property::ListChar(v) => list_indices(&v),
+                Property::ListUChar(v) => list_indices(&v),
+                Property::ListShort(v) => list_indices(&v),
+                Property::ListUShort(v) => list_indices(&v),
+                Property::ListInt(v) => list_indices(&v),
+                Property::ListUInt(v) => list_indices(&v),
+                Property::ListFloat(_) | Property::ListDouble(_) => {
+                    Err(format!("Face property '{}' has non-integer indices", key))
                 }
-                ply_rs::ply::Property::ListUInt(v) => {
-                    self.indices = v.iter().map(|&i| i as usize).collect();
+                _ => Err(format!("Face property '{}' is not a list", key)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::load;
+    use crate::{
+        materials::{Material, Matte},
+        math::{Normal, Point2, Spectrum, Transform},
+        textures::ConstantTexture,
+    };
+
+    fn white() -> Arc<dyn Material> {
+        Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ))
+    }
+
+    /// Writes `contents` into a temporary PLY and loads it
+    fn load_bytes(name: &str, contents: &[u8]) -> super::Result<super::PlyResult> {
+        let path =
+            std::env::temp_dir().join(format!("yuki_ply_{}_{}.ply", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let loaded = load(&path, &white(), Some(Transform::default()), None);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn binary_matches_ascii() {
+        let points = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.5],
+        ];
+        let header = |format: &str| {
+            format!(
+                "ply
+format {} 1.0
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+",
+                format
+            )
+        };
+        let binary =
+            |format: &str, to_bytes: fn(f32) -> [u8; 4], index_bytes: fn(i32) -> [u8; 4]| {
+                let mut bytes = header(format).into_bytes();
+                for p in &points {
+                    for &c in p {
+                        bytes.extend_from_slice(&to_bytes(c));
+                    }
                 }
-                _ => (),
-            },
-            _ => (),
+                bytes.push(4);
+                for i in 0..4 {
+                    bytes.extend_from_slice(&index_bytes(i));
+                }
+                bytes
+            };
+
+        let mut ascii = header("ascii");
+        for p in &points {
+            ascii += &format!("{} {} {}\n", p[0], p[1], p[2]);
+        }
+        ascii += "4 0 1 2 3\n";
+
+        let ascii = load_bytes("ascii", ascii.as_bytes()).unwrap().mesh;
+        assert_eq!(ascii.indices, vec![0, 1, 2, 0, 2, 3]);
+        for (name, bytes) in [
+            (
+                "le",
+                binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes),
+            ),
+            (
+                "be",
+                binary("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes),
+            ),
+        ] {
+            let mesh = load_bytes(name, &bytes).unwrap().mesh;
+            assert_eq!(mesh.points, ascii.points, "{}", name);
+            assert_eq!(mesh.indices, ascii.indices, "{}", name);
         }
     }
+
+    #[test]
+    fn invalid_faces_are_errors() {
+        let ply = |face_property: &str, face: &str| {
+            format!(
+                "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+{}
+end_header
+0 0 0
+1 0 0
+0 1 0
+{}
+",
+                face_property, face
+            )
+        };
+
+        let float_indices = ply("property list uchar float vertex_indices", "3 0 1 2");
+        assert!(load_bytes("float", float_indices.as_bytes()).is_err());
+
+        let negative = ply("property list uchar int vertex_indices", "3 0 -1 2");
+        assert!(load_bytes("negative", negative.as_bytes()).is_err());
+
+        let out_of_bounds = ply("property list uchar int vertex_indices", "3 0 1 3");
+        assert!(load_bytes("bounds", out_of_bounds.as_bytes()).is_err());
+
+        let not_list = ply("property int vertex_indices", "0");
+        assert!(load_bytes("scalar", not_list.as_bytes()).is_err());
+
+        let valid = ply("property list uchar uint vertex_index", "3 0 1 2");
+        assert!(load_bytes("valid", valid.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn normals_are_smoothed_on_request() {
+        let path = std::env::temp_dir().join(format!("yuki_ply_smooth_{}.ply", std::process::id()));
+        std::fs::write(
+            &path,
+            "ply
+format ascii 1.0
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+4 0 1 2 3
+",
+        )
+        .unwrap();
+        let geometric = load(&path, &white(), Some(Transform::default()), None);
+        let smooth = load(&path, &white(), Some(Transform::default()), Some(60.0));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(geometric.unwrap().mesh.normals.is_empty());
+        assert_eq!(
+            smooth.unwrap().mesh.normals,
+            vec![Normal::new(0.0, 0.0, 1.0); 4]
+        );
+    }
+
+    #[test]
+    fn vertex_normals_and_uvs_reach_mesh() {
+        let ply = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nz
+property float nx
+property float ny
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 1 0 0 0 0
+1 0 0 0.6 0.8 0 1 0
+0 1 0 0.8 0 0.6 0 1
+3 0 1 2
+";
+        let mesh = load_bytes("attributes", ply.as_bytes()).unwrap().mesh;
+        assert_eq!(
+            mesh.normals,
+            vec![
+                Normal::new(0.0, 0.0, 1.0),
+                Normal::new(0.8, 0.0, 0.6),
+                Normal::new(0.0, 0.6, 0.8),
+            ]
+        );
+        assert_eq!(
+            mesh.uvs,
+            vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(1.0, 0.0),
+                Point2::new(0.0, 1.0),
+            ]
+        );
+    }
 }
@@ -0,0 +1,127 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    interaction::SurfaceInteraction,
+    materials::Bsdf,
+    math::{AnimatedTransform, Bounds3, Ray},
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Primitive_Interface_and_Geometric_Primitives#TransformedPrimitive:ObjectInstancingandAnimatedPrimitives
+
+/// A [Shape] that is moved by an [AnimatedTransform].
+///
+/// The wrapped [Shape] is expected to be in object space, i.e. created with an identity
+/// transform.
+pub struct AnimatedShape {
+    shape: Arc<dyn Shape>,
+    object_to_world: Arc<AnimatedTransform<f32>>,
+}
+
+impl AnimatedShape {
+    /// Creates a new `AnimatedShape`.
+    pub fn new(shape: Arc<dyn Shape>, object_to_world: Arc<AnimatedTransform<f32>>) -> Self {
+        Self {
+            shape,
+            object_to_world,
+        }
+    }
+}
+
+impl Shape for AnimatedShape {
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        let object_to_world = self.object_to_world.interpolate(ray.time);
+        let world_to_object = object_to_world.inverted();
+
+        // The direction isn't normalized by the transform so t stays valid in world space
+        let hit = self.shape.intersect(&world_to_object * ray)?;
+
+        let mut si = &object_to_world * hit.si;
+        if object_to_world.swaps_handedness() {
+            si.n = -si.n;
+            si.shading.n = -si.shading.n;
+        }
+
+        // Pass the hit shape through as it might be inside an instance
+        Some(Hit {
+            t: hit.t,
+            si,
+            shape: hit.shape,
+        })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        self.object_to_world.motion_bounds(self.shape.world_bound())
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        self.object_to_world.start().swaps_handedness()
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        self.shape.compute_scattering_functions(scratch, si)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::AnimatedShape;
+    use crate::{
+        materials::Matte,
+        math::{
+            transforms::translation, AnimatedTransform, Point3, Ray, Spectrum, Transform, Vec3,
+        },
+        shapes::{Shape, Sphere},
+        textures::ConstantTexture,
+    };
+
+    fn moving_sphere() -> AnimatedShape {
+        let material = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let sphere = Arc::new(Sphere::new(&Transform::default(), 1.0, material));
+        let object_to_world = Arc::new(AnimatedTransform::new(
+            &Transform::default(),
+            0.0,
+            &translation(Vec3::new(4.0, 0.0, 0.0)),
+            1.0,
+        ));
+        AnimatedShape::new(sphere, object_to_world)
+    }
+
+    #[test]
+    fn hits_follow_time() {
+        let shape = moving_sphere();
+
+        let ray = Ray::new(
+            Point3::new(2.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        assert!(shape.intersect(ray).is_none());
+        assert!(shape.intersect(Ray { time: 1.0, ..ray }).is_none());
+
+        let hit = shape.intersect(Ray { time: 0.5, ..ray }).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-5);
+        assert!((hit.si.p - Point3::new(2.0, 0.0, 1.0)).len() < 1e-5);
+        assert!((hit.si.n.z.abs() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn world_bound_covers_motion() {
+        let bound = moving_sphere().world_bound();
+        assert!((bound.p_min - Point3::new(-1.0, -1.0, -1.0)).len() < 1e-5);
+        assert!((bound.p_max - Point3::new(5.0, 1.0, 1.0)).len() < 1e-5);
+    }
+}
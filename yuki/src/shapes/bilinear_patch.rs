@@ -0,0 +1,278 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    interaction::SurfaceInteraction,
+    lights::AreaLight,
+    materials::{Bsdf, Material},
+    math::{gamma, Bounds3, Point2, Point3, Ray, Transform, Vec2, Vec3},
+};
+
+// Based on Physically Based Rendering 4th ed.
+// https://pbr-book.org/4ed/Shapes/Bilinear_Patches
+
+/// A bilinear patch object spanned by four corner points.
+pub struct BilinearPatch {
+    /// Corners in world space, ordered as p00, p10, p01, p11
+    points: [Point3<f32>; 4],
+    /// Optional corner UVs, ordered like `points`
+    uvs: Option<[Point2<f32>; 4]>,
+    material: Arc<dyn Material>,
+    area_light: Option<Arc<dyn AreaLight>>,
+    transform_swaps_handedness: bool,
+}
+
+impl BilinearPatch {
+    /// Creates a new `BilinearPatch`.
+    /// `points` and `uvs` are expected in order p00, p10, p01, p11.
+    /// Patch (u, v) is used as the UV if `uvs` is `None`.
+    pub fn new(
+        object_to_world: &Transform<f32>,
+        points: [Point3<f32>; 4],
+        uvs: Option<[Point2<f32>; 4]>,
+        material: Arc<dyn Material>,
+        area_light: Option<Arc<dyn AreaLight>>,
+    ) -> Self {
+        Self {
+            points: points.map(|p| object_to_world * p),
+            uvs,
+            material,
+            area_light,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+        }
+    }
+}
+
+fn lerp_p(t: f32, p0: Point3<f32>, p1: Point3<f32>) -> Point3<f32> {
+    p0 * (1.0 - t) + p1 * t
+}
+
+fn lerp_v(t: f32, v0: Vec3<f32>, v1: Vec3<f32>) -> Vec3<f32> {
+    v0 * (1.0 - t) + v1 * t
+}
+
+impl Shape for BilinearPatch {
+    #[allow(clippy::many_single_char_names)] // Convention
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        let [p00, p10, p01, p11] = self.points;
+
+        // Solve the quadratic for u where the ray and the line between the patch edges
+        // at u intersect
+        let a = (p10 - p00).cross(p01 - p11).dot(ray.d);
+        let c = (p00 - ray.o).cross(ray.d).dot(p01 - p00);
+        let b = (p10 - ray.o).cross(ray.d).dot(p11 - p10) - (a + c);
+
+        let (u0, u1) = if a == 0.0 {
+            // Patch is a parallelogram in ray's direction so this is linear
+            if b == 0.0 {
+                return None;
+            }
+            let u = -c / b;
+            (u, u)
+        } else {
+            let discrim = b * b - 4.0 * a * c;
+            if discrim < 0.0 {
+                return None;
+            }
+            let rd = discrim.sqrt();
+            let q = if b < 0.0 {
+                -0.5 * (b - rd)
+            } else {
+                -0.5 * (b + rd)
+            };
+            // q can only be zero if both b and c are, in which case u=0 is the single root
+            if q == 0.0 {
+                (0.0, 0.0)
+            } else {
+                (q / a, c / q)
+            }
+        };
+
+        // Find the closest valid hit for the u candidates
+        let mut t = ray.t_max;
+        let mut uv = None;
+        for u in [u0, u1] {
+            if !(0.0..=1.0).contains(&u) {
+                continue;
+            }
+
+            let uo = lerp_p(u, p00, p10);
+            let ud = lerp_p(u, p01, p11) - uo;
+            let delta_o = uo - ray.o;
+            let perp = ray.d.cross(ud);
+            let p2 = perp.len_sqr();
+            // 3x3 determinants with columns (delta_o, ray.d, perp) and (delta_o, ud, perp)
+            let v_scaled = delta_o.dot(ray.d.cross(perp));
+            let t_scaled = delta_o.dot(ud.cross(perp));
+            if t_scaled > 0.0 && t_scaled < t * p2 && (0.0..=p2).contains(&v_scaled) {
+                t = t_scaled / p2;
+                uv = Some(Point2::new(u, v_scaled / p2));
+            }
+        }
+        let Point2 { x: u, y: v } = uv?;
+
+        let p = lerp_p(v, lerp_p(u, p00, p10), lerp_p(u, p01, p11));
+        let mut dpdu = lerp_v(v, p10 - p00, p11 - p01);
+        let mut dpdv = lerp_v(u, p01 - p00, p11 - p10);
+
+        let uv = if let Some([uv00, uv10, uv01, uv11]) = self.uvs {
+            let st = uv00 * ((1.0 - u) * (1.0 - v))
+                + uv10 * (u * (1.0 - v))
+                + uv01 * ((1.0 - u) * v)
+                + uv11 * (u * v);
+
+            // Express the partial derivatives in relation to the authored uvs
+            let dstdu: Vec2<f32> = (uv10 - uv00) * (1.0 - v) + (uv11 - uv01) * v;
+            let dstdv: Vec2<f32> = (uv01 - uv00) * (1.0 - u) + (uv11 - uv10) * u;
+            let det = dstdu.x * dstdv.y - dstdu.y * dstdv.x;
+            if det != 0.0 {
+                let inv_det = 1.0 / det;
+                let dpds = (dpdu * dstdv.y - dpdv * dstdu.y) * inv_det;
+                let dpdt = (dpdv * dstdu.x - dpdu * dstdv.x) * inv_det;
+                dpdu = dpds;
+                dpdv = dpdt;
+            }
+
+            st
+        } else {
+            Point2::new(u, v)
+        };
+
+        let mut si =
+            SurfaceInteraction::new(p, -ray.d, uv, dpdu, dpdv, self, self.area_light.clone());
+        // The interpolation weights sum to one so the corners bound the magnitudes involved
+        si.p_error = [p00, p10, p01, p11]
+            .iter()
+            .fold(Vec3::zeros(), |sum, p| sum + Vec3::from(p.abs()))
+            * gamma(6);
+
+        Some(Hit { t, si, shape: self })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        Bounds3::new(self.points[0], self.points[1])
+            .union_p(self.points[2])
+            .union_p(self.points[3])
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        self.transform_swaps_handedness
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        self.material.compute_scattering_functions(scratch, si)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::abs_diff_eq;
+    use std::sync::Arc;
+
+    use super::BilinearPatch;
+    use crate::{
+        materials::{Material, Matte},
+        math::{Point2, Point3, Ray, Spectrum, Transform, Vec3},
+        shapes::Shape,
+        textures::ConstantTexture,
+    };
+
+    fn patch(points: [Point3<f32>; 4]) -> BilinearPatch {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        BilinearPatch::new(&Transform::default(), points, None, material, None)
+    }
+
+    fn down(x: f32, y: f32) -> Ray<f32> {
+        Ray::new(
+            Point3::new(x, y, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        )
+    }
+
+    #[test]
+    fn planar_hit() {
+        let patch = patch([
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(2.0, 1.0, 0.0),
+        ]);
+
+        let hit = patch.intersect(down(0.5, 0.75)).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-5, "{}", hit.t);
+        assert!(abs_diff_eq!(
+            hit.si.uv,
+            Point2::new(0.25, 0.75),
+            epsilon = 1e-5
+        ));
+        assert!(abs_diff_eq!(
+            hit.si.p,
+            Point3::new(0.5, 0.75, 0.0),
+            epsilon = 1e-5
+        ));
+    }
+
+    #[test]
+    fn warped_hit() {
+        let (p00, p10, p01, p11) = (
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+        );
+        let patch = patch([p00, p10, p01, p11]);
+
+        // The patch is z = u * v over the unit square
+        let ray = down(0.25, 0.75);
+        let hit = patch.intersect(ray).unwrap();
+        let Point2 { x: u, y: v } = hit.si.uv;
+        assert!(abs_diff_eq!(
+            hit.si.uv,
+            Point2::new(0.25, 0.75),
+            epsilon = 1e-5
+        ));
+        assert!((hit.t - (5.0 - 0.25 * 0.75)).abs() < 1e-5, "{}", hit.t);
+
+        let bilinear = p00 * ((1.0 - u) * (1.0 - v))
+            + p10 * (u * (1.0 - v))
+            + p01 * ((1.0 - u) * v)
+            + p11 * (u * v);
+        assert!(abs_diff_eq!(hit.si.p, bilinear, epsilon = 1e-5));
+        assert!(abs_diff_eq!(ray.point(hit.t), bilinear, epsilon = 1e-5));
+    }
+
+    #[test]
+    fn misses() {
+        let patch = patch([
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+        ]);
+
+        // Outside the patch
+        assert!(patch.intersect(down(1.5, 0.5)).is_none());
+        assert!(patch.intersect(down(0.5, -0.5)).is_none());
+        // Patch is behind the ray
+        let away = Ray::new(
+            Point3::new(0.5, 0.5, 5.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            f32::INFINITY,
+        );
+        assert!(patch.intersect(away).is_none());
+        // Patch is past the ray's extent
+        let short = Ray::new(Point3::new(0.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0), 4.0);
+        assert!(patch.intersect(short).is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    interaction::SurfaceInteraction,
+    materials::{Bsdf, Material},
+    math::{Bounds3, Point2, Point3, Ray, Transform, Vec3},
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Shapes/Cylinders
+
+/// An open cylinder around the z-axis.
+pub struct Cylinder {
+    object_to_world: Transform<f32>,
+    world_to_object: Transform<f32>,
+    radius: f32,
+    z_min: f32,
+    z_max: f32,
+    phi_max: f32,
+    material: Arc<dyn Material>,
+    transform_swaps_handedness: bool,
+}
+
+impl Cylinder {
+    /// Creates a new `Cylinder`. `phi_max` is in degrees.
+    pub fn new(
+        object_to_world: &Transform<f32>,
+        radius: f32,
+        z_min: f32,
+        z_max: f32,
+        phi_max: f32,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            object_to_world: object_to_world.clone(),
+            world_to_object: object_to_world.inverted(),
+            radius,
+            z_min: z_min.min(z_max),
+            z_max: z_min.max(z_max),
+            phi_max: phi_max.clamp(0.0, 360.0).to_radians(),
+            material,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+        }
+    }
+
+    /// Returns the refined hit point and its phi if the hit at `t` is within the clipped surface.
+    fn clipped_hit(&self, r: &Ray<f32>, t: f32) -> Option<(Point3<f32>, f32)> {
+        let mut p = r.point(t);
+        // Refine
+        let hit_radius = (p.x * p.x + p.y * p.y).sqrt();
+        p.x *= self.radius / hit_radius;
+        p.y *= self.radius / hit_radius;
+
+        let phi = {
+            let mut phi = p.y.atan2(p.x);
+            if phi < 0.0 {
+                phi += 2.0 * std::f32::consts::PI;
+            }
+            phi
+        };
+
+        if p.z < self.z_min || p.z > self.z_max || phi > self.phi_max {
+            None
+        } else {
+            Some((p, phi))
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    #[allow(clippy::many_single_char_names)] // Convention
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        let r = &self.world_to_object * ray;
+
+        // Quadratic coefficients
+        let a = r.d.x * r.d.x + r.d.y * r.d.y;
+        let b = 2.0 * (r.d.x * r.o.x + r.d.y * r.o.y);
+        let c = r.o.x * r.o.x + r.o.y * r.o.y - self.radius * self.radius;
+
+        // Rays parallel to the axis never hit the side
+        if a == 0.0 {
+            return None;
+        }
+
+        // Solve quadratic equation for ts
+        let discrim = b * b - 4.0 * a * c;
+        if discrim < 0.0 {
+            return None;
+        }
+        let rd = discrim.sqrt();
+
+        let q = if b < 0.0 {
+            -0.5 * (b - rd)
+        } else {
+            -0.5 * (b + rd)
+        };
+
+        // Find hit points
+        let mut t0 = q / a;
+        let mut t1 = c / q;
+        if t0 > t1 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+
+        if t0 > r.t_max || t1 <= 0.0 {
+            return None;
+        }
+
+        // The near hit might be clipped away, in which case we see the inside through it
+        let near_hit = if t0 > 0.0 {
+            self.clipped_hit(&r, t0)
+        } else {
+            None
+        };
+        let (t, p, phi) = match near_hit {
+            Some((p, phi)) => (t0, p, phi),
+            None => {
+                if t1 > r.t_max {
+                    return None;
+                }
+                let (p, phi) = self.clipped_hit(&r, t1)?;
+                (t1, p, phi)
+            }
+        };
+
+        let u = phi / self.phi_max;
+        let v = (p.z - self.z_min) / (self.z_max - self.z_min);
+
+        let dpdu = Vec3::new(-self.phi_max * p.y, self.phi_max * p.x, 0.0);
+        let dpdv = Vec3::new(0.0, 0.0, self.z_max - self.z_min);
+
+        let si = &self.object_to_world
+            * SurfaceInteraction::new(p, -r.d, Point2::new(u, v), dpdu, dpdv, self, None);
+
+        Some(Hit { t, si, shape: self })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        &self.object_to_world
+            * Bounds3::new(
+                Point3::new(-self.radius, -self.radius, self.z_min),
+                Point3::new(self.radius, self.radius, self.z_max),
+            )
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        self.transform_swaps_handedness
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        self.material.compute_scattering_functions(scratch, si)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Cylinder;
+    use crate::{
+        materials::Matte,
+        math::{Point3, Ray, Spectrum, Transform, Vec3},
+        shapes::Shape,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn perpendicular_ray_hits_near_side() {
+        let material = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let cylinder = Cylinder::new(&Transform::default(), 1.0, -1.0, 1.0, 360.0, material);
+
+        let ray = Ray::new(
+            Point3::new(-3.0, 0.0, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            f32::INFINITY,
+        );
+        let hit = cylinder.intersect(ray).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+        assert!((hit.si.p - Point3::new(-1.0, 0.0, 0.5)).len() < 1e-5);
+        assert!((hit.si.uv.y - 0.75).abs() < 1e-5);
+
+        // Starting inside should give the far side
+        let inside = Ray::new(
+            Point3::new(0.0, 0.0, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            f32::INFINITY,
+        );
+        let hit = cylinder.intersect(inside).unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert!((hit.si.p - Point3::new(1.0, 0.0, 0.5)).len() < 1e-5);
+
+        // Ray shorter than the near hit
+        let short = Ray::new(Point3::new(-3.0, 0.0, 0.5), Vec3::new(1.0, 0.0, 0.0), 1.5);
+        assert!(cylinder.intersect(short).is_none());
+
+        // Outside the z range
+        let above = Ray::new(
+            Point3::new(-3.0, 0.0, 1.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            f32::INFINITY,
+        );
+        assert!(cylinder.intersect(above).is_none());
+    }
+
+    #[test]
+    fn clipped_near_side_hits_far_side() {
+        let material = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        // Half cylinder on the +y side
+        let cylinder = Cylinder::new(&Transform::default(), 1.0, -1.0, 1.0, 180.0, material);
+
+        let ray = Ray::new(
+            Point3::new(0.0, -3.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            f32::INFINITY,
+        );
+        let hit = cylinder.intersect(ray).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,180 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    interaction::SurfaceInteraction,
+    materials::{Bsdf, Material},
+    math::{Bounds3, Point2, Point3, Ray, Transform, Vec3},
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Shapes/Other_Quadrics#Disks
+
+/// A disk or an annulus perpendicular to the z-axis.
+pub struct Disk {
+    object_to_world: Transform<f32>,
+    world_to_object: Transform<f32>,
+    height: f32,
+    radius: f32,
+    inner_radius: f32,
+    material: Arc<dyn Material>,
+    transform_swaps_handedness: bool,
+}
+
+impl Disk {
+    /// Creates a new `Disk` at `height` along the object space z-axis.
+    pub fn new(
+        object_to_world: &Transform<f32>,
+        height: f32,
+        radius: f32,
+        inner_radius: f32,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(inner_radius >= 0.0 && inner_radius < radius);
+
+        Self {
+            object_to_world: object_to_world.clone(),
+            world_to_object: object_to_world.inverted(),
+            height,
+            radius,
+            inner_radius,
+            material,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+        }
+    }
+}
+
+impl Shape for Disk {
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        let r = &self.world_to_object * ray;
+
+        // Rays parallel to the disk can't hit it
+        if r.d.z == 0.0 {
+            return None;
+        }
+        let t = (self.height - r.o.z) / r.d.z;
+        if t <= 0.0 || t >= r.t_max {
+            return None;
+        }
+
+        // Reject hits outside the annulus
+        let p = {
+            let mut p = r.point(t);
+            // Refine
+            p.z = self.height;
+            p
+        };
+        let dist2 = p.x * p.x + p.y * p.y;
+        if dist2 > self.radius * self.radius || dist2 < self.inner_radius * self.inner_radius {
+            return None;
+        }
+
+        let phi = {
+            let mut phi = p.y.atan2(p.x);
+            if phi < 0.0 {
+                phi += 2.0 * std::f32::consts::PI;
+            }
+            phi
+        };
+
+        let phi_max = 2.0 * std::f32::consts::PI;
+        let dist = dist2.sqrt();
+        let u = phi / phi_max;
+        let v = (self.radius - dist) / (self.radius - self.inner_radius);
+
+        let dpdu = Vec3::new(-phi_max * p.y, phi_max * p.x, 0.0);
+        let dpdv = if dist > 0.0 {
+            Vec3::new(p.x, p.y, 0.0) * ((self.inner_radius - self.radius) / dist)
+        } else {
+            // Degenerate at the center, any direction on the plane works
+            Vec3::new(self.inner_radius - self.radius, 0.0, 0.0)
+        };
+
+        let si = &self.object_to_world
+            * SurfaceInteraction::new(p, -r.d, Point2::new(u, v), dpdu, dpdv, self, None);
+
+        Some(Hit { t, si, shape: self })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        &self.object_to_world
+            * Bounds3::new(
+                Point3::new(-self.radius, -self.radius, self.height),
+                Point3::new(self.radius, self.radius, self.height),
+            )
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        self.transform_swaps_handedness
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        self.material.compute_scattering_functions(scratch, si)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Disk;
+    use crate::{
+        materials::Matte,
+        math::{transforms::translation, Point3, Ray, Spectrum, Vec3},
+        shapes::Shape,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn hits_along_axis() {
+        let material = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let disk = Disk::new(
+            &translation(Vec3::new(0.0, 0.0, 1.0)),
+            0.5,
+            2.0,
+            0.0,
+            material,
+        );
+
+        let ray = Ray::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        let hit = disk.intersect(ray).unwrap();
+        assert!((hit.t - 3.5).abs() < 1e-6);
+        assert!((hit.si.p - Point3::new(0.0, 0.0, 1.5)).len() < 1e-6);
+
+        // Rays through the hole or outside the radius miss
+        let annulus = Disk::new(
+            &translation(Vec3::new(0.0, 0.0, 1.0)),
+            0.5,
+            2.0,
+            1.0,
+            Arc::clone(&disk.material),
+        );
+        assert!(annulus.intersect(ray).is_none());
+        let outside = Ray::new(
+            Point3::new(2.5, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        assert!(disk.intersect(outside).is_none());
+        let inside = Ray::new(
+            Point3::new(1.5, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        assert!((annulus.intersect(inside).unwrap().t - 3.5).abs() < 1e-6);
+    }
+}
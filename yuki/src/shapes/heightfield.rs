@@ -0,0 +1,40 @@
+use super::Mesh;
+use crate::math::{Point2, Point3, Transform};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://github.com/mmp/pbrt-v3/blob/master/src/shapes/heightfield.cpp
+
+/// Creates a triangulated `Mesh` of the `nu` by `nv` grid of heights `z` over the unit square.
+///
+/// Heights are given row by row with u increasing first.
+pub fn heightfield_mesh(object_to_world: &Transform<f32>, nu: usize, nv: usize, z: &[f32]) -> Mesh {
+    assert!(
+        nu >= 2 && nv >= 2,
+        "Heightfield should have at least 2x2 heights"
+    );
+    assert_eq!(z.len(), nu * nv, "Heightfield should have nu * nv heights");
+
+    let mut points = Vec::with_capacity(nu * nv);
+    let mut uvs = Vec::with_capacity(nu * nv);
+    for v in 0..nv {
+        for u in 0..nu {
+            let uv = Point2::new(
+                (u as f32) / ((nu - 1) as f32),
+                (v as f32) / ((nv - 1) as f32),
+            );
+            points.push(Point3::new(uv.x, uv.y, z[v * nu + u]));
+            uvs.push(uv);
+        }
+    }
+
+    let vertex = |u: usize, v: usize| v * nu + u;
+    let mut indices = Vec::with_capacity((nu - 1) * (nv - 1) * 6);
+    for v in 0..(nv - 1) {
+        for u in 0..(nu - 1) {
+            indices.extend_from_slice(&[vertex(u, v), vertex(u + 1, v), vertex(u + 1, v + 1)]);
+            indices.extend_from_slice(&[vertex(u, v), vertex(u + 1, v + 1), vertex(u, v + 1)]);
+        }
+    }
+
+    Mesh::new(object_to_world, indices, points, Vec::new(), uvs)
+}
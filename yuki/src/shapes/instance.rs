@@ -0,0 +1,143 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    bvh::BoundingVolumeHierarchy,
+    interaction::SurfaceInteraction,
+    materials::Bsdf,
+    math::{Bounds3, Ray, Transform},
+};
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Primitive_Interface_and_Geometric_Primitives#TransformedPrimitive:ObjectInstancingandAnimatedPrimitives
+
+/// An instance of the shapes in a [`BoundingVolumeHierarchy`] placed with its own transform.
+///
+/// Any number of `Instance`s can share the same hierarchy so repeated geometry is only stored
+/// once. Hits report the intersected shape inside the hierarchy as their [`Hit::shape`].
+pub struct Instance {
+    bvh: Arc<BoundingVolumeHierarchy>,
+    object_to_world: Transform<f32>,
+    world_to_object: Transform<f32>,
+}
+
+impl Instance {
+    /// Creates a new `Instance` of the shapes in `bvh`.
+    pub fn new(bvh: Arc<BoundingVolumeHierarchy>, object_to_world: &Transform<f32>) -> Self {
+        Self {
+            bvh,
+            object_to_world: object_to_world.clone(),
+            world_to_object: object_to_world.inverted(),
+        }
+    }
+}
+
+impl Shape for Instance {
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        // The direction isn't normalized by the transform so t stays valid in world space.
+        // Differentials are computed for the final hit by the scene hierarchy.
+        let object_ray = Ray {
+            differential: None,
+            ..&self.world_to_object * ray
+        };
+        let hit = self.bvh.intersect(object_ray).hit?;
+
+        let mut si = &self.object_to_world * hit.si;
+        if self.object_to_world.swaps_handedness() {
+            si.n = -si.n;
+            si.shading.n = -si.shading.n;
+        }
+
+        Some(Hit {
+            t: hit.t,
+            si,
+            shape: hit.shape,
+        })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        &self.object_to_world * self.bvh.bounds()
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        self.object_to_world.swaps_handedness()
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        _scratch: &'a ScopedScratch,
+        _si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        unreachable!("Hits on instances are reported on the instanced shapes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Instance;
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        materials::{Material, Matte},
+        math::{transforms::translation, Point3, Ray, Spectrum, Transform, Vec3},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn instances_hit_at_their_transforms() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2],
+            vec![
+                Point3::new(-1.0, -1.0, 0.0),
+                Point3::new(1.0, -1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let triangle: Arc<dyn Shape> = Arc::new(Triangle::new(mesh, 0, material, None));
+        let (object, _) = BoundingVolumeHierarchy::new(vec![triangle], 1, SplitMethod::Middle);
+        let object = Arc::new(object);
+
+        let instances: Vec<Arc<dyn Shape>> = [-3.0, 3.0]
+            .iter()
+            .map(|&x| {
+                Arc::new(Instance::new(
+                    Arc::clone(&object),
+                    &translation(Vec3::new(x, 0.0, -2.0)),
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+        let (scene, _) = BoundingVolumeHierarchy::new(instances, 1, SplitMethod::Middle);
+
+        for x in [-3.0, 3.0] {
+            let ray = Ray::new(
+                Point3::new(x, 0.0, 5.0),
+                Vec3::new(0.0, 0.0, -1.0),
+                f32::INFINITY,
+            );
+            let hit = scene.intersect(ray).hit.unwrap();
+            assert!((hit.t - 7.0).abs() < 1e-5);
+            assert!((hit.si.p - Point3::new(x, 0.0, -2.0)).len() < 1e-5);
+            assert!((hit.si.n.z.abs() - 1.0).abs() < 1e-5);
+        }
+
+        // The object itself isn't in the scene at the origin
+        let ray = Ray::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+        assert!(scene.intersect(ray).hit.is_none());
+    }
+}
@@ -0,0 +1,207 @@
+use super::Mesh;
+use crate::math::{Point3, Transform, Vec3};
+
+use std::collections::HashMap;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Shapes/Subdivision_Surfaces
+
+/// Creates a `Mesh` by applying `levels` of Loop subdivision on the triangles given by `indices`
+/// and `points`, with the final points pushed to the limit surface.
+///
+/// Edges not shared by exactly two triangles are treated as boundaries.
+pub fn loop_subdivision_mesh(
+    object_to_world: &Transform<f32>,
+    levels: u32,
+    indices: &[usize],
+    points: &[Point3<f32>],
+) -> Mesh {
+    assert!(indices.len() % 3 == 0);
+
+    let mut indices = indices.to_vec();
+    let mut points: Vec<Vec3<f32>> = points.iter().map(|&p| p.into()).collect();
+    for _ in 0..levels {
+        (indices, points) = subdivide(&indices, &points);
+    }
+
+    let topology = Topology::new(&indices, points.len());
+    let limit_points = (0..points.len())
+        .map(|v| {
+            let p = points[v];
+            let boundary = topology.boundary_neighbors(v);
+            let limit = if boundary.len() == 2 {
+                p * (3.0 / 5.0) + (points[boundary[0]] + points[boundary[1]]) * (1.0 / 5.0)
+            } else if boundary.is_empty() {
+                let neighbors = &topology.neighbors[v];
+                let n = neighbors.len() as f32;
+                let beta = 1.0 / (n + 3.0 / (8.0 * beta(neighbors.len())));
+                neighbor_sum(&points, neighbors) * beta + p * (1.0 - n * beta)
+            } else {
+                p
+            };
+            Point3::new(limit.x, limit.y, limit.z)
+        })
+        .collect();
+
+    // Smooth everything as the limit surface has no creases
+    Mesh::new(
+        object_to_world,
+        indices,
+        limit_points,
+        Vec::new(),
+        Vec::new(),
+    )
+    .with_computed_smooth_normals(std::f32::consts::PI)
+}
+
+/// Returns the weight of the neighbors of an interior vertex with `valence` neighbors.
+fn beta(valence: usize) -> f32 {
+    if valence == 3 {
+        3.0 / 16.0
+    } else {
+        3.0 / (8.0 * (valence as f32))
+    }
+}
+
+fn neighbor_sum(points: &[Vec3<f32>], neighbors: &[usize]) -> Vec3<f32> {
+    neighbors
+        .iter()
+        .fold(Vec3::zeros(), |sum, &n| sum + points[n])
+}
+
+fn edge_key(v0: usize, v1: usize) -> (usize, usize) {
+    (v0.min(v1), v0.max(v1))
+}
+
+struct Topology {
+    /// Vertices opposite to each edge in the triangles that share it
+    edges: HashMap<(usize, usize), Vec<usize>>,
+    /// Neighbors of each vertex in the order they are first seen
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(indices: &[usize], vertex_count: usize) -> Self {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        let mut neighbors = vec![Vec::new(); vertex_count];
+        for tri in indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (v0, v1, opposite) = (tri[i], tri[(i + 1) % 3], tri[(i + 2) % 3]);
+                let edge = edges.entry(edge_key(v0, v1)).or_insert_with(|| {
+                    neighbors[v0].push(v1);
+                    neighbors[v1].push(v0);
+                    Vec::new()
+                });
+                edge.push(opposite);
+            }
+        }
+        Self { edges, neighbors }
+    }
+
+    fn is_boundary(&self, v0: usize, v1: usize) -> bool {
+        self.edges[&edge_key(v0, v1)].len() != 2
+    }
+
+    fn boundary_neighbors(&self, v: usize) -> Vec<usize> {
+        self.neighbors[v]
+            .iter()
+            .copied()
+            .filter(|&n| self.is_boundary(v, n))
+            .collect()
+    }
+}
+
+/// Splits each triangle into four, returning the new indices and points.
+fn subdivide(indices: &[usize], points: &[Vec3<f32>]) -> (Vec<usize>, Vec<Vec3<f32>>) {
+    let topology = Topology::new(indices, points.len());
+
+    // Existing vertices are smoothed in place
+    let mut new_points: Vec<Vec3<f32>> = (0..points.len())
+        .map(|v| {
+            let p = points[v];
+            let boundary = topology.boundary_neighbors(v);
+            if boundary.len() == 2 {
+                p * (3.0 / 4.0) + (points[boundary[0]] + points[boundary[1]]) * (1.0 / 8.0)
+            } else if boundary.is_empty() {
+                let neighbors = &topology.neighbors[v];
+                let beta = beta(neighbors.len());
+                neighbor_sum(points, neighbors) * beta + p * (1.0 - (neighbors.len() as f32) * beta)
+            } else {
+                p
+            }
+        })
+        .collect();
+
+    // New vertices are added on each edge
+    let mut edge_vertices: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut edge_vertex = |v0: usize, v1: usize, new_points: &mut Vec<Vec3<f32>>| {
+        *edge_vertices.entry(edge_key(v0, v1)).or_insert_with(|| {
+            let opposite = &topology.edges[&edge_key(v0, v1)];
+            let p = if opposite.len() == 2 {
+                (points[v0] + points[v1]) * (3.0 / 8.0)
+                    + (points[opposite[0]] + points[opposite[1]]) * (1.0 / 8.0)
+            } else {
+                (points[v0] + points[v1]) * 0.5
+            };
+            new_points.push(p);
+            new_points.len() - 1
+        })
+    };
+
+    let mut new_indices = Vec::with_capacity(indices.len() * 4);
+    for tri in indices.chunks_exact(3) {
+        let (v0, v1, v2) = (tri[0], tri[1], tri[2]);
+        let e01 = edge_vertex(v0, v1, &mut new_points);
+        let e12 = edge_vertex(v1, v2, &mut new_points);
+        let e20 = edge_vertex(v2, v0, &mut new_points);
+        // Keep the winding of the original triangle
+        new_indices.extend_from_slice(&[v0, e01, e20]);
+        new_indices.extend_from_slice(&[e01, v1, e12]);
+        new_indices.extend_from_slice(&[e20, e12, v2]);
+        new_indices.extend_from_slice(&[e01, e12, e20]);
+    }
+
+    (new_indices, new_points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{loop_subdivision_mesh, Topology};
+    use crate::math::{Point3, Transform};
+
+    #[test]
+    fn subdivision_quadruples_triangles_and_keeps_boundary() {
+        // Unit square in the xy-plane
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        let indices = [0, 1, 2, 0, 2, 3];
+        let boundary_edge_count = |indices: &[usize], vertex_count: usize| {
+            Topology::new(indices, vertex_count)
+                .edges
+                .values()
+                .filter(|opposite| opposite.len() == 1)
+                .count()
+        };
+
+        let mesh = loop_subdivision_mesh(&Transform::default(), 1, &indices, &points);
+
+        assert_eq!(mesh.indices.len(), indices.len() * 4);
+        // Smooth normals shouldn't split any vertices on a plane
+        assert_eq!(mesh.points.len(), 9);
+        assert_eq!(
+            boundary_edge_count(&mesh.indices, mesh.points.len()),
+            2 * boundary_edge_count(&indices, points.len())
+        );
+        for p in &mesh.points {
+            assert!(p.z.abs() < 1e-6);
+            assert!((0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.y));
+        }
+        for n in &mesh.normals {
+            assert!((n.z - 1.0).abs() < 1e-6);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::math::{Normal, Point2, Point3, Transform};
+use crate::math::{Normal, Point2, Point3, Transform, Vec3};
+
+use std::collections::HashMap;
 
 // Based on Physically Based Rendering 3rd ed.
 // http://www.pbr-book.org/3ed-2018/Light_Sources/Point_Lights.html
@@ -12,11 +14,15 @@ pub struct Mesh {
     pub points: Vec<Point3<f32>>,
     pub normals: Vec<Normal<f32>>,
     pub uvs: Vec<Point2<f32>>,
+    /// Normalized vertex tangents in world space along increasing u, empty if there are no uvs
+    pub tangents: Vec<Vec3<f32>>,
     pub transform_swaps_handedness: bool,
 }
 
 impl Mesh {
     /// Creates a new `Mesh`
+    ///
+    /// Vertex tangents are computed from the uv parameterization if `uvs` are given.
     pub fn new(
         object_to_world: &Transform<f32>,
         indices: Vec<usize>,
@@ -32,13 +38,203 @@ impl Mesh {
             *n = object_to_world * *n;
         }
 
+        let tangents = compute_tangents(&indices, &points, &uvs);
+
         Self {
             object_to_world: object_to_world.clone(),
             indices,
             points,
             normals,
             uvs,
+            tangents,
             transform_swaps_handedness: object_to_world.swaps_handedness(),
         }
     }
+
+    /// Returns this `Mesh` with its normals replaced by computed smooth vertex normals.
+    ///
+    /// Faces sharing a vertex are only smoothed together if the angle between them is at most
+    /// `crease_angle` radians. Vertices on sharper edges are split to keep the edges hard.
+    pub fn with_computed_smooth_normals(self, crease_angle: f32) -> Self {
+        // Area weighted face normals, oriented the same way as geometric normals in Triangle
+        let face_normals: Vec<Vec3<f32>> = self
+            .indices
+            .chunks_exact(3)
+            .map(|f| {
+                let (p0, p1, p2) = (self.points[f[0]], self.points[f[1]], self.points[f[2]]);
+                let n = (p1 - p0).cross(p2 - p0);
+                if self.transform_swaps_handedness {
+                    -n
+                } else {
+                    n
+                }
+            })
+            .collect();
+        let face_directions: Vec<Vec3<f32>> = face_normals
+            .iter()
+            .map(|&n| {
+                if n.len_sqr() > 0.0 {
+                    n.normalized()
+                } else {
+                    Vec3::zeros()
+                }
+            })
+            .collect();
+
+        let mut vertex_faces = vec![Vec::new(); self.points.len()];
+        for (i, &v) in self.indices.iter().enumerate() {
+            vertex_faces[v].push(i / 3);
+        }
+
+        let cos_crease = crease_angle.cos();
+        let mut indices = Vec::with_capacity(self.indices.len());
+        let mut points = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut tangents = Vec::new();
+        // Corners that end up with identical normals share the output vertex
+        let mut output_vertices: HashMap<(usize, [u32; 3]), usize> = HashMap::new();
+        for (i, &v) in self.indices.iter().enumerate() {
+            let face = i / 3;
+            let n = vertex_faces[v]
+                .iter()
+                .filter(|&&f| {
+                    f == face || face_directions[f].dot(face_directions[face]) >= cos_crease
+                })
+                .fold(Vec3::zeros(), |sum, &f| sum + face_normals[f]);
+            let n = if n.len_sqr() > 0.0 {
+                Normal::from(n.normalized())
+            } else {
+                Normal::zeros()
+            };
+
+            let key = (v, [n.x.to_bits(), n.y.to_bits(), n.z.to_bits()]);
+            let index = *output_vertices.entry(key).or_insert_with(|| {
+                points.push(self.points[v]);
+                normals.push(n);
+                if !self.uvs.is_empty() {
+                    uvs.push(self.uvs[v]);
+                }
+                if !self.tangents.is_empty() {
+                    tangents.push(self.tangents[v]);
+                }
+                points.len() - 1
+            });
+            indices.push(index);
+        }
+
+        Self {
+            object_to_world: self.object_to_world,
+            indices,
+            points,
+            normals,
+            uvs,
+            tangents,
+            transform_swaps_handedness: self.transform_swaps_handedness,
+        }
+    }
+}
+
+/// Returns the normalized vertex tangents along increasing u, summed from the faces sharing each
+/// vertex.
+///
+/// Returns an empty `Vec` if there are no `uvs`. Vertices only on faces with degenerate uvs get
+/// zero tangents.
+fn compute_tangents(
+    indices: &[usize],
+    points: &[Point3<f32>],
+    uvs: &[Point2<f32>],
+) -> Vec<Vec3<f32>> {
+    if uvs.is_empty() {
+        return Vec::new();
+    }
+
+    let mut tangents = vec![Vec3::zeros(); points.len()];
+    for f in indices.chunks_exact(3) {
+        // Same derivative as in Triangle::intersect
+        let duv02 = uvs[f[0]] - uvs[f[2]];
+        let duv12 = uvs[f[1]] - uvs[f[2]];
+        let dp02 = points[f[0]] - points[f[2]];
+        let dp12 = points[f[1]] - points[f[2]];
+
+        let uv_det = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+        if uv_det == 0.0 {
+            continue;
+        }
+        let dpdu = (dp02 * duv12[1] - dp12 * duv02[1]) / uv_det;
+        for &v in f {
+            tangents[v] += dpdu;
+        }
+    }
+
+    for t in &mut tangents {
+        if t.len_sqr() > 0.0 {
+            *t = t.normalized();
+        }
+    }
+
+    tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::abs_diff_eq;
+
+    use super::Mesh;
+    use crate::math::{Normal, Point3, Transform};
+
+    /// Creates two triangles sharing the edge from (0,0,0) to (0,1,0), the second one rotated
+    /// around the edge by `angle` radians.
+    fn hinge(angle: f32) -> Mesh {
+        Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 0, 3, 1],
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(angle.cos(), 0.0, angle.sin()),
+            ],
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn coplanar_faces_share_normals() {
+        let mesh = hinge(0.0).with_computed_smooth_normals(60f32.to_radians());
+        assert_eq!(mesh.points.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 3, 1]);
+        for &n in &mesh.normals {
+            assert!(abs_diff_eq!(n, Normal::new(0.0, 0.0, 1.0), epsilon = 1e-6));
+        }
+    }
+
+    #[test]
+    fn creases_split_vertices() {
+        let angle = 90f32.to_radians();
+        let face_normals = [
+            Normal::new(0.0, 0.0, 1.0),
+            Normal::new(-angle.sin(), 0.0, angle.cos()),
+        ];
+
+        // Faces meet at 90 degrees so the shared edge stays hard with a 60 degree crease angle
+        let mesh = hinge(angle).with_computed_smooth_normals(60f32.to_radians());
+        assert_eq!(mesh.points.len(), 6);
+        for (i, &v) in mesh.indices.iter().enumerate() {
+            assert!(abs_diff_eq!(
+                mesh.normals[v],
+                face_normals[i / 3],
+                epsilon = 1e-6
+            ));
+        }
+
+        // Larger crease angle smooths over the edge
+        let mesh = hinge(angle).with_computed_smooth_normals(120f32.to_radians());
+        assert_eq!(mesh.points.len(), 4);
+        let smooth = (face_normals[0] + face_normals[1]).normalized();
+        for v in [0, 1] {
+            assert!(abs_diff_eq!(mesh.normals[v], smooth, epsilon = 1e-6));
+        }
+    }
 }
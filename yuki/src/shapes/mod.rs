@@ -1,8 +1,24 @@
+mod animated;
+mod bilinear_patch;
+mod cylinder;
+mod disk;
+mod heightfield;
+mod instance;
+mod loop_subdiv;
 mod mesh;
+mod quad;
 mod sphere;
 mod triangle;
 
+pub use animated::AnimatedShape;
+pub use bilinear_patch::BilinearPatch;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use heightfield::heightfield_mesh;
+pub use instance::Instance;
+pub use loop_subdiv::loop_subdivision_mesh;
 pub use mesh::Mesh;
+pub use quad::Quad;
 pub use sphere::Sphere;
 pub use triangle::Triangle;
 
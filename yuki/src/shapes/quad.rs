@@ -0,0 +1,185 @@
+use allocators::ScopedScratch;
+use std::sync::Arc;
+
+use super::{Hit, Shape};
+use crate::{
+    interaction::SurfaceInteraction,
+    lights::AreaLight,
+    materials::{Bsdf, Material},
+    math::{gamma, Bounds3, Point2, Point3, Ray, Vec3},
+};
+
+/// A parallelogram spanned by two edges from a corner point.
+pub struct Quad {
+    /// Corner in world space
+    origin: Point3<f32>,
+    /// World space edges, the surface normal is `edge0 x edge1`
+    edges: [Vec3<f32>; 2],
+    /// Unnormalized surface normal
+    n: Vec3<f32>,
+    material: Arc<dyn Material>,
+    area_light: Option<Arc<dyn AreaLight>>,
+}
+
+impl Quad {
+    /// Creates a new `Quad` from world space `origin` and `edges`.
+    pub fn new(
+        origin: Point3<f32>,
+        edges: [Vec3<f32>; 2],
+        material: Arc<dyn Material>,
+        area_light: Option<Arc<dyn AreaLight>>,
+    ) -> Self {
+        let n = edges[0].cross(edges[1]);
+        assert!(n.len_sqr() > 0.0, "Quad edges should not be parallel");
+
+        Self {
+            origin,
+            edges,
+            n,
+            material,
+            area_light,
+        }
+    }
+}
+
+impl Shape for Quad {
+    fn intersect(&self, ray: Ray<f32>) -> Option<Hit> {
+        // Rays parallel to the quad can't hit it
+        let denom = self.n.dot(ray.d);
+        if denom == 0.0 {
+            return None;
+        }
+        let t = self.n.dot(self.origin - ray.o) / denom;
+        if t <= 0.0 || t >= ray.t_max {
+            return None;
+        }
+
+        // Find the parallelogram coordinates of the hit on the plane
+        let p = ray.point(t);
+        let d = p - self.origin;
+        let w = self.n / self.n.len_sqr();
+        let u = w.dot(d.cross(self.edges[1]));
+        let v = w.dot(self.edges[0].cross(d));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        // Refine the hit onto the quad as the error in the ray point grows with t
+        let (du, dv) = (self.edges[0] * u, self.edges[1] * v);
+        let p = self.origin + du + dv;
+        let mut si = SurfaceInteraction::new(
+            p,
+            -ray.d,
+            Point2::new(u, v),
+            self.edges[0],
+            self.edges[1],
+            self,
+            self.area_light.clone(),
+        );
+        si.p_error = (Vec3::from(self.origin.abs()) + du.abs() + dv.abs()) * gamma(5);
+
+        Some(Hit { t, si, shape: self })
+    }
+
+    fn world_bound(&self) -> Bounds3<f32> {
+        Bounds3::new(self.origin, self.origin + self.edges[0])
+            .union_p(self.origin + self.edges[1])
+            .union_p(self.origin + self.edges[0] + self.edges[1])
+    }
+
+    fn transform_swaps_handedness(&self) -> bool {
+        false
+    }
+
+    fn compute_scattering_functions<'a>(
+        &self,
+        scratch: &'a ScopedScratch,
+        si: &SurfaceInteraction,
+    ) -> Bsdf<'a> {
+        self.material.compute_scattering_functions(scratch, si)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Quad;
+    use crate::{
+        interaction::{Interaction, SurfaceInteraction},
+        lights::{AreaLight, Light, RectangularLight},
+        materials::Matte,
+        math::{transforms::translation, Point2, Point3, Ray, Spectrum, Vec2, Vec3},
+        shapes::Shape,
+        textures::ConstantTexture,
+    };
+
+    #[test]
+    fn light_samples_land_on_quad() {
+        let light = Arc::new(RectangularLight::new(
+            &translation(Vec3::new(1.0, 2.0, 3.0)),
+            Spectrum::ones(),
+            Vec2::new(0.5, 0.25),
+        ));
+        // Spans the same rectangle as the light, facing -y
+        let quad = Quad::new(
+            Point3::new(0.75, 2.0, 2.875),
+            [Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.25)],
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::zeros())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+            Some(Arc::clone(&light) as Arc<dyn AreaLight>),
+        );
+        let bounds = quad.world_bound();
+
+        let si = SurfaceInteraction::new(
+            Point3::new(0.5, 0.0, 3.5),
+            Vec3::new(0.0, 1.0, 0.0),
+            Point2::zeros(),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            &quad,
+            None,
+        );
+
+        let steps = 16;
+        for i in 0..steps {
+            for j in 0..steps {
+                let u = Point2::new(
+                    ((i as f32) + 0.5) / (steps as f32),
+                    ((j as f32) + 0.5) / (steps as f32),
+                );
+                let sample = light.sample_li(&Interaction::from(&si), u);
+
+                let hit = quad
+                    .intersect(Ray::new(si.p, sample.l, f32::INFINITY))
+                    .unwrap();
+                let p = hit.si.p;
+                assert!(p.x >= bounds.p_min.x - 1e-5 && p.x <= bounds.p_max.x + 1e-5);
+                assert!(p.z >= bounds.p_min.z - 1e-5 && p.z <= bounds.p_max.z + 1e-5);
+                assert!((p.y - 2.0).abs() < 1e-5);
+                assert!(hit.si.n.y < 0.0);
+                assert!(hit.si.area_light.is_some());
+
+                // Solid angle pdf of the sample matches the analytic rectangle pdf
+                let cos_theta = hit.si.n.dot_v(-sample.l).abs();
+                let expected_pdf = si.p.dist_sqr(p) / (cos_theta * 0.5 * 0.25);
+                assert!((sample.pdf - expected_pdf).abs() < 1e-3 * expected_pdf);
+                assert!(
+                    (light.pdf_li(&Interaction::from(&si), sample.l) - expected_pdf).abs()
+                        < 1e-3 * expected_pdf
+                );
+            }
+        }
+
+        let miss = Ray::new(
+            si.p,
+            (Point3::new(1.5, 2.0, 3.0) - si.p).normalized(),
+            f32::INFINITY,
+        );
+        assert!(quad.intersect(miss).is_none());
+    }
+}
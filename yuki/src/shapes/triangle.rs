@@ -6,18 +6,23 @@ use crate::{
     interaction::SurfaceInteraction,
     lights::AreaLight,
     materials::{Bsdf, Material},
-    math::{coordinate_system, Bounds3, Normal, Point2, Ray, Vec3},
+    math::{coordinate_system, gamma, Bounds3, Normal, Point2, Ray, Vec3},
+    textures::Texture,
 };
 
 // Based on Physically Based Rendering 3rd ed.
 // http://www.pbr-book.org/3ed-2018/Shapes/Triangle_Meshes.html
 
+/// Hits where the alpha mask is below this are cut out, matching the usual cutoff for foliage
+const ALPHA_CUTOFF: f32 = 0.5;
+
 /// A triangle object.
 pub struct Triangle {
     mesh: Arc<Mesh>,
     vertices: [usize; 3],
     material: Arc<dyn Material>,
     area_light: Option<Arc<dyn AreaLight>>,
+    alpha: Option<Arc<dyn Texture<f32>>>,
 }
 
 impl Triangle {
@@ -41,8 +46,17 @@ impl Triangle {
             vertices,
             material,
             area_light,
+            alpha: None,
         }
     }
+
+    /// Returns this `Triangle` with `alpha` as its cut-out mask.
+    ///
+    /// Rays pass through the parts of the triangle where `alpha` is below 0.5.
+    pub fn with_alpha(mut self, alpha: Option<Arc<dyn Texture<f32>>>) -> Self {
+        self.alpha = alpha;
+        self
+    }
 }
 
 impl Shape for Triangle {
@@ -182,6 +196,18 @@ impl Shape for Triangle {
             self,
             self.area_light.clone(),
         );
+        // From https://www.pbr-book.org/3ed-2018/Shapes/Managing_Rounding_Error#Triangles
+        si.p_error = (Vec3::from((p0 * b0).abs())
+            + Vec3::from((p1 * b1).abs())
+            + Vec3::from((p2 * b2).abs()))
+            * gamma(7);
+
+        // Let the ray continue to shapes behind cut-out parts
+        if let Some(alpha) = &self.alpha {
+            if alpha.evaluate(&si) < ALPHA_CUTOFF {
+                return None;
+            }
+        }
 
         // Authored mesh UVs might not preserve orientation, but winding order is typically constant
         let n = Normal::from(dp02.cross(dp12).normalized());
@@ -193,14 +219,26 @@ impl Shape for Triangle {
             si.shading.n = n;
         }
 
+        // Interpolated tangents give a shading frame that is continuous across triangles
+        let tangent = if self.mesh.tangents.is_empty() {
+            None
+        } else {
+            let t0 = self.mesh.tangents[self.vertices[0]];
+            let t1 = self.mesh.tangents[self.vertices[1]];
+            let t2 = self.mesh.tangents[self.vertices[2]];
+            Some(t0 * b0 + t1 * b1 + t2 * b2).filter(|t| t.len_sqr() > 0.0)
+        };
+
         // Set up shading normals
-        if !self.mesh.normals.is_empty() {
-            let n0 = self.mesh.normals[self.vertices[0]];
-            let n1 = self.mesh.normals[self.vertices[1]];
-            let n2 = self.mesh.normals[self.vertices[2]];
+        if !self.mesh.normals.is_empty() || tangent.is_some() {
+            let ns = if self.mesh.normals.is_empty() {
+                si.n.into()
+            } else {
+                let n0 = self.mesh.normals[self.vertices[0]];
+                let n1 = self.mesh.normals[self.vertices[1]];
+                let n2 = self.mesh.normals[self.vertices[2]];
 
-            let ns = {
-                let n = Vec3::from(n0 * b0 + n1 * b1 + n2 * b2).normalized();
+                let n = Vec3::from(n0 * b0 + n1 * b1 + n2 * b2);
                 if n.len_sqr() > 0.0 {
                     n.normalized()
                 } else {
@@ -208,7 +246,16 @@ impl Shape for Triangle {
                 }
             };
 
-            let (ss, ts) = {
+            let (ss, ts) = if let Some(tangent) = tangent {
+                // Keep the tangent pointing along u when projecting it on the shading plane
+                let ts = ns.cross(tangent);
+                if ts.len_sqr() > 0.0 {
+                    let ts = ts.normalized();
+                    (ts.cross(ns), ts)
+                } else {
+                    coordinate_system(ns)
+                }
+            } else {
                 let mut ss = si.dpdu.normalized();
                 let mut ts = ss.cross(ns);
                 if ts.len_sqr() > 0.0 {
@@ -246,3 +293,117 @@ impl Shape for Triangle {
         self.material.compute_scattering_functions(scratch, si)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Triangle;
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        materials::{Material, Matte},
+        math::{Point2, Point3, Ray, Spectrum, Transform, Vec3},
+        shapes::{Mesh, Shape},
+        textures::{ConstantTexture, Texture},
+    };
+
+    #[test]
+    fn shading_dpdu_follows_u() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let quad = |uvs: Vec<Point2<f32>>| {
+            Arc::new(Mesh::new(
+                &Transform::default(),
+                vec![0, 1, 2, 0, 2, 3],
+                vec![
+                    Point3::new(0.0, 0.0, 0.0),
+                    Point3::new(1.0, 0.0, 0.0),
+                    Point3::new(1.0, 1.0, 0.0),
+                    Point3::new(0.0, 1.0, 0.0),
+                ],
+                Vec::new(),
+                uvs,
+            ))
+        };
+
+        let mesh = quad(vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(1.0, 0.0),
+            Point2::new(1.0, 1.0),
+            Point2::new(0.0, 1.0),
+        ]);
+        assert_eq!(mesh.tangents, vec![Vec3::new(1.0, 0.0, 0.0); 4]);
+
+        for (first_vertex, x, y) in [(0, 0.7, 0.3), (3, 0.3, 0.7)] {
+            let triangle =
+                Triangle::new(Arc::clone(&mesh), first_vertex, Arc::clone(&material), None);
+            let ray = Ray::new(
+                Point3::new(x, y, 1.0),
+                Vec3::new(0.0, 0.0, -1.0),
+                f32::INFINITY,
+            );
+            let si = triangle.intersect(ray).unwrap().si;
+            assert!((si.shading.dpdu.normalized() - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-6);
+            assert!((si.shading.dpdv.normalized() - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-6);
+            assert!((Vec3::from(si.shading.n) - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-6);
+        }
+
+        // No tangents without uvs
+        assert!(quad(Vec::new()).tangents.is_empty());
+    }
+
+    #[test]
+    fn zero_alpha_is_invisible() {
+        let material: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let triangle = |z: f32, alpha: Option<f32>| {
+            let mesh = Arc::new(Mesh::new(
+                &Transform::default(),
+                vec![0, 1, 2],
+                vec![
+                    Point3::new(-1.0, -1.0, z),
+                    Point3::new(1.0, -1.0, z),
+                    Point3::new(0.0, 1.0, z),
+                ],
+                Vec::new(),
+                Vec::new(),
+            ));
+            Arc::new(
+                Triangle::new(mesh, 0, Arc::clone(&material), None).with_alpha(
+                    alpha.map(|a| Arc::new(ConstantTexture::new(a)) as Arc<dyn Texture<f32>>),
+                ),
+            ) as Arc<dyn Shape>
+        };
+        let ray = Ray::new(
+            Point3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            f32::INFINITY,
+        );
+
+        assert!(triangle(0.0, Some(0.0)).intersect(ray).is_none());
+        assert!(triangle(0.0, Some(1.0)).intersect(ray).is_some());
+        assert!(triangle(0.0, None).intersect(ray).is_some());
+
+        // Traversal continues to the shapes behind the masked one
+        let (bvh, _) = BoundingVolumeHierarchy::new(
+            vec![triangle(0.0, Some(0.0)), triangle(-1.0, None)],
+            1,
+            SplitMethod::Middle,
+        );
+        let hit = bvh.intersect(ray).hit.unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+
+        let (bvh, _) =
+            BoundingVolumeHierarchy::new(vec![triangle(0.0, Some(0.0))], 1, SplitMethod::Middle);
+        assert!(bvh.intersect(ray).hit.is_none());
+        assert!(!bvh.any_intersect(ray, None));
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use super::Texture;
+use crate::interaction::SurfaceInteraction;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Texture/Solid_and_Procedural_Texturing#Checkerboard
+
+pub struct CheckerboardTexture<T> {
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+    u_scale: f32,
+    v_scale: f32,
+}
+
+impl<T> CheckerboardTexture<T> {
+    /// Creates a new `CheckerboardTexture` with `u_scale`, `v_scale` squares per unit of uv.
+    pub fn new(
+        tex1: Arc<dyn Texture<T>>,
+        tex2: Arc<dyn Texture<T>>,
+        u_scale: f32,
+        v_scale: f32,
+    ) -> Self {
+        Self {
+            tex1,
+            tex2,
+            u_scale,
+            v_scale,
+        }
+    }
+}
+
+impl<T> Texture<T> for CheckerboardTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let u = (si.uv.x * self.u_scale).floor() as i64;
+        let v = (si.uv.y * self.v_scale).floor() as i64;
+        if (u + v).rem_euclid(2) == 0 {
+            self.tex1.evaluate(si)
+        } else {
+            self.tex2.evaluate(si)
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use super::Texture;
-use crate::{interaction::SurfaceInteraction, math::Spectrum};
+use super::{Texture, UvTransform};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Point2, Spectrum},
+};
 
 use image::io::Reader as ImageReader;
-use std::{io::Cursor, path::PathBuf};
+use std::{
+    io::Cursor,
+    ops::{Add, Mul},
+    path::PathBuf,
+};
 
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Texture/Image_Texture
@@ -45,10 +52,35 @@ macro_rules! load_f32_spectrum_image {
     };
 }
 
+/// Texel filtering in [`ImageTexture`] lookups
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+}
+
+/// Handling of [`ImageTexture`] lookups outside [0,1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    /// Texels outside the image are zero
+    Black,
+}
+
+/// Image texture with a mip pyramid for filtered lookups
 pub struct ImageTexture<T>
 where
     T: Copy + Send + Sync,
 {
+    /// Full resolution first, halving down to a single texel
+    levels: Vec<MipLevel<T>>,
+    uv_transform: UvTransform,
+    filter_mode: FilterMode,
+    wrap_mode: WrapMode,
+}
+
+struct MipLevel<T> {
     data: Vec<T>,
     width: usize,
     height: usize,
@@ -78,36 +110,199 @@ impl ImageTexture<Spectrum<f32>> {
     }
 }
 
-impl<T> Texture<T> for ImageTexture<T>
+impl ImageTexture<f32> {
+    /// Loads the image at `path` as its per-texel luminance.
+    pub fn new_luminance(path: &PathBuf) -> Result<Self, LoadError> {
+        let img = ImageTexture::<Spectrum<f32>>::new(path)?;
+        let MipLevel {
+            data,
+            width,
+            height,
+        } = &img.levels[0];
+
+        Ok(ImageTexture::from_texels(
+            data.iter().map(Spectrum::luminance).collect(),
+            *width,
+            *height,
+        ))
+    }
+}
+
+impl<T> ImageTexture<T>
 where
     T: Copy + Send + Sync,
 {
-    fn evaluate(&self, si: &SurfaceInteraction) -> T {
-        // TODO: Mapping (UVMapping2D with scale and offset)
-        let mut st = si.uv;
+    /// Returns this `ImageTexture` with `uv_transform` applied to the surface uvs before lookups.
+    pub fn with_uv_transform(mut self, uv_transform: UvTransform) -> Self {
+        self.uv_transform = uv_transform;
+        self
+    }
+
+    /// Returns this `ImageTexture` with lookups using `filter_mode`.
+    pub fn with_filter_mode(mut self, filter_mode: FilterMode) -> Self {
+        self.filter_mode = filter_mode;
+        self
+    }
+
+    /// Returns this `ImageTexture` with lookups outside the image handled by `wrap_mode`.
+    pub fn with_wrap_mode(mut self, wrap_mode: WrapMode) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
 
-        // Repeat
-        st.x = st.x.fract();
-        if st.x < 0.0 {
-            st.x = 1.0 + st.x;
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+}
+
+impl<T> ImageTexture<T>
+where
+    T: Copy + Send + Sync + Default + Add<Output = T> + Mul<f32, Output = T>,
+{
+    /// Creates a new `ImageTexture` from row-major texels, top row first.
+    fn from_texels(data: Vec<T>, width: usize, height: usize) -> Self {
+        let mut levels = vec![MipLevel {
+            data,
+            width,
+            height,
+        }];
+
+        // Box filter down to a single texel, edge texels are repeated for odd sizes
+        while let Some(prev) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            let width = (prev.width / 2).max(1);
+            let height = (prev.height / 2).max(1);
+            let texel = |x: usize, y: usize| {
+                prev.data[y.min(prev.height - 1) * prev.width + x.min(prev.width - 1)]
+            };
+            let mut data = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    data.push(
+                        (texel(2 * x, 2 * y)
+                            + texel(2 * x + 1, 2 * y)
+                            + texel(2 * x, 2 * y + 1)
+                            + texel(2 * x + 1, 2 * y + 1))
+                            * 0.25,
+                    );
+                }
+            }
+            levels.push(MipLevel {
+                data,
+                width,
+                height,
+            });
         }
-        st.y = st.y.fract();
-        if st.y < 0.0 {
-            st.y = 1.0 + st.y;
+
+        Self {
+            levels,
+            uv_transform: UvTransform::default(),
+            filter_mode: FilterMode::Nearest,
+            wrap_mode: WrapMode::Repeat,
         }
+    }
 
-        // Flip y
-        st.y = 1.0 - st.y;
+    /// Returns the average of the texels, the single texel at the top of the mip pyramid.
+    pub fn average(&self) -> T {
+        self.levels.last().unwrap().data[0]
+    }
 
-        // TODO: Split into MipMap like in pbrt
-        {
-            let mut st = st;
-            st.x = st.x * (self.width as f32) - 0.5;
-            st.y = st.y * (self.height as f32) - 0.5;
+    /// Looks up the full resolution texture at `st`, handling `st` outside [0,1) by the wrap mode.
+    pub fn lookup(&self, st: Point2<f32>) -> T {
+        self.lookup_level(0, st)
+    }
+
+    /// Looks up the texture at `st` filtered over `width` in st, blending between the two mip
+    /// levels closest to the filter width.
+    pub fn lookup_filtered(&self, st: Point2<f32>, width: f32) -> T {
+        // Level where texels are `width` wide
+        let texels = self.levels[0].width.max(self.levels[0].height) as f32;
+        let level = (width * texels).max(1e-8).log2();
+
+        let last = self.levels.len() - 1;
+        if level <= 0.0 {
+            self.lookup_level(0, st)
+        } else if level >= last as f32 {
+            self.lookup_level(last, st)
+        } else {
+            #[allow(clippy::cast_sign_loss)] // Level is positive
+            let l0 = level.floor() as usize;
+            let delta = level.fract();
+            self.lookup_level(l0, st) * (1.0 - delta) + self.lookup_level(l0 + 1, st) * delta
+        }
+    }
+
+    /// Looks up mip `level` at `st` with the filter mode.
+    fn lookup_level(&self, level: usize, st: Point2<f32>) -> T {
+        let level = &self.levels[level];
+
+        // Texel space with y flipped, texel centers are at integer coordinates
+        let x = st.x * (level.width as f32) - 0.5;
+        let y = (1.0 - st.y) * (level.height as f32) - 0.5;
+
+        match self.filter_mode {
+            FilterMode::Nearest => {
+                self.texel(level, (x + 0.5).floor() as i64, (y + 0.5).floor() as i64)
+            }
+            FilterMode::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (dx, dy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
 
-            self.data[(st.y as usize) * self.width + (st.x as usize)]
+                self.texel(level, x0, y0) * ((1.0 - dx) * (1.0 - dy))
+                    + self.texel(level, x0 + 1, y0) * (dx * (1.0 - dy))
+                    + self.texel(level, x0, y0 + 1) * ((1.0 - dx) * dy)
+                    + self.texel(level, x0 + 1, y0 + 1) * (dx * dy)
+            }
         }
     }
+
+    /// Returns the texel at `x`, `y` in `level`, wrapping coordinates outside the image.
+    fn texel(&self, level: &MipLevel<T>, x: i64, y: i64) -> T {
+        #[allow(clippy::cast_possible_wrap)] // Images aren't that large
+        let (width, height) = (level.width as i64, level.height as i64);
+        let (x, y) = match self.wrap_mode {
+            WrapMode::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            WrapMode::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+            WrapMode::Black => {
+                if x < 0 || x >= width || y < 0 || y >= height {
+                    return T::default();
+                }
+                (x, y)
+            }
+        };
+
+        #[allow(clippy::cast_sign_loss)] // Wrapped into the image
+        let index = (y as usize) * level.width + (x as usize);
+        level.data[index]
+    }
+}
+
+impl<T> Texture<T> for ImageTexture<T>
+where
+    T: Copy + Send + Sync + Default + Add<Output = T> + Mul<f32, Output = T>,
+{
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        // Filter over the larger of the pixel footprint's extents in uv
+        let width = 2.0
+            * si.dudx
+                .abs()
+                .max(si.dvdx.abs())
+                .max(si.dudy.abs())
+                .max(si.dvdy.abs());
+        self.evaluate_filtered(si, width)
+    }
+
+    fn evaluate_filtered(&self, si: &SurfaceInteraction, filter_width: f32) -> T {
+        let scale = self.uv_transform.scale;
+        self.lookup_filtered(
+            self.uv_transform.apply(si.uv),
+            filter_width * scale.x.abs().max(scale.y.abs()),
+        )
+    }
 }
 
 fn load_image_spectrum_f32<R: std::io::Read + std::io::BufRead + std::io::Seek>(
@@ -133,9 +328,161 @@ fn load_image_spectrum_f32<R: std::io::Read + std::io::BufRead + std::io::Seek>(
         }
     }
 
-    Ok(ImageTexture {
-        data,
-        width,
-        height,
-    })
+    Ok(ImageTexture::from_texels(data, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::abs_diff_eq;
+    use std::{io::Cursor, sync::Arc};
+
+    use super::{FilterMode, ImageTexture, WrapMode};
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::Matte,
+        math::{Point2, Point3, Spectrum, Transform, Vec2, Vec3},
+        shapes::Sphere,
+        textures::{ConstantTexture, Texture, UvTransform},
+    };
+
+    #[test]
+    fn uv_transform_tiles() {
+        // Gradient along u so that each texel is distinct
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 2, |x, _| {
+            image::Rgb([(x * 32) as u8, 0, 0])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+        let texture = || ImageTexture::from_image_bytes(&bytes).unwrap();
+
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        let si = |uv: Point2<f32>| {
+            SurfaceInteraction::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                uv,
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                &shape,
+                None,
+            )
+        };
+
+        let raw = texture();
+        let identity = texture().with_uv_transform(UvTransform::default());
+        let tiled =
+            texture().with_uv_transform(UvTransform::new(Vec2::new(2.0, 1.0), Vec2::new(0.0, 0.0)));
+        for i in 0..32 {
+            let uv = Point2::new((i as f32 + 0.25) / 32.0, 0.25);
+            assert_eq!(identity.evaluate(&si(uv)), raw.lookup(uv));
+
+            // Twice the tiling repeats every half of the uv range
+            let doubled = Point2::new(uv.x * 2.0, uv.y);
+            assert_eq!(tiled.evaluate(&si(uv)), raw.lookup(doubled));
+            let next_tile = Point2::new(uv.x + 0.5, uv.y);
+            assert_eq!(tiled.evaluate(&si(uv)), tiled.evaluate(&si(next_tile)));
+        }
+    }
+
+    #[test]
+    fn wrap_modes() {
+        // Each texel is distinct
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(4, 4, |x, y| {
+            image::Rgb([(x * 60) as u8, (y * 60) as u8, 0])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+        let texture = |wrap_mode| {
+            ImageTexture::from_image_bytes(&bytes)
+                .unwrap()
+                .with_wrap_mode(wrap_mode)
+        };
+        // Rows are stored top to bottom while v goes up
+        let texel = |texture: &ImageTexture<Spectrum<f32>>, x: usize, y: usize| {
+            texture.levels[0].data[y * texture.width() + x]
+        };
+
+        let st = Point2::new(-0.1, 1.1);
+
+        let repeat = texture(WrapMode::Repeat);
+        assert_eq!(repeat.lookup(st), texel(&repeat, 3, 3));
+        assert_eq!(repeat.lookup(st), repeat.lookup(Point2::new(0.9, 0.1)));
+
+        let clamp = texture(WrapMode::Clamp);
+        assert_eq!(clamp.lookup(st), texel(&clamp, 0, 0));
+
+        let black = texture(WrapMode::Black);
+        assert_eq!(black.lookup(st), Spectrum::zeros());
+        assert_eq!(black.lookup(Point2::new(0.1, 0.9)), texel(&black, 0, 0));
+    }
+
+    #[test]
+    fn bilinear_averages_texels() {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(2, 2, |x, y| {
+            image::Rgb([(x * 200) as u8, (y * 100) as u8, ((x + y) * 50) as u8])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+        let texture = ImageTexture::from_image_bytes(&bytes)
+            .unwrap()
+            .with_filter_mode(FilterMode::Bilinear)
+            .with_wrap_mode(WrapMode::Clamp);
+
+        let average = texture.levels[0]
+            .data
+            .iter()
+            .fold(Spectrum::zeros(), |acc, &t| acc + t)
+            / 4.0;
+        assert!(abs_diff_eq!(
+            texture.lookup(Point2::new(0.5, 0.5)),
+            average,
+            epsilon = 1e-6
+        ));
+
+        // Texel centers are exact
+        assert!(abs_diff_eq!(
+            texture.lookup(Point2::new(0.25, 0.75)),
+            texture.levels[0].data[0],
+            epsilon = 1e-6
+        ));
+    }
+
+    #[test]
+    fn mip_levels_blend() {
+        // Left half white, right half black
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(4, 4, |x, _| {
+            image::Rgb([if x < 2 { 255 } else { 0 }; 3])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+        let texture = ImageTexture::from_image_bytes(&bytes).unwrap();
+        assert_eq!(texture.levels.len(), 3);
+
+        let st = Point2::new(0.25, 0.5);
+        // Narrow filters use the full resolution image and wide ones the average
+        assert_eq!(texture.lookup_filtered(st, 0.0), texture.lookup(st));
+        assert_eq!(texture.lookup_filtered(st, 0.1), Spectrum::ones());
+        assert_eq!(texture.lookup_filtered(st, 2.0), Spectrum::ones() * 0.5);
+
+        // Halfway between the 2x2 level and the single texel
+        assert!(abs_diff_eq!(
+            texture.lookup_filtered(st, 2.0f32.powf(1.5) / 4.0),
+            Spectrum::ones() * 0.75,
+            epsilon = 1e-5
+        ));
+    }
 }
@@ -0,0 +1,44 @@
+use std::{
+    ops::{Add, Mul},
+    sync::Arc,
+};
+
+use super::Texture;
+use crate::interaction::SurfaceInteraction;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Texture/Texture_Interface_and_Basic_Textures#MixTextures
+
+/// Linear interpolation between two textures
+pub struct MixTexture<T> {
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+    amount: Arc<dyn Texture<f32>>,
+}
+
+impl<T> MixTexture<T> {
+    /// Creates a new `MixTexture` that is `tex1` where `amount` is `0` and `tex2` where it is `1`.
+    pub fn new(
+        tex1: Arc<dyn Texture<T>>,
+        tex2: Arc<dyn Texture<T>>,
+        amount: Arc<dyn Texture<f32>>,
+    ) -> Self {
+        Self { tex1, tex2, amount }
+    }
+}
+
+impl<T> Texture<T> for MixTexture<T>
+where
+    T: Add<Output = T> + Mul<f32, Output = T>,
+{
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let amount = self.amount.evaluate(si);
+        self.tex1.evaluate(si) * (1.0 - amount) + self.tex2.evaluate(si) * amount
+    }
+
+    fn evaluate_filtered(&self, si: &SurfaceInteraction, filter_width: f32) -> T {
+        let amount = self.amount.evaluate_filtered(si, filter_width);
+        self.tex1.evaluate_filtered(si, filter_width) * (1.0 - amount)
+            + self.tex2.evaluate_filtered(si, filter_width) * amount
+    }
+}
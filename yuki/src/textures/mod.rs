@@ -1,10 +1,20 @@
+mod checkerboard;
 mod constant;
 mod image_texture;
+mod mix;
+mod normal_map;
+mod scale;
+mod uv_transform;
 
 use crate::interaction::SurfaceInteraction;
 
+pub use checkerboard::CheckerboardTexture;
 pub use constant::ConstantTexture;
-pub use image_texture::{ImageTexture, LoadError};
+pub use image_texture::{FilterMode, ImageTexture, LoadError, WrapMode};
+pub use mix::MixTexture;
+pub use normal_map::NormalMapTexture;
+pub use scale::ScaleTexture;
+pub use uv_transform::UvTransform;
 
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Texture/Texture_Interface_and_Basic_Textures
@@ -13,4 +23,11 @@ pub trait Texture<T>: Send + Sync {
     // TODO: This shouldn't return by value if Spectrum is generalized for larger spectra at some point
     /// Evaluates this `Texture` at the given [`SurfaceInteraction`].
     fn evaluate(&self, si: &SurfaceInteraction) -> T;
+
+    /// Evaluates this `Texture` at the given [`SurfaceInteraction`], filtered over a width in uv.
+    ///
+    /// Point samples by default.
+    fn evaluate_filtered(&self, si: &SurfaceInteraction, _filter_width: f32) -> T {
+        self.evaluate(si)
+    }
 }
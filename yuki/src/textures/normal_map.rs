@@ -0,0 +1,25 @@
+use super::{ImageTexture, Texture};
+use crate::{
+    interaction::SurfaceInteraction,
+    math::{Normal, Spectrum},
+};
+
+/// Decodes the RGB texels of an [`ImageTexture`] into tangent-space normals.
+///
+/// The channels are expected to be stored linearly as `(n + 1) / 2`.
+pub struct NormalMapTexture {
+    image: ImageTexture<Spectrum<f32>>,
+}
+
+impl NormalMapTexture {
+    pub fn new(image: ImageTexture<Spectrum<f32>>) -> Self {
+        Self { image }
+    }
+}
+
+impl Texture<Normal<f32>> for NormalMapTexture {
+    fn evaluate(&self, si: &SurfaceInteraction) -> Normal<f32> {
+        let rgb = self.image.evaluate(si);
+        Normal::new(rgb.r * 2.0 - 1.0, rgb.g * 2.0 - 1.0, rgb.b * 2.0 - 1.0).normalized()
+    }
+}
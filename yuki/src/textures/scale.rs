@@ -0,0 +1,83 @@
+use std::{ops::Mul, sync::Arc};
+
+use super::Texture;
+use crate::interaction::SurfaceInteraction;
+
+// Based on Physically Based Rendering 3rd ed.
+// https://www.pbr-book.org/3ed-2018/Texture/Texture_Interface_and_Basic_Textures#ScaleTexture
+
+/// Product of two textures
+pub struct ScaleTexture<T> {
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+}
+
+impl<T> ScaleTexture<T> {
+    pub fn new(tex1: Arc<dyn Texture<T>>, tex2: Arc<dyn Texture<T>>) -> Self {
+        Self { tex1, tex2 }
+    }
+}
+
+impl<T> Texture<T> for ScaleTexture<T>
+where
+    T: Mul<Output = T>,
+{
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        self.tex1.evaluate(si) * self.tex2.evaluate(si)
+    }
+
+    fn evaluate_filtered(&self, si: &SurfaceInteraction, filter_width: f32) -> T {
+        self.tex1.evaluate_filtered(si, filter_width)
+            * self.tex2.evaluate_filtered(si, filter_width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ScaleTexture;
+    use crate::{
+        interaction::SurfaceInteraction,
+        materials::Matte,
+        math::{Point2, Point3, Spectrum, Transform, Vec3},
+        shapes::Sphere,
+        textures::{ConstantTexture, Texture},
+    };
+
+    #[test]
+    fn constants_multiply() {
+        // Only used for its handedness
+        let shape = Sphere::new(
+            &Transform::default(),
+            1.0,
+            Arc::new(Matte::new(
+                Arc::new(ConstantTexture::new(Spectrum::ones())),
+                Arc::new(ConstantTexture::new(0.0)),
+                None,
+                None,
+            )),
+        );
+        let si = SurfaceInteraction::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Point2::new(0.3, 0.7),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            &shape,
+            None,
+        );
+
+        let texture = ScaleTexture::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(0.5, 1.0, 2.0))),
+            Arc::new(ConstantTexture::new(Spectrum::new(4.0, 3.0, 0.25))),
+        );
+        assert_eq!(texture.evaluate(&si), Spectrum::new(2.0, 3.0, 0.5));
+
+        let texture = ScaleTexture::new(
+            Arc::new(ConstantTexture::new(0.5)),
+            Arc::new(ConstantTexture::new(3.0)),
+        );
+        assert_eq!(texture.evaluate(&si), 1.5);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::math::{Point2, Vec2};
+
+/// Affine transform from surface uvs to texture space
+///
+/// Applied as scale, then rotation around the origin and finally offset. Textures wrap the
+/// transformed uvs like they do the raw ones.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvTransform {
+    pub scale: Vec2<f32>,
+    pub offset: Vec2<f32>,
+    /// Counter-clockwise, in radians
+    pub rotation: f32,
+}
+
+impl UvTransform {
+    /// Creates a new `UvTransform` without rotation, matching pbrt's `uscale`, `vscale`,
+    /// `udelta` and `vdelta`.
+    pub fn new(scale: Vec2<f32>, offset: Vec2<f32>) -> Self {
+        Self {
+            scale,
+            offset,
+            rotation: 0.0,
+        }
+    }
+
+    /// Returns `uv` in texture space.
+    pub fn apply(&self, uv: Point2<f32>) -> Point2<f32> {
+        let u = uv.x * self.scale.x;
+        let v = uv.y * self.scale.y;
+        let (sin, cos) = self.rotation.sin_cos();
+        Point2::new(
+            u * cos - v * sin + self.offset.x,
+            u * sin + v * cos + self.offset.y,
+        )
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self::new(Vec2::new(1.0, 1.0), Vec2::new(0.0, 0.0))
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{interaction::Interaction, lights::AreaLight, math::Ray, scene::Scene};
+use crate::{
+    interaction::{Interaction, SurfaceInteraction},
+    lights::AreaLight,
+    math::{Ray, Spectrum},
+    scene::Scene,
+    shapes::Hit,
+};
+
+use allocators::ScopedScratch;
 
 // Based on Physically Based Rendering 3rd ed.
 // https://www.pbr-book.org/3ed-2018/Light_Sources/Light_Interface#VisibilityTesting
@@ -14,6 +22,11 @@ impl<'a> VisibilityTester<'a> {
         Self { p0, p1, area_light }
     }
 
+    /// Returns the far end of the tested segment.
+    pub fn p1(&self) -> &Interaction {
+        &self.p1
+    }
+
     pub fn ray(&self) -> Ray<f32> {
         self.p0.spawn_ray_to(&self.p1)
     }
@@ -21,4 +34,155 @@ impl<'a> VisibilityTester<'a> {
     pub fn unoccluded(&self, scene: &Scene) -> bool {
         !scene.bvh.any_intersect(self.ray(), self.area_light)
     }
+
+    /// Returns the transmittance between the end points.
+    ///
+    /// Surfaces with specular transmission scale the transmittance instead of blocking the segment
+    /// and the first opaque surface terminates it at zero.
+    pub fn unoccluded_transmittance(
+        &self,
+        scratch: &ScopedScratch,
+        scene: &Scene,
+    ) -> Spectrum<f32> {
+        let mut transmittance = Spectrum::ones();
+        let mut ray = self.ray();
+        while let Some(Hit { si, shape, .. }) = scene.bvh.intersect(ray).hit {
+            if !self.is_target(&si) {
+                let bsdf = shape.compute_scattering_functions(scratch, &si);
+                transmittance *= bsdf.transmittance(si.wo);
+                if transmittance.is_black() {
+                    return Spectrum::zeros();
+                }
+            }
+            ray = Interaction::from(&si).spawn_ray_to(&self.p1);
+        }
+        transmittance
+    }
+
+    /// Checks if `si` is on the area light this `VisibilityTester` targets.
+    fn is_target(&self, si: &SurfaceInteraction) -> bool {
+        match (self.area_light, &si.area_light) {
+            (Some(target), Some(l)) => std::ptr::eq(
+                (l.as_ref() as *const dyn AreaLight).cast::<()>(),
+                (target as *const dyn AreaLight).cast::<()>(),
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocators::{LinearAllocator, ScopedScratch};
+    use std::sync::Arc;
+
+    use super::VisibilityTester;
+    use crate::{
+        bvh::{BoundingVolumeHierarchy, SplitMethod},
+        interaction::Interaction,
+        materials::{Material, Matte, MicrofacetDistributionType, Uber},
+        math::{Normal, Point3, Spectrum, Transform},
+        scene::{Scene, SceneLoadSettings},
+        shapes::{Mesh, Shape, Triangle},
+        textures::ConstantTexture,
+    };
+
+    /// Creates a scene with a quad of `material` at y = 1.
+    fn occluder_scene(material: Arc<dyn Material>) -> Scene {
+        let mesh = Arc::new(Mesh::new(
+            &Transform::default(),
+            vec![0, 1, 2, 0, 2, 3],
+            vec![
+                Point3::new(-1.0, 1.0, -1.0),
+                Point3::new(-1.0, 1.0, 1.0),
+                Point3::new(1.0, 1.0, 1.0),
+                Point3::new(1.0, 1.0, -1.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+        ));
+        let shapes: Vec<Arc<dyn Shape>> = [0, 3]
+            .into_iter()
+            .map(|v0| {
+                Arc::new(Triangle::new(
+                    Arc::clone(&mesh),
+                    v0,
+                    Arc::clone(&material),
+                    None,
+                )) as Arc<dyn Shape>
+            })
+            .collect();
+        let (bvh, shapes) = BoundingVolumeHierarchy::new(shapes, 1, SplitMethod::Middle);
+
+        Scene {
+            name: "Visibility test".into(),
+            load_settings: SceneLoadSettings::default(),
+            meshes: vec![mesh],
+            shapes,
+            bvh,
+            lights: Vec::new(),
+            light_distribution: None,
+            background: Spectrum::zeros(),
+            medium: None,
+        }
+    }
+
+    fn uber(opacity: f32) -> Arc<dyn Material> {
+        let zeros = || Arc::new(ConstantTexture::new(Spectrum::zeros()));
+        Arc::new(Uber::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            zeros(),
+            zeros(),
+            zeros(),
+            Arc::new(ConstantTexture::new(0.1)),
+            Arc::new(ConstantTexture::new(Spectrum::ones() * opacity)),
+            1.5,
+            true,
+            MicrofacetDistributionType::TrowbridgeReitz,
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn transmittance_through_occluders() {
+        let matte: Arc<dyn Material> = Arc::new(Matte::new(
+            Arc::new(ConstantTexture::new(Spectrum::ones())),
+            Arc::new(ConstantTexture::new(0.0)),
+            None,
+            None,
+        ));
+        let tester = VisibilityTester::new(
+            Interaction {
+                p: Point3::new(0.2, 0.0, 0.1),
+                n: Normal::new(0.0, 1.0, 0.0),
+                ..Interaction::default()
+            },
+            Interaction {
+                p: Point3::new(-0.1, 2.0, 0.3),
+                n: Normal::new(0.0, -1.0, 0.0),
+                ..Interaction::default()
+            },
+            None,
+        );
+
+        let mut alloc = LinearAllocator::new(1024 * 16);
+        let scratch = ScopedScratch::new(&mut alloc);
+        let all_near =
+            |tr: Spectrum<f32>, v: f32| [tr.r, tr.g, tr.b].iter().all(|c| (c - v).abs() < 1e-5);
+
+        let transparent = occluder_scene(uber(0.0));
+        let tr = tester.unoccluded_transmittance(&scratch, &transparent);
+        assert!(all_near(tr, 1.0), "{:?}", tr);
+        assert!(!tester.unoccluded(&transparent));
+
+        let translucent = occluder_scene(uber(0.25));
+        let tr = tester.unoccluded_transmittance(&scratch, &translucent);
+        assert!(all_near(tr, 0.75), "{:?}", tr);
+
+        let opaque = occluder_scene(matte);
+        assert!(tester
+            .unoccluded_transmittance(&scratch, &opaque)
+            .is_black());
+    }
 }